@@ -0,0 +1,2 @@
+ALTER TABLE providers
+DROP COLUMN secondary_api_key_id;
@@ -0,0 +1,2 @@
+ALTER TABLE providers
+ADD COLUMN secondary_api_key_id UUID REFERENCES secrets (id) ON UPDATE CASCADE ON DELETE SET NULL;
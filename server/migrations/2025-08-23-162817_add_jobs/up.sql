@@ -0,0 +1,14 @@
+CREATE TABLE jobs (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  user_id UUID NOT NULL REFERENCES users (id),
+  provider_id INTEGER NOT NULL REFERENCES providers (id) ON DELETE CASCADE,
+  status TEXT NOT NULL,
+  batch_id TEXT,
+  data JSONB NOT NULL,
+  result JSONB,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+SELECT
+  diesel_manage_updated_at ('jobs');
@@ -0,0 +1,2 @@
+ALTER TABLE providers
+DROP COLUMN debug_logging;
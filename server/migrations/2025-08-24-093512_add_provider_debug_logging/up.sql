@@ -0,0 +1,2 @@
+ALTER TABLE providers
+ADD COLUMN debug_logging BOOLEAN NOT NULL DEFAULT FALSE;
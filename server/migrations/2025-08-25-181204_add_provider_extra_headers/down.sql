@@ -0,0 +1,3 @@
+ALTER TABLE providers
+DROP COLUMN extra_headers,
+DROP COLUMN extra_headers_nonce;
@@ -0,0 +1,3 @@
+ALTER TABLE providers
+ADD COLUMN extra_headers BYTEA,
+ADD COLUMN extra_headers_nonce BYTEA;
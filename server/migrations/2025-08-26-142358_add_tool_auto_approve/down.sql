@@ -0,0 +1,5 @@
+ALTER TABLE system_tools
+DROP COLUMN auto_approve;
+
+ALTER TABLE external_api_tools
+DROP COLUMN auto_approve;
@@ -0,0 +1,5 @@
+ALTER TABLE system_tools
+ADD COLUMN auto_approve BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE external_api_tools
+ADD COLUMN auto_approve BOOLEAN NOT NULL DEFAULT FALSE;
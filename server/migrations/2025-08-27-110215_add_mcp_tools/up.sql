@@ -0,0 +1,12 @@
+CREATE TABLE mcp_tools (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  user_id UUID NOT NULL REFERENCES users (id),
+  data JSONB NOT NULL,
+  secret_1 UUID REFERENCES secrets (id) ON UPDATE CASCADE ON DELETE SET NULL,
+  auto_approve BOOLEAN NOT NULL DEFAULT FALSE,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+SELECT
+  diesel_manage_updated_at ('mcp_tools');
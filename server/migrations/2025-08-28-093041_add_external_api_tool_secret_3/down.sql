@@ -0,0 +1,2 @@
+ALTER TABLE external_api_tools
+DROP COLUMN secret_3;
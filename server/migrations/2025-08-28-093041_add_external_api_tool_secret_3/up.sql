@@ -0,0 +1,2 @@
+ALTER TABLE external_api_tools
+ADD COLUMN secret_3 UUID REFERENCES secrets (id) ON UPDATE CASCADE ON DELETE SET NULL;
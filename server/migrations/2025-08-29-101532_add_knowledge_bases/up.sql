@@ -0,0 +1,42 @@
+CREATE EXTENSION IF NOT EXISTS vector;
+
+CREATE TABLE knowledge_bases (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+    user_id UUID NOT NULL REFERENCES users (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    provider_id INTEGER NOT NULL REFERENCES providers (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    description TEXT,
+    embedding_model TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+);
+
+CREATE INDEX knowledge_bases_user_id_idx ON knowledge_bases (user_id);
+
+SELECT
+    diesel_manage_updated_at ('knowledge_bases');
+
+CREATE TABLE knowledge_documents (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+    knowledge_base_id UUID NOT NULL REFERENCES knowledge_bases (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    content_length INTEGER NOT NULL,
+    chunk_count INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+);
+
+CREATE INDEX knowledge_documents_knowledge_base_id_idx ON knowledge_documents (knowledge_base_id);
+
+-- Embeddings of different knowledge bases can have different dimensions, so the vector
+-- column isn't fixed to a dimension (and chunks are searched within one knowledge base)
+CREATE TABLE knowledge_chunks (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+    document_id UUID NOT NULL REFERENCES knowledge_documents (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    knowledge_base_id UUID NOT NULL REFERENCES knowledge_bases (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    chunk_index INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    embedding VECTOR NOT NULL
+);
+
+CREATE INDEX knowledge_chunks_knowledge_base_id_idx ON knowledge_chunks (knowledge_base_id);
+CREATE INDEX knowledge_chunks_document_id_idx ON knowledge_chunks (document_id);
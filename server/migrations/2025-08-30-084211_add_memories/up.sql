@@ -0,0 +1,11 @@
+CREATE TABLE memories (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+    user_id UUID NOT NULL REFERENCES users (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    -- Model used to generate the embedding, if the memory was embedded for semantic recall
+    embedding_model TEXT,
+    embedding VECTOR,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+);
+
+CREATE INDEX memories_user_id_idx ON memories (user_id);
@@ -0,0 +1,6 @@
+ALTER TABLE system_tools
+DROP COLUMN cache_ttl;
+ALTER TABLE external_api_tools
+DROP COLUMN cache_ttl;
+ALTER TABLE mcp_tools
+DROP COLUMN cache_ttl;
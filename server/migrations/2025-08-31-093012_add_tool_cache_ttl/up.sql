@@ -0,0 +1,6 @@
+ALTER TABLE system_tools
+ADD COLUMN cache_ttl INTEGER;
+ALTER TABLE external_api_tools
+ADD COLUMN cache_ttl INTEGER;
+ALTER TABLE mcp_tools
+ADD COLUMN cache_ttl INTEGER;
@@ -0,0 +1,9 @@
+ALTER TABLE system_tools
+DROP COLUMN max_calls_per_hour,
+DROP COLUMN max_calls_per_session;
+ALTER TABLE external_api_tools
+DROP COLUMN max_calls_per_hour,
+DROP COLUMN max_calls_per_session;
+ALTER TABLE mcp_tools
+DROP COLUMN max_calls_per_hour,
+DROP COLUMN max_calls_per_session;
@@ -0,0 +1,9 @@
+ALTER TABLE system_tools
+ADD COLUMN max_calls_per_hour INTEGER,
+ADD COLUMN max_calls_per_session INTEGER;
+ALTER TABLE external_api_tools
+ADD COLUMN max_calls_per_hour INTEGER,
+ADD COLUMN max_calls_per_session INTEGER;
+ALTER TABLE mcp_tools
+ADD COLUMN max_calls_per_hour INTEGER,
+ADD COLUMN max_calls_per_session INTEGER;
@@ -0,0 +1,14 @@
+CREATE TABLE tool_runs (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  -- ID of the system, external API, or MCP tool
+  tool_id UUID NOT NULL,
+  tool_name TEXT NOT NULL,
+  session_id UUID REFERENCES chat_sessions (id) ON DELETE SET NULL,
+  status TEXT NOT NULL,
+  duration_ms INTEGER NOT NULL,
+  response_bytes INTEGER NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX tool_runs_tool_id_created_at_idx ON tool_runs (tool_id, created_at);
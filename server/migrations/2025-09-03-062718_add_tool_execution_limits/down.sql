@@ -0,0 +1,9 @@
+ALTER TABLE system_tools
+DROP COLUMN timeout_seconds,
+DROP COLUMN max_output_bytes;
+ALTER TABLE external_api_tools
+DROP COLUMN timeout_seconds,
+DROP COLUMN max_output_bytes;
+ALTER TABLE mcp_tools
+DROP COLUMN timeout_seconds,
+DROP COLUMN max_output_bytes;
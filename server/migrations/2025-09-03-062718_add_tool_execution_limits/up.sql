@@ -0,0 +1,9 @@
+ALTER TABLE system_tools
+ADD COLUMN timeout_seconds INTEGER,
+ADD COLUMN max_output_bytes INTEGER;
+ALTER TABLE external_api_tools
+ADD COLUMN timeout_seconds INTEGER,
+ADD COLUMN max_output_bytes INTEGER;
+ALTER TABLE mcp_tools
+ADD COLUMN timeout_seconds INTEGER,
+ADD COLUMN max_output_bytes INTEGER;
@@ -0,0 +1,3 @@
+ALTER TABLE chat_sessions
+DROP COLUMN pinned,
+DROP COLUMN archived_at;
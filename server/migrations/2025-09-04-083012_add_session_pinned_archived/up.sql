@@ -0,0 +1,3 @@
+ALTER TABLE chat_sessions
+ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT FALSE,
+ADD COLUMN archived_at TIMESTAMPTZ;
@@ -0,0 +1,3 @@
+DROP TABLE message_embeddings;
+
+DROP TABLE session_search_settings;
@@ -0,0 +1,26 @@
+-- User settings for the semantic search of chat sessions: the messages are embedded with
+-- the given provider and model when they're saved
+CREATE TABLE session_search_settings (
+    user_id UUID PRIMARY KEY REFERENCES users (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    provider_id INTEGER NOT NULL REFERENCES providers (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    embedding_model TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+);
+
+SELECT
+    diesel_manage_updated_at ('session_search_settings');
+
+-- Like knowledge chunks, the vector column isn't fixed to a dimension (and messages are
+-- searched by embedding model)
+CREATE TABLE message_embeddings (
+    message_id UUID PRIMARY KEY REFERENCES chat_messages (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    session_id UUID NOT NULL REFERENCES chat_sessions (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    embedding_model TEXT NOT NULL,
+    embedding VECTOR NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+);
+
+CREATE INDEX message_embeddings_user_id_model_idx ON message_embeddings (user_id, embedding_model);
+CREATE INDEX message_embeddings_session_id_idx ON message_embeddings (session_id);
@@ -0,0 +1,9 @@
+DROP INDEX chat_messages_deleted_at_idx;
+
+DROP INDEX chat_sessions_deleted_at_idx;
+
+ALTER TABLE chat_messages
+DROP COLUMN deleted_at;
+
+ALTER TABLE chat_sessions
+DROP COLUMN deleted_at;
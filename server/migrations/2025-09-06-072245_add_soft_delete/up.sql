@@ -0,0 +1,14 @@
+-- Deleted sessions and messages are kept in the trash until they're purged
+ALTER TABLE chat_sessions
+ADD COLUMN deleted_at TIMESTAMPTZ;
+
+ALTER TABLE chat_messages
+ADD COLUMN deleted_at TIMESTAMPTZ;
+
+CREATE INDEX chat_sessions_deleted_at_idx ON chat_sessions (deleted_at)
+WHERE
+    deleted_at IS NOT NULL;
+
+CREATE INDEX chat_messages_deleted_at_idx ON chat_messages (deleted_at)
+WHERE
+    deleted_at IS NOT NULL;
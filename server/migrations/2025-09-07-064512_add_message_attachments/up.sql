@@ -0,0 +1,15 @@
+-- Files uploaded by the user and attached to chat messages. Attachments are linked to the
+-- message when it's sent.
+CREATE TABLE message_attachments (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  user_id UUID NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  message_id UUID REFERENCES chat_messages (id) ON DELETE CASCADE,
+  name TEXT NOT NULL,
+  mime_type TEXT NOT NULL,
+  -- Path of the file in the local storage
+  storage_path TEXT NOT NULL,
+  size INTEGER NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX message_attachments_message_id_idx ON message_attachments (message_id);
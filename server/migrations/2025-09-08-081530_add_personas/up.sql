@@ -0,0 +1,16 @@
+-- Reusable session setups (e.g. "Coding assistant"), with a system prompt and default
+-- provider, options, and tools
+CREATE TABLE personas (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+    user_id UUID NOT NULL REFERENCES users (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    provider_id INTEGER REFERENCES providers (id) ON UPDATE CASCADE ON DELETE SET NULL,
+    name TEXT NOT NULL,
+    data JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+);
+
+CREATE INDEX personas_user_id_idx ON personas (user_id);
+
+SELECT
+    diesel_manage_updated_at ('personas');
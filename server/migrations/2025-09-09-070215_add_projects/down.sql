@@ -0,0 +1,7 @@
+ALTER TABLE message_attachments
+DROP COLUMN project_id;
+
+ALTER TABLE chat_sessions
+DROP COLUMN project_id;
+
+DROP TABLE projects;
@@ -0,0 +1,25 @@
+-- Projects group chat sessions that share instructions and files
+CREATE TABLE projects (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+    user_id UUID NOT NULL REFERENCES users (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    instructions TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+);
+
+CREATE INDEX projects_user_id_idx ON projects (user_id);
+
+SELECT
+    diesel_manage_updated_at ('projects');
+
+ALTER TABLE chat_sessions
+ADD COLUMN project_id UUID REFERENCES projects (id) ON DELETE SET NULL;
+
+CREATE INDEX chat_sessions_project_id_idx ON chat_sessions (project_id);
+
+-- Uploaded files can be added to a project instead of a message
+ALTER TABLE message_attachments
+ADD COLUMN project_id UUID REFERENCES projects (id) ON DELETE CASCADE;
+
+CREATE INDEX message_attachments_project_id_idx ON message_attachments (project_id);
@@ -0,0 +1,27 @@
+-- Prompts sent to a provider on a cron schedule, with the responses saved to a session
+CREATE TABLE scheduled_prompts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+    user_id UUID NOT NULL REFERENCES users (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    session_id UUID NOT NULL REFERENCES chat_sessions (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    provider_id INTEGER NOT NULL REFERENCES providers (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    prompt TEXT NOT NULL,
+    cron TEXT NOT NULL,
+    timezone TEXT NOT NULL DEFAULT 'UTC',
+    data JSONB NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    next_run_at TIMESTAMPTZ,
+    last_run_at TIMESTAMPTZ,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+);
+
+CREATE INDEX scheduled_prompts_user_id_idx ON scheduled_prompts (user_id);
+
+CREATE INDEX scheduled_prompts_next_run_at_idx ON scheduled_prompts (next_run_at)
+WHERE
+    enabled;
+
+SELECT
+    diesel_manage_updated_at ('scheduled_prompts');
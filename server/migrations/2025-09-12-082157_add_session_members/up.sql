@@ -0,0 +1,10 @@
+-- Users invited to a chat session by its owner, with read or write access
+CREATE TABLE session_members (
+    session_id UUID NOT NULL REFERENCES chat_sessions (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    role TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+    PRIMARY KEY (session_id, user_id)
+);
+
+CREATE INDEX session_members_user_id_idx ON session_members (user_id);
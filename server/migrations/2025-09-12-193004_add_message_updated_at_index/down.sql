@@ -0,0 +1 @@
+DROP INDEX chat_messages_session_id_updated_at_idx;
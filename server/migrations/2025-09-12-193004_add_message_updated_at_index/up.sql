@@ -0,0 +1,2 @@
+-- For syncing the messages of a session changed since a given time
+CREATE INDEX chat_messages_session_id_updated_at_idx ON chat_messages (session_id, updated_at);
@@ -0,0 +1,7 @@
+-- Pinned messages are always kept in the context sent to the assistant
+ALTER TABLE chat_messages
+ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX chat_messages_pinned_idx ON chat_messages (session_id)
+WHERE
+    pinned;
@@ -0,0 +1,4 @@
+ALTER TABLE app_api_keys
+DROP COLUMN scopes,
+DROP COLUMN expires_at,
+DROP COLUMN last_used_at;
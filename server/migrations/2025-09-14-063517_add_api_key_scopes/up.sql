@@ -0,0 +1,5 @@
+-- Scopes, expiration, and last use of API keys. Existing keys keep full access.
+ALTER TABLE app_api_keys
+ADD COLUMN scopes TEXT[] NOT NULL DEFAULT '{admin}',
+ADD COLUMN expires_at TIMESTAMPTZ,
+ADD COLUMN last_used_at TIMESTAMPTZ;
@@ -0,0 +1,2 @@
+ALTER TABLE app_api_keys
+DROP COLUMN allowed_ips;
@@ -0,0 +1,3 @@
+-- CIDR ranges that the API key can be used from (any address if NULL)
+ALTER TABLE app_api_keys
+ADD COLUMN allowed_ips TEXT[];
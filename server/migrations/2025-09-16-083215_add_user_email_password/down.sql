@@ -0,0 +1,4 @@
+ALTER TABLE users
+DROP COLUMN email,
+DROP COLUMN password_hash,
+DROP COLUMN email_verified_at;
@@ -0,0 +1,5 @@
+-- Email and password for local (email + password) authentication
+ALTER TABLE users
+ADD COLUMN email TEXT UNIQUE,
+ADD COLUMN password_hash TEXT,
+ADD COLUMN email_verified_at TIMESTAMPTZ;
@@ -0,0 +1,13 @@
+-- Role of the user: 'admin' or 'member'
+ALTER TABLE users
+ADD COLUMN role TEXT NOT NULL DEFAULT 'member';
+
+-- The first user of existing deployments becomes the admin
+UPDATE users
+SET role = 'admin'
+WHERE id = (
+    SELECT id
+    FROM users
+    ORDER BY created_at
+    LIMIT 1
+);
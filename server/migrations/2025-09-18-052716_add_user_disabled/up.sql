@@ -0,0 +1,3 @@
+-- When the user was disabled by an admin (the user can't log in while disabled)
+ALTER TABLE users
+ADD COLUMN disabled_at TIMESTAMPTZ;
@@ -0,0 +1,15 @@
+ALTER TABLE mcp_tools
+DROP COLUMN organization_id;
+
+ALTER TABLE external_api_tools
+DROP COLUMN organization_id;
+
+ALTER TABLE personas
+DROP COLUMN organization_id;
+
+ALTER TABLE providers
+DROP COLUMN organization_id;
+
+DROP TABLE organization_members;
+
+DROP TABLE organizations;
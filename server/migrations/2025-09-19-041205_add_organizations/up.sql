@@ -0,0 +1,51 @@
+-- Organizations (team workspaces) whose members share providers, tools, and personas
+CREATE TABLE organizations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid (),
+    name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+);
+
+SELECT
+    diesel_manage_updated_at ('organizations');
+
+-- Members of an organization, with the 'owner', 'admin', or 'member' role
+CREATE TABLE organization_members (
+    organization_id UUID NOT NULL REFERENCES organizations (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    role TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+    PRIMARY KEY (organization_id, user_id)
+);
+
+CREATE INDEX organization_members_user_id_idx ON organization_members (user_id);
+
+-- Organization that the resource is shared with (resources stay with their creator if the
+-- organization is deleted)
+ALTER TABLE providers
+ADD COLUMN organization_id UUID REFERENCES organizations (id) ON DELETE SET NULL;
+
+ALTER TABLE personas
+ADD COLUMN organization_id UUID REFERENCES organizations (id) ON DELETE SET NULL;
+
+ALTER TABLE external_api_tools
+ADD COLUMN organization_id UUID REFERENCES organizations (id) ON DELETE SET NULL;
+
+ALTER TABLE mcp_tools
+ADD COLUMN organization_id UUID REFERENCES organizations (id) ON DELETE SET NULL;
+
+CREATE INDEX providers_organization_id_idx ON providers (organization_id)
+WHERE
+    organization_id IS NOT NULL;
+
+CREATE INDEX personas_organization_id_idx ON personas (organization_id)
+WHERE
+    organization_id IS NOT NULL;
+
+CREATE INDEX external_api_tools_organization_id_idx ON external_api_tools (organization_id)
+WHERE
+    organization_id IS NOT NULL;
+
+CREATE INDEX mcp_tools_organization_id_idx ON mcp_tools (organization_id)
+WHERE
+    organization_id IS NOT NULL;
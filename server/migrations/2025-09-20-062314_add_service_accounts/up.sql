@@ -0,0 +1,16 @@
+-- Service accounts: non-interactive users (e.g. bots posting into shared sessions) that can
+-- only authenticate with scoped API keys
+CREATE TABLE service_accounts (
+    user_id UUID PRIMARY KEY REFERENCES users (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    description TEXT,
+    -- Admin who created the service account
+    created_by UUID REFERENCES users (id) ON DELETE SET NULL,
+    -- Max number of chat messages sent per hour and per day (no limit if NULL)
+    max_messages_per_hour INTEGER,
+    max_messages_per_day INTEGER,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+);
+
+SELECT
+    diesel_manage_updated_at ('service_accounts');
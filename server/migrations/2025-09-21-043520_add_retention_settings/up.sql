@@ -0,0 +1,14 @@
+-- Data retention settings of each user. Sessions and tool runs older than the retention
+-- period are deleted by a background task (no limit if NULL, besides the server's limit).
+CREATE TABLE retention_settings (
+    user_id UUID PRIMARY KEY REFERENCES users (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    -- Days before sessions without new messages are moved to the trash
+    session_retention_days INTEGER,
+    -- Days before tool runs are deleted
+    tool_run_retention_days INTEGER,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW ()
+);
+
+SELECT
+    diesel_manage_updated_at ('retention_settings');
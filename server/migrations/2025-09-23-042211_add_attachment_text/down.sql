@@ -0,0 +1,3 @@
+ALTER TABLE message_attachments
+DROP COLUMN extracted_text,
+DROP COLUMN extracted_at;
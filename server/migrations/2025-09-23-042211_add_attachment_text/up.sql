@@ -0,0 +1,5 @@
+-- Text extracted from uploaded documents (PDF, DOCX, and HTML) in the background, and when
+-- the extraction finished (also set if it failed, with no text)
+ALTER TABLE message_attachments
+ADD COLUMN extracted_text TEXT,
+ADD COLUMN extracted_at TIMESTAMPTZ;
@@ -0,0 +1,2 @@
+-- Total size of the user's stored files, in bytes (NULL until the files are counted)
+ALTER TABLE users ADD COLUMN storage_used BIGINT;
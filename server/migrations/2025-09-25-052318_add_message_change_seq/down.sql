@@ -0,0 +1,8 @@
+DROP INDEX chat_messages_session_id_change_seq_idx;
+CREATE INDEX chat_messages_session_id_updated_at_idx ON chat_messages (session_id, updated_at);
+
+DROP TRIGGER chat_messages_change_seq_trigger ON chat_messages;
+DROP FUNCTION chat_messages_change_seq_update ();
+
+ALTER TABLE chat_messages
+DROP COLUMN change_seq;
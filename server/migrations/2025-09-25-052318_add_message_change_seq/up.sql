@@ -0,0 +1,27 @@
+-- Sequence number of the last change of the message, used as the cursor for syncing the
+-- messages of a session (timestamps can't be used, as they're set at the start of the
+-- transaction, so a change can be committed after a later timestamp was synced)
+ALTER TABLE chat_messages
+ADD COLUMN change_seq BIGSERIAL NOT NULL;
+
+CREATE OR REPLACE FUNCTION chat_messages_change_seq_update () RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND (NEW."content", NEW.meta, NEW.pinned, NEW.deleted_at)
+        IS NOT DISTINCT FROM (OLD."content", OLD.meta, OLD.pinned, OLD.deleted_at) THEN
+        RETURN NEW;
+    END IF;
+    -- Lock the session until the end of the transaction, so that the changes of a session
+    -- are committed in the order of their sequence numbers
+    PERFORM pg_advisory_xact_lock(hashtextextended(NEW.session_id::text, 0));
+    NEW.change_seq := nextval(pg_get_serial_sequence('chat_messages', 'change_seq'));
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER chat_messages_change_seq_trigger
+BEFORE INSERT OR UPDATE ON chat_messages
+FOR EACH ROW EXECUTE FUNCTION chat_messages_change_seq_update ();
+
+DROP INDEX chat_messages_session_id_updated_at_idx;
+-- For syncing the messages of a session changed since a given cursor
+CREATE INDEX chat_messages_session_id_change_seq_idx ON chat_messages (session_id, change_seq);
@@ -0,0 +1,33 @@
+CREATE OR REPLACE FUNCTION chat_messages_search_vector_update () RETURNS trigger AS $$
+BEGIN
+    IF NEW."content" LIKE 'enc:v1:%' THEN
+        NEW.search_vector :=
+            setweight(to_tsvector('english', (
+                SELECT title FROM chat_sessions WHERE id = NEW.session_id
+            )), 'A') || NEW.content_vector;
+    ELSE
+        NEW.search_vector :=
+            setweight(to_tsvector('english', (
+                SELECT title FROM chat_sessions WHERE id = NEW.session_id
+            )), 'A') || setweight(to_tsvector('english', NEW."content"), 'B');
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION chat_sessions_search_vector_update () RETURNS trigger AS $$
+	BEGIN
+		IF old.title = new.title THEN RETURN NEW; END IF;
+		UPDATE chat_messages
+		SET search_vector =
+	        setweight(to_tsvector('english', NEW.title), 'A') || CASE
+	            WHEN "content" LIKE 'enc:v1:%' THEN content_vector
+	            ELSE setweight(to_tsvector('english', "content"), 'B')
+	        END
+	    WHERE session_id = NEW.id;
+	    RETURN NEW;
+	END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE chat_messages
+DROP COLUMN content_encrypted;
@@ -0,0 +1,2 @@
+-- When the user's stored files were last counted, for recounting them periodically
+ALTER TABLE users ADD COLUMN storage_counted_at TIMESTAMPTZ;
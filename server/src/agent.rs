@@ -0,0 +1,145 @@
+//! Auto mode for chat streams: the server executes the tool calls of auto-approved tools, and
+//! sends the results back to the provider to continue the response.
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::{
+    attachments::{add_project_attachments, load_attachments},
+    db::{
+        models::{ChatRsMessage, ChatRsMessageMeta, ChatRsMessageRole, ChatRsToolCall},
+        services::ChatDbService,
+        DbConnection,
+    },
+    knowledge::KnowledgeService,
+    provider::{
+        LlmApiProvider, LlmAttachment, LlmProviderOptions, LlmStream, LlmStreamError, LlmTool,
+    },
+    storage::LocalStorage,
+    stream::LlmStreamWriter,
+    tools::{find_executable_tool, run_tool_call, save_tool_call_output, ToolStorage},
+    utils::Encryptor,
+};
+
+/// Default max number of tool-calling iterations in auto mode.
+const DEFAULT_MAX_ITERATIONS: u32 = 5;
+/// Upper limit for the max number of tool-calling iterations in auto mode.
+const MAX_ITERATIONS_LIMIT: u32 = 20;
+
+/// Runs the tool calls of the assistant in auto mode, and continues the chat with the results
+pub struct AutoToolRunner {
+    pub user_id: Uuid,
+    pub session_id: Uuid,
+    pub provider_api: Box<dyn LlmApiProvider>,
+    pub tools: Option<Vec<LlmTool>>,
+    pub options: LlmProviderOptions,
+    /// System prompt of the project and preset, added to the start of the conversation
+    pub system_prompt: Option<String>,
+    /// Images and PDFs of the project, attached to the first user message
+    pub project_attachments: Vec<LlmAttachment>,
+    pub http_client: reqwest::Client,
+    pub encryptor: Encryptor,
+    pub storage: LocalStorage,
+    pub knowledge: KnowledgeService,
+    /// Whether MCP tools using the stdio transport are allowed
+    pub allow_mcp_stdio: bool,
+    /// Max number of tool-calling iterations (default: 5, max: 20)
+    pub max_iterations: Option<u32>,
+}
+
+impl AutoToolRunner {
+    /// The max number of tool-calling iterations
+    pub fn max_iterations(&self) -> u32 {
+        self.max_iterations
+            .unwrap_or(DEFAULT_MAX_ITERATIONS)
+            .min(MAX_ITERATIONS_LIMIT)
+    }
+
+    /// Execute the tool calls, saving and streaming each result, and then start the next
+    /// provider response. Returns `None` if the chat can't continue automatically: a tool
+    /// isn't auto-approved (the tool calls are then left for the user), the stream was
+    /// cancelled, or a request failed.
+    pub async fn continue_with_tool_calls(
+        &self,
+        db: &mut DbConnection,
+        writer: &mut LlmStreamWriter,
+        tool_calls: &[ChatRsToolCall],
+    ) -> Option<LlmStream> {
+        let mut executions = Vec::with_capacity(tool_calls.len());
+        for tool_call in tool_calls {
+            let tool = find_executable_tool(
+                db,
+                &self.encryptor,
+                &self.http_client,
+                &self.user_id,
+                tool_call,
+                self.allow_mcp_stdio,
+            );
+            match tool.await {
+                Ok(tool) if tool.auto_approve() => executions.push((tool, tool_call.clone())),
+                Ok(_) => return None,
+                Err(err) => {
+                    rocket::warn!("Failed to find tool for auto mode: {}", err);
+                    return None;
+                }
+            }
+        }
+
+        writer.keep_alive();
+        let tool_storage =
+            ToolStorage::new(self.storage.clone(), self.knowledge.clone(), self.user_id)
+                .with_session(self.session_id);
+        for (tool, tool_call) in executions {
+            // Logs are only saved to the tool message
+            let (log_tx, mut log_rx) = tokio::sync::mpsc::channel(50);
+            let execute = run_tool_call(tool, tool_call, &self.http_client, &tool_storage, log_tx);
+            let discard_logs = async { while log_rx.recv().await.is_some() {} };
+            let (output, _) = tokio::join!(execute, discard_logs);
+
+            let message = save_tool_call_output(db, &self.user_id, &self.session_id, output)
+                .await
+                .inspect_err(|err| rocket::error!("Failed to save tool message: {}", err))
+                .ok()?;
+            if let Err(LlmStreamError::StreamCancelled) = writer.tool_result(&message).await {
+                return None;
+            }
+        }
+
+        let (_, mut messages) = ChatDbService::new(db)
+            .get_session_with_messages(&self.user_id, &self.session_id)
+            .await
+            .inspect_err(|err| rocket::error!("Failed to get session messages: {}", err))
+            .ok()?;
+        if let Err(err) = load_attachments(db, &self.storage, &mut messages).await {
+            rocket::warn!("Failed to load attachments: {}", err);
+        }
+        add_project_attachments(&mut messages, &self.project_attachments);
+        if let Some(system_prompt) = &self.system_prompt {
+            messages.insert(
+                0,
+                ChatRsMessage {
+                    id: Uuid::new_v4(),
+                    session_id: self.session_id,
+                    role: ChatRsMessageRole::System,
+                    content: system_prompt.clone(),
+                    meta: ChatRsMessageMeta::default(),
+                    created_at: Utc::now(),
+                    pinned: false,
+                    content_encrypted: false,
+                },
+            );
+        }
+        match self
+            .provider_api
+            .chat_stream(messages, self.tools.clone(), &self.options)
+            .await
+        {
+            Ok(stream) => Some(stream),
+            Err(err) => {
+                let warning = format!("Failed to continue after tool calls: {err}");
+                writer.warning(&warning).await.ok();
+                None
+            }
+        }
+    }
+}
@@ -1,17 +1,191 @@
+mod admin;
 mod api_key;
 mod auth;
+mod backup;
 mod chat;
+mod file;
 mod info;
+mod job;
+mod knowledge;
+mod memory;
+mod organization;
+mod persona;
+mod preset;
+mod project;
 mod provider;
+mod retention;
+mod schedule;
 mod secret;
+mod service_account;
 mod session;
 mod tool;
+mod usage;
+mod user;
+mod webhook;
 
+pub use admin::get_routes as admin_routes;
 pub use api_key::get_routes as api_key_routes;
 pub use auth::get_routes as auth_routes;
+pub use backup::get_routes as backup_routes;
 pub use chat::get_routes as chat_routes;
+pub use file::get_routes as file_routes;
 pub use info::get_routes as info_routes;
+pub use job::get_routes as job_routes;
+pub use knowledge::get_routes as knowledge_routes;
+pub use memory::get_routes as memory_routes;
+pub use organization::get_routes as organization_routes;
+pub use persona::get_routes as persona_routes;
+pub use preset::get_routes as preset_routes;
+pub use project::get_routes as project_routes;
 pub use provider::get_routes as provider_routes;
+pub use retention::get_routes as retention_routes;
+pub use schedule::get_routes as schedule_routes;
 pub use secret::get_routes as secret_routes;
+pub use service_account::get_routes as service_account_routes;
 pub use session::get_routes as session_routes;
 pub use tool::get_routes as tool_routes;
+pub use usage::get_routes as usage_routes;
+pub use user::get_routes as user_routes;
+pub use webhook::get_routes as webhook_routes;
+
+use rocket_okapi::{okapi::openapi3::OpenApi, r#gen::OpenApiGenerator, settings::OpenApiSettings};
+use schemars::JsonSchema;
+
+/// Add the schema of a type that isn't part of a route's request/response (e.g. SSE events)
+/// to the OpenAPI components, so that it's available to generated clients.
+fn add_component_schema<T: JsonSchema>(spec: &mut OpenApi, settings: &OpenApiSettings) {
+    let mut generator = OpenApiGenerator::new(settings);
+    generator.json_schema::<T>();
+    if let Some(schemas) = generator.into_openapi().components.map(|c| c.schemas) {
+        spec.components
+            .get_or_insert_with(Default::default)
+            .schemas
+            .extend(schemas);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket_okapi::settings::OpenApiSettings;
+    use serde_json::{json, Value};
+
+    /// Get a JSON schema document referencing the given component schema of the published
+    /// OpenAPI spec.
+    fn get_published_schema(name: &str) -> Value {
+        let settings = OpenApiSettings::default();
+        let mut schemas = serde_json::Map::new();
+        for (_, spec) in [super::chat_routes(&settings), super::tool_routes(&settings)] {
+            for (schema_name, schema) in spec.components.unwrap_or_default().schemas {
+                schemas.insert(schema_name, serde_json::to_value(schema).unwrap());
+            }
+        }
+        assert!(schemas.contains_key(name), "schema {name} not published");
+
+        json!({
+            "$ref": format!("#/components/schemas/{name}"),
+            "components": { "schemas": schemas }
+        })
+    }
+
+    #[test]
+    fn test_error_response_contract() {
+        let schema = get_published_schema("ErrorResponse");
+        for r#type in ["bad_request", "unauthorized", "not_found", "server"] {
+            let payload = json!({ "type": r#type, "message": "Error!" });
+            assert!(jsonschema::is_valid(&schema, &payload), "{payload}");
+        }
+        assert!(!jsonschema::is_valid(
+            &schema,
+            &json!({ "type": "teapot", "message": "Error!" })
+        ));
+        assert!(!jsonschema::is_valid(
+            &schema,
+            &json!({ "message": "Error!" })
+        ));
+    }
+
+    #[test]
+    fn test_chat_stream_event_contract() {
+        let schema = get_published_schema("ChatStreamEvent");
+        let payloads = [
+            json!({ "type": "start" }),
+            json!({ "type": "text", "data": "Hello" }),
+            json!({ "type": "tool_call", "data": "{}" }),
+            json!({ "type": "tool_result", "data": "{}" }),
+            json!({ "type": "finish_reason", "data": "length" }),
+            json!({ "type": "warning", "data": "Deprecated!" }),
+            json!({ "type": "error", "data": "Error!" }),
+            json!({ "type": "end" }),
+        ];
+        for payload in payloads {
+            assert!(jsonschema::is_valid(&schema, &payload), "{payload}");
+        }
+        assert!(!jsonschema::is_valid(&schema, &json!({ "type": "text" })));
+        assert!(!jsonschema::is_valid(
+            &schema,
+            &json!({ "type": "unknown" })
+        ));
+        assert!(!jsonschema::is_valid(
+            &schema,
+            &json!({ "type": "finish_reason", "data": "tired" })
+        ));
+    }
+
+    #[test]
+    fn test_chat_stream_error_contract() {
+        let schema = get_published_schema("ChatStreamError");
+        let payload = json!({
+            "code": "invalid_api_key",
+            "message": "The API key of the provider is missing or invalid",
+            "details": "Provider error: OpenAI API error 401 Unauthorized"
+        });
+        assert!(jsonschema::is_valid(&schema, &payload), "{payload}");
+        assert!(jsonschema::is_valid(
+            &schema,
+            &json!({ "message": "Error!" })
+        ));
+        assert!(!jsonschema::is_valid(
+            &schema,
+            &json!({ "code": "teapot", "message": "Error!" })
+        ));
+    }
+
+    #[test]
+    fn test_tool_stream_event_contract() {
+        let schema = get_published_schema("ToolStreamEvent");
+        for r#type in ["result", "log", "debug", "error"] {
+            let payload = json!({ "type": r#type, "data": "Output" });
+            assert!(jsonschema::is_valid(&schema, &payload), "{payload}");
+        }
+        assert!(!jsonschema::is_valid(&schema, &json!({ "type": "log" })));
+    }
+
+    #[test]
+    fn test_batch_tool_stream_event_contract() {
+        let schema = get_published_schema("BatchToolStreamEvent");
+        let payload = json!({ "tool_call_id": "call_1", "type": "log", "data": "Output" });
+        assert!(jsonschema::is_valid(&schema, &payload), "{payload}");
+        assert!(!jsonschema::is_valid(
+            &schema,
+            &json!({ "type": "log", "data": "Output" })
+        ));
+    }
+
+    #[test]
+    fn test_create_tool_response_contract() {
+        let schema = get_published_schema("CreateToolResponse");
+        let payload = json!({
+            "type": "system",
+            "id": "0b5e4c9a-6a1f-4f4e-9f3c-2f0a7c3c9b1d",
+            "user_id": "6f7e4f3a-8c2d-4b1e-9a5f-1d2c3b4a5e6f",
+            "data": { "type": "system_info" },
+            "created_at": "2025-08-01T00:00:00Z",
+            "updated_at": "2025-08-01T00:00:00Z"
+        });
+        assert!(jsonschema::is_valid(&schema, &payload), "{payload}");
+
+        let mut untagged = payload.clone();
+        untagged.as_object_mut().unwrap().remove("type");
+        assert!(!jsonschema::is_valid(&schema, &untagged));
+    }
+}
@@ -0,0 +1,65 @@
+//! Hidden admin routes (not included in the OpenAPI docs). Only enabled if an admin
+//! token is configured, and requests must include the token in the `X-Admin-Token` header.
+//! Routes for admin users are in the `user` module.
+
+use rocket::{
+    async_trait,
+    http::Status,
+    post,
+    request::{FromRequest, Outcome},
+    routes,
+    serde::json::Json,
+    Request, Route, State,
+};
+use subtle::ConstantTimeEq;
+
+use crate::{
+    config::AppConfig,
+    db::DbPool,
+    load_test::{run_load_test, LoadTestConfig, LoadTestReport},
+    stream::ExclusiveStreamClient,
+};
+
+pub fn get_routes() -> Vec<Route> {
+    routes![start_load_test]
+}
+
+/// Request guard for admin routes. Forwards to a 404 if no admin token is configured.
+struct AdminToken;
+
+#[async_trait]
+impl<'r> FromRequest<'r> for AdminToken {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(admin_token) = req
+            .rocket()
+            .state::<AppConfig>()
+            .and_then(|config| config.admin_token.as_deref())
+        else {
+            return Outcome::Forward(Status::NotFound);
+        };
+        match req.headers().get_one("X-Admin-Token") {
+            Some(token) if bool::from(token.as_bytes().ct_eq(admin_token.as_bytes())) => {
+                Outcome::Success(AdminToken)
+            }
+            _ => Outcome::Error((Status::Unauthorized, ())),
+        }
+    }
+}
+
+/// Spawn concurrent synthetic chat streams to validate the sizing of the database and of the
+/// stream backend of the deployment
+#[post("/load-test", data = "<input>")]
+async fn start_load_test(
+    _admin: AdminToken,
+    db_pool: &State<DbPool>,
+    stream_client: ExclusiveStreamClient,
+    input: Json<LoadTestConfig>,
+) -> Json<LoadTestReport> {
+    rocket::warn!("Starting load test with {} streams", input.streams);
+    let report = run_load_test(&input, db_pool, stream_client.into_inner()).await;
+    rocket::warn!("Load test finished: {:?}", report);
+
+    Json(report)
+}
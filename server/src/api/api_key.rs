@@ -1,3 +1,4 @@
+use chrono::Utc;
 use rocket::{delete, get, post, serde::json::Json, Route, State};
 use rocket_okapi::{
     okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
@@ -6,9 +7,10 @@ use schemars::JsonSchema;
 use uuid::Uuid;
 
 use crate::{
-    auth::{build_api_key_string, ChatRsUserId},
+    auth::{build_api_key_string, parse_allowed_ips, ChatRsUserId},
     db::{
-        models::{ChatRsApiKey, NewChatRsApiKey},
+        models::{ChatRsApiKey, ChatRsApiKeyScope, NewChatRsApiKey},
+        pagination::ListQuery,
         services::ApiKeyDbService,
         DbConnection,
     },
@@ -16,36 +18,47 @@ use crate::{
     utils::Encryptor,
 };
 
+/// Max number of days until an API key expires
+const MAX_EXPIRES_IN_DAYS: u32 = 3650;
+
 pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
     openapi_get_routes_spec![settings: get_all_api_keys, create_api_key, delete_api_key]
 }
 
-/// List all API keys
+/// List API keys. The filter matches API key names.
 #[openapi(tag = "API Keys")]
-#[get("/")]
+#[get("/?<query..>")]
 async fn get_all_api_keys(
     user_id: ChatRsUserId,
     mut db: DbConnection,
+    query: ListQuery,
 ) -> Result<Json<Vec<ChatRsApiKey>>, ApiError> {
-    let keys = ApiKeyDbService::new(&mut db)
-        .find_by_user_id(&user_id)
-        .await?;
+    let keys = ApiKeyDbService::new(&mut db).list(&user_id, &query).await?;
 
     Ok(Json(keys))
 }
 
 #[derive(JsonSchema, serde::Deserialize)]
-struct ApiKeyCreateInput {
-    name: String,
+pub(super) struct ApiKeyCreateInput {
+    pub name: String,
+    /// What the API key can access (default: `admin`, i.e. full access)
+    pub scopes: Option<Vec<ChatRsApiKeyScope>>,
+    /// Number of days until the API key expires (default: never, max: 3650)
+    pub expires_in_days: Option<u32>,
+    /// IP addresses or CIDR ranges (e.g. `192.168.1.0/24`) that the API key can be used from
+    /// (default: any address, max: 20)
+    pub allowed_ips: Option<Vec<String>>,
 }
 
 #[derive(JsonSchema, serde::Serialize)]
-struct ApiKeyCreateResponse {
+pub(super) struct ApiKeyCreateResponse {
     id: Uuid,
     key: String,
 }
 
-/// Create a new API key
+/// Create a new API key. Routes for chats and sessions accept the `chat:read` and
+/// `chat:write` scopes, and routes for approving and executing tool calls accept the
+/// `tools:execute` scope. All other routes need the `admin` scope.
 #[openapi(tag = "API Keys")]
 #[post("/", data = "<input>")]
 async fn create_api_key(
@@ -54,18 +67,51 @@ async fn create_api_key(
     encryptor: &State<Encryptor>,
     input: Json<ApiKeyCreateInput>,
 ) -> Result<Json<ApiKeyCreateResponse>, ApiError> {
-    let key_id = ApiKeyDbService::new(&mut db)
+    let response = create_key(&mut db, encryptor, &user_id, &input).await?;
+
+    Ok(Json(response))
+}
+
+/// Create an API key for the user, and build the key string
+pub(super) async fn create_key(
+    db: &mut DbConnection,
+    encryptor: &Encryptor,
+    user_id: &Uuid,
+    input: &ApiKeyCreateInput,
+) -> Result<ApiKeyCreateResponse, ApiError> {
+    let mut scopes: Vec<String> = match &input.scopes {
+        Some(scopes) => scopes
+            .iter()
+            .map(|scope| <&str>::from(scope).to_owned())
+            .collect(),
+        None => vec![<&str>::from(&ChatRsApiKeyScope::Admin).to_owned()],
+    };
+    scopes.sort_unstable();
+    scopes.dedup();
+    let expires_at = input
+        .expires_in_days
+        .map(|days| Utc::now() + chrono::Duration::days(days.clamp(1, MAX_EXPIRES_IN_DAYS).into()));
+    let allowed_ips = input
+        .allowed_ips
+        .as_deref()
+        .filter(|ranges| !ranges.is_empty())
+        .map(parse_allowed_ips)
+        .transpose()?;
+    let key_id = ApiKeyDbService::new(db)
         .create(NewChatRsApiKey {
-            user_id: &user_id,
+            user_id,
             name: &input.name,
+            scopes: &scopes,
+            expires_at,
+            allowed_ips: allowed_ips.as_ref(),
         })
         .await?;
     let (ciphertext, nonce) = encryptor.encrypt_bytes(key_id.as_bytes())?;
 
-    Ok(Json(ApiKeyCreateResponse {
+    Ok(ApiKeyCreateResponse {
         id: key_id,
         key: build_api_key_string(&ciphertext, &nonce),
-    }))
+    })
 }
 
 /// Delete an API key
@@ -1,35 +1,66 @@
+use chrono::Utc;
 use rocket::{
-    delete, get, post,
+    delete, get,
+    http::ContentType,
+    post,
     request::{FromRequest, Outcome},
+    response::{self, Redirect, Responder},
     serde::json::Json,
-    Route,
+    Request, Response, Route, State,
 };
 use rocket_flex_session::Session;
 use rocket_okapi::{
-    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+    okapi::openapi3::{OpenApi, RefOr, Response as OpenApiResponse, Responses},
+    openapi, openapi_get_routes_spec,
+    r#gen::OpenApiGenerator,
+    response::OpenApiResponderInner,
+    settings::OpenApiSettings,
     OpenApiFromRequest,
 };
 use schemars::JsonSchema;
+use tokio::io::DuplexStream;
+use uuid::Uuid;
 
 use crate::{
+    api::user::delete_user_data,
     auth::{
-        ChatRsAuthSession, DiscordOAuthConfig, GitHubOAuthConfig, GoogleOAuthConfig, OIDCConfig,
-        SSOHeaderMergedConfig,
+        consume_token, create_token, get_auth_sessions, hash_password, normalize_email,
+        revoke_all_auth_sessions, revoke_auth_session, start_auth_session, verify_password,
+        AuthClientInfo, AuthSessionInfo, AuthThrottle, ChatRsAuthSession, ChatRsUserId,
+        DiscordOAuthConfig, GitHubOAuthConfig, GoogleOAuthConfig, LocalAuthConfig, LocalAuthError,
+        LocalAuthToken, OIDCConfig, SSOHeaderMergedConfig, ThrottleSubject,
     },
+    data_export::{get_archive_name, UserDataExport},
     db::{
-        models::ChatRsUser,
-        services::{
-            ApiKeyDbService, ChatDbService, ProviderDbService, SecretDbService, ToolDbService,
-            UserDbService,
-        },
+        models::{ChatRsUser, ChatRsUserIdentity, NewChatRsUser, UpdateChatRsUser},
+        services::UserDbService,
         DbConnection,
     },
     errors::ApiError,
+    redis::RedisClient,
+    storage::LocalStorage,
 };
 
 /// Auth routes
 pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
-    openapi_get_routes_spec![settings: user, auth_config, logout, delete_account]
+    openapi_get_routes_spec![
+        settings: user,
+        auth_config,
+        logout,
+        logout_redirect,
+        list_sessions,
+        revoke_session,
+        list_identities,
+        unlink_identity,
+        local_register,
+        local_login,
+        local_verify_email,
+        local_request_password_reset,
+        local_reset_password,
+        export_data,
+        delete_account,
+        delete_account_legacy
+    ]
 }
 
 /// # Get User
@@ -51,6 +82,8 @@ struct AuthConfig {
     discord: bool,
     /// OIDC configuration
     oidc: Option<OIDC>,
+    /// Email + password login configuration
+    local: Option<Local>,
     /// SSO configuration
     sso: Option<SSO>,
 }
@@ -63,6 +96,19 @@ struct OIDC {
     name: String,
 }
 
+#[derive(Debug, JsonSchema, serde::Serialize)]
+struct Local {
+    /// Whether email + password login is enabled
+    enabled: bool,
+    /// Whether new users can register
+    registration: bool,
+    /// Whether passwords can be reset by email
+    password_reset: bool,
+    /// Site key of the CAPTCHA that must be solved to register, log in, and reset the
+    /// password (if required). Its token is sent as `captcha_token`.
+    captcha_site_key: Option<String>,
+}
+
 #[derive(Debug, JsonSchema, serde::Serialize)]
 struct SSO {
     /// Whether SSO header authentication is enabled
@@ -78,6 +124,9 @@ impl<'r> FromRequest<'r> for AuthConfig {
     async fn from_request(req: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
         let rocket = req.rocket();
         let sso_config = rocket.state::<SSOHeaderMergedConfig>();
+        let captcha_config = rocket
+            .state::<AuthThrottle>()
+            .and_then(|throttle| throttle.captcha.as_ref());
 
         Outcome::Success(AuthConfig {
             github: rocket.state::<GitHubOAuthConfig>().is_some(),
@@ -87,6 +136,12 @@ impl<'r> FromRequest<'r> for AuthConfig {
                 enabled: true,
                 name: config.oidc_name.clone().unwrap_or("OIDC".to_owned()),
             }),
+            local: rocket.state::<LocalAuthConfig>().map(|config| Local {
+                enabled: true,
+                registration: config.registration,
+                password_reset: config.verifies_email(),
+                captcha_site_key: captcha_config.map(|captcha| captcha.site_key.clone()),
+            }),
             sso: sso_config.map(|config| SSO {
                 enabled: true,
                 logout_url: config.logout_url.clone(),
@@ -106,12 +161,464 @@ async fn auth_config(config: AuthConfig) -> Json<AuthConfig> {
 /// # Log out
 #[openapi(tag = "Auth")]
 #[post("/logout")]
-async fn logout(mut session: Session<'_, ChatRsAuthSession>) -> Result<String, ApiError> {
-    session.delete();
+async fn logout(
+    mut session: Session<'_, ChatRsAuthSession>,
+    redis: RedisClient,
+) -> Result<String, ApiError> {
+    end_session(&mut session, &redis).await?;
 
     Ok("Logout successful".to_string())
 }
 
+/// # Log out and redirect
+/// Log out, and redirect to the logout URL of the remote service if SSO header authentication
+/// is configured with one (otherwise to the home page). Meant for browser navigation, as the
+/// proxy session must also be ended to log out.
+#[openapi(tag = "Auth")]
+#[get("/logout")]
+async fn logout_redirect(
+    mut session: Session<'_, ChatRsAuthSession>,
+    redis: RedisClient,
+    sso_config: Option<&State<SSOHeaderMergedConfig>>,
+) -> Result<Redirect, ApiError> {
+    end_session(&mut session, &redis).await?;
+    let logout_url = sso_config.and_then(|config| config.logout_url.clone());
+
+    Ok(Redirect::to(logout_url.unwrap_or_else(|| "/".to_owned())))
+}
+
+/// Delete the login session of the request, and revoke its tracked entry
+async fn end_session(
+    session: &mut Session<'_, ChatRsAuthSession>,
+    redis: &fred::clients::Client,
+) -> Result<(), ApiError> {
+    let ids = session.tap(|data| {
+        data.and_then(|auth_session| Some((auth_session.user_id()?, auth_session.session_id()?)))
+    });
+    if let Some((user_id, session_id)) = ids {
+        revoke_auth_session(redis, &user_id, &session_id).await?;
+    }
+    session.delete();
+
+    Ok(())
+}
+
+/// A login method linked to the user
+#[derive(Debug, JsonSchema, serde::Serialize)]
+struct AuthIdentity {
+    provider: ChatRsUserIdentity,
+    /// ID of the account at the provider, the email, or the proxy username
+    account: String,
+    /// Whether the login method is enabled on the server
+    enabled: bool,
+}
+
+impl AuthConfig {
+    /// Whether the login method is enabled on the server
+    fn is_enabled(&self, identity: ChatRsUserIdentity) -> bool {
+        match identity {
+            ChatRsUserIdentity::Github => self.github,
+            ChatRsUserIdentity::Google => self.google,
+            ChatRsUserIdentity::Discord => self.discord,
+            ChatRsUserIdentity::Oidc => self.oidc.is_some(),
+            ChatRsUserIdentity::Sso => self.sso.is_some(),
+            ChatRsUserIdentity::Local => self.local.is_some(),
+        }
+    }
+}
+
+/// Get the login methods linked to the user. Email + password login is only included if the
+/// user has a password.
+async fn get_identities(
+    db: &mut DbConnection,
+    user: &ChatRsUser,
+    config: &AuthConfig,
+) -> Result<Vec<AuthIdentity>, ApiError> {
+    let has_password = UserDbService::new(db).has_password(&user.id).await?;
+    let identities = [
+        (ChatRsUserIdentity::Github, user.github_id.as_ref()),
+        (ChatRsUserIdentity::Google, user.google_id.as_ref()),
+        (ChatRsUserIdentity::Discord, user.discord_id.as_ref()),
+        (ChatRsUserIdentity::Oidc, user.oidc_id.as_ref()),
+        (ChatRsUserIdentity::Sso, user.sso_username.as_ref()),
+        (
+            ChatRsUserIdentity::Local,
+            user.email.as_ref().filter(|_| has_password),
+        ),
+    ];
+
+    Ok(identities
+        .into_iter()
+        .filter_map(|(provider, account)| {
+            Some(AuthIdentity {
+                provider,
+                account: account?.clone(),
+                enabled: config.is_enabled(provider),
+            })
+        })
+        .collect())
+}
+
+/// # List identities
+/// List the login methods linked to the user
+#[openapi(tag = "Auth")]
+#[get("/identities")]
+async fn list_identities(
+    user: ChatRsUser,
+    mut db: DbConnection,
+    config: AuthConfig,
+) -> Result<Json<Vec<AuthIdentity>>, ApiError> {
+    let identities = get_identities(&mut db, &user, &config).await?;
+
+    Ok(Json(identities))
+}
+
+/// # Unlink identity
+/// Remove a login method from the user. At least one other login method that's enabled on the
+/// server must remain. Unlinking email + password login also removes the user's email. The
+/// SSO username can't be unlinked while SSO header authentication is enabled.
+#[openapi(tag = "Auth")]
+#[delete("/identities/<provider>")]
+async fn unlink_identity(
+    user: ChatRsUser,
+    mut db: DbConnection,
+    config: AuthConfig,
+    provider: ChatRsUserIdentity,
+) -> Result<String, ApiError> {
+    if provider == ChatRsUserIdentity::Sso && config.sso.is_some() {
+        return Err(ApiError::Forbidden(
+            "The SSO username is managed by the proxy".into(),
+        ));
+    }
+    let identities = get_identities(&mut db, &user, &config).await?;
+    if !identities
+        .iter()
+        .any(|identity| identity.provider == provider)
+    {
+        return Err(diesel::result::Error::NotFound.into());
+    }
+    if !identities
+        .iter()
+        .any(|identity| identity.provider != provider && identity.enabled)
+    {
+        return Err(ApiError::Forbidden(
+            "Can't unlink the last login method".into(),
+        ));
+    }
+    UserDbService::new(&mut db)
+        .unlink_identity(&user.id, provider)
+        .await?;
+
+    Ok(user.id.to_string())
+}
+
+/// A login session of the user
+#[derive(Debug, JsonSchema, serde::Serialize)]
+struct AuthSession {
+    id: Uuid,
+    #[serde(flatten)]
+    info: AuthSessionInfo,
+    /// Whether this is the session of the current request
+    current: bool,
+}
+
+/// # List login sessions
+/// List the active login sessions of the user, most recently active first
+#[openapi(tag = "Auth")]
+#[get("/sessions")]
+async fn list_sessions(
+    user_id: ChatRsUserId,
+    session: Session<'_, ChatRsAuthSession>,
+    redis: RedisClient,
+) -> Result<Json<Vec<AuthSession>>, ApiError> {
+    let current_id = session.tap(|data| data.and_then(|auth_session| auth_session.session_id()));
+    let sessions = get_auth_sessions(&redis, &user_id)
+        .await?
+        .into_iter()
+        .map(|(id, info)| AuthSession {
+            id,
+            info,
+            current: current_id == Some(id),
+        })
+        .collect();
+
+    Ok(Json(sessions))
+}
+
+/// # Revoke login session
+/// Log out the given session of the user, e.g. on a lost device
+#[openapi(tag = "Auth")]
+#[delete("/sessions/<session_id>")]
+async fn revoke_session(
+    user_id: ChatRsUserId,
+    redis: RedisClient,
+    session_id: Uuid,
+) -> Result<String, ApiError> {
+    if !revoke_auth_session(&redis, &user_id, &session_id).await? {
+        return Err(diesel::result::Error::NotFound.into());
+    }
+
+    Ok(session_id.to_string())
+}
+
+/// Get the email + password login config, if enabled
+fn get_local_config(config: Option<&State<LocalAuthConfig>>) -> Result<&LocalAuthConfig, ApiError> {
+    config
+        .map(|config| config.inner())
+        .ok_or_else(|| ApiError::Forbidden("Email + password login is disabled".to_owned()))
+}
+
+/// Get the subjects that failed attempts are counted for: the IP address of the client, and
+/// the account with the email if given
+fn get_throttle_subjects<'a>(
+    client_info: &AuthClientInfo,
+    email: Option<&'a str>,
+) -> Vec<ThrottleSubject<'a>> {
+    let ip = client_info.ip().map(ThrottleSubject::Ip);
+    ip.into_iter()
+        .chain(email.map(ThrottleSubject::Account))
+        .collect()
+}
+
+#[derive(Debug, JsonSchema, serde::Deserialize)]
+struct LocalRegisterInput {
+    email: String,
+    name: String,
+    password: String,
+    /// CAPTCHA token, if required by the server
+    captcha_token: Option<String>,
+}
+
+/// # Register with email
+/// Register a new user with an email and password. If emails are configured on the server,
+/// a verification link is sent to the email. Otherwise, the user is logged in right away.
+#[openapi(tag = "Auth")]
+#[post("/local/register", data = "<input>")]
+async fn local_register(
+    config: Option<&State<LocalAuthConfig>>,
+    throttle: &State<AuthThrottle>,
+    mut db: DbConnection,
+    redis: RedisClient,
+    mut session: Session<'_, ChatRsAuthSession>,
+    client_info: AuthClientInfo,
+    input: Json<LocalRegisterInput>,
+) -> Result<String, ApiError> {
+    let config = get_local_config(config)?;
+    if !config.registration {
+        return Err(LocalAuthError::RegistrationDisabled.into());
+    }
+    let subjects = get_throttle_subjects(&client_info, None);
+    throttle.check(&redis, &subjects).await?;
+    throttle
+        .verify_captcha(input.captcha_token.as_deref(), client_info.ip())
+        .await?;
+    let email = normalize_email(&input.email)?;
+    let name = input.name.trim();
+    if name.is_empty() {
+        return Err(LocalAuthError::MissingName.into());
+    }
+    let password_hash = hash_password(&input.password).await?;
+
+    let mut db_service = UserDbService::new(&mut db);
+    if db_service.find_by_email(&email).await?.is_some() {
+        throttle.record_failure(&redis, &subjects).await?;
+        return Err(LocalAuthError::EmailTaken.into());
+    }
+    let user = db_service
+        .create(NewChatRsUser {
+            name,
+            email: Some(&email),
+            password_hash: Some(&password_hash),
+            email_verified_at: (!config.verifies_email()).then(Utc::now),
+            ..Default::default()
+        })
+        .await?;
+
+    if config.verifies_email() {
+        let token = create_token(&redis, LocalAuthToken::VerifyEmail, &user.id).await?;
+        config.send_verification_email(&email, &token).await?;
+        return Ok("Verification email sent".to_owned());
+    }
+    start_auth_session(&mut session, &redis, &client_info, user.id).await?;
+
+    Ok("Registration successful".to_owned())
+}
+
+#[derive(Debug, JsonSchema, serde::Deserialize)]
+struct LocalLoginInput {
+    email: String,
+    password: String,
+    /// CAPTCHA token, if required by the server
+    captcha_token: Option<String>,
+}
+
+/// # Log in with email
+/// Log in with an email and password. If the email isn't verified yet, a new verification
+/// link is sent. After too many failed attempts, the account and the IP address are locked
+/// out for a while.
+#[openapi(tag = "Auth")]
+#[post("/local/login", data = "<input>")]
+async fn local_login(
+    config: Option<&State<LocalAuthConfig>>,
+    throttle: &State<AuthThrottle>,
+    mut db: DbConnection,
+    redis: RedisClient,
+    mut session: Session<'_, ChatRsAuthSession>,
+    client_info: AuthClientInfo,
+    input: Json<LocalLoginInput>,
+) -> Result<String, ApiError> {
+    let config = get_local_config(config)?;
+    let email = normalize_email(&input.email).map_err(|_| LocalAuthError::InvalidCredentials)?;
+    let subjects = get_throttle_subjects(&client_info, Some(&email));
+    throttle.check(&redis, &subjects).await?;
+    throttle
+        .verify_captcha(input.captcha_token.as_deref(), client_info.ip())
+        .await?;
+    let credentials = UserDbService::new(&mut db)
+        .find_credentials_by_email(&email)
+        .await?;
+    let is_valid = match &credentials {
+        Some((_, Some(password_hash), _)) => {
+            verify_password(&input.password, password_hash).await?
+        }
+        _ => false,
+    };
+    let Some((user_id, _, email_verified_at)) = credentials.filter(|_| is_valid) else {
+        throttle.record_failure(&redis, &subjects).await?;
+        return Err(LocalAuthError::InvalidCredentials.into());
+    };
+    // Failures from the client's IP address are kept, so that they can't be reset by logging
+    // in to another account
+    throttle
+        .record_success(&redis, &[ThrottleSubject::Account(&email)])
+        .await?;
+    if UserDbService::new(&mut db).is_disabled(&user_id).await? {
+        return Err(ApiError::Authentication("User is disabled".to_owned()));
+    }
+    if config.verifies_email() && email_verified_at.is_none() {
+        let token = create_token(&redis, LocalAuthToken::VerifyEmail, &user_id).await?;
+        config.send_verification_email(&email, &token).await?;
+        return Err(LocalAuthError::EmailNotVerified.into());
+    }
+    start_auth_session(&mut session, &redis, &client_info, user_id).await?;
+
+    Ok("Login successful".to_owned())
+}
+
+/// # Verify email
+/// Verify the user's email with the link sent by email, and redirect to the app
+#[openapi(tag = "Auth")]
+#[get("/local/verify?<token>")]
+async fn local_verify_email(
+    mut db: DbConnection,
+    redis: RedisClient,
+    token: &str,
+) -> Result<Redirect, ApiError> {
+    let user_id = consume_token(&redis, LocalAuthToken::VerifyEmail, token)
+        .await?
+        .ok_or(LocalAuthError::InvalidToken)?;
+    UserDbService::new(&mut db)
+        .update(
+            &user_id,
+            UpdateChatRsUser {
+                email_verified_at: Some(Utc::now()),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    Ok(Redirect::to("/"))
+}
+
+#[derive(Debug, JsonSchema, serde::Deserialize)]
+struct LocalPasswordResetRequestInput {
+    email: String,
+    /// CAPTCHA token, if required by the server
+    captcha_token: Option<String>,
+}
+
+/// # Request password reset
+/// Send a password reset link to the email, if there's an account with this email. Requests
+/// are throttled like failed logins, to limit the emails sent.
+#[openapi(tag = "Auth")]
+#[post("/local/password-reset/request", data = "<input>")]
+async fn local_request_password_reset(
+    config: Option<&State<LocalAuthConfig>>,
+    throttle: &State<AuthThrottle>,
+    mut db: DbConnection,
+    redis: RedisClient,
+    client_info: AuthClientInfo,
+    input: Json<LocalPasswordResetRequestInput>,
+) -> Result<String, ApiError> {
+    let config = get_local_config(config)?;
+    if !config.verifies_email() {
+        return Err(LocalAuthError::EmailUnavailable.into());
+    }
+    let email = normalize_email(&input.email)?;
+    let subjects = get_throttle_subjects(&client_info, Some(&email));
+    throttle.check(&redis, &subjects).await?;
+    throttle
+        .verify_captcha(input.captcha_token.as_deref(), client_info.ip())
+        .await?;
+    throttle.record_failure(&redis, &subjects).await?;
+    let user = UserDbService::new(&mut db).find_by_email(&email).await?;
+    if let Some(user) = user {
+        let token = create_token(&redis, LocalAuthToken::ResetPassword, &user.id).await?;
+        config.send_reset_email(&email, &token).await?;
+    }
+
+    Ok("If an account exists with this email, a password reset link was sent".to_owned())
+}
+
+#[derive(Debug, JsonSchema, serde::Deserialize)]
+struct LocalPasswordResetInput {
+    /// Token from the password reset link
+    token: String,
+    /// New password
+    password: String,
+}
+
+/// # Reset password
+/// Set a new password with the token from the password reset link. This also verifies the
+/// email, and logs out all sessions of the user.
+#[openapi(tag = "Auth")]
+#[post("/local/password-reset", data = "<input>")]
+async fn local_reset_password(
+    config: Option<&State<LocalAuthConfig>>,
+    throttle: &State<AuthThrottle>,
+    mut db: DbConnection,
+    redis: RedisClient,
+    client_info: AuthClientInfo,
+    input: Json<LocalPasswordResetInput>,
+) -> Result<String, ApiError> {
+    get_local_config(config)?;
+    let subjects = get_throttle_subjects(&client_info, None);
+    throttle.check(&redis, &subjects).await?;
+    let password_hash = hash_password(&input.password).await?;
+    let Some(user_id) = consume_token(&redis, LocalAuthToken::ResetPassword, &input.token).await?
+    else {
+        throttle.record_failure(&redis, &subjects).await?;
+        return Err(LocalAuthError::InvalidToken.into());
+    };
+    let user = UserDbService::new(&mut db)
+        .find_by_id(&user_id)
+        .await?
+        .ok_or(LocalAuthError::InvalidToken)?;
+    UserDbService::new(&mut db)
+        .update(
+            &user.id,
+            UpdateChatRsUser {
+                password_hash: Some(&password_hash),
+                email_verified_at: user.email_verified_at.or(Some(Utc::now())),
+                ..Default::default()
+            },
+        )
+        .await?;
+    revoke_all_auth_sessions(&redis, &user.id).await?;
+
+    Ok("Password reset successful".to_owned())
+}
+
 #[derive(Debug, JsonSchema, serde::Deserialize)]
 struct DeleteAccountInput {
     /// Confirmation message: "DELETE MY ACCOUNT"
@@ -119,39 +626,90 @@ struct DeleteAccountInput {
 }
 
 /// # Delete account
-/// Delete account and all associated data. ⚠️ WARNING: This action is irreversible.
+/// Delete account and all associated data: chat sessions, providers, tools, secrets, API
+/// keys, stored files, login sessions, etc. ⚠️ WARNING: This action is irreversible.
 #[openapi(tag = "Auth")]
-#[delete("/user/delete-my-account", data = "<input>")]
+#[delete("/account", data = "<input>")]
 async fn delete_account(
     user: ChatRsUser,
     mut db: DbConnection,
+    storage: &State<LocalStorage>,
+    redis: RedisClient,
     input: Json<DeleteAccountInput>,
 ) -> Result<String, ApiError> {
     if input.confirm != "DELETE MY ACCOUNT" {
         return Err(ApiError::Authentication("Invalid confirmation".to_string()));
     }
 
-    let sessions = ChatDbService::new(&mut db).delete_by_user(&user.id).await?;
-    let providers = ProviderDbService::new(&mut db)
-        .delete_by_user(&user.id)
-        .await?;
-    let tools = ToolDbService::new(&mut db).delete_by_user(&user.id).await?;
-    let secrets = SecretDbService::new(&mut db)
-        .delete_by_user(&user.id)
-        .await?;
-    let api_keys = ApiKeyDbService::new(&mut db)
-        .delete_by_user(&user.id)
-        .await?;
+    delete_user_data(&mut db, storage, &redis, &user.id).await
+}
 
-    let user_id = UserDbService::new(&mut db).delete(&user.id).await?;
-
-    Ok(format!(
-        "Deleted user {}:  {} providers, {} sessions, {} tools, {} secrets, {} API keys",
-        user_id,
-        providers.len(),
-        sessions.len(),
-        tools.len(),
-        secrets.len(),
-        api_keys.len()
-    ))
+/// # Delete account (legacy)
+/// Same as `DELETE /auth/account`, kept for older clients.
+#[openapi(tag = "Auth")]
+#[delete("/user/delete-my-account", data = "<input>")]
+async fn delete_account_legacy(
+    user: ChatRsUser,
+    db: DbConnection,
+    storage: &State<LocalStorage>,
+    redis: RedisClient,
+    input: Json<DeleteAccountInput>,
+) -> Result<String, ApiError> {
+    delete_account(user, db, storage, redis, input).await
+}
+
+/// # Export data
+/// Download a tar archive of all the user's data: profile, chat sessions and messages,
+/// providers, tools, memories, and stored files. Secrets and API keys are not exported.
+#[openapi(tag = "Auth")]
+#[post("/export")]
+async fn export_data(
+    user: ChatRsUser,
+    mut db: DbConnection,
+    storage: &State<LocalStorage>,
+) -> Result<DataExportArchive, ApiError> {
+    let name = get_archive_name(&user.id);
+    let export = UserDataExport::collect(&mut db, storage, user).await?;
+
+    Ok(DataExportArchive {
+        name,
+        archive: export.into_archive(),
+    })
+}
+
+/// Tar archive of the user's data (or of a backup), streamed as a download
+pub(super) struct DataExportArchive {
+    pub(super) name: String,
+    pub(super) archive: DuplexStream,
+}
+
+impl<'r> Responder<'r, 'static> for DataExportArchive {
+    fn respond_to(self, _req: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .header(ContentType::new("application", "x-tar"))
+            .raw_header(
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", self.name),
+            )
+            .streamed_body(self.archive)
+            .ok()
+    }
+}
+
+/// OpenAPI documentation for the data export response.
+impl OpenApiResponderInner for DataExportArchive {
+    fn responses(_gen: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        let mut responses = schemars::Map::new();
+        responses.insert(
+            "200".to_string(),
+            RefOr::Object(OpenApiResponse {
+                description: "Tar archive of the exported data".to_string(),
+                ..Default::default()
+            }),
+        );
+        Ok(Responses {
+            responses,
+            ..Default::default()
+        })
+    }
 }
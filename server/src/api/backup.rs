@@ -0,0 +1,122 @@
+use rocket::{
+    data::{Data, ToByteUnit},
+    http::Status,
+    post,
+    request::{FromRequest, Outcome},
+    serde::json::Json,
+    Request, Route, State,
+};
+use rocket_okapi::{
+    okapi::openapi3::{OpenApi, Parameter, ParameterValue},
+    openapi, openapi_get_routes_spec,
+    r#gen::OpenApiGenerator,
+    request::{OpenApiFromRequest, RequestHeaderInput},
+    settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+
+use crate::{
+    api::auth::DataExportArchive,
+    auth::AdminUserId,
+    backup::{get_backup_name, restore_backup, InstanceBackup, RestoreReport},
+    db::DbConnection,
+    errors::ApiError,
+    utils::Encryptor,
+};
+
+/// Maximum size of an uploaded backup, in GiB
+const MAX_BACKUP_SIZE_GIB: u64 = 16;
+
+/// Header with the passphrase of the uploaded backup
+const PASSPHRASE_HEADER: &str = "X-Backup-Passphrase";
+
+pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
+    openapi_get_routes_spec![settings: create_backup, restore_backup_archive]
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct BackupInput {
+    /// Passphrase for encrypting the secrets of the backup (at least 12 characters). It's
+    /// needed to restore the backup.
+    passphrase: String,
+}
+
+/// # Create backup
+/// Download a consistent export of the users, sessions, messages, providers, secrets, and
+/// tools of the instance, as a tar archive. Secrets are encrypted with the passphrase.
+#[openapi(tag = "Admin")]
+#[post("/", data = "<input>")]
+async fn create_backup(
+    _admin_id: AdminUserId,
+    db: DbConnection,
+    encryptor: &State<Encryptor>,
+    input: Json<BackupInput>,
+) -> Result<DataExportArchive, ApiError> {
+    let backup = InstanceBackup::new(db, encryptor, &input.passphrase)?;
+
+    Ok(DataExportArchive {
+        name: get_backup_name(),
+        archive: backup.into_archive(),
+    })
+}
+
+/// # Restore backup
+/// Import a backup archive (max 16 GiB), with its passphrase in the `X-Backup-Passphrase`
+/// header. Rows that already exist are skipped, so backups should be restored on a new
+/// instance.
+#[openapi(tag = "Admin")]
+#[post("/restore", data = "<data>")]
+async fn restore_backup_archive(
+    _admin_id: AdminUserId,
+    mut db: DbConnection,
+    encryptor: &State<Encryptor>,
+    passphrase: BackupPassphrase,
+    data: Data<'_>,
+) -> Result<Json<RestoreReport>, ApiError> {
+    let archive = data.open(MAX_BACKUP_SIZE_GIB.gibibytes());
+    let report = restore_backup(&mut db, encryptor, archive, &passphrase.0).await?;
+
+    Ok(Json(report))
+}
+
+/// Request guard for the passphrase header of the uploaded backup
+struct BackupPassphrase(String);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BackupPassphrase {
+    type Error = &'r str;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match req.headers().get_one(PASSPHRASE_HEADER) {
+            Some(passphrase) => Outcome::Success(BackupPassphrase(passphrase.to_owned())),
+            None => Outcome::Error((Status::BadRequest, "Missing backup passphrase")),
+        }
+    }
+}
+
+/// OpenAPI documentation for the passphrase header.
+impl<'r> OpenApiFromRequest<'r> for BackupPassphrase {
+    fn from_request_input(
+        gen: &mut OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> rocket_okapi::Result<RequestHeaderInput> {
+        Ok(RequestHeaderInput::Parameter(Parameter {
+            name: PASSPHRASE_HEADER.to_owned(),
+            location: "header".to_owned(),
+            description: Some("The passphrase of the backup".to_owned()),
+            required: true,
+            deprecated: false,
+            allow_empty_value: false,
+            value: ParameterValue::Schema {
+                style: None,
+                explode: None,
+                allow_reserved: false,
+                schema: gen.json_schema_no_ref::<String>(),
+                example: None,
+                examples: None,
+            },
+            extensions: Default::default(),
+        }))
+    }
+}
@@ -1,5 +1,6 @@
 use std::{borrow::Cow, pin::Pin};
 
+use chrono::Utc;
 use rocket::{
     futures::{stream, Stream, StreamExt},
     get, post,
@@ -15,124 +16,282 @@ use tokio_stream::wrappers::ReceiverStream;
 use uuid::Uuid;
 
 use crate::{
-    api::session::DEFAULT_SESSION_TITLE,
-    auth::ChatRsUserId,
+    agent::AutoToolRunner,
+    api::{
+        add_component_schema, service_account::check_message_quota, session::DEFAULT_SESSION_TITLE,
+    },
+    attachments::{
+        add_project_attachments, check_attachments, load_attachments, load_project_context,
+        ProjectContext, MAX_ATTACHMENTS,
+    },
+    auth::{ChatRead, ChatRsScopedUserId, ChatWrite},
+    config::AppConfig,
     db::{
         models::{
-            AssistantMeta, ChatRsMessageMeta, ChatRsMessageRole, ChatRsSessionMeta,
-            NewChatRsMessage, UpdateChatRsSession,
+            AssistantMeta, ChatRsMessage, ChatRsMessageMeta, ChatRsMessageRole, ChatRsSessionMeta,
+            ChatRsSessionProviderConfig, ChatRsSessionRole, NewChatRsMessage, UpdateChatRsSession,
+        },
+        services::{
+            AttachmentDbService, ChatDbService, PresetDbService, ProjectDbService,
+            ProviderDbService, ToolDbService,
         },
-        services::{ChatDbService, ProviderDbService, ToolDbService},
         DbConnection, DbPool,
     },
     errors::ApiError,
-    provider::{build_llm_provider_api, LlmError, LlmProviderOptions},
-    redis::{ExclusiveRedisClient, RedisClient},
+    knowledge::KnowledgeService,
+    provider::{
+        build_llm_provider_api, get_extra_headers, LlmError, LlmErrorInfo, LlmProviderOptions,
+    },
+    provider_debug::ProviderDebugLogger,
+    provider_health::ProviderHealthService,
+    provider_models::find_model_deprecation,
+    redis::RedisClient,
+    shutdown::StreamShutdown,
+    storage::LocalStorage,
     stream::{
-        cancel_current_chat_stream, check_chat_stream_exists, get_current_chat_streams,
-        LastEventId, LlmStreamWriter, SseStreamReader,
+        acquire_chat_stream, cancel_current_chat_stream, check_chat_stream_exists,
+        get_chat_stream_key, get_current_chat_streams, is_chat_stream_active,
+        publish_session_event, release_chat_stream, ChatStreamInfo, ExclusiveStreamClient,
+        LastEventId, LlmStreamWriter, RedisStreamChunk, SessionEvent, SseStreamReader,
+        StreamClient,
     },
-    tools::{get_llm_tools_from_input, SendChatToolInput},
+    tools::{get_llm_tools_from_input, request_approvals, SendChatToolInput},
     utils::{generate_title, Encryptor},
+    webhooks::trigger_message_webhooks,
 };
 
 pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
-    openapi_get_routes_spec![
+    let (routes, mut spec) = openapi_get_routes_spec![
         settings: get_chat_streams,
         send_chat_stream,
         connect_to_chat_stream,
         cancel_chat_stream,
-    ]
+    ];
+    add_component_schema::<RedisStreamChunk>(&mut spec, settings);
+    add_component_schema::<LlmErrorInfo>(&mut spec, settings);
+    (routes, spec)
 }
 
 #[derive(Debug, JsonSchema, serde::Serialize)]
 pub struct GetChatStreamsResponse {
+    /// IDs of the sessions with an ongoing stream
     sessions: Vec<String>,
+    /// The ongoing streams, latest first
+    streams: Vec<ChatStreamInfo>,
 }
 
 /// # Get chat streams
-/// Get the ongoing chat response streams
+/// Get the ongoing chat response streams, e.g. to show the sessions that are generating a
+/// response and to reconnect to them with `/<session_id>/stream`
 #[openapi(tag = "Chat")]
 #[get("/streams")]
 pub async fn get_chat_streams(
-    user_id: ChatRsUserId,
-    redis: RedisClient,
+    user_id: ChatRsScopedUserId<ChatRead>,
+    stream_client: StreamClient,
 ) -> Result<Json<GetChatStreamsResponse>, ApiError> {
-    let sessions = get_current_chat_streams(&redis, &user_id).await?;
-    Ok(Json(GetChatStreamsResponse { sessions }))
+    let streams = get_current_chat_streams(&stream_client, &user_id).await?;
+    let sessions = streams
+        .iter()
+        .map(|stream| stream.session_id.to_string())
+        .collect();
+    Ok(Json(GetChatStreamsResponse { sessions, streams }))
 }
 
 #[derive(JsonSchema, serde::Deserialize)]
 pub struct SendChatInput<'a> {
     /// The new chat message from the user
     message: Option<Cow<'a, str>>,
-    /// The ID of the provider to chat with
-    provider_id: i32,
-    /// Configuration for the provider
-    options: LlmProviderOptions,
+    /// IDs of the uploaded files to attach to the new message (see `/api/file/upload`)
+    attachments: Option<Vec<Uuid>>,
+    /// The ID of a saved preset to use. The provider, options, and tools given here
+    /// will override those of the preset.
+    preset_id: Option<Uuid>,
+    /// The ID of the provider to chat with. Defaults to the preset's provider, or the
+    /// provider last used in this session.
+    provider_id: Option<i32>,
+    /// The ID of a provider to use instead if the chosen provider is currently unhealthy.
+    /// The fallback provider's default model will be used.
+    fallback_provider_id: Option<i32>,
+    /// Configuration for the provider. Defaults to the preset's options, or the options
+    /// last used in this session with the same provider.
+    options: Option<LlmProviderOptions>,
     /// Configuration of tools available to the assistant
     tools: Option<SendChatToolInput>,
+    /// Preview the tools the assistant would call, without executing any of them. The
+    /// planned tool calls are saved to the assistant message for review.
+    dry_run: Option<bool>,
+    /// Let the server execute the tool calls of auto-approved tools, and continue the
+    /// response with the results until the assistant gives a final answer. Tool calls of
+    /// other tools are left for the user to execute.
+    auto: Option<SendChatAutoInput>,
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+pub struct SendChatAutoInput {
+    /// Max number of times tool calls are executed before stopping (default: 5, max: 20)
+    max_iterations: Option<u32>,
 }
 
 #[derive(JsonSchema, serde::Serialize)]
 pub struct SendChatResponse {
     message: &'static str,
     url: String,
+    /// Warning about the request (e.g. the model is deprecated)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    warning: Option<String>,
 }
 
 /// # Start chat stream
 /// Send a chat message and start the streamed assistant response. After the response
 /// has started, use the `/<session_id>/stream` endpoint to connect to the SSE stream.
+/// Members of a shared session need the `write` role, and the response uses their own
+/// providers, presets, and tools.
 #[openapi(tag = "Chat")]
 #[post("/<session_id>", data = "<input>")]
 pub async fn send_chat_stream(
-    user_id: ChatRsUserId,
+    user_id: ChatRsScopedUserId<ChatWrite>,
     db_pool: &State<DbPool>,
     mut db: DbConnection,
     redis: RedisClient,
-    redis_writer: ExclusiveRedisClient,
+    stream_writer_client: ExclusiveStreamClient,
+    shutdown: &State<StreamShutdown>,
+    app_config: &State<AppConfig>,
     encryptor: &State<Encryptor>,
     http_client: &State<reqwest::Client>,
+    storage: &State<LocalStorage>,
+    knowledge: KnowledgeService,
     session_id: Uuid,
     mut input: Json<SendChatInput<'_>>,
 ) -> Result<Json<SendChatResponse>, ApiError> {
-    // Check that we aren't already streaming a response for this session
-    if check_chat_stream_exists(&redis, &user_id, &session_id).await? {
+    // Get the session and check that the user can send messages to it
+    let (session, role) = ChatDbService::new(&mut db)
+        .get_session_with_role(&user_id, &session_id)
+        .await?;
+    check_write_role(&role)?;
+    check_message_quota(&mut db, &redis, &user_id).await?;
+    let owner_id = session.user_id;
+
+    // Check that we aren't already streaming a response for this session (on any server instance)
+    let streams = stream_writer_client.into_inner();
+    if is_chat_stream_active(&streams, &owner_id, &session_id).await? {
         return Err(LlmError::AlreadyStreaming)?;
     }
+    let active_stream = shutdown.start_stream().ok_or(LlmError::ShuttingDown)?;
 
-    // Get session and message history
-    let (session, mut messages) = ChatDbService::new(&mut db)
-        .get_session_with_messages(&user_id, &session_id)
-        .await?;
+    // Check the attached files
+    let attachment_ids = input
+        .attachments
+        .take()
+        .filter(|_| input.message.is_some())
+        .unwrap_or_default();
+    if !attachment_ids.is_empty() {
+        check_attachments(&mut db, &user_id, &attachment_ids, MAX_ATTACHMENTS).await?;
+    }
+
+    // Get message history
+    let mut messages = ChatDbService::new(&mut db).get_messages(&session).await?;
+
+    // Merge the input with the chosen preset
+    let preset = match input.preset_id {
+        Some(preset_id) => Some(
+            PresetDbService::new(&mut db)
+                .find_by_id(&user_id, &preset_id)
+                .await?,
+        ),
+        None => None,
+    };
+    let remembered_config = session.meta.provider_config.clone();
+    let requested_provider_id = input
+        .provider_id
+        .or(preset.as_ref().map(|preset| preset.provider_id))
+        .or(remembered_config.as_ref().map(|config| config.provider_id))
+        .ok_or(LlmError::MissingProviderConfig)?;
+    let remembered_options = remembered_config
+        .filter(|config| config.provider_id == requested_provider_id)
+        .map(|config| config.options);
+    let (preset_options, preset_tools, system_prompt) = match preset {
+        Some(preset) => (
+            Some(preset.data.options),
+            preset.data.tools,
+            preset.data.system_prompt,
+        ),
+        None => (None, None, None),
+    };
+    let requested_options = input
+        .options
+        .take()
+        .or(preset_options)
+        .or(remembered_options)
+        .ok_or(LlmError::MissingProviderConfig)?;
+    let mut options = requested_options.clone();
+    let tool_input = input.tools.take().or(preset_tools);
+
+    // Use the fallback provider if the chosen provider is unhealthy
+    let mut provider_id = requested_provider_id;
+    if let Some(fallback_id) = input.fallback_provider_id {
+        if !ProviderHealthService::new(&redis)
+            .is_available(provider_id)
+            .await?
+        {
+            rocket::info!("Provider {provider_id} unhealthy, using fallback {fallback_id}");
+            provider_id = fallback_id;
+        }
+    }
 
     // Build the LLM provider
-    let (provider, api_key_secret) = ProviderDbService::new(&mut db)
-        .get_by_id(&user_id, input.provider_id)
+    let (provider, api_key_secret, secondary_api_key_secret) = ProviderDbService::new(&mut db)
+        .get_by_id_with_secrets(&user_id, provider_id)
         .await?;
+    if provider_id != requested_provider_id {
+        options.model = provider.default_model.clone();
+    }
+
+    // Check if the model has been deprecated by the server operator
+    let deprecation =
+        find_model_deprecation(app_config.deprecated_models.as_deref(), &options.model);
+    if let Some(deprecation) = deprecation.filter(|deprecation| deprecation.is_expired()) {
+        return Err(LlmError::ModelRetired(deprecation.warning()))?;
+    }
+    let warning = deprecation.map(|deprecation| deprecation.warning());
     let api_key = api_key_secret
         .map(|secret| encryptor.decrypt_string(&secret.ciphertext, &secret.nonce))
         .transpose()?;
-    let provider_api = build_llm_provider_api(
+    let secondary_api_key = secondary_api_key_secret
+        .map(|secret| encryptor.decrypt_string(&secret.ciphertext, &secret.nonce))
+        .transpose()?;
+    let extra_headers = get_extra_headers(&provider, encryptor)?;
+    let mut provider_api = build_llm_provider_api(
         &provider.provider_type.as_str().try_into()?,
         provider.base_url.as_deref(),
         api_key.as_deref(),
+        secondary_api_key.as_deref(),
+        &extra_headers,
         &http_client,
         &redis,
     )?;
-
-    // Get the user's chosen tools
-    let mut tools = None;
-    if let Some(tool_input) = input.tools.as_ref() {
-        let mut tool_db_service = ToolDbService::new(&mut db);
-        tools = Some(get_llm_tools_from_input(&user_id, tool_input, &mut tool_db_service).await?);
+    if provider.debug_logging {
+        provider_api.set_debug_logger(ProviderDebugLogger::new(
+            &redis,
+            provider.id,
+            &[api_key.as_deref(), secondary_api_key.as_deref()],
+        ));
     }
 
+    // Get the user's chosen tools, and the tools enabled automatically
+    let default_tool_input = SendChatToolInput::default();
+    let llm_tools = get_llm_tools_from_input(
+        &user_id,
+        tool_input.as_ref().unwrap_or(&default_tool_input),
+        &mut ToolDbService::new(&mut db),
+    )
+    .await?;
+    let tools = (tool_input.is_some() || !llm_tools.is_empty()).then_some(llm_tools);
+
     // Generate session title if needed, and save user message to database
     if let Some(user_message) = &input.message {
         if messages.is_empty() && session.title == DEFAULT_SESSION_TITLE {
             generate_title(
-                &user_id,
+                &owner_id,
                 &session_id,
                 &user_message,
                 &provider_api,
@@ -148,98 +307,248 @@ pub async fn send_chat_stream(
                 meta: ChatRsMessageMeta::default(),
             })
             .await?;
+        if !attachment_ids.is_empty() {
+            AttachmentDbService::new(&mut db)
+                .attach_to_message(&user_id, &attachment_ids, &new_message.id)
+                .await?;
+        }
+        knowledge.index_message(&owner_id, &new_message);
+        let event = SessionEvent::Message(&new_message);
+        publish_session_event(&streams, &session_id, &event).await;
         messages.push(new_message);
     }
+    load_attachments(&mut db, storage, &mut messages).await?;
 
-    // Update session metadata if needed
-    if let Some(tool_input) = input.tools.take() {
-        if session
-            .meta
-            .tool_config
-            .is_none_or(|config| config != tool_input)
-        {
-            let meta = ChatRsSessionMeta::new(Some(tool_input));
-            let data = UpdateChatRsSession {
-                meta: Some(&meta),
-                ..Default::default()
-            };
-            ChatDbService::new(&mut db)
-                .update_session(&user_id, &session_id, data)
+    // Add the instructions and files of the session's project
+    let project_context = match session.project_id {
+        Some(project_id) => {
+            let project = ProjectDbService::new(&mut db)
+                .find_by_id(&owner_id, &project_id)
                 .await?;
+            load_project_context(&mut db, storage, &project).await?
+        }
+        None => ProjectContext::default(),
+    };
+    add_project_attachments(&mut messages, &project_context.attachments);
+    let system_prompt = match (project_context.system_prompt, system_prompt) {
+        (Some(project_prompt), Some(preset_prompt)) => {
+            Some(format!("{project_prompt}\n\n{preset_prompt}"))
         }
+        (project_prompt, preset_prompt) => project_prompt.or(preset_prompt),
+    };
+
+    // Remember the tools and provider configuration for the session (providers and tools
+    // belong to the user, so only the owner's configuration is remembered)
+    let provider_config = ChatRsSessionProviderConfig {
+        provider_id: requested_provider_id,
+        options: requested_options,
+    };
+    let tool_config_changed = tool_input
+        .as_ref()
+        .is_some_and(|tool_input| session.meta.tool_config.as_ref() != Some(tool_input));
+    let config_changed =
+        tool_config_changed || session.meta.provider_config.as_ref() != Some(&provider_config);
+    if config_changed && *user_id == owner_id {
+        let meta = ChatRsSessionMeta {
+            tool_config: tool_input.or(session.meta.tool_config),
+            provider_config: Some(provider_config),
+            persona_id: session.meta.persona_id,
+        };
+        let data = UpdateChatRsSession {
+            meta: Some(&meta),
+            ..Default::default()
+        };
+        ChatDbService::new(&mut db)
+            .update_session(&owner_id, &session_id, data)
+            .await?;
+    }
+
+    // Add the system prompt of the project and preset
+    if let Some(system_prompt) = system_prompt.clone() {
+        messages.insert(
+            0,
+            ChatRsMessage {
+                id: Uuid::new_v4(),
+                session_id,
+                role: ChatRsMessageRole::System,
+                content: system_prompt,
+                meta: ChatRsMessageMeta::default(),
+                created_at: Utc::now(),
+                pinned: false,
+                content_encrypted: false,
+            },
+        );
     }
 
     // Get the provider's stream response
-    let stream = provider_api
-        .chat_stream(messages, tools, &input.options)
-        .await?;
-    let provider_id = input.provider_id;
-    let provider_options = input.options.clone();
+    let message_indexer = knowledge.clone();
+    let dry_run = input.dry_run.unwrap_or(false);
+    let auto_tools = match input.auto.take().filter(|_| !dry_run) {
+        Some(auto) => Some(AutoToolRunner {
+            user_id: *user_id,
+            session_id,
+            provider_api: dyn_clone::clone_box(&*provider_api),
+            tools: tools.clone(),
+            options: options.clone(),
+            system_prompt,
+            project_attachments: project_context.attachments,
+            http_client: http_client.inner().clone(),
+            encryptor: encryptor.inner().clone(),
+            storage: storage.inner().clone(),
+            knowledge,
+            allow_mcp_stdio: app_config.mcp_stdio.unwrap_or(false),
+            max_iterations: auto.max_iterations,
+        }),
+        None => None,
+    };
 
-    // Create the Redis stream
-    let mut stream_writer = LlmStreamWriter::new(redis_writer, &user_id, &session_id);
-    stream_writer.start().await?;
+    // Take the lease of the stream, so that other server instances don't start a response for
+    // the session. It's released if the stream can't be started.
+    let Some(lease) = acquire_chat_stream(&streams, &owner_id, &session_id).await? else {
+        return Err(LlmError::AlreadyStreaming)?;
+    };
+    let mut stream = match provider_api.chat_stream(messages, tools, &options).await {
+        Ok(stream) => stream,
+        Err(err) => {
+            release_chat_stream(&streams, &lease).await;
+            return Err(err)?;
+        }
+    };
+    let provider_options = options;
+
+    // Create the stream
+    let mut stream_writer = LlmStreamWriter::new(streams.clone(), &owner_id, &session_id)
+        .with_interrupt(active_stream.interrupt_signal())
+        .with_lease(lease.clone());
+    if let Err(err) = stream_writer
+        .start_with_model(provider_id, &provider_options.model)
+        .await
+    {
+        release_chat_stream(&streams, &lease).await;
+        return Err(err)?;
+    }
+    if let Some(warning) = &warning {
+        stream_writer.warning(warning).await?;
+    }
+    let event = SessionEvent::StreamStarted { user_id: *user_id };
+    publish_session_event(&streams, &session_id, &event).await;
 
-    // Spawn a task to stream and save the response
+    // Spawn a task to stream and save the response(s)
     tokio::spawn(async move {
-        let (text, tool_calls, usage, errors, cancelled) = stream_writer.process(stream).await;
-        let assistant_meta = AssistantMeta {
-            provider_id,
-            provider_options: Some(provider_options),
-            tool_calls,
-            usage,
-            errors,
-            partial: cancelled.then_some(true),
-        };
-        let db_result = ChatDbService::new(&mut db)
-            .save_message(NewChatRsMessage {
-                session_id: &session_id,
-                role: ChatRsMessageRole::Assistant,
-                content: &text.unwrap_or_default(),
-                meta: ChatRsMessageMeta::new_assistant(assistant_meta),
-            })
-            .await;
-        if let Err(err) = db_result {
-            rocket::error!("Failed to save assistant message: {}", err);
-        }
-        if !cancelled {
-            stream_writer.end().await.ok();
+        let _active_stream = active_stream; // Tracked until the response is saved
+        let mut iteration = 0;
+        loop {
+            let (text, tool_calls, usage, finish_reason, errors, cancelled) =
+                stream_writer.process(stream).await;
+            let interrupted = stream_writer.interrupted();
+            let (mut tool_calls, planned_tool_calls) = match dry_run {
+                true => (None, tool_calls),
+                false => (tool_calls, None),
+            };
+            if let Some(tool_calls) = tool_calls.as_mut() {
+                if let Err(err) = request_approvals(&mut db, &user_id, tool_calls).await {
+                    rocket::error!("Failed to request tool call approvals: {}", err);
+                }
+            }
+            let mut auto_tool_calls = auto_tools
+                .as_ref()
+                .filter(|_| !cancelled && !interrupted)
+                .filter(|auto_tools| iteration < auto_tools.max_iterations())
+                .and_then(|_| tool_calls.clone());
+            let assistant_meta = AssistantMeta {
+                provider_id,
+                provider_options: Some(provider_options.clone()),
+                tool_calls,
+                planned_tool_calls,
+                usage,
+                finish_reason,
+                errors,
+                partial: (cancelled || interrupted).then_some(true),
+                feedback: None,
+                user_id: Some(*user_id),
+            };
+            let db_result = ChatDbService::new(&mut db)
+                .save_message(NewChatRsMessage {
+                    session_id: &session_id,
+                    role: ChatRsMessageRole::Assistant,
+                    content: &text.unwrap_or_default(),
+                    meta: ChatRsMessageMeta::new_assistant(assistant_meta),
+                })
+                .await;
+            match db_result {
+                Ok(message) => {
+                    if let Err(err) = trigger_message_webhooks(&mut db, &owner_id, &message).await {
+                        rocket::warn!("Failed to trigger webhooks: {}", err);
+                    }
+                    let event = SessionEvent::Message(&message);
+                    publish_session_event(&streams, &session_id, &event).await;
+                    message_indexer.index_message(&owner_id, &message);
+                }
+                Err(err) => {
+                    rocket::error!("Failed to save assistant message: {}", err);
+                    auto_tool_calls = None;
+                }
+            }
+            if cancelled {
+                return;
+            }
+
+            // Auto mode: execute the tool calls and continue with the next response
+            let (Some(auto_tools), Some(tool_calls)) = (&auto_tools, auto_tool_calls) else {
+                break;
+            };
+            match auto_tools
+                .continue_with_tool_calls(&mut db, &mut stream_writer, &tool_calls)
+                .await
+            {
+                Some(next_stream) => stream = next_stream,
+                None => break,
+            }
+            iteration += 1;
         }
+        stream_writer.end().await.ok();
     });
 
     Ok(Json(SendChatResponse {
         message: "Stream started",
         url: format!("/api/chat/{}/stream", session_id),
+        warning,
     }))
 }
 
 /// # Connect to chat stream
-/// Connect to an ongoing chat stream and stream the assistant response
+/// Connect to an ongoing chat stream and stream the assistant response. Events are
+/// described by the `ChatStreamEvent` schema. Reconnecting clients can send the ID of the
+/// last received event in the `Last-Event-ID` header, to resume the stream after that event.
 #[openapi(tag = "Chat")]
 #[get("/<session_id>/stream")]
 pub async fn connect_to_chat_stream(
-    user_id: ChatRsUserId,
-    redis_reader: ExclusiveRedisClient,
+    user_id: ChatRsScopedUserId<ChatRead>,
+    mut db: DbConnection,
+    stream_reader_client: ExclusiveStreamClient,
     session_id: Uuid,
     start_event_id: Option<LastEventId>,
 ) -> Result<EventStream<Pin<Box<dyn Stream<Item = Event> + Send>>>, ApiError> {
-    let stream_reader = SseStreamReader::new(redis_reader);
+    let (session, _) = ChatDbService::new(&mut db)
+        .get_session_with_role(&user_id, &session_id)
+        .await?;
+    let stream_reader = SseStreamReader::new(stream_reader_client.into_inner());
+    let key = get_chat_stream_key(&session.user_id, &session_id);
 
-    // Get all previous events from the Redis stream, and return them if we're already at the end of the stream
+    // Get the previous events from the stream (after the Last-Event-ID if resuming), and
+    // return them if we're already at the end of the stream
     let (prev_events, last_event_id, is_end) = stream_reader
-        .get_prev_events(&user_id, &session_id, start_event_id.as_deref())
+        .get_prev_events(&key, start_event_id.as_deref())
         .await?;
     let prev_events_stream = stream::iter(prev_events);
     if is_end {
         return Ok(EventStream::from(prev_events_stream.boxed()));
     }
 
-    // Spawn a task to receive new events from Redis and add them to this channel
+    // Spawn a task to receive new events from the stream and add them to this channel
     let (tx, rx) = tokio::sync::mpsc::channel::<Event>(50);
     tokio::spawn(async move {
-        stream_reader
-            .stream(&user_id, &session_id, &last_event_id, &tx)
-            .await;
+        stream_reader.stream(&key, &last_event_id, &tx).await;
         drop(tx);
     });
 
@@ -253,13 +562,28 @@ pub async fn connect_to_chat_stream(
 #[openapi(tag = "Chat")]
 #[post("/<session_id>/cancel")]
 pub async fn cancel_chat_stream(
-    user_id: ChatRsUserId,
-    redis: RedisClient,
+    user_id: ChatRsScopedUserId<ChatWrite>,
+    mut db: DbConnection,
+    streams: StreamClient,
     session_id: Uuid,
 ) -> Result<(), ApiError> {
-    if !check_chat_stream_exists(&redis, &user_id, &session_id).await? {
+    let (session, role) = ChatDbService::new(&mut db)
+        .get_session_with_role(&user_id, &session_id)
+        .await?;
+    check_write_role(&role)?;
+    if !check_chat_stream_exists(&streams, &session.user_id, &session_id).await? {
         return Err(LlmError::StreamNotFound)?;
     }
-    cancel_current_chat_stream(&redis, &user_id, &session_id).await?;
+    cancel_current_chat_stream(&streams, &session.user_id, &session_id).await?;
+    Ok(())
+}
+
+/// Check that the user's role in the session allows sending messages
+fn check_write_role(role: &ChatRsSessionRole) -> Result<(), ApiError> {
+    if !role.can_write() {
+        return Err(ApiError::Forbidden(
+            "Read-only members can't send messages to the session".into(),
+        ));
+    }
     Ok(())
 }
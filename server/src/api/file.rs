@@ -0,0 +1,717 @@
+use std::{io::SeekFrom, path::PathBuf};
+
+use chrono::{DateTime, Utc};
+use rocket::{
+    data::{self, Data, FromData, ToByteUnit},
+    delete, get,
+    http::{ContentType, Status},
+    post, put,
+    request::{self, FromRequest},
+    response::{self, Responder},
+    serde::json::Json,
+    Request, Response, Route, State,
+};
+use rocket_okapi::{
+    okapi::openapi3::{
+        MediaType, OpenApi, Parameter, ParameterValue, RefOr, RequestBody,
+        Response as OpenApiResponse, Responses,
+    },
+    openapi, openapi_get_routes_spec,
+    r#gen::OpenApiGenerator,
+    request::{OpenApiFromData, OpenApiFromRequest, RequestHeaderInput},
+    response::OpenApiResponderInner,
+    settings::OpenApiSettings,
+};
+use schemars::schema::{InstanceType, SchemaObject};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::{
+    attachments::save_attachment,
+    auth::{ChatRead, ChatRsScopedUserId, ChatRsUserId, ChatWrite},
+    db::{models::ChatRsAttachment, DbConnection, DbPool},
+    errors::ApiError,
+    extraction::spawn_text_extraction,
+    scan::{FileScanner, ScanAction, ScanResult},
+    storage::{LocalStorage, StorageError, StorageUsage, StoredFileInfo},
+};
+
+/// Maximum size of an uploaded file, in MiB
+const MAX_UPLOAD_SIZE_MIB: u64 = 20;
+/// Cache policy of the stored files: only cached by the browser, and revalidated each time as
+/// files can be replaced
+const CACHE_CONTROL: &str = "private, no-cache";
+/// Headers of all file responses: the files are user content, so browsers shouldn't guess
+/// their type or run their scripts
+const FILE_SECURITY_HEADERS: [(&str, &str); 2] = [
+    ("X-Content-Type-Options", "nosniff"),
+    ("Content-Security-Policy", "sandbox"),
+];
+
+pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
+    openapi_get_routes_spec![
+        settings: upload_file,
+        list_files,
+        get_usage,
+        get_file,
+        put_file,
+        delete_file
+    ]
+}
+
+/// Upload a file to attach to a chat message: an image (PNG, JPEG, GIF, or WebP), a document
+/// (PDF, DOCX, or HTML), or a text file (plain text or Markdown), up to 20 MiB. The file type
+/// is given by the `Content-Type` header. Pass the ID of the uploaded file in the
+/// `attachments` of the next chat message. The text of documents is extracted in the
+/// background, and `extracted_at` is set once it's done. If virus scanning is enabled,
+/// infected files are rejected with a 422 status.
+#[openapi(tag = "Files")]
+#[post("/upload?<name>", data = "<file>")]
+async fn upload_file(
+    user_id: ChatRsScopedUserId<ChatWrite>,
+    mut db: DbConnection,
+    db_pool: &State<DbPool>,
+    storage: &State<LocalStorage>,
+    name: &str,
+    file: FileData,
+) -> Result<Json<ChatRsAttachment>, ApiError> {
+    let attachment = save_attachment(
+        &mut db,
+        storage,
+        &user_id,
+        name,
+        &file.mime_type,
+        &file.data,
+    )
+    .await?;
+    spawn_text_extraction(db_pool.inner().clone(), &attachment, file.data);
+
+    Ok(Json(attachment))
+}
+
+/// List the files and directories in one of the user's directories, given by its `path`
+/// relative to the user's directory (default: the user's directory)
+#[openapi(tag = "Files")]
+#[get("/list?<path>")]
+async fn list_files(
+    user_id: ChatRsScopedUserId<ChatRead>,
+    storage: &State<LocalStorage>,
+    path: Option<&str>,
+) -> Result<Json<Vec<StoredFileInfo>>, ApiError> {
+    let mut storage_path = PathBuf::from(user_id.to_string());
+    if let Some(path) = path {
+        storage_path.push(path.trim_start_matches("./"));
+    }
+    let files = storage.list(&user_id, &storage_path).await?;
+
+    Ok(Json(files))
+}
+
+/// Get the total size of the user's stored files (uploads, and files written by tools), and
+/// their storage quota. Uploads that would exceed the quota are rejected.
+#[openapi(tag = "Files")]
+#[get("/usage")]
+async fn get_usage(
+    user_id: ChatRsScopedUserId<ChatRead>,
+    storage: &State<LocalStorage>,
+) -> Result<Json<StorageUsage>, ApiError> {
+    Ok(Json(storage.usage(&user_id).await?))
+}
+
+/// Get a stored file (e.g. an image generated by a tool) by its storage path. The file is
+/// streamed from the storage. Supports a single byte range in the `Range` header (also with
+/// `If-Range`), and conditional requests with the `ETag` and `Last-Modified` of the file
+/// (`If-None-Match` and `If-Modified-Since`). Only raster images (PNG, JPEG, GIF, and WebP)
+/// are displayed inline, other files are downloaded as attachments.
+#[openapi(tag = "Files")]
+#[get("/<storage_path..>")]
+async fn get_file(
+    user_id: ChatRsScopedUserId<ChatRead>,
+    storage: &State<LocalStorage>,
+    storage_path: PathBuf,
+    range: RangeHeader,
+    conditions: ConditionalHeaders,
+) -> Result<RangedFile, ApiError> {
+    let path = storage.get_path(&user_id, &storage_path).await?;
+    let mut file = tokio::fs::File::open(&path)
+        .await
+        .map_err(StorageError::from)?;
+    let metadata = file.metadata().await.map_err(StorageError::from)?;
+    if !metadata.is_file() {
+        return Err(StorageError::NotFound)?;
+    }
+    let size = metadata.len();
+    let content_type = path
+        .extension()
+        .and_then(|ext| ContentType::from_extension(&ext.to_string_lossy()))
+        .unwrap_or(ContentType::Binary);
+    let validators = FileValidators::new(&metadata);
+    if conditions.is_not_modified(&validators) {
+        return Ok(RangedFile::NotModified { validators });
+    }
+
+    let range_header = range.0.filter(|_| conditions.is_range_valid(&validators));
+    let range = match range_header
+        .as_deref()
+        .map(|header| parse_range(header, size))
+    {
+        Some(Some(Ok(range))) => Some(range),
+        Some(Some(Err(()))) => {
+            return Ok(RangedFile::Unsatisfiable { size });
+        }
+        Some(None) | None => None,
+    };
+    if let Some((start, _)) = range {
+        file.seek(SeekFrom::Start(start))
+            .await
+            .map_err(StorageError::from)?;
+    }
+
+    Ok(RangedFile::File {
+        file,
+        size,
+        range,
+        content_type,
+        validators,
+    })
+}
+
+/// Upload a file to the given storage path, replacing any existing file, as long as the
+/// user's files stay within their storage quota. Files can be up to 20 MiB.
+#[openapi(tag = "Files")]
+#[put("/<storage_path..>", data = "<file>")]
+async fn put_file(
+    user_id: ChatRsUserId,
+    storage: &State<LocalStorage>,
+    storage_path: PathBuf,
+    file: FileData,
+) -> Result<(), ApiError> {
+    storage.write(&user_id, &storage_path, &file.data).await?;
+
+    Ok(())
+}
+
+/// Delete a stored file
+#[openapi(tag = "Files")]
+#[delete("/<storage_path..>")]
+async fn delete_file(
+    user_id: ChatRsUserId,
+    storage: &State<LocalStorage>,
+    storage_path: PathBuf,
+) -> Result<(), ApiError> {
+    storage.delete(&user_id, &storage_path).await?;
+
+    Ok(())
+}
+
+/// Data guard for the raw body of an uploaded file, up to 20 MiB, with the MIME type of the
+/// `Content-Type` header.
+pub struct FileData {
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromData<'r> for FileData {
+    type Error = StorageError;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let mime_type = match req.content_type() {
+            Some(content_type) => format!("{}/{}", content_type.top(), content_type.sub()),
+            None => "application/octet-stream".to_owned(),
+        };
+        let max_size = MAX_UPLOAD_SIZE_MIB.mebibytes();
+        let data = match data.open(max_size).into_bytes().await {
+            Ok(bytes) if bytes.is_complete() => bytes.into_inner(),
+            Ok(_) => {
+                return data::Outcome::Error((
+                    Status::PayloadTooLarge,
+                    StorageError::TooLarge(max_size.as_u64()),
+                ))
+            }
+            Err(err) => return data::Outcome::Error((Status::InternalServerError, err.into())),
+        };
+        if let Some(scanner) = req.rocket().state::<FileScanner>() {
+            if let Err((status, err)) = scan_upload(req, scanner, &mime_type, &data).await {
+                return data::Outcome::Error((status, err));
+            }
+        }
+
+        data::Outcome::Success(FileData { mime_type, data })
+    }
+}
+
+/// Scan the uploaded file, and quarantine it if it's infected and the scanner is configured to
+async fn scan_upload(
+    req: &Request<'_>,
+    scanner: &FileScanner,
+    mime_type: &str,
+    data: &[u8],
+) -> Result<(), (Status, StorageError)> {
+    let signature = match scanner.scan(data).await {
+        Ok(ScanResult::Clean) => return Ok(()),
+        Ok(ScanResult::Infected(signature)) => signature,
+        Err(err) => {
+            rocket::error!("Failed to scan upload: {err}");
+            return Err((Status::ServiceUnavailable, err.into()));
+        }
+    };
+    rocket::warn!("Rejected infected upload ({signature})");
+    if scanner.action == ScanAction::Quarantine {
+        if let Some(storage) = req.rocket().state::<LocalStorage>() {
+            match storage.quarantine(data, mime_type).await {
+                Ok(path) => rocket::warn!("Quarantined infected upload at {}", path.display()),
+                Err(err) => rocket::error!("Failed to quarantine upload: {err}"),
+            }
+        }
+    }
+
+    Err((
+        Status::UnprocessableEntity,
+        StorageError::Infected(signature),
+    ))
+}
+
+/// OpenAPI documentation for the raw file body of the FileData guard.
+impl<'r> OpenApiFromData<'r> for FileData {
+    fn request_body(_gen: &mut OpenApiGenerator) -> rocket_okapi::Result<RequestBody> {
+        let schema = SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            format: Some("binary".to_owned()),
+            ..Default::default()
+        };
+        let mut content = schemars::Map::new();
+        content.insert(
+            "application/octet-stream".to_owned(),
+            MediaType {
+                schema: Some(schema),
+                ..Default::default()
+            },
+        );
+        Ok(RequestBody {
+            description: Some("The file, with its type in the `Content-Type` header".to_owned()),
+            content,
+            required: true,
+            ..Default::default()
+        })
+    }
+}
+
+/// Request guard for the optional `Range` header.
+struct RangeHeader(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RangeHeader {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let range = req
+            .headers()
+            .get_one("Range")
+            .map(|header| header.to_owned());
+        request::Outcome::Success(RangeHeader(range))
+    }
+}
+
+/// OpenAPI documentation for the `Range` header.
+impl<'r> OpenApiFromRequest<'r> for RangeHeader {
+    fn from_request_input(
+        gen: &mut OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> rocket_okapi::Result<RequestHeaderInput> {
+        Ok(RequestHeaderInput::Parameter(Parameter {
+            name: "Range".to_owned(),
+            location: "header".to_owned(),
+            description: Some("A single byte range of the file, e.g. `bytes=0-1023`".to_owned()),
+            required: false,
+            deprecated: false,
+            allow_empty_value: false,
+            value: ParameterValue::Schema {
+                style: None,
+                explode: None,
+                allow_reserved: false,
+                schema: gen.json_schema_no_ref::<String>(),
+                example: None,
+                examples: None,
+            },
+            extensions: Default::default(),
+        }))
+    }
+}
+
+/// Request guard for the headers of conditional requests (`If-None-Match`, `If-Modified-Since`,
+/// and `If-Range`).
+struct ConditionalHeaders {
+    if_none_match: Option<String>,
+    if_modified_since: Option<DateTime<Utc>>,
+    if_range: Option<String>,
+}
+
+impl ConditionalHeaders {
+    /// Whether the cached file of the client is still current. `If-Modified-Since` is ignored
+    /// if `If-None-Match` is set.
+    fn is_not_modified(&self, validators: &FileValidators) -> bool {
+        if let Some(if_none_match) = &self.if_none_match {
+            return if_none_match.split(',').any(|etag| {
+                let etag = etag.trim();
+                etag == "*" || etag.trim_start_matches("W/") == validators.etag
+            });
+        }
+        match (self.if_modified_since, validators.modified_at) {
+            (Some(since), Some(modified_at)) => modified_at.timestamp() <= since.timestamp(),
+            _ => false,
+        }
+    }
+
+    /// Whether the `Range` header should be used: the `If-Range` header, if set, must match
+    /// the `ETag` or the `Last-Modified` date of the file.
+    fn is_range_valid(&self, validators: &FileValidators) -> bool {
+        match &self.if_range {
+            Some(if_range) => {
+                *if_range == validators.etag
+                    || validators.last_modified().as_deref() == Some(if_range.as_str())
+            }
+            None => true,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ConditionalHeaders {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let headers = req.headers();
+        request::Outcome::Success(ConditionalHeaders {
+            if_none_match: headers.get_one("If-None-Match").map(str::to_owned),
+            if_modified_since: headers
+                .get_one("If-Modified-Since")
+                .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+                .map(|date| date.with_timezone(&Utc)),
+            if_range: headers
+                .get_one("If-Range")
+                .map(|value| value.trim().to_owned()),
+        })
+    }
+}
+
+/// OpenAPI documentation for the `If-None-Match` header (`If-Modified-Since` and `If-Range`
+/// are also supported).
+impl<'r> OpenApiFromRequest<'r> for ConditionalHeaders {
+    fn from_request_input(
+        gen: &mut OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> rocket_okapi::Result<RequestHeaderInput> {
+        Ok(RequestHeaderInput::Parameter(Parameter {
+            name: "If-None-Match".to_owned(),
+            location: "header".to_owned(),
+            description: Some(
+                "The `ETag` of the cached file, to get a 304 response if it hasn't changed"
+                    .to_owned(),
+            ),
+            required: false,
+            deprecated: false,
+            allow_empty_value: false,
+            value: ParameterValue::Schema {
+                style: None,
+                explode: None,
+                allow_reserved: false,
+                schema: gen.json_schema_no_ref::<String>(),
+                example: None,
+                examples: None,
+            },
+            extensions: Default::default(),
+        }))
+    }
+}
+
+/// Validators of a stored file for caching, from its size and modification time
+struct FileValidators {
+    etag: String,
+    modified_at: Option<DateTime<Utc>>,
+}
+
+impl FileValidators {
+    fn new(metadata: &std::fs::Metadata) -> Self {
+        let modified_at: Option<DateTime<Utc>> = metadata.modified().ok().map(DateTime::from);
+        let modified_nanos = modified_at
+            .and_then(|date| date.timestamp_nanos_opt())
+            .unwrap_or_default();
+        Self {
+            etag: format!("\"{:x}-{:x}\"", metadata.len(), modified_nanos),
+            modified_at,
+        }
+    }
+
+    /// Modification time, formatted as an HTTP date
+    fn last_modified(&self) -> Option<String> {
+        self.modified_at
+            .map(|date| date.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+    }
+
+    fn add_headers(&self, response: &mut response::Builder<'_>) {
+        response
+            .raw_header("ETag", self.etag.clone())
+            .raw_header("Cache-Control", CACHE_CONTROL);
+        if let Some(last_modified) = self.last_modified() {
+            response.raw_header("Last-Modified", last_modified);
+        }
+    }
+}
+
+/// Parse a `Range` header with a single byte range into the inclusive start and end of the
+/// range. Returns `None` if the header should be ignored (e.g. multiple ranges), and an
+/// error if the range is not satisfiable.
+fn parse_range(header: &str, size: u64) -> Option<Result<(u64, u64), ()>> {
+    let range = header.trim().strip_prefix("bytes=")?;
+    if range.contains(',') {
+        return None;
+    }
+    let (start, end) = range.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            (size.saturating_sub(suffix), size.checked_sub(1)?)
+        }
+        (start, "") => (start.parse().ok()?, size.saturating_sub(1)),
+        (start, end) => {
+            let end: u64 = end.parse().ok()?;
+            (start.parse().ok()?, end.min(size.saturating_sub(1)))
+        }
+    };
+    if start >= size || start > end {
+        return Some(Err(()));
+    }
+
+    Some(Ok((start, end)))
+}
+
+/// Only display raster images inline: other files (e.g. HTML or SVG) could run scripts in the
+/// origin of the app
+fn content_disposition(content_type: &ContentType) -> &'static str {
+    let inline_types = [
+        ContentType::PNG,
+        ContentType::JPEG,
+        ContentType::GIF,
+        ContentType::WEBP,
+    ];
+    match inline_types.contains(content_type) {
+        true => "inline",
+        false => "attachment",
+    }
+}
+
+/// A stored file, or part of it if a byte range was requested
+enum RangedFile {
+    File {
+        file: tokio::fs::File,
+        /// Full size of the file, in bytes
+        size: u64,
+        /// Inclusive start and end of the requested range
+        range: Option<(u64, u64)>,
+        content_type: ContentType,
+        validators: FileValidators,
+    },
+    /// The file hasn't changed since the client cached it
+    NotModified { validators: FileValidators },
+    /// The requested range is outside of the file
+    Unsatisfiable { size: u64 },
+}
+
+impl<'r> Responder<'r, 'static> for RangedFile {
+    fn respond_to(self, _req: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build();
+        response.raw_header("Accept-Ranges", "bytes");
+        for (name, value) in FILE_SECURITY_HEADERS {
+            response.raw_header(name, value);
+        }
+        match self {
+            RangedFile::File {
+                file,
+                size,
+                range: Some((start, end)),
+                content_type,
+                validators,
+            } => {
+                let length = end - start + 1;
+                validators.add_headers(&mut response);
+                response
+                    .status(Status::PartialContent)
+                    .raw_header("Content-Disposition", content_disposition(&content_type))
+                    .header(content_type)
+                    .raw_header("Content-Range", format!("bytes {start}-{end}/{size}"))
+                    .raw_header("Content-Length", length.to_string())
+                    .streamed_body(file.take(length));
+            }
+            RangedFile::File {
+                file,
+                size,
+                content_type,
+                validators,
+                ..
+            } => {
+                validators.add_headers(&mut response);
+                response
+                    .raw_header("Content-Disposition", content_disposition(&content_type))
+                    .header(content_type)
+                    .sized_body(Some(size as usize), file);
+            }
+            RangedFile::NotModified { validators } => {
+                validators.add_headers(&mut response);
+                response.status(Status::NotModified);
+            }
+            RangedFile::Unsatisfiable { size } => {
+                response
+                    .status(Status::RangeNotSatisfiable)
+                    .raw_header("Content-Range", format!("bytes */{size}"));
+            }
+        }
+
+        response.ok()
+    }
+}
+
+/// OpenAPI documentation for the file responses.
+impl OpenApiResponderInner for RangedFile {
+    fn responses(_gen: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        let mut responses = schemars::Map::new();
+        let response_data = vec![
+            ("200", "The file"),
+            ("206", "The requested byte range of the file"),
+            ("304", "The file hasn't changed since it was cached"),
+            ("416", "The requested byte range is outside of the file"),
+        ];
+        for (status, description) in response_data {
+            responses.insert(
+                status.to_string(),
+                RefOr::Object(OpenApiResponse {
+                    description: description.to_string(),
+                    ..Default::default()
+                }),
+            );
+        }
+        Ok(Responses {
+            responses,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_content_disposition() {
+        for ext in ["png", "jpg", "jpeg", "gif", "webp"] {
+            let content_type = ContentType::from_extension(ext).unwrap();
+            assert_eq!(content_disposition(&content_type), "inline", "{ext}");
+        }
+        for ext in ["svg", "html", "htm", "xml", "pdf", "txt", "js"] {
+            let content_type = ContentType::from_extension(ext).unwrap();
+            assert_eq!(content_disposition(&content_type), "attachment", "{ext}");
+        }
+        assert_eq!(content_disposition(&ContentType::Binary), "attachment");
+    }
+
+    fn validators() -> FileValidators {
+        FileValidators {
+            etag: "\"a-1\"".to_owned(),
+            modified_at: Some(Utc.with_ymd_and_hms(2025, 1, 2, 3, 4, 5).unwrap()),
+        }
+    }
+
+    fn conditions(
+        if_none_match: Option<&str>,
+        if_modified_since: Option<DateTime<Utc>>,
+        if_range: Option<&str>,
+    ) -> ConditionalHeaders {
+        ConditionalHeaders {
+            if_none_match: if_none_match.map(str::to_owned),
+            if_modified_since,
+            if_range: if_range.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let validators = validators();
+        for if_none_match in ["\"a-1\"", "W/\"a-1\"", "*", "\"b-2\", \"a-1\""] {
+            let conditions = conditions(Some(if_none_match), None, None);
+            assert!(conditions.is_not_modified(&validators), "{if_none_match}");
+        }
+        for if_none_match in ["\"b-2\"", "\"b-2\", W/\"c-3\"", "a-1", ""] {
+            let conditions = conditions(Some(if_none_match), None, None);
+            assert!(!conditions.is_not_modified(&validators), "{if_none_match}");
+        }
+    }
+
+    #[test]
+    fn test_if_modified_since() {
+        let validators = validators();
+        let modified_at = validators.modified_at.unwrap();
+        let not_modified = [modified_at, modified_at + chrono::Duration::hours(1)];
+        for since in not_modified {
+            assert!(conditions(None, Some(since), None).is_not_modified(&validators));
+        }
+        let modified = modified_at - chrono::Duration::seconds(1);
+        assert!(!conditions(None, Some(modified), None).is_not_modified(&validators));
+        assert!(!conditions(None, None, None).is_not_modified(&validators));
+
+        // Ignored if `If-None-Match` is set
+        let conditions = conditions(Some("\"b-2\""), Some(modified_at), None);
+        assert!(!conditions.is_not_modified(&validators));
+    }
+
+    #[test]
+    fn test_if_range() {
+        let validators = validators();
+        for if_range in ["\"a-1\"", "Thu, 02 Jan 2025 03:04:05 GMT"] {
+            assert!(conditions(None, None, Some(if_range)).is_range_valid(&validators));
+        }
+        for if_range in ["W/\"a-1\"", "\"b-2\"", "Thu, 02 Jan 2025 03:04:06 GMT", "*"] {
+            assert!(!conditions(None, None, Some(if_range)).is_range_valid(&validators));
+        }
+        assert!(conditions(None, None, None).is_range_valid(&validators));
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-4", 10), Some(Ok((0, 4))));
+        assert_eq!(parse_range("bytes=2-100", 10), Some(Ok((2, 9))));
+        assert_eq!(parse_range(" bytes=9-9 ", 10), Some(Ok((9, 9))));
+        // Open ranges
+        assert_eq!(parse_range("bytes=5-", 10), Some(Ok((5, 9))));
+        assert_eq!(parse_range("bytes=0-", 1), Some(Ok((0, 0))));
+        // Suffix ranges
+        assert_eq!(parse_range("bytes=-3", 10), Some(Ok((7, 9))));
+        assert_eq!(parse_range("bytes=-20", 10), Some(Ok((0, 9))));
+    }
+
+    #[test]
+    fn test_parse_unsatisfiable_range() {
+        assert_eq!(parse_range("bytes=10-", 10), Some(Err(())));
+        assert_eq!(parse_range("bytes=10-20", 10), Some(Err(())));
+        assert_eq!(parse_range("bytes=5-2", 10), Some(Err(())));
+        assert_eq!(parse_range("bytes=-0", 10), Some(Err(())));
+        assert_eq!(parse_range("bytes=0-", 0), Some(Err(())));
+    }
+
+    #[test]
+    fn test_parse_ignored_range() {
+        for header in [
+            "bytes=0-1,3-4",
+            "items=0-4",
+            "bytes=a-4",
+            "bytes=0-b",
+            "bytes=4",
+            "bytes=-",
+        ] {
+            assert_eq!(parse_range(header, 10), None, "{header}");
+        }
+        assert_eq!(parse_range("bytes=-5", 0), None);
+    }
+}
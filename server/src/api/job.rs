@@ -0,0 +1,102 @@
+use rocket::{delete, get, post, serde::json::Json, Route, State};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+use uuid::Uuid;
+
+use crate::{
+    auth::ChatRsUserId,
+    db::{
+        models::{ChatRsJob, ChatRsJobData},
+        services::JobDbService,
+        DbConnection,
+    },
+    errors::ApiError,
+    jobs::{build_batch_provider, submit_job},
+    redis::RedisClient,
+    utils::Encryptor,
+};
+
+pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
+    openapi_get_routes_spec![settings: get_all_jobs, get_job, create_job, delete_job]
+}
+
+/// # List jobs
+/// List all background jobs
+#[openapi(tag = "Jobs")]
+#[get("/")]
+async fn get_all_jobs(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+) -> Result<Json<Vec<ChatRsJob>>, ApiError> {
+    let jobs = JobDbService::new(&mut db).find_by_user_id(&user_id).await?;
+
+    Ok(Json(jobs))
+}
+
+/// # Get job
+/// Get a background job and its results (once completed)
+#[openapi(tag = "Jobs")]
+#[get("/<job_id>")]
+async fn get_job(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    job_id: Uuid,
+) -> Result<Json<ChatRsJob>, ApiError> {
+    let job = JobDbService::new(&mut db)
+        .find_by_id(&user_id, &job_id)
+        .await?;
+
+    Ok(Json(job))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct JobCreateInput {
+    /// The ID of the provider to submit the job to (must be an Anthropic provider)
+    provider_id: i32,
+    /// The job to run
+    #[serde(flatten)]
+    data: ChatRsJobData,
+}
+
+/// # Create job
+/// Submit a long, non-interactive job (e.g. summarizing many sessions) to the provider's
+/// batch API. Results are checked for periodically, and saved to the job once available.
+#[openapi(tag = "Jobs")]
+#[post("/", data = "<input>")]
+async fn create_job(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    redis: RedisClient,
+    encryptor: &State<Encryptor>,
+    http_client: &State<reqwest::Client>,
+    input: Json<JobCreateInput>,
+) -> Result<Json<ChatRsJob>, ApiError> {
+    let provider = build_batch_provider(
+        &mut db,
+        encryptor,
+        http_client,
+        &redis,
+        &user_id,
+        input.provider_id,
+    )
+    .await?;
+    let job = submit_job(&mut db, &provider, &user_id, input.provider_id, &input.data).await?;
+
+    Ok(Json(job))
+}
+
+/// # Delete job
+/// Delete a background job and its results
+#[openapi(tag = "Jobs")]
+#[delete("/<job_id>")]
+async fn delete_job(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    job_id: Uuid,
+) -> Result<String, ApiError> {
+    let id = JobDbService::new(&mut db).delete(&user_id, &job_id).await?;
+
+    Ok(id.to_string())
+}
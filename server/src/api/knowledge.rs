@@ -0,0 +1,279 @@
+use rocket::{delete, get, patch, post, serde::json::Json, Route};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+use uuid::Uuid;
+
+use crate::{
+    auth::ChatRsUserId,
+    db::{
+        models::{
+            ChatRsKnowledgeBase, ChatRsKnowledgeDocument, ChatRsKnowledgeSearchResult,
+            NewChatRsKnowledgeBase, UpdateChatRsKnowledgeBase,
+        },
+        services::{AttachmentDbService, KnowledgeDbService, ProviderDbService},
+        DbConnection,
+    },
+    errors::ApiError,
+    extraction::is_extractable,
+    knowledge::{KnowledgeError, KnowledgeService},
+    storage::StorageError,
+};
+
+/// Default number of chunks returned by a search
+const DEFAULT_SEARCH_LIMIT: u8 = 5;
+/// Upper limit for the number of chunks returned by a search
+const SEARCH_LIMIT_MAX: u8 = 50;
+
+pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
+    openapi_get_routes_spec![
+        settings: get_all_knowledge_bases,
+        create_knowledge_base,
+        update_knowledge_base,
+        delete_knowledge_base,
+        get_documents,
+        add_document,
+        add_file_document,
+        delete_document,
+        search_knowledge_base
+    ]
+}
+
+/// # List knowledge bases
+/// List all of the user's knowledge bases
+#[openapi(tag = "Knowledge Bases")]
+#[get("/")]
+async fn get_all_knowledge_bases(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+) -> Result<Json<Vec<ChatRsKnowledgeBase>>, ApiError> {
+    let knowledge_bases = KnowledgeDbService::new(&mut db)
+        .find_by_user_id(&user_id)
+        .await?;
+
+    Ok(Json(knowledge_bases))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct KnowledgeBaseCreateInput {
+    name: String,
+    /// Description of the contents, shown to the LLM when searching
+    description: Option<String>,
+    /// The ID of the provider used to generate embeddings (OpenAI-compatible or Ollama)
+    provider_id: i32,
+    /// The embedding model (e.g. `text-embedding-3-small`, `nomic-embed-text`). Can't be
+    /// changed after documents are added.
+    embedding_model: String,
+}
+
+/// # Create knowledge base
+/// Create a knowledge base of documents that can be searched by the knowledge search tool
+#[openapi(tag = "Knowledge Bases")]
+#[post("/", data = "<input>")]
+async fn create_knowledge_base(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    input: Json<KnowledgeBaseCreateInput>,
+) -> Result<Json<ChatRsKnowledgeBase>, ApiError> {
+    // Check that the provider exists
+    ProviderDbService::new(&mut db)
+        .get_accessible_by_id(&user_id, input.provider_id)
+        .await?;
+    let knowledge_base = KnowledgeDbService::new(&mut db)
+        .create(NewChatRsKnowledgeBase {
+            user_id: &user_id,
+            provider_id: input.provider_id,
+            name: &input.name,
+            description: input.description.as_deref(),
+            embedding_model: &input.embedding_model,
+        })
+        .await?;
+
+    Ok(Json(knowledge_base))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct KnowledgeBaseUpdateInput {
+    name: Option<String>,
+    /// Description of the contents, shown to the LLM when searching
+    description: Option<String>,
+}
+
+/// # Update knowledge base
+/// Update the name and description of a knowledge base
+#[openapi(tag = "Knowledge Bases")]
+#[patch("/<knowledge_base_id>", data = "<input>")]
+async fn update_knowledge_base(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    knowledge_base_id: Uuid,
+    input: Json<KnowledgeBaseUpdateInput>,
+) -> Result<Json<ChatRsKnowledgeBase>, ApiError> {
+    let knowledge_base = KnowledgeDbService::new(&mut db)
+        .update(
+            &user_id,
+            &knowledge_base_id,
+            UpdateChatRsKnowledgeBase {
+                name: input.name.as_deref(),
+                description: input.description.as_deref().map(Some),
+            },
+        )
+        .await?;
+
+    Ok(Json(knowledge_base))
+}
+
+/// # Delete knowledge base
+/// Delete a knowledge base and all of its documents
+#[openapi(tag = "Knowledge Bases")]
+#[delete("/<knowledge_base_id>")]
+async fn delete_knowledge_base(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    knowledge_base_id: Uuid,
+) -> Result<String, ApiError> {
+    let id = KnowledgeDbService::new(&mut db)
+        .delete(&user_id, &knowledge_base_id)
+        .await?;
+
+    Ok(id.to_string())
+}
+
+/// # List documents
+/// List the documents of a knowledge base
+#[openapi(tag = "Knowledge Bases")]
+#[get("/<knowledge_base_id>/documents")]
+async fn get_documents(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    knowledge_base_id: Uuid,
+) -> Result<Json<Vec<ChatRsKnowledgeDocument>>, ApiError> {
+    let mut knowledge_db_service = KnowledgeDbService::new(&mut db);
+    let knowledge_base = knowledge_db_service
+        .find_by_id(&user_id, &knowledge_base_id)
+        .await?
+        .ok_or(KnowledgeError::NotFound)?;
+    let documents = knowledge_db_service
+        .list_documents(&knowledge_base.id)
+        .await?;
+
+    Ok(Json(documents))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct DocumentInput {
+    /// Name of the document (e.g. the file name)
+    name: String,
+    /// Text content of the document (plain text or Markdown)
+    content: String,
+}
+
+/// # Add document
+/// Add a text document to a knowledge base. The document is split into chunks, which are
+/// embedded by the knowledge base's provider.
+#[openapi(tag = "Knowledge Bases")]
+#[post("/<knowledge_base_id>/documents", data = "<input>")]
+async fn add_document(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    knowledge: KnowledgeService,
+    knowledge_base_id: Uuid,
+    input: Json<DocumentInput>,
+) -> Result<Json<ChatRsKnowledgeDocument>, ApiError> {
+    let knowledge_base = KnowledgeDbService::new(&mut db)
+        .find_by_id(&user_id, &knowledge_base_id)
+        .await?
+        .ok_or(KnowledgeError::NotFound)?;
+    drop(db);
+    let document = knowledge
+        .add_document(&user_id, &knowledge_base, &input.name, &input.content)
+        .await?;
+
+    Ok(Json(document))
+}
+
+/// # Add uploaded document
+/// Add the text extracted from an uploaded document (PDF, DOCX, or HTML) to a knowledge base.
+/// The text is extracted in the background after the upload.
+#[openapi(tag = "Knowledge Bases")]
+#[post("/<knowledge_base_id>/documents/file/<attachment_id>")]
+async fn add_file_document(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    knowledge: KnowledgeService,
+    knowledge_base_id: Uuid,
+    attachment_id: Uuid,
+) -> Result<Json<ChatRsKnowledgeDocument>, ApiError> {
+    let knowledge_base = KnowledgeDbService::new(&mut db)
+        .find_by_id(&user_id, &knowledge_base_id)
+        .await?
+        .ok_or(KnowledgeError::NotFound)?;
+    let attachment = AttachmentDbService::new(&mut db)
+        .find_by_id(&user_id, &attachment_id)
+        .await?
+        .ok_or(StorageError::NotFound)?;
+    drop(db);
+    if !is_extractable(&attachment.mime_type) {
+        return Err(StorageError::UnsupportedType(attachment.mime_type))?;
+    }
+    if attachment.extracted_at.is_none() {
+        return Err(KnowledgeError::TextNotExtracted)?;
+    }
+    let content = attachment.extracted_text.unwrap_or_default();
+    let document = knowledge
+        .add_document(&user_id, &knowledge_base, &attachment.name, &content)
+        .await?;
+
+    Ok(Json(document))
+}
+
+/// # Delete document
+/// Delete a document from a knowledge base
+#[openapi(tag = "Knowledge Bases")]
+#[delete("/<knowledge_base_id>/documents/<document_id>")]
+async fn delete_document(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    knowledge_base_id: Uuid,
+    document_id: Uuid,
+) -> Result<String, ApiError> {
+    let mut knowledge_db_service = KnowledgeDbService::new(&mut db);
+    let knowledge_base = knowledge_db_service
+        .find_by_id(&user_id, &knowledge_base_id)
+        .await?
+        .ok_or(KnowledgeError::NotFound)?;
+    let id = knowledge_db_service
+        .delete_document(&knowledge_base.id, &document_id)
+        .await?;
+
+    Ok(id.to_string())
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct SearchInput {
+    query: String,
+    /// Max number of chunks to return (default: 5, max: 50)
+    limit: Option<u8>,
+}
+
+/// # Search knowledge base
+/// Find the document chunks most similar to the query
+#[openapi(tag = "Knowledge Bases")]
+#[post("/<knowledge_base_id>/search", data = "<input>")]
+async fn search_knowledge_base(
+    user_id: ChatRsUserId,
+    knowledge: KnowledgeService,
+    knowledge_base_id: Uuid,
+    input: Json<SearchInput>,
+) -> Result<Json<Vec<ChatRsKnowledgeSearchResult>>, ApiError> {
+    let limit = input
+        .limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .clamp(1, SEARCH_LIMIT_MAX);
+    let results = knowledge
+        .search(&user_id, &knowledge_base_id, &input.query, limit)
+        .await?;
+
+    Ok(Json(results))
+}
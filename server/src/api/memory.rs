@@ -0,0 +1,68 @@
+use rocket::{delete, get, serde::json::Json, Route};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use uuid::Uuid;
+
+use crate::{
+    auth::ChatRsUserId,
+    db::{models::ChatRsMemory, services::MemoryDbService, DbConnection},
+    errors::ApiError,
+};
+
+/// Default number of memories returned when listing
+const DEFAULT_LIMIT: i64 = 100;
+/// Upper limit for the number of memories returned when listing
+const LIMIT_MAX: i64 = 1000;
+
+pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
+    openapi_get_routes_spec![settings: get_memories, delete_memory, delete_all_memories]
+}
+
+/// # List memories
+/// List the memories saved by the memory tool, newest first
+#[openapi(tag = "Memories")]
+#[get("/?<limit>")]
+async fn get_memories(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    limit: Option<i64>,
+) -> Result<Json<Vec<ChatRsMemory>>, ApiError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, LIMIT_MAX);
+    let memories = MemoryDbService::new(&mut db)
+        .find_by_user_id(&user_id, limit)
+        .await?;
+
+    Ok(Json(memories))
+}
+
+/// # Delete memory
+/// Delete a saved memory
+#[openapi(tag = "Memories")]
+#[delete("/<memory_id>")]
+async fn delete_memory(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    memory_id: Uuid,
+) -> Result<String, ApiError> {
+    let id = MemoryDbService::new(&mut db)
+        .delete(&user_id, &memory_id)
+        .await?;
+
+    Ok(id.to_string())
+}
+
+/// # Delete all memories
+/// Delete all saved memories. Returns the number of deleted memories.
+#[openapi(tag = "Memories")]
+#[delete("/")]
+async fn delete_all_memories(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+) -> Result<String, ApiError> {
+    let ids = MemoryDbService::new(&mut db)
+        .delete_by_user(&user_id)
+        .await?;
+
+    Ok(ids.len().to_string())
+}
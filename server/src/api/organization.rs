@@ -0,0 +1,494 @@
+use diesel::OptionalExtension;
+use rocket::{delete, get, patch, post, put, serde::json::Json, Route};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+use uuid::Uuid;
+
+use crate::{
+    auth::ChatRsUserId,
+    db::{
+        models::{
+            ChatRsOrganization, ChatRsOrganizationMemberInfo, ChatRsOrganizationRole,
+            NewChatRsOrganization, NewChatRsOrganizationMember, UpdateChatRsOrganization,
+        },
+        services::{
+            OrganizationDbService, PersonaDbService, ProviderDbService, ToolDbService,
+            UserDbService,
+        },
+        DbConnection,
+    },
+    errors::ApiError,
+};
+
+pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
+    openapi_get_routes_spec![
+        settings: get_all_organizations,
+        create_organization,
+        update_organization,
+        delete_organization,
+        get_organization_members,
+        add_organization_member,
+        update_organization_member,
+        remove_organization_member,
+        share_provider,
+        unshare_provider,
+        share_tool,
+        unshare_tool,
+        share_persona,
+        unshare_persona
+    ]
+}
+
+/// Get the user's role in the organization. Returns a not found error if the user isn't a
+/// member, so that other organizations aren't revealed.
+pub async fn get_member_role(
+    db: &mut DbConnection,
+    organization_id: &Uuid,
+    user_id: &Uuid,
+) -> Result<ChatRsOrganizationRole, ApiError> {
+    let role = OrganizationDbService::new(db)
+        .find_role(organization_id, user_id)
+        .await?
+        .ok_or(diesel::result::Error::NotFound)?;
+
+    Ok(role)
+}
+
+/// Get the user's role in the organization, and check that they can manage it
+async fn get_manager_role(
+    db: &mut DbConnection,
+    organization_id: &Uuid,
+    user_id: &Uuid,
+) -> Result<ChatRsOrganizationRole, ApiError> {
+    let role = get_member_role(db, organization_id, user_id).await?;
+    if !role.can_manage() {
+        return Err(ApiError::Forbidden(
+            "Only the owner and admins can manage the organization".into(),
+        ));
+    }
+
+    Ok(role)
+}
+
+#[derive(JsonSchema, serde::Serialize)]
+struct OrganizationResponse {
+    #[serde(flatten)]
+    organization: ChatRsOrganization,
+    /// Role of the current user in the organization
+    role: ChatRsOrganizationRole,
+}
+
+/// # List organizations
+/// List the organizations that the user is a member of
+#[openapi(tag = "Organizations")]
+#[get("/")]
+async fn get_all_organizations(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+) -> Result<Json<Vec<OrganizationResponse>>, ApiError> {
+    let organizations = OrganizationDbService::new(&mut db)
+        .find_by_user(&user_id)
+        .await?
+        .into_iter()
+        .map(|(organization, role)| OrganizationResponse {
+            organization,
+            role: role.as_str().into(),
+        })
+        .collect();
+
+    Ok(Json(organizations))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct OrganizationInput {
+    /// Name of the organization
+    name: String,
+}
+
+/// # Create organization
+/// Create an organization, with the current user as its owner. Members of an organization
+/// can use the providers, tools, and personas shared with it.
+#[openapi(tag = "Organizations")]
+#[post("/", data = "<input>")]
+async fn create_organization(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    input: Json<OrganizationInput>,
+) -> Result<Json<OrganizationResponse>, ApiError> {
+    let organization = OrganizationDbService::new(&mut db)
+        .create(&user_id, NewChatRsOrganization { name: &input.name })
+        .await?;
+
+    Ok(Json(OrganizationResponse {
+        organization,
+        role: ChatRsOrganizationRole::Owner,
+    }))
+}
+
+/// # Update organization
+/// Rename an organization. Only the owner and admins can update the organization.
+#[openapi(tag = "Organizations")]
+#[patch("/<organization_id>", data = "<input>")]
+async fn update_organization(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    organization_id: Uuid,
+    input: Json<OrganizationInput>,
+) -> Result<Json<OrganizationResponse>, ApiError> {
+    let role = get_manager_role(&mut db, &organization_id, &user_id).await?;
+    let organization = OrganizationDbService::new(&mut db)
+        .update(
+            &organization_id,
+            UpdateChatRsOrganization {
+                name: Some(&input.name),
+            },
+        )
+        .await?;
+
+    Ok(Json(OrganizationResponse { organization, role }))
+}
+
+/// # Delete organization
+/// Delete an organization. The shared providers, tools, and personas are kept by their
+/// owners. Only the owner can delete the organization.
+#[openapi(tag = "Organizations")]
+#[delete("/<organization_id>")]
+async fn delete_organization(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    organization_id: Uuid,
+) -> Result<String, ApiError> {
+    let role = get_member_role(&mut db, &organization_id, &user_id).await?;
+    if role != ChatRsOrganizationRole::Owner {
+        return Err(ApiError::Forbidden(
+            "Only the owner can delete the organization".into(),
+        ));
+    }
+    let id = OrganizationDbService::new(&mut db)
+        .delete(&organization_id)
+        .await?;
+
+    Ok(id.to_string())
+}
+
+/// # List organization members
+/// List the members of an organization
+#[openapi(tag = "Organizations")]
+#[get("/<organization_id>/members")]
+async fn get_organization_members(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    organization_id: Uuid,
+) -> Result<Json<Vec<ChatRsOrganizationMemberInfo>>, ApiError> {
+    get_member_role(&mut db, &organization_id, &user_id).await?;
+    let members = OrganizationDbService::new(&mut db)
+        .find_members(&organization_id)
+        .await?;
+
+    Ok(Json(members))
+}
+
+/// Role of an added member of an organization
+#[derive(JsonSchema, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum OrganizationMemberRoleInput {
+    /// Can manage the organization's members, and share resources with the organization
+    Admin,
+    /// Can use the resources shared with the organization
+    Member,
+}
+impl From<&OrganizationMemberRoleInput> for &str {
+    fn from(value: &OrganizationMemberRoleInput) -> Self {
+        match value {
+            OrganizationMemberRoleInput::Admin => (&ChatRsOrganizationRole::Admin).into(),
+            OrganizationMemberRoleInput::Member => (&ChatRsOrganizationRole::Member).into(),
+        }
+    }
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct OrganizationMemberInput {
+    /// ID of the user to add
+    user_id: Uuid,
+    /// Role of the user in the organization
+    role: OrganizationMemberRoleInput,
+}
+
+/// Check that the member isn't the owner of the organization, whose role can't be changed
+async fn check_not_owner(
+    db: &mut DbConnection,
+    organization_id: &Uuid,
+    member_id: &Uuid,
+) -> Result<(), ApiError> {
+    let role = OrganizationDbService::new(db)
+        .find_role(organization_id, member_id)
+        .await?;
+    if role == Some(ChatRsOrganizationRole::Owner) {
+        return Err(ApiError::Forbidden(
+            "The owner's role can't be changed".into(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// # Add organization member
+/// Add a user to an organization, or change their role if they're already a member. Only the
+/// owner and admins can add members.
+#[openapi(tag = "Organizations")]
+#[post("/<organization_id>/members", data = "<input>")]
+async fn add_organization_member(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    organization_id: Uuid,
+    input: Json<OrganizationMemberInput>,
+) -> Result<(), ApiError> {
+    get_manager_role(&mut db, &organization_id, &user_id).await?;
+    check_not_owner(&mut db, &organization_id, &input.user_id).await?;
+    UserDbService::new(&mut db)
+        .find_by_id(&input.user_id)
+        .await?
+        .ok_or(diesel::result::Error::NotFound)?;
+    OrganizationDbService::new(&mut db)
+        .upsert_member(NewChatRsOrganizationMember {
+            organization_id: &organization_id,
+            user_id: &input.user_id,
+            role: (&input.role).into(),
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct OrganizationMemberUpdateInput {
+    /// Role of the user in the organization
+    role: OrganizationMemberRoleInput,
+}
+
+/// # Update organization member
+/// Change the role of a member of an organization. Only the owner and admins can change the
+/// roles of members.
+#[openapi(tag = "Organizations")]
+#[patch("/<organization_id>/members/<member_id>", data = "<input>")]
+async fn update_organization_member(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    organization_id: Uuid,
+    member_id: Uuid,
+    input: Json<OrganizationMemberUpdateInput>,
+) -> Result<(), ApiError> {
+    get_manager_role(&mut db, &organization_id, &user_id).await?;
+    check_not_owner(&mut db, &organization_id, &member_id).await?;
+    OrganizationDbService::new(&mut db)
+        .update_member_role(&organization_id, &member_id, (&input.role).into())
+        .await?;
+
+    Ok(())
+}
+
+/// # Remove organization member
+/// Remove a member from an organization, and stop sharing their resources with it. The owner
+/// and admins can remove any member except the owner, and members can remove themselves to
+/// leave the organization.
+#[openapi(tag = "Organizations")]
+#[delete("/<organization_id>/members/<member_id>")]
+async fn remove_organization_member(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    organization_id: Uuid,
+    member_id: Uuid,
+) -> Result<(), ApiError> {
+    let role = get_member_role(&mut db, &organization_id, &user_id).await?;
+    if !role.can_manage() && member_id != *user_id {
+        return Err(ApiError::Forbidden(
+            "Only the owner and admins can remove other members".into(),
+        ));
+    }
+    if OrganizationDbService::new(&mut db)
+        .find_role(&organization_id, &member_id)
+        .await?
+        == Some(ChatRsOrganizationRole::Owner)
+    {
+        return Err(ApiError::Forbidden(
+            "The owner can't be removed from the organization".into(),
+        ));
+    }
+    OrganizationDbService::new(&mut db)
+        .delete_member(&organization_id, &member_id)
+        .await?;
+
+    Ok(())
+}
+
+/// # Share provider
+/// Share one of your providers (and its API key) with an organization, so that all members
+/// can chat with it. Only the owner and admins of the organization can share resources.
+#[openapi(tag = "Organizations")]
+#[put("/<organization_id>/providers/<provider_id>")]
+async fn share_provider(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    organization_id: Uuid,
+    provider_id: i32,
+) -> Result<(), ApiError> {
+    get_manager_role(&mut db, &organization_id, &user_id).await?;
+    ProviderDbService::new(&mut db)
+        .share_with_organization(&user_id, provider_id, &organization_id)
+        .await?;
+
+    Ok(())
+}
+
+/// # Unshare provider
+/// Stop sharing a provider with an organization. The owner of the provider and the admins of
+/// the organization can unshare it.
+#[openapi(tag = "Organizations")]
+#[delete("/<organization_id>/providers/<provider_id>")]
+async fn unshare_provider(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    organization_id: Uuid,
+    provider_id: i32,
+) -> Result<(), ApiError> {
+    let role = get_member_role(&mut db, &organization_id, &user_id).await?;
+    if !role.can_manage() {
+        // Check that the user owns the provider
+        ProviderDbService::new(&mut db)
+            .get_by_id(&user_id, provider_id)
+            .await?;
+    }
+    ProviderDbService::new(&mut db)
+        .unshare_from_organization(&organization_id, provider_id)
+        .await?;
+
+    Ok(())
+}
+
+/// # Share tool
+/// Share one of your external API or MCP tools (and its secrets) with an organization, so that
+/// all members can use it. System tools can't be shared. Only the owner and admins of the
+/// organization can share resources.
+#[openapi(tag = "Organizations")]
+#[put("/<organization_id>/tools/<tool_id>")]
+async fn share_tool(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    organization_id: Uuid,
+    tool_id: Uuid,
+) -> Result<(), ApiError> {
+    get_manager_role(&mut db, &organization_id, &user_id).await?;
+    let mut tool_db_service = ToolDbService::new(&mut db);
+    let external_api_tool = tool_db_service
+        .share_external_api_tool_with_organization(&user_id, &tool_id, &organization_id)
+        .await
+        .optional()?;
+    if external_api_tool.is_none() {
+        tool_db_service
+            .share_mcp_tool_with_organization(&user_id, &tool_id, &organization_id)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// # Unshare tool
+/// Stop sharing a tool with an organization. The owner of the tool and the admins of the
+/// organization can unshare it.
+#[openapi(tag = "Organizations")]
+#[delete("/<organization_id>/tools/<tool_id>")]
+async fn unshare_tool(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    organization_id: Uuid,
+    tool_id: Uuid,
+) -> Result<(), ApiError> {
+    let role = get_member_role(&mut db, &organization_id, &user_id).await?;
+    let mut tool_db_service = ToolDbService::new(&mut db);
+    if !role.can_manage() {
+        // Check that the user owns the tool
+        let external_api_tool = tool_db_service
+            .find_external_api_tool_by_id(&user_id, &tool_id)
+            .await?;
+        if external_api_tool.is_none() {
+            tool_db_service
+                .find_mcp_tool_by_id(&user_id, &tool_id)
+                .await?
+                .ok_or(diesel::result::Error::NotFound)?;
+        }
+    }
+    let external_api_tool = tool_db_service
+        .unshare_external_api_tool_from_organization(&organization_id, &tool_id)
+        .await
+        .optional()?;
+    if external_api_tool.is_none() {
+        tool_db_service
+            .unshare_mcp_tool_from_organization(&organization_id, &tool_id)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// # Share persona
+/// Share one of your personas with an organization. The persona's provider must already be
+/// shared with the organization. Only the owner and admins of the organization can share
+/// resources.
+#[openapi(tag = "Organizations")]
+#[put("/<organization_id>/personas/<persona_id>")]
+async fn share_persona(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    organization_id: Uuid,
+    persona_id: Uuid,
+) -> Result<(), ApiError> {
+    get_manager_role(&mut db, &organization_id, &user_id).await?;
+    let persona = PersonaDbService::new(&mut db)
+        .find_by_id(&user_id, &persona_id)
+        .await?;
+    if let Some(provider_id) = persona.provider_id {
+        let (provider, _) = ProviderDbService::new(&mut db)
+            .get_accessible_by_id(&user_id, provider_id)
+            .await?;
+        if provider.organization_id != Some(organization_id) {
+            return Err(ApiError::Forbidden(
+                "The persona's provider must be shared with the organization first".into(),
+            ));
+        }
+    }
+    PersonaDbService::new(&mut db)
+        .share_with_organization(&user_id, &persona_id, &organization_id)
+        .await?;
+
+    Ok(())
+}
+
+/// # Unshare persona
+/// Stop sharing a persona with an organization. The owner of the persona and the admins of
+/// the organization can unshare it.
+#[openapi(tag = "Organizations")]
+#[delete("/<organization_id>/personas/<persona_id>")]
+async fn unshare_persona(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    organization_id: Uuid,
+    persona_id: Uuid,
+) -> Result<(), ApiError> {
+    let role = get_member_role(&mut db, &organization_id, &user_id).await?;
+    if !role.can_manage() {
+        // Check that the user owns the persona
+        let persona = PersonaDbService::new(&mut db)
+            .find_by_id(&user_id, &persona_id)
+            .await?;
+        if persona.user_id != *user_id {
+            return Err(diesel::result::Error::NotFound.into());
+        }
+    }
+    PersonaDbService::new(&mut db)
+        .unshare_from_organization(&organization_id, &persona_id)
+        .await?;
+
+    Ok(())
+}
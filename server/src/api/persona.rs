@@ -0,0 +1,158 @@
+use rocket::{delete, get, patch, post, serde::json::Json, Route};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+use uuid::Uuid;
+
+use crate::{
+    auth::ChatRsUserId,
+    db::{
+        models::{ChatRsPersona, ChatRsPersonaData, NewChatRsPersona, UpdateChatRsPersona},
+        services::{PersonaDbService, ProviderDbService},
+        DbConnection,
+    },
+    errors::ApiError,
+};
+
+pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
+    openapi_get_routes_spec![
+        settings: get_all_personas,
+        get_persona,
+        create_persona,
+        update_persona,
+        delete_persona
+    ]
+}
+
+/// # List personas
+/// List all saved personas
+#[openapi(tag = "Personas")]
+#[get("/")]
+async fn get_all_personas(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+) -> Result<Json<Vec<ChatRsPersona>>, ApiError> {
+    let personas = PersonaDbService::new(&mut db)
+        .find_by_user_id(&user_id)
+        .await?;
+
+    Ok(Json(personas))
+}
+
+/// # Get persona
+/// Get a saved persona
+#[openapi(tag = "Personas")]
+#[get("/<persona_id>")]
+async fn get_persona(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    persona_id: Uuid,
+) -> Result<Json<ChatRsPersona>, ApiError> {
+    let persona = PersonaDbService::new(&mut db)
+        .find_by_id(&user_id, &persona_id)
+        .await?;
+
+    Ok(Json(persona))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct PersonaCreateInput {
+    /// Name of the persona
+    name: String,
+    /// The ID of the default provider to chat with
+    provider_id: Option<i32>,
+    /// The persona configuration
+    #[serde(flatten)]
+    data: ChatRsPersonaData,
+}
+
+/// # Create persona
+/// Save a reusable setup for new chat sessions: a system prompt, and the default provider,
+/// options, and tools. Create a session from it with `POST /api/session?persona_id=...`.
+#[openapi(tag = "Personas")]
+#[post("/", data = "<input>")]
+async fn create_persona(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    input: Json<PersonaCreateInput>,
+) -> Result<Json<ChatRsPersona>, ApiError> {
+    // Check that the provider exists
+    if let Some(provider_id) = input.provider_id {
+        ProviderDbService::new(&mut db)
+            .get_accessible_by_id(&user_id, provider_id)
+            .await?;
+    }
+    let persona = PersonaDbService::new(&mut db)
+        .create(NewChatRsPersona {
+            user_id: &user_id,
+            provider_id: input.provider_id,
+            name: &input.name,
+            data: &input.data,
+        })
+        .await?;
+
+    Ok(Json(persona))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct PersonaUpdateInput {
+    /// Name of the persona
+    name: Option<String>,
+    /// The ID of the default provider to chat with
+    provider_id: Option<i32>,
+    /// Remove the default provider
+    clear_provider: Option<bool>,
+    /// The persona configuration (replaces the existing configuration)
+    data: Option<ChatRsPersonaData>,
+}
+
+/// # Update persona
+/// Update a saved persona. Existing sessions created from it are unchanged.
+#[openapi(tag = "Personas")]
+#[patch("/<persona_id>", data = "<input>")]
+async fn update_persona(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    persona_id: Uuid,
+    input: Json<PersonaUpdateInput>,
+) -> Result<Json<ChatRsPersona>, ApiError> {
+    if let Some(provider_id) = input.provider_id {
+        ProviderDbService::new(&mut db)
+            .get_accessible_by_id(&user_id, provider_id)
+            .await?;
+    }
+    let provider_id = match input.clear_provider {
+        Some(true) => Some(None),
+        _ => input.provider_id.map(Some),
+    };
+    let persona = PersonaDbService::new(&mut db)
+        .update(
+            &user_id,
+            &persona_id,
+            UpdateChatRsPersona {
+                provider_id,
+                name: input.name.as_deref(),
+                data: input.data.as_ref(),
+            },
+        )
+        .await?;
+
+    Ok(Json(persona))
+}
+
+/// # Delete persona
+/// Delete a saved persona. Existing sessions created from it are unchanged.
+#[openapi(tag = "Personas")]
+#[delete("/<persona_id>")]
+async fn delete_persona(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    persona_id: Uuid,
+) -> Result<String, ApiError> {
+    let id = PersonaDbService::new(&mut db)
+        .delete(&user_id, &persona_id)
+        .await?;
+
+    Ok(id.to_string())
+}
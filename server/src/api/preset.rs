@@ -0,0 +1,135 @@
+use rocket::{delete, get, patch, post, serde::json::Json, Route};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+use uuid::Uuid;
+
+use crate::{
+    auth::ChatRsUserId,
+    db::{
+        models::{
+            ChatRsProviderPreset, ChatRsProviderPresetData, NewChatRsProviderPreset,
+            UpdateChatRsProviderPreset,
+        },
+        services::{PresetDbService, ProviderDbService},
+        DbConnection,
+    },
+    errors::ApiError,
+};
+
+pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
+    openapi_get_routes_spec![
+        settings: get_all_presets,
+        create_preset,
+        update_preset,
+        delete_preset
+    ]
+}
+
+/// # List presets
+/// List all saved provider presets
+#[openapi(tag = "Presets")]
+#[get("/")]
+async fn get_all_presets(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+) -> Result<Json<Vec<ChatRsProviderPreset>>, ApiError> {
+    let presets = PresetDbService::new(&mut db)
+        .find_by_user_id(&user_id)
+        .await?;
+
+    Ok(Json(presets))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct PresetCreateInput {
+    /// Name of the preset
+    name: String,
+    /// The ID of the provider to chat with
+    provider_id: i32,
+    /// The preset configuration
+    #[serde(flatten)]
+    data: ChatRsProviderPresetData,
+}
+
+/// # Create preset
+/// Save a named bundle of provider options, system prompt, and tools
+#[openapi(tag = "Presets")]
+#[post("/", data = "<input>")]
+async fn create_preset(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    input: Json<PresetCreateInput>,
+) -> Result<Json<ChatRsProviderPreset>, ApiError> {
+    // Check that the provider exists
+    ProviderDbService::new(&mut db)
+        .get_accessible_by_id(&user_id, input.provider_id)
+        .await?;
+    let preset = PresetDbService::new(&mut db)
+        .create(NewChatRsProviderPreset {
+            user_id: &user_id,
+            provider_id: input.provider_id,
+            name: &input.name,
+            data: &input.data,
+        })
+        .await?;
+
+    Ok(Json(preset))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct PresetUpdateInput {
+    /// Name of the preset
+    name: Option<String>,
+    /// The ID of the provider to chat with
+    provider_id: Option<i32>,
+    /// The preset configuration (replaces the existing configuration)
+    data: Option<ChatRsProviderPresetData>,
+}
+
+/// # Update preset
+/// Update a saved provider preset
+#[openapi(tag = "Presets")]
+#[patch("/<preset_id>", data = "<input>")]
+async fn update_preset(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    preset_id: Uuid,
+    input: Json<PresetUpdateInput>,
+) -> Result<Json<ChatRsProviderPreset>, ApiError> {
+    if let Some(provider_id) = input.provider_id {
+        ProviderDbService::new(&mut db)
+            .get_accessible_by_id(&user_id, provider_id)
+            .await?;
+    }
+    let preset = PresetDbService::new(&mut db)
+        .update(
+            &user_id,
+            &preset_id,
+            UpdateChatRsProviderPreset {
+                provider_id: input.provider_id,
+                name: input.name.as_deref(),
+                data: input.data.as_ref(),
+            },
+        )
+        .await?;
+
+    Ok(Json(preset))
+}
+
+/// # Delete preset
+/// Delete a saved provider preset
+#[openapi(tag = "Presets")]
+#[delete("/<preset_id>")]
+async fn delete_preset(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    preset_id: Uuid,
+) -> Result<String, ApiError> {
+    let id = PresetDbService::new(&mut db)
+        .delete(&user_id, &preset_id)
+        .await?;
+
+    Ok(id.to_string())
+}
@@ -0,0 +1,230 @@
+use std::path::Path;
+
+use rocket::{delete, get, patch, post, serde::json::Json, Route, State};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+use uuid::Uuid;
+
+use crate::{
+    attachments::{check_attachments, MAX_PROJECT_FILES},
+    auth::ChatRsUserId,
+    db::{
+        models::{ChatRsAttachment, ChatRsProject, NewChatRsProject, UpdateChatRsProject},
+        services::{AttachmentDbService, ProjectDbService},
+        DbConnection,
+    },
+    errors::ApiError,
+    storage::{LocalStorage, StorageError},
+};
+
+pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
+    openapi_get_routes_spec![
+        settings: get_all_projects,
+        get_project,
+        create_project,
+        update_project,
+        delete_project,
+        add_project_files,
+        delete_project_file
+    ]
+}
+
+/// # List projects
+/// List all projects
+#[openapi(tag = "Projects")]
+#[get("/")]
+async fn get_all_projects(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+) -> Result<Json<Vec<ChatRsProject>>, ApiError> {
+    let projects = ProjectDbService::new(&mut db)
+        .find_by_user_id(&user_id)
+        .await?;
+
+    Ok(Json(projects))
+}
+
+#[derive(JsonSchema, serde::Serialize)]
+struct GetProjectResponse {
+    #[serde(flatten)]
+    project: ChatRsProject,
+    /// Files of the project, given to the assistant in the project's sessions
+    files: Vec<ChatRsAttachment>,
+}
+
+/// # Get project
+/// Get a project and its files. List its sessions with `GET /api/session?project_id=...`.
+#[openapi(tag = "Projects")]
+#[get("/<project_id>")]
+async fn get_project(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    project_id: Uuid,
+) -> Result<Json<GetProjectResponse>, ApiError> {
+    let project = ProjectDbService::new(&mut db)
+        .find_by_id(&user_id, &project_id)
+        .await?;
+    let files = AttachmentDbService::new(&mut db)
+        .find_by_project(&project.id)
+        .await?;
+
+    Ok(Json(GetProjectResponse { project, files }))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct ProjectCreateInput {
+    /// Name of the project
+    name: String,
+    /// Instructions given to the assistant in all sessions of the project
+    instructions: Option<String>,
+}
+
+/// # Create project
+/// Create a project to group sessions that share instructions and files. Create a session in
+/// the project with `POST /api/session?project_id=...`.
+#[openapi(tag = "Projects")]
+#[post("/", data = "<input>")]
+async fn create_project(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    input: Json<ProjectCreateInput>,
+) -> Result<Json<ChatRsProject>, ApiError> {
+    let project = ProjectDbService::new(&mut db)
+        .create(NewChatRsProject {
+            user_id: &user_id,
+            name: &input.name,
+            instructions: input.instructions.as_deref(),
+        })
+        .await?;
+
+    Ok(Json(project))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct ProjectUpdateInput {
+    /// Name of the project
+    name: Option<String>,
+    /// Instructions given to the assistant in all sessions of the project (an empty string
+    /// removes the instructions)
+    instructions: Option<String>,
+}
+
+/// # Update project
+/// Update a project. The changes apply to the next responses in all of its sessions.
+#[openapi(tag = "Projects")]
+#[patch("/<project_id>", data = "<input>")]
+async fn update_project(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    project_id: Uuid,
+    input: Json<ProjectUpdateInput>,
+) -> Result<Json<ChatRsProject>, ApiError> {
+    let instructions = input
+        .instructions
+        .as_deref()
+        .map(|instructions| Some(instructions).filter(|i| !i.trim().is_empty()));
+    let project = ProjectDbService::new(&mut db)
+        .update(
+            &user_id,
+            &project_id,
+            UpdateChatRsProject {
+                name: input.name.as_deref(),
+                instructions,
+            },
+        )
+        .await?;
+
+    Ok(Json(project))
+}
+
+/// # Delete project
+/// Delete a project and its files. Its sessions are kept, without the project.
+#[openapi(tag = "Projects")]
+#[delete("/<project_id>")]
+async fn delete_project(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    storage: &State<LocalStorage>,
+    project_id: Uuid,
+) -> Result<String, ApiError> {
+    let files = AttachmentDbService::new(&mut db)
+        .find_by_project(&project_id)
+        .await?;
+    let id = ProjectDbService::new(&mut db)
+        .delete(&user_id, &project_id)
+        .await?;
+    for file in files {
+        if let Err(err) = storage
+            .delete(&user_id, Path::new(&file.storage_path))
+            .await
+        {
+            rocket::warn!("Failed to delete project file {}: {}", file.id, err);
+        }
+    }
+
+    Ok(id.to_string())
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct ProjectFilesInput {
+    /// IDs of the uploaded files to add to the project (see `/api/file/upload`)
+    attachments: Vec<Uuid>,
+}
+
+/// # Add project files
+/// Add uploaded files to a project, up to 20 files per project. Text files are included in
+/// the system prompt of the project's sessions, and images and PDFs are attached to the
+/// first message.
+#[openapi(tag = "Projects")]
+#[post("/<project_id>/files", data = "<input>")]
+async fn add_project_files(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    project_id: Uuid,
+    input: Json<ProjectFilesInput>,
+) -> Result<Json<Vec<ChatRsAttachment>>, ApiError> {
+    let project = ProjectDbService::new(&mut db)
+        .find_by_id(&user_id, &project_id)
+        .await?;
+    let existing_count = AttachmentDbService::new(&mut db)
+        .find_by_project(&project.id)
+        .await?
+        .len();
+    if existing_count + input.attachments.len() > MAX_PROJECT_FILES {
+        return Err(StorageError::TooManyFiles(MAX_PROJECT_FILES))?;
+    }
+    check_attachments(&mut db, &user_id, &input.attachments, MAX_PROJECT_FILES).await?;
+    let mut db_service = AttachmentDbService::new(&mut db);
+    db_service
+        .attach_to_project(&user_id, &input.attachments, &project.id)
+        .await?;
+    let files = db_service.find_by_project(&project.id).await?;
+
+    Ok(Json(files))
+}
+
+/// # Delete project file
+/// Remove a file from a project, and delete it
+#[openapi(tag = "Projects")]
+#[delete("/<project_id>/files/<file_id>")]
+async fn delete_project_file(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    storage: &State<LocalStorage>,
+    project_id: Uuid,
+    file_id: Uuid,
+) -> Result<String, ApiError> {
+    let file = AttachmentDbService::new(&mut db)
+        .delete_from_project(&user_id, &project_id, &file_id)
+        .await?;
+    if let Err(err) = storage
+        .delete(&user_id, Path::new(&file.storage_path))
+        .await
+    {
+        rocket::warn!("Failed to delete project file {}: {}", file.id, err);
+    }
+
+    Ok(file.id.to_string())
+}
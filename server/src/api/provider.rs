@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
 use rocket::{delete, get, patch, post, serde::json::Json, Route, State};
 use rocket_okapi::{
     okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
@@ -7,17 +10,21 @@ use uuid::Uuid;
 
 use crate::{
     auth::ChatRsUserId,
+    config::AppConfig,
     db::{
         models::{
             ChatRsProvider, ChatRsProviderType, NewChatRsProvider, NewChatRsSecret,
             UpdateChatRsProvider, UpdateChatRsSecret,
         },
+        pagination::ListQuery,
         services::{ProviderDbService, SecretDbService},
         DbConnection,
     },
     errors::ApiError,
-    provider::build_llm_provider_api,
-    provider_models::LlmModel,
+    provider::{build_header_map, build_llm_provider_api, get_extra_headers, LlmError},
+    provider_debug::{ProviderDebugEntry, ProviderDebugService},
+    provider_health::{ProviderHealth, ProviderHealthService},
+    provider_models::{find_model_deprecation, LlmModel},
     redis::RedisClient,
     utils::Encryptor,
 };
@@ -25,56 +32,116 @@ use crate::{
 pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
     openapi_get_routes_spec![
         settings: get_all_providers,
+        get_provider_health,
         list_models,
+        get_provider_debug_log,
         create_provider,
         update_provider,
+        rotate_provider_key,
         delete_provider
     ]
 }
 
 /// # List providers
-/// List all configured providers
+/// List the configured providers. The filter matches provider names.
 #[openapi(tag = "Providers")]
-#[get("/")]
+#[get("/?<query..>")]
 async fn get_all_providers(
     user_id: ChatRsUserId,
     mut db: DbConnection,
+    query: ListQuery,
 ) -> Result<Json<Vec<ChatRsProvider>>, ApiError> {
     let providers = ProviderDbService::new(&mut db)
-        .find_by_user_id(&user_id)
+        .list(&user_id, &query)
         .await?;
 
     Ok(Json(providers))
 }
 
+/// # Get provider health
+/// Get the availability and latency of each provider from the latest background health
+/// check. Providers that haven't been checked yet are omitted.
+#[openapi(tag = "Providers")]
+#[get("/health")]
+async fn get_provider_health(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    redis: RedisClient,
+) -> Result<Json<HashMap<i32, ProviderHealth>>, ApiError> {
+    let provider_ids: Vec<i32> = ProviderDbService::new(&mut db)
+        .find_by_user_id(&user_id)
+        .await?
+        .into_iter()
+        .map(|provider| provider.id)
+        .collect();
+    let health = ProviderHealthService::new(&redis)
+        .get(&provider_ids)
+        .await?;
+
+    Ok(Json(health))
+}
+
 /// # List models
-/// List all models for a provider
+/// List all models for a provider. Models deprecated by the server operator are flagged
+/// with the `deprecation` field.
 #[openapi(tag = "Providers")]
 #[get("/<provider_id>/models")]
 async fn list_models(
     user_id: ChatRsUserId,
     mut db: DbConnection,
     redis: RedisClient,
+    app_config: &State<AppConfig>,
     encryptor: &State<Encryptor>,
     http_client: &State<reqwest::Client>,
     provider_id: i32,
 ) -> Result<Json<Vec<LlmModel>>, ApiError> {
-    let (provider, api_key_secret) = ProviderDbService::new(&mut db)
-        .get_by_id(&user_id, provider_id)
+    let (provider, api_key_secret, secondary_api_key_secret) = ProviderDbService::new(&mut db)
+        .get_by_id_with_secrets(&user_id, provider_id)
         .await?;
     let provider_type: ChatRsProviderType = provider.provider_type.as_str().try_into()?;
     let api_key = api_key_secret
         .map(|secret| encryptor.decrypt_string(&secret.ciphertext, &secret.nonce))
         .transpose()?;
+    let secondary_api_key = secondary_api_key_secret
+        .map(|secret| encryptor.decrypt_string(&secret.ciphertext, &secret.nonce))
+        .transpose()?;
+    let extra_headers = get_extra_headers(&provider, encryptor)?;
     let provider_api = build_llm_provider_api(
         &provider_type,
         provider.base_url.as_deref(),
         api_key.as_deref(),
+        secondary_api_key.as_deref(),
+        &extra_headers,
         &http_client,
         &redis,
     )?;
 
-    Ok(Json(provider_api.list_models().await?))
+    let mut models = provider_api.list_models().await?;
+    for model in models.iter_mut() {
+        model.deprecation =
+            find_model_deprecation(app_config.deprecated_models.as_deref(), &model.id).cloned();
+    }
+
+    Ok(Json(models))
+}
+
+/// # Get provider debug log
+/// Get the raw requests and stream chunks captured for the provider (oldest first), if
+/// `debug_logging` is enabled. Secrets are redacted.
+#[openapi(tag = "Providers")]
+#[get("/<provider_id>/debug")]
+async fn get_provider_debug_log(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    redis: RedisClient,
+    provider_id: i32,
+) -> Result<Json<Vec<ProviderDebugEntry>>, ApiError> {
+    ProviderDbService::new(&mut db)
+        .get_by_id(&user_id, provider_id)
+        .await?;
+    let entries = ProviderDebugService::new(&redis).get(provider_id).await?;
+
+    Ok(Json(entries))
 }
 
 #[derive(JsonSchema, serde::Deserialize)]
@@ -84,6 +151,8 @@ struct ProviderCreateInput {
     base_url: Option<String>,
     default_model: String,
     api_key: Option<String>,
+    /// Extra headers sent with every request to the provider (e.g. for an AI gateway or proxy)
+    extra_headers: Option<HashMap<String, String>>,
 }
 
 /// # Create provider
@@ -105,10 +174,16 @@ async fn create_provider(
                 name: &format!("{} API Key", input.name),
                 ciphertext: &ciphertext,
                 nonce: &nonce,
+                oauth2: None,
             })
             .await?;
         api_key_id = Some(secret_id);
     }
+    let extra_headers = input
+        .extra_headers
+        .as_ref()
+        .map(|headers| encrypt_extra_headers(encryptor, headers))
+        .transpose()?;
     let provider = ProviderDbService::new(&mut db)
         .create(NewChatRsProvider {
             name: &input.name,
@@ -117,6 +192,10 @@ async fn create_provider(
             base_url: input.base_url.as_deref(),
             default_model: &input.default_model,
             api_key_id,
+            extra_headers: extra_headers
+                .as_ref()
+                .map(|(ciphertext, _)| ciphertext.as_slice()),
+            extra_headers_nonce: extra_headers.as_ref().map(|(_, nonce)| nonce.as_slice()),
         })
         .await?;
 
@@ -129,15 +208,20 @@ struct ProviderUpdateInput {
     base_url: Option<String>,
     default_model: Option<String>,
     api_key: Option<String>,
+    /// Capture raw requests and stream chunks for troubleshooting (see the debug log endpoint)
+    debug_logging: Option<bool>,
+    /// Extra headers sent with every request to the provider. Replaces any existing extra headers.
+    extra_headers: Option<HashMap<String, String>>,
 }
 
 /// # Update provider
-/// Update an LLM Provider
+/// Update an LLM Provider. Disabling `debug_logging` deletes the captured debug log.
 #[openapi(tag = "Providers")]
 #[patch("/<provider_id>", data = "<input>")]
 async fn update_provider(
     user_id: ChatRsUserId,
     mut db: DbConnection,
+    redis: RedisClient,
     provider_id: i32,
     encryptor: &State<Encryptor>,
     input: Json<ProviderUpdateInput>,
@@ -170,12 +254,18 @@ async fn update_provider(
                         name: &format!("{} API Key", provider.name),
                         ciphertext: &ciphertext,
                         nonce: &nonce,
+                        oauth2: None,
                     })
                     .await?,
             ),
         };
     }
 
+    let extra_headers = input
+        .extra_headers
+        .as_ref()
+        .map(|headers| encrypt_extra_headers(encryptor, headers))
+        .transpose()?;
     let updated = ProviderDbService::new(&mut db)
         .update(
             &user_id,
@@ -185,6 +275,58 @@ async fn update_provider(
                 name: input.name.as_deref(),
                 base_url: input.base_url.as_deref(),
                 default_model: input.default_model.as_deref(),
+                debug_logging: input.debug_logging,
+                extra_headers: extra_headers
+                    .as_ref()
+                    .map(|(ciphertext, _)| ciphertext.as_slice()),
+                extra_headers_nonce: extra_headers.as_ref().map(|(_, nonce)| nonce.as_slice()),
+            },
+        )
+        .await?;
+    if input.debug_logging == Some(false) {
+        ProviderDebugService::new(&redis).clear(provider_id).await?;
+    }
+
+    Ok(Json(updated))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct ProviderRotateKeyInput {
+    /// The new API key
+    api_key: String,
+}
+
+/// # Rotate provider API key
+/// Set a new API key for the provider. The current key is kept as the secondary key, and
+/// is used if the new key is rejected by the provider. Any previous secondary key is deleted.
+#[openapi(tag = "Providers")]
+#[post("/<provider_id>/rotate-key", data = "<input>")]
+async fn rotate_provider_key(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    provider_id: i32,
+    encryptor: &State<Encryptor>,
+    input: Json<ProviderRotateKeyInput>,
+) -> Result<Json<ChatRsProvider>, ApiError> {
+    let (provider, _) = ProviderDbService::new(&mut db)
+        .get_by_id(&user_id, provider_id)
+        .await?;
+    let (ciphertext, nonce) = encryptor.encrypt_string(&input.api_key)?;
+    let name = format!(
+        "{} API Key ({})",
+        provider.name,
+        Utc::now().format("%Y-%m-%d")
+    );
+    let updated = ProviderDbService::new(&mut db)
+        .rotate_api_key(
+            &user_id,
+            provider_id,
+            NewChatRsSecret {
+                user_id: &user_id,
+                name: &name,
+                ciphertext: &ciphertext,
+                nonce: &nonce,
+                oauth2: None,
             },
         )
         .await?;
@@ -209,9 +351,25 @@ async fn delete_provider(
             .delete(&user_id, &secret.id)
             .await?;
     }
+    if let Some(secret_id) = provider.secondary_api_key_id {
+        SecretDbService::new(&mut db)
+            .delete(&user_id, &secret_id)
+            .await?;
+    }
     ProviderDbService::new(&mut db)
         .delete(&user_id, provider_id)
         .await?;
 
     Ok(Json(provider))
 }
+
+/// Validate and encrypt the extra headers of a provider
+fn encrypt_extra_headers(
+    encryptor: &Encryptor,
+    headers: &HashMap<String, String>,
+) -> Result<(Vec<u8>, Vec<u8>), ApiError> {
+    build_header_map(headers)?;
+    let json = serde_json::to_string(headers).map_err(|_| LlmError::EncryptionError)?;
+
+    Ok(encryptor.encrypt_string(&json)?)
+}
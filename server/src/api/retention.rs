@@ -0,0 +1,144 @@
+use rocket::{delete, get, put, serde::json::Json, Route, State};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+
+use crate::{
+    auth::{AdminUserId, ChatRsUserId},
+    config::AppConfig,
+    db::{
+        models::{ChatRsRetentionSettings, NewChatRsRetentionSettings},
+        services::RetentionDbService,
+        DbConnection,
+    },
+    errors::ApiError,
+    retention::{apply_retention, get_user_retention_report, RetentionPolicy, RetentionReport},
+};
+
+/// Max retention period in days (higher values are clamped)
+const MAX_RETENTION_DAYS: u32 = 365 * 100;
+
+pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
+    openapi_get_routes_spec![
+        settings: get_retention_settings,
+        update_retention_settings,
+        delete_retention_settings,
+        get_retention_report,
+        get_server_retention_report
+    ]
+}
+
+#[derive(JsonSchema, serde::Serialize)]
+struct RetentionSettingsResponse {
+    /// The user's retention settings, if set
+    settings: Option<ChatRsRetentionSettings>,
+    /// Retention period of sessions set by the server, in days
+    server_session_retention_days: Option<u64>,
+    /// Retention period of tool runs set by the server, in days
+    server_tool_run_retention_days: Option<u64>,
+}
+
+/// # Get retention settings
+/// Get the user's data retention settings, and the retention periods of the server. The
+/// shorter of the user's and the server's period applies.
+#[openapi(tag = "Retention")]
+#[get("/")]
+async fn get_retention_settings(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    config: &State<AppConfig>,
+) -> Result<Json<RetentionSettingsResponse>, ApiError> {
+    let settings = RetentionDbService::new(&mut db)
+        .get_settings(&user_id)
+        .await?;
+    let policy = RetentionPolicy::from_config(config);
+
+    Ok(Json(RetentionSettingsResponse {
+        settings,
+        server_session_retention_days: policy.session_days,
+        server_tool_run_retention_days: policy.tool_run_days,
+    }))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct RetentionSettingsInput {
+    /// Days before sessions without new messages are moved to the trash. Set to 0 or omit
+    /// for no limit.
+    session_retention_days: Option<u32>,
+    /// Days before tool runs are deleted. Set to 0 or omit for no limit.
+    tool_run_retention_days: Option<u32>,
+}
+
+/// # Update retention settings
+/// Set the retention periods of the user's data. Old data is deleted by a background task,
+/// see the report endpoint for what would be deleted.
+#[openapi(tag = "Retention")]
+#[put("/", data = "<input>")]
+async fn update_retention_settings(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    input: Json<RetentionSettingsInput>,
+) -> Result<Json<ChatRsRetentionSettings>, ApiError> {
+    let days = |value: Option<u32>| {
+        value
+            .filter(|days| *days > 0)
+            .map(|days| days.min(MAX_RETENTION_DAYS) as i32)
+    };
+    let settings = RetentionDbService::new(&mut db)
+        .upsert_settings(NewChatRsRetentionSettings {
+            user_id: &user_id,
+            session_retention_days: days(input.session_retention_days),
+            tool_run_retention_days: days(input.tool_run_retention_days),
+        })
+        .await?;
+
+    Ok(Json(settings))
+}
+
+/// # Delete retention settings
+/// Remove the user's retention periods (the server's periods still apply)
+#[openapi(tag = "Retention")]
+#[delete("/")]
+async fn delete_retention_settings(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+) -> Result<(), ApiError> {
+    RetentionDbService::new(&mut db)
+        .delete_settings(&user_id)
+        .await?;
+
+    Ok(())
+}
+
+/// # Get retention report
+/// Dry run of the retention policies: count the user's sessions and tool runs that would be
+/// deleted by the next run, without deleting them
+#[openapi(tag = "Retention")]
+#[get("/report")]
+async fn get_retention_report(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    config: &State<AppConfig>,
+) -> Result<Json<RetentionReport>, ApiError> {
+    let policy = RetentionPolicy::from_config(config);
+    let report = get_user_retention_report(&mut db, policy, &user_id).await?;
+
+    Ok(Json(report))
+}
+
+/// # Get server retention report
+/// Dry run of the retention policies for all users: count the sessions and tool runs that
+/// would be deleted by the next run, without deleting them
+#[openapi(tag = "Admin")]
+#[get("/report/all")]
+async fn get_server_retention_report(
+    _admin_id: AdminUserId,
+    mut db: DbConnection,
+    config: &State<AppConfig>,
+) -> Result<Json<RetentionReport>, ApiError> {
+    let policy = RetentionPolicy::from_config(config);
+    let report = apply_retention(&mut db, policy, true).await?;
+
+    Ok(Json(report))
+}
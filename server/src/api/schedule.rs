@@ -0,0 +1,235 @@
+use chrono::Utc;
+use rocket::{delete, get, patch, post, serde::json::Json, Route, State};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+use uuid::Uuid;
+
+use crate::{
+    auth::ChatRsUserId,
+    db::{
+        models::{
+            ChatRsMessage, ChatRsScheduledPrompt, ChatRsScheduledPromptData,
+            NewChatRsScheduledPrompt, UpdateChatRsScheduledPrompt,
+        },
+        services::{ChatDbService, ProviderDbService, ScheduleDbService},
+        DbConnection,
+    },
+    errors::ApiError,
+    redis::RedisClient,
+    scheduler::{next_run, run_scheduled_prompt, ScheduleError, MAX_SCHEDULED_PROMPTS},
+    utils::Encryptor,
+};
+
+pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
+    openapi_get_routes_spec![
+        settings: get_all_schedules,
+        create_schedule,
+        update_schedule,
+        delete_schedule,
+        run_schedule
+    ]
+}
+
+/// # List scheduled prompts
+/// List all scheduled prompts
+#[openapi(tag = "Schedules")]
+#[get("/")]
+async fn get_all_schedules(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+) -> Result<Json<Vec<ChatRsScheduledPrompt>>, ApiError> {
+    let schedules = ScheduleDbService::new(&mut db)
+        .find_by_user_id(&user_id)
+        .await?;
+
+    Ok(Json(schedules))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct ScheduleCreateInput {
+    /// Name of the scheduled prompt
+    name: String,
+    /// The prompt to send
+    prompt: String,
+    /// Cron expression of the schedule, with 5 fields (minute, hour, day of month, month, and
+    /// day of week), e.g. `0 9 * * MON-FRI` for 9:00 on weekdays
+    cron: String,
+    /// IANA timezone name of the schedule (default: `UTC`)
+    timezone: Option<String>,
+    /// The session to save the prompts and responses to
+    session_id: Uuid,
+    /// The ID of the provider to send the prompt to
+    provider_id: i32,
+    /// Configuration for the provider
+    #[serde(flatten)]
+    data: ChatRsScheduledPromptData,
+    /// Whether the prompt runs on its schedule (default: true)
+    enabled: Option<bool>,
+}
+
+/// # Create scheduled prompt
+/// Schedule a prompt to be sent to a provider, e.g. for a daily digest or a recurring report.
+/// Each prompt and response is saved to the given session. The prompt is sent on its own,
+/// without the rest of the session's messages.
+#[openapi(tag = "Schedules")]
+#[post("/", data = "<input>")]
+async fn create_schedule(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    input: Json<ScheduleCreateInput>,
+) -> Result<Json<ChatRsScheduledPrompt>, ApiError> {
+    let count = ScheduleDbService::new(&mut db)
+        .count_by_user_id(&user_id)
+        .await?;
+    if count >= MAX_SCHEDULED_PROMPTS {
+        return Err(ScheduleError::TooManySchedules(MAX_SCHEDULED_PROMPTS))?;
+    }
+    // Check that the session and provider exist
+    ChatDbService::new(&mut db)
+        .get_session(&user_id, &input.session_id)
+        .await?;
+    ProviderDbService::new(&mut db)
+        .get_accessible_by_id(&user_id, input.provider_id)
+        .await?;
+
+    let timezone = input.timezone.as_deref().unwrap_or("UTC");
+    let enabled = input.enabled.unwrap_or(true);
+    let next_run_at = next_run(&input.cron, timezone, Utc::now())?.filter(|_| enabled);
+    let schedule = ScheduleDbService::new(&mut db)
+        .create(NewChatRsScheduledPrompt {
+            user_id: &user_id,
+            session_id: &input.session_id,
+            provider_id: input.provider_id,
+            name: &input.name,
+            prompt: &input.prompt,
+            cron: &input.cron,
+            timezone,
+            data: &input.data,
+            enabled,
+            next_run_at,
+        })
+        .await?;
+
+    Ok(Json(schedule))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct ScheduleUpdateInput {
+    /// Name of the scheduled prompt
+    name: Option<String>,
+    /// The prompt to send
+    prompt: Option<String>,
+    /// Cron expression of the schedule, with 5 fields (minute, hour, day of month, month, and
+    /// day of week)
+    cron: Option<String>,
+    /// IANA timezone name of the schedule
+    timezone: Option<String>,
+    /// The session to save the prompts and responses to
+    session_id: Option<Uuid>,
+    /// The ID of the provider to send the prompt to
+    provider_id: Option<i32>,
+    /// Configuration for the provider (replaces the existing configuration)
+    data: Option<ChatRsScheduledPromptData>,
+    /// Whether the prompt runs on its schedule
+    enabled: Option<bool>,
+}
+
+/// # Update scheduled prompt
+/// Update a scheduled prompt. The next run is recalculated from the current time.
+#[openapi(tag = "Schedules")]
+#[patch("/<schedule_id>", data = "<input>")]
+async fn update_schedule(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    schedule_id: Uuid,
+    input: Json<ScheduleUpdateInput>,
+) -> Result<Json<ChatRsScheduledPrompt>, ApiError> {
+    let current = ScheduleDbService::new(&mut db)
+        .find_by_id(&user_id, &schedule_id)
+        .await?;
+    if let Some(session_id) = &input.session_id {
+        ChatDbService::new(&mut db)
+            .get_session(&user_id, session_id)
+            .await?;
+    }
+    if let Some(provider_id) = input.provider_id {
+        ProviderDbService::new(&mut db)
+            .get_accessible_by_id(&user_id, provider_id)
+            .await?;
+    }
+
+    let cron = input.cron.as_deref().unwrap_or(&current.cron);
+    let timezone = input.timezone.as_deref().unwrap_or(&current.timezone);
+    let enabled = input.enabled.unwrap_or(current.enabled);
+    let next_run_at = next_run(cron, timezone, Utc::now())?.filter(|_| enabled);
+    let schedule = ScheduleDbService::new(&mut db)
+        .update(
+            &user_id,
+            &schedule_id,
+            UpdateChatRsScheduledPrompt {
+                session_id: input.session_id.as_ref(),
+                provider_id: input.provider_id,
+                name: input.name.as_deref(),
+                prompt: input.prompt.as_deref(),
+                cron: input.cron.as_deref(),
+                timezone: input.timezone.as_deref(),
+                data: input.data.as_ref(),
+                enabled: input.enabled,
+                next_run_at: Some(next_run_at),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    Ok(Json(schedule))
+}
+
+/// # Delete scheduled prompt
+/// Delete a scheduled prompt. Its messages are kept in the session.
+#[openapi(tag = "Schedules")]
+#[delete("/<schedule_id>")]
+async fn delete_schedule(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    schedule_id: Uuid,
+) -> Result<String, ApiError> {
+    let id = ScheduleDbService::new(&mut db)
+        .delete(&user_id, &schedule_id)
+        .await?;
+
+    Ok(id.to_string())
+}
+
+/// # Run scheduled prompt
+/// Send a scheduled prompt now, and get the response. Doesn't change the next scheduled run.
+#[openapi(tag = "Schedules")]
+#[post("/<schedule_id>/run")]
+async fn run_schedule(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    redis: RedisClient,
+    encryptor: &State<Encryptor>,
+    http_client: &State<reqwest::Client>,
+    schedule_id: Uuid,
+) -> Result<Json<ChatRsMessage>, ApiError> {
+    let schedule = ScheduleDbService::new(&mut db)
+        .find_by_id(&user_id, &schedule_id)
+        .await?;
+    let result = run_scheduled_prompt(&mut db, &redis, encryptor, http_client, &schedule).await;
+    let error = result.as_ref().err().map(|err| err.to_string());
+    ScheduleDbService::new(&mut db)
+        .update(
+            &user_id,
+            &schedule_id,
+            UpdateChatRsScheduledPrompt {
+                last_run_at: Some(Some(Utc::now())),
+                last_error: Some(error.as_deref()),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    Ok(Json(result?))
+}
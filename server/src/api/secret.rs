@@ -9,10 +9,12 @@ use crate::{
     auth::ChatRsUserId,
     db::{
         models::{ChatRsSecretMeta, NewChatRsSecret},
+        pagination::ListQuery,
         services::SecretDbService,
         DbConnection,
     },
     errors::ApiError,
+    tools::{OAuth2SecretInput, OAuth2Settings},
     utils::Encryptor,
 };
 
@@ -20,24 +22,48 @@ pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
     openapi_get_routes_spec![settings: get_all_secrets, create_secret, delete_secret]
 }
 
-/// List all secrets
+/// List secrets. The filter matches secret names.
 #[openapi(tag = "Secrets")]
-#[get("/")]
+#[get("/?<query..>")]
 async fn get_all_secrets(
     user_id: ChatRsUserId,
     mut db: DbConnection,
+    query: ListQuery,
 ) -> Result<Json<Vec<ChatRsSecretMeta>>, ApiError> {
-    let secrets = SecretDbService::new(&mut db)
-        .find_by_user_id(&user_id)
-        .await?;
+    let secrets = SecretDbService::new(&mut db).list(&user_id, &query).await?;
 
     Ok(Json(secrets))
 }
 
 #[derive(JsonSchema, serde::Deserialize)]
 pub struct SecretInput {
+    /// The secret key. For OAuth2 credentials, this is the refresh token.
     pub key: String,
     pub name: String,
+    /// Manage the secret as an OAuth2 credential. Access tokens are refreshed automatically
+    /// before executing tools, and can be referenced as `${access_token}`.
+    pub oauth2: Option<OAuth2SecretInput>,
+}
+
+impl SecretInput {
+    /// Encrypt the key (or the tokens of the OAuth2 credential), returning the ciphertext,
+    /// nonce, and OAuth2 settings
+    pub fn encrypt(
+        &self,
+        encryptor: &Encryptor,
+    ) -> Result<(Vec<u8>, Vec<u8>, Option<OAuth2Settings>), ApiError> {
+        match &self.oauth2 {
+            Some(oauth2) => {
+                let (settings, plaintext) = oauth2.to_secret(&self.key)?;
+                let (ciphertext, nonce) = encryptor.encrypt_string(&plaintext)?;
+                Ok((ciphertext, nonce, Some(settings)))
+            }
+            None => {
+                let (ciphertext, nonce) = encryptor.encrypt_string(&self.key)?;
+                Ok((ciphertext, nonce, None))
+            }
+        }
+    }
 }
 
 /// Create a new secret
@@ -49,13 +75,14 @@ async fn create_secret(
     encryptor: &State<Encryptor>,
     input: Json<SecretInput>,
 ) -> Result<String, ApiError> {
-    let (ciphertext, nonce) = encryptor.encrypt_string(&input.key)?;
+    let (ciphertext, nonce, oauth2) = input.encrypt(encryptor)?;
     let id = SecretDbService::new(&mut db)
         .create(NewChatRsSecret {
             user_id: &user_id,
             name: &input.name,
             ciphertext: &ciphertext,
             nonce: &nonce,
+            oauth2: oauth2.as_ref(),
         })
         .await?;
 
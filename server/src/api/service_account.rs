@@ -0,0 +1,292 @@
+//! Service accounts: non-interactive users for automation, e.g. bots posting into shared
+//! sessions. Service accounts have no login method, so they can only authenticate with the
+//! scoped API keys created for them by an admin, and can't be impersonated. They have their
+//! own message quotas, and use the providers and tools shared with them by organizations.
+
+use rocket::{delete, get, patch, post, serde::json::Json, Route, State};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+use uuid::Uuid;
+
+use crate::{
+    api::{
+        api_key::{create_key, ApiKeyCreateInput, ApiKeyCreateResponse},
+        user::delete_user_data,
+    },
+    auth::AdminUserId,
+    db::{
+        models::{
+            ChatRsApiKey, ChatRsApiKeyScope, ChatRsServiceAccount, ChatRsServiceAccountInfo,
+            NewChatRsServiceAccount, UpdateChatRsServiceAccount,
+        },
+        pagination::ListQuery,
+        services::{ApiKeyDbService, ServiceAccountDbService},
+        DbConnection,
+    },
+    errors::ApiError,
+    redis::{increment_count, redis_key, RedisClient},
+    storage::LocalStorage,
+    utils::Encryptor,
+};
+
+/// Max value of the message quotas (higher values are clamped)
+const MAX_MESSAGE_QUOTA: u32 = 1_000_000;
+/// Expiration of the hourly message counts in seconds
+const HOURLY_MESSAGES_TTL: i64 = 2 * 60 * 60; // 2 hours
+/// Expiration of the daily message counts in seconds
+const DAILY_MESSAGES_TTL: i64 = 2 * 24 * 60 * 60; // 2 days
+
+/// Service account management routes (admins only)
+pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
+    openapi_get_routes_spec![
+        settings: list_service_accounts,
+        create_service_account,
+        update_service_account,
+        delete_service_account,
+        list_service_account_api_keys,
+        create_service_account_api_key,
+        delete_service_account_api_key
+    ]
+}
+
+/// # List service accounts
+#[openapi(tag = "Admin")]
+#[get("/")]
+async fn list_service_accounts(
+    _admin_id: AdminUserId,
+    mut db: DbConnection,
+) -> Result<Json<Vec<ChatRsServiceAccountInfo>>, ApiError> {
+    let accounts = ServiceAccountDbService::new(&mut db).list().await?;
+
+    Ok(Json(accounts))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct ServiceAccountInput {
+    description: Option<String>,
+    /// Max number of chat messages sent per hour. Set to 0 to remove the limit.
+    max_messages_per_hour: Option<u32>,
+    /// Max number of chat messages sent per day. Set to 0 to remove the limit.
+    max_messages_per_day: Option<u32>,
+}
+
+impl ServiceAccountInput {
+    /// Get the updates of the service account (0 unsets a quota)
+    fn to_update(&self) -> UpdateChatRsServiceAccount<'_> {
+        let quota = |value: Option<u32>| match value {
+            Some(0) => Some(None),
+            Some(value) => Some(Some(value.min(MAX_MESSAGE_QUOTA) as i32)),
+            None => None,
+        };
+        UpdateChatRsServiceAccount {
+            description: self
+                .description
+                .as_deref()
+                .map(|description| Some(description.trim()).filter(|d| !d.is_empty())),
+            max_messages_per_hour: quota(self.max_messages_per_hour),
+            max_messages_per_day: quota(self.max_messages_per_day),
+        }
+    }
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct ServiceAccountCreateInput {
+    /// Name of the service account, shown as the author of its messages
+    name: String,
+    #[serde(flatten)]
+    settings: ServiceAccountInput,
+}
+
+/// # Create service account
+/// Create a service account. Create API keys for it to authenticate.
+#[openapi(tag = "Admin")]
+#[post("/", data = "<input>")]
+async fn create_service_account(
+    admin_id: AdminUserId,
+    mut db: DbConnection,
+    input: Json<ServiceAccountCreateInput>,
+) -> Result<Json<ChatRsServiceAccount>, ApiError> {
+    let settings = input.settings.to_update();
+    let account = ServiceAccountDbService::new(&mut db)
+        .create(
+            input.name.trim(),
+            NewChatRsServiceAccount {
+                description: settings.description.flatten(),
+                created_by: &admin_id,
+                max_messages_per_hour: settings.max_messages_per_hour.flatten(),
+                max_messages_per_day: settings.max_messages_per_day.flatten(),
+            },
+        )
+        .await?;
+    rocket::info!(
+        "Admin {} created service account {}",
+        *admin_id,
+        account.user_id
+    );
+
+    Ok(Json(account))
+}
+
+/// # Update service account
+/// Update the description and message quotas of the service account
+#[openapi(tag = "Admin")]
+#[patch("/<user_id>", data = "<input>")]
+async fn update_service_account(
+    _admin_id: AdminUserId,
+    mut db: DbConnection,
+    user_id: Uuid,
+    input: Json<ServiceAccountInput>,
+) -> Result<Json<ChatRsServiceAccount>, ApiError> {
+    let account = ServiceAccountDbService::new(&mut db)
+        .update(&user_id, input.to_update())
+        .await?;
+
+    Ok(Json(account))
+}
+
+/// # Delete service account
+/// Delete the service account, its API keys, and all of its data. ⚠️ WARNING: This action is
+/// irreversible.
+#[openapi(tag = "Admin")]
+#[delete("/<user_id>")]
+async fn delete_service_account(
+    admin_id: AdminUserId,
+    mut db: DbConnection,
+    storage: &State<LocalStorage>,
+    redis: RedisClient,
+    user_id: Uuid,
+) -> Result<String, ApiError> {
+    find_service_account(&mut db, &user_id).await?;
+    rocket::warn!(
+        "Admin {} is deleting service account {}",
+        *admin_id,
+        user_id
+    );
+
+    delete_user_data(&mut db, storage, &redis, &user_id).await
+}
+
+/// # List service account API keys
+#[openapi(tag = "Admin")]
+#[get("/<user_id>/api-keys?<query..>")]
+async fn list_service_account_api_keys(
+    _admin_id: AdminUserId,
+    mut db: DbConnection,
+    user_id: Uuid,
+    query: ListQuery,
+) -> Result<Json<Vec<ChatRsApiKey>>, ApiError> {
+    find_service_account(&mut db, &user_id).await?;
+    let keys = ApiKeyDbService::new(&mut db).list(&user_id, &query).await?;
+
+    Ok(Json(keys))
+}
+
+/// # Create service account API key
+/// Create an API key for the service account. The scopes are required, and can't include
+/// the `admin` scope.
+#[openapi(tag = "Admin")]
+#[post("/<user_id>/api-keys", data = "<input>")]
+async fn create_service_account_api_key(
+    admin_id: AdminUserId,
+    mut db: DbConnection,
+    encryptor: &State<Encryptor>,
+    user_id: Uuid,
+    input: Json<ApiKeyCreateInput>,
+) -> Result<Json<ApiKeyCreateResponse>, ApiError> {
+    find_service_account(&mut db, &user_id).await?;
+    match &input.scopes {
+        Some(scopes) if !scopes.is_empty() && !scopes.contains(&ChatRsApiKeyScope::Admin) => {}
+        _ => {
+            return Err(ApiError::Forbidden(
+                "Service account API keys need scopes other than `admin`".to_owned(),
+            ))
+        }
+    }
+    let response = create_key(&mut db, encryptor, &user_id, &input).await?;
+    rocket::info!(
+        "Admin {} created an API key for service account {}",
+        *admin_id,
+        user_id
+    );
+
+    Ok(Json(response))
+}
+
+/// # Delete service account API key
+#[openapi(tag = "Admin")]
+#[delete("/<user_id>/api-keys/<api_key_id>")]
+async fn delete_service_account_api_key(
+    _admin_id: AdminUserId,
+    mut db: DbConnection,
+    user_id: Uuid,
+    api_key_id: Uuid,
+) -> Result<(), ApiError> {
+    find_service_account(&mut db, &user_id).await?;
+    let _ = ApiKeyDbService::new(&mut db)
+        .delete(&user_id, &api_key_id)
+        .await?;
+
+    Ok(())
+}
+
+/// Get the service account, or a not found error
+async fn find_service_account(
+    db: &mut DbConnection,
+    user_id: &Uuid,
+) -> Result<ChatRsServiceAccount, ApiError> {
+    let account = ServiceAccountDbService::new(db)
+        .find(user_id)
+        .await?
+        .ok_or(diesel::result::Error::NotFound)?;
+
+    Ok(account)
+}
+
+/// Count a message sent by the user, checking the message quotas if the user is a service
+/// account
+pub(super) async fn check_message_quota(
+    db: &mut DbConnection,
+    redis: &fred::clients::Client,
+    user_id: &Uuid,
+) -> Result<(), ApiError> {
+    let Some(account) = ServiceAccountDbService::new(db).find(user_id).await? else {
+        return Ok(());
+    };
+    let now = chrono::Utc::now();
+    let quotas = [
+        (
+            account.max_messages_per_hour,
+            redis_key(format_args!(
+                "user:{}:messages:{}",
+                user_id,
+                now.format("%Y-%m-%dT%H")
+            )),
+            HOURLY_MESSAGES_TTL,
+            "hour",
+        ),
+        (
+            account.max_messages_per_day,
+            redis_key(format_args!(
+                "user:{}:messages:{}",
+                user_id,
+                now.format("%Y-%m-%d")
+            )),
+            DAILY_MESSAGES_TTL,
+            "day",
+        ),
+    ];
+    for (max, key, ttl, period) in quotas {
+        let Some(max) = max else {
+            continue;
+        };
+        if increment_count(redis, &key, ttl).await? > max as u64 {
+            return Err(ApiError::RateLimited(format!(
+                "Service account is limited to {max} messages per {period}"
+            )));
+        }
+    }
+
+    Ok(())
+}
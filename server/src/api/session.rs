@@ -1,99 +1,705 @@
-use rocket::{delete, get, patch, post, serde::json::Json, Route};
+use std::pin::Pin;
+
+use chrono::{DateTime, Utc};
+use rocket::{
+    data::{Data, ToByteUnit},
+    delete,
+    futures::{stream, Stream, StreamExt},
+    get, patch, post, put,
+    response::stream::{Event, EventStream},
+    serde::json::Json,
+    Route, State,
+};
 use rocket_okapi::{
     okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
 };
 use schemars::JsonSchema;
 use serde::Deserialize;
+use tokio_stream::wrappers::ReceiverStream;
 use uuid::Uuid;
 
 use crate::{
-    auth::ChatRsUserId,
+    api::add_component_schema,
+    auth::{ChatRead, ChatRsScopedUserId, ChatWrite},
     db::{
-        models::{ChatRsMessage, ChatRsSession, NewChatRsSession, UpdateChatRsSession},
-        services::ChatDbService,
-        DbConnection,
+        models::{
+            ChatRsAttachment, ChatRsDeletedMessage, ChatRsFeedbackRating, ChatRsMessage,
+            ChatRsMessageFeedback, ChatRsMessageRole, ChatRsSession, ChatRsSessionMemberInfo,
+            ChatRsSessionMeta, ChatRsSessionProviderConfig, ChatRsSessionRole,
+            ChatRsSessionSearchSettings, NewChatRsSession, NewChatRsSessionMember,
+            NewChatRsSessionSearchSettings, UpdateChatRsSession,
+        },
+        pagination::ListQuery,
+        services::{
+            AttachmentDbService, ChatDbService, PersonaDbService, ProjectDbService,
+            ProviderDbService, SessionMemberDbService, SessionSearchDbService, UserDbService,
+        },
+        DbConnection, DbPool, DbReadConnection,
     },
     errors::ApiError,
-    utils::SessionSearchResult,
+    import::{parse_export, parse_transcript, spawn_bulk_import, ExportFormat, TranscriptFormat},
+    knowledge::{HybridSessionSearchResult, KnowledgeService},
+    provider::LlmProviderOptions,
+    stream::{
+        get_import_stream_key, subscribe_session_events, ExclusiveStreamClient, ImportProgress,
+        ImportStreamEvent, ImportStreamWriter, LastEventId, SessionEvent, SseStreamReader,
+        StreamClient,
+    },
+    tools::delete_code_runner_workspace,
+    utils::{MessageSearchResult, SessionSearchResult},
 };
 
 pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
-    openapi_get_routes_spec![
+    let (routes, mut spec) = openapi_get_routes_spec![
         settings: get_all_sessions,
+        get_shared_sessions,
         create_session,
+        import_session,
+        bulk_import_sessions,
+        connect_to_import_stream,
         get_session,
+        sync_session_messages,
+        connect_to_session_events,
+        get_session_members,
+        add_session_member,
+        update_session_member,
+        remove_session_member,
+        get_pinned_messages,
+        search_session_messages,
         search_sessions,
+        hybrid_search_sessions,
+        get_search_settings,
+        update_search_settings,
+        delete_search_settings,
         update_session,
+        get_session_options,
+        clear_session_options,
+        delete_session_workspace,
         delete_session,
-        delete_message
-    ]
+        update_message,
+        update_message_feedback,
+        export_feedback,
+        delete_message,
+        get_trash,
+        restore_session,
+        restore_message
+    ];
+    add_component_schema::<ImportStreamEvent>(&mut spec, settings);
+    add_component_schema::<SessionEvent>(&mut spec, settings);
+    (routes, spec)
 }
 
 pub const DEFAULT_SESSION_TITLE: &str = "New Chat";
+/// Maximum size of an imported export file, in MiB.
+const MAX_EXPORT_SIZE_MIB: u64 = 100;
 
 #[derive(JsonSchema, serde::Serialize)]
 struct SessionIdResponse {
     session_id: String,
 }
 
-/// List chat sessions
+/// List chat sessions, pinned sessions first and then most recently updated first. The filter
+/// matches session titles. Archived sessions are excluded unless `include_archived` is set.
+/// Set `project_id` to only list the sessions of a project.
 #[openapi(tag = "Chat Session")]
-#[get("/")]
+#[get("/?<query..>&<include_archived>&<project_id>")]
 async fn get_all_sessions(
-    user_id: ChatRsUserId,
-    mut db: DbConnection,
+    user_id: ChatRsScopedUserId<ChatRead>,
+    mut db: DbReadConnection,
+    query: ListQuery,
+    include_archived: Option<bool>,
+    project_id: Option<Uuid>,
 ) -> Result<Json<Vec<ChatRsSession>>, ApiError> {
     let sessions = ChatDbService::new(&mut db)
-        .get_all_sessions(&user_id)
+        .list_sessions(
+            &user_id,
+            &query,
+            include_archived.unwrap_or(false),
+            project_id.as_ref(),
+        )
         .await?;
 
     Ok(Json(sessions))
 }
 
-/// Create a new chat session
+#[derive(JsonSchema, serde::Serialize)]
+struct SharedSessionResponse {
+    session: ChatRsSession,
+    /// The user's role in the session
+    #[schemars(with = "ChatRsSessionRole")]
+    role: String,
+}
+
+/// List the chat sessions that other users have shared with the user, most recently updated
+/// first
+#[openapi(tag = "Chat Session")]
+#[get("/shared")]
+async fn get_shared_sessions(
+    user_id: ChatRsScopedUserId<ChatRead>,
+    mut db: DbReadConnection,
+) -> Result<Json<Vec<SharedSessionResponse>>, ApiError> {
+    let sessions = ChatDbService::new(&mut db)
+        .list_shared_sessions(&user_id)
+        .await?
+        .into_iter()
+        .map(|(session, role)| SharedSessionResponse { session, role })
+        .collect();
+
+    Ok(Json(sessions))
+}
+
+/// Create a new chat session, optionally from a persona and/or in a project. The persona's
+/// system prompt is added as the first message, and its provider, options, and tools are
+/// used by default. The project's instructions and files are given to the assistant in
+/// every response.
 #[openapi(tag = "Chat Session")]
-#[post("/")]
+#[post("/?<persona_id>&<project_id>")]
 async fn create_session(
-    user_id: ChatRsUserId,
+    user_id: ChatRsScopedUserId<ChatWrite>,
     mut db: DbConnection,
+    persona_id: Option<Uuid>,
+    project_id: Option<Uuid>,
 ) -> Result<Json<SessionIdResponse>, ApiError> {
+    // Check that the project exists
+    if let Some(project_id) = project_id {
+        ProjectDbService::new(&mut db)
+            .find_by_id(&user_id, &project_id)
+            .await?;
+    }
+    let Some(persona_id) = persona_id else {
+        let id = ChatDbService::new(&mut db)
+            .create_session(NewChatRsSession {
+                user_id: &user_id,
+                title: DEFAULT_SESSION_TITLE,
+                meta: None,
+                project_id: project_id.as_ref(),
+            })
+            .await?;
+        return Ok(Json(SessionIdResponse { session_id: id }));
+    };
+
+    let persona = PersonaDbService::new(&mut db)
+        .find_by_id(&user_id, &persona_id)
+        .await?;
+    let provider_config = match persona.provider_id {
+        Some(provider_id) => {
+            let options = match persona.data.options {
+                Some(options) => options,
+                None => LlmProviderOptions {
+                    model: ProviderDbService::new(&mut db)
+                        .get_accessible_by_id(&user_id, provider_id)
+                        .await?
+                        .0
+                        .default_model,
+                    ..Default::default()
+                },
+            };
+            Some(ChatRsSessionProviderConfig {
+                provider_id,
+                options,
+            })
+        }
+        None => None,
+    };
+    let meta = ChatRsSessionMeta {
+        tool_config: persona.data.tools,
+        provider_config,
+        persona_id: Some(persona.id),
+    };
+    let session = NewChatRsSession {
+        user_id: &user_id,
+        title: DEFAULT_SESSION_TITLE,
+        meta: Some(&meta),
+        project_id: project_id.as_ref(),
+    };
+    let mut db_service = ChatDbService::new(&mut db);
+    let id = match persona.data.system_prompt.as_deref() {
+        Some(system_prompt) => db_service
+            .import_session(session, &[(ChatRsMessageRole::System, system_prompt, None)])
+            .await?
+            .to_string(),
+        None => db_service.create_session(session).await?,
+    };
+
+    Ok(Json(SessionIdResponse { session_id: id }))
+}
+
+#[derive(JsonSchema, Deserialize)]
+struct ImportSessionInput {
+    /// Format of the transcript
+    format: TranscriptFormat,
+    /// The transcript. JSON transcripts have an optional `title` and a list of `messages`
+    /// with a `role` (`user`, `assistant`, or `system`) and `content`. Markdown transcripts
+    /// have an optional `# Title` heading, and each message starts with a `## User`,
+    /// `## Assistant`, or `## System` heading.
+    content: String,
+    /// Only validate the transcript, without importing it
+    validate_only: Option<bool>,
+}
+
+#[derive(JsonSchema, serde::Serialize)]
+struct ImportSessionResponse {
+    /// ID of the imported session (not set if only validating, or if there are problems)
+    session_id: Option<String>,
+    /// Number of messages in the transcript
+    message_count: usize,
+    /// Problems found in the transcript. If there are any, nothing is imported.
+    problems: Vec<String>,
+}
+
+/// Import a conversation from another tool as a new chat session
+#[openapi(tag = "Chat Session")]
+#[post("/import", data = "<input>")]
+async fn import_session(
+    user_id: ChatRsScopedUserId<ChatWrite>,
+    mut db: DbConnection,
+    input: Json<ImportSessionInput>,
+) -> Result<Json<ImportSessionResponse>, ApiError> {
+    let transcript = match parse_transcript(input.format, &input.content) {
+        Ok(transcript) => transcript,
+        Err(problems) => {
+            return Ok(Json(ImportSessionResponse {
+                session_id: None,
+                message_count: 0,
+                problems,
+            }))
+        }
+    };
+    let message_count = transcript.messages.len();
+    if input.validate_only.unwrap_or(false) {
+        return Ok(Json(ImportSessionResponse {
+            session_id: None,
+            message_count,
+            problems: Vec::new(),
+        }));
+    }
+
+    let title = transcript
+        .title
+        .as_deref()
+        .map(str::trim)
+        .filter(|title| !title.is_empty())
+        .unwrap_or(DEFAULT_SESSION_TITLE);
+    let messages: Vec<_> = transcript
+        .messages
+        .iter()
+        .map(|message| (message.role, message.content.as_str(), message.created_at))
+        .collect();
     let id = ChatDbService::new(&mut db)
-        .create_session(NewChatRsSession {
-            user_id: &user_id,
-            title: DEFAULT_SESSION_TITLE,
+        .import_session(
+            NewChatRsSession {
+                user_id: &user_id,
+                title,
+                meta: None,
+                project_id: None,
+            },
+            &messages,
+        )
+        .await?;
+
+    Ok(Json(ImportSessionResponse {
+        session_id: Some(id.to_string()),
+        message_count,
+        problems: Vec::new(),
+    }))
+}
+
+#[derive(JsonSchema, serde::Serialize)]
+struct BulkImportResponse {
+    /// ID of the import, to follow its progress (not set if only validating, or if there
+    /// are problems)
+    import_id: Option<String>,
+    /// Number of conversations to import
+    conversation_count: usize,
+    /// Problems found in the export file. If there are any, nothing is imported.
+    problems: Vec<String>,
+}
+
+/// # Import exported conversations
+/// Import the conversations of a ChatGPT or Claude export (the `conversations.json` file,
+/// sent as the request body) as new chat sessions. The sessions are created in the
+/// background: connect to the import stream to follow the progress.
+#[openapi(tag = "Chat Session")]
+#[post("/import/bulk?<format>&<validate_only>", data = "<data>")]
+async fn bulk_import_sessions(
+    user_id: ChatRsScopedUserId<ChatWrite>,
+    db_pool: &State<DbPool>,
+    streams: StreamClient,
+    format: ExportFormat,
+    validate_only: Option<bool>,
+    data: Data<'_>,
+) -> Result<Json<BulkImportResponse>, ApiError> {
+    let problem_response = |problems: Vec<String>| {
+        Ok(Json(BulkImportResponse {
+            import_id: None,
+            conversation_count: 0,
+            problems,
+        }))
+    };
+    let content = match data
+        .open(MAX_EXPORT_SIZE_MIB.mebibytes())
+        .into_string()
+        .await
+    {
+        Ok(content) if content.is_complete() => content.into_inner(),
+        Ok(_) => {
+            return problem_response(vec![format!(
+                "The export file is too large (max: {MAX_EXPORT_SIZE_MIB} MiB)"
+            )])
+        }
+        Err(err) => return problem_response(vec![format!("Failed to read the export: {err}")]),
+    };
+    let transcripts = match parse_export(format, &content) {
+        Ok(transcripts) => transcripts,
+        Err(problems) => return problem_response(problems),
+    };
+    let conversation_count = transcripts.len();
+    if validate_only.unwrap_or(false) {
+        return Ok(Json(BulkImportResponse {
+            import_id: None,
+            conversation_count,
+            problems: Vec::new(),
+        }));
+    }
+
+    let import_id = Uuid::new_v4();
+    let writer = ImportStreamWriter::new(streams, &user_id, &import_id);
+    writer
+        .start(&ImportProgress {
+            total: conversation_count,
+            ..Default::default()
         })
         .await?;
+    spawn_bulk_import(
+        *user_id,
+        transcripts,
+        DEFAULT_SESSION_TITLE,
+        writer,
+        db_pool.inner().clone(),
+    );
 
-    Ok(Json(SessionIdResponse { session_id: id }))
+    Ok(Json(BulkImportResponse {
+        import_id: Some(import_id.to_string()),
+        conversation_count,
+        problems: Vec::new(),
+    }))
+}
+
+/// # Connect to import stream
+/// Stream the progress of a bulk import. Events are described by the `ImportStreamEvent`
+/// schema. The stream is available for an hour after the import has ended.
+#[openapi(tag = "Chat Session")]
+#[get("/import/<import_id>/stream")]
+async fn connect_to_import_stream(
+    user_id: ChatRsScopedUserId<ChatRead>,
+    stream_reader_client: ExclusiveStreamClient,
+    import_id: Uuid,
+    start_event_id: Option<LastEventId>,
+) -> Result<EventStream<Pin<Box<dyn Stream<Item = Event> + Send>>>, ApiError> {
+    let stream_reader = SseStreamReader::new(stream_reader_client.into_inner());
+    let key = get_import_stream_key(&user_id, &import_id);
+
+    // Return the previous events if the import has already ended
+    let (prev_events, last_event_id, is_end) = stream_reader
+        .get_prev_events(&key, start_event_id.as_deref())
+        .await?;
+    let prev_events_stream = stream::iter(prev_events);
+    if is_end {
+        return Ok(EventStream::from(prev_events_stream.boxed()));
+    }
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<Event>(50);
+    tokio::spawn(async move {
+        stream_reader.stream(&key, &last_event_id, &tx).await;
+        drop(tx);
+    });
+
+    let stream = prev_events_stream.chain(ReceiverStream::new(rx)).boxed();
+    Ok(EventStream::from(stream))
 }
 
 #[derive(JsonSchema, serde::Serialize)]
 struct GetSessionResponse {
     session: ChatRsSession,
     messages: Vec<ChatRsMessage>,
+    /// Files attached to the messages
+    attachments: Vec<ChatRsAttachment>,
 }
 
 /// Get a chat session and its messages
 #[openapi(tag = "Chat Session")]
 #[get("/<session_id>")]
 async fn get_session(
-    user_id: ChatRsUserId,
-    mut db: DbConnection,
+    user_id: ChatRsScopedUserId<ChatRead>,
+    mut db: DbReadConnection,
     session_id: Uuid,
 ) -> Result<Json<GetSessionResponse>, ApiError> {
     let (session, messages) = ChatDbService::new(&mut db)
         .get_session_with_messages(&user_id, &session_id)
         .await?;
+    let message_ids: Vec<Uuid> = messages.iter().map(|message| message.id).collect();
+    let attachments = AttachmentDbService::new(&mut db)
+        .find_by_message_ids(&message_ids)
+        .await?;
+
+    Ok(Json(GetSessionResponse {
+        session,
+        messages,
+        attachments,
+    }))
+}
 
-    Ok(Json(GetSessionResponse { session, messages }))
+#[derive(JsonSchema, serde::Serialize)]
+struct SyncMessagesResponse {
+    /// Messages created or updated since the cursor (all messages if no cursor was given),
+    /// oldest change first
+    messages: Vec<ChatRsMessage>,
+    /// Files attached to the messages
+    attachments: Vec<ChatRsAttachment>,
+    /// IDs of the messages deleted since the cursor
+    deleted_message_ids: Vec<Uuid>,
+    /// Cursor for the next sync: the sequence number of the last change
+    cursor: i64,
+}
+
+/// # Sync session messages
+/// Get the messages of a chat session that changed since the `since` cursor (the `cursor` of
+/// the previous sync), and the IDs of the deleted messages, so that
+/// clients can update long sessions without fetching all messages. Messages permanently
+/// deleted from the trash aren't reported, so clients that haven't synced for longer than
+/// the trash retention should fetch the whole session again.
+#[openapi(tag = "Chat Session")]
+#[get("/<session_id>/messages?<since>")]
+async fn sync_session_messages(
+    user_id: ChatRsScopedUserId<ChatRead>,
+    mut db: DbReadConnection,
+    session_id: Uuid,
+    since: Option<i64>,
+) -> Result<Json<SyncMessagesResponse>, ApiError> {
+    let mut db_service = ChatDbService::new(&mut db);
+    let (session, _) = db_service
+        .get_session_with_role(&user_id, &session_id)
+        .await?;
+    let changes = db_service.get_changed_messages(&session, since).await?;
+
+    let cursor = changes
+        .iter()
+        .map(|(_, change_seq, _)| *change_seq)
+        .max()
+        .or(since)
+        .unwrap_or_default();
+    let mut messages = Vec::with_capacity(changes.len());
+    let mut deleted_message_ids = Vec::new();
+    for (message, _, deleted_at) in changes {
+        match deleted_at {
+            Some(_) => deleted_message_ids.push(message.id),
+            None => messages.push(message),
+        }
+    }
+    let message_ids: Vec<Uuid> = messages.iter().map(|message| message.id).collect();
+    let attachments = AttachmentDbService::new(&mut db)
+        .find_by_message_ids(&message_ids)
+        .await?;
+
+    Ok(Json(SyncMessagesResponse {
+        messages,
+        attachments,
+        deleted_message_ids,
+        cursor,
+    }))
+}
+
+/// Connect to the live events of a chat session, e.g. to show the messages sent by the other
+/// members of a shared session. Events are described by the `SessionEvent` schema.
+#[openapi(tag = "Chat Session")]
+#[get("/<session_id>/events")]
+async fn connect_to_session_events(
+    user_id: ChatRsScopedUserId<ChatRead>,
+    mut db: DbConnection,
+    streams: StreamClient,
+    session_id: Uuid,
+) -> Result<EventStream<Pin<Box<dyn Stream<Item = Event> + Send>>>, ApiError> {
+    ChatDbService::new(&mut db)
+        .get_session_with_role(&user_id, &session_id)
+        .await?;
+    let rx = subscribe_session_events(&streams, &session_id).await?;
+
+    Ok(EventStream::from(ReceiverStream::new(rx).boxed()))
+}
+
+/// List the members of a chat session
+#[openapi(tag = "Chat Session")]
+#[get("/<session_id>/members")]
+async fn get_session_members(
+    user_id: ChatRsScopedUserId<ChatRead>,
+    mut db: DbConnection,
+    session_id: Uuid,
+) -> Result<Json<Vec<ChatRsSessionMemberInfo>>, ApiError> {
+    ChatDbService::new(&mut db)
+        .get_session_with_role(&user_id, &session_id)
+        .await?;
+    let members = SessionMemberDbService::new(&mut db)
+        .find_by_session(&session_id)
+        .await?;
+
+    Ok(Json(members))
+}
+
+/// Role of an invited member of a session
+#[derive(JsonSchema, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SessionMemberRoleInput {
+    /// Can read the session and send messages
+    Write,
+    /// Can only read the session
+    Read,
+}
+impl From<&SessionMemberRoleInput> for &str {
+    fn from(value: &SessionMemberRoleInput) -> Self {
+        match value {
+            SessionMemberRoleInput::Write => (&ChatRsSessionRole::Write).into(),
+            SessionMemberRoleInput::Read => (&ChatRsSessionRole::Read).into(),
+        }
+    }
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct SessionMemberInput {
+    /// ID of the user to invite
+    user_id: Uuid,
+    /// Role of the user in the session
+    role: SessionMemberRoleInput,
+}
+
+/// Invite a user to a chat session, or change their role if they're already a member. Members
+/// with the `write` role can send messages to the session with their own providers and tools.
+/// Only the owner of the session can invite members.
+#[openapi(tag = "Chat Session")]
+#[post("/<session_id>/members", data = "<input>")]
+async fn add_session_member(
+    user_id: ChatRsScopedUserId<ChatWrite>,
+    mut db: DbConnection,
+    session_id: Uuid,
+    input: Json<SessionMemberInput>,
+) -> Result<(), ApiError> {
+    let session = ChatDbService::new(&mut db)
+        .get_session(&user_id, &session_id)
+        .await?;
+    if input.user_id == session.user_id {
+        return Err(ApiError::Forbidden(
+            "The owner can't be invited to their own session".into(),
+        ));
+    }
+    UserDbService::new(&mut db)
+        .find_by_id(&input.user_id)
+        .await?
+        .ok_or(diesel::result::Error::NotFound)?;
+    SessionMemberDbService::new(&mut db)
+        .upsert(NewChatRsSessionMember {
+            session_id: &session_id,
+            user_id: &input.user_id,
+            role: (&input.role).into(),
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct SessionMemberUpdateInput {
+    /// Role of the user in the session
+    role: SessionMemberRoleInput,
+}
+
+/// Change the role of a member of a chat session. Only the owner of the session can change
+/// the roles of members.
+#[openapi(tag = "Chat Session")]
+#[patch("/<session_id>/members/<member_id>", data = "<input>")]
+async fn update_session_member(
+    user_id: ChatRsScopedUserId<ChatWrite>,
+    mut db: DbConnection,
+    session_id: Uuid,
+    member_id: Uuid,
+    input: Json<SessionMemberUpdateInput>,
+) -> Result<(), ApiError> {
+    ChatDbService::new(&mut db)
+        .get_session(&user_id, &session_id)
+        .await?;
+    SessionMemberDbService::new(&mut db)
+        .update_role(&session_id, &member_id, (&input.role).into())
+        .await?;
+
+    Ok(())
+}
+
+/// Remove a member from a chat session. The owner can remove any member, and members can
+/// remove themselves to leave the session.
+#[openapi(tag = "Chat Session")]
+#[delete("/<session_id>/members/<member_id>")]
+async fn remove_session_member(
+    user_id: ChatRsScopedUserId<ChatWrite>,
+    mut db: DbConnection,
+    session_id: Uuid,
+    member_id: Uuid,
+) -> Result<(), ApiError> {
+    let (_, role) = ChatDbService::new(&mut db)
+        .get_session_with_role(&user_id, &session_id)
+        .await?;
+    if role != ChatRsSessionRole::Owner && member_id != *user_id {
+        return Err(ApiError::Forbidden(
+            "Only the owner can remove other members".into(),
+        ));
+    }
+    SessionMemberDbService::new(&mut db)
+        .delete(&session_id, &member_id)
+        .await?;
+
+    Ok(())
+}
+
+/// List the pinned messages of a chat session, in chronological order. Pinned messages are
+/// always sent to the assistant with the session's messages.
+#[openapi(tag = "Chat Session")]
+#[get("/<session_id>/pinned")]
+async fn get_pinned_messages(
+    user_id: ChatRsScopedUserId<ChatRead>,
+    mut db: DbReadConnection,
+    session_id: Uuid,
+) -> Result<Json<Vec<ChatRsMessage>>, ApiError> {
+    let mut db_service = ChatDbService::new(&mut db);
+    db_service
+        .get_session_with_role(&user_id, &session_id)
+        .await?;
+    let messages = db_service.get_pinned_messages(&session_id).await?;
+
+    Ok(Json(messages))
+}
+
+/// Search the messages of a chat session. Returns the matching messages in chronological
+/// order, with snippets of the matching text.
+#[openapi(tag = "Chat Session")]
+#[get("/<session_id>/search?<q>")]
+async fn search_session_messages(
+    user_id: ChatRsScopedUserId<ChatRead>,
+    mut db: DbReadConnection,
+    session_id: Uuid,
+    q: &str,
+) -> Result<Json<Vec<MessageSearchResult>>, ApiError> {
+    let mut db_service = ChatDbService::new(&mut db);
+    db_service.get_session(&user_id, &session_id).await?;
+    let messages = db_service
+        .search_session_messages(&user_id, &session_id, q)
+        .await?;
+
+    Ok(Json(messages))
 }
 
 /// Search chat sessions by title and messages
 #[openapi(tag = "Chat Session")]
 #[get("/search?<query>")]
 async fn search_sessions(
-    user_id: ChatRsUserId,
-    mut db: DbConnection,
+    user_id: ChatRsScopedUserId<ChatRead>,
+    mut db: DbReadConnection,
     query: &str,
 ) -> Result<Json<Vec<SessionSearchResult>>, ApiError> {
     let sessions = ChatDbService::new(&mut db)
@@ -103,26 +709,119 @@ async fn search_sessions(
     Ok(Json(sessions))
 }
 
+/// Search chat sessions by full-text search, and by the meaning of their messages if the
+/// semantic search is set up. The full-text and semantic matches are merged and ranked by
+/// their combined score.
+#[openapi(tag = "Chat Session")]
+#[get("/search/hybrid?<query>&<limit>")]
+async fn hybrid_search_sessions(
+    user_id: ChatRsScopedUserId<ChatRead>,
+    knowledge: KnowledgeService,
+    query: &str,
+    limit: Option<u8>,
+) -> Result<Json<Vec<HybridSessionSearchResult>>, ApiError> {
+    let sessions = knowledge
+        .search_sessions(&user_id, query, limit.unwrap_or(10).clamp(1, 50))
+        .await?;
+
+    Ok(Json(sessions))
+}
+
+/// Get the settings of the semantic search of sessions, if set up
+#[openapi(tag = "Chat Session")]
+#[get("/search/settings")]
+async fn get_search_settings(
+    user_id: ChatRsScopedUserId<ChatRead>,
+    mut db: DbConnection,
+) -> Result<Json<Option<ChatRsSessionSearchSettings>>, ApiError> {
+    let settings = SessionSearchDbService::new(&mut db)
+        .get_settings(&user_id)
+        .await?;
+
+    Ok(Json(settings))
+}
+
+#[derive(JsonSchema, Deserialize)]
+struct SearchSettingsInput {
+    /// The ID of the provider used to generate embeddings (OpenAI-compatible or Ollama)
+    provider_id: i32,
+    /// The embedding model (e.g. `text-embedding-3-small`, `nomic-embed-text`). Changing the
+    /// model deletes the embeddings of the other models.
+    embedding_model: String,
+}
+
+/// Set up the semantic search of sessions. New messages are embedded with the given
+/// provider and model when they're saved.
+#[openapi(tag = "Chat Session")]
+#[put("/search/settings", data = "<input>")]
+async fn update_search_settings(
+    user_id: ChatRsScopedUserId<ChatWrite>,
+    mut db: DbConnection,
+    input: Json<SearchSettingsInput>,
+) -> Result<Json<ChatRsSessionSearchSettings>, ApiError> {
+    // Check that the provider exists
+    ProviderDbService::new(&mut db)
+        .get_accessible_by_id(&user_id, input.provider_id)
+        .await?;
+    let settings = SessionSearchDbService::new(&mut db)
+        .upsert_settings(NewChatRsSessionSearchSettings {
+            user_id: &user_id,
+            provider_id: input.provider_id,
+            embedding_model: &input.embedding_model,
+        })
+        .await?;
+
+    Ok(Json(settings))
+}
+
+/// Turn off the semantic search of sessions, and delete the message embeddings
+#[openapi(tag = "Chat Session")]
+#[delete("/search/settings")]
+async fn delete_search_settings(
+    user_id: ChatRsScopedUserId<ChatWrite>,
+    mut db: DbConnection,
+) -> Result<(), ApiError> {
+    SessionSearchDbService::new(&mut db)
+        .delete_settings(&user_id)
+        .await?;
+
+    Ok(())
+}
+
 #[derive(Deserialize, JsonSchema)]
 struct UpdateSessionInput {
-    title: String,
+    title: Option<String>,
+    /// Pin or unpin the session
+    pinned: Option<bool>,
+    /// Archive or unarchive the session
+    archived: Option<bool>,
 }
 
 /// Update chat session
 #[openapi(tag = "Chat Session")]
 #[patch("/<session_id>", data = "<body>")]
 async fn update_session(
-    user_id: ChatRsUserId,
+    user_id: ChatRsScopedUserId<ChatWrite>,
     mut db: DbConnection,
     session_id: Uuid,
     body: Json<UpdateSessionInput>,
 ) -> Result<Json<SessionIdResponse>, ApiError> {
+    if body.title.is_none() && body.pinned.is_none() && body.archived.is_none() {
+        let session = ChatDbService::new(&mut db)
+            .get_session(&user_id, &session_id)
+            .await?;
+        return Ok(Json(SessionIdResponse {
+            session_id: session.id.to_string(),
+        }));
+    }
     let updated_id = ChatDbService::new(&mut db)
         .update_session(
             &user_id,
             &session_id,
             UpdateChatRsSession {
-                title: Some(&body.title),
+                title: body.title.as_deref(),
+                pinned: body.pinned,
+                archived_at: body.archived.map(|archived| archived.then(Utc::now)),
                 ..Default::default()
             },
         )
@@ -133,11 +832,208 @@ async fn update_session(
     }))
 }
 
-/// Delete a chat message
+/// # Get session options
+/// Get the provider and options last used in the session, which are used as defaults
+/// when sending the next message
+#[openapi(tag = "Chat Session")]
+#[get("/<session_id>/options")]
+async fn get_session_options(
+    user_id: ChatRsScopedUserId<ChatRead>,
+    mut db: DbConnection,
+    session_id: Uuid,
+) -> Result<Json<Option<ChatRsSessionProviderConfig>>, ApiError> {
+    let session = ChatDbService::new(&mut db)
+        .get_session(&user_id, &session_id)
+        .await?;
+
+    Ok(Json(session.meta.provider_config))
+}
+
+/// # Clear session options
+/// Forget the provider and options last used in the session
+#[openapi(tag = "Chat Session")]
+#[delete("/<session_id>/options")]
+async fn clear_session_options(
+    user_id: ChatRsScopedUserId<ChatWrite>,
+    mut db: DbConnection,
+    session_id: Uuid,
+) -> Result<(), ApiError> {
+    let mut db_service = ChatDbService::new(&mut db);
+    let mut session = db_service.get_session(&user_id, &session_id).await?;
+    if session.meta.provider_config.take().is_some() {
+        db_service
+            .update_session(
+                &user_id,
+                &session_id,
+                UpdateChatRsSession {
+                    meta: Some(&session.meta),
+                    ..Default::default()
+                },
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Reset the code runner's persistent workspace of the session, deleting its files
+#[openapi(tag = "Chat Session")]
+#[delete("/<session_id>/workspace")]
+async fn delete_session_workspace(
+    user_id: ChatRsScopedUserId<ChatWrite>,
+    mut db: DbConnection,
+    session_id: Uuid,
+) -> Result<(), ApiError> {
+    let session = ChatDbService::new(&mut db)
+        .get_session(&user_id, &session_id)
+        .await?;
+    delete_code_runner_workspace(&session.id).await?;
+
+    Ok(())
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct MessageUpdateInput {
+    /// Pin or unpin the message
+    pinned: bool,
+}
+
+/// Pin or unpin a chat message. Members of a shared session need the `write` role.
+#[openapi(tag = "Chat Session")]
+#[patch("/<session_id>/<message_id>", data = "<input>")]
+async fn update_message(
+    user_id: ChatRsScopedUserId<ChatWrite>,
+    mut db: DbConnection,
+    session_id: Uuid,
+    message_id: Uuid,
+    input: Json<MessageUpdateInput>,
+) -> Result<Json<ChatRsMessage>, ApiError> {
+    let mut db_service = ChatDbService::new(&mut db);
+    let (_, role) = db_service
+        .get_session_with_role(&user_id, &session_id)
+        .await?;
+    if !role.can_write() {
+        return Err(ApiError::Forbidden(
+            "Read-only members can't update messages".into(),
+        ));
+    }
+    let message = db_service
+        .set_message_pinned(&session_id, &message_id, input.pinned)
+        .await?;
+
+    Ok(Json(message))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct MessageFeedbackInput {
+    /// Thumbs up or down
+    rating: Option<ChatRsFeedbackRating>,
+    /// Free-text comment on the message
+    comment: Option<String>,
+}
+
+/// Give feedback on an assistant message, replacing any previous feedback. Remove the
+/// feedback by leaving out both the rating and the comment. Members of a shared session
+/// need the `write` role.
+#[openapi(tag = "Chat Session")]
+#[post("/<session_id>/message/<message_id>/feedback", data = "<input>")]
+async fn update_message_feedback(
+    user_id: ChatRsScopedUserId<ChatWrite>,
+    mut db: DbConnection,
+    session_id: Uuid,
+    message_id: Uuid,
+    input: Json<MessageFeedbackInput>,
+) -> Result<Json<ChatRsMessage>, ApiError> {
+    let mut db_service = ChatDbService::new(&mut db);
+    let (_, role) = db_service
+        .get_session_with_role(&user_id, &session_id)
+        .await?;
+    if !role.can_write() {
+        return Err(ApiError::Forbidden(
+            "Read-only members can't give feedback on messages".into(),
+        ));
+    }
+    let message = db_service
+        .find_assistant_message(&session_id, &message_id)
+        .await?;
+    let MessageFeedbackInput { rating, comment } = input.into_inner();
+    let comment = comment.filter(|comment| !comment.trim().is_empty());
+    let mut meta = message.meta;
+    let assistant = meta.assistant.get_or_insert_with(Default::default);
+    assistant.feedback = (rating.is_some() || comment.is_some()).then(|| ChatRsMessageFeedback {
+        user_id: *user_id,
+        rating,
+        comment,
+        updated_at: Utc::now(),
+    });
+    let message = db_service.update_message_meta(&message.id, meta).await?;
+
+    Ok(Json(message))
+}
+
+#[derive(JsonSchema, serde::Serialize)]
+struct FeedbackExample {
+    session_id: Uuid,
+    message_id: Uuid,
+    /// The user message that the assistant responded to
+    prompt: Option<String>,
+    /// The assistant response
+    response: String,
+    /// The model that generated the response
+    model: Option<String>,
+    rating: Option<ChatRsFeedbackRating>,
+    comment: Option<String>,
+    /// When the response was generated
+    created_at: DateTime<Utc>,
+}
+
+/// Export the assistant messages that the user gave feedback on, with the user messages they
+/// responded to, e.g. to build evaluation datasets. Sorted from oldest to newest.
+#[openapi(tag = "Chat Session")]
+#[get("/feedback/export")]
+async fn export_feedback(
+    user_id: ChatRsScopedUserId<ChatRead>,
+    mut db: DbConnection,
+) -> Result<Json<Vec<FeedbackExample>>, ApiError> {
+    let mut db_service = ChatDbService::new(&mut db);
+    let messages = db_service.find_messages_with_feedback(&user_id).await?;
+    let mut session_ids: Vec<Uuid> = messages.iter().map(|m| m.session_id).collect();
+    session_ids.sort_unstable();
+    session_ids.dedup();
+    let user_messages = db_service.get_user_messages(&session_ids).await?;
+
+    let examples = messages
+        .into_iter()
+        .filter_map(|message| {
+            let assistant = message.meta.assistant?;
+            let feedback = assistant.feedback?;
+            let prompt = user_messages
+                .iter()
+                .filter(|m| m.session_id == message.session_id)
+                .take_while(|m| m.created_at < message.created_at)
+                .last()
+                .map(|m| m.content.clone());
+            Some(FeedbackExample {
+                session_id: message.session_id,
+                message_id: message.id,
+                prompt,
+                response: message.content,
+                model: assistant.provider_options.map(|options| options.model),
+                rating: feedback.rating,
+                comment: feedback.comment,
+                created_at: message.created_at,
+            })
+        })
+        .collect();
+
+    Ok(Json(examples))
+}
+
+/// Move a chat message to the trash
 #[openapi(tag = "Chat Session")]
 #[delete("/<session_id>/<message_id>")]
 async fn delete_message(
-    user_id: ChatRsUserId,
+    user_id: ChatRsScopedUserId<ChatWrite>,
     mut db: DbConnection,
     session_id: Uuid,
     message_id: Uuid,
@@ -149,11 +1045,12 @@ async fn delete_message(
     Ok(())
 }
 
-/// Delete chat session
+/// Move chat session to the trash. Sessions and messages in the trash are permanently
+/// deleted after the retention period.
 #[openapi(tag = "Chat Session")]
 #[delete("/<session_id>")]
 async fn delete_session(
-    user_id: ChatRsUserId,
+    user_id: ChatRsScopedUserId<ChatWrite>,
     mut db: DbConnection,
     session_id: Uuid,
 ) -> Result<Json<SessionIdResponse>, ApiError> {
@@ -165,3 +1062,56 @@ async fn delete_session(
         session_id: deleted_id.to_string(),
     }))
 }
+
+#[derive(JsonSchema, serde::Serialize)]
+struct TrashResponse {
+    /// Sessions in the trash
+    sessions: Vec<ChatRsSession>,
+    /// Messages in the trash, from sessions that aren't in the trash
+    messages: Vec<ChatRsDeletedMessage>,
+}
+
+/// List the sessions and messages in the trash, most recently deleted first
+#[openapi(tag = "Chat Session")]
+#[get("/trash")]
+async fn get_trash(
+    user_id: ChatRsScopedUserId<ChatRead>,
+    mut db: DbConnection,
+) -> Result<Json<TrashResponse>, ApiError> {
+    let (sessions, messages) = ChatDbService::new(&mut db).list_trash(&user_id).await?;
+
+    Ok(Json(TrashResponse { sessions, messages }))
+}
+
+/// Restore a chat session from the trash
+#[openapi(tag = "Chat Session")]
+#[post("/<session_id>/restore")]
+async fn restore_session(
+    user_id: ChatRsScopedUserId<ChatWrite>,
+    mut db: DbConnection,
+    session_id: Uuid,
+) -> Result<Json<SessionIdResponse>, ApiError> {
+    let restored_id = ChatDbService::new(&mut db)
+        .restore_session(&user_id, &session_id)
+        .await?;
+
+    Ok(Json(SessionIdResponse {
+        session_id: restored_id.to_string(),
+    }))
+}
+
+/// Restore a chat message from the trash
+#[openapi(tag = "Chat Session")]
+#[post("/<session_id>/<message_id>/restore")]
+async fn restore_message(
+    user_id: ChatRsScopedUserId<ChatWrite>,
+    mut db: DbConnection,
+    session_id: Uuid,
+    message_id: Uuid,
+) -> Result<(), ApiError> {
+    let mut db_service = ChatDbService::new(&mut db);
+    let _ = db_service.get_session(&user_id, &session_id).await?;
+    let _ = db_service.restore_message(&session_id, &message_id).await?;
+
+    Ok(())
+}
@@ -1,9 +1,9 @@
-use std::pin::Pin;
+use std::{collections::HashMap, pin::Pin};
 
 use rocket::{
     delete,
-    futures::{Stream, StreamExt},
-    get, post,
+    futures::{stream, Stream, StreamExt},
+    get, patch, post,
     response::stream::{Event, EventStream},
     serde::json::Json,
     Route, State,
@@ -16,33 +16,59 @@ use tokio_stream::wrappers::ReceiverStream;
 use uuid::Uuid;
 
 use crate::{
-    api::secret::SecretInput,
-    auth::ChatRsUserId,
+    api::{add_component_schema, secret::SecretInput},
+    auth::{ChatRsScopedUserId, ChatRsUserId, ToolsExecute},
+    config::AppConfig,
     db::{
         models::{
-            ChatRsExecutedToolCall, ChatRsExternalApiTool, ChatRsMessageMeta, ChatRsMessageRole,
-            ChatRsSystemTool, NewChatRsExternalApiTool, NewChatRsMessage, NewChatRsSecret,
-            NewChatRsSystemTool,
+            ChatRsExternalApiTool, ChatRsMcpTool, ChatRsMessage, ChatRsSecret, ChatRsSystemTool,
+            ChatRsToolCall, ChatRsToolRun, ChatRsToolRunStatus, NewChatRsExternalApiTool,
+            NewChatRsMcpTool, NewChatRsSecret, NewChatRsSystemTool, UpdateChatRsExternalApiTool,
+            UpdateChatRsMcpTool, UpdateChatRsSecret, UpdateChatRsSystemTool,
         },
-        services::{ChatDbService, SecretDbService, ToolDbService},
+        pagination::ListQuery,
+        services::{ChatDbService, SecretDbService, ToolDbService, ToolRunDbService},
         DbConnection,
     },
     errors::ApiError,
-    provider::LlmToolType,
+    knowledge::KnowledgeService,
+    storage::LocalStorage,
+    stream::{
+        get_tool_stream_key, ExclusiveStreamClient, LastEventId, SseStreamReader, StreamClient,
+        ToolStreamWriter,
+    },
     tools::{
-        ChatRsExternalApiToolConfig, ChatRsSystemToolConfig, ToolError, ToolLog, ToolResponseFormat,
+        find_executable_tool, run_tool_call, save_tool_call_output, BatchToolLog,
+        ChatRsExternalApiToolConfig, ChatRsMcpToolConfig, ChatRsSystemToolConfig, McpTransport,
+        ToolError, ToolLog, ToolParameters, ToolStorage, UnsavedTool, EXTERNAL_API_SECRET_NAMES,
     },
     utils::{Encryptor, SenderWithLogging},
 };
 
 pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
-    openapi_get_routes_spec![settings:
+    let (routes, mut spec) = openapi_get_routes_spec![settings:
         get_all_tools,
+        get_tool_runs,
+        get_tool_run_stats,
+        approve_tool_call,
         execute_tool,
+        execute_all_tools,
+        connect_to_tool_stream,
+        connect_to_batch_tool_stream,
+        test_tool,
         create_tool,
+        export_tools,
+        import_tools,
+        update_system_tool,
+        update_external_api_tool,
+        update_mcp_tool,
         delete_system_tool,
         delete_external_api_tool,
-    ]
+        delete_mcp_tool,
+    ];
+    add_component_schema::<ToolLog>(&mut spec, settings);
+    add_component_schema::<BatchToolLog>(&mut spec, settings);
+    (routes, spec)
 }
 
 #[derive(JsonSchema, serde::Serialize)]
@@ -51,23 +77,103 @@ struct GetAllToolsResponse {
     system: Vec<ChatRsSystemTool>,
     /// External API tools
     external_api: Vec<ChatRsExternalApiTool>,
+    /// MCP tools
+    mcp: Vec<ChatRsMcpTool>,
 }
 
-/// List all tools
+/// List tools. The limit and cursor apply to all lists, and the filter is not supported.
 #[openapi(tag = "Tools")]
-#[get("/")]
+#[get("/?<query..>")]
 async fn get_all_tools(
     user_id: ChatRsUserId,
     mut db: DbConnection,
+    query: ListQuery,
 ) -> Result<Json<GetAllToolsResponse>, ApiError> {
-    let (system, external_api) = ToolDbService::new(&mut db).find_by_user(&user_id).await?;
+    let (system, external_api, mcp) = ToolDbService::new(&mut db).list(&user_id, &query).await?;
 
     Ok(Json(GetAllToolsResponse {
         system,
         external_api,
+        mcp,
     }))
 }
 
+/// List the executions of a tool. The filter matches the names of the tools called.
+#[openapi(tag = "Tools")]
+#[get("/<tool_id>/runs?<query..>")]
+async fn get_tool_runs(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    tool_id: Uuid,
+    query: ListQuery,
+) -> Result<Json<Vec<ChatRsToolRun>>, ApiError> {
+    let runs = ToolRunDbService::new(&mut db)
+        .list(&user_id, &tool_id, &query)
+        .await?;
+
+    Ok(Json(runs))
+}
+
+/// Default number of days included in the tool run stats
+const DEFAULT_STATS_DAYS: u16 = 30;
+
+#[derive(Default, JsonSchema, serde::Serialize)]
+struct ToolRunStatsResponse {
+    /// Number of executions
+    runs: i64,
+    /// Number of executions that returned an error
+    errors: i64,
+    /// Number of executions that returned a cached result
+    cached: i64,
+    /// Average execution time in milliseconds, excluding cached results
+    avg_duration_ms: Option<i64>,
+    /// Longest execution time in milliseconds
+    max_duration_ms: Option<i32>,
+    /// Average size of the tool responses in bytes
+    avg_response_bytes: Option<i64>,
+}
+
+/// Get the aggregate stats of the executions of a tool over the last `days` (default: 30)
+#[openapi(tag = "Tools")]
+#[get("/<tool_id>/runs/stats?<days>")]
+async fn get_tool_run_stats(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    tool_id: Uuid,
+    days: Option<u16>,
+) -> Result<Json<ToolRunStatsResponse>, ApiError> {
+    let since =
+        chrono::Utc::now() - chrono::Duration::days(days.unwrap_or(DEFAULT_STATS_DAYS).into());
+    let stats = ToolRunDbService::new(&mut db)
+        .stats(&user_id, &tool_id, since)
+        .await?;
+
+    let mut response = ToolRunStatsResponse::default();
+    let (mut executed, mut total_duration, mut total_bytes) = (0, 0, 0);
+    for row in stats {
+        response.runs += row.count;
+        total_bytes += row.total_response_bytes.unwrap_or_default();
+        if row.status == <&str>::from(ChatRsToolRunStatus::Cached) {
+            response.cached += row.count;
+            continue;
+        }
+        if row.status == <&str>::from(ChatRsToolRunStatus::Error) {
+            response.errors += row.count;
+        }
+        executed += row.count;
+        total_duration += row.total_duration_ms.unwrap_or_default();
+        response.max_duration_ms = response.max_duration_ms.max(row.max_duration_ms);
+    }
+    if executed > 0 {
+        response.avg_duration_ms = Some(total_duration / executed);
+    }
+    if response.runs > 0 {
+        response.avg_response_bytes = Some(total_bytes / response.runs);
+    }
+
+    Ok(Json(response))
+}
+
 #[derive(JsonSchema, serde::Deserialize)]
 #[serde(rename_all = "snake_case")]
 enum CreateToolInput {
@@ -80,14 +186,32 @@ enum CreateToolInput {
         config: ChatRsExternalApiToolConfig,
         /// API key / secret key
         secret_1: Option<SecretInput>,
+        /// Additional secret (e.g. client ID or signing secret)
+        secret_2: Option<SecretInput>,
+        /// Additional secret (e.g. client ID or signing secret)
+        secret_3: Option<SecretInput>,
+    },
+    /// Add an MCP server, discovering its tools and resources
+    Mcp {
+        /// Name of the MCP server. Will be prefixed to the tool names.
+        name: String,
+        /// How to connect to the MCP server
+        transport: McpTransport,
+        /// API key / secret key
+        secret_1: Option<SecretInput>,
     },
 }
 
+/// The created tool, tagged by the type of tool
 #[derive(JsonSchema, serde::Serialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 enum CreateToolResponse {
+    /// The created system tool
     System(ChatRsSystemTool),
+    /// The created external API tool
     ExternalApi(ChatRsExternalApiTool),
+    /// The created MCP tool
+    Mcp(ChatRsMcpTool),
 }
 
 /// Create a new tool
@@ -96,7 +220,9 @@ enum CreateToolResponse {
 async fn create_tool(
     user_id: ChatRsUserId,
     mut db: DbConnection,
+    app_config: &State<AppConfig>,
     encryptor: &State<Encryptor>,
+    http_client: &State<reqwest::Client>,
     input: Json<CreateToolInput>,
 ) -> Result<Json<CreateToolResponse>, ApiError> {
     match input.into_inner() {
@@ -113,160 +239,867 @@ async fn create_tool(
         CreateToolInput::ExternalApi {
             mut config,
             secret_1,
+            secret_2,
+            secret_3,
         } => {
             config.validate()?;
-            let mut secret_1_id = None;
-            if let Some(secret_input) = secret_1 {
-                let (ciphertext, nonce) = encryptor.encrypt_string(&secret_input.key)?;
-                let new_secret_id = SecretDbService::new(&mut db)
-                    .create(NewChatRsSecret {
-                        user_id: &user_id,
-                        name: &secret_input.name,
-                        ciphertext: &ciphertext,
-                        nonce: &nonce,
-                    })
-                    .await?;
-                secret_1_id = Some(new_secret_id);
+            let mut secret_ids = [None; 3];
+            for (secret_id, secret_input) in
+                secret_ids.iter_mut().zip([secret_1, secret_2, secret_3])
+            {
+                if let Some(secret_input) = secret_input {
+                    *secret_id =
+                        Some(save_secret(&mut db, encryptor, &user_id, None, &secret_input).await?);
+                }
             }
+            let [secret_1_id, secret_2_id, secret_3_id] = secret_ids;
             let tool = ToolDbService::new(&mut db)
                 .create_external_api_tool(NewChatRsExternalApiTool {
                     user_id: &user_id,
                     data: &config,
                     secret_1: secret_1_id.as_ref(),
+                    secret_2: secret_2_id.as_ref(),
+                    secret_3: secret_3_id.as_ref(),
                 })
                 .await?;
             Ok(Json(CreateToolResponse::ExternalApi(tool)))
         }
+        CreateToolInput::Mcp {
+            name,
+            transport,
+            secret_1,
+        } => {
+            transport.check_allowed(app_config.mcp_stdio.unwrap_or(false))?;
+            check_no_oauth2_secret(&secret_1)?;
+            let secret = secret_1.as_ref().map(|secret| secret.key.as_str());
+            let config =
+                ChatRsMcpToolConfig::discover(name, transport, secret, http_client).await?;
+            let mut secret_1_id = None;
+            if let Some(secret_input) = secret_1 {
+                secret_1_id =
+                    Some(save_secret(&mut db, encryptor, &user_id, None, &secret_input).await?);
+            }
+            let tool = ToolDbService::new(&mut db)
+                .create_mcp_tool(NewChatRsMcpTool {
+                    user_id: &user_id,
+                    data: &config,
+                    secret_1: secret_1_id.as_ref(),
+                })
+                .await?;
+            Ok(Json(CreateToolResponse::Mcp(tool)))
+        }
+    }
+}
+
+/// Version of the format of exported tool bundles
+const TOOL_BUNDLE_VERSION: u32 = 1;
+
+/// Exported tool configurations. Secrets are not included, and are referenced by
+/// placeholders instead.
+#[derive(JsonSchema, serde::Serialize, serde::Deserialize)]
+struct ToolBundle {
+    /// Version of the bundle format
+    version: u32,
+    tools: Vec<BundledTool>,
+}
+
+/// An exported tool, tagged by the type of tool. Auto-approval is not exported, and must
+/// be enabled again after importing.
+#[derive(JsonSchema, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BundledTool {
+    System {
+        config: ChatRsSystemToolConfig,
+        /// Placeholder of the secret used by the tool (e.g. a database connection string)
+        secret: Option<String>,
+        #[serde(flatten)]
+        settings: ToolSettingsInput,
+    },
+    ExternalApi {
+        config: ChatRsExternalApiToolConfig,
+        /// Placeholders of the tool's secrets, in order (`secret_1`, `secret_2`, `secret_3`)
+        secrets: [Option<String>; 3],
+        #[serde(flatten)]
+        settings: ToolSettingsInput,
+    },
+    Mcp {
+        config: ChatRsMcpToolConfig,
+        /// Placeholder of the tool's secret
+        secret_1: Option<String>,
+        #[serde(flatten)]
+        settings: ToolSettingsInput,
+    },
+}
+
+/// Placeholders of the exported secrets, named after the secrets
+#[derive(Default)]
+struct SecretPlaceholders(HashMap<Uuid, String>);
+
+impl SecretPlaceholders {
+    /// Get the placeholder of the secret, making sure different secrets with the same name
+    /// get different placeholders
+    async fn get(
+        &mut self,
+        db: &mut DbConnection,
+        user_id: &Uuid,
+        secret_id: &Uuid,
+    ) -> Result<String, ApiError> {
+        if let Some(placeholder) = self.0.get(secret_id) {
+            return Ok(placeholder.clone());
+        }
+        let name = SecretDbService::new(db)
+            .find_by_id(user_id, secret_id)
+            .await?
+            .map(|secret| secret.name)
+            .unwrap_or_else(|| "secret".into());
+        let mut placeholder = name.clone();
+        let mut suffix = 1;
+        while self.0.values().any(|existing| *existing == placeholder) {
+            suffix += 1;
+            placeholder = format!("{name} ({suffix})");
+        }
+        self.0.insert(*secret_id, placeholder.clone());
+        Ok(placeholder)
+    }
+
+    async fn get_optional(
+        &mut self,
+        db: &mut DbConnection,
+        user_id: &Uuid,
+        secret_id: Option<&Uuid>,
+    ) -> Result<Option<String>, ApiError> {
+        match secret_id {
+            Some(secret_id) => Ok(Some(self.get(db, user_id, secret_id).await?)),
+            None => Ok(None),
+        }
     }
 }
 
-/// Execute a tool call and stream its output
+/// Export all tool configurations as a JSON bundle, e.g. to share them with another
+/// instance. Secrets are not exported, and are replaced by placeholders named after the
+/// secrets. Auto-approval is not exported.
+#[openapi(tag = "Tools")]
+#[get("/export")]
+async fn export_tools(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+) -> Result<Json<ToolBundle>, ApiError> {
+    let mut tool_db_service = ToolDbService::new(&mut db);
+    let system_tools = tool_db_service.find_system_tools_by_user(&user_id).await?;
+    // Tools shared with the user's organizations are only exported by their owners
+    let mut external_api_tools = tool_db_service
+        .find_external_api_tools_by_user(&user_id)
+        .await?;
+    external_api_tools.retain(|tool| tool.user_id == *user_id);
+    let mut mcp_tools = tool_db_service.find_mcp_tools_by_user(&user_id).await?;
+    mcp_tools.retain(|tool| tool.user_id == *user_id);
+
+    let mut placeholders = SecretPlaceholders::default();
+    let mut tools =
+        Vec::with_capacity(system_tools.len() + external_api_tools.len() + mcp_tools.len());
+    for mut tool in system_tools {
+        let secret = match tool.data.secret_id_mut() {
+            Some(secret_id) => {
+                let placeholder = placeholders.get(&mut db, &user_id, secret_id).await?;
+                *secret_id = Uuid::nil();
+                Some(placeholder)
+            }
+            None => None,
+        };
+        tools.push(BundledTool::System {
+            config: tool.data,
+            secret,
+            settings: ToolSettingsInput::new(
+                tool.cache_ttl,
+                tool.max_calls_per_hour,
+                tool.max_calls_per_session,
+                tool.timeout_seconds,
+                tool.max_output_bytes,
+            ),
+        });
+    }
+    for tool in external_api_tools {
+        let mut secrets = [None, None, None];
+        for (placeholder, secret_id) in secrets.iter_mut().zip(tool.secret_ids()) {
+            *placeholder = placeholders
+                .get_optional(&mut db, &user_id, secret_id.as_ref())
+                .await?;
+        }
+        tools.push(BundledTool::ExternalApi {
+            config: tool.data,
+            secrets,
+            settings: ToolSettingsInput::new(
+                tool.cache_ttl,
+                tool.max_calls_per_hour,
+                tool.max_calls_per_session,
+                tool.timeout_seconds,
+                tool.max_output_bytes,
+            ),
+        });
+    }
+    for tool in mcp_tools {
+        let secret_1 = placeholders
+            .get_optional(&mut db, &user_id, tool.secret_1.as_ref())
+            .await?;
+        tools.push(BundledTool::Mcp {
+            config: tool.data,
+            secret_1,
+            settings: ToolSettingsInput::new(
+                tool.cache_ttl,
+                tool.max_calls_per_hour,
+                tool.max_calls_per_session,
+                tool.timeout_seconds,
+                tool.max_output_bytes,
+            ),
+        });
+    }
+
+    Ok(Json(ToolBundle {
+        version: TOOL_BUNDLE_VERSION,
+        tools,
+    }))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct ImportToolsInput {
+    /// The exported tool bundle
+    bundle: ToolBundle,
+    /// Secrets to save for the placeholders of the bundle, keyed by placeholder. All
+    /// placeholders used by the tools must be given.
+    #[serde(default)]
+    secrets: HashMap<String, SecretInput>,
+    /// Only check the bundle for problems, without importing it (default: false)
+    validate_only: Option<bool>,
+}
+
+#[derive(JsonSchema, serde::Serialize)]
+struct ImportToolsResponse {
+    /// The imported tools (empty if only validating, or if there are problems)
+    tools: Vec<CreateToolResponse>,
+    /// Problems found in the bundle. If there are any, nothing is imported.
+    problems: Vec<String>,
+}
+
+/// Import the tools of a JSON bundle exported with `GET /api/tool/export`. MCP tools are
+/// not rediscovered, and keep the tools and resources of the bundle.
+#[openapi(tag = "Tools")]
+#[post("/import", data = "<input>")]
+async fn import_tools(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    app_config: &State<AppConfig>,
+    encryptor: &State<Encryptor>,
+    input: Json<ImportToolsInput>,
+) -> Result<Json<ImportToolsResponse>, ApiError> {
+    let ImportToolsInput {
+        mut bundle,
+        secrets,
+        validate_only,
+    } = input.into_inner();
+    let problems =
+        validate_tool_bundle(&mut bundle, &secrets, app_config.mcp_stdio.unwrap_or(false));
+    if !problems.is_empty() || validate_only.unwrap_or(false) {
+        return Ok(Json(ImportToolsResponse {
+            tools: Vec::new(),
+            problems,
+        }));
+    }
+
+    // Save the secrets used by the tools
+    let mut secret_ids = HashMap::with_capacity(secrets.len());
+    for (placeholder, secret_input) in &secrets {
+        let is_used = bundle.tools.iter().any(|tool| match tool {
+            BundledTool::System { secret, .. } => secret.as_ref() == Some(placeholder),
+            BundledTool::ExternalApi { secrets, .. } => {
+                secrets.iter().any(|s| s.as_ref() == Some(placeholder))
+            }
+            BundledTool::Mcp { secret_1, .. } => secret_1.as_ref() == Some(placeholder),
+        });
+        if is_used {
+            let secret_id = save_secret(&mut db, encryptor, &user_id, None, secret_input).await?;
+            secret_ids.insert(placeholder.as_str(), secret_id);
+        }
+    }
+    let get_secret_id = |placeholder: &Option<String>| {
+        placeholder
+            .as_deref()
+            .and_then(|placeholder| secret_ids.get(placeholder))
+            .copied()
+    };
+
+    let mut tools = Vec::with_capacity(bundle.tools.len());
+    let mut tool_db_service = ToolDbService::new(&mut db);
+    for tool in bundle.tools {
+        match tool {
+            BundledTool::System {
+                mut config,
+                secret,
+                settings,
+            } => {
+                if let (Some(secret_id), Some(new_id)) =
+                    (config.secret_id_mut(), get_secret_id(&secret))
+                {
+                    *secret_id = new_id;
+                }
+                let tool = tool_db_service
+                    .create_system_tool(NewChatRsSystemTool {
+                        user_id: &user_id,
+                        data: &config,
+                    })
+                    .await?;
+                let settings = settings.to_update()?;
+                let tool = tool_db_service
+                    .update_system_tool(
+                        &user_id,
+                        &tool.id,
+                        UpdateChatRsSystemTool {
+                            cache_ttl: settings.cache_ttl,
+                            max_calls_per_hour: settings.max_calls_per_hour,
+                            max_calls_per_session: settings.max_calls_per_session,
+                            timeout_seconds: settings.timeout_seconds,
+                            max_output_bytes: settings.max_output_bytes,
+                            ..Default::default()
+                        },
+                    )
+                    .await?;
+                tools.push(CreateToolResponse::System(tool));
+            }
+            BundledTool::ExternalApi {
+                config,
+                secrets,
+                settings,
+            } => {
+                let [secret_1, secret_2, secret_3] = secrets.map(|s| get_secret_id(&s));
+                let tool = tool_db_service
+                    .create_external_api_tool(NewChatRsExternalApiTool {
+                        user_id: &user_id,
+                        data: &config,
+                        secret_1: secret_1.as_ref(),
+                        secret_2: secret_2.as_ref(),
+                        secret_3: secret_3.as_ref(),
+                    })
+                    .await?;
+                let settings = settings.to_update()?;
+                let tool = tool_db_service
+                    .update_external_api_tool(
+                        &user_id,
+                        &tool.id,
+                        UpdateChatRsExternalApiTool {
+                            cache_ttl: settings.cache_ttl,
+                            max_calls_per_hour: settings.max_calls_per_hour,
+                            max_calls_per_session: settings.max_calls_per_session,
+                            timeout_seconds: settings.timeout_seconds,
+                            max_output_bytes: settings.max_output_bytes,
+                            ..Default::default()
+                        },
+                    )
+                    .await?;
+                tools.push(CreateToolResponse::ExternalApi(tool));
+            }
+            BundledTool::Mcp {
+                config,
+                secret_1,
+                settings,
+            } => {
+                let secret_1 = get_secret_id(&secret_1);
+                let tool = tool_db_service
+                    .create_mcp_tool(NewChatRsMcpTool {
+                        user_id: &user_id,
+                        data: &config,
+                        secret_1: secret_1.as_ref(),
+                    })
+                    .await?;
+                let settings = settings.to_update()?;
+                let tool = tool_db_service
+                    .update_mcp_tool(
+                        &user_id,
+                        &tool.id,
+                        UpdateChatRsMcpTool {
+                            cache_ttl: settings.cache_ttl,
+                            max_calls_per_hour: settings.max_calls_per_hour,
+                            max_calls_per_session: settings.max_calls_per_session,
+                            timeout_seconds: settings.timeout_seconds,
+                            max_output_bytes: settings.max_output_bytes,
+                            ..Default::default()
+                        },
+                    )
+                    .await?;
+                tools.push(CreateToolResponse::Mcp(tool));
+            }
+        }
+    }
+
+    Ok(Json(ImportToolsResponse {
+        tools,
+        problems: Vec::new(),
+    }))
+}
+
+/// Validate the configurations, settings, and secret placeholders of the bundled tools,
+/// returning the problems found
+fn validate_tool_bundle(
+    bundle: &mut ToolBundle,
+    secrets: &HashMap<String, SecretInput>,
+    allow_mcp_stdio: bool,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    if bundle.version > TOOL_BUNDLE_VERSION {
+        problems.push(format!(
+            "Unsupported bundle version {} (max: {TOOL_BUNDLE_VERSION})",
+            bundle.version
+        ));
+        return problems;
+    }
+    let check_secret = |placeholder: &Option<String>| match placeholder {
+        Some(placeholder) if !secrets.contains_key(placeholder) => {
+            Err(format!("missing secret for placeholder '{placeholder}'"))
+        }
+        _ => Ok(()),
+    };
+
+    for (index, tool) in bundle.tools.iter_mut().enumerate() {
+        let result = match tool {
+            BundledTool::System {
+                config,
+                secret,
+                settings,
+            } => config
+                .validate()
+                .map_err(|e| e.to_string())
+                .and_then(|_| match (config.secret_id(), secret.as_ref()) {
+                    (Some(_), None) => Err("missing secret placeholder".into()),
+                    _ => check_secret(secret),
+                })
+                .and_then(|_| settings.to_update().map_err(|e| e.to_string())),
+            BundledTool::ExternalApi {
+                config,
+                secrets,
+                settings,
+            } => config
+                .validate()
+                .map_err(|e| e.to_string())
+                .and_then(|_| secrets.iter().try_for_each(check_secret))
+                .and_then(|_| settings.to_update().map_err(|e| e.to_string())),
+            BundledTool::Mcp {
+                config,
+                secret_1,
+                settings,
+            } => config
+                .transport
+                .check_allowed(allow_mcp_stdio)
+                .map_err(|e| e.to_string())
+                .and_then(|_| check_secret(secret_1))
+                .and_then(|_| match secret_1.as_ref().and_then(|s| secrets.get(s)) {
+                    Some(secret) if secret.oauth2.is_some() => {
+                        Err("OAuth2 credentials aren't supported for MCP tools".into())
+                    }
+                    _ => Ok(()),
+                })
+                .and_then(|_| settings.to_update().map_err(|e| e.to_string())),
+        };
+        if let Err(problem) = result {
+            problems.push(format!("Tool {}: {problem}", index + 1));
+        }
+    }
+
+    problems
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct ApproveToolCallInput {
+    /// Whether to approve or deny the tool call
+    approved: bool,
+}
+
+/// Approve or deny a tool call that requires confirmation before being executed
+#[openapi(tag = "Tools")]
+#[post("/approve/<message_id>/<tool_call_id>", data = "<input>")]
+async fn approve_tool_call(
+    user_id: ChatRsScopedUserId<ToolsExecute>,
+    mut db: DbConnection,
+    message_id: Uuid,
+    tool_call_id: &str,
+    input: Json<ApproveToolCallInput>,
+) -> Result<Json<ChatRsToolCall>, ApiError> {
+    let mut message = find_tool_call_message(&mut db, &user_id, &message_id).await?;
+    let tool_call = message
+        .meta
+        .assistant
+        .as_mut()
+        .and_then(|meta| meta.tool_calls.as_mut())
+        .and_then(|tool_calls| {
+            tool_calls
+                .iter_mut()
+                .find(|tool_call| tool_call.id == tool_call_id)
+        })
+        .ok_or(ToolError::ToolCallNotFound)?;
+    let approval = tool_call
+        .approval
+        .as_mut()
+        .ok_or(ToolError::ApprovalNotRequired)?;
+    approval.decide(input.approved)?;
+    let tool_call = tool_call.clone();
+    ChatDbService::new(&mut db)
+        .update_message_meta(&message.id, message.meta)
+        .await?;
+
+    Ok(Json(tool_call))
+}
+
+/// Find an assistant message whose tool calls the user can approve and execute: the user needs
+/// write access to the session, and must have started the turn (as the tools belong to them)
+async fn find_tool_call_message(
+    db: &mut DbConnection,
+    user_id: &Uuid,
+    message_id: &Uuid,
+) -> Result<ChatRsMessage, ApiError> {
+    let mut db_service = ChatDbService::new(db);
+    let message = db_service.find_message(message_id).await?;
+    let (session, role) = db_service
+        .get_session_with_role(user_id, &message.session_id)
+        .await?;
+    if !role.can_write() {
+        return Err(ApiError::Forbidden(
+            "Read-only members can't execute tool calls".into(),
+        ));
+    }
+    let turn_user_id = message
+        .meta
+        .assistant
+        .as_ref()
+        .and_then(|meta| meta.user_id)
+        .unwrap_or(session.user_id);
+    if turn_user_id != *user_id {
+        return Err(ApiError::Forbidden(
+            "Tool calls can only be executed by the member who sent the message".into(),
+        ));
+    }
+
+    Ok(message)
+}
+
+/// Maximum number of tool calls executed concurrently when executing all tool calls of a message
+const MAX_CONCURRENT_TOOL_CALLS: usize = 4;
+
+/// Execute a tool call and stream its output. Events are described by the
+/// `ToolStreamEvent` schema, between a `start` and an `end` event, with `ping` events while
+/// the tool is running. The execution continues if the client disconnects, and the output
+/// can be streamed again from `/api/tool/execute/<message_id>/<tool_call_id>/stream`.
 #[openapi(tag = "Tools")]
 #[post("/execute/<message_id>/<tool_call_id>")]
 async fn execute_tool(
-    user_id: ChatRsUserId,
+    user_id: ChatRsScopedUserId<ToolsExecute>,
     mut db: DbConnection,
+    streams: StreamClient,
+    stream_reader_client: ExclusiveStreamClient,
+    app_config: &State<AppConfig>,
     http_client: &State<reqwest::Client>,
     encryptor: &State<Encryptor>,
+    storage: &State<LocalStorage>,
+    knowledge: KnowledgeService,
     message_id: Uuid,
     tool_call_id: &str,
 ) -> Result<EventStream<Pin<Box<dyn Stream<Item = Event> + Send>>>, ApiError> {
     // Find message, tool call, and tool
-    let message = ChatDbService::new(&mut db)
-        .find_message(&user_id, &message_id)
-        .await?;
-    let tool_call = message
-        .meta
-        .assistant
-        .and_then(|meta| meta.tool_calls)
+    let message = find_tool_call_message(&mut db, &user_id, &message_id).await?;
+    let assistant_meta = message.meta.assistant.ok_or(ToolError::ToolCallNotFound)?;
+    if assistant_meta
+        .planned_tool_calls
+        .is_some_and(|planned| planned.iter().any(|tc| tc.id == tool_call_id))
+    {
+        return Err(ToolError::DryRunToolCall)?;
+    }
+    let tool_call = assistant_meta
+        .tool_calls
         .and_then(|tool_calls| {
             tool_calls
                 .into_iter()
                 .find(|tool_call| tool_call.id == tool_call_id)
         })
         .ok_or(ToolError::ToolCallNotFound)?;
-    let mut tool_db_service = ToolDbService::new(&mut db);
-    let (system_tool, external_api_tool, secret_1) = match tool_call.tool_type {
-        LlmToolType::System => {
-            let tool = tool_db_service
-                .find_system_tool_by_id(&user_id, &tool_call.tool_id)
-                .await?
-                .ok_or(ToolError::ToolNotFound)?;
-            (Some(tool), None, None)
+    tool_call.check_approval()?;
+    let allow_mcp_stdio = app_config.mcp_stdio.unwrap_or(false);
+    let tool = find_executable_tool(
+        &mut db,
+        encryptor,
+        http_client,
+        &user_id,
+        &tool_call,
+        allow_mcp_stdio,
+    )
+    .await?;
+
+    let writer = ToolStreamWriter::new(streams, &user_id, &message_id, Some(tool_call_id));
+    writer.start().await?;
+    let key = writer.key().to_owned();
+    let (streaming_tx, mut streaming_rx) = tokio::sync::mpsc::channel(50);
+    let http_client = http_client.inner().clone();
+    let tool_storage = ToolStorage::new(storage.inner().clone(), knowledge, *user_id)
+        .with_session(message.session_id);
+
+    // Spawn async task to execute tool, write its output to the stream, and save final
+    // result to database
+    tokio::spawn(async move {
+        let forward_logs = writer.forward(&mut streaming_rx);
+        let execute = run_tool_call(tool, tool_call, &http_client, &tool_storage, streaming_tx);
+        let (output, _) = tokio::join!(execute, forward_logs);
+        let _ = save_tool_call_output(&mut db, &user_id, &message.session_id, output).await;
+        writer.end().await;
+    });
+
+    // Stream output
+    read_tool_stream(stream_reader_client, key, None).await
+}
+
+/// Execute all tool calls of an assistant message concurrently, and stream their output.
+/// Events are described by the `BatchToolStreamEvent` schema and include the ID of the tool
+/// call they belong to, between a `start` and an `end` event, with `ping` events while the
+/// tools are running. The execution continues if the client disconnects, and the output can
+/// be streamed again from `/api/tool/execute/<message_id>/stream`.
+#[openapi(tag = "Tools")]
+#[post("/execute/<message_id>")]
+async fn execute_all_tools(
+    user_id: ChatRsScopedUserId<ToolsExecute>,
+    mut db: DbConnection,
+    streams: StreamClient,
+    stream_reader_client: ExclusiveStreamClient,
+    app_config: &State<AppConfig>,
+    http_client: &State<reqwest::Client>,
+    encryptor: &State<Encryptor>,
+    storage: &State<LocalStorage>,
+    knowledge: KnowledgeService,
+    message_id: Uuid,
+) -> Result<EventStream<Pin<Box<dyn Stream<Item = Event> + Send>>>, ApiError> {
+    // Find message, tool calls, and tools
+    let message = find_tool_call_message(&mut db, &user_id, &message_id).await?;
+    let assistant_meta = message.meta.assistant.ok_or(ToolError::ToolCallNotFound)?;
+    if assistant_meta.planned_tool_calls.is_some() {
+        return Err(ToolError::DryRunToolCall)?;
+    }
+    let tool_calls = assistant_meta
+        .tool_calls
+        .filter(|tool_calls| !tool_calls.is_empty())
+        .ok_or(ToolError::ToolCallNotFound)?;
+    let allow_mcp_stdio = app_config.mcp_stdio.unwrap_or(false);
+    let mut executions = Vec::with_capacity(tool_calls.len());
+    for tool_call in tool_calls {
+        tool_call.check_approval()?;
+        let tool = find_executable_tool(
+            &mut db,
+            encryptor,
+            http_client,
+            &user_id,
+            &tool_call,
+            allow_mcp_stdio,
+        )
+        .await?;
+        executions.push((tool, tool_call));
+    }
+
+    let writer = ToolStreamWriter::new(streams, &user_id, &message_id, None);
+    writer.start().await?;
+    let key = writer.key().to_owned();
+    let (streaming_tx, mut streaming_rx) = tokio::sync::mpsc::channel(50);
+    let http_client = http_client.inner().clone();
+    let tool_storage = ToolStorage::new(storage.inner().clone(), knowledge, *user_id)
+        .with_session(message.session_id);
+
+    // Spawn async task to write the output of the tools to the stream
+    tokio::spawn(async move {
+        writer.forward(&mut streaming_rx).await;
+        writer.end().await;
+    });
+
+    // Spawn async task to execute the tools, and save each result to database once finished
+    tokio::spawn(async move {
+        let (http_client, tool_storage) = (&http_client, &tool_storage);
+        let mut results = stream::iter(executions)
+            .map(|(tool, tool_call)| {
+                let streaming_tx = streaming_tx.clone();
+                async move {
+                    // Tag the tool's output with the tool call ID
+                    let tool_call_id = tool_call.id.clone();
+                    let (call_tx, mut call_rx) = tokio::sync::mpsc::channel(50);
+                    let forward_logs = async {
+                        while let Some(log) = call_rx.recv().await {
+                            let tool_call_id = tool_call_id.clone();
+                            let chunk = BatchToolLog { tool_call_id, log };
+                            if streaming_tx.send(chunk).await.is_err() {
+                                break; // Stream writer stopped
+                            }
+                        }
+                    };
+                    let execute =
+                        run_tool_call(tool, tool_call, http_client, tool_storage, call_tx);
+                    let (result, _) = tokio::join!(execute, forward_logs);
+                    result
+                }
+            })
+            .buffered(MAX_CONCURRENT_TOOL_CALLS);
+        while let Some(output) = results.next().await {
+            let _ = save_tool_call_output(&mut db, &user_id, &message.session_id, output).await;
         }
-        LlmToolType::ExternalApi => {
-            let (tool, secret) = tool_db_service
-                .find_external_api_tool_by_id(&user_id, &tool_call.tool_id)
-                .await?
-                .map(|(tool, secret)| {
-                    let secret = secret.map(|s| encryptor.decrypt_string(&s.ciphertext, &s.nonce));
-                    (tool, secret)
-                })
-                .ok_or(ToolError::ToolNotFound)?;
-            (None, Some(tool), secret.transpose()?)
+    });
+
+    // Stream output
+    read_tool_stream(stream_reader_client, key, None).await
+}
+
+/// # Connect to tool stream
+/// Stream the output of the execution of a tool call. Events are described by the
+/// `ToolStreamEvent` schema. Reconnecting clients can send the ID of the last received event
+/// in the `Last-Event-ID` header, to resume the stream after that event. The stream is
+/// available for 10 minutes after the execution has ended.
+#[openapi(tag = "Tools")]
+#[get("/execute/<message_id>/<tool_call_id>/stream")]
+async fn connect_to_tool_stream(
+    user_id: ChatRsScopedUserId<ToolsExecute>,
+    stream_reader_client: ExclusiveStreamClient,
+    message_id: Uuid,
+    tool_call_id: &str,
+    start_event_id: Option<LastEventId>,
+) -> Result<EventStream<Pin<Box<dyn Stream<Item = Event> + Send>>>, ApiError> {
+    let key = get_tool_stream_key(&user_id, &message_id, Some(tool_call_id));
+    read_tool_stream(stream_reader_client, key, start_event_id.as_deref()).await
+}
+
+/// # Connect to batch tool stream
+/// Stream the output of the execution of all tool calls of a message. Events are described
+/// by the `BatchToolStreamEvent` schema. Reconnecting clients can send the ID of the last
+/// received event in the `Last-Event-ID` header, to resume the stream after that event. The
+/// stream is available for 10 minutes after the execution has ended.
+#[openapi(tag = "Tools")]
+#[get("/execute/<message_id>/stream")]
+async fn connect_to_batch_tool_stream(
+    user_id: ChatRsScopedUserId<ToolsExecute>,
+    stream_reader_client: ExclusiveStreamClient,
+    message_id: Uuid,
+    start_event_id: Option<LastEventId>,
+) -> Result<EventStream<Pin<Box<dyn Stream<Item = Event> + Send>>>, ApiError> {
+    let key = get_tool_stream_key(&user_id, &message_id, None);
+    read_tool_stream(stream_reader_client, key, start_event_id.as_deref()).await
+}
+
+/// Stream the events of a tool output stream, after the given event ID if resuming
+async fn read_tool_stream(
+    stream_reader_client: ExclusiveStreamClient,
+    key: String,
+    start_event_id: Option<&str>,
+) -> Result<EventStream<Pin<Box<dyn Stream<Item = Event> + Send>>>, ApiError> {
+    let stream_reader = SseStreamReader::new(stream_reader_client.into_inner());
+    let (prev_events, last_event_id, is_end) =
+        stream_reader.get_prev_events(&key, start_event_id).await?;
+    let prev_events_stream = stream::iter(prev_events);
+    if is_end {
+        return Ok(EventStream::from(prev_events_stream.boxed()));
+    }
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<Event>(50);
+    tokio::spawn(async move {
+        stream_reader.stream(&key, &last_event_id, &tx).await;
+        drop(tx);
+    });
+
+    let stream = prev_events_stream.chain(ReceiverStream::new(rx)).boxed();
+    Ok(EventStream::from(stream))
+}
+
+/// Unsaved tool configuration to test
+#[derive(JsonSchema, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TestToolConfig {
+    /// System tool configuration
+    System(ChatRsSystemToolConfig),
+    /// External API tool configuration
+    ExternalApi {
+        /// The configuration for the external API tool
+        #[serde(flatten)]
+        config: ChatRsExternalApiToolConfig,
+        /// API key / secret key (not saved)
+        secret_1: Option<SecretInput>,
+        /// Additional secret (not saved)
+        secret_2: Option<SecretInput>,
+        /// Additional secret (not saved)
+        secret_3: Option<SecretInput>,
+    },
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct TestToolInput {
+    /// The tool configuration to test
+    tool: TestToolConfig,
+    /// Name of the tool to call, as given to the assistant
+    tool_name: String,
+    /// Sample input parameters for the tool
+    parameters: ToolParameters,
+    /// Execute the tool in safe mode after validating (default: false). Tools with side
+    /// effects, e.g. running code, custom API requests that don't use GET or HEAD, or GraphQL
+    /// mutations, are not executed.
+    execute: Option<bool>,
+}
+
+/// Test a tool configuration before saving it. Validates the configuration and the sample
+/// parameters, and optionally executes the tool in safe mode while streaming its output.
+/// Events are described by the `ToolStreamEvent` schema.
+#[openapi(tag = "Tools")]
+#[post("/test", data = "<input>")]
+async fn test_tool(
+    user_id: ChatRsUserId,
+    http_client: &State<reqwest::Client>,
+    storage: &State<LocalStorage>,
+    knowledge: KnowledgeService,
+    input: Json<TestToolInput>,
+) -> Result<EventStream<Pin<Box<dyn Stream<Item = Event> + Send>>>, ApiError> {
+    let input = input.into_inner();
+    let mut tool = match input.tool {
+        TestToolConfig::System(config) => UnsavedTool::System(config),
+        TestToolConfig::ExternalApi {
+            config,
+            secret_1,
+            secret_2,
+            secret_3,
+        } => {
+            for secret_input in [&secret_1, &secret_2, &secret_3] {
+                check_no_oauth2_secret(secret_input)?;
+            }
+            let secrets = EXTERNAL_API_SECRET_NAMES
+                .into_iter()
+                .zip([secret_1, secret_2, secret_3])
+                .filter_map(|(name, secret)| Some((name.to_owned(), secret?.key)))
+                .collect();
+            UnsavedTool::ExternalApi(config, secrets)
         }
     };
+    tool.validate(&input.tool_name, &input.parameters)?;
+    if !input.execute.unwrap_or(false) {
+        let chunk = ToolLog::Log("Configuration and parameters are valid".into());
+        return Ok(EventStream::from(
+            stream::once(async move { Event::from(chunk) }).boxed(),
+        ));
+    }
 
     let (streaming_tx, streaming_rx) = tokio::sync::mpsc::channel(50);
     let http_client = http_client.inner().clone();
-    let secrets = secret_1.into_iter().collect::<Vec<_>>();
+    let tool_storage = ToolStorage::new(storage.inner().clone(), knowledge, *user_id);
 
-    // Spawn async tasks to collect logs, execute tool, and save final result to database
+    // Spawn async task to execute the tool, without saving any result
     tokio::spawn(async move {
-        let (log_tx, mut log_rx) = tokio::sync::mpsc::channel(50);
-        let sender_with_logging = SenderWithLogging::new(streaming_tx, log_tx);
-
-        let log_collector_task = tokio::spawn(async move {
-            let mut logs = None;
-            let mut errors = None;
-            while let Some(chunk) = log_rx.recv().await {
-                match chunk {
-                    ToolLog::Log(data) => logs
-                        .get_or_insert_with(|| Vec::with_capacity(20))
-                        .push(data),
-                    ToolLog::Error(data) => errors
-                        .get_or_insert_with(|| Vec::with_capacity(5))
-                        .push(data),
-                    _ => {}
-                }
-            }
-            (logs, errors)
-        });
-
-        // Execute tool and collect logs
-        let tool_result = match (system_tool, external_api_tool) {
-            (Some(system_tool), None) => {
-                system_tool
-                    .build_executor()
-                    .validate_and_execute(
-                        &tool_call.tool_name,
-                        &tool_call.parameters,
-                        &sender_with_logging,
-                    )
-                    .await
-            }
-            (None, Some(api_tool)) => {
-                api_tool
-                    .build_executor()
-                    .validate_and_execute(
-                        &tool_call.tool_name,
-                        &tool_call.parameters,
-                        &secrets,
-                        &http_client,
-                        &sender_with_logging,
-                    )
-                    .await
-            }
-            _ => unreachable!(),
-        };
-        let (content, format, is_error) = match tool_result {
-            Ok((response, format)) => (response, format, None),
-            Err(e) => (e.to_string(), ToolResponseFormat::Text, Some(true)),
+        let (log_tx, _) = tokio::sync::mpsc::channel(1); // Logs aren't collected
+        let sender = SenderWithLogging::new(streaming_tx, log_tx).with_redactor(tool.redactor());
+        let chunk = match tool
+            .execute_safe(
+                &input.tool_name,
+                &input.parameters,
+                &http_client,
+                &tool_storage,
+                &sender,
+            )
+            .await
+        {
+            Ok((response, _)) => ToolLog::Result(response),
+            Err(err) => ToolLog::Error(err.to_string()),
         };
-        drop(sender_with_logging); // Drop sender to close logging channel
-        let (logs, errors) = log_collector_task.await.unwrap_or_default();
-
-        // Save final result to database
-        let _ = ChatDbService::new(&mut db)
-            .save_message(NewChatRsMessage {
-                session_id: &message.session_id,
-                role: ChatRsMessageRole::Tool,
-                content: &content,
-                meta: ChatRsMessageMeta {
-                    tool_call: Some(ChatRsExecutedToolCall {
-                        id: tool_call.id,
-                        tool_id: tool_call.tool_id,
-                        tool_name: tool_call.tool_name,
-                        tool_type: tool_call.tool_type,
-                        response_format: format,
-                        is_error,
-                        logs,
-                        errors,
-                    }),
-                    ..Default::default()
-                },
-            })
-            .await;
+        let _ = sender.send(chunk).await;
     });
 
     // Stream output
@@ -276,6 +1109,328 @@ async fn execute_tool(
     Ok(EventStream::from(stream))
 }
 
+#[derive(JsonSchema, serde::Deserialize)]
+struct UpdateSystemToolInput {
+    /// The new configuration for the system tool
+    config: Option<ChatRsSystemToolConfig>,
+    /// Whether the server may execute calls of this tool automatically, when chatting
+    /// in auto mode
+    auto_approve: Option<bool>,
+    #[serde(flatten)]
+    settings: ToolSettingsInput,
+}
+
+/// Update a system tool. Tool calls of previous messages keep referencing the tool.
+#[openapi(tag = "Tools")]
+#[patch("/system/<tool_id>", data = "<input>")]
+async fn update_system_tool(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    tool_id: Uuid,
+    input: Json<UpdateSystemToolInput>,
+) -> Result<Json<ChatRsSystemTool>, ApiError> {
+    if let Some(config) = &input.config {
+        config.validate()?;
+    }
+    let settings = input.settings.to_update()?;
+    let tool = ToolDbService::new(&mut db)
+        .find_system_tool_by_id(&user_id, &tool_id)
+        .await?
+        .ok_or(ToolError::ToolNotFound)?;
+    if input.config.is_none() && input.auto_approve.is_none() && settings.is_empty() {
+        return Ok(Json(tool));
+    }
+    let tool = ToolDbService::new(&mut db)
+        .update_system_tool(
+            &user_id,
+            &tool.id,
+            UpdateChatRsSystemTool {
+                data: input.config.as_ref(),
+                auto_approve: input.auto_approve,
+                cache_ttl: settings.cache_ttl,
+                max_calls_per_hour: settings.max_calls_per_hour,
+                max_calls_per_session: settings.max_calls_per_session,
+                timeout_seconds: settings.timeout_seconds,
+                max_output_bytes: settings.max_output_bytes,
+            },
+        )
+        .await?;
+
+    Ok(Json(tool))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct UpdateExternalApiToolInput {
+    /// The new configuration for the external API tool
+    config: Option<ChatRsExternalApiToolConfig>,
+    /// New API key / secret key, replacing the current one
+    secret_1: Option<SecretInput>,
+    /// New additional secret, replacing the current one
+    secret_2: Option<SecretInput>,
+    /// New additional secret, replacing the current one
+    secret_3: Option<SecretInput>,
+    /// Whether the server may execute calls of this tool automatically, when chatting
+    /// in auto mode
+    auto_approve: Option<bool>,
+    #[serde(flatten)]
+    settings: ToolSettingsInput,
+}
+
+/// Update an external API tool. Tool calls of previous messages keep referencing the tool.
+#[openapi(tag = "Tools")]
+#[patch("/external-api/<tool_id>", data = "<input>")]
+async fn update_external_api_tool(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    encryptor: &State<Encryptor>,
+    tool_id: Uuid,
+    input: Json<UpdateExternalApiToolInput>,
+) -> Result<Json<ChatRsExternalApiTool>, ApiError> {
+    let mut input = input.into_inner();
+    if let Some(config) = input.config.as_mut() {
+        config.validate()?;
+    }
+    let settings = input.settings.to_update()?;
+    let (tool, secrets) = ToolDbService::new(&mut db)
+        .find_external_api_tool_by_id(&user_id, &tool_id)
+        .await?
+        .ok_or(ToolError::ToolNotFound)?;
+    let mut secret_ids = [None; 3];
+    let secret_inputs = [&input.secret_1, &input.secret_2, &input.secret_3];
+    for ((secret_id, existing_secret), secret_input) in
+        secret_ids.iter_mut().zip(secrets).zip(secret_inputs)
+    {
+        if let Some(secret_input) = secret_input {
+            *secret_id = Some(
+                save_secret(&mut db, encryptor, &user_id, existing_secret, secret_input).await?,
+            );
+        }
+    }
+    let [secret_1_id, secret_2_id, secret_3_id] = secret_ids;
+    if input.config.is_none()
+        && secret_ids.iter().all(Option::is_none)
+        && input.auto_approve.is_none()
+        && settings.is_empty()
+    {
+        return Ok(Json(tool));
+    }
+    let tool = ToolDbService::new(&mut db)
+        .update_external_api_tool(
+            &user_id,
+            &tool.id,
+            UpdateChatRsExternalApiTool {
+                data: input.config.as_ref(),
+                secret_1: secret_1_id,
+                secret_2: secret_2_id,
+                secret_3: secret_3_id,
+                auto_approve: input.auto_approve,
+                cache_ttl: settings.cache_ttl,
+                max_calls_per_hour: settings.max_calls_per_hour,
+                max_calls_per_session: settings.max_calls_per_session,
+                timeout_seconds: settings.timeout_seconds,
+                max_output_bytes: settings.max_output_bytes,
+            },
+        )
+        .await?;
+
+    Ok(Json(tool))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct UpdateMcpToolInput {
+    /// New API key / secret key, replacing the current one
+    secret_1: Option<SecretInput>,
+    /// Whether the server may execute calls of this tool automatically, when chatting
+    /// in auto mode
+    auto_approve: Option<bool>,
+    #[serde(flatten)]
+    settings: ToolSettingsInput,
+}
+
+/// Update an MCP tool. Tool calls of previous messages keep referencing the tool.
+#[openapi(tag = "Tools")]
+#[patch("/mcp/<tool_id>", data = "<input>")]
+async fn update_mcp_tool(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    encryptor: &State<Encryptor>,
+    tool_id: Uuid,
+    input: Json<UpdateMcpToolInput>,
+) -> Result<Json<ChatRsMcpTool>, ApiError> {
+    check_no_oauth2_secret(&input.secret_1)?;
+    let settings = input.settings.to_update()?;
+    let (tool, secret_1) = ToolDbService::new(&mut db)
+        .find_mcp_tool_by_id(&user_id, &tool_id)
+        .await?
+        .ok_or(ToolError::ToolNotFound)?;
+    let secret_1_id = match &input.secret_1 {
+        Some(secret_input) => {
+            Some(save_secret(&mut db, encryptor, &user_id, secret_1, secret_input).await?)
+        }
+        None => None,
+    };
+    if secret_1_id.is_none() && input.auto_approve.is_none() && settings.is_empty() {
+        return Ok(Json(tool));
+    }
+    let tool = ToolDbService::new(&mut db)
+        .update_mcp_tool(
+            &user_id,
+            &tool.id,
+            UpdateChatRsMcpTool {
+                secret_1: secret_1_id,
+                auto_approve: input.auto_approve,
+                cache_ttl: settings.cache_ttl,
+                max_calls_per_hour: settings.max_calls_per_hour,
+                max_calls_per_session: settings.max_calls_per_session,
+                timeout_seconds: settings.timeout_seconds,
+                max_output_bytes: settings.max_output_bytes,
+            },
+        )
+        .await?;
+
+    Ok(Json(tool))
+}
+
+/// Replace the key of the tool's existing secret, or create a new secret
+async fn save_secret(
+    db: &mut DbConnection,
+    encryptor: &Encryptor,
+    user_id: &Uuid,
+    existing_secret: Option<ChatRsSecret>,
+    secret_input: &SecretInput,
+) -> Result<Uuid, ApiError> {
+    let (ciphertext, nonce, oauth2) = secret_input.encrypt(encryptor)?;
+    let mut secret_db_service = SecretDbService::new(db);
+    let secret_id = match existing_secret {
+        Some(secret) => {
+            secret_db_service
+                .update(
+                    user_id,
+                    &secret.id,
+                    UpdateChatRsSecret {
+                        name: Some(&secret_input.name),
+                        ciphertext: Some(&ciphertext),
+                        nonce: Some(&nonce),
+                        oauth2: Some(oauth2.as_ref()),
+                    },
+                )
+                .await?
+        }
+        None => {
+            secret_db_service
+                .create(NewChatRsSecret {
+                    user_id,
+                    name: &secret_input.name,
+                    ciphertext: &ciphertext,
+                    nonce: &nonce,
+                    oauth2: oauth2.as_ref(),
+                })
+                .await?
+        }
+    };
+
+    Ok(secret_id)
+}
+
+/// OAuth2 credentials are only supported by saved external API tools
+fn check_no_oauth2_secret(secret_input: &Option<SecretInput>) -> Result<(), ToolError> {
+    if secret_input.as_ref().is_some_and(|s| s.oauth2.is_some()) {
+        return Err(ToolError::InvalidConfiguration(
+            "OAuth2 credentials are only supported for saved external API tools".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Max seconds to cache tool results
+const MAX_CACHE_TTL: u32 = 7 * 24 * 60 * 60;
+/// Max value of the call limits of tools
+const MAX_CALL_LIMIT: u32 = 100_000;
+/// Max configurable timeout of tools, in seconds
+const MAX_TIMEOUT_SECONDS: u32 = 60 * 60;
+/// Max configurable output size of tools, in bytes
+const MAX_OUTPUT_BYTES: u32 = 10 * 1024 * 1024;
+
+/// Optional settings of a tool
+#[derive(JsonSchema, serde::Serialize, serde::Deserialize)]
+struct ToolSettingsInput {
+    /// Seconds to cache the results of identical calls of this tool (max 1 week). Set to
+    /// 0 to disable caching.
+    cache_ttl: Option<u32>,
+    /// Max number of calls of this tool per hour. Set to 0 to remove the limit.
+    max_calls_per_hour: Option<u32>,
+    /// Max number of calls of this tool per chat session. Set to 0 to remove the limit.
+    max_calls_per_session: Option<u32>,
+    /// Max seconds to execute a call of this tool (default: 300, max: 3600). Set to 0 to
+    /// use the default.
+    timeout_seconds: Option<u32>,
+    /// Max size of the output of this tool in bytes. Longer output is truncated (default:
+    /// 1 MiB, max: 10 MiB). Set to 0 to use the default.
+    max_output_bytes: Option<u32>,
+}
+
+/// Validated updates of the tool's settings. `Some(None)` unsets a setting.
+struct ToolSettingsUpdate {
+    cache_ttl: Option<Option<i32>>,
+    max_calls_per_hour: Option<Option<i32>>,
+    max_calls_per_session: Option<Option<i32>>,
+    timeout_seconds: Option<Option<i32>>,
+    max_output_bytes: Option<Option<i32>>,
+}
+
+impl ToolSettingsInput {
+    /// Get the input from the tool's current settings
+    fn new(
+        cache_ttl: Option<i32>,
+        max_calls_per_hour: Option<i32>,
+        max_calls_per_session: Option<i32>,
+        timeout_seconds: Option<i32>,
+        max_output_bytes: Option<i32>,
+    ) -> Self {
+        let to_u32 = |value: Option<i32>| value.and_then(|v| v.try_into().ok());
+        Self {
+            cache_ttl: to_u32(cache_ttl),
+            max_calls_per_hour: to_u32(max_calls_per_hour),
+            max_calls_per_session: to_u32(max_calls_per_session),
+            timeout_seconds: to_u32(timeout_seconds),
+            max_output_bytes: to_u32(max_output_bytes),
+        }
+    }
+
+    /// Validate the settings, and get the updates of the tool's columns (0 unsets a setting)
+    fn to_update(&self) -> Result<ToolSettingsUpdate, ToolError> {
+        let update = |value: Option<u32>, max: u32, name: &str| match value {
+            Some(value) if value > max => Err(ToolError::InvalidConfiguration(format!(
+                "{name} can't be more than {max}"
+            ))),
+            Some(0) => Ok(Some(None)),
+            Some(value) => Ok(Some(Some(value as i32))),
+            None => Ok(None),
+        };
+        Ok(ToolSettingsUpdate {
+            cache_ttl: update(self.cache_ttl, MAX_CACHE_TTL, "Cache TTL")?,
+            max_calls_per_hour: update(self.max_calls_per_hour, MAX_CALL_LIMIT, "Call limit")?,
+            max_calls_per_session: update(
+                self.max_calls_per_session,
+                MAX_CALL_LIMIT,
+                "Call limit",
+            )?,
+            timeout_seconds: update(self.timeout_seconds, MAX_TIMEOUT_SECONDS, "Timeout")?,
+            max_output_bytes: update(self.max_output_bytes, MAX_OUTPUT_BYTES, "Max output size")?,
+        })
+    }
+}
+
+impl ToolSettingsUpdate {
+    fn is_empty(&self) -> bool {
+        self.cache_ttl.is_none()
+            && self.max_calls_per_hour.is_none()
+            && self.max_calls_per_session.is_none()
+            && self.timeout_seconds.is_none()
+            && self.max_output_bytes.is_none()
+    }
+}
+
 /// Delete a system tool
 #[openapi(tag = "Tools")]
 #[delete("/system/<tool_id>")]
@@ -299,11 +1454,11 @@ async fn delete_external_api_tool(
     mut db: DbConnection,
     tool_id: Uuid,
 ) -> Result<String, ApiError> {
-    let (tool, secret_1) = ToolDbService::new(&mut db)
+    let (tool, secrets) = ToolDbService::new(&mut db)
         .find_external_api_tool_by_id(&user_id, &tool_id)
         .await?
         .ok_or(ToolError::ToolNotFound)?;
-    if let Some(secret) = secret_1 {
+    for secret in secrets.into_iter().flatten() {
         let _ = SecretDbService::new(&mut db)
             .delete(&user_id, &secret.id)
             .await?;
@@ -314,3 +1469,27 @@ async fn delete_external_api_tool(
 
     Ok(id.to_string())
 }
+
+/// Delete an MCP tool
+#[openapi(tag = "Tools")]
+#[delete("/mcp/<tool_id>")]
+async fn delete_mcp_tool(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    tool_id: Uuid,
+) -> Result<String, ApiError> {
+    let (tool, secret_1) = ToolDbService::new(&mut db)
+        .find_mcp_tool_by_id(&user_id, &tool_id)
+        .await?
+        .ok_or(ToolError::ToolNotFound)?;
+    if let Some(secret) = secret_1 {
+        let _ = SecretDbService::new(&mut db)
+            .delete(&user_id, &secret.id)
+            .await?;
+    }
+    let id = ToolDbService::new(&mut db)
+        .delete_mcp_tool(&user_id, &tool.id)
+        .await?;
+
+    Ok(id.to_string())
+}
@@ -0,0 +1,152 @@
+use chrono::{DateTime, Days, NaiveDate, NaiveTime, Utc};
+use rocket::{
+    form::{self, FromFormField, ValueField},
+    get,
+    serde::json::Json,
+    Route,
+};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+use uuid::Uuid;
+
+use crate::{
+    api::organization::get_member_role,
+    auth::ChatRsUserId,
+    db::{
+        models::{ChatRsOrganizationUsage, ChatRsUsageSummary, UsageGroupBy},
+        services::UsageDbService,
+        DbConnection,
+    },
+    errors::ApiError,
+};
+
+pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
+    openapi_get_routes_spec![settings: get_usage_summary, get_organization_usage]
+}
+
+/// Default number of days included in the usage summary
+const DEFAULT_SUMMARY_DAYS: u64 = 30;
+
+/// A date query parameter, formatted as `YYYY-MM-DD`
+#[derive(Debug, Clone, Copy, JsonSchema)]
+#[schemars(transparent)]
+struct QueryDate(NaiveDate);
+
+#[rocket::async_trait]
+impl<'v> FromFormField<'v> for QueryDate {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        NaiveDate::parse_from_str(field.value, "%Y-%m-%d")
+            .map(QueryDate)
+            .map_err(|_| form::Error::validation("Invalid date (expected YYYY-MM-DD)").into())
+    }
+}
+
+#[derive(Default, JsonSchema, serde::Serialize)]
+struct UsageTotals {
+    /// Number of assistant messages
+    messages: i64,
+    input_tokens: i64,
+    output_tokens: i64,
+    /// Total cost, if reported by the providers (only OpenRouter)
+    cost: Option<f64>,
+}
+
+#[derive(JsonSchema, serde::Serialize)]
+struct UsageSummaryResponse {
+    /// First day of the summary (UTC)
+    from: NaiveDate,
+    /// Last day of the summary (UTC)
+    to: NaiveDate,
+    /// Totals across all groups
+    totals: UsageTotals,
+    /// Usage of each day, model, or provider
+    groups: Vec<ChatRsUsageSummary>,
+}
+
+/// # Get usage summary
+/// Get the token usage and cost of the assistant messages between the `from` and `to` dates
+/// (inclusive, UTC), grouped by day, model, or provider. Defaults to the last 30 days,
+/// grouped by day.
+#[openapi(tag = "Usage")]
+#[get("/summary?<from>&<to>&<group_by>")]
+async fn get_usage_summary(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    from: Option<QueryDate>,
+    to: Option<QueryDate>,
+    group_by: Option<UsageGroupBy>,
+) -> Result<Json<UsageSummaryResponse>, ApiError> {
+    let (from, to) = get_date_range(from, to);
+    let (start, end) = (start_of_day(from), start_of_day(to + Days::new(1)));
+    let groups = UsageDbService::new(&mut db)
+        .summary(&user_id, start, end, group_by.unwrap_or_default())
+        .await?;
+
+    let mut totals = UsageTotals::default();
+    for group in &groups {
+        totals.messages += group.messages;
+        totals.input_tokens += group.input_tokens;
+        totals.output_tokens += group.output_tokens;
+        if let Some(cost) = group.cost {
+            *totals.cost.get_or_insert(0.0) += cost;
+        }
+    }
+
+    Ok(Json(UsageSummaryResponse {
+        from,
+        to,
+        totals,
+        groups,
+    }))
+}
+
+/// Get the `from` and `to` dates of a summary, defaulting to the last 30 days
+fn get_date_range(from: Option<QueryDate>, to: Option<QueryDate>) -> (NaiveDate, NaiveDate) {
+    let to = to.map_or_else(|| Utc::now().date_naive(), |date| date.0);
+    let from = from.map_or_else(|| to - Days::new(DEFAULT_SUMMARY_DAYS - 1), |date| date.0);
+    (from, to)
+}
+
+fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    date.and_time(NaiveTime::MIN).and_utc()
+}
+
+#[derive(JsonSchema, serde::Serialize)]
+struct OrganizationUsageResponse {
+    /// First day of the summary (UTC)
+    from: NaiveDate,
+    /// Last day of the summary (UTC)
+    to: NaiveDate,
+    /// Usage of each member
+    members: Vec<ChatRsOrganizationUsage>,
+}
+
+/// # Get organization usage
+/// Get the token usage and cost of each member of an organization with the providers shared
+/// with the organization, between the `from` and `to` dates (inclusive, UTC). Defaults to the
+/// last 30 days. Only the owner and admins of the organization can view its usage.
+#[openapi(tag = "Usage")]
+#[get("/organization/<organization_id>?<from>&<to>")]
+async fn get_organization_usage(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    organization_id: Uuid,
+    from: Option<QueryDate>,
+    to: Option<QueryDate>,
+) -> Result<Json<OrganizationUsageResponse>, ApiError> {
+    let role = get_member_role(&mut db, &organization_id, &user_id).await?;
+    if !role.can_manage() {
+        return Err(ApiError::Forbidden(
+            "Only the owner and admins can view the organization's usage".into(),
+        ));
+    }
+    let (from, to) = get_date_range(from, to);
+    let (start, end) = (start_of_day(from), start_of_day(to + Days::new(1)));
+    let members = UsageDbService::new(&mut db)
+        .organization_summary(&organization_id, start, end)
+        .await?;
+
+    Ok(Json(OrganizationUsageResponse { from, to, members }))
+}
@@ -0,0 +1,281 @@
+use chrono::Utc;
+use rocket::{delete, get, patch, post, serde::json::Json, Route, State};
+use rocket_flex_session::Session;
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        revoke_all_auth_sessions, start_impersonation_session, AdminUserId, AuthClientInfo,
+        ChatRsAuthSession,
+    },
+    db::{
+        models::{ChatRsUser, ChatRsUserRole, UpdateChatRsUser},
+        pagination::ListQuery,
+        services::{
+            ApiKeyDbService, ChatDbService, JobDbService, MemoryDbService, PresetDbService,
+            ProviderDbService, SecretDbService, ServiceAccountDbService, ToolDbService,
+            UserDbService,
+        },
+        DbConnection,
+    },
+    errors::ApiError,
+    redis::{delete_user_keys, RedisClient},
+    storage::{LocalStorage, StorageUsage},
+    tools::delete_code_runner_workspace,
+};
+
+/// User management routes (admins only)
+pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
+    openapi_get_routes_spec![
+        settings: list_users,
+        update_user_role,
+        disable_user,
+        enable_user,
+        get_user_storage,
+        delete_user,
+        impersonate_user
+    ]
+}
+
+/// # List users
+/// List the users of the server, with the size of their stored files. The filter matches user
+/// names and emails.
+#[openapi(tag = "Admin")]
+#[get("/?<query..>")]
+async fn list_users(
+    _admin_id: AdminUserId,
+    mut db: DbConnection,
+    query: ListQuery,
+) -> Result<Json<Vec<ChatRsUser>>, ApiError> {
+    let users = UserDbService::new(&mut db).list(&query).await?;
+
+    Ok(Json(users))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct UserRoleInput {
+    role: ChatRsUserRole,
+}
+
+/// # Update user role
+/// Promote a user to admin, or demote them to member. The last admin can't be demoted.
+#[openapi(tag = "Admin")]
+#[patch("/<user_id>/role", data = "<input>")]
+async fn update_user_role(
+    _admin_id: AdminUserId,
+    mut db: DbConnection,
+    user_id: Uuid,
+    input: Json<UserRoleInput>,
+) -> Result<String, ApiError> {
+    let mut db_service = UserDbService::new(&mut db);
+    let current_role = db_service
+        .find_role(&user_id)
+        .await?
+        .ok_or(diesel::result::Error::NotFound)?;
+    let admin_role: &str = (&ChatRsUserRole::Admin).into();
+    if input.role == ChatRsUserRole::Member
+        && current_role == admin_role
+        && db_service.count_by_role(admin_role).await? <= 1
+    {
+        return Err(ApiError::Forbidden(
+            "Can't demote the last admin".to_owned(),
+        ));
+    }
+    db_service
+        .update(
+            &user_id,
+            UpdateChatRsUser {
+                role: Some((&input.role).into()),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    Ok(user_id.to_string())
+}
+
+/// Check that the admin isn't managing their own account through the admin routes
+fn check_not_self(admin_id: &AdminUserId, user_id: &Uuid) -> Result<(), ApiError> {
+    if **admin_id == *user_id {
+        return Err(ApiError::Forbidden(
+            "Admins can't disable, delete, or impersonate themselves".to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+/// # Disable user
+/// Disable the user and log out all of their sessions. Disabled users can't log in or use
+/// their API keys, but their data is kept.
+#[openapi(tag = "Admin")]
+#[post("/<user_id>/disable")]
+async fn disable_user(
+    admin_id: AdminUserId,
+    mut db: DbConnection,
+    redis: RedisClient,
+    user_id: Uuid,
+) -> Result<String, ApiError> {
+    check_not_self(&admin_id, &user_id)?;
+    UserDbService::new(&mut db)
+        .update(
+            &user_id,
+            UpdateChatRsUser {
+                disabled_at: Some(Some(Utc::now())),
+                ..Default::default()
+            },
+        )
+        .await?;
+    revoke_all_auth_sessions(&redis, &user_id).await?;
+    rocket::info!("Admin {} disabled user {}", *admin_id, user_id);
+
+    Ok(user_id.to_string())
+}
+
+/// # Enable user
+/// Enable a disabled user
+#[openapi(tag = "Admin")]
+#[post("/<user_id>/enable")]
+async fn enable_user(
+    _admin_id: AdminUserId,
+    mut db: DbConnection,
+    user_id: Uuid,
+) -> Result<String, ApiError> {
+    UserDbService::new(&mut db)
+        .update(
+            &user_id,
+            UpdateChatRsUser {
+                disabled_at: Some(None),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    Ok(user_id.to_string())
+}
+
+/// # Get user storage
+/// Get the total size of the user's stored files, and their storage quota
+#[openapi(tag = "Admin")]
+#[get("/<user_id>/storage")]
+async fn get_user_storage(
+    _admin_id: AdminUserId,
+    mut db: DbConnection,
+    storage: &State<LocalStorage>,
+    user_id: Uuid,
+) -> Result<Json<StorageUsage>, ApiError> {
+    UserDbService::new(&mut db)
+        .find_by_id(&user_id)
+        .await?
+        .ok_or(diesel::result::Error::NotFound)?;
+    drop(db);
+
+    Ok(Json(storage.usage(&user_id).await?))
+}
+
+/// # Delete user
+/// Delete the user and all associated data. ⚠️ WARNING: This action is irreversible.
+#[openapi(tag = "Admin")]
+#[delete("/<user_id>")]
+async fn delete_user(
+    admin_id: AdminUserId,
+    mut db: DbConnection,
+    storage: &State<LocalStorage>,
+    redis: RedisClient,
+    user_id: Uuid,
+) -> Result<String, ApiError> {
+    check_not_self(&admin_id, &user_id)?;
+    UserDbService::new(&mut db)
+        .find_by_id(&user_id)
+        .await?
+        .ok_or(diesel::result::Error::NotFound)?;
+    rocket::warn!("Admin {} is deleting user {}", *admin_id, user_id);
+
+    delete_user_data(&mut db, storage, &redis, &user_id).await
+}
+
+/// # Impersonate user
+/// Log in as the user for debugging. This replaces the admin's current session, so the admin
+/// needs to log in again afterwards. The session is listed in the user's login sessions.
+#[openapi(tag = "Admin")]
+#[post("/<user_id>/impersonate")]
+async fn impersonate_user(
+    admin_id: AdminUserId,
+    mut db: DbConnection,
+    redis: RedisClient,
+    mut session: Session<'_, ChatRsAuthSession>,
+    client_info: AuthClientInfo,
+    user_id: Uuid,
+) -> Result<String, ApiError> {
+    check_not_self(&admin_id, &user_id)?;
+    let user = UserDbService::new(&mut db)
+        .find_by_id(&user_id)
+        .await?
+        .ok_or(diesel::result::Error::NotFound)?;
+    if user.disabled_at.is_some() {
+        return Err(ApiError::Forbidden("User is disabled".to_owned()));
+    }
+    if ServiceAccountDbService::new(&mut db)
+        .is_service_account(&user_id)
+        .await?
+    {
+        return Err(ApiError::Forbidden(
+            "Service accounts can only use API keys".to_owned(),
+        ));
+    }
+    rocket::warn!("Admin {} is impersonating user {}", *admin_id, user_id);
+    start_impersonation_session(&mut session, &redis, &client_info, user.id, *admin_id).await?;
+
+    Ok(user_id.to_string())
+}
+
+/// Delete the user and all associated data: sessions (and their code runner workspaces),
+/// presets, jobs, providers, tools, secrets, API keys, memories, stored files, login sessions,
+/// and other Redis keys
+pub async fn delete_user_data(
+    db: &mut DbConnection,
+    storage: &LocalStorage,
+    redis: &fred::clients::Client,
+    user_id: &Uuid,
+) -> Result<String, ApiError> {
+    let sessions = ChatDbService::new(db).delete_by_user(user_id).await?;
+    let presets = PresetDbService::new(db).delete_by_user(user_id).await?;
+    let jobs = JobDbService::new(db).delete_by_user(user_id).await?;
+    let providers = ProviderDbService::new(db).delete_by_user(user_id).await?;
+    let tools = ToolDbService::new(db).delete_by_user(user_id).await?;
+    let secrets = SecretDbService::new(db).delete_by_user(user_id).await?;
+    let api_keys = ApiKeyDbService::new(db).delete_by_user(user_id).await?;
+    let memories = MemoryDbService::new(db).delete_by_user(user_id).await?;
+
+    storage.delete_by_user(user_id).await?;
+    for session_id in &sessions {
+        if let Err(err) = delete_code_runner_workspace(session_id).await {
+            rocket::debug!(
+                "Failed to delete workspace of session {}: {}",
+                session_id,
+                err
+            );
+        }
+    }
+
+    revoke_all_auth_sessions(redis, user_id).await?;
+    delete_user_keys(redis, user_id).await?;
+    UserDbService::new(db).delete(user_id).await?;
+
+    Ok(format!(
+        "Deleted user {}:  {} providers, {} presets, {} jobs, {} sessions, {} tools, \
+        {} secrets, {} API keys, {} memories",
+        user_id,
+        providers.len(),
+        presets.len(),
+        jobs.len(),
+        sessions.len(),
+        tools.len(),
+        secrets.len(),
+        api_keys.len(),
+        memories.len()
+    ))
+}
@@ -0,0 +1,239 @@
+use rocket::{delete, get, patch, post, serde::json::Json, Route, State};
+use rocket_okapi::{
+    okapi::openapi3::OpenApi, openapi, openapi_get_routes_spec, settings::OpenApiSettings,
+};
+use schemars::JsonSchema;
+use uuid::Uuid;
+
+use crate::{
+    auth::ChatRsUserId,
+    db::{
+        models::{
+            ChatRsWebhook, ChatRsWebhookDelivery, ChatRsWebhookEvent, NewChatRsWebhook,
+            UpdateChatRsWebhook,
+        },
+        services::WebhookDbService,
+        DbConnection,
+    },
+    errors::ApiError,
+    utils::Encryptor,
+    webhooks::{generate_secret, queue_deliveries, validate_url, WebhookError, MAX_WEBHOOKS},
+};
+
+/// Max number of deliveries returned when listing deliveries
+const MAX_DELIVERIES_LIMIT: i64 = 100;
+
+pub fn get_routes(settings: &OpenApiSettings) -> (Vec<Route>, OpenApi) {
+    openapi_get_routes_spec![
+        settings: get_all_webhooks,
+        create_webhook,
+        update_webhook,
+        delete_webhook,
+        get_deliveries,
+        ping_webhook
+    ]
+}
+
+/// # List webhooks
+/// List all webhooks
+#[openapi(tag = "Webhooks")]
+#[get("/")]
+async fn get_all_webhooks(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+) -> Result<Json<Vec<ChatRsWebhook>>, ApiError> {
+    let webhooks = WebhookDbService::new(&mut db)
+        .find_by_user_id(&user_id)
+        .await?;
+
+    Ok(Json(webhooks))
+}
+
+#[derive(JsonSchema, serde::Serialize)]
+struct WebhookResponse {
+    #[serde(flatten)]
+    webhook: ChatRsWebhook,
+    /// The signing secret of the webhook. Only returned when the webhook is created, or when
+    /// the secret is rotated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secret: Option<String>,
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct WebhookCreateInput {
+    /// Name of the webhook
+    name: String,
+    /// URL that the events are POSTed to
+    url: String,
+    /// The events that trigger the webhook
+    events: Vec<ChatRsWebhookEvent>,
+    /// Whether the webhook is called (default: true)
+    enabled: Option<bool>,
+}
+
+/// # Create webhook
+/// Create a webhook that is called on chat events. Each event is POSTed to the URL as JSON,
+/// with the event in the `X-RsChat-Event` header and the delivery ID in the
+/// `X-RsChat-Delivery` header. The body is signed with HMAC-SHA256 using the returned secret,
+/// in the `X-Signature-256` header (`sha256=<hex signature>`). Failed deliveries are retried
+/// up to 5 times.
+#[openapi(tag = "Webhooks")]
+#[post("/", data = "<input>")]
+async fn create_webhook(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    encryptor: &State<Encryptor>,
+    input: Json<WebhookCreateInput>,
+) -> Result<Json<WebhookResponse>, ApiError> {
+    let count = WebhookDbService::new(&mut db)
+        .count_by_user_id(&user_id)
+        .await?;
+    if count >= MAX_WEBHOOKS {
+        return Err(WebhookError::TooManyWebhooks(MAX_WEBHOOKS))?;
+    }
+    validate_url(&input.url)?;
+    let events = get_event_names(&input.events)?;
+    let secret = generate_secret();
+    let (ciphertext, nonce) = encryptor.encrypt_string(&secret)?;
+    let webhook = WebhookDbService::new(&mut db)
+        .create(NewChatRsWebhook {
+            user_id: &user_id,
+            name: &input.name,
+            url: &input.url,
+            events: &events,
+            secret_ciphertext: &ciphertext,
+            secret_nonce: &nonce,
+            enabled: input.enabled.unwrap_or(true),
+        })
+        .await?;
+
+    Ok(Json(WebhookResponse {
+        webhook,
+        secret: Some(secret),
+    }))
+}
+
+#[derive(JsonSchema, serde::Deserialize)]
+struct WebhookUpdateInput {
+    /// Name of the webhook
+    name: Option<String>,
+    /// URL that the events are POSTed to
+    url: Option<String>,
+    /// The events that trigger the webhook
+    events: Option<Vec<ChatRsWebhookEvent>>,
+    /// Whether the webhook is called
+    enabled: Option<bool>,
+    /// Generate a new signing secret
+    rotate_secret: Option<bool>,
+}
+
+/// # Update webhook
+/// Update a webhook. Pending deliveries are sent with the updated URL and secret.
+#[openapi(tag = "Webhooks")]
+#[patch("/<webhook_id>", data = "<input>")]
+async fn update_webhook(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    encryptor: &State<Encryptor>,
+    webhook_id: Uuid,
+    input: Json<WebhookUpdateInput>,
+) -> Result<Json<WebhookResponse>, ApiError> {
+    if let Some(url) = &input.url {
+        validate_url(url)?;
+    }
+    let events = input.events.as_deref().map(get_event_names).transpose()?;
+    let secret = input.rotate_secret.unwrap_or(false).then(generate_secret);
+    let encrypted_secret = secret
+        .as_deref()
+        .map(|secret| encryptor.encrypt_string(secret))
+        .transpose()?;
+    let webhook = WebhookDbService::new(&mut db)
+        .update(
+            &user_id,
+            &webhook_id,
+            UpdateChatRsWebhook {
+                name: input.name.as_deref(),
+                url: input.url.as_deref(),
+                events: events.as_ref(),
+                secret_ciphertext: encrypted_secret.as_ref().map(|(ciphertext, _)| ciphertext),
+                secret_nonce: encrypted_secret.as_ref().map(|(_, nonce)| nonce),
+                enabled: input.enabled,
+            },
+        )
+        .await?;
+
+    Ok(Json(WebhookResponse { webhook, secret }))
+}
+
+/// # Delete webhook
+/// Delete a webhook and its pending deliveries
+#[openapi(tag = "Webhooks")]
+#[delete("/<webhook_id>")]
+async fn delete_webhook(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    webhook_id: Uuid,
+) -> Result<String, ApiError> {
+    let id = WebhookDbService::new(&mut db)
+        .delete(&user_id, &webhook_id)
+        .await?;
+
+    Ok(id.to_string())
+}
+
+/// # List webhook deliveries
+/// List the latest deliveries of a webhook (default: 50, max: 100). Finished deliveries are
+/// kept for 7 days.
+#[openapi(tag = "Webhooks")]
+#[get("/<webhook_id>/deliveries?<limit>")]
+async fn get_deliveries(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    webhook_id: Uuid,
+    limit: Option<i64>,
+) -> Result<Json<Vec<ChatRsWebhookDelivery>>, ApiError> {
+    let limit = limit.unwrap_or(50).clamp(1, MAX_DELIVERIES_LIMIT);
+    let deliveries = WebhookDbService::new(&mut db)
+        .find_deliveries(&user_id, &webhook_id, limit)
+        .await?;
+
+    Ok(Json(deliveries))
+}
+
+/// # Ping webhook
+/// Queue a `ping` event to the webhook, to test it. Returns the ID of the delivery.
+#[openapi(tag = "Webhooks")]
+#[post("/<webhook_id>/ping")]
+async fn ping_webhook(
+    user_id: ChatRsUserId,
+    mut db: DbConnection,
+    webhook_id: Uuid,
+) -> Result<String, ApiError> {
+    let webhook = WebhookDbService::new(&mut db)
+        .find_by_id(&user_id, &webhook_id)
+        .await?;
+    let data = serde_json::json!({ "webhook_id": webhook.id });
+    let delivery_ids =
+        queue_deliveries(&mut db, &[webhook], ChatRsWebhookEvent::Ping, data).await?;
+
+    Ok(delivery_ids
+        .first()
+        .map(|id| id.to_string())
+        .unwrap_or_default())
+}
+
+/// Get the names of the events to save, without duplicates
+fn get_event_names(events: &[ChatRsWebhookEvent]) -> Result<Vec<String>, WebhookError> {
+    let mut names: Vec<String> = Vec::with_capacity(events.len());
+    for event in events {
+        let name: &str = event.into();
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_owned());
+        }
+    }
+    if names.is_empty() {
+        return Err(WebhookError::NoEvents);
+    }
+
+    Ok(names)
+}
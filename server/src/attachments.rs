@@ -0,0 +1,226 @@
+//! Files uploaded by the user and attached to chat messages or added to projects. Images and
+//! PDFs are given to multimodal providers, and text files and the extracted text of other
+//! documents (DOCX and HTML) are included in the message text.
+
+use std::{collections::HashMap, path::Path};
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        models::{
+            ChatRsAttachment, ChatRsMessage, ChatRsMessageRole, ChatRsProject, NewChatRsAttachment,
+        },
+        services::AttachmentDbService,
+        DbConnection,
+    },
+    errors::ApiError,
+    provider::LlmAttachment,
+    storage::{LocalStorage, StorageError, DOCX_MIME_TYPE},
+};
+
+/// Max size of an attached file, in MiB
+pub const MAX_ATTACHMENT_SIZE_MIB: u64 = 20;
+/// Max number of files attached to a message
+pub const MAX_ATTACHMENTS: usize = 10;
+/// Max number of files added to a project
+pub const MAX_PROJECT_FILES: usize = 20;
+
+/// Instructions and files of a project, given to the assistant in the project's sessions
+#[derive(Debug, Default)]
+pub struct ProjectContext {
+    /// The project's instructions and text files
+    pub system_prompt: Option<String>,
+    /// The project's images and PDFs, attached to the first user message
+    pub attachments: Vec<LlmAttachment>,
+}
+
+/// A stored file loaded for the provider
+enum LoadedFile {
+    /// Text file, formatted to be included in a message
+    Text(String),
+    File(LlmAttachment),
+}
+
+/// Save an uploaded file to the storage, ready to be attached to a message
+pub async fn save_attachment(
+    db: &mut DbConnection,
+    storage: &LocalStorage,
+    user_id: &Uuid,
+    name: &str,
+    mime_type: &str,
+    data: &[u8],
+) -> Result<ChatRsAttachment, ApiError> {
+    let storage_path = storage.save(user_id, data, mime_type).await?;
+    let attachment = AttachmentDbService::new(db)
+        .create(NewChatRsAttachment {
+            user_id,
+            name: name.trim(),
+            mime_type,
+            storage_path: &storage_path,
+            size: i32::try_from(data.len()).unwrap_or(i32::MAX),
+        })
+        .await?;
+
+    Ok(attachment)
+}
+
+/// Check that the uploaded files exist, aren't attached to another message or project yet,
+/// and that there are at most `max_files` of them
+pub async fn check_attachments(
+    db: &mut DbConnection,
+    user_id: &Uuid,
+    attachment_ids: &[Uuid],
+    max_files: usize,
+) -> Result<(), ApiError> {
+    if attachment_ids.len() > max_files {
+        return Err(StorageError::TooManyFiles(max_files))?;
+    }
+    let attachments = AttachmentDbService::new(db)
+        .find_unattached(user_id, attachment_ids)
+        .await?;
+    if attachments.len() != attachment_ids.len() {
+        return Err(StorageError::NotFound)?;
+    }
+
+    Ok(())
+}
+
+/// Load the files attached to the user messages from the storage, so they're sent to the
+/// provider with the messages. Files missing from the storage are skipped.
+pub async fn load_attachments(
+    db: &mut DbConnection,
+    storage: &LocalStorage,
+    messages: &mut [ChatRsMessage],
+) -> Result<(), ApiError> {
+    let message_ids: Vec<Uuid> = messages
+        .iter()
+        .filter(|message| message.role == ChatRsMessageRole::User)
+        .map(|message| message.id)
+        .collect();
+    if message_ids.is_empty() {
+        return Ok(());
+    }
+    let mut attachments_by_message: HashMap<Uuid, Vec<ChatRsAttachment>> = HashMap::new();
+    for attachment in AttachmentDbService::new(db)
+        .find_by_message_ids(&message_ids)
+        .await?
+    {
+        if let Some(message_id) = attachment.message_id {
+            attachments_by_message
+                .entry(message_id)
+                .or_default()
+                .push(attachment);
+        }
+    }
+
+    for message in messages.iter_mut() {
+        let Some(attachments) = attachments_by_message.remove(&message.id) else {
+            continue;
+        };
+        for attachment in attachments {
+            match load_file(storage, attachment).await {
+                Some(LoadedFile::Text(text)) => message.content.push_str(&format!("\n\n{text}")),
+                Some(LoadedFile::File(file)) => message.meta.attachments.push(file),
+                None => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Load the instructions and files of a project from the storage. Files missing from the
+/// storage are skipped.
+pub async fn load_project_context(
+    db: &mut DbConnection,
+    storage: &LocalStorage,
+    project: &ChatRsProject,
+) -> Result<ProjectContext, ApiError> {
+    let mut sections: Vec<String> = project
+        .instructions
+        .iter()
+        .filter(|instructions| !instructions.trim().is_empty())
+        .map(|instructions| instructions.trim().to_owned())
+        .collect();
+    let mut attachments = Vec::new();
+    for attachment in AttachmentDbService::new(db)
+        .find_by_project(&project.id)
+        .await?
+    {
+        match load_file(storage, attachment).await {
+            Some(LoadedFile::Text(text)) => sections.push(text),
+            Some(LoadedFile::File(file)) => attachments.push(file),
+            None => {}
+        }
+    }
+
+    Ok(ProjectContext {
+        system_prompt: (!sections.is_empty()).then(|| sections.join("\n\n")),
+        attachments,
+    })
+}
+
+/// Attach the project's images and PDFs to the first user message
+pub fn add_project_attachments(messages: &mut [ChatRsMessage], attachments: &[LlmAttachment]) {
+    if attachments.is_empty() {
+        return;
+    }
+    if let Some(message) = messages
+        .iter_mut()
+        .find(|message| message.role == ChatRsMessageRole::User)
+    {
+        message
+            .meta
+            .attachments
+            .splice(0..0, attachments.iter().cloned());
+    }
+}
+
+/// Read a stored file from the storage of the user that uploaded it: text files and extracted
+/// text are formatted to be included in a message, and other files are encoded for the
+/// provider. DOCX files without extracted text are skipped, as providers don't support them.
+async fn load_file(storage: &LocalStorage, attachment: ChatRsAttachment) -> Option<LoadedFile> {
+    if attachment.mime_type != "application/pdf" {
+        if let Some(text) = &attachment.extracted_text {
+            return Some(LoadedFile::Text(format_text_file(&attachment.name, text)));
+        }
+    }
+    if attachment.mime_type == DOCX_MIME_TYPE {
+        rocket::warn!("Attachment {} has no extracted text", attachment.id);
+        return None;
+    }
+
+    let max_size = MAX_ATTACHMENT_SIZE_MIB * 1024 * 1024;
+    let data = match storage
+        .read(
+            &attachment.user_id,
+            Path::new(&attachment.storage_path),
+            max_size,
+        )
+        .await
+    {
+        Ok(data) => data,
+        Err(err) => {
+            rocket::warn!("Failed to read attachment {}: {}", attachment.id, err);
+            return None;
+        }
+    };
+    if attachment.mime_type.starts_with("text/") {
+        return Some(LoadedFile::Text(format_text_file(
+            &attachment.name,
+            &String::from_utf8_lossy(&data),
+        )));
+    }
+
+    Some(LoadedFile::File(LlmAttachment {
+        name: attachment.name,
+        mime_type: attachment.mime_type,
+        data: BASE64_STANDARD.encode(&data),
+    }))
+}
+
+fn format_text_file(name: &str, text: &str) -> String {
+    format!("<file name=\"{name}\">\n{text}\n</file>")
+}
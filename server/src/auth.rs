@@ -1,17 +1,36 @@
 mod api_key;
+mod client_ip;
 mod guard;
+mod local;
 mod oauth;
 mod session;
+mod session_key_fallback;
 mod sso_header;
+mod throttle;
 
 use rocket::fairing::AdHoc;
 
-pub use api_key::build_api_key_string;
-pub use guard::ChatRsUserId;
+pub use api_key::{build_api_key_string, parse_allowed_ips, ApiKeyError};
+pub use client_ip::get_client_ip;
+pub use guard::{
+    AdminUserId, ChatRead, ChatRsScopedUserId, ChatRsUserId, ChatWrite, RequiredScope, ToolsExecute,
+};
+pub use local::{
+    consume_token, create_token, hash_password, normalize_email, verify_password, LocalAuthConfig,
+    LocalAuthError, LocalAuthToken,
+};
 pub use oauth::{DiscordOAuthConfig, GitHubOAuthConfig, GoogleOAuthConfig, OIDCConfig};
-pub use session::ChatRsAuthSession;
+pub use session::{
+    get_auth_sessions, revoke_all_auth_sessions, revoke_auth_session, start_auth_session,
+    start_impersonation_session, AuthClientInfo, AuthSessionInfo, ChatRsAuthSession,
+};
 pub use sso_header::SSOHeaderMergedConfig;
-use {oauth::setup_oauth, session::setup_session, sso_header::setup_sso_header_auth};
+pub use throttle::{AuthThrottle, ThrottleSubject};
+use {
+    client_ip::setup_trusted_proxies, local::setup_local_auth, oauth::setup_oauth,
+    session::setup_session, session_key_fallback::setup_session_key_fallback,
+    sso_header::setup_sso_header_auth, throttle::setup_auth_throttle,
+};
 
 /// Fairing that sets up all authentication services
 pub fn setup_auth(base_path: &str) -> AdHoc {
@@ -19,8 +38,12 @@ pub fn setup_auth(base_path: &str) -> AdHoc {
 
     AdHoc::on_ignite("Auth services", |rocket| async {
         rocket
+            .attach(setup_trusted_proxies())
             .attach(setup_session())
+            .attach(setup_session_key_fallback())
             .attach(setup_sso_header_auth())
+            .attach(setup_local_auth())
+            .attach(setup_auth_throttle())
             .attach(setup_oauth(base_path))
     })
 }
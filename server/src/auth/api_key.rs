@@ -1,3 +1,6 @@
+use std::net::IpAddr;
+
+use ipnet::IpNet;
 use rocket::{
     http::Status,
     outcome::{try_outcome, IntoOutcome},
@@ -6,14 +9,58 @@ use rocket::{
 use uuid::Uuid;
 
 use crate::{
-    auth::ChatRsUserId,
-    db::{services::ApiKeyDbService, DbConnection},
+    db::{models::ChatRsApiKey, services::ApiKeyDbService, DbConnection},
     utils::Encryptor,
 };
 
 const API_KEY_PREFIX: &str = "rs-chat-key";
 const API_KEY_HEADER_PREFIX: &str = "Bearer rs-chat-key|";
 
+/// Max number of CIDR ranges in the IP allowlist of an API key
+const MAX_ALLOWED_IPS: usize = 20;
+
+/// Errors of API key settings
+#[derive(Debug, thiserror::Error)]
+pub enum ApiKeyError {
+    #[error("Invalid IP address or CIDR range: {0}")]
+    InvalidIpRange(String),
+    #[error("Too many IP ranges (max {0})")]
+    TooManyIpRanges(usize),
+}
+
+/// Parse and normalize the IP allowlist of an API key. Accepts CIDR ranges (e.g.
+/// `192.168.1.0/24`) and single IP addresses.
+pub fn parse_allowed_ips(ranges: &[String]) -> Result<Vec<String>, ApiKeyError> {
+    if ranges.len() > MAX_ALLOWED_IPS {
+        return Err(ApiKeyError::TooManyIpRanges(MAX_ALLOWED_IPS));
+    }
+    ranges
+        .iter()
+        .map(|range| {
+            let range = range.trim();
+            range
+                .parse::<IpNet>()
+                .or_else(|_| range.parse::<IpAddr>().map(IpNet::from))
+                .map(|net| net.trunc().to_string())
+                .map_err(|_| ApiKeyError::InvalidIpRange(range.to_owned()))
+        })
+        .collect()
+}
+
+/// Check that the client IP is in the API key's allowlist, if it has one
+fn is_ip_allowed(allowed_ips: Option<&[String]>, client_ip: Option<IpAddr>) -> bool {
+    let Some(allowed_ips) = allowed_ips else {
+        return true;
+    };
+    let Some(client_ip) = client_ip.map(|ip| ip.to_canonical()) else {
+        return false;
+    };
+    allowed_ips
+        .iter()
+        .filter_map(|range| range.parse::<IpNet>().ok())
+        .any(|net| net.contains(&client_ip))
+}
+
 /// Build an API key string from the given ciphertext and nonce
 pub fn build_api_key_string(ciphertext: &[u8], nonce: &[u8]) -> String {
     format!(
@@ -24,12 +71,14 @@ pub fn build_api_key_string(ciphertext: &[u8], nonce: &[u8]) -> String {
     )
 }
 
-/// Handle login/authentication via API key
+/// Handle login/authentication via API key, and get the API key if it's valid. The client IP
+/// (see the `trusted_proxies` setting for proxies) must be in the key's IP allowlist.
 pub async fn get_api_key_auth_outcome<'r>(
     auth_header: &str,
+    client_ip: Option<IpAddr>,
     encryptor: &Encryptor,
     db: &mut DbConnection,
-) -> Outcome<ChatRsUserId, &'r str> {
+) -> Outcome<ChatRsApiKey, &'r str> {
     let (nonce, ciphertext) = try_outcome!(auth_header
         .strip_prefix(API_KEY_HEADER_PREFIX)
         .and_then(|s| s.split_once('|'))
@@ -44,9 +93,115 @@ pub async fn get_api_key_auth_outcome<'r>(
         .and_then(|key_bytes| Uuid::from_slice(&key_bytes).map_err(|_| "Couldn't parse UUID"))
         .or_error(Status::Unauthorized));
 
-    match ApiKeyDbService::new(db).find_by_id(&key_id).await {
-        Ok(Some(api_key)) => Outcome::Success(ChatRsUserId(api_key.user_id)),
+    let mut db_service = ApiKeyDbService::new(db);
+    match db_service.find_by_id(&key_id).await {
+        Ok(Some(api_key)) if api_key.is_expired() => {
+            Outcome::Error((Status::Unauthorized, "API key expired"))
+        }
+        Ok(Some(api_key)) if !is_ip_allowed(api_key.allowed_ips.as_deref(), client_ip) => {
+            Outcome::Error((
+                Status::Forbidden,
+                "API key not allowed from this IP address",
+            ))
+        }
+        Ok(Some(api_key)) => {
+            if let Err(err) = db_service.mark_used(&api_key.id).await {
+                rocket::warn!("Failed to update last use of API key: {}", err);
+            }
+            Outcome::Success(api_key)
+        }
         Ok(None) => Outcome::Error((Status::Unauthorized, "API key not found")),
         Err(_) => Outcome::Error((Status::InternalServerError, "Database error")),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(ranges: &[&str]) -> Vec<String> {
+        ranges.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn ip(s: &str) -> Option<IpAddr> {
+        Some(s.parse().unwrap())
+    }
+
+    #[test]
+    fn test_parse_allowed_ips() {
+        let parsed = parse_allowed_ips(&ranges(&[" 192.168.1.7/24 ", "10.0.0.1", "2001:db8::1"]));
+        assert_eq!(
+            parsed.unwrap(),
+            vec!["192.168.1.0/24", "10.0.0.1/32", "2001:db8::1/128"]
+        );
+        assert_eq!(parse_allowed_ips(&[]).unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_parse_allowed_ips_invalid() {
+        for invalid in ["not-an-ip", "10.0.0.0/33", "10.0.0", "::1/129", ""] {
+            assert!(
+                matches!(
+                    parse_allowed_ips(&ranges(&[invalid])),
+                    Err(ApiKeyError::InvalidIpRange(_))
+                ),
+                "{invalid} should be rejected"
+            );
+        }
+        let too_many = vec!["10.0.0.1".to_owned(); MAX_ALLOWED_IPS + 1];
+        assert!(matches!(
+            parse_allowed_ips(&too_many),
+            Err(ApiKeyError::TooManyIpRanges(_))
+        ));
+    }
+
+    #[test]
+    fn test_is_ip_allowed() {
+        let allowed = ranges(&["10.0.0.0/8", "2001:db8::/32"]);
+        assert!(is_ip_allowed(None, None));
+        assert!(is_ip_allowed(None, ip("203.0.113.5")));
+        assert!(!is_ip_allowed(Some(allowed.as_slice()), None));
+        assert!(is_ip_allowed(Some(allowed.as_slice()), ip("10.1.2.3")));
+        assert!(is_ip_allowed(Some(allowed.as_slice()), ip("2001:db8::5")));
+        assert!(!is_ip_allowed(Some(allowed.as_slice()), ip("11.0.0.1")));
+        assert!(!is_ip_allowed(Some(&[][..]), ip("10.1.2.3")));
+    }
+
+    #[test]
+    fn test_is_ip_allowed_ipv4_mapped() {
+        let allowed = ranges(&["10.0.0.0/8"]);
+        assert!(is_ip_allowed(
+            Some(allowed.as_slice()),
+            ip("::ffff:10.1.2.3")
+        ));
+        assert!(!is_ip_allowed(
+            Some(allowed.as_slice()),
+            ip("::ffff:11.0.0.1")
+        ));
+    }
+
+    #[test]
+    fn test_is_ip_allowed_any() {
+        let allowed = ranges(&["0.0.0.0/0"]);
+        assert!(is_ip_allowed(Some(allowed.as_slice()), ip("203.0.113.5")));
+        assert!(is_ip_allowed(
+            Some(allowed.as_slice()),
+            ip("::ffff:203.0.113.5")
+        ));
+        assert!(!is_ip_allowed(Some(allowed.as_slice()), ip("2001:db8::1")));
+        assert!(is_ip_allowed(
+            Some(ranges(&["::/0"]).as_slice()),
+            ip("2001:db8::1")
+        ));
+    }
+
+    #[test]
+    fn test_is_ip_allowed_ignores_invalid_ranges() {
+        let allowed = ranges(&["garbage", "10.0.0.0/8"]);
+        assert!(is_ip_allowed(Some(allowed.as_slice()), ip("10.1.2.3")));
+        assert!(!is_ip_allowed(
+            Some(ranges(&["garbage"]).as_slice()),
+            ip("10.1.2.3")
+        ));
+    }
+}
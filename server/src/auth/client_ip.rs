@@ -0,0 +1,122 @@
+//! IP address of the client, for the IP allowlists of API keys, the login throttle, and the
+//! sessions. The address of the peer is used, unless the peer is one of the configured trusted
+//! proxies: then the address in the proxy's IP header (Rocket's `ip_header` setting, default
+//! `X-Real-IP`) is used.
+
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+use rocket::{fairing::AdHoc, Request};
+
+use crate::config::get_app_config;
+
+/// CIDR ranges of the trusted reverse proxies
+#[derive(Debug, Default)]
+pub struct TrustedProxies(Vec<IpNet>);
+
+impl TrustedProxies {
+    /// Parse the trusted proxies. Accepts CIDR ranges and single IP addresses.
+    pub fn parse(ranges: &[String]) -> Result<Self, String> {
+        ranges
+            .iter()
+            .map(|range| {
+                let range = range.trim();
+                range
+                    .parse::<IpNet>()
+                    .or_else(|_| range.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| range.to_owned())
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    fn contains(&self, ip: &IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.0.iter().any(|net| net.contains(&ip))
+    }
+}
+
+/// Get the IP address of the client
+pub fn get_client_ip(req: &Request<'_>) -> Option<IpAddr> {
+    let peer_ip = req.remote().map(|addr| addr.ip());
+    match req.rocket().state::<TrustedProxies>() {
+        Some(proxies) => resolve_client_ip(peer_ip, req.real_ip(), proxies),
+        None => peer_ip,
+    }
+}
+
+/// Only use the IP from the header if the request was sent by a trusted proxy
+fn resolve_client_ip(
+    peer_ip: Option<IpAddr>,
+    header_ip: Option<IpAddr>,
+    proxies: &TrustedProxies,
+) -> Option<IpAddr> {
+    match peer_ip {
+        Some(ip) if proxies.contains(&ip) => header_ip.or(peer_ip),
+        _ => peer_ip,
+    }
+}
+
+/// Fairing that sets up the trusted proxies, if configured
+pub fn setup_trusted_proxies() -> AdHoc {
+    AdHoc::try_on_ignite("Trusted proxies", |rocket| async {
+        let Some(ranges) = get_app_config(&rocket).trusted_proxies.as_deref() else {
+            return Ok(rocket);
+        };
+        match TrustedProxies::parse(ranges) {
+            Ok(proxies) => {
+                rocket::info!("Trusting the IP header of proxies: {ranges:?}");
+                Ok(rocket.manage(proxies))
+            }
+            Err(range) => {
+                rocket::error!("Invalid trusted proxy: {range}");
+                Err(rocket)
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> Option<IpAddr> {
+        Some(s.parse().unwrap())
+    }
+
+    #[test]
+    fn test_parse_trusted_proxies() {
+        let proxies = TrustedProxies::parse(&["10.0.0.0/8".into(), "::1".into()]).unwrap();
+        assert_eq!(proxies.0.len(), 2);
+        assert!(TrustedProxies::parse(&["not-an-ip".into()]).is_err());
+        assert!(TrustedProxies::parse(&["10.0.0.0/33".into()]).is_err());
+    }
+
+    #[test]
+    fn test_header_ignored_from_untrusted_peer() {
+        let proxies = TrustedProxies::parse(&["10.0.0.0/8".into()]).unwrap();
+        assert_eq!(
+            resolve_client_ip(ip("203.0.113.5"), ip("1.2.3.4"), &proxies),
+            ip("203.0.113.5")
+        );
+        assert_eq!(resolve_client_ip(None, ip("1.2.3.4"), &proxies), None);
+    }
+
+    #[test]
+    fn test_header_used_from_trusted_peer() {
+        let proxies = TrustedProxies::parse(&["10.0.0.0/8".into()]).unwrap();
+        assert_eq!(
+            resolve_client_ip(ip("10.1.2.3"), ip("1.2.3.4"), &proxies),
+            ip("1.2.3.4")
+        );
+        assert_eq!(
+            resolve_client_ip(ip("::ffff:10.1.2.3"), ip("1.2.3.4"), &proxies),
+            ip("1.2.3.4")
+        );
+        // No header: fall back to the proxy's address
+        assert_eq!(
+            resolve_client_ip(ip("10.1.2.3"), None, &proxies),
+            ip("10.1.2.3")
+        );
+    }
+}
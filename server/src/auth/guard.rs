@@ -1,9 +1,10 @@
-use std::ops::Deref;
+use std::{marker::PhantomData, ops::Deref};
 
 use rocket::{
     http::Status,
     outcome::try_outcome,
     request::{FromRequest, Outcome},
+    Request,
 };
 use rocket_flex_session::Session;
 use rocket_okapi::{
@@ -16,14 +17,22 @@ use uuid::Uuid;
 use crate::{
     auth::{
         api_key::get_api_key_auth_outcome,
+        client_ip::get_client_ip,
+        session::{revoke_auth_session, touch_auth_session},
         sso_header::{get_sso_auth_outcome, get_sso_user_from_headers},
         ChatRsAuthSession, SSOHeaderMergedConfig,
     },
-    db::{models::ChatRsUser, services::UserDbService, DbConnection},
+    db::{
+        models::{ChatRsApiKeyScope, ChatRsUser, ChatRsUserRole},
+        services::UserDbService,
+        DbConnection,
+    },
+    redis::RedisClient,
     utils::Encryptor,
 };
 
-/// User ID request guard to ensure a logged-in user.
+/// User ID request guard to ensure a logged-in user. Requests authenticated with an API key
+/// need the `admin` scope: use [`ChatRsScopedUserId`] for routes available to other scopes.
 pub struct ChatRsUserId(pub Uuid);
 
 impl Deref for ChatRsUserId {
@@ -39,27 +48,211 @@ impl Deref for ChatRsUserId {
 impl<'r> FromRequest<'r> for ChatRsUserId {
     type Error = &'r str;
 
-    async fn from_request(req: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
-        // Try authentication via proxy headers if configured
-        if let Some(config) = req.rocket().state::<SSOHeaderMergedConfig>() {
-            if let Some(proxy_user) = get_sso_user_from_headers(config, req.headers()) {
-                let mut db = try_outcome!(req.guard::<DbConnection>().await);
-                return get_sso_auth_outcome(&proxy_user, config, &mut db).await;
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        authenticate(req, ChatRsApiKeyScope::Admin)
+            .await
+            .map(ChatRsUserId)
+    }
+}
+
+/// A scope that a route requires from API keys, used with the [`ChatRsScopedUserId`] guard.
+pub trait RequiredScope: Send + Sync + 'static {
+    const SCOPE: ChatRsApiKeyScope;
+}
+
+/// Requires the `chat:read` scope
+pub struct ChatRead;
+impl RequiredScope for ChatRead {
+    const SCOPE: ChatRsApiKeyScope = ChatRsApiKeyScope::ChatRead;
+}
+
+/// Requires the `chat:write` scope
+pub struct ChatWrite;
+impl RequiredScope for ChatWrite {
+    const SCOPE: ChatRsApiKeyScope = ChatRsApiKeyScope::ChatWrite;
+}
+
+/// Requires the `tools:execute` scope
+pub struct ToolsExecute;
+impl RequiredScope for ToolsExecute {
+    const SCOPE: ChatRsApiKeyScope = ChatRsApiKeyScope::ToolsExecute;
+}
+
+/// User ID request guard to ensure a logged-in user. Requests authenticated with an API key
+/// need the given scope (or the `admin` scope).
+pub struct ChatRsScopedUserId<S: RequiredScope>(pub Uuid, PhantomData<S>);
+
+impl<S: RequiredScope> Deref for ChatRsScopedUserId<S> {
+    type Target = Uuid;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, S: RequiredScope> FromRequest<'r> for ChatRsScopedUserId<S> {
+    type Error = &'r str;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        authenticate(req, S::SCOPE)
+            .await
+            .map(|user_id| ChatRsScopedUserId(user_id, PhantomData))
+    }
+}
+
+/// User ID request guard to ensure a logged-in admin of the server. Requests authenticated
+/// with an API key need the `admin` scope.
+pub struct AdminUserId(pub Uuid);
+
+impl Deref for AdminUserId {
+    type Target = Uuid;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminUserId {
+    type Error = &'r str;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let user_id = try_outcome!(authenticate(req, ChatRsApiKeyScope::Admin).await);
+        let mut db = try_outcome!(req.guard::<DbConnection>().await);
+        match UserDbService::new(&mut db).find_role(&user_id).await {
+            Ok(Some(role)) if role == <&str>::from(&ChatRsUserRole::Admin) => {
+                Outcome::Success(AdminUserId(user_id))
             }
-        };
+            Ok(_) => Outcome::Error((Status::Forbidden, "Admin role required")),
+            Err(e) => {
+                rocket::error!("Admin guard: database error: {}", e);
+                Outcome::Error((Status::InternalServerError, "Database error"))
+            }
+        }
+    }
+}
 
-        // Try authentication via API key
-        if let Some(auth_header) = req.headers().get_one("Authorization") {
-            let encryptor = req.rocket().state::<Encryptor>().expect("should exist");
+/// Authenticate the user of the request, and check the scope if authenticated with an API key.
+/// Session and proxy header authentication have full access.
+async fn authenticate<'r>(
+    req: &'r Request<'_>,
+    scope: ChatRsApiKeyScope,
+) -> Outcome<Uuid, &'r str> {
+    // Try authentication via proxy headers if configured
+    let sso_config = req.rocket().state::<SSOHeaderMergedConfig>();
+    if let Some(config) = sso_config {
+        if let Some(proxy_user) = get_sso_user_from_headers(config, req.headers()) {
             let mut db = try_outcome!(req.guard::<DbConnection>().await);
-            return get_api_key_auth_outcome(auth_header, encryptor, &mut db).await;
+            let user_id = try_outcome!(get_sso_auth_outcome(&proxy_user, config, &mut db).await);
+            let user_id = try_outcome!(check_not_disabled(&mut db, user_id.0).await);
+            return bind_session_to_sso(req, proxy_user.username, user_id).await;
+        }
+    };
+
+    // Try authentication via API key
+    if let Some(auth_header) = req.headers().get_one("Authorization") {
+        let encryptor = req.rocket().state::<Encryptor>().expect("should exist");
+        let mut db = try_outcome!(req.guard::<DbConnection>().await);
+        let api_key = try_outcome!(
+            get_api_key_auth_outcome(auth_header, get_client_ip(req), encryptor, &mut db).await
+        );
+        if !api_key.allows(scope) {
+            return Outcome::Error((Status::Forbidden, "API key is missing the required scope"));
+        }
+        return check_not_disabled(&mut db, api_key.user_id).await;
+    }
+
+    // Try authentication via session
+    let mut session = try_outcome!(req.guard::<Session<ChatRsAuthSession>>().await);
+    let Some((user_id, session_id, sso_username)) = session.tap(|data| {
+        data.and_then(|auth_session| {
+            Some((
+                auth_session.user_id()?,
+                auth_session.session_id(),
+                auth_session.sso_username(),
+            ))
+        })
+    }) else {
+        return Outcome::Error((Status::Unauthorized, "Unauthorized"));
+    };
+    // The SSO header disappeared from a session used behind the proxy: the user logged out of
+    // the remote service, so end the local session too
+    if sso_config.is_some() && sso_username.is_some() {
+        if let Some(session_id) = session_id {
+            let redis = try_outcome!(req
+                .guard::<RedisClient>()
+                .await
+                .map_error(|(status, _)| (status, "Redis error")));
+            if let Err(err) = revoke_auth_session(&redis, &user_id, &session_id).await {
+                rocket::error!("Session guard: Redis error: {}", err);
+            }
         }
+        session.delete();
+        return Outcome::Error((Status::Unauthorized, "SSO session ended"));
+    }
+    // Check that the session hasn't been revoked (sessions created before session tracking
+    // was added don't have an ID, so check that the user isn't disabled instead)
+    let Some(session_id) = session_id else {
+        let mut db = try_outcome!(req.guard::<DbConnection>().await);
+        return check_not_disabled(&mut db, user_id).await;
+    };
+    let redis = try_outcome!(req
+        .guard::<RedisClient>()
+        .await
+        .map_error(|(status, _)| (status, "Redis error")));
+    match touch_auth_session(&redis, &user_id, &session_id, get_client_ip(req)).await {
+        Ok(true) => Outcome::Success(user_id),
+        Ok(false) => {
+            session.delete();
+            Outcome::Error((Status::Unauthorized, "Session revoked"))
+        }
+        Err(err) => {
+            rocket::error!("Session guard: Redis error: {}", err);
+            Outcome::Error((Status::InternalServerError, "Redis error"))
+        }
+    }
+}
 
-        // Try authentication via session
-        let session = try_outcome!(req.guard::<Session<ChatRsAuthSession>>().await);
-        match session.tap(|data| data.and_then(|auth_session| auth_session.user_id())) {
-            Some(user_id) => Outcome::Success(ChatRsUserId(user_id)),
-            None => Outcome::Error((Status::Unauthorized, "Unauthorized")),
+/// Bind the request's login session (if any) to the proxy user, so that it ends when the proxy
+/// stops sending the SSO header. A session of another user is ended, as the proxy user changed.
+async fn bind_session_to_sso<'r>(
+    req: &'r Request<'_>,
+    username: &str,
+    user_id: Uuid,
+) -> Outcome<Uuid, &'r str> {
+    let mut session = try_outcome!(req.guard::<Session<ChatRsAuthSession>>().await);
+    let Some(auth_session) = session.tap(|data| data.cloned()) else {
+        return Outcome::Success(user_id);
+    };
+    if auth_session.user_id() != Some(user_id) {
+        if let (Some(session_user_id), Some(session_id)) =
+            (auth_session.user_id(), auth_session.session_id())
+        {
+            let redis = try_outcome!(req
+                .guard::<RedisClient>()
+                .await
+                .map_error(|(status, _)| (status, "Redis error")));
+            if let Err(err) = revoke_auth_session(&redis, &session_user_id, &session_id).await {
+                rocket::error!("SSO header auth: Redis error: {}", err);
+            }
+        }
+        session.delete();
+    } else if auth_session.sso_username().as_deref() != Some(username) {
+        session.set(auth_session.with_sso_username(username));
+    }
+
+    Outcome::Success(user_id)
+}
+
+/// Check that the user wasn't disabled by an admin
+async fn check_not_disabled<'r>(db: &mut DbConnection, user_id: Uuid) -> Outcome<Uuid, &'r str> {
+    match UserDbService::new(db).is_disabled(&user_id).await {
+        Ok(false) => Outcome::Success(user_id),
+        Ok(true) => Outcome::Error((Status::Unauthorized, "User is disabled")),
+        Err(e) => {
+            rocket::error!("User guard: database error: {}", e);
+            Outcome::Error((Status::InternalServerError, "Database error"))
         }
     }
 }
@@ -95,6 +288,28 @@ impl<'a> OpenApiFromRequest<'a> for ChatRsUserId {
     }
 }
 
+/// OpenAPI documentation for API key authentication when using the ChatRsScopedUserId guard.
+impl<'a, S: RequiredScope> OpenApiFromRequest<'a> for ChatRsScopedUserId<S> {
+    fn from_request_input(
+        _gen: &mut OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> rocket_okapi::Result<RequestHeaderInput> {
+        api_key_docs()
+    }
+}
+
+/// OpenAPI documentation for API key authentication when using the AdminUserId guard.
+impl<'a> OpenApiFromRequest<'a> for AdminUserId {
+    fn from_request_input(
+        _gen: &mut OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> rocket_okapi::Result<RequestHeaderInput> {
+        api_key_docs()
+    }
+}
+
 /// OpenAPI documentation for API key authentication when using the ChatRsUser guard.
 impl<'a> OpenApiFromRequest<'a> for ChatRsUser {
     fn from_request_input(
@@ -8,16 +8,21 @@ use rocket_flex_session::Session;
 use rocket_oauth2::{HyperRustlsAdapter, OAuth2, OAuthConfig, StaticProvider, TokenResponse};
 use serde::Deserialize;
 use std::future::Future;
+use uuid::Uuid;
 
 use crate::{
-    auth::ChatRsAuthSession,
+    auth::{
+        session::{start_auth_session, AuthClientInfo},
+        ChatRsAuthSession,
+    },
     config::{get_app_config, get_config_provider},
     db::{
-        models::{ChatRsUser, NewChatRsUser, UpdateChatRsUser},
+        models::{ChatRsUser, ChatRsUserRole, NewChatRsUser, UpdateChatRsUser},
         services::UserDbService,
         DbConnection,
     },
     errors::ApiError,
+    redis::RedisClient,
 };
 pub use discord::{DiscordOAuthConfig, DiscordProvider};
 pub use github::{GitHubOAuthConfig, GitHubProvider};
@@ -57,6 +62,16 @@ trait OAuthProvider {
     fn is_user_linked(user: &ChatRsUser) -> bool;
     fn create_update_user(user_data: &UserData) -> UpdateChatRsUser;
     fn create_new_user(user_data: &UserData) -> NewChatRsUser;
+    /// Map the claims of the user (the raw user info and the token response) to a role, if
+    /// configured. Returns an error if the user isn't allowed to access the app, and `None` to
+    /// keep the user's current role.
+    fn get_user_role(
+        &self,
+        _user_info: &serde_json::Value,
+        _token: &TokenResponse<Self::UserInfo>,
+    ) -> Result<Option<ChatRsUserRole>, ApiError> {
+        Ok(None)
+    }
 }
 
 /// Fairing that sets up OAuth login and routes
@@ -136,6 +151,8 @@ async fn generic_login_callback<P: OAuthProvider>(
     token: TokenResponse<P::UserInfo>,
     config: &P::Config,
     mut session: Session<'_, ChatRsAuthSession>,
+    redis: RedisClient,
+    client_info: AuthClientInfo,
 ) -> Result<Redirect, ApiError> {
     let client = reqwest::Client::builder()
         .build()
@@ -147,7 +164,7 @@ async fn generic_login_callback<P: OAuthProvider>(
         request = request.header(key, value);
     }
 
-    let user_info: P::UserInfo = request
+    let user_info_value: serde_json::Value = request
         .send()
         .await
         .map_err(|e| {
@@ -156,19 +173,30 @@ async fn generic_login_callback<P: OAuthProvider>(
         .json()
         .await
         .map_err(|e| ApiError::Authentication(format!("Failed to deserialize response: {}", e)))?;
+    let role = P::new(config).get_user_role(&user_info_value, &token)?;
+    let user_info: P::UserInfo = serde_json::from_value(user_info_value)
+        .map_err(|e| ApiError::Authentication(format!("Failed to deserialize response: {}", e)))?;
     let user_data = P::extract_user_data(user_info);
 
     let mut db_service = UserDbService::new(&mut db);
     match P::find_linked_user(&mut db_service, &user_data).await? {
         // Existing linked user found: create new session
         Some(existing_user) => {
-            session.set(ChatRsAuthSession::new(existing_user.id));
+            if existing_user.disabled_at.is_some() {
+                return Err(ApiError::Authentication("User is disabled".to_owned()));
+            }
+            if let Some(role) = role {
+                sync_user_role(&mut db_service, &existing_user.id, role).await?;
+            }
+            start_auth_session(&mut session, &redis, &client_info, existing_user.id).await?;
         }
         None => match session.tap(|data| data.and_then(|auth_session| auth_session.user_id())) {
             // No linked user and no session found: create new user and session
             None => {
-                let new_user = db_service.create(P::create_new_user(&user_data)).await?;
-                session.set(ChatRsAuthSession::new(new_user.id));
+                let mut new_user = P::create_new_user(&user_data);
+                new_user.role = role.as_ref().map(<&str>::from);
+                let new_user = db_service.create(new_user).await?;
+                start_auth_session(&mut session, &redis, &client_info, new_user.id).await?;
             }
             // No linked user but there is a current session
             Some(user_id) => {
@@ -181,6 +209,9 @@ async fn generic_login_callback<P: OAuthProvider>(
                         db_service
                             .update(&user_id, P::create_update_user(&user_data))
                             .await?;
+                        if let Some(role) = role {
+                            sync_user_role(&mut db_service, &user_id, role).await?;
+                        }
                     }
                     // User is already linked to this OAuth provider
                     true => {
@@ -196,3 +227,35 @@ async fn generic_login_callback<P: OAuthProvider>(
 
     Ok(Redirect::to("/"))
 }
+
+/// Update the user's role to the role mapped from the OAuth provider's claims. The last admin
+/// isn't demoted, so that the server always has an admin.
+async fn sync_user_role(
+    db_service: &mut UserDbService<'_>,
+    user_id: &Uuid,
+    role: ChatRsUserRole,
+) -> Result<(), ApiError> {
+    let role: &str = (&role).into();
+    let current_role = db_service.find_role(user_id).await?;
+    if current_role.as_deref() == Some(role) {
+        return Ok(());
+    }
+    let admin_role: &str = (&ChatRsUserRole::Admin).into();
+    if current_role.as_deref() == Some(admin_role)
+        && db_service.count_by_role(admin_role).await? <= 1
+    {
+        rocket::warn!("OAuth: not demoting {}, the last admin", user_id);
+        return Ok(());
+    }
+    db_service
+        .update(
+            user_id,
+            UpdateChatRsUser {
+                role: Some(role),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    Ok(())
+}
@@ -10,9 +10,13 @@ use crate::{
         DbConnection,
     },
     errors::ApiError,
+    redis::RedisClient,
 };
 
-use super::{generic_login, generic_login_callback, ChatRsAuthSession, OAuthProvider, UserData};
+use super::{
+    generic_login, generic_login_callback, AuthClientInfo, ChatRsAuthSession, OAuthProvider,
+    UserData,
+};
 
 /// Discord OAuth provider
 pub struct DiscordProvider {
@@ -131,6 +135,8 @@ async fn discord_login_callback(
     token: TokenResponse<DiscordUserInfo>,
     config: &State<DiscordOAuthConfig>,
     session: Session<'_, ChatRsAuthSession>,
+    redis: RedisClient,
+    client_info: AuthClientInfo,
 ) -> Result<Redirect, ApiError> {
-    generic_login_callback::<DiscordProvider>(db, token, config, session).await
+    generic_login_callback::<DiscordProvider>(db, token, config, session, redis, client_info).await
 }
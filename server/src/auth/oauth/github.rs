@@ -10,9 +10,13 @@ use crate::{
         DbConnection,
     },
     errors::ApiError,
+    redis::RedisClient,
 };
 
-use super::{generic_login, generic_login_callback, ChatRsAuthSession, OAuthProvider, UserData};
+use super::{
+    generic_login, generic_login_callback, AuthClientInfo, ChatRsAuthSession, OAuthProvider,
+    UserData,
+};
 
 /// GitHub OAuth provider
 pub struct GitHubProvider {
@@ -127,6 +131,8 @@ async fn github_login_callback(
     token: TokenResponse<GitHubUserInfo>,
     config: &State<GitHubOAuthConfig>,
     session: Session<'_, ChatRsAuthSession>,
+    redis: RedisClient,
+    client_info: AuthClientInfo,
 ) -> Result<Redirect, ApiError> {
-    generic_login_callback::<GitHubProvider>(db, token, config, session).await
+    generic_login_callback::<GitHubProvider>(db, token, config, session, redis, client_info).await
 }
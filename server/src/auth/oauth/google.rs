@@ -10,9 +10,13 @@ use crate::{
         DbConnection,
     },
     errors::ApiError,
+    redis::RedisClient,
 };
 
-use super::{generic_login, generic_login_callback, ChatRsAuthSession, OAuthProvider, UserData};
+use super::{
+    generic_login, generic_login_callback, AuthClientInfo, ChatRsAuthSession, OAuthProvider,
+    UserData,
+};
 
 /// Google OAuth provider
 pub struct GoogleProvider {
@@ -123,6 +127,9 @@ async fn google_login_callback(
     token: TokenResponse<GoogleUserInfo>,
     config: &State<GoogleOAuthConfig>,
     session: Session<'_, ChatRsAuthSession>,
+    redis: RedisClient,
+    client_info: AuthClientInfo,
 ) -> Result<Redirect, ApiError> {
-    generic_login_callback::<GoogleProvider>(db, token, config.inner(), session).await
+    generic_login_callback::<GoogleProvider>(db, token, config.inner(), session, redis, client_info)
+        .await
 }
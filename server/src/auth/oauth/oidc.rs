@@ -1,3 +1,4 @@
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
 use rocket::{get, http::CookieJar, response::Redirect, routes, Route, State};
 use rocket_flex_session::Session;
 use rocket_oauth2::{OAuth2, StaticProvider, TokenResponse};
@@ -5,14 +6,18 @@ use serde::Deserialize;
 
 use crate::{
     db::{
-        models::{ChatRsUser, NewChatRsUser, UpdateChatRsUser},
+        models::{ChatRsUser, ChatRsUserRole, NewChatRsUser, UpdateChatRsUser},
         services::UserDbService,
         DbConnection,
     },
     errors::ApiError,
+    redis::RedisClient,
 };
 
-use super::{generic_login, generic_login_callback, ChatRsAuthSession, OAuthProvider, UserData};
+use super::{
+    generic_login, generic_login_callback, AuthClientInfo, ChatRsAuthSession, OAuthProvider,
+    UserData,
+};
 
 /// Custom OIDC provider
 pub struct OIDCProvider {
@@ -33,6 +38,15 @@ pub struct OIDCConfig {
     oidc_scopes: Option<String>,
     /// Name of the OIDC provider (default: "OIDC")
     pub oidc_name: Option<String>,
+    /// Claim with the groups (or roles) of the user, read from the userinfo response or the
+    /// ID token. Nested claims are separated by dots, e.g. `realm_access.roles` (default:
+    /// "groups")
+    oidc_groups_claim: Option<String>,
+    /// If set, only users in this group will be allowed to access the app
+    oidc_user_group: Option<String>,
+    /// If set, users in this group become admins, and other users are members. Roles are
+    /// updated on every login.
+    oidc_admin_group: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -128,6 +142,67 @@ impl OAuthProvider for OIDCProvider {
             ..Default::default()
         }
     }
+
+    fn get_user_role(
+        &self,
+        user_info: &serde_json::Value,
+        token: &TokenResponse<Self::UserInfo>,
+    ) -> Result<Option<ChatRsUserRole>, ApiError> {
+        if self.config.oidc_user_group.is_none() && self.config.oidc_admin_group.is_none() {
+            return Ok(None);
+        }
+        let claim = self.config.oidc_groups_claim.as_deref().unwrap_or("groups");
+        let groups = get_groups(user_info, claim)
+            .or_else(|| get_id_token_claims(token).and_then(|claims| get_groups(&claims, claim)))
+            .unwrap_or_default();
+        if let Some(allowed_user_group) = &self.config.oidc_user_group {
+            if !groups.contains(allowed_user_group) {
+                rocket::debug!("OIDC: user group not allowed");
+                return Err(ApiError::Authentication(
+                    "User group not allowed".to_owned(),
+                ));
+            }
+        }
+
+        Ok(self.config.oidc_admin_group.as_ref().map(|admin_group| {
+            match groups.contains(admin_group) {
+                true => ChatRsUserRole::Admin,
+                false => ChatRsUserRole::Member,
+            }
+        }))
+    }
+}
+
+/// Read the groups from the (possibly nested) claim. The claim can be an array of strings, or
+/// a string of comma-separated groups.
+fn get_groups(claims: &serde_json::Value, claim: &str) -> Option<Vec<String>> {
+    let value = claim
+        .split('.')
+        .try_fold(claims, |value, key| value.get(key))?;
+    match value {
+        serde_json::Value::Array(groups) => Some(
+            groups
+                .iter()
+                .filter_map(|group| group.as_str().map(str::to_owned))
+                .collect(),
+        ),
+        serde_json::Value::String(groups) => Some(
+            groups
+                .split(',')
+                .map(|group| group.trim().to_owned())
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Decode the claims of the ID token. The signature isn't verified, as the token was received
+/// directly from the token endpoint over TLS.
+fn get_id_token_claims(token: &TokenResponse<OIDCUserInfo>) -> Option<serde_json::Value> {
+    let id_token = token.as_value().get("id_token")?.as_str()?;
+    let payload = id_token.split('.').nth(1)?;
+    let json = BASE64_URL_SAFE_NO_PAD.decode(payload).ok()?;
+    serde_json::from_slice(&json).ok()
 }
 
 #[get("/login/oidc")]
@@ -145,6 +220,53 @@ async fn oidc_login_callback(
     token: TokenResponse<OIDCUserInfo>,
     config: &State<OIDCConfig>,
     session: Session<'_, ChatRsAuthSession>,
+    redis: RedisClient,
+    client_info: AuthClientInfo,
 ) -> Result<Redirect, ApiError> {
-    generic_login_callback::<OIDCProvider>(db, token, config, session).await
+    generic_login_callback::<OIDCProvider>(db, token, config, session, redis, client_info).await
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_get_groups() {
+        let claims = json!({
+            "groups": ["admins", "users", 1],
+            "roles": "admins, users",
+            "realm_access": { "roles": ["admins"] },
+            "empty": [],
+            "count": 2,
+        });
+        assert_eq!(
+            get_groups(&claims, "groups"),
+            Some(vec!["admins".to_owned(), "users".to_owned()])
+        );
+        assert_eq!(
+            get_groups(&claims, "roles"),
+            Some(vec!["admins".to_owned(), "users".to_owned()])
+        );
+        assert_eq!(
+            get_groups(&claims, "realm_access.roles"),
+            Some(vec!["admins".to_owned()])
+        );
+        assert_eq!(get_groups(&claims, "empty"), Some(vec![]));
+    }
+
+    #[test]
+    fn test_get_missing_groups() {
+        let claims = json!({ "count": 2, "realm_access": { "roles": null } });
+        for claim in [
+            "groups",
+            "count",
+            "realm_access",
+            "realm_access.roles",
+            "count.groups",
+        ] {
+            assert_eq!(get_groups(&claims, claim), None, "{claim}");
+        }
+    }
 }
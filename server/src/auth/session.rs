@@ -1,15 +1,39 @@
-use std::ops::Deref;
+use std::{net::IpAddr, ops::Deref};
 
-use chrono::Utc;
-use rocket::fairing::AdHoc;
-use rocket_flex_session::{storage::redis::RedisFredStorage, RocketFlexSession};
+use chrono::{DateTime, Utc};
+use fred::{
+    prelude::{FredResult, KeysInterface},
+    types::{scan::ScanType, Expiration},
+};
+use rocket::{
+    async_trait,
+    fairing::AdHoc,
+    request::{FromRequest, Outcome},
+    Request,
+};
+use rocket_flex_session::{storage::redis::RedisFredStorage, RocketFlexSession, Session};
+use rocket_okapi::OpenApiFromRequest;
+use schemars::JsonSchema;
 use uuid::Uuid;
 
-use crate::{config::get_app_config, redis::build_redis_pool};
+use crate::{
+    auth::get_client_ip,
+    config::get_app_config,
+    redis::{build_redis_pool, redis_key},
+};
 
 const USER_ID_KEY: &str = "user_id";
 const USER_ID_BYTES_KEY: &str = "user_id_bytes";
 const START_TIME_KEY: &str = "start_time";
+const SESSION_ID_KEY: &str = "session_id";
+const SSO_USERNAME_KEY: &str = "sso_username";
+
+/// Name of the session cookie.
+pub(super) const SESSION_COOKIE_NAME: &str = "auth_rs_chat";
+/// Expiration of inactive sessions in seconds.
+pub(super) const SESSION_TTL: i64 = 60 * 60 * 24 * 2; // 2 days
+/// Min interval in seconds between updates of the last activity of a session.
+const LAST_SEEN_INTERVAL: i64 = 60;
 
 /// Type representing the session data.
 #[derive(Debug, Clone)]
@@ -32,14 +56,37 @@ impl ChatRsAuthSession {
             user_id.as_bytes().as_slice().into(),
         );
         hash.insert(START_TIME_KEY.into(), Utc::now().to_rfc3339().into());
+        hash.insert(SESSION_ID_KEY.into(), Uuid::new_v4().to_string().into());
         ChatRsAuthSession(hash)
     }
 
+    /// ID of the session, for listing and revoking it (not set for sessions created before
+    /// session tracking was added)
+    pub fn session_id(&self) -> Option<Uuid> {
+        self.get(&fred::types::Key::from_static_str(SESSION_ID_KEY))
+            .and_then(|val| val.as_str())
+            .and_then(|id| Uuid::parse_str(&id).ok())
+    }
+
     pub fn user_id(&self) -> Option<Uuid> {
         self.get(&fred::types::Key::from_static_str(USER_ID_BYTES_KEY))
             .and_then(|val| val.as_bytes())
             .and_then(|bytes| Uuid::from_slice(bytes).ok())
     }
+
+    /// Proxy username of the SSO header that the session was used with. The session is only
+    /// valid while the proxy keeps sending the header.
+    pub fn sso_username(&self) -> Option<String> {
+        self.get(&fred::types::Key::from_static_str(SSO_USERNAME_KEY))
+            .and_then(|val| val.as_string())
+    }
+
+    /// Bind the session to the proxy username of the SSO header
+    pub fn with_sso_username(&self, username: &str) -> Self {
+        let mut hash = self.0.clone();
+        hash.insert(SSO_USERNAME_KEY.into(), username.into());
+        ChatRsAuthSession(hash)
+    }
 }
 
 /// Possible errors when parsing session data from Redis hash.
@@ -77,17 +124,227 @@ pub fn setup_session() -> AdHoc {
         let session_redis_pool = build_redis_pool(config, 2).expect("Failed to build Redis pool");
         let session_fairing: RocketFlexSession<ChatRsAuthSession> = RocketFlexSession::builder()
             .with_options(|opt| {
-                opt.cookie_name = "auth_rs_chat".to_string();
-                opt.ttl = Some(60 * 60 * 24 * 2); // 2 days
+                opt.cookie_name = SESSION_COOKIE_NAME.to_string();
+                opt.ttl = Some(SESSION_TTL as u32);
                 opt.rolling = true;
             })
             .storage(RedisFredStorage::new(
                 session_redis_pool,
                 rocket_flex_session::storage::redis::RedisType::Hash,
-                "sess:",
+                format!(
+                    "{}sess:",
+                    app_config.redis_key_prefix.as_deref().unwrap_or_default()
+                ),
             ))
             .build();
 
         rocket.attach(session_fairing)
     })
 }
+
+/// Request guard for the device and IP address of the client, tracked for each session.
+#[derive(Debug, OpenApiFromRequest)]
+pub struct AuthClientInfo {
+    ip: Option<IpAddr>,
+    user_agent: Option<String>,
+}
+#[async_trait]
+impl<'r> FromRequest<'r> for AuthClientInfo {
+    type Error = ();
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(AuthClientInfo {
+            ip: get_client_ip(req),
+            user_agent: req.headers().get_one("User-Agent").map(str::to_owned),
+        })
+    }
+}
+
+impl AuthClientInfo {
+    /// IP address of the client
+    pub fn ip(&self) -> Option<IpAddr> {
+        self.ip
+    }
+}
+
+/// Information about a login session, tracked in Redis
+#[derive(Debug, JsonSchema, serde::Serialize, serde::Deserialize)]
+pub struct AuthSessionInfo {
+    /// User agent of the client
+    pub device: Option<String>,
+    /// Last IP address of the client
+    pub ip: Option<String>,
+    /// When the user logged in
+    pub created_at: DateTime<Utc>,
+    /// Last activity of the session (updated at most once per minute)
+    pub last_seen_at: DateTime<Utc>,
+    /// ID of the admin who started the session to impersonate the user
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonated_by: Option<Uuid>,
+}
+
+/// Get the prefix of the Redis keys of the user's tracked login sessions
+fn get_auth_session_prefix(user_id: &Uuid) -> String {
+    redis_key(format_args!("user:{}:auth_session:", user_id))
+}
+
+/// Get the Redis key of the tracked info of the user's login session
+fn get_auth_session_key(user_id: &Uuid, session_id: &Uuid) -> String {
+    format!("{}{}", get_auth_session_prefix(user_id), session_id)
+}
+
+/// Save the info of the user's login session, which expires with the session
+async fn save_auth_session_info(
+    redis: &fred::clients::Client,
+    user_id: &Uuid,
+    session_id: &Uuid,
+    info: &AuthSessionInfo,
+) -> FredResult<()> {
+    let value = serde_json::to_string(info).unwrap_or_default();
+    redis
+        .set(
+            get_auth_session_key(user_id, session_id),
+            value,
+            Some(Expiration::EX(SESSION_TTL)),
+            None,
+            false,
+        )
+        .await
+}
+
+/// Start tracking a new login session.
+async fn track_auth_session(
+    redis: &fred::clients::Client,
+    session: &ChatRsAuthSession,
+    client: &AuthClientInfo,
+    impersonated_by: Option<Uuid>,
+) -> FredResult<()> {
+    let (Some(user_id), Some(session_id)) = (session.user_id(), session.session_id()) else {
+        return Ok(());
+    };
+    let now = Utc::now();
+    let info = AuthSessionInfo {
+        device: client.user_agent.clone(),
+        ip: client.ip.map(|ip| ip.to_string()),
+        created_at: now,
+        last_seen_at: now,
+        impersonated_by,
+    };
+    save_auth_session_info(redis, &user_id, &session_id, &info).await
+}
+
+/// Create a new login session for the user, and track it for the user's list of sessions.
+pub async fn start_auth_session(
+    session: &mut Session<'_, ChatRsAuthSession>,
+    redis: &fred::clients::Client,
+    client: &AuthClientInfo,
+    user_id: Uuid,
+) -> FredResult<()> {
+    let auth_session = ChatRsAuthSession::new(user_id);
+    track_auth_session(redis, &auth_session, client, None).await?;
+    session.set(auth_session);
+
+    Ok(())
+}
+
+/// Replace the admin's session with a new session of the user, for debugging. The session is
+/// listed in the user's sessions, marked with the admin's ID.
+pub async fn start_impersonation_session(
+    session: &mut Session<'_, ChatRsAuthSession>,
+    redis: &fred::clients::Client,
+    client: &AuthClientInfo,
+    user_id: Uuid,
+    admin_id: Uuid,
+) -> FredResult<()> {
+    let auth_session = ChatRsAuthSession::new(user_id);
+    track_auth_session(redis, &auth_session, client, Some(admin_id)).await?;
+    session.set(auth_session);
+
+    Ok(())
+}
+
+/// Update the last activity of a tracked login session. Returns `false` if the session was
+/// revoked or has expired.
+pub async fn touch_auth_session(
+    redis: &fred::clients::Client,
+    user_id: &Uuid,
+    session_id: &Uuid,
+    client_ip: Option<IpAddr>,
+) -> FredResult<bool> {
+    let key = get_auth_session_key(user_id, session_id);
+    let Some(value): Option<String> = redis.get(&key).await? else {
+        return Ok(false);
+    };
+    let Ok(mut info) = serde_json::from_str::<AuthSessionInfo>(&value) else {
+        return Ok(false);
+    };
+    let now = Utc::now();
+    if (now - info.last_seen_at).num_seconds() >= LAST_SEEN_INTERVAL {
+        info.last_seen_at = now;
+        if let Some(ip) = client_ip {
+            info.ip = Some(ip.to_string());
+        }
+        save_auth_session_info(redis, user_id, session_id, &info).await?;
+    }
+
+    Ok(true)
+}
+
+/// Get the tracked login sessions of the user, most recently active first.
+pub async fn get_auth_sessions(
+    redis: &fred::clients::Client,
+    user_id: &Uuid,
+) -> FredResult<Vec<(Uuid, AuthSessionInfo)>> {
+    let prefix = get_auth_session_prefix(user_id);
+    let pattern = format!("{}*", prefix);
+    let mut keys = Vec::new();
+    let mut cursor = "0".to_owned();
+    loop {
+        let (next_cursor, page): (String, Vec<String>) = redis
+            .scan_page(cursor, &pattern, Some(100), Some(ScanType::String))
+            .await?;
+        keys.extend(page);
+        if next_cursor == "0" {
+            break;
+        }
+        cursor = next_cursor;
+    }
+
+    let mut sessions = Vec::with_capacity(keys.len());
+    for key in keys {
+        let Some(session_id) = key
+            .strip_prefix(&prefix)
+            .and_then(|id| Uuid::parse_str(id).ok())
+        else {
+            continue;
+        };
+        let value: Option<String> = redis.get(&key).await?;
+        if let Some(info) = value.and_then(|value| serde_json::from_str(&value).ok()) {
+            sessions.push((session_id, info));
+        }
+    }
+    sessions.sort_by(|(_, a), (_, b)| b.last_seen_at.cmp(&a.last_seen_at));
+
+    Ok(sessions)
+}
+
+/// Revoke a login session of the user. Returns `false` if the session wasn't found.
+pub async fn revoke_auth_session(
+    redis: &fred::clients::Client,
+    user_id: &Uuid,
+    session_id: &Uuid,
+) -> FredResult<bool> {
+    let deleted: i64 = redis.del(get_auth_session_key(user_id, session_id)).await?;
+    Ok(deleted > 0)
+}
+
+/// Revoke all login sessions of the user, e.g. after a password reset.
+pub async fn revoke_all_auth_sessions(
+    redis: &fred::clients::Client,
+    user_id: &Uuid,
+) -> FredResult<()> {
+    for (session_id, _) in get_auth_sessions(redis, user_id).await? {
+        revoke_auth_session(redis, user_id, &session_id).await?;
+    }
+
+    Ok(())
+}
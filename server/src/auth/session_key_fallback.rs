@@ -0,0 +1,179 @@
+//! Fallback to the previous secret keys for the session cookies. Rocket decrypts the private
+//! cookies with the current secret key only, so without it, rotating the key would log out all
+//! users. A session cookie encrypted with a previous key (see `RS_CHAT_PREVIOUS_SECRET_KEYS`)
+//! is re-issued under the current key, and a request rejected because of the old cookie is
+//! redirected to itself, so the client retries it with the re-issued cookie.
+
+use std::io::Cursor;
+
+use cookie::{CookieJar as RawCookieJar, Key};
+use rocket::{
+    fairing::AdHoc,
+    http::{Cookie, Header, SameSite, Status},
+    time::Duration,
+};
+
+use crate::{
+    auth::session::{SESSION_COOKIE_NAME, SESSION_TTL},
+    config::get_app_config,
+};
+
+/// Cookie keys of the current and previous secret keys
+struct SessionCookieKeys {
+    current: Key,
+    previous: Vec<Key>,
+}
+
+/// Session cookie re-issued under the current key during the request (already encrypted)
+struct ReissuedSessionCookie(Option<Cookie<'static>>);
+
+impl SessionCookieKeys {
+    /// Decrypt the cookie with the previous keys, and encrypt it again with the current key.
+    fn reissue(&self, cookie: &Cookie<'static>) -> Option<Cookie<'static>> {
+        let jar = RawCookieJar::new();
+        let decrypted = self
+            .previous
+            .iter()
+            .find_map(|key| jar.private(key).decrypt(cookie.clone()))?;
+
+        let mut jar = RawCookieJar::new();
+        jar.private_mut(&self.current).add(
+            Cookie::build((SESSION_COOKIE_NAME, decrypted.value().to_owned()))
+                .path("/")
+                .http_only(true)
+                .same_site(SameSite::Lax)
+                .max_age(Duration::seconds(SESSION_TTL)),
+        );
+        jar.get(SESSION_COOKIE_NAME).cloned()
+    }
+}
+
+/// Derive the cookie key from a secret key, the same way as Rocket does for its secret key.
+fn derive_cookie_key(secret_key: &str) -> Option<Key> {
+    let bytes = hex::decode(secret_key).ok()?;
+    match bytes.len() {
+        32 => Some(Key::derive_from(&bytes)),
+        64 => Key::try_from(bytes.as_slice()).ok(),
+        _ => None,
+    }
+}
+
+/// Fairing that re-issues the session cookies encrypted with a previous secret key.
+pub fn setup_session_key_fallback() -> AdHoc {
+    AdHoc::on_ignite("Session key fallback", |rocket| async {
+        let app_config = get_app_config(&rocket);
+        let previous: Vec<Key> = app_config
+            .previous_secret_keys
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|key| derive_cookie_key(key))
+            .collect();
+        let Some(current) = derive_cookie_key(&app_config.secret_key) else {
+            return rocket;
+        };
+        if previous.is_empty() {
+            return rocket;
+        }
+
+        rocket
+            .manage(SessionCookieKeys { current, previous })
+            .attach(AdHoc::on_request("Session key fallback", |req, _| {
+                Box::pin(async move {
+                    let Some(keys) = req.rocket().state::<SessionCookieKeys>() else {
+                        return;
+                    };
+                    let cookies = req.cookies();
+                    let Some(cookie) = cookies.get(SESSION_COOKIE_NAME) else {
+                        return;
+                    };
+                    if cookies.get_private(SESSION_COOKIE_NAME).is_some() {
+                        return;
+                    }
+                    if let Some(reissued) = keys.reissue(cookie) {
+                        rocket::info!("Re-issuing session cookie under the current secret key");
+                        req.local_cache(|| ReissuedSessionCookie(Some(reissued)));
+                    }
+                })
+            }))
+            .attach(AdHoc::on_response("Session key fallback", |req, res| {
+                Box::pin(async move {
+                    let ReissuedSessionCookie(Some(reissued)) =
+                        req.local_cache(|| ReissuedSessionCookie(None))
+                    else {
+                        return;
+                    };
+                    // Replace any cookie set for the session (error responses don't keep
+                    // the cookies set during the request)
+                    let other_cookies: Vec<String> = res
+                        .headers()
+                        .get("Set-Cookie")
+                        .filter(|header| {
+                            Cookie::parse(*header)
+                                .map_or(true, |cookie| cookie.name() != SESSION_COOKIE_NAME)
+                        })
+                        .map(str::to_owned)
+                        .collect();
+                    res.remove_header("Set-Cookie");
+                    for header in other_cookies {
+                        res.adjoin_header(Header::new("Set-Cookie", header));
+                    }
+                    res.adjoin_header(reissued.clone());
+
+                    if res.status() == Status::Unauthorized {
+                        res.set_status(Status::TemporaryRedirect);
+                        res.set_raw_header("Location", req.uri().to_string());
+                        res.set_sized_body(0, Cursor::new(""));
+                    }
+                })
+            }))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD_KEY: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+    const NEW_KEY: &str = "1111111111111111111111111111111111111111111111111111111111111111";
+
+    fn encrypt_session_cookie(secret_key: &str, session_id: &str) -> Cookie<'static> {
+        let key = derive_cookie_key(secret_key).expect("valid key");
+        let mut jar = RawCookieJar::new();
+        jar.private_mut(&key)
+            .add(Cookie::new(SESSION_COOKIE_NAME, session_id.to_owned()));
+        jar.get(SESSION_COOKIE_NAME).cloned().expect("cookie added")
+    }
+
+    fn keys() -> SessionCookieKeys {
+        SessionCookieKeys {
+            current: derive_cookie_key(NEW_KEY).expect("valid key"),
+            previous: vec![derive_cookie_key(OLD_KEY).expect("valid key")],
+        }
+    }
+
+    #[test]
+    fn test_reissue_cookie_of_previous_key() {
+        let keys = keys();
+        let old_cookie = encrypt_session_cookie(OLD_KEY, "session-id");
+        let reissued = keys.reissue(&old_cookie).expect("should decrypt");
+
+        let jar = RawCookieJar::new();
+        let decrypted = jar.private(&keys.current).decrypt(reissued);
+        assert_eq!(decrypted.as_ref().map(Cookie::value), Some("session-id"));
+    }
+
+    #[test]
+    fn test_no_reissue_of_unknown_key() {
+        let other_key = "2222222222222222222222222222222222222222222222222222222222222222";
+        let cookie = encrypt_session_cookie(other_key, "session-id");
+        assert!(keys().reissue(&cookie).is_none());
+    }
+
+    #[test]
+    fn test_derive_cookie_key_rejects_invalid_keys() {
+        assert!(derive_cookie_key("not-hex").is_none());
+        assert!(derive_cookie_key("abcd").is_none());
+        assert!(derive_cookie_key(OLD_KEY).is_some());
+    }
+}
@@ -0,0 +1,302 @@
+//! Throttling of the email + password auth endpoints, against brute-force attacks. Failed
+//! attempts are counted in Redis for each IP address and each account, and once there are too
+//! many, the IP address or account is locked out for a while. The lockout doubles with each
+//! further lockout within a day. A CAPTCHA can also be required, with any provider that has
+//! an hCaptcha-compatible verification endpoint (hCaptcha, reCAPTCHA, Cloudflare Turnstile).
+
+use std::net::IpAddr;
+
+use fred::{
+    prelude::{FredResult, KeysInterface},
+    types::Expiration,
+};
+use rocket::fairing::AdHoc;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    auth::LocalAuthError,
+    config::get_config_provider,
+    errors::ApiError,
+    redis::{increment_count, redis_key},
+};
+
+/// Default number of failed attempts before a lockout
+const DEFAULT_MAX_FAILURES: u32 = 5;
+/// Default duration of the first lockout, in seconds
+const DEFAULT_LOCKOUT_SECONDS: i64 = 60;
+/// Default max duration of a lockout, in seconds
+const DEFAULT_MAX_LOCKOUT_SECONDS: i64 = 60 * 60 * 24; // 1 day
+/// Failed attempts are forgotten after this many seconds without another failure.
+const FAILURES_TTL: i64 = 60 * 15; // 15 minutes
+/// Lockouts are forgotten after this many seconds without another lockout.
+const LOCKOUTS_TTL: i64 = 60 * 60 * 24; // 1 day
+
+/// Throttling configuration. Can be set via environment variables.
+#[derive(Debug, Default, Deserialize)]
+struct AuthThrottleEnvConfig {
+    /// Failed attempts allowed before a lockout (default: 5). Set to 0 to disable throttling.
+    auth_max_failures: Option<u32>,
+    /// Duration of the first lockout in seconds (default: 60)
+    auth_lockout_seconds: Option<i64>,
+    /// Max duration of a lockout in seconds (default: 1 day)
+    auth_max_lockout_seconds: Option<i64>,
+    /// Verification endpoint of the CAPTCHA provider, e.g. `https://hcaptcha.com/siteverify`
+    captcha_verify_url: Option<String>,
+    /// Secret key of the CAPTCHA provider
+    captcha_secret: Option<String>,
+    /// Site key of the CAPTCHA provider, given to the frontend
+    captcha_site_key: Option<String>,
+}
+
+/// Throttling of the auth endpoints, added to Rocket state
+pub struct AuthThrottle {
+    max_failures: u32,
+    lockout_seconds: i64,
+    max_lockout_seconds: i64,
+    /// CAPTCHA required for the throttled endpoints, if configured
+    pub captcha: Option<CaptchaConfig>,
+}
+
+/// CAPTCHA configuration
+pub struct CaptchaConfig {
+    /// Site key of the CAPTCHA provider
+    pub site_key: String,
+    verify_url: String,
+    secret: String,
+    http_client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct CaptchaVerifyResponse {
+    success: bool,
+}
+
+/// Who the failed attempts are counted for
+#[derive(Debug, Clone, Copy)]
+pub enum ThrottleSubject<'a> {
+    /// IP address of the client
+    Ip(IpAddr),
+    /// Account with the (normalized) email
+    Account(&'a str),
+}
+
+impl ThrottleSubject<'_> {
+    /// Get the prefix of the subject's Redis keys. Only the hash of the email is stored.
+    fn key_prefix(&self) -> String {
+        match self {
+            ThrottleSubject::Ip(ip) => redis_key(format_args!("auth_throttle:ip:{}", ip)),
+            ThrottleSubject::Account(email) => {
+                let hash = hex::encode(Sha256::digest(email.as_bytes()));
+                redis_key(format_args!("auth_throttle:account:{}", hash))
+            }
+        }
+    }
+}
+
+/// Fairing that sets up the throttling of the auth endpoints
+pub fn setup_auth_throttle() -> AdHoc {
+    AdHoc::on_ignite("Auth throttling", |rocket| async {
+        let config = get_config_provider()
+            .extract::<AuthThrottleEnvConfig>()
+            .unwrap_or_default();
+        let captcha = match (config.captcha_verify_url, config.captcha_secret) {
+            (Some(verify_url), Some(secret)) => Some(CaptchaConfig {
+                site_key: config
+                    .captcha_site_key
+                    .expect("RS_CHAT_CAPTCHA_SITE_KEY should be set"),
+                verify_url,
+                secret,
+                http_client: reqwest::Client::new(),
+            }),
+            _ => None,
+        };
+        if captcha.is_some() {
+            rocket::info!("Auth throttling: CAPTCHA enabled!");
+        }
+        let throttle = AuthThrottle {
+            max_failures: config.auth_max_failures.unwrap_or(DEFAULT_MAX_FAILURES),
+            lockout_seconds: config
+                .auth_lockout_seconds
+                .unwrap_or(DEFAULT_LOCKOUT_SECONDS),
+            max_lockout_seconds: config
+                .auth_max_lockout_seconds
+                .unwrap_or(DEFAULT_MAX_LOCKOUT_SECONDS),
+            captcha,
+        };
+
+        rocket.manage(throttle)
+    })
+}
+
+impl AuthThrottle {
+    fn is_enabled(&self) -> bool {
+        self.max_failures > 0
+    }
+
+    /// Check that none of the subjects are locked out
+    pub async fn check(
+        &self,
+        redis: &fred::clients::Client,
+        subjects: &[ThrottleSubject<'_>],
+    ) -> Result<(), ApiError> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        for subject in subjects {
+            let ttl: i64 = redis
+                .ttl(format!("{}:lockout", subject.key_prefix()))
+                .await?;
+            if ttl > 0 {
+                return Err(LocalAuthError::TooManyAttempts(ttl).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Verify the CAPTCHA token, if a CAPTCHA is required
+    pub async fn verify_captcha(
+        &self,
+        token: Option<&str>,
+        ip: Option<IpAddr>,
+    ) -> Result<(), LocalAuthError> {
+        match &self.captcha {
+            Some(captcha) => captcha.verify(token, ip).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Duration of a lockout in seconds, doubling with each lockout (starting at 1) up to the
+    /// max duration
+    fn lockout_duration(&self, lockouts: u64) -> i64 {
+        self.lockout_seconds
+            .saturating_mul(1_i64 << lockouts.saturating_sub(1).min(30))
+            .min(self.max_lockout_seconds)
+    }
+
+    /// Count a failed attempt for the subjects, locking them out if there are too many
+    pub async fn record_failure(
+        &self,
+        redis: &fred::clients::Client,
+        subjects: &[ThrottleSubject<'_>],
+    ) -> FredResult<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        for subject in subjects {
+            let prefix = subject.key_prefix();
+            let failures_key = format!("{}:failures", prefix);
+            let failures = increment_count(redis, &failures_key, FAILURES_TTL).await?;
+            if failures < self.max_failures.into() {
+                continue;
+            }
+
+            let lockouts =
+                increment_count(redis, &format!("{}:lockouts", prefix), LOCKOUTS_TTL).await?;
+            let duration = self.lockout_duration(lockouts);
+            rocket::warn!("Auth throttling: locking out {} for {}s", prefix, duration);
+            let _: () = redis
+                .set(
+                    format!("{}:lockout", prefix),
+                    "1",
+                    Some(Expiration::EX(duration)),
+                    None,
+                    false,
+                )
+                .await?;
+            let _: () = redis.del(&failures_key).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Forget the failed attempts of the subjects, after a successful login
+    pub async fn record_success(
+        &self,
+        redis: &fred::clients::Client,
+        subjects: &[ThrottleSubject<'_>],
+    ) -> FredResult<()> {
+        for subject in subjects {
+            let prefix = subject.key_prefix();
+            let keys = vec![
+                format!("{}:failures", prefix),
+                format!("{}:lockouts", prefix),
+            ];
+            let _: () = redis.del(keys).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl CaptchaConfig {
+    /// Verify the CAPTCHA token with the provider
+    async fn verify(&self, token: Option<&str>, ip: Option<IpAddr>) -> Result<(), LocalAuthError> {
+        let token = token
+            .filter(|token| !token.is_empty())
+            .ok_or(LocalAuthError::InvalidCaptcha)?;
+        let mut form = vec![
+            ("secret", self.secret.clone()),
+            ("response", token.to_owned()),
+        ];
+        if let Some(ip) = ip {
+            form.push(("remoteip", ip.to_string()));
+        }
+        let response: CaptchaVerifyResponse = self
+            .http_client
+            .post(&self.verify_url)
+            .form(&form)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| LocalAuthError::Captcha(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| LocalAuthError::Captcha(e.to_string()))?;
+        if !response.success {
+            return Err(LocalAuthError::InvalidCaptcha);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_throttle(lockout_seconds: i64, max_lockout_seconds: i64) -> AuthThrottle {
+        AuthThrottle {
+            max_failures: DEFAULT_MAX_FAILURES,
+            lockout_seconds,
+            max_lockout_seconds,
+            captcha: None,
+        }
+    }
+
+    #[test]
+    fn test_lockout_duration() {
+        let throttle = new_throttle(60, 1000);
+        assert_eq!(throttle.lockout_duration(1), 60);
+        assert_eq!(throttle.lockout_duration(2), 120);
+        assert_eq!(throttle.lockout_duration(3), 240);
+        assert_eq!(throttle.lockout_duration(4), 480);
+        assert_eq!(throttle.lockout_duration(5), 960);
+        assert_eq!(throttle.lockout_duration(6), 1000);
+        assert_eq!(throttle.lockout_duration(0), 60);
+    }
+
+    #[test]
+    fn test_lockout_duration_overflow() {
+        let throttle = new_throttle(i64::MAX / 2, i64::MAX);
+        assert_eq!(throttle.lockout_duration(3), i64::MAX);
+        let throttle = new_throttle(60, DEFAULT_MAX_LOCKOUT_SECONDS);
+        for lockouts in [31, 32, 64, 1000, u64::MAX] {
+            assert_eq!(
+                throttle.lockout_duration(lockouts),
+                DEFAULT_MAX_LOCKOUT_SECONDS
+            );
+        }
+    }
+}
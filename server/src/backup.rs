@@ -0,0 +1,463 @@
+//! Backup and restore of the instance data, for migrating to another instance without access
+//! to the database. The backup is a consistent logical export of the tables, streamed as a tar
+//! archive containing:
+//!
+//! - `manifest.json`: the backup version, creation time, salt, and number of rows per table
+//! - `tables/<table>/<batch>.json`: the rows of each table, in batches
+//!
+//! The tables are exported and restored in batches, so that they're never fully loaded in
+//! memory.
+//!
+//! Secrets, provider headers, and encrypted messages are re-encrypted with a key derived from
+//! the backup passphrase, so that the backup can be restored on an instance with a different
+//! secret key. On restore, they're re-encrypted with the secret key of the instance, and all
+//! the messages are encrypted if message encryption is enabled on the instance.
+//!
+//! API keys, webhooks, login sessions, stored files, knowledge bases, and usage data are not
+//! included. Restores are meant for new instances: rows that already exist are skipped.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Utc};
+use rocket::futures::StreamExt;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, DuplexStream},
+    sync::mpsc,
+};
+
+use crate::{
+    data_export::append_json,
+    db::{
+        services::{BackupDbService, ExportBatch, ImportBatch},
+        DbConnection,
+    },
+    errors::ApiError,
+    provider::LlmError,
+    utils::{decrypt_content_with, encrypt_content_with, Encryptor, MessageEncryption},
+};
+
+/// Version of the backup format
+const BACKUP_VERSION: u32 = 1;
+/// Minimum length of the backup passphrase
+const MIN_PASSPHRASE_LENGTH: usize = 12;
+/// Length of the salt for deriving the backup key, in bytes
+const SALT_LENGTH: usize = 16;
+/// Size of the buffer between the archive writer and the response, in bytes
+const ARCHIVE_BUFFER_SIZE: usize = 64 * 1024;
+/// Number of rows in each batch of a table
+const BATCH_SIZE: usize = 1000;
+/// Path of the manifest in the archive, which must be the first file
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Tables included in the backup, in the order they're restored (referenced tables first)
+const BACKUP_TABLES: &[&str] = &[
+    "users",
+    "organizations",
+    "organization_members",
+    "secrets",
+    "providers",
+    "projects",
+    "chat_sessions",
+    "chat_messages",
+    "session_members",
+    "system_tools",
+    "external_api_tools",
+    "mcp_tools",
+];
+
+/// Encrypted columns of the backed up tables: the table, ciphertext column, and nonce column
+const ENCRYPTED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("secrets", "ciphertext", "nonce"),
+    ("providers", "extra_headers", "extra_headers_nonce"),
+];
+
+/// Table of the messages, whose content may be encrypted
+const MESSAGES_TABLE: &str = "chat_messages";
+
+/// Errors of backups
+#[derive(Debug, thiserror::Error)]
+pub enum BackupError {
+    #[error("Backup passphrase must be at least {MIN_PASSPHRASE_LENGTH} characters")]
+    WeakPassphrase,
+    #[error("Invalid backup archive: {0}")]
+    InvalidArchive(String),
+    #[error("Unsupported backup version: {0}")]
+    UnsupportedVersion(u32),
+    #[error("Failed to decrypt the backup: wrong passphrase?")]
+    Decryption,
+}
+
+/// Rows of a table, as exported by Postgres
+type Rows = Vec<serde_json::Map<String, Value>>;
+
+#[derive(Serialize, Deserialize)]
+struct BackupManifest {
+    version: u32,
+    created_at: DateTime<Utc>,
+    /// Hex-encoded salt for deriving the backup key from the passphrase
+    salt: String,
+    /// Number of rows of each table
+    tables: BTreeMap<String, usize>,
+}
+
+/// Backup of the instance data, exported from the database while writing the archive
+pub struct InstanceBackup {
+    db: DbConnection,
+    encryptor: Encryptor,
+    backup_encryptor: Encryptor,
+    salt: [u8; SALT_LENGTH],
+}
+
+/// Result of a restore
+#[derive(Debug, Default, JsonSchema, Serialize)]
+pub struct RestoreReport {
+    /// Number of restored rows of each table
+    pub restored: BTreeMap<String, usize>,
+    /// Number of rows of each table that were skipped, because they already exist
+    pub skipped: BTreeMap<String, usize>,
+}
+
+fn check_passphrase(passphrase: &str) -> Result<(), BackupError> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LENGTH {
+        return Err(BackupError::WeakPassphrase);
+    }
+    Ok(())
+}
+
+impl InstanceBackup {
+    /// Prepare the backup, deriving the key for re-encrypting the encrypted values from the
+    /// backup passphrase
+    pub fn new(
+        db: DbConnection,
+        encryptor: &Encryptor,
+        passphrase: &str,
+    ) -> Result<Self, ApiError> {
+        check_passphrase(passphrase)?;
+        let salt = rand::random::<[u8; SALT_LENGTH]>();
+        let backup_encryptor = Encryptor::from_passphrase(passphrase, &salt)?;
+
+        Ok(Self {
+            db,
+            encryptor: encryptor.clone(),
+            backup_encryptor,
+            salt,
+        })
+    }
+
+    /// Write the archive in a background task, and get the reader of the archive. The archive
+    /// is truncated if the export or writing fails.
+    pub fn into_archive(self) -> DuplexStream {
+        let (writer, reader) = tokio::io::duplex(ARCHIVE_BUFFER_SIZE);
+        tokio::spawn(async move {
+            if let Err(err) = self.write_archive(writer).await {
+                rocket::warn!("Failed to write backup: {}", err);
+            }
+        });
+
+        reader
+    }
+
+    /// Export the tables in batches, and write each batch to the archive as it's exported. The
+    /// archive is only finished if the whole export succeeded.
+    async fn write_archive(self, writer: DuplexStream) -> std::io::Result<()> {
+        let InstanceBackup {
+            mut db,
+            encryptor,
+            backup_encryptor,
+            salt,
+        } = self;
+        let (sender, mut receiver) = mpsc::channel(1);
+        let export = BackupDbService::new(&mut db).export_tables(BACKUP_TABLES, BATCH_SIZE, sender);
+        let write = async {
+            let mut tar = tokio_tar::Builder::new(writer);
+            let mut batch_numbers: HashMap<&str, usize> = HashMap::new();
+            while let Some(batch) = receiver.recv().await {
+                match batch {
+                    ExportBatch::Counts(counts) => {
+                        let manifest = BackupManifest {
+                            version: BACKUP_VERSION,
+                            created_at: Utc::now(),
+                            salt: hex::encode(salt),
+                            tables: counts
+                                .into_iter()
+                                .map(|(table, count)| (table.to_owned(), count))
+                                .collect(),
+                        };
+                        append_json(&mut tar, MANIFEST_FILE, &manifest).await?;
+                    }
+                    ExportBatch::Rows(table, rows) => {
+                        let mut rows = parse_exported_rows(&rows).map_err(std::io::Error::other)?;
+                        reencrypt_columns(table, &mut rows, &encryptor, &backup_encryptor)
+                            .map_err(std::io::Error::other)?;
+                        if table == MESSAGES_TABLE {
+                            seal_messages(&mut rows, &encryptor, &backup_encryptor)
+                                .map_err(std::io::Error::other)?;
+                        }
+                        let number = batch_numbers.entry(table).or_default();
+                        *number += 1;
+                        let path = format!("tables/{table}/{number:06}.json");
+                        append_json(&mut tar, &path, &rows).await?;
+                    }
+                }
+            }
+            Ok::<_, std::io::Error>(tar)
+        };
+
+        let (exported, written) = tokio::join!(export, write);
+        let tar = written?;
+        exported.map_err(std::io::Error::other)?;
+        tar.finish().await
+    }
+}
+
+/// File name of the backup archive, for the download
+pub fn get_backup_name() -> String {
+    format!("rs-chat-backup-{}.tar", Utc::now().format("%Y%m%d-%H%M%S"))
+}
+
+/// Restore a backup archive while it's read, re-encrypting the encrypted values with the
+/// secret key of the instance. The batches of rows are inserted in one transaction, which is
+/// only committed if the whole archive is valid.
+pub async fn restore_backup(
+    db: &mut DbConnection,
+    encryptor: &Encryptor,
+    reader: impl AsyncRead + Unpin + Send,
+    passphrase: &str,
+) -> Result<RestoreReport, ApiError> {
+    let message_encryption = db.message_encryption().clone();
+    let (sender, receiver) = mpsc::channel(1);
+    let import = BackupDbService::new(db).import_tables(receiver);
+    let read = read_backup_archive(reader, encryptor, &message_encryption, passphrase, sender);
+    let (imported, manifest) = tokio::join!(import, read);
+    // Errors of the archive first, as they also stop the import
+    let manifest = manifest?;
+    let imported = imported?;
+    let manifest =
+        manifest.ok_or_else(|| BackupError::InvalidArchive("restore was interrupted".into()))?;
+
+    let mut report = RestoreReport::default();
+    for (table, total) in manifest.tables {
+        let restored = imported.get(table.as_str()).copied().unwrap_or_default();
+        report
+            .skipped
+            .insert(table.clone(), total.saturating_sub(restored));
+        report.restored.insert(table, restored);
+    }
+
+    Ok(report)
+}
+
+/// Read the backup archive, and send its batches of rows to the import after re-encrypting
+/// them. The end of the archive is only sent if the number of rows of each table matches the
+/// manifest. Returns `None` if the import stopped before the end of the archive.
+async fn read_backup_archive(
+    reader: impl AsyncRead + Unpin + Send,
+    encryptor: &Encryptor,
+    message_encryption: &MessageEncryption,
+    passphrase: &str,
+    sender: mpsc::Sender<ImportBatch>,
+) -> Result<Option<BackupManifest>, ApiError> {
+    let invalid = |err: std::io::Error| BackupError::InvalidArchive(err.to_string());
+    let mut archive = tokio_tar::Archive::new(reader);
+    let mut entries = archive.entries().map_err(invalid)?;
+
+    let manifest: BackupManifest = match next_entry(&mut entries).await? {
+        Some((path, content)) if path == MANIFEST_FILE => serde_json::from_slice(&content)
+            .map_err(|err| BackupError::InvalidArchive(format!("{MANIFEST_FILE}: {err}")))?,
+        _ => {
+            let message = format!("{MANIFEST_FILE} must be the first file");
+            return Err(BackupError::InvalidArchive(message).into());
+        }
+    };
+    if manifest.version != BACKUP_VERSION {
+        return Err(BackupError::UnsupportedVersion(manifest.version).into());
+    }
+    let salt = hex::decode(&manifest.salt)
+        .map_err(|_| BackupError::InvalidArchive("invalid salt".into()))?;
+    let backup_encryptor = Encryptor::from_passphrase(passphrase, &salt)?;
+
+    let mut counts: HashMap<&'static str, usize> = HashMap::new();
+    let mut table_index = 0;
+    while let Some((path, content)) = next_entry(&mut entries).await? {
+        let Some(table) = get_batch_table(&path, &mut table_index)? else {
+            continue;
+        };
+        let mut rows = parse_rows(table, &content)?;
+        reencrypt_columns(table, &mut rows, &backup_encryptor, encryptor)
+            .map_err(|_| BackupError::Decryption)?;
+        if table == MESSAGES_TABLE {
+            open_messages(&mut rows, &backup_encryptor, message_encryption)
+                .map_err(|_| BackupError::Decryption)?;
+        }
+        *counts.entry(table).or_default() += rows.len();
+        let rows = serde_json::to_string(&rows).map_err(LlmError::from)?;
+        if sender.send(ImportBatch::Rows(table, rows)).await.is_err() {
+            return Ok(None);
+        }
+    }
+
+    for (table, expected) in &manifest.tables {
+        let count = counts.get(table.as_str()).copied().unwrap_or_default();
+        if count != *expected {
+            let message = format!("table {table} has {count} rows instead of {expected}");
+            return Err(BackupError::InvalidArchive(message).into());
+        }
+    }
+    if sender.send(ImportBatch::Done).await.is_err() {
+        return Ok(None);
+    }
+
+    Ok(Some(manifest))
+}
+
+/// Read the next file of the archive: its path and content
+async fn next_entry<R: AsyncRead + Unpin + Send>(
+    entries: &mut tokio_tar::Entries<R>,
+) -> Result<Option<(String, Vec<u8>)>, BackupError> {
+    let invalid = |err: std::io::Error| BackupError::InvalidArchive(err.to_string());
+    let Some(entry) = entries.next().await else {
+        return Ok(None);
+    };
+    let mut entry = entry.map_err(invalid)?;
+    let path = entry
+        .path()
+        .map_err(invalid)?
+        .to_string_lossy()
+        .into_owned();
+    let mut content = Vec::new();
+    entry.read_to_end(&mut content).await.map_err(invalid)?;
+
+    Ok(Some((path, content)))
+}
+
+/// Get the table of a batch file (`tables/<table>/<batch>.json`), or `None` for other files.
+/// The tables must be in the order of the restore, so that referenced rows are inserted first.
+fn get_batch_table(path: &str, min_index: &mut usize) -> Result<Option<&'static str>, BackupError> {
+    let Some(index) = path
+        .strip_prefix("tables/")
+        .and_then(|path| path.split_once('/'))
+        .and_then(|(table, _)| BACKUP_TABLES.iter().position(|t| *t == table))
+    else {
+        return Ok(None);
+    };
+    if index < *min_index {
+        return Err(BackupError::InvalidArchive(format!(
+            "{path}: tables are out of order"
+        )));
+    }
+    *min_index = index;
+
+    Ok(Some(BACKUP_TABLES[index]))
+}
+
+/// Parse the rows exported by Postgres, as JSON objects
+fn parse_exported_rows(rows: &[String]) -> Result<Rows, LlmError> {
+    rows.iter()
+        .map(|row| serde_json::from_str(row).map_err(LlmError::from))
+        .collect()
+}
+
+fn parse_rows(table: &str, content: &[u8]) -> Result<Rows, BackupError> {
+    serde_json::from_slice(content)
+        .map_err(|err| BackupError::InvalidArchive(format!("table {table}: {err}")))
+}
+
+/// Re-encrypt the encrypted columns of the rows from one key to another
+fn reencrypt_columns(
+    table: &str,
+    rows: &mut Rows,
+    from: &Encryptor,
+    to: &Encryptor,
+) -> Result<(), LlmError> {
+    let columns = ENCRYPTED_COLUMNS.iter().filter(|(t, ..)| *t == table);
+    for (_, ciphertext_column, nonce_column) in columns {
+        for row in rows.iter_mut() {
+            let (Some(ciphertext), Some(nonce)) = (
+                get_bytea(row, ciphertext_column)?,
+                get_bytea(row, nonce_column)?,
+            ) else {
+                continue;
+            };
+            let plaintext = from.decrypt_bytes(&ciphertext, &nonce)?;
+            let (ciphertext, nonce) = to.encrypt_bytes(&plaintext)?;
+            row.insert(ciphertext_column.to_string(), format_bytea(&ciphertext));
+            row.insert(nonce_column.to_string(), format_bytea(&nonce));
+        }
+    }
+
+    Ok(())
+}
+
+/// Re-encrypt the encrypted messages with the backup key
+fn seal_messages(
+    rows: &mut Rows,
+    encryptor: &Encryptor,
+    backup_encryptor: &Encryptor,
+) -> Result<(), LlmError> {
+    for row in rows.iter_mut() {
+        if !is_encrypted_row(row) {
+            continue;
+        }
+        let Some(Value::String(content)) = row.get_mut("content") else {
+            continue;
+        };
+        let plaintext = decrypt_content_with(encryptor, content)?;
+        *content = encrypt_content_with(backup_encryptor, &plaintext)?;
+    }
+
+    Ok(())
+}
+
+/// Decrypt the encrypted messages of the backup, and encrypt all the messages if message
+/// encryption is enabled on this instance
+fn open_messages(
+    rows: &mut Rows,
+    backup_encryptor: &Encryptor,
+    message_encryption: &MessageEncryption,
+) -> Result<(), LlmError> {
+    for row in rows.iter_mut() {
+        let encrypted = is_encrypted_row(row);
+        let Some(Value::String(content)) = row.get_mut("content") else {
+            continue;
+        };
+        let plaintext = match encrypted {
+            true => decrypt_content_with(backup_encryptor, content)?,
+            false => std::mem::take(content),
+        };
+        let encrypted = message_encryption.encrypt(&plaintext)?;
+        row.insert("content_encrypted".into(), Value::Bool(encrypted.is_some()));
+        row.insert(
+            "content".into(),
+            Value::String(encrypted.unwrap_or(plaintext)),
+        );
+    }
+
+    Ok(())
+}
+
+/// Whether the content of a message row is encrypted
+fn is_encrypted_row(row: &serde_json::Map<String, Value>) -> bool {
+    matches!(row.get("content_encrypted"), Some(Value::Bool(true)))
+}
+
+/// Get the value of a `bytea` column, which Postgres exports in the hex format (`\x...`)
+fn get_bytea(
+    row: &serde_json::Map<String, Value>,
+    column: &str,
+) -> Result<Option<Vec<u8>>, LlmError> {
+    match row.get(column) {
+        Some(Value::String(value)) => value
+            .strip_prefix("\\x")
+            .and_then(|hex_value| hex::decode(hex_value).ok())
+            .map(Some)
+            .ok_or(LlmError::DecryptionError),
+        _ => Ok(None),
+    }
+}
+
+fn format_bytea(bytes: &[u8]) -> Value {
+    Value::String(format!("\\x{}", hex::encode(bytes)))
+}
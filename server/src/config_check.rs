@@ -0,0 +1,361 @@
+//! Validation of the server config. The config is checked on startup, which fails if there are
+//! errors, and a report of the effective config (with the secrets redacted) is logged. Run the
+//! server with `--check-config` (e.g. `run-server --check-config`) to check the config and the
+//! connections to the database, Redis, and ClamAV, without starting the server.
+
+use std::{path::Path, time::Duration};
+
+use diesel_async::{
+    pooled_connection::{deadpool::Pool, AsyncDieselConnectionManager},
+    AsyncPgConnection,
+};
+use fred::prelude::{Client as RedisClient, ClientLike, Config as RedisConfig};
+use reqwest::Url;
+use rocket::{fairing::AdHoc, figment::Figment};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    auth::{DiscordOAuthConfig, GitHubOAuthConfig, GoogleOAuthConfig, OIDCConfig},
+    config::{get_app_config, get_config_provider, AppConfig},
+    scan::{FileScanner, ScanAction},
+    storage::DEFAULT_STORAGE_PATH,
+    utils::Encryptor,
+    web::WEB_DIST,
+};
+
+/// Timeout of the connection checks
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Config values that are replaced in the report
+const SECRET_FIELDS: &[&str] = &["secret_key", "previous_secret_keys", "admin_token"];
+/// Replacement of the secret values in the report
+const REDACTED: &str = "***";
+
+/// Result of a config check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckLevel {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// A checked config setting
+#[derive(Debug)]
+pub struct ConfigCheck {
+    pub name: &'static str,
+    pub level: CheckLevel,
+    pub message: String,
+}
+
+impl ConfigCheck {
+    fn ok(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            level: CheckLevel::Ok,
+            message: message.into(),
+        }
+    }
+
+    fn warning(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            level: CheckLevel::Warning,
+            message: message.into(),
+        }
+    }
+
+    fn error(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            level: CheckLevel::Error,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let level = match self.level {
+            CheckLevel::Ok => "ok",
+            CheckLevel::Warning => "warning",
+            CheckLevel::Error => "error",
+        };
+        write!(f, "[{}] {}: {}", level, self.name, self.message)
+    }
+}
+
+/// Check the config values, without connecting to the database or Redis
+pub fn check_config(config: &AppConfig, figment: &Figment) -> Vec<ConfigCheck> {
+    let mut checks = vec![check_secret_keys(config)];
+    if config.index_encrypted_messages.unwrap_or(false) && !config.encrypt_messages.unwrap_or(false)
+    {
+        checks.push(ConfigCheck::warning(
+            "index_encrypted_messages",
+            "has no effect unless encrypt_messages is enabled",
+        ));
+    }
+    checks.push(check_url(
+        "server_address",
+        &config.server_address,
+        &["http", "https"],
+    ));
+    checks.push(check_url(
+        "database_url",
+        &config.database_url,
+        &["postgres", "postgresql"],
+    ));
+    for url in config.database_replica_urls.iter().flatten() {
+        checks.push(check_url(
+            "database_replica_urls",
+            url,
+            &["postgres", "postgresql"],
+        ));
+    }
+    checks.push(match RedisConfig::from_url(&config.redis_url) {
+        Ok(_) => ConfigCheck::ok("redis_url", "valid Redis URL"),
+        Err(err) => ConfigCheck::error("redis_url", format!("invalid Redis URL: {err}")),
+    });
+    checks.extend(check_oauth(figment));
+    checks.push(check_storage_path(
+        config
+            .storage_path
+            .as_deref()
+            .unwrap_or(DEFAULT_STORAGE_PATH),
+    ));
+    checks.push(check_static_path(
+        config.static_path.as_deref().unwrap_or(WEB_DIST),
+    ));
+    if let Some(path) = &config.code_runner_wasm_path {
+        checks.push(match Path::new(path).is_dir() {
+            true => ConfigCheck::ok("code_runner_wasm_path", path.as_str()),
+            false => ConfigCheck::warning("code_runner_wasm_path", format!("{path} not found")),
+        });
+    }
+
+    checks
+}
+
+/// Check the connections to the database and Redis, and to clamd if it's configured
+pub async fn check_connections(config: &AppConfig) -> Vec<ConfigCheck> {
+    let mut checks = Vec::with_capacity(3);
+
+    let manager = AsyncDieselConnectionManager::<AsyncPgConnection>::new(&config.database_url);
+    let pool: Result<Pool<AsyncPgConnection>, _> = Pool::builder(manager).max_size(1).build();
+    checks.push(match pool {
+        Ok(pool) => {
+            let check = match tokio::time::timeout(CONNECT_TIMEOUT, pool.get()).await {
+                Ok(Ok(_)) => ConfigCheck::ok("database_url", "connected"),
+                Ok(Err(err)) => {
+                    ConfigCheck::error("database_url", format!("connection failed: {err}"))
+                }
+                Err(_) => ConfigCheck::error("database_url", "connection timed out"),
+            };
+            pool.close();
+            check
+        }
+        Err(err) => ConfigCheck::error("database_url", err.to_string()),
+    });
+
+    checks.push(match RedisConfig::from_url(&config.redis_url) {
+        Ok(redis_config) => {
+            let client = RedisClient::new(redis_config, None, None, None);
+            match tokio::time::timeout(CONNECT_TIMEOUT, client.init()).await {
+                Ok(Ok(_)) => {
+                    let _ = client.quit().await;
+                    ConfigCheck::ok("redis_url", "connected")
+                }
+                Ok(Err(err)) => {
+                    ConfigCheck::error("redis_url", format!("connection failed: {err}"))
+                }
+                Err(_) => ConfigCheck::error("redis_url", "connection timed out"),
+            }
+        }
+        Err(err) => ConfigCheck::error("redis_url", format!("invalid Redis URL: {err}")),
+    });
+
+    if let Some(address) = &config.clamav_address {
+        let scanner = FileScanner::new(address, ScanAction::default());
+        checks.push(match scanner.ping().await {
+            Ok(_) => ConfigCheck::ok("clamav_address", "connected"),
+            Err(err) => ConfigCheck::error("clamav_address", err.to_string()),
+        });
+    }
+
+    checks
+}
+
+/// Get the effective config, with the secret values and the passwords of URLs redacted
+pub fn get_redacted_config(config: &AppConfig) -> Value {
+    let mut value = serde_json::to_value(config).unwrap_or_default();
+    if let Value::Object(fields) = &mut value {
+        fields.retain(|_, value| !value.is_null());
+        for (name, value) in fields.iter_mut() {
+            if SECRET_FIELDS.contains(&name.as_str()) {
+                *value = Value::String(REDACTED.into());
+            } else {
+                redact_urls(value);
+            }
+        }
+    }
+
+    value
+}
+
+/// Replace the passwords of the URLs in the value
+fn redact_urls(value: &mut Value) {
+    match value {
+        Value::String(string) => {
+            if let Ok(mut url) = Url::parse(string) {
+                if url.password().is_some() && url.set_password(Some(REDACTED)).is_ok() {
+                    *string = url.to_string();
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact_urls),
+        _ => {}
+    }
+}
+
+fn check_secret_keys(config: &AppConfig) -> ConfigCheck {
+    match Encryptor::from_config(config) {
+        Ok(_) => {
+            let previous = config.previous_secret_keys.as_ref().map_or(0, Vec::len);
+            ConfigCheck::ok(
+                "secret_key",
+                format!("valid 32-byte key ({previous} previous keys)"),
+            )
+        }
+        Err(_) => ConfigCheck::error(
+            "secret_key",
+            "secret_key and previous_secret_keys must be 64-character hexadecimal strings",
+        ),
+    }
+}
+
+fn check_url(name: &'static str, url: &str, schemes: &[&str]) -> ConfigCheck {
+    match Url::parse(url) {
+        Ok(url) if schemes.contains(&url.scheme()) => ConfigCheck::ok(name, "valid URL"),
+        Ok(url) => ConfigCheck::error(
+            name,
+            format!(
+                "unsupported URL scheme `{}` (expected {})",
+                url.scheme(),
+                schemes.join(", ")
+            ),
+        ),
+        Err(err) => ConfigCheck::error(name, format!("invalid URL: {err}")),
+    }
+}
+
+/// Check the config of the OAuth providers that have at least one setting
+fn check_oauth(figment: &Figment) -> Vec<ConfigCheck> {
+    [
+        check_oauth_provider::<GitHubOAuthConfig>(figment, "GitHub", "github_"),
+        check_oauth_provider::<GoogleOAuthConfig>(figment, "Google", "google_"),
+        check_oauth_provider::<DiscordOAuthConfig>(figment, "Discord", "discord_"),
+        check_oauth_provider::<OIDCConfig>(figment, "OIDC", "oidc_"),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+fn check_oauth_provider<C: for<'de> Deserialize<'de>>(
+    figment: &Figment,
+    provider: &'static str,
+    prefix: &str,
+) -> Option<ConfigCheck> {
+    let configured = figment
+        .data()
+        .ok()?
+        .values()
+        .any(|dict| dict.keys().any(|key| key.starts_with(prefix)));
+    if !configured {
+        return None;
+    }
+
+    Some(match figment.extract::<C>() {
+        Ok(_) => ConfigCheck::ok(provider, "OAuth login enabled"),
+        Err(err) => ConfigCheck::error(provider, format!("incomplete OAuth config: {err}")),
+    })
+}
+
+/// Check that the storage directory exists (or can be created) and is writable
+fn check_storage_path(path: &str) -> ConfigCheck {
+    let probe = Path::new(path).join(".rs-chat-write-check");
+    let result = std::fs::create_dir_all(path)
+        .and_then(|_| std::fs::write(&probe, b""))
+        .and_then(|_| std::fs::remove_file(&probe));
+    match result {
+        Ok(_) => ConfigCheck::ok("storage_path", path),
+        Err(err) => ConfigCheck::error("storage_path", format!("{path} is not writable: {err}")),
+    }
+}
+
+fn check_static_path(path: &str) -> ConfigCheck {
+    match Path::new(path).join("index.html").is_file() {
+        true => ConfigCheck::ok("static_path", path),
+        false => ConfigCheck::warning(
+            "static_path",
+            format!("{path}/index.html not found, the web app won't be served"),
+        ),
+    }
+}
+
+/// Fairing that checks the config on startup and logs the report. Startup is aborted if the
+/// config has errors.
+pub fn setup_config_check() -> AdHoc {
+    AdHoc::try_on_ignite("Config check", |rocket| async {
+        let app_config = get_app_config(&rocket);
+        rocket::info!("Config: {}", get_redacted_config(app_config));
+
+        let mut has_errors = false;
+        for check in check_config(app_config, rocket.figment()) {
+            match check.level {
+                CheckLevel::Ok => {}
+                CheckLevel::Warning => rocket::warn!("Config: {}", check),
+                CheckLevel::Error => {
+                    rocket::error!("Config: {}", check);
+                    has_errors = true;
+                }
+            }
+        }
+
+        match has_errors {
+            true => Err(rocket),
+            false => Ok(rocket),
+        }
+    })
+}
+
+/// Run the `--check-config` command: load and check the server config, check the connections
+/// to the database and Redis, and print the report. Fails if the config has errors.
+pub async fn run_check_config_command() -> Result<(), Box<dyn std::error::Error>> {
+    let figment = get_config_provider();
+    let config: AppConfig = figment.extract()?;
+
+    println!("Effective config:");
+    if let Value::Object(fields) = get_redacted_config(&config) {
+        for (name, value) in fields {
+            println!("  {name} = {value}");
+        }
+    }
+
+    let mut checks = check_config(&config, &figment);
+    checks.extend(check_connections(&config).await);
+    println!("\nChecks:");
+    for check in &checks {
+        println!("  {check}");
+    }
+
+    let errors = checks
+        .iter()
+        .filter(|check| check.level == CheckLevel::Error)
+        .count();
+    if errors > 0 {
+        return Err(format!("Config has {errors} errors").into());
+    }
+    println!("\nConfig is valid");
+
+    Ok(())
+}
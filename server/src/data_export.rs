@@ -0,0 +1,154 @@
+//! Export of all the data of a user as a tar archive, so that users can download a copy of
+//! their account. Secrets and API keys are never exported, and tools only reference their
+//! secrets by ID. The archive contains:
+//!
+//! - `user.json`: the user's profile
+//! - `providers.json`: the user's providers, without their keys
+//! - `tools.json`: the user's system, external API, and MCP tools
+//! - `memories.json`: the user's memories
+//! - `sessions/<session_id>.json`: each chat session, with its messages
+//! - `files/`: the user's stored files
+
+use std::path::PathBuf;
+
+use serde::Serialize;
+use tokio::io::DuplexStream;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        models::{
+            ChatRsExternalApiTool, ChatRsMcpTool, ChatRsMemory, ChatRsMessage, ChatRsProvider,
+            ChatRsSession, ChatRsSystemTool, ChatRsUser,
+        },
+        services::{ChatDbService, MemoryDbService, ProviderDbService, ToolDbService},
+        DbConnection,
+    },
+    errors::ApiError,
+    storage::LocalStorage,
+};
+
+/// Size of the buffer between the archive writer and the response, in bytes
+const ARCHIVE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// All the data of a user, collected from the database before writing the archive
+pub struct UserDataExport {
+    user: ChatRsUser,
+    providers: Vec<ChatRsProvider>,
+    tools: ExportedTools,
+    memories: Vec<ChatRsMemory>,
+    sessions: Vec<ExportedSession>,
+    /// Directory of the user's stored files
+    files_dir: PathBuf,
+}
+
+#[derive(Serialize)]
+struct ExportedTools {
+    system: Vec<ChatRsSystemTool>,
+    external_api: Vec<ChatRsExternalApiTool>,
+    mcp: Vec<ChatRsMcpTool>,
+}
+
+#[derive(Serialize)]
+struct ExportedSession {
+    session: ChatRsSession,
+    messages: Vec<ChatRsMessage>,
+}
+
+impl UserDataExport {
+    /// Collect the user's data. Resources shared with the user by their organizations are
+    /// only exported by their owners.
+    pub async fn collect(
+        db: &mut DbConnection,
+        storage: &LocalStorage,
+        user: ChatRsUser,
+    ) -> Result<Self, ApiError> {
+        let user_id = user.id;
+        let mut providers = ProviderDbService::new(db).find_by_user_id(&user_id).await?;
+        providers.retain(|provider| provider.user_id == user_id);
+
+        let mut tool_db_service = ToolDbService::new(db);
+        let system = tool_db_service.find_system_tools_by_user(&user_id).await?;
+        let mut external_api = tool_db_service
+            .find_external_api_tools_by_user(&user_id)
+            .await?;
+        external_api.retain(|tool| tool.user_id == user_id);
+        let mut mcp = tool_db_service.find_mcp_tools_by_user(&user_id).await?;
+        mcp.retain(|tool| tool.user_id == user_id);
+
+        let memories = MemoryDbService::new(db)
+            .find_by_user_id(&user_id, i64::MAX)
+            .await?;
+
+        let mut chat_db_service = ChatDbService::new(db);
+        let chat_sessions = chat_db_service.find_sessions_by_user(&user_id).await?;
+        let mut sessions = Vec::with_capacity(chat_sessions.len());
+        for session in chat_sessions {
+            let messages = chat_db_service.get_messages(&session).await?;
+            sessions.push(ExportedSession { session, messages });
+        }
+
+        Ok(Self {
+            user,
+            providers,
+            tools: ExportedTools {
+                system,
+                external_api,
+                mcp,
+            },
+            memories,
+            sessions,
+            files_dir: storage.get_user_dir(&user_id),
+        })
+    }
+
+    /// Write the archive in a background task, and get the reader of the archive. The archive
+    /// is truncated if writing fails.
+    pub fn into_archive(self) -> DuplexStream {
+        let (writer, reader) = tokio::io::duplex(ARCHIVE_BUFFER_SIZE);
+        tokio::spawn(async move {
+            let user_id = self.user.id;
+            if let Err(err) = self.write_archive(writer).await {
+                rocket::warn!("Failed to write data export of user {}: {}", user_id, err);
+            }
+        });
+
+        reader
+    }
+
+    async fn write_archive(self, writer: DuplexStream) -> std::io::Result<()> {
+        let mut tar = tokio_tar::Builder::new(writer);
+        tar.follow_symlinks(false);
+        append_json(&mut tar, "user.json", &self.user).await?;
+        append_json(&mut tar, "providers.json", &self.providers).await?;
+        append_json(&mut tar, "tools.json", &self.tools).await?;
+        append_json(&mut tar, "memories.json", &self.memories).await?;
+        for session in &self.sessions {
+            let path = format!("sessions/{}.json", session.session.id);
+            append_json(&mut tar, &path, session).await?;
+        }
+        if tokio::fs::try_exists(&self.files_dir).await? {
+            tar.append_dir_all("files", &self.files_dir).await?;
+        }
+
+        tar.finish().await
+    }
+}
+
+/// Add a JSON file to the archive
+pub async fn append_json(
+    tar: &mut tokio_tar::Builder<DuplexStream>,
+    path: &str,
+    value: &impl Serialize,
+) -> std::io::Result<()> {
+    let content = serde_json::to_vec_pretty(value)?;
+    let mut header = tokio_tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    tar.append_data(&mut header, path, content.as_slice()).await
+}
+
+/// File name of the archive, for the download
+pub fn get_archive_name(user_id: &Uuid) -> String {
+    format!("rs-chat-export-{}.tar", user_id)
+}
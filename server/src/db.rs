@@ -1,12 +1,19 @@
 pub mod models;
+pub mod pagination;
 pub mod schema;
 pub mod services;
 
-use std::ops::{Deref, DerefMut};
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 use diesel_async::{
     pooled_connection::{
-        deadpool::{Object, Pool},
+        deadpool::{Object, Pool, PoolError},
         AsyncDieselConnectionManager,
     },
     AsyncPgConnection,
@@ -20,21 +27,34 @@ use rocket::{
 };
 use rocket_okapi::OpenApiFromRequest;
 
-use crate::config::get_app_config;
+use crate::{
+    config::get_app_config,
+    shutdown::StreamShutdown,
+    utils::{Encryptor, MessageEncryption},
+};
 
 /// Database connection, available as a request guard. When used as a request parameter,
 /// it will retrieve a connection from the managed Postgres pool.
 #[derive(OpenApiFromRequest)]
-pub struct DbConnection(pub Object<AsyncPgConnection>);
+pub struct DbConnection {
+    conn: Object<AsyncPgConnection>,
+    message_encryption: Arc<MessageEncryption>,
+}
+impl DbConnection {
+    /// Message encryption settings, used when saving and loading messages
+    pub fn message_encryption(&self) -> &MessageEncryption {
+        &self.message_encryption
+    }
+}
 impl Deref for DbConnection {
     type Target = Object<AsyncPgConnection>;
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.conn
     }
 }
 impl DerefMut for DbConnection {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        &mut self.conn
     }
 }
 
@@ -49,7 +69,7 @@ impl<'r> FromRequest<'r> for DbConnection {
             return Outcome::Error((Status::InternalServerError, "Database not initialized"));
         };
         match pool.get().await {
-            Ok(conn) => Outcome::Success(DbConnection(conn)),
+            Ok(conn) => Outcome::Success(conn),
             Err(e) => {
                 rocket::error!("Couldn't get database connection: {}", e);
                 Outcome::Error((Status::InternalServerError, "Couldn't get connection"))
@@ -58,50 +78,164 @@ impl<'r> FromRequest<'r> for DbConnection {
     }
 }
 
+/// Read-only database connection, available as a request guard. Retrieves a connection from
+/// one of the read replicas (in turn), or from the primary database if no replicas are
+/// configured or the replica is unavailable. Replicas can lag behind the primary, so this
+/// should only be used for queries that don't need the latest writes, e.g. listing sessions.
+#[derive(OpenApiFromRequest)]
+pub struct DbReadConnection(pub DbConnection);
+impl Deref for DbReadConnection {
+    type Target = DbConnection;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+impl DerefMut for DbReadConnection {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for DbReadConnection {
+    type Error = &'static str;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        if let Some(pool) = req
+            .rocket()
+            .state::<DbReplicaPools>()
+            .and_then(|p| p.next())
+        {
+            match pool.get().await {
+                Ok(conn) => return Outcome::Success(DbReadConnection(conn)),
+                Err(e) => rocket::warn!("Couldn't get read replica connection: {}", e),
+            }
+        }
+        DbConnection::from_request(req).await.map(DbReadConnection)
+    }
+}
+
 /** The database pool stored in Rocket's managed state */
-pub type DbPool = Pool<AsyncPgConnection>;
+#[derive(Clone)]
+pub struct DbPool {
+    pool: Pool<AsyncPgConnection>,
+    message_encryption: Arc<MessageEncryption>,
+}
+impl DbPool {
+    pub fn new(pool: Pool<AsyncPgConnection>, message_encryption: MessageEncryption) -> Self {
+        Self {
+            pool,
+            message_encryption: Arc::new(message_encryption),
+        }
+    }
+
+    /// Get a connection from the pool
+    pub async fn get(&self) -> Result<DbConnection, PoolError> {
+        Ok(DbConnection {
+            conn: self.pool.get().await?,
+            message_encryption: self.message_encryption.clone(),
+        })
+    }
 
-/// Fairing that sets up and initializes the Postgres database
+    /// Maximum number of connections of the pool
+    pub fn max_size(&self) -> usize {
+        self.pool.status().max_size
+    }
+
+    pub fn close(&self) {
+        self.pool.close();
+    }
+}
+
+/// The pools of the read replicas, stored in Rocket's managed state
+pub struct DbReplicaPools {
+    pools: Vec<DbPool>,
+    next: AtomicUsize,
+}
+impl DbReplicaPools {
+    /// Get the pool of the next replica (round-robin)
+    fn next(&self) -> Option<&DbPool> {
+        if self.pools.is_empty() {
+            return None;
+        }
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.pools.len();
+        self.pools.get(index)
+    }
+}
+
+/// Fairing that sets up and initializes the Postgres database. Must be attached after the
+/// encryption setup, for the message encryption.
 pub fn setup_db() -> AdHoc {
     AdHoc::on_ignite("Database", |rocket| async {
+        let app_config = get_app_config(&rocket);
+        let encryptor = rocket.state::<Encryptor>().expect("should be set up");
+        let message_encryption = MessageEncryption::from_config(app_config, encryptor);
+        let config =
+            AsyncDieselConnectionManager::<AsyncPgConnection>::new(&app_config.database_url);
+        let pool = DbPool::new(
+            Pool::builder(config)
+                .build()
+                .expect("Failed to parse database URL"),
+            message_encryption.clone(),
+        );
+        let replica_pools = DbReplicaPools {
+            pools: app_config
+                .database_replica_urls
+                .iter()
+                .flatten()
+                .map(|url| {
+                    let config = AsyncDieselConnectionManager::<AsyncPgConnection>::new(url);
+                    DbPool::new(
+                        Pool::builder(config)
+                            .build()
+                            .expect("Failed to parse database replica URL"),
+                        message_encryption.clone(),
+                    )
+                })
+                .collect(),
+            next: AtomicUsize::new(0),
+        };
+        if !replica_pools.pools.is_empty() {
+            rocket::info!("Database: {} read replicas", replica_pools.pools.len());
+        }
+        let mut conn = pool
+            .pool
+            .get()
+            .await
+            .expect("Failed to connect to database");
+
+        static MIGRATIONS: EmbeddedMigrations = embed_migrations!();
+        MIGRATIONS
+            .pending_migrations(&mut conn)
+            .await
+            .expect("Failed to get pending migrations")
+            .iter()
+            .for_each(|migration| {
+                rocket::info!("Running migration: {}", migration.name);
+            });
+        MIGRATIONS
+            .run_pending_migrations(&mut conn)
+            .await
+            .expect("Database migrations failed");
+        rocket::info!("Migrations completed successfully");
+
         rocket
-            .attach(AdHoc::on_ignite(
-                "Initialize database connection",
-                |rocket| async {
-                    let config = AsyncDieselConnectionManager::<AsyncPgConnection>::new(
-                        &get_app_config(&rocket).database_url,
-                    );
-                    let pool: DbPool = Pool::builder(config)
-                        .build()
-                        .expect("Failed to parse database URL");
-                    let mut conn = pool.get().await.expect("Failed to connect to database");
-
-                    static MIGRATIONS: EmbeddedMigrations = embed_migrations!();
-                    MIGRATIONS
-                        .pending_migrations(&mut conn)
-                        .await
-                        .expect("Failed to get pending migrations")
-                        .iter()
-                        .for_each(|migration| {
-                            rocket::info!("Running migration: {}", migration.name);
-                        });
-                    MIGRATIONS
-                        .run_pending_migrations(&mut conn)
-                        .await
-                        .expect("Database migrations failed");
-                    rocket::info!("Migrations completed successfully");
-
-                    rocket.manage(pool)
-                },
-            ))
+            .manage(pool)
+            .manage(replica_pools)
             .attach(AdHoc::on_shutdown(
                 "Shutdown database connection",
                 |rocket| {
                     Box::pin(async {
+                        if let Some(shutdown) = rocket.state::<StreamShutdown>() {
+                            shutdown.finished().await; // streams may still save responses
+                        }
                         if let Some(pool) = rocket.state::<DbPool>() {
                             rocket::info!("Shutting down database connection");
                             pool.close();
                         }
+                        if let Some(replica_pools) = rocket.state::<DbReplicaPools>() {
+                            replica_pools.pools.iter().for_each(|pool| pool.close());
+                        }
                     })
                 },
             ))
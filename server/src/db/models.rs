@@ -1,15 +1,47 @@
 mod api_key;
+mod attachment;
 mod chat;
+mod job;
+mod knowledge;
+mod memory;
+mod organization;
+mod persona;
+mod preset;
+mod project;
 mod provider;
+mod retention;
+mod schedule;
 mod secret;
+mod service_account;
+mod session_member;
+mod session_search;
 mod tool;
+mod tool_run;
+mod usage;
 mod user;
+mod webhook;
 
 use crate::db::schema;
 
 pub use api_key::*;
+pub use attachment::*;
 pub use chat::*;
+pub use job::*;
+pub use knowledge::*;
+pub use memory::*;
+pub use organization::*;
+pub use persona::*;
+pub use preset::*;
+pub use project::*;
 pub use provider::*;
+pub use retention::*;
+pub use schedule::*;
 pub use secret::*;
+pub use service_account::*;
+pub use session_member::*;
+pub use session_search::*;
 pub use tool::*;
+pub use tool_run::*;
+pub use usage::*;
 pub use user::*;
+pub use webhook::*;
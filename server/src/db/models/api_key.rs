@@ -4,6 +4,7 @@ use diesel::{
     Selectable,
 };
 use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::db::models::ChatRsUser;
@@ -17,6 +18,58 @@ pub struct ChatRsApiKey {
     pub user_id: Uuid,
     pub name: String,
     pub created_at: DateTime<Utc>,
+    /// What the API key can access
+    #[schemars(with = "Vec<ChatRsApiKeyScope>")]
+    pub scopes: Vec<String>,
+    /// When the API key expires
+    pub expires_at: Option<DateTime<Utc>>,
+    /// When the API key was last used (updated at most once per minute)
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// CIDR ranges that the API key can be used from (any address if not set)
+    pub allowed_ips: Option<Vec<String>>,
+}
+
+impl ChatRsApiKey {
+    /// Whether the API key has expired
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+
+    /// Whether the API key's scopes allow the given scope
+    pub fn allows(&self, scope: ChatRsApiKeyScope) -> bool {
+        self.scopes
+            .iter()
+            .any(|s| s == <&str>::from(&ChatRsApiKeyScope::Admin) || s == <&str>::from(&scope))
+    }
+}
+
+/// Scope of an API key
+#[derive(Debug, Clone, Copy, PartialEq, JsonSchema, Serialize, Deserialize)]
+pub enum ChatRsApiKeyScope {
+    /// Read chat sessions and connect to chat streams
+    #[serde(rename = "chat:read")]
+    ChatRead,
+    /// Send messages, and create and update chat sessions
+    #[serde(rename = "chat:write")]
+    ChatWrite,
+    /// Approve and execute tool calls
+    #[serde(rename = "tools:execute")]
+    ToolsExecute,
+    /// Full access, including the account's providers, tools, secrets, and API keys
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl From<&ChatRsApiKeyScope> for &str {
+    fn from(value: &ChatRsApiKeyScope) -> Self {
+        match value {
+            ChatRsApiKeyScope::ChatRead => "chat:read",
+            ChatRsApiKeyScope::ChatWrite => "chat:write",
+            ChatRsApiKeyScope::ToolsExecute => "tools:execute",
+            ChatRsApiKeyScope::Admin => "admin",
+        }
+    }
 }
 
 #[derive(Insertable)]
@@ -24,4 +77,7 @@ pub struct ChatRsApiKey {
 pub struct NewChatRsApiKey<'r> {
     pub user_id: &'r Uuid,
     pub name: &'r str,
+    pub scopes: &'r Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub allowed_ips: Option<&'r Vec<String>>,
 }
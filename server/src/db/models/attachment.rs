@@ -0,0 +1,48 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    prelude::{Associations, Identifiable, Insertable, Queryable},
+    Selectable,
+};
+use schemars::JsonSchema;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::db::models::ChatRsUser;
+
+/// A file uploaded by the user and attached to a chat message, or added to a project
+#[derive(Debug, Identifiable, Queryable, Selectable, Associations, JsonSchema, Serialize)]
+#[diesel(belongs_to(ChatRsUser, foreign_key = user_id))]
+#[diesel(table_name = super::schema::message_attachments)]
+pub struct ChatRsAttachment {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub user_id: Uuid,
+    /// The message the file is attached to (not set until the message is sent)
+    pub message_id: Option<Uuid>,
+    /// Original name of the file
+    pub name: String,
+    pub mime_type: String,
+    /// Path of the file in the storage (can be fetched from `/api/file/<storage_path>`)
+    pub storage_path: String,
+    /// Size of the file in bytes
+    pub size: i32,
+    pub created_at: DateTime<Utc>,
+    /// The project the file is added to, instead of a message
+    pub project_id: Option<Uuid>,
+    /// Text extracted from the document (PDF, DOCX, or HTML)
+    #[serde(skip)]
+    pub extracted_text: Option<String>,
+    /// When the text extraction finished (not set for other files, or while the text is being
+    /// extracted)
+    pub extracted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = super::schema::message_attachments)]
+pub struct NewChatRsAttachment<'r> {
+    pub user_id: &'r Uuid,
+    pub name: &'r str,
+    pub mime_type: &'r str,
+    pub storage_path: &'r str,
+    pub size: i32,
+}
@@ -10,8 +10,9 @@ use uuid::Uuid;
 
 use crate::{
     db::models::{ChatRsExecutedToolCall, ChatRsToolCall, ChatRsUser},
-    provider::{LlmProviderOptions, LlmUsage},
+    provider::{LlmAttachment, LlmFinishReason, LlmProviderOptions, LlmUsage},
     tools::SendChatToolInput,
+    utils::MessageEncryption,
 };
 
 #[derive(Identifiable, Associations, Queryable, Selectable, JsonSchema, serde::Serialize)]
@@ -23,8 +24,17 @@ pub struct ChatRsSession {
     pub user_id: Uuid,
     pub title: String,
     pub meta: ChatRsSessionMeta,
+    /// Pinned sessions are listed first
+    pub pinned: bool,
+    /// When the session was archived. Archived sessions are hidden from the session list
+    /// by default.
+    pub archived_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// When the session was moved to the trash
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// The project of the session
+    pub project_id: Option<Uuid>,
 }
 
 #[derive(Debug, Default, JsonSchema, Serialize, Deserialize, AsJsonb)]
@@ -32,11 +42,21 @@ pub struct ChatRsSessionMeta {
     /// User configuration of tools for this session
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_config: Option<SendChatToolInput>,
+    /// Last-used provider and options for this session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_config: Option<ChatRsSessionProviderConfig>,
+    /// The persona this session was created from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub persona_id: Option<Uuid>,
 }
-impl ChatRsSessionMeta {
-    pub fn new(tool_config: Option<SendChatToolInput>) -> Self {
-        Self { tool_config }
-    }
+
+/// Provider configuration remembered for a session
+#[derive(Debug, Clone, PartialEq, JsonSchema, Serialize, Deserialize)]
+pub struct ChatRsSessionProviderConfig {
+    /// The ID of the provider
+    pub provider_id: i32,
+    /// Configuration for the provider
+    pub options: LlmProviderOptions,
 }
 
 #[derive(Insertable)]
@@ -44,6 +64,8 @@ impl ChatRsSessionMeta {
 pub struct NewChatRsSession<'r> {
     pub user_id: &'r Uuid,
     pub title: &'r str,
+    pub meta: Option<&'r ChatRsSessionMeta>,
+    pub project_id: Option<&'r Uuid>,
 }
 
 #[derive(AsChangeset, Default)]
@@ -51,11 +73,13 @@ pub struct NewChatRsSession<'r> {
 pub struct UpdateChatRsSession<'r> {
     pub title: Option<&'r str>,
     pub meta: Option<&'r ChatRsSessionMeta>,
+    pub pinned: Option<bool>,
+    pub archived_at: Option<Option<DateTime<Utc>>>,
 }
 
 #[derive(diesel_derive_enum::DbEnum)]
 #[db_enum(existing_type_path = "crate::db::schema::sql_types::ChatMessageRole")]
-#[derive(Debug, PartialEq, Eq, JsonSchema, serde::Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, JsonSchema, serde::Serialize)]
 pub enum ChatRsMessageRole {
     User,
     Assistant,
@@ -73,6 +97,36 @@ pub struct ChatRsMessage {
     pub content: String,
     pub meta: ChatRsMessageMeta,
     pub created_at: DateTime<Utc>,
+    /// Whether the message is pinned, so that it's always sent to the assistant
+    pub pinned: bool,
+    /// Whether the content is still encrypted (see `decrypt`)
+    #[serde(skip)]
+    pub content_encrypted: bool,
+}
+
+impl ChatRsMessage {
+    /// Decrypt the content of the message if it's encrypted. If it can't be decrypted, the
+    /// encrypted content is kept.
+    pub fn decrypt(&mut self, encryption: &MessageEncryption) {
+        if !self.content_encrypted {
+            return;
+        }
+        if let Some(plaintext) = encryption.decrypt(&self.content) {
+            self.content = plaintext;
+            self.content_encrypted = false;
+        }
+    }
+}
+
+/// A message in the trash
+#[derive(Queryable, Selectable, JsonSchema, serde::Serialize)]
+#[diesel(table_name = super::schema::chat_messages)]
+pub struct ChatRsDeletedMessage {
+    #[diesel(embed)]
+    #[serde(flatten)]
+    pub message: ChatRsMessage,
+    /// When the message was moved to the trash
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Default, JsonSchema, Serialize, Deserialize, AsJsonb)]
@@ -83,12 +137,16 @@ pub struct ChatRsMessageMeta {
     /// Tool messages: metadata of the executed tool call
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_call: Option<ChatRsExecutedToolCall>,
+    /// User messages: the attached files, loaded from the storage before sending the messages
+    /// to the provider (not saved in the metadata)
+    #[serde(skip)]
+    pub attachments: Vec<LlmAttachment>,
 }
 impl ChatRsMessageMeta {
     pub fn new_assistant(assistant: AssistantMeta) -> Self {
         Self {
             assistant: Some(assistant),
-            tool_call: None,
+            ..Default::default()
         }
     }
 }
@@ -103,15 +161,52 @@ pub struct AssistantMeta {
     /// The tool calls requested by the assistant
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<ChatRsToolCall>>,
+    /// Dry runs: the tool calls the assistant would have made. These are only saved for
+    /// review, and can't be executed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub planned_tool_calls: Option<Vec<ChatRsToolCall>>,
     /// Provider usage information
     #[serde(skip_serializing_if = "Option::is_none")]
     pub usage: Option<LlmUsage>,
+    /// The reason the provider stopped generating the message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<LlmFinishReason>,
     /// Errors encountered during message generation
     #[serde(skip_serializing_if = "Option::is_none")]
     pub errors: Option<Vec<String>>,
     /// Whether this is a partial and/or interrupted message
     #[serde(skip_serializing_if = "Option::is_none")]
     pub partial: Option<bool>,
+    /// Feedback of the user on the message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feedback: Option<ChatRsMessageFeedback>,
+    /// The user who started this turn (the owner of the session if not set). The tools
+    /// offered to the assistant belong to this user, so only they can approve and execute the
+    /// tool calls.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<Uuid>,
+}
+
+/// Feedback of a user on an assistant message, e.g. to build evaluation datasets
+#[derive(Debug, Clone, JsonSchema, Serialize, Deserialize)]
+pub struct ChatRsMessageFeedback {
+    /// The user that gave the feedback
+    pub user_id: Uuid,
+    /// Thumbs up or down
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rating: Option<ChatRsFeedbackRating>,
+    /// Free-text comment on the message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// When the feedback was last updated
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, JsonSchema, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatRsFeedbackRating {
+    Up,
+    Down,
 }
 
 #[derive(Insertable)]
@@ -122,3 +217,15 @@ pub struct NewChatRsMessage<'r> {
     pub content: &'r str,
     pub meta: ChatRsMessageMeta,
 }
+
+/// A message imported from a transcript. The creation time is set explicitly to keep the
+/// order of the messages.
+#[derive(Insertable)]
+#[diesel(table_name = super::schema::chat_messages)]
+pub struct NewImportedChatRsMessage<'r> {
+    pub session_id: &'r Uuid,
+    pub role: ChatRsMessageRole,
+    pub content: &'r str,
+    pub meta: ChatRsMessageMeta,
+    pub created_at: DateTime<Utc>,
+}
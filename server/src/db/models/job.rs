@@ -0,0 +1,108 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    prelude::{AsChangeset, Associations, Identifiable, Insertable, Queryable},
+    Selectable,
+};
+use diesel_as_jsonb::AsJsonb;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    db::models::{ChatRsProvider, ChatRsUser},
+    provider::LlmProviderOptions,
+};
+
+#[derive(Identifiable, Associations, Queryable, Selectable, JsonSchema, Serialize)]
+#[diesel(belongs_to(ChatRsUser, foreign_key = user_id))]
+#[diesel(belongs_to(ChatRsProvider, foreign_key = provider_id))]
+#[diesel(table_name = super::schema::jobs)]
+pub struct ChatRsJob {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub user_id: Uuid,
+    pub provider_id: i32,
+    #[schemars(with = "ChatRsJobStatus")]
+    pub status: String,
+    /// ID of the batch submitted to the provider
+    #[serde(skip)]
+    pub batch_id: Option<String>,
+    pub data: ChatRsJobData,
+    pub result: Option<ChatRsJobResult>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Status of a background job
+#[derive(Debug, PartialEq, JsonSchema, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatRsJobStatus {
+    /// Submitted to the provider and waiting for results
+    Processing,
+    /// Results have been received
+    Completed,
+    /// The job could not be completed
+    Failed,
+}
+
+impl From<&ChatRsJobStatus> for &str {
+    fn from(value: &ChatRsJobStatus) -> Self {
+        match value {
+            ChatRsJobStatus::Processing => "processing",
+            ChatRsJobStatus::Completed => "completed",
+            ChatRsJobStatus::Failed => "failed",
+        }
+    }
+}
+
+/// Input of a background job
+#[derive(Debug, JsonSchema, Serialize, Deserialize, AsJsonb)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChatRsJobData {
+    /// Summarize chat sessions
+    SummarizeSessions {
+        /// IDs of the sessions to summarize
+        session_ids: Vec<Uuid>,
+        /// Configuration for the provider
+        options: LlmProviderOptions,
+    },
+}
+
+/// Results of a background job
+#[derive(Debug, Default, JsonSchema, Serialize, Deserialize, AsJsonb)]
+pub struct ChatRsJobResult {
+    pub items: Vec<ChatRsJobResultItem>,
+    /// Error message if the job failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Result of one item of a background job (e.g. the summary of one session)
+#[derive(Debug, JsonSchema, Serialize, Deserialize)]
+pub struct ChatRsJobResultItem {
+    /// ID of the item (e.g. the session ID)
+    pub id: Uuid,
+    /// Output text from the provider
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// Error message if this item failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = super::schema::jobs)]
+pub struct NewChatRsJob<'r> {
+    pub user_id: &'r Uuid,
+    pub provider_id: i32,
+    pub status: &'r str,
+    pub batch_id: Option<&'r str>,
+    pub data: &'r ChatRsJobData,
+}
+
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = super::schema::jobs)]
+pub struct UpdateChatRsJob<'r> {
+    pub status: Option<&'r str>,
+    pub result: Option<&'r ChatRsJobResult>,
+}
@@ -0,0 +1,87 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    prelude::{AsChangeset, Associations, Identifiable, Insertable, Queryable},
+    Selectable,
+};
+use pgvector::Vector;
+use schemars::JsonSchema;
+use uuid::Uuid;
+
+use crate::db::models::ChatRsUser;
+
+#[derive(Identifiable, Queryable, Selectable, Associations, JsonSchema, serde::Serialize)]
+#[diesel(belongs_to(ChatRsUser, foreign_key = user_id))]
+#[diesel(table_name = super::schema::knowledge_bases)]
+pub struct ChatRsKnowledgeBase {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub user_id: Uuid,
+    /// Provider used to generate the embeddings
+    pub provider_id: i32,
+    pub name: String,
+    /// Description of the contents, shown to the LLM when searching
+    pub description: Option<String>,
+    /// Model used to generate the embeddings
+    pub embedding_model: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = super::schema::knowledge_bases)]
+pub struct NewChatRsKnowledgeBase<'r> {
+    pub user_id: &'r Uuid,
+    pub provider_id: i32,
+    pub name: &'r str,
+    pub description: Option<&'r str>,
+    pub embedding_model: &'r str,
+}
+
+#[derive(Default, AsChangeset)]
+#[diesel(table_name = super::schema::knowledge_bases)]
+pub struct UpdateChatRsKnowledgeBase<'r> {
+    pub name: Option<&'r str>,
+    pub description: Option<Option<&'r str>>,
+}
+
+#[derive(Identifiable, Queryable, Selectable, Associations, JsonSchema, serde::Serialize)]
+#[diesel(belongs_to(ChatRsKnowledgeBase, foreign_key = knowledge_base_id))]
+#[diesel(table_name = super::schema::knowledge_documents)]
+pub struct ChatRsKnowledgeDocument {
+    pub id: Uuid,
+    pub knowledge_base_id: Uuid,
+    pub name: String,
+    /// Number of characters in the document
+    pub content_length: i32,
+    pub chunk_count: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = super::schema::knowledge_documents)]
+pub struct NewChatRsKnowledgeDocument<'r> {
+    pub knowledge_base_id: &'r Uuid,
+    pub name: &'r str,
+    pub content_length: i32,
+    pub chunk_count: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = super::schema::knowledge_chunks)]
+pub struct NewChatRsKnowledgeChunk<'r> {
+    pub document_id: &'r Uuid,
+    pub knowledge_base_id: &'r Uuid,
+    pub chunk_index: i32,
+    pub content: &'r str,
+    pub embedding: Vector,
+}
+
+/// A chunk of a document matching a knowledge base search
+#[derive(Debug, Queryable, JsonSchema, serde::Serialize)]
+pub struct ChatRsKnowledgeSearchResult {
+    pub document_name: String,
+    pub chunk_index: i32,
+    pub content: String,
+    /// Cosine distance of the chunk to the query (lower is more similar)
+    pub distance: f64,
+}
@@ -0,0 +1,32 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    prelude::{Associations, Identifiable, Insertable, Queryable},
+    Selectable,
+};
+use pgvector::Vector;
+use schemars::JsonSchema;
+use uuid::Uuid;
+
+use crate::db::models::ChatRsUser;
+
+#[derive(Identifiable, Queryable, Selectable, Associations, JsonSchema, serde::Serialize)]
+#[diesel(belongs_to(ChatRsUser, foreign_key = user_id))]
+#[diesel(table_name = super::schema::memories)]
+pub struct ChatRsMemory {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub user_id: Uuid,
+    pub content: String,
+    /// Model used to embed the memory for semantic recall
+    pub embedding_model: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = super::schema::memories)]
+pub struct NewChatRsMemory<'r> {
+    pub user_id: &'r Uuid,
+    pub content: &'r str,
+    pub embedding_model: Option<&'r str>,
+    pub embedding: Option<Vector>,
+}
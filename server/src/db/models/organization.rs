@@ -0,0 +1,107 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    prelude::{AsChangeset, Associations, Identifiable, Insertable, Queryable},
+    Selectable,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::models::ChatRsUser;
+
+/// A team workspace whose members share providers, tools, and personas
+#[derive(Identifiable, Queryable, Selectable, JsonSchema, Serialize)]
+#[diesel(table_name = super::schema::organizations)]
+pub struct ChatRsOrganization {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = super::schema::organizations)]
+pub struct NewChatRsOrganization<'r> {
+    pub name: &'r str,
+}
+
+#[derive(AsChangeset)]
+#[diesel(table_name = super::schema::organizations)]
+pub struct UpdateChatRsOrganization<'r> {
+    pub name: Option<&'r str>,
+}
+
+/// A user who is a member of an organization
+#[derive(Identifiable, Associations, Queryable, Selectable)]
+#[diesel(belongs_to(ChatRsOrganization, foreign_key = organization_id))]
+#[diesel(belongs_to(ChatRsUser, foreign_key = user_id))]
+#[diesel(primary_key(organization_id, user_id))]
+#[diesel(table_name = super::schema::organization_members)]
+pub struct ChatRsOrganizationMember {
+    pub organization_id: Uuid,
+    pub user_id: Uuid,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A member of an organization, with their name
+#[derive(Queryable, JsonSchema, Serialize)]
+pub struct ChatRsOrganizationMemberInfo {
+    pub user_id: Uuid,
+    pub name: String,
+    pub avatar_url: Option<String>,
+    #[schemars(with = "ChatRsOrganizationRole")]
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Role of a user in an organization
+#[derive(Debug, Clone, Copy, PartialEq, JsonSchema, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatRsOrganizationRole {
+    /// Created the organization: can manage it, and delete it
+    Owner,
+    /// Can manage the organization's members, and share resources with the organization
+    Admin,
+    /// Can use the resources shared with the organization
+    Member,
+}
+
+impl ChatRsOrganizationRole {
+    /// Whether the role can manage members and shared resources
+    pub fn can_manage(&self) -> bool {
+        matches!(
+            self,
+            ChatRsOrganizationRole::Owner | ChatRsOrganizationRole::Admin
+        )
+    }
+}
+
+impl From<&ChatRsOrganizationRole> for &str {
+    fn from(value: &ChatRsOrganizationRole) -> Self {
+        match value {
+            ChatRsOrganizationRole::Owner => "owner",
+            ChatRsOrganizationRole::Admin => "admin",
+            ChatRsOrganizationRole::Member => "member",
+        }
+    }
+}
+
+impl From<&str> for ChatRsOrganizationRole {
+    /// Role of a member saved in the database (unknown values are treated as members)
+    fn from(value: &str) -> Self {
+        match value {
+            "owner" => ChatRsOrganizationRole::Owner,
+            "admin" => ChatRsOrganizationRole::Admin,
+            _ => ChatRsOrganizationRole::Member,
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = super::schema::organization_members)]
+pub struct NewChatRsOrganizationMember<'r> {
+    pub organization_id: &'r Uuid,
+    pub user_id: &'r Uuid,
+    pub role: &'r str,
+}
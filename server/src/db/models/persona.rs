@@ -0,0 +1,60 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    prelude::{AsChangeset, Associations, Identifiable, Insertable, Queryable},
+    Selectable,
+};
+use diesel_as_jsonb::AsJsonb;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{db::models::ChatRsUser, provider::LlmProviderOptions, tools::SendChatToolInput};
+
+/// A reusable setup for new chat sessions (e.g. "Coding assistant")
+#[derive(Identifiable, Associations, Queryable, Selectable, JsonSchema, Serialize)]
+#[diesel(belongs_to(ChatRsUser, foreign_key = user_id))]
+#[diesel(table_name = super::schema::personas)]
+pub struct ChatRsPersona {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub user_id: Uuid,
+    /// Default provider of the sessions (not set if the provider was deleted)
+    pub provider_id: Option<i32>,
+    pub name: String,
+    pub data: ChatRsPersonaData,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Organization that the persona is shared with
+    pub organization_id: Option<Uuid>,
+}
+
+/// Saved configuration of a persona
+#[derive(Debug, JsonSchema, Serialize, Deserialize, AsJsonb)]
+pub struct ChatRsPersonaData {
+    /// System prompt sent at the start of the conversation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// Default model and options for the provider (defaults to the provider's default model)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<LlmProviderOptions>,
+    /// Default configuration of tools available to the assistant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<SendChatToolInput>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = super::schema::personas)]
+pub struct NewChatRsPersona<'r> {
+    pub user_id: &'r Uuid,
+    pub provider_id: Option<i32>,
+    pub name: &'r str,
+    pub data: &'r ChatRsPersonaData,
+}
+
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = super::schema::personas)]
+pub struct UpdateChatRsPersona<'r> {
+    pub provider_id: Option<Option<i32>>,
+    pub name: Option<&'r str>,
+    pub data: Option<&'r ChatRsPersonaData>,
+}
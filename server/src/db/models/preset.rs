@@ -0,0 +1,60 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    prelude::{AsChangeset, Associations, Identifiable, Insertable, Queryable},
+    Selectable,
+};
+use diesel_as_jsonb::AsJsonb;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    db::models::{ChatRsProvider, ChatRsUser},
+    provider::LlmProviderOptions,
+    tools::SendChatToolInput,
+};
+
+#[derive(Identifiable, Associations, Queryable, Selectable, JsonSchema, Serialize)]
+#[diesel(belongs_to(ChatRsUser, foreign_key = user_id))]
+#[diesel(belongs_to(ChatRsProvider, foreign_key = provider_id))]
+#[diesel(table_name = super::schema::provider_presets)]
+pub struct ChatRsProviderPreset {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub user_id: Uuid,
+    pub provider_id: i32,
+    pub name: String,
+    pub data: ChatRsProviderPresetData,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Saved configuration of a preset
+#[derive(Debug, JsonSchema, Serialize, Deserialize, AsJsonb)]
+pub struct ChatRsProviderPresetData {
+    /// Configuration for the provider
+    pub options: LlmProviderOptions,
+    /// System prompt sent at the start of the conversation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// Configuration of tools available to the assistant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<SendChatToolInput>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = super::schema::provider_presets)]
+pub struct NewChatRsProviderPreset<'r> {
+    pub user_id: &'r Uuid,
+    pub provider_id: i32,
+    pub name: &'r str,
+    pub data: &'r ChatRsProviderPresetData,
+}
+
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = super::schema::provider_presets)]
+pub struct UpdateChatRsProviderPreset<'r> {
+    pub provider_id: Option<i32>,
+    pub name: Option<&'r str>,
+    pub data: Option<&'r ChatRsProviderPresetData>,
+}
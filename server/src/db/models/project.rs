@@ -0,0 +1,40 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    prelude::{AsChangeset, Associations, Identifiable, Insertable, Queryable},
+    Selectable,
+};
+use schemars::JsonSchema;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::db::models::ChatRsUser;
+
+/// A group of chat sessions sharing instructions and files
+#[derive(Identifiable, Associations, Queryable, Selectable, JsonSchema, Serialize)]
+#[diesel(belongs_to(ChatRsUser, foreign_key = user_id))]
+#[diesel(table_name = super::schema::projects)]
+pub struct ChatRsProject {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub user_id: Uuid,
+    pub name: String,
+    /// Instructions given to the assistant in all sessions of the project
+    pub instructions: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = super::schema::projects)]
+pub struct NewChatRsProject<'r> {
+    pub user_id: &'r Uuid,
+    pub name: &'r str,
+    pub instructions: Option<&'r str>,
+}
+
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = super::schema::projects)]
+pub struct UpdateChatRsProject<'r> {
+    pub name: Option<&'r str>,
+    pub instructions: Option<Option<&'r str>>,
+}
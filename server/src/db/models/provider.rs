@@ -22,7 +22,18 @@ pub struct ChatRsProvider {
     pub default_model: String,
     pub base_url: Option<String>,
     pub api_key_id: Option<Uuid>,
+    /// API key used if the primary API key is rejected (e.g. during key rotation)
+    pub secondary_api_key_id: Option<Uuid>,
     pub created_at: DateTime<Utc>,
+    /// Whether raw requests and stream chunks are captured for troubleshooting
+    pub debug_logging: bool,
+    /// Encrypted JSON map of extra headers sent with every request to the provider
+    #[serde(skip_serializing)]
+    pub extra_headers: Option<Vec<u8>>,
+    #[serde(skip_serializing)]
+    pub extra_headers_nonce: Option<Vec<u8>>,
+    /// Organization that the provider is shared with
+    pub organization_id: Option<Uuid>,
 }
 
 #[derive(Insertable)]
@@ -34,6 +45,8 @@ pub struct NewChatRsProvider<'a> {
     pub base_url: Option<&'a str>,
     pub default_model: &'a str,
     pub api_key_id: Option<Uuid>,
+    pub extra_headers: Option<&'a [u8]>,
+    pub extra_headers_nonce: Option<&'a [u8]>,
 }
 
 #[derive(Default, AsChangeset)]
@@ -43,6 +56,9 @@ pub struct UpdateChatRsProvider<'a> {
     pub base_url: Option<&'a str>,
     pub default_model: Option<&'a str>,
     pub api_key_id: Option<Uuid>,
+    pub debug_logging: Option<bool>,
+    pub extra_headers: Option<&'a [u8]>,
+    pub extra_headers_nonce: Option<&'a [u8]>,
 }
 
 /// The API type of the provider
@@ -0,0 +1,35 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    prelude::{AsChangeset, Associations, Identifiable, Insertable, Queryable},
+    Selectable,
+};
+use schemars::JsonSchema;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::db::models::ChatRsUser;
+
+/// User settings for the automatic deletion of old data
+#[derive(Identifiable, Queryable, Selectable, Associations, JsonSchema, Serialize)]
+#[diesel(belongs_to(ChatRsUser, foreign_key = user_id))]
+#[diesel(table_name = super::schema::retention_settings)]
+#[diesel(primary_key(user_id))]
+pub struct ChatRsRetentionSettings {
+    #[serde(skip)]
+    pub user_id: Uuid,
+    /// Days before sessions without new messages are moved to the trash
+    pub session_retention_days: Option<i32>,
+    /// Days before tool runs are deleted
+    pub tool_run_retention_days: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = super::schema::retention_settings)]
+#[diesel(treat_none_as_null = true)]
+pub struct NewChatRsRetentionSettings<'r> {
+    pub user_id: &'r Uuid,
+    pub session_retention_days: Option<i32>,
+    pub tool_run_retention_days: Option<i32>,
+}
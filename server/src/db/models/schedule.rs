@@ -0,0 +1,82 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    prelude::{AsChangeset, Associations, Identifiable, Insertable, Queryable},
+    Selectable,
+};
+use diesel_as_jsonb::AsJsonb;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    db::models::{ChatRsProvider, ChatRsSession, ChatRsUser},
+    provider::LlmProviderOptions,
+};
+
+/// A prompt sent to a provider on a schedule, with the response saved to a session
+#[derive(Identifiable, Associations, Queryable, Selectable, JsonSchema, Serialize)]
+#[diesel(belongs_to(ChatRsUser, foreign_key = user_id))]
+#[diesel(belongs_to(ChatRsSession, foreign_key = session_id))]
+#[diesel(belongs_to(ChatRsProvider, foreign_key = provider_id))]
+#[diesel(table_name = super::schema::scheduled_prompts)]
+pub struct ChatRsScheduledPrompt {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub user_id: Uuid,
+    /// The session that the prompts and responses are saved to
+    pub session_id: Uuid,
+    pub provider_id: i32,
+    pub name: String,
+    pub prompt: String,
+    /// Cron expression of the schedule, e.g. `0 9 * * MON-FRI`
+    pub cron: String,
+    /// IANA timezone name of the schedule
+    pub timezone: String,
+    pub data: ChatRsScheduledPromptData,
+    pub enabled: bool,
+    /// Time of the next run (not set if disabled)
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    /// Error of the last run, if it failed
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Saved configuration of a scheduled prompt
+#[derive(Debug, JsonSchema, Serialize, Deserialize, AsJsonb)]
+pub struct ChatRsScheduledPromptData {
+    /// Configuration for the provider
+    pub options: LlmProviderOptions,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = super::schema::scheduled_prompts)]
+pub struct NewChatRsScheduledPrompt<'r> {
+    pub user_id: &'r Uuid,
+    pub session_id: &'r Uuid,
+    pub provider_id: i32,
+    pub name: &'r str,
+    pub prompt: &'r str,
+    pub cron: &'r str,
+    pub timezone: &'r str,
+    pub data: &'r ChatRsScheduledPromptData,
+    pub enabled: bool,
+    pub next_run_at: Option<DateTime<Utc>>,
+}
+
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = super::schema::scheduled_prompts)]
+pub struct UpdateChatRsScheduledPrompt<'r> {
+    pub session_id: Option<&'r Uuid>,
+    pub provider_id: Option<i32>,
+    pub name: Option<&'r str>,
+    pub prompt: Option<&'r str>,
+    pub cron: Option<&'r str>,
+    pub timezone: Option<&'r str>,
+    pub data: Option<&'r ChatRsScheduledPromptData>,
+    pub enabled: Option<bool>,
+    pub next_run_at: Option<Option<DateTime<Utc>>>,
+    pub last_run_at: Option<Option<DateTime<Utc>>>,
+    pub last_error: Option<Option<&'r str>>,
+}
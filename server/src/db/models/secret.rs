@@ -6,7 +6,7 @@ use diesel::{
 use schemars::JsonSchema;
 use uuid::Uuid;
 
-use crate::db::models::ChatRsUser;
+use crate::{db::models::ChatRsUser, tools::OAuth2Settings};
 
 #[derive(Identifiable, Queryable, Selectable, Associations)]
 #[diesel(belongs_to(ChatRsUser, foreign_key = user_id))]
@@ -18,6 +18,8 @@ pub struct ChatRsSecret {
     pub ciphertext: Vec<u8>,
     pub nonce: Vec<u8>,
     pub created_at: DateTime<Utc>,
+    /// Settings if the secret is an OAuth2 credential
+    pub oauth2: Option<OAuth2Settings>,
 }
 
 #[derive(Identifiable, Queryable, Selectable, Associations, JsonSchema, serde::Serialize)]
@@ -29,6 +31,8 @@ pub struct ChatRsSecretMeta {
     pub user_id: Uuid,
     pub name: String,
     pub created_at: DateTime<Utc>,
+    /// Settings if the secret is an OAuth2 credential
+    pub oauth2: Option<OAuth2Settings>,
 }
 
 #[derive(Insertable)]
@@ -38,6 +42,7 @@ pub struct NewChatRsSecret<'r> {
     pub name: &'r str,
     pub ciphertext: &'r Vec<u8>,
     pub nonce: &'r Vec<u8>,
+    pub oauth2: Option<&'r OAuth2Settings>,
 }
 
 #[derive(Default, AsChangeset)]
@@ -46,4 +51,5 @@ pub struct UpdateChatRsSecret<'r> {
     pub name: Option<&'r str>,
     pub ciphertext: Option<&'r Vec<u8>>,
     pub nonce: Option<&'r Vec<u8>>,
+    pub oauth2: Option<Option<&'r OAuth2Settings>>,
 }
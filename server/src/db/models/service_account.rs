@@ -0,0 +1,57 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    prelude::{AsChangeset, Associations, Identifiable, Insertable, Queryable},
+    Selectable,
+};
+use schemars::JsonSchema;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::db::models::ChatRsUser;
+
+/// A non-interactive user for automation (e.g. a bot posting into shared sessions), that can
+/// only authenticate with scoped API keys
+#[derive(Identifiable, Associations, Queryable, Selectable, JsonSchema, Serialize)]
+#[diesel(belongs_to(ChatRsUser, foreign_key = user_id))]
+#[diesel(primary_key(user_id))]
+#[diesel(table_name = super::schema::service_accounts)]
+pub struct ChatRsServiceAccount {
+    pub user_id: Uuid,
+    pub description: Option<String>,
+    /// Admin who created the service account
+    pub created_by: Option<Uuid>,
+    /// Max number of chat messages sent per hour (no limit if not set)
+    pub max_messages_per_hour: Option<i32>,
+    /// Max number of chat messages sent per day (no limit if not set)
+    pub max_messages_per_day: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A service account, with the name of its user
+#[derive(JsonSchema, Serialize)]
+pub struct ChatRsServiceAccountInfo {
+    pub name: String,
+    /// When the service account was disabled by an admin
+    pub disabled_at: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    pub account: ChatRsServiceAccount,
+}
+
+/// A new service account, inserted with the ID of its new user
+#[derive(Insertable)]
+#[diesel(table_name = super::schema::service_accounts)]
+pub struct NewChatRsServiceAccount<'r> {
+    pub description: Option<&'r str>,
+    pub created_by: &'r Uuid,
+    pub max_messages_per_hour: Option<i32>,
+    pub max_messages_per_day: Option<i32>,
+}
+
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = super::schema::service_accounts)]
+pub struct UpdateChatRsServiceAccount<'r> {
+    pub description: Option<Option<&'r str>>,
+    pub max_messages_per_hour: Option<Option<i32>>,
+    pub max_messages_per_day: Option<Option<i32>>,
+}
@@ -0,0 +1,81 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    prelude::{Associations, Identifiable, Insertable, Queryable},
+    Selectable,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::models::{ChatRsSession, ChatRsUser};
+
+/// A user invited to a chat session by its owner
+#[derive(Identifiable, Associations, Queryable, Selectable)]
+#[diesel(belongs_to(ChatRsSession, foreign_key = session_id))]
+#[diesel(belongs_to(ChatRsUser, foreign_key = user_id))]
+#[diesel(primary_key(session_id, user_id))]
+#[diesel(table_name = super::schema::session_members)]
+pub struct ChatRsSessionMember {
+    pub session_id: Uuid,
+    pub user_id: Uuid,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A member of a chat session, with their name
+#[derive(Queryable, JsonSchema, Serialize)]
+pub struct ChatRsSessionMemberInfo {
+    pub user_id: Uuid,
+    pub name: String,
+    pub avatar_url: Option<String>,
+    #[schemars(with = "ChatRsSessionRole")]
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Role of a user in a chat session
+#[derive(Debug, Clone, Copy, PartialEq, JsonSchema, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatRsSessionRole {
+    /// Created the session: can chat, manage the session, and invite members
+    Owner,
+    /// Can read the session and send messages
+    Write,
+    /// Can only read the session
+    Read,
+}
+
+impl ChatRsSessionRole {
+    /// Whether the role can send messages to the session
+    pub fn can_write(&self) -> bool {
+        matches!(self, ChatRsSessionRole::Owner | ChatRsSessionRole::Write)
+    }
+}
+
+impl From<&ChatRsSessionRole> for &str {
+    fn from(value: &ChatRsSessionRole) -> Self {
+        match value {
+            ChatRsSessionRole::Owner => "owner",
+            ChatRsSessionRole::Write => "write",
+            ChatRsSessionRole::Read => "read",
+        }
+    }
+}
+
+impl From<&str> for ChatRsSessionRole {
+    /// Role of a member saved in the database (unknown values are treated as read-only)
+    fn from(value: &str) -> Self {
+        match value {
+            "write" => ChatRsSessionRole::Write,
+            _ => ChatRsSessionRole::Read,
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = super::schema::session_members)]
+pub struct NewChatRsSessionMember<'r> {
+    pub session_id: &'r Uuid,
+    pub user_id: &'r Uuid,
+    pub role: &'r str,
+}
@@ -0,0 +1,56 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    prelude::{AsChangeset, Associations, Identifiable, Insertable, Queryable},
+    Selectable,
+};
+use pgvector::Vector;
+use schemars::JsonSchema;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::db::models::ChatRsUser;
+
+/// User settings for the semantic search of chat sessions
+#[derive(Identifiable, Queryable, Selectable, Associations, JsonSchema, Serialize)]
+#[diesel(belongs_to(ChatRsUser, foreign_key = user_id))]
+#[diesel(table_name = super::schema::session_search_settings)]
+#[diesel(primary_key(user_id))]
+pub struct ChatRsSessionSearchSettings {
+    #[serde(skip)]
+    pub user_id: Uuid,
+    /// Provider used to embed the messages
+    pub provider_id: i32,
+    /// Model used to embed the messages
+    pub embedding_model: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = super::schema::session_search_settings)]
+pub struct NewChatRsSessionSearchSettings<'r> {
+    pub user_id: &'r Uuid,
+    pub provider_id: i32,
+    pub embedding_model: &'r str,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = super::schema::message_embeddings)]
+pub struct NewChatRsMessageEmbedding<'r> {
+    pub message_id: &'r Uuid,
+    pub session_id: &'r Uuid,
+    pub user_id: &'r Uuid,
+    pub embedding_model: &'r str,
+    pub embedding: Vector,
+}
+
+/// The closest message of a session in a semantic search
+#[derive(Debug, Queryable, JsonSchema, Serialize)]
+pub struct ChatRsMessageSearchResult {
+    pub session_id: Uuid,
+    pub session_title: String,
+    pub message_id: Uuid,
+    pub content: String,
+    /// Cosine distance of the message to the query (lower is more similar)
+    pub distance: f64,
+}
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use chrono::{DateTime, Utc};
 use diesel::{
-    prelude::{Associations, Identifiable, Insertable, Queryable},
+    prelude::{AsChangeset, Associations, Identifiable, Insertable, Queryable},
     Selectable,
 };
 use schemars::JsonSchema;
@@ -12,7 +12,10 @@ use uuid::Uuid;
 use crate::{
     db::models::ChatRsUser,
     provider::LlmToolType,
-    tools::{ChatRsExternalApiToolConfig, ChatRsSystemToolConfig, ToolResponseFormat},
+    tools::{
+        ChatRsExternalApiToolConfig, ChatRsMcpToolConfig, ChatRsSystemToolConfig, ToolCallApproval,
+        ToolResponseFormat,
+    },
 };
 
 #[derive(Debug, Identifiable, Queryable, Selectable, Associations, Serialize, JsonSchema)]
@@ -24,6 +27,18 @@ pub struct ChatRsSystemTool {
     pub data: ChatRsSystemToolConfig,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Whether the server may execute calls of this tool automatically (in auto mode)
+    pub auto_approve: bool,
+    /// Seconds to cache the results of identical calls of this tool (not cached if not set)
+    pub cache_ttl: Option<i32>,
+    /// Max number of calls of this tool per hour (unlimited if not set)
+    pub max_calls_per_hour: Option<i32>,
+    /// Max number of calls of this tool per chat session (unlimited if not set)
+    pub max_calls_per_session: Option<i32>,
+    /// Max seconds to execute a call of this tool (default timeout if not set)
+    pub timeout_seconds: Option<i32>,
+    /// Max size of the output of this tool in bytes (default limit if not set)
+    pub max_output_bytes: Option<i32>,
 }
 
 #[derive(Insertable)]
@@ -33,6 +48,18 @@ pub struct NewChatRsSystemTool<'r> {
     pub data: &'r ChatRsSystemToolConfig,
 }
 
+#[derive(Default, AsChangeset)]
+#[diesel(table_name = super::schema::system_tools)]
+pub struct UpdateChatRsSystemTool<'r> {
+    pub data: Option<&'r ChatRsSystemToolConfig>,
+    pub auto_approve: Option<bool>,
+    pub cache_ttl: Option<Option<i32>>,
+    pub max_calls_per_hour: Option<Option<i32>>,
+    pub max_calls_per_session: Option<Option<i32>>,
+    pub timeout_seconds: Option<Option<i32>>,
+    pub max_output_bytes: Option<Option<i32>>,
+}
+
 #[derive(Debug, Identifiable, Queryable, Selectable, Associations, Serialize, JsonSchema)]
 #[diesel(belongs_to(ChatRsUser, foreign_key = user_id))]
 #[diesel(table_name = super::schema::external_api_tools)]
@@ -44,6 +71,28 @@ pub struct ChatRsExternalApiTool {
     pub secret_2: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Whether the server may execute calls of this tool automatically (in auto mode)
+    pub auto_approve: bool,
+    pub secret_3: Option<Uuid>,
+    /// Seconds to cache the results of identical calls of this tool (not cached if not set)
+    pub cache_ttl: Option<i32>,
+    /// Max number of calls of this tool per hour (unlimited if not set)
+    pub max_calls_per_hour: Option<i32>,
+    /// Max number of calls of this tool per chat session (unlimited if not set)
+    pub max_calls_per_session: Option<i32>,
+    /// Max seconds to execute a call of this tool (default timeout if not set)
+    pub timeout_seconds: Option<i32>,
+    /// Max size of the output of this tool in bytes (default limit if not set)
+    pub max_output_bytes: Option<i32>,
+    /// Organization that the tool is shared with
+    pub organization_id: Option<Uuid>,
+}
+
+impl ChatRsExternalApiTool {
+    /// IDs of the tool's secrets, in order (`secret_1`, `secret_2`, `secret_3`)
+    pub fn secret_ids(&self) -> [Option<Uuid>; 3] {
+        [self.secret_1, self.secret_2, self.secret_3]
+    }
 }
 
 #[derive(Insertable)]
@@ -52,6 +101,69 @@ pub struct NewChatRsExternalApiTool<'r> {
     pub user_id: &'r Uuid,
     pub data: &'r ChatRsExternalApiToolConfig,
     pub secret_1: Option<&'r Uuid>,
+    pub secret_2: Option<&'r Uuid>,
+    pub secret_3: Option<&'r Uuid>,
+}
+
+#[derive(Default, AsChangeset)]
+#[diesel(table_name = super::schema::external_api_tools)]
+pub struct UpdateChatRsExternalApiTool<'r> {
+    pub data: Option<&'r ChatRsExternalApiToolConfig>,
+    pub secret_1: Option<Uuid>,
+    pub secret_2: Option<Uuid>,
+    pub secret_3: Option<Uuid>,
+    pub auto_approve: Option<bool>,
+    pub cache_ttl: Option<Option<i32>>,
+    pub max_calls_per_hour: Option<Option<i32>>,
+    pub max_calls_per_session: Option<Option<i32>>,
+    pub timeout_seconds: Option<Option<i32>>,
+    pub max_output_bytes: Option<Option<i32>>,
+}
+
+#[derive(Debug, Identifiable, Queryable, Selectable, Associations, Serialize, JsonSchema)]
+#[diesel(belongs_to(ChatRsUser, foreign_key = user_id))]
+#[diesel(table_name = super::schema::mcp_tools)]
+pub struct ChatRsMcpTool {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub data: ChatRsMcpToolConfig,
+    pub secret_1: Option<Uuid>,
+    /// Whether the server may execute calls of this tool automatically (in auto mode)
+    pub auto_approve: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Seconds to cache the results of identical calls of this tool (not cached if not set)
+    pub cache_ttl: Option<i32>,
+    /// Max number of calls of this tool per hour (unlimited if not set)
+    pub max_calls_per_hour: Option<i32>,
+    /// Max number of calls of this tool per chat session (unlimited if not set)
+    pub max_calls_per_session: Option<i32>,
+    /// Max seconds to execute a call of this tool (default timeout if not set)
+    pub timeout_seconds: Option<i32>,
+    /// Max size of the output of this tool in bytes (default limit if not set)
+    pub max_output_bytes: Option<i32>,
+    /// Organization that the tool is shared with
+    pub organization_id: Option<Uuid>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = super::schema::mcp_tools)]
+pub struct NewChatRsMcpTool<'r> {
+    pub user_id: &'r Uuid,
+    pub data: &'r ChatRsMcpToolConfig,
+    pub secret_1: Option<&'r Uuid>,
+}
+
+#[derive(Default, AsChangeset)]
+#[diesel(table_name = super::schema::mcp_tools)]
+pub struct UpdateChatRsMcpTool {
+    pub secret_1: Option<Uuid>,
+    pub auto_approve: Option<bool>,
+    pub cache_ttl: Option<Option<i32>>,
+    pub max_calls_per_hour: Option<Option<i32>>,
+    pub max_calls_per_session: Option<Option<i32>>,
+    pub timeout_seconds: Option<Option<i32>>,
+    pub max_output_bytes: Option<Option<i32>>,
 }
 
 /// A tool call requested by the provider
@@ -68,6 +180,9 @@ pub struct ChatRsToolCall {
     pub tool_type: LlmToolType,
     /// Input parameters passed to the tool
     pub parameters: HashMap<String, serde_json::Value>,
+    /// Approval of the tool call, if the tool requires explicit confirmation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub approval: Option<ToolCallApproval>,
 }
 
 /// Metadata for an executed tool call
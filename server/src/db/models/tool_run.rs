@@ -0,0 +1,80 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    prelude::{Associations, Identifiable, Insertable, Queryable},
+    Selectable,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::models::ChatRsUser;
+
+/// A saved execution of a tool call
+#[derive(Identifiable, Queryable, Selectable, Associations, JsonSchema, Serialize)]
+#[diesel(belongs_to(ChatRsUser, foreign_key = user_id))]
+#[diesel(table_name = super::schema::tool_runs)]
+pub struct ChatRsToolRun {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub user_id: Uuid,
+    pub tool_id: Uuid,
+    /// Name of the tool called, as given to the assistant
+    pub tool_name: String,
+    /// Chat session of the tool call (not set if the session was deleted)
+    pub session_id: Option<Uuid>,
+    #[schemars(with = "ChatRsToolRunStatus")]
+    pub status: String,
+    /// Execution time in milliseconds
+    pub duration_ms: i32,
+    /// Size of the tool response in bytes
+    pub response_bytes: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Status of a tool execution
+#[derive(Debug, Clone, Copy, PartialEq, JsonSchema, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatRsToolRunStatus {
+    /// The tool was executed successfully
+    Success,
+    /// The tool returned an error
+    Error,
+    /// The result of an identical call was returned from the cache
+    Cached,
+}
+
+impl From<ChatRsToolRunStatus> for &str {
+    fn from(value: ChatRsToolRunStatus) -> Self {
+        match value {
+            ChatRsToolRunStatus::Success => "success",
+            ChatRsToolRunStatus::Error => "error",
+            ChatRsToolRunStatus::Cached => "cached",
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = super::schema::tool_runs)]
+pub struct NewChatRsToolRun<'r> {
+    pub user_id: &'r Uuid,
+    pub tool_id: &'r Uuid,
+    pub tool_name: &'r str,
+    pub session_id: Option<&'r Uuid>,
+    pub status: &'r str,
+    pub duration_ms: i32,
+    pub response_bytes: i32,
+}
+
+/// Aggregate stats of the executions of a tool with a status
+#[derive(Queryable)]
+pub struct ChatRsToolRunStats {
+    pub status: String,
+    /// Number of executions
+    pub count: i64,
+    /// Total execution time in milliseconds
+    pub total_duration_ms: Option<i64>,
+    /// Longest execution time in milliseconds
+    pub max_duration_ms: Option<i32>,
+    /// Total size of the tool responses in bytes
+    pub total_response_bytes: Option<i64>,
+}
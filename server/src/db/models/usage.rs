@@ -0,0 +1,58 @@
+use diesel::prelude::QueryableByName;
+use rocket::FromFormField;
+use schemars::JsonSchema;
+use serde::Serialize;
+use uuid::Uuid;
+
+/// How the usage summary is grouped
+#[derive(Debug, Default, Clone, Copy, PartialEq, FromFormField, JsonSchema)]
+#[schemars(rename_all = "snake_case")]
+pub enum UsageGroupBy {
+    /// By day (UTC)
+    #[default]
+    Day,
+    /// By model
+    Model,
+    /// By provider
+    Provider,
+}
+
+/// Aggregate usage of the assistant messages in a group
+#[derive(Debug, QueryableByName, JsonSchema, Serialize)]
+pub struct ChatRsUsageSummary {
+    /// The date (`YYYY-MM-DD`), model, or provider name of the group
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub group: String,
+    /// ID of the provider, when grouped by provider (not set if the provider was deleted)
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Integer>)]
+    pub provider_id: Option<i32>,
+    /// Number of assistant messages
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub messages: i64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub input_tokens: i64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub output_tokens: i64,
+    /// Total cost, if reported by the provider (only OpenRouter)
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Double>)]
+    pub cost: Option<f64>,
+}
+
+/// Aggregate usage of a member of the organization, with the organization's shared providers
+#[derive(Debug, QueryableByName, JsonSchema, Serialize)]
+pub struct ChatRsOrganizationUsage {
+    #[diesel(sql_type = diesel::sql_types::Uuid)]
+    pub user_id: Uuid,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub name: String,
+    /// Number of assistant messages
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub messages: i64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub input_tokens: i64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub output_tokens: i64,
+    /// Total cost, if reported by the provider (only OpenRouter)
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::Double>)]
+    pub cost: Option<f64>,
+}
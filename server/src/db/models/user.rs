@@ -4,9 +4,10 @@ use diesel::{
     Selectable,
 };
 use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Identifiable, Queryable, Selectable, JsonSchema, serde::Serialize)]
+#[derive(Identifiable, Queryable, Selectable, JsonSchema, Serialize)]
 #[diesel(table_name = super::schema::users)]
 pub struct ChatRsUser {
     pub id: Uuid,
@@ -23,10 +24,81 @@ pub struct ChatRsUser {
     pub oidc_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sso_username: Option<String>,
+    /// Email for email + password login
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// When the email was verified
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified_at: Option<DateTime<Utc>>,
+    /// Role of the user in the server
+    #[schemars(with = "ChatRsUserRole")]
+    pub role: String,
+    /// When the user was disabled by an admin
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disabled_at: Option<DateTime<Utc>>,
+    /// Total size of the user's stored files, in bytes (not set until the files are counted)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage_used: Option<i64>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+impl ChatRsUser {
+    /// Whether the user is an admin of the server
+    pub fn is_admin(&self) -> bool {
+        self.role == <&str>::from(&ChatRsUserRole::Admin)
+    }
+}
+
+/// Role of a user in the server
+#[derive(Debug, Clone, Copy, PartialEq, JsonSchema, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatRsUserRole {
+    /// Can manage global settings, users, and shared providers
+    Admin,
+    /// Regular user
+    Member,
+}
+
+impl From<&ChatRsUserRole> for &str {
+    fn from(value: &ChatRsUserRole) -> Self {
+        match value {
+            ChatRsUserRole::Admin => "admin",
+            ChatRsUserRole::Member => "member",
+        }
+    }
+}
+
+/// A login method of a user
+#[derive(Debug, Clone, Copy, PartialEq, JsonSchema, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRsUserIdentity {
+    Github,
+    Google,
+    Discord,
+    Oidc,
+    /// SSO / proxy header authentication
+    Sso,
+    /// Email + password login
+    Local,
+}
+
+impl<'a> rocket::request::FromParam<'a> for ChatRsUserIdentity {
+    type Error = &'a str;
+
+    fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+        match param {
+            "github" => Ok(ChatRsUserIdentity::Github),
+            "google" => Ok(ChatRsUserIdentity::Google),
+            "discord" => Ok(ChatRsUserIdentity::Discord),
+            "oidc" => Ok(ChatRsUserIdentity::Oidc),
+            "sso" => Ok(ChatRsUserIdentity::Sso),
+            "local" => Ok(ChatRsUserIdentity::Local),
+            _ => Err(param),
+        }
+    }
+}
+
 #[derive(Insertable, Default)]
 #[diesel(table_name = super::schema::users)]
 pub struct NewChatRsUser<'r> {
@@ -37,6 +109,10 @@ pub struct NewChatRsUser<'r> {
     pub sso_username: Option<&'r str>,
     pub name: &'r str,
     pub avatar_url: Option<&'r str>,
+    pub email: Option<&'r str>,
+    pub password_hash: Option<&'r str>,
+    pub email_verified_at: Option<DateTime<Utc>>,
+    pub role: Option<&'r str>,
 }
 
 #[derive(AsChangeset, Default)]
@@ -49,4 +125,8 @@ pub struct UpdateChatRsUser<'r> {
     pub sso_username: Option<&'r str>,
     pub name: Option<&'r str>,
     pub avatar_url: Option<&'r str>,
+    pub password_hash: Option<&'r str>,
+    pub email_verified_at: Option<DateTime<Utc>>,
+    pub role: Option<&'r str>,
+    pub disabled_at: Option<Option<DateTime<Utc>>>,
 }
@@ -0,0 +1,147 @@
+use chrono::{DateTime, Utc};
+use diesel::{
+    prelude::{AsChangeset, Associations, Identifiable, Insertable, Queryable},
+    Selectable,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::models::ChatRsUser;
+
+/// An outbound webhook, called with a signed JSON payload on chat events
+#[derive(Identifiable, Associations, Queryable, Selectable, JsonSchema, Serialize)]
+#[diesel(belongs_to(ChatRsUser, foreign_key = user_id))]
+#[diesel(table_name = super::schema::webhooks)]
+pub struct ChatRsWebhook {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub user_id: Uuid,
+    pub name: String,
+    pub url: String,
+    /// The events that trigger the webhook
+    #[schemars(with = "Vec<ChatRsWebhookEvent>")]
+    pub events: Vec<String>,
+    /// Encrypted signing secret
+    #[serde(skip)]
+    pub secret_ciphertext: Vec<u8>,
+    #[serde(skip)]
+    pub secret_nonce: Vec<u8>,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Chat events that can trigger a webhook
+#[derive(Debug, Clone, Copy, PartialEq, JsonSchema, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatRsWebhookEvent {
+    /// The assistant finished a response
+    MessageCompleted,
+    /// The assistant requested tool calls that need to be executed
+    ToolCallRequested,
+    /// The provider stream had errors
+    StreamError,
+    /// Test event, sent on request
+    Ping,
+}
+
+impl From<&ChatRsWebhookEvent> for &str {
+    fn from(value: &ChatRsWebhookEvent) -> Self {
+        match value {
+            ChatRsWebhookEvent::MessageCompleted => "message_completed",
+            ChatRsWebhookEvent::ToolCallRequested => "tool_call_requested",
+            ChatRsWebhookEvent::StreamError => "stream_error",
+            ChatRsWebhookEvent::Ping => "ping",
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = super::schema::webhooks)]
+pub struct NewChatRsWebhook<'r> {
+    pub user_id: &'r Uuid,
+    pub name: &'r str,
+    pub url: &'r str,
+    pub events: &'r Vec<String>,
+    pub secret_ciphertext: &'r Vec<u8>,
+    pub secret_nonce: &'r Vec<u8>,
+    pub enabled: bool,
+}
+
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = super::schema::webhooks)]
+pub struct UpdateChatRsWebhook<'r> {
+    pub name: Option<&'r str>,
+    pub url: Option<&'r str>,
+    pub events: Option<&'r Vec<String>>,
+    pub secret_ciphertext: Option<&'r Vec<u8>>,
+    pub secret_nonce: Option<&'r Vec<u8>>,
+    pub enabled: Option<bool>,
+}
+
+/// A call of a webhook, retried until it succeeds or runs out of attempts
+#[derive(Identifiable, Associations, Queryable, Selectable, JsonSchema, Serialize)]
+#[diesel(belongs_to(ChatRsWebhook, foreign_key = webhook_id))]
+#[diesel(table_name = super::schema::webhook_deliveries)]
+pub struct ChatRsWebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    #[schemars(with = "ChatRsWebhookEvent")]
+    pub event: String,
+    /// The JSON payload sent to the webhook
+    pub payload: serde_json::Value,
+    #[schemars(with = "ChatRsWebhookDeliveryStatus")]
+    pub status: String,
+    pub attempts: i32,
+    /// Time of the next attempt (not set once delivered or failed)
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    /// HTTP status of the last response
+    pub response_status: Option<i32>,
+    /// Error of the last attempt, if it failed
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Status of a webhook delivery
+#[derive(Debug, PartialEq, JsonSchema, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatRsWebhookDeliveryStatus {
+    /// Waiting for the next attempt
+    Pending,
+    /// The webhook responded with a success status
+    Delivered,
+    /// All attempts failed
+    Failed,
+}
+
+impl From<&ChatRsWebhookDeliveryStatus> for &str {
+    fn from(value: &ChatRsWebhookDeliveryStatus) -> Self {
+        match value {
+            ChatRsWebhookDeliveryStatus::Pending => "pending",
+            ChatRsWebhookDeliveryStatus::Delivered => "delivered",
+            ChatRsWebhookDeliveryStatus::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = super::schema::webhook_deliveries)]
+pub struct NewChatRsWebhookDelivery<'r> {
+    pub webhook_id: &'r Uuid,
+    pub event: &'r str,
+    pub payload: &'r serde_json::Value,
+    pub status: &'r str,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+}
+
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = super::schema::webhook_deliveries)]
+pub struct UpdateChatRsWebhookDelivery<'r> {
+    pub status: Option<&'r str>,
+    pub attempts: Option<i32>,
+    pub next_attempt_at: Option<Option<DateTime<Utc>>>,
+    pub response_status: Option<Option<i32>>,
+    pub last_error: Option<Option<&'r str>>,
+}
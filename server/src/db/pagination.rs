@@ -0,0 +1,71 @@
+//! Shared query parameters for paginated list endpoints
+
+use std::str::FromStr;
+
+use rocket::{FromForm, FromFormField};
+use schemars::JsonSchema;
+
+/// Default number of items returned by list endpoints.
+const DEFAULT_LIMIT: i64 = 100;
+/// Maximum number of items that can be requested from list endpoints.
+const MAX_LIMIT: i64 = 500;
+
+/// Query parameters for list endpoints. Pages are fetched with a cursor: to get the next
+/// page, pass the ID of the last item of the previous page.
+#[derive(Debug, Default, FromForm, JsonSchema)]
+pub struct ListQuery {
+    /// Maximum number of items to return (default: 100, max: 500)
+    pub limit: Option<i64>,
+    /// ID of the last item of the previous page
+    pub cursor: Option<String>,
+    /// Sort order (default: newest first)
+    pub sort: Option<ListSort>,
+    /// Only include items whose name contains this text (case-insensitive)
+    pub filter: Option<String>,
+}
+
+/// Sort order of list endpoints
+#[derive(Debug, Default, Clone, Copy, PartialEq, FromFormField, JsonSchema)]
+#[schemars(rename_all = "snake_case")]
+pub enum ListSort {
+    /// Newest first (most recently updated for sessions)
+    #[default]
+    Newest,
+    /// Oldest first
+    Oldest,
+}
+
+impl ListQuery {
+    /// The number of items to return
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+
+    /// The sort order
+    pub fn sort(&self) -> ListSort {
+        self.sort.unwrap_or_default()
+    }
+
+    /// Parse the cursor as an item ID. An invalid cursor can't match any item, so it is
+    /// reported as not found.
+    pub fn cursor<T: FromStr>(&self) -> Result<Option<T>, diesel::result::Error> {
+        self.cursor
+            .as_deref()
+            .map(|cursor| cursor.parse().map_err(|_| diesel::result::Error::NotFound))
+            .transpose()
+    }
+
+    /// The `ILIKE` pattern for the name filter, if given
+    pub fn filter_pattern(&self) -> Option<String> {
+        let filter = self.filter.as_deref()?.trim();
+        if filter.is_empty() {
+            return None;
+        }
+        let escaped = filter
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+
+        Some(format!("%{escaped}%"))
+    }
+}
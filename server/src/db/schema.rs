@@ -16,6 +16,10 @@ diesel::table! {
         user_id -> Uuid,
         name -> Text,
         created_at -> Timestamptz,
+        scopes -> Array<Text>,
+        expires_at -> Nullable<Timestamptz>,
+        last_used_at -> Nullable<Timestamptz>,
+        allowed_ips -> Nullable<Array<Text>>,
     }
 }
 
@@ -33,6 +37,11 @@ diesel::table! {
         updated_at -> Timestamptz,
         meta -> Jsonb,
         search_vector -> Tsvector,
+        deleted_at -> Nullable<Timestamptz>,
+        pinned -> Bool,
+        content_vector -> Tsvector,
+        change_seq -> Int8,
+        content_encrypted -> Bool,
     }
 }
 
@@ -44,6 +53,10 @@ diesel::table! {
         updated_at -> Timestamptz,
         user_id -> Uuid,
         meta -> Jsonb,
+        pinned -> Bool,
+        archived_at -> Nullable<Timestamptz>,
+        deleted_at -> Nullable<Timestamptz>,
+        project_id -> Nullable<Uuid>,
     }
 }
 
@@ -56,6 +69,182 @@ diesel::table! {
         secret_2 -> Nullable<Uuid>,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        auto_approve -> Bool,
+        secret_3 -> Nullable<Uuid>,
+        cache_ttl -> Nullable<Int4>,
+        max_calls_per_hour -> Nullable<Int4>,
+        max_calls_per_session -> Nullable<Int4>,
+        timeout_seconds -> Nullable<Int4>,
+        max_output_bytes -> Nullable<Int4>,
+        organization_id -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    jobs (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        provider_id -> Int4,
+        status -> Text,
+        batch_id -> Nullable<Text>,
+        data -> Jsonb,
+        result -> Nullable<Jsonb>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    knowledge_bases (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        provider_id -> Int4,
+        name -> Text,
+        description -> Nullable<Text>,
+        embedding_model -> Text,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use pgvector::sql_types::*;
+
+    knowledge_chunks (id) {
+        id -> Uuid,
+        document_id -> Uuid,
+        knowledge_base_id -> Uuid,
+        chunk_index -> Int4,
+        content -> Text,
+        embedding -> Vector,
+    }
+}
+
+diesel::table! {
+    knowledge_documents (id) {
+        id -> Uuid,
+        knowledge_base_id -> Uuid,
+        name -> Text,
+        content_length -> Int4,
+        chunk_count -> Int4,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    mcp_tools (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        data -> Jsonb,
+        secret_1 -> Nullable<Uuid>,
+        auto_approve -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        cache_ttl -> Nullable<Int4>,
+        max_calls_per_hour -> Nullable<Int4>,
+        max_calls_per_session -> Nullable<Int4>,
+        timeout_seconds -> Nullable<Int4>,
+        max_output_bytes -> Nullable<Int4>,
+        organization_id -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use pgvector::sql_types::*;
+
+    memories (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        content -> Text,
+        embedding_model -> Nullable<Text>,
+        embedding -> Nullable<Vector>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    message_attachments (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        message_id -> Nullable<Uuid>,
+        name -> Text,
+        mime_type -> Text,
+        storage_path -> Text,
+        size -> Int4,
+        created_at -> Timestamptz,
+        project_id -> Nullable<Uuid>,
+        extracted_text -> Nullable<Text>,
+        extracted_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use pgvector::sql_types::*;
+
+    message_embeddings (message_id) {
+        message_id -> Uuid,
+        session_id -> Uuid,
+        user_id -> Uuid,
+        embedding_model -> Text,
+        embedding -> Vector,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    organization_members (organization_id, user_id) {
+        organization_id -> Uuid,
+        user_id -> Uuid,
+        role -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    organizations (id) {
+        id -> Uuid,
+        name -> Text,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    personas (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        provider_id -> Nullable<Int4>,
+        name -> Text,
+        data -> Jsonb,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        organization_id -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    projects (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        name -> Text,
+        instructions -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    provider_presets (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        provider_id -> Int4,
+        name -> Text,
+        data -> Jsonb,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
@@ -69,6 +258,41 @@ diesel::table! {
         default_model -> Text,
         api_key_id -> Nullable<Uuid>,
         created_at -> Timestamptz,
+        secondary_api_key_id -> Nullable<Uuid>,
+        debug_logging -> Bool,
+        extra_headers -> Nullable<Bytea>,
+        extra_headers_nonce -> Nullable<Bytea>,
+        organization_id -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    retention_settings (user_id) {
+        user_id -> Uuid,
+        session_retention_days -> Nullable<Int4>,
+        tool_run_retention_days -> Nullable<Int4>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    scheduled_prompts (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        session_id -> Uuid,
+        provider_id -> Int4,
+        name -> Text,
+        prompt -> Text,
+        cron -> Text,
+        timezone -> Text,
+        data -> Jsonb,
+        enabled -> Bool,
+        next_run_at -> Nullable<Timestamptz>,
+        last_run_at -> Nullable<Timestamptz>,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
@@ -80,6 +304,38 @@ diesel::table! {
         nonce -> Bytea,
         created_at -> Timestamptz,
         name -> Text,
+        oauth2 -> Nullable<Jsonb>,
+    }
+}
+
+diesel::table! {
+    service_accounts (user_id) {
+        user_id -> Uuid,
+        description -> Nullable<Text>,
+        created_by -> Nullable<Uuid>,
+        max_messages_per_hour -> Nullable<Int4>,
+        max_messages_per_day -> Nullable<Int4>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    session_members (session_id, user_id) {
+        session_id -> Uuid,
+        user_id -> Uuid,
+        role -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    session_search_settings (user_id) {
+        user_id -> Uuid,
+        provider_id -> Int4,
+        embedding_model -> Text,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
@@ -90,6 +346,26 @@ diesel::table! {
         data -> Jsonb,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        auto_approve -> Bool,
+        cache_ttl -> Nullable<Int4>,
+        max_calls_per_hour -> Nullable<Int4>,
+        max_calls_per_session -> Nullable<Int4>,
+        timeout_seconds -> Nullable<Int4>,
+        max_output_bytes -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    tool_runs (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        tool_id -> Uuid,
+        tool_name -> Text,
+        session_id -> Nullable<Uuid>,
+        status -> Text,
+        duration_ms -> Int4,
+        response_bytes -> Int4,
+        created_at -> Timestamptz,
     }
 }
 
@@ -117,27 +393,127 @@ diesel::table! {
         discord_id -> Nullable<Text>,
         oidc_id -> Nullable<Text>,
         avatar_url -> Nullable<Text>,
+        email -> Nullable<Text>,
+        password_hash -> Nullable<Text>,
+        email_verified_at -> Nullable<Timestamptz>,
+        role -> Text,
+        disabled_at -> Nullable<Timestamptz>,
+        storage_used -> Nullable<Int8>,
+        storage_counted_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    webhook_deliveries (id) {
+        id -> Uuid,
+        webhook_id -> Uuid,
+        event -> Text,
+        payload -> Jsonb,
+        status -> Text,
+        attempts -> Int4,
+        next_attempt_at -> Nullable<Timestamptz>,
+        response_status -> Nullable<Int4>,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    webhooks (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        name -> Text,
+        url -> Text,
+        events -> Array<Text>,
+        secret_ciphertext -> Bytea,
+        secret_nonce -> Bytea,
+        enabled -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
 diesel::joinable!(app_api_keys -> users (user_id));
 diesel::joinable!(chat_messages -> chat_sessions (session_id));
+diesel::joinable!(chat_sessions -> projects (project_id));
 diesel::joinable!(chat_sessions -> users (user_id));
+diesel::joinable!(external_api_tools -> organizations (organization_id));
 diesel::joinable!(external_api_tools -> users (user_id));
+diesel::joinable!(jobs -> providers (provider_id));
+diesel::joinable!(jobs -> users (user_id));
+diesel::joinable!(knowledge_bases -> providers (provider_id));
+diesel::joinable!(knowledge_bases -> users (user_id));
+diesel::joinable!(knowledge_chunks -> knowledge_bases (knowledge_base_id));
+diesel::joinable!(knowledge_chunks -> knowledge_documents (document_id));
+diesel::joinable!(knowledge_documents -> knowledge_bases (knowledge_base_id));
+diesel::joinable!(mcp_tools -> organizations (organization_id));
+diesel::joinable!(mcp_tools -> secrets (secret_1));
+diesel::joinable!(mcp_tools -> users (user_id));
+diesel::joinable!(memories -> users (user_id));
+diesel::joinable!(message_attachments -> chat_messages (message_id));
+diesel::joinable!(message_attachments -> projects (project_id));
+diesel::joinable!(message_attachments -> users (user_id));
+diesel::joinable!(message_embeddings -> chat_messages (message_id));
+diesel::joinable!(message_embeddings -> chat_sessions (session_id));
+diesel::joinable!(message_embeddings -> users (user_id));
+diesel::joinable!(organization_members -> organizations (organization_id));
+diesel::joinable!(organization_members -> users (user_id));
+diesel::joinable!(personas -> organizations (organization_id));
+diesel::joinable!(personas -> providers (provider_id));
+diesel::joinable!(personas -> users (user_id));
+diesel::joinable!(projects -> users (user_id));
+diesel::joinable!(provider_presets -> providers (provider_id));
+diesel::joinable!(provider_presets -> users (user_id));
+diesel::joinable!(providers -> organizations (organization_id));
 diesel::joinable!(providers -> secrets (api_key_id));
 diesel::joinable!(providers -> users (user_id));
+diesel::joinable!(scheduled_prompts -> chat_sessions (session_id));
+diesel::joinable!(scheduled_prompts -> providers (provider_id));
+diesel::joinable!(retention_settings -> users (user_id));
+diesel::joinable!(scheduled_prompts -> users (user_id));
 diesel::joinable!(secrets -> users (user_id));
+diesel::joinable!(service_accounts -> users (user_id));
+diesel::joinable!(session_members -> chat_sessions (session_id));
+diesel::joinable!(session_members -> users (user_id));
+diesel::joinable!(session_search_settings -> providers (provider_id));
+diesel::joinable!(session_search_settings -> users (user_id));
 diesel::joinable!(system_tools -> users (user_id));
+diesel::joinable!(tool_runs -> chat_sessions (session_id));
+diesel::joinable!(tool_runs -> users (user_id));
 diesel::joinable!(tools -> users (user_id));
+diesel::joinable!(webhook_deliveries -> webhooks (webhook_id));
+diesel::joinable!(webhooks -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     app_api_keys,
     chat_messages,
     chat_sessions,
     external_api_tools,
+    jobs,
+    knowledge_bases,
+    knowledge_chunks,
+    knowledge_documents,
+    mcp_tools,
+    memories,
+    message_attachments,
+    message_embeddings,
+    organization_members,
+    organizations,
+    personas,
+    projects,
+    provider_presets,
     providers,
+    retention_settings,
+    scheduled_prompts,
     secrets,
+    service_accounts,
+    session_members,
+    session_search_settings,
     system_tools,
+    tool_runs,
     tools,
     users,
+    webhook_deliveries,
+    webhooks,
 );
@@ -1,13 +1,47 @@
 mod api_key;
+mod attachment;
+mod backup;
 mod chat;
+mod job;
+mod knowledge;
+mod memory;
+mod organization;
+mod persona;
+mod preset;
+mod project;
 mod provider;
+mod retention;
+mod schedule;
 mod secret;
+mod service_account;
+mod session_member;
+mod session_search;
 mod tool;
+mod tool_run;
+mod usage;
 mod user;
+mod webhook;
 
 pub use api_key::ApiKeyDbService;
+pub use attachment::AttachmentDbService;
+pub use backup::{BackupDbService, ExportBatch, ImportBatch};
 pub use chat::ChatDbService;
+pub use job::JobDbService;
+pub use knowledge::KnowledgeDbService;
+pub use memory::MemoryDbService;
+pub use organization::{user_organization_ids, OrganizationDbService};
+pub use persona::PersonaDbService;
+pub use preset::PresetDbService;
+pub use project::ProjectDbService;
 pub use provider::ProviderDbService;
+pub use retention::RetentionDbService;
+pub use schedule::ScheduleDbService;
 pub use secret::SecretDbService;
+pub use service_account::ServiceAccountDbService;
+pub use session_member::SessionMemberDbService;
+pub use session_search::SessionSearchDbService;
 pub use tool::ToolDbService;
+pub use tool_run::ToolRunDbService;
+pub use usage::UsageDbService;
 pub use user::UserDbService;
+pub use webhook::WebhookDbService;
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
 use diesel::result::Error;
 use diesel_async::RunQueryDsl;
@@ -5,6 +6,7 @@ use uuid::Uuid;
 
 use crate::db::{
     models::{ChatRsApiKey, NewChatRsApiKey},
+    pagination::{ListQuery, ListSort},
     schema::app_api_keys,
     DbConnection,
 };
@@ -37,6 +39,55 @@ impl<'a> ApiKeyDbService<'a> {
         Ok(keys)
     }
 
+    /// List the user's API keys
+    pub async fn list(
+        &mut self,
+        user_id: &Uuid,
+        params: &ListQuery,
+    ) -> Result<Vec<ChatRsApiKey>, Error> {
+        let mut query = app_api_keys::table
+            .filter(app_api_keys::user_id.eq(user_id))
+            .select(ChatRsApiKey::as_select())
+            .into_boxed();
+        if let Some(pattern) = params.filter_pattern() {
+            query = query.filter(app_api_keys::name.ilike(pattern));
+        }
+        if let Some(cursor) = params.cursor::<Uuid>()? {
+            let cursor_time: DateTime<Utc> = app_api_keys::table
+                .filter(app_api_keys::user_id.eq(user_id))
+                .filter(app_api_keys::id.eq(cursor))
+                .select(app_api_keys::created_at)
+                .first(self.db)
+                .await?;
+            query = match params.sort() {
+                ListSort::Newest => query.filter(
+                    app_api_keys::created_at
+                        .lt(cursor_time)
+                        .or(app_api_keys::created_at
+                            .eq(cursor_time)
+                            .and(app_api_keys::id.lt(cursor))),
+                ),
+                ListSort::Oldest => query.filter(
+                    app_api_keys::created_at
+                        .gt(cursor_time)
+                        .or(app_api_keys::created_at
+                            .eq(cursor_time)
+                            .and(app_api_keys::id.gt(cursor))),
+                ),
+            };
+        }
+        query = match params.sort() {
+            ListSort::Newest => {
+                query.order_by((app_api_keys::created_at.desc(), app_api_keys::id.desc()))
+            }
+            ListSort::Oldest => {
+                query.order_by((app_api_keys::created_at.asc(), app_api_keys::id.asc()))
+            }
+        };
+
+        query.limit(params.limit()).load(self.db).await
+    }
+
     pub async fn create(&mut self, api_key: NewChatRsApiKey<'_>) -> Result<Uuid, Error> {
         let id: Uuid = diesel::insert_into(app_api_keys::table)
             .values(api_key)
@@ -47,6 +98,23 @@ impl<'a> ApiKeyDbService<'a> {
         Ok(id)
     }
 
+    /// Update the last use of the API key, if it wasn't updated in the last minute
+    pub async fn mark_used(&mut self, api_key_id: &Uuid) -> Result<(), Error> {
+        let now = Utc::now();
+        diesel::update(app_api_keys::table)
+            .filter(app_api_keys::id.eq(api_key_id))
+            .filter(
+                app_api_keys::last_used_at
+                    .is_null()
+                    .or(app_api_keys::last_used_at.lt(now - chrono::Duration::minutes(1))),
+            )
+            .set(app_api_keys::last_used_at.eq(now))
+            .execute(self.db)
+            .await?;
+
+        Ok(())
+    }
+
     pub async fn delete(&mut self, user_id: &Uuid, api_key_id: &Uuid) -> Result<Uuid, Error> {
         let id: Uuid = diesel::delete(app_api_keys::table)
             .filter(app_api_keys::id.eq(api_key_id))
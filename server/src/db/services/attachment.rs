@@ -0,0 +1,151 @@
+use diesel::prelude::*;
+use diesel::result::Error;
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::db::{
+    models::{ChatRsAttachment, NewChatRsAttachment},
+    schema::message_attachments,
+    DbConnection,
+};
+
+pub struct AttachmentDbService<'a> {
+    pub db: &'a mut DbConnection,
+}
+
+impl<'a> AttachmentDbService<'a> {
+    pub fn new(db: &'a mut DbConnection) -> Self {
+        AttachmentDbService { db }
+    }
+
+    pub async fn create(
+        &mut self,
+        attachment: NewChatRsAttachment<'_>,
+    ) -> Result<ChatRsAttachment, Error> {
+        diesel::insert_into(message_attachments::table)
+            .values(attachment)
+            .returning(ChatRsAttachment::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    /// Find one of the user's attachments
+    pub async fn find_by_id(
+        &mut self,
+        user_id: &Uuid,
+        id: &Uuid,
+    ) -> Result<Option<ChatRsAttachment>, Error> {
+        message_attachments::table
+            .filter(message_attachments::user_id.eq(user_id))
+            .filter(message_attachments::id.eq(id))
+            .select(ChatRsAttachment::as_select())
+            .first(self.db)
+            .await
+            .optional()
+    }
+
+    /// Find the user's attachments that aren't attached to a message or project yet
+    pub async fn find_unattached(
+        &mut self,
+        user_id: &Uuid,
+        ids: &[Uuid],
+    ) -> Result<Vec<ChatRsAttachment>, Error> {
+        message_attachments::table
+            .filter(message_attachments::user_id.eq(user_id))
+            .filter(message_attachments::id.eq_any(ids))
+            .filter(message_attachments::message_id.is_null())
+            .filter(message_attachments::project_id.is_null())
+            .select(ChatRsAttachment::as_select())
+            .load(self.db)
+            .await
+    }
+
+    /// Attach the user's attachments to a message
+    pub async fn attach_to_message(
+        &mut self,
+        user_id: &Uuid,
+        ids: &[Uuid],
+        message_id: &Uuid,
+    ) -> Result<usize, Error> {
+        diesel::update(message_attachments::table)
+            .filter(message_attachments::user_id.eq(user_id))
+            .filter(message_attachments::id.eq_any(ids))
+            .filter(message_attachments::message_id.is_null())
+            .filter(message_attachments::project_id.is_null())
+            .set(message_attachments::message_id.eq(message_id))
+            .execute(self.db)
+            .await
+    }
+
+    /// Add the user's unattached attachments to a project
+    pub async fn attach_to_project(
+        &mut self,
+        user_id: &Uuid,
+        ids: &[Uuid],
+        project_id: &Uuid,
+    ) -> Result<usize, Error> {
+        diesel::update(message_attachments::table)
+            .filter(message_attachments::user_id.eq(user_id))
+            .filter(message_attachments::id.eq_any(ids))
+            .filter(message_attachments::message_id.is_null())
+            .filter(message_attachments::project_id.is_null())
+            .set(message_attachments::project_id.eq(project_id))
+            .execute(self.db)
+            .await
+    }
+
+    /// Get the files of a project, in the order they were uploaded
+    pub async fn find_by_project(
+        &mut self,
+        project_id: &Uuid,
+    ) -> Result<Vec<ChatRsAttachment>, Error> {
+        message_attachments::table
+            .filter(message_attachments::project_id.eq(project_id))
+            .select(ChatRsAttachment::as_select())
+            .order_by(message_attachments::created_at.asc())
+            .load(self.db)
+            .await
+    }
+
+    /// Delete a file of a project
+    pub async fn delete_from_project(
+        &mut self,
+        user_id: &Uuid,
+        project_id: &Uuid,
+        id: &Uuid,
+    ) -> Result<ChatRsAttachment, Error> {
+        diesel::delete(message_attachments::table)
+            .filter(message_attachments::user_id.eq(user_id))
+            .filter(message_attachments::project_id.eq(project_id))
+            .filter(message_attachments::id.eq(id))
+            .returning(ChatRsAttachment::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    /// Get the attachments of the given messages, in the order they were uploaded
+    pub async fn find_by_message_ids(
+        &mut self,
+        message_ids: &[Uuid],
+    ) -> Result<Vec<ChatRsAttachment>, Error> {
+        message_attachments::table
+            .filter(message_attachments::message_id.eq_any(message_ids))
+            .select(ChatRsAttachment::as_select())
+            .order_by(message_attachments::created_at.asc())
+            .load(self.db)
+            .await
+    }
+
+    /// Save the text extracted from an attachment (`None` if the extraction failed)
+    pub async fn set_extracted_text(&mut self, id: &Uuid, text: Option<&str>) -> Result<(), Error> {
+        diesel::update(message_attachments::table)
+            .filter(message_attachments::id.eq(id))
+            .set((
+                message_attachments::extracted_text.eq(text),
+                message_attachments::extracted_at.eq(diesel::dsl::now),
+            ))
+            .execute(self.db)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use diesel::{
+    result::Error,
+    sql_types::{BigInt, Text},
+    QueryableByName,
+};
+use diesel_async::{scoped_futures::ScopedFutureExt, AsyncConnection, RunQueryDsl};
+use tokio::sync::mpsc;
+
+use crate::db::DbConnection;
+
+pub struct BackupDbService<'a> {
+    pub db: &'a mut DbConnection,
+}
+
+/// Batch of rows exported by [`BackupDbService::export_tables`]
+pub enum ExportBatch {
+    /// Number of rows of each table, sent before the rows
+    Counts(Vec<(&'static str, usize)>),
+    /// Rows of a table, as JSON objects
+    Rows(&'static str, Vec<String>),
+}
+
+/// Batch of rows to insert by [`BackupDbService::import_tables`]
+pub enum ImportBatch {
+    /// Rows of a table, as a JSON array
+    Rows(&'static str, String),
+    /// All the rows were sent. The transaction is rolled back if the sender is dropped before
+    /// sending this.
+    Done,
+}
+
+/// Name of the cursor for exporting the rows of a table
+const EXPORT_CURSOR: &str = "backup_rows";
+
+#[derive(QueryableByName)]
+struct ExportedRow {
+    #[diesel(sql_type = Text)]
+    data: String,
+}
+
+#[derive(QueryableByName)]
+struct RowCount {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+impl<'a> BackupDbService<'a> {
+    pub fn new(db: &'a mut DbConnection) -> Self {
+        BackupDbService { db }
+    }
+
+    /// Export all the rows of the tables as JSON objects, sending them in batches of the given
+    /// size. The tables are read with a cursor in one read-only, repeatable read transaction,
+    /// so that the export is consistent without loading whole tables in memory. Stops if the
+    /// receiver is dropped.
+    pub async fn export_tables(
+        &mut self,
+        tables: &[&'static str],
+        batch_size: usize,
+        sender: mpsc::Sender<ExportBatch>,
+    ) -> Result<(), Error> {
+        let tables = tables.to_vec();
+        self.db
+            .build_transaction()
+            .repeatable_read()
+            .read_only()
+            .run::<_, Error, _>(|conn| {
+                async move {
+                    let mut counts = Vec::with_capacity(tables.len());
+                    for table in &tables {
+                        let query = format!("SELECT COUNT(*) AS count FROM {table}");
+                        let result: RowCount = diesel::sql_query(query).get_result(conn).await?;
+                        counts.push((*table, result.count as usize));
+                    }
+                    sender
+                        .send(ExportBatch::Counts(counts))
+                        .await
+                        .map_err(|_| Error::RollbackTransaction)?;
+
+                    for table in tables {
+                        let query = format!(
+                            "DECLARE {EXPORT_CURSOR} NO SCROLL CURSOR FOR \
+                            SELECT row_to_json(t)::text AS data FROM {table} t"
+                        );
+                        diesel::sql_query(query).execute(conn).await?;
+                        loop {
+                            let query = format!("FETCH {batch_size} FROM {EXPORT_CURSOR}");
+                            let rows: Vec<ExportedRow> =
+                                diesel::sql_query(query).load(conn).await?;
+                            if rows.is_empty() {
+                                break;
+                            }
+                            let rows = rows.into_iter().map(|row| row.data).collect();
+                            sender
+                                .send(ExportBatch::Rows(table, rows))
+                                .await
+                                .map_err(|_| Error::RollbackTransaction)?;
+                        }
+                        diesel::sql_query(format!("CLOSE {EXPORT_CURSOR}"))
+                            .execute(conn)
+                            .await?;
+                    }
+                    Ok(())
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    /// Insert the batches of rows (JSON arrays) into the tables as they're received, in one
+    /// transaction. Rows conflicting with existing rows are skipped. Returns the number of
+    /// inserted rows of each table.
+    pub async fn import_tables(
+        &mut self,
+        mut receiver: mpsc::Receiver<ImportBatch>,
+    ) -> Result<HashMap<&'static str, usize>, Error> {
+        self.db
+            .transaction::<_, Error, _>(|conn| {
+                async move {
+                    let mut imported: HashMap<&'static str, usize> = HashMap::new();
+                    loop {
+                        match receiver.recv().await {
+                            Some(ImportBatch::Rows(table, rows)) => {
+                                let query = format!(
+                                    "WITH inserted AS (INSERT INTO {table} \
+                                    SELECT * FROM json_populate_recordset(NULL::{table}, $1::json) \
+                                    ON CONFLICT DO NOTHING RETURNING 1) \
+                                    SELECT COUNT(*) AS count FROM inserted"
+                                );
+                                let result: RowCount = diesel::sql_query(query)
+                                    .bind::<Text, _>(rows)
+                                    .get_result(conn)
+                                    .await?;
+                                *imported.entry(table).or_default() += result.count as usize;
+                            }
+                            Some(ImportBatch::Done) => break,
+                            None => return Err(Error::RollbackTransaction),
+                        }
+                    }
+
+                    // Restored providers keep their IDs, so move the sequence past them
+                    diesel::sql_query(
+                        "SELECT setval(pg_get_serial_sequence('providers', 'id'), \
+                        COALESCE(MAX(id), 0) + 1, false) FROM providers",
+                    )
+                    .execute(conn)
+                    .await?;
+
+                    Ok(imported)
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+}
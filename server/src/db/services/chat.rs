@@ -1,16 +1,24 @@
-use diesel::prelude::*;
-use diesel_async::RunQueryDsl;
+use chrono::{DateTime, Utc};
+use diesel::{pg::Pg, prelude::*};
+use diesel_async::{scoped_futures::ScopedFutureExt, AsyncConnection, RunQueryDsl};
 use uuid::Uuid;
 
 use crate::{
     db::{
         models::{
-            ChatRsMessage, ChatRsSession, NewChatRsMessage, NewChatRsSession, UpdateChatRsSession,
+            ChatRsDeletedMessage, ChatRsMessage, ChatRsMessageMeta, ChatRsMessageRole,
+            ChatRsSession, ChatRsSessionRole, NewChatRsMessage, NewChatRsSession,
+            NewImportedChatRsMessage, UpdateChatRsSession,
         },
-        schema::{chat_messages, chat_sessions},
+        pagination::{ListQuery, ListSort},
+        schema::{chat_messages, chat_sessions, session_members},
         DbConnection,
     },
-    utils::{full_text_query, SessionSearchResult},
+    provider::LlmError,
+    utils::{
+        full_text_query, session_full_text_query, MessageEncryption, MessageSearchResult,
+        SessionSearchResult,
+    },
 };
 
 pub struct ChatDbService<'a> {
@@ -34,71 +42,393 @@ impl<'a> ChatDbService<'a> {
         Ok(id.to_string())
     }
 
+    /// Create a session with the given messages (e.g. imported from a transcript), in a
+    /// single transaction. The messages are saved in the given order, with their original
+    /// times if known.
+    pub async fn import_session(
+        &mut self,
+        session: NewChatRsSession<'_>,
+        messages: &[(ChatRsMessageRole, &str, Option<DateTime<Utc>>)],
+    ) -> Result<Uuid, diesel::result::Error> {
+        let encryption = self.db.message_encryption().clone();
+        let contents = messages
+            .iter()
+            .map(|(_, content, _)| encryption.encrypt(content))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(encryption_error)?;
+        self.db
+            .transaction::<_, diesel::result::Error, _>(|conn| {
+                async move {
+                    let session_id: Uuid = diesel::insert_into(chat_sessions::table)
+                        .values(session)
+                        .returning(chat_sessions::id)
+                        .get_result(conn)
+                        .await?;
+                    // Keep the times increasing, so that the messages stay in order
+                    let mut last_time: Option<DateTime<Utc>> = None;
+                    let new_messages: Vec<_> = messages
+                        .iter()
+                        .zip(&contents)
+                        .map(|((role, plaintext, created_at), encrypted)| {
+                            let created_at = match (*created_at, last_time) {
+                                (Some(time), Some(last)) if time > last => time,
+                                (Some(time), None) => time,
+                                (_, Some(last)) => last + chrono::Duration::milliseconds(1),
+                                (None, None) => Utc::now(),
+                            };
+                            last_time = Some(created_at);
+                            let new_message = NewImportedChatRsMessage {
+                                session_id: &session_id,
+                                role: *role,
+                                content: encrypted.as_deref().unwrap_or(*plaintext),
+                                meta: ChatRsMessageMeta::default(),
+                                created_at,
+                            };
+                            (
+                                new_message,
+                                chat_messages::content_encrypted.eq(encrypted.is_some()),
+                            )
+                        })
+                        .collect();
+                    let ids: Vec<Uuid> = diesel::insert_into(chat_messages::table)
+                        .values(&new_messages)
+                        .returning(chat_messages::id)
+                        .get_results(conn)
+                        .await?;
+                    for ((id, (_, plaintext, _)), encrypted) in
+                        ids.iter().zip(messages).zip(&contents)
+                    {
+                        if encrypted.is_some() {
+                            index_encrypted_message(conn, &encryption, id, plaintext).await?;
+                        }
+                    }
+
+                    Ok(session_id)
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
     pub async fn save_message(
         &mut self,
         message: NewChatRsMessage<'_>,
     ) -> Result<ChatRsMessage, diesel::result::Error> {
-        let message = diesel::insert_into(chat_messages::table)
-            .values(message)
+        let plaintext = message.content;
+        let encryption = self.db.message_encryption().clone();
+        let encrypted = encryption.encrypt(plaintext).map_err(encryption_error)?;
+        let new_message = NewChatRsMessage {
+            content: encrypted.as_deref().unwrap_or(plaintext),
+            ..message
+        };
+        let mut message = diesel::insert_into(chat_messages::table)
+            .values((
+                new_message,
+                chat_messages::content_encrypted.eq(encrypted.is_some()),
+            ))
             .returning(ChatRsMessage::as_select())
             .get_result(self.db)
             .await?;
+        if message.content_encrypted {
+            index_encrypted_message(self.db, &encryption, &message.id, plaintext).await?;
+            message.content = plaintext.to_owned();
+            message.content_encrypted = false;
+        }
         Ok(message)
     }
 
     pub async fn save_messages(
         &mut self,
-        messages: &[NewChatRsMessage<'_>],
+        messages: Vec<NewChatRsMessage<'_>>,
     ) -> Result<Vec<ChatRsMessage>, diesel::result::Error> {
-        let messages = diesel::insert_into(chat_messages::table)
-            .values(messages)
+        let encryption = self.db.message_encryption().clone();
+        let plaintexts: Vec<&str> = messages.iter().map(|message| message.content).collect();
+        let contents = plaintexts
+            .iter()
+            .map(|content| encryption.encrypt(content))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(encryption_error)?;
+        let new_messages: Vec<_> = messages
+            .into_iter()
+            .zip(&plaintexts)
+            .zip(&contents)
+            .map(|((message, plaintext), encrypted)| {
+                let new_message = NewChatRsMessage {
+                    content: encrypted.as_deref().unwrap_or(*plaintext),
+                    ..message
+                };
+                (
+                    new_message,
+                    chat_messages::content_encrypted.eq(encrypted.is_some()),
+                )
+            })
+            .collect();
+        let mut messages: Vec<ChatRsMessage> = diesel::insert_into(chat_messages::table)
+            .values(&new_messages)
             .returning(ChatRsMessage::as_select())
             .get_results(self.db)
             .await?;
+        for (message, plaintext) in messages.iter_mut().zip(plaintexts) {
+            if message.content_encrypted {
+                index_encrypted_message(self.db, &encryption, &message.id, plaintext).await?;
+                message.content = plaintext.to_owned();
+                message.content_encrypted = false;
+            }
+        }
         Ok(messages)
     }
 
+    /// Get the IDs and raw encrypted contents of the encrypted messages (of all users), in
+    /// batches ordered by ID
+    pub async fn find_encrypted_messages(
+        &mut self,
+        after_id: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<(Uuid, String)>, diesel::result::Error> {
+        let mut query = chat_messages::table
+            .filter(chat_messages::content_encrypted.eq(true))
+            .select((chat_messages::id, chat_messages::content))
+            .order_by(chat_messages::id.asc())
+            .limit(limit)
+            .into_boxed();
+        if let Some(after_id) = after_id {
+            query = query.filter(chat_messages::id.gt(after_id));
+        }
+        query.load(self.db).await
+    }
+
+    /// Replace the raw encrypted content of a message (e.g. re-encrypted with a new key)
+    pub async fn update_encrypted_content(
+        &mut self,
+        message_id: &Uuid,
+        content: &str,
+    ) -> Result<(), diesel::result::Error> {
+        diesel::update(chat_messages::table.find(message_id))
+            .set(chat_messages::content.eq(content))
+            .execute(self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// Find a message by its ID. The access of the user to the session of the message must be
+    /// checked separately (see `get_session_with_role`).
     pub async fn find_message(
         &mut self,
-        user_id: &Uuid,
         message_id: &Uuid,
     ) -> Result<ChatRsMessage, diesel::result::Error> {
         chat_messages::table
             .inner_join(chat_sessions::table.on(chat_sessions::id.eq(chat_messages::session_id)))
             .select(ChatRsMessage::as_select())
-            .filter(chat_sessions::user_id.eq(user_id))
+            .filter(chat_sessions::deleted_at.is_null())
             .filter(chat_messages::id.eq(message_id))
+            .filter(chat_messages::deleted_at.is_null())
+            .get_result(self.db)
+            .await
+            .map(|message| decrypt_message(self.db, message))
+    }
+
+    /// Find an assistant message of the session
+    pub async fn find_assistant_message(
+        &mut self,
+        session_id: &Uuid,
+        message_id: &Uuid,
+    ) -> Result<ChatRsMessage, diesel::result::Error> {
+        chat_messages::table
+            .filter(chat_messages::session_id.eq(session_id))
+            .filter(chat_messages::id.eq(message_id))
+            .filter(chat_messages::role.eq(ChatRsMessageRole::Assistant))
+            .filter(chat_messages::deleted_at.is_null())
+            .select(ChatRsMessage::as_select())
+            .first(self.db)
+            .await
+            .map(|message| decrypt_message(self.db, message))
+    }
+
+    /// Find the assistant messages that the user gave feedback on, in chronological order
+    pub async fn find_messages_with_feedback(
+        &mut self,
+        user_id: &Uuid,
+    ) -> Result<Vec<ChatRsMessage>, diesel::result::Error> {
+        chat_messages::table
+            .inner_join(chat_sessions::table)
+            .filter(chat_sessions::deleted_at.is_null())
+            .filter(chat_messages::deleted_at.is_null())
+            .filter(chat_messages::role.eq(ChatRsMessageRole::Assistant))
+            .filter(
+                chat_messages::meta
+                    .retrieve_as_object("assistant")
+                    .retrieve_as_object("feedback")
+                    .retrieve_as_text("user_id")
+                    .eq(user_id.to_string()),
+            )
+            .select(ChatRsMessage::as_select())
+            .order_by(chat_messages::created_at.asc())
+            .load(self.db)
+            .await
+            .map(|messages| decrypt_messages(self.db, messages))
+    }
+
+    /// Get the user messages of the given sessions, in chronological order
+    pub async fn get_user_messages(
+        &mut self,
+        session_ids: &[Uuid],
+    ) -> Result<Vec<ChatRsMessage>, diesel::result::Error> {
+        chat_messages::table
+            .filter(chat_messages::session_id.eq_any(session_ids))
+            .filter(chat_messages::role.eq(ChatRsMessageRole::User))
+            .filter(chat_messages::deleted_at.is_null())
+            .select(ChatRsMessage::as_select())
+            .order_by(chat_messages::created_at.asc())
+            .load(self.db)
+            .await
+            .map(|messages| decrypt_messages(self.db, messages))
+    }
+
+    /// Update the metadata of a message (e.g. the approval state of its tool calls)
+    pub async fn update_message_meta(
+        &mut self,
+        message_id: &Uuid,
+        meta: ChatRsMessageMeta,
+    ) -> Result<ChatRsMessage, diesel::result::Error> {
+        diesel::update(chat_messages::table.find(message_id))
+            .set(chat_messages::meta.eq(meta))
+            .returning(ChatRsMessage::as_select())
             .get_result(self.db)
             .await
+            .map(|message| decrypt_message(self.db, message))
     }
 
+    /// Move a message to the trash
     pub async fn delete_message(
         &mut self,
         session_id: &Uuid,
         message_id: &Uuid,
     ) -> Result<String, diesel::result::Error> {
-        let id: Uuid = diesel::delete(chat_messages::table)
+        let id: Uuid = diesel::update(chat_messages::table)
             .filter(chat_messages::session_id.eq(session_id))
             .filter(chat_messages::id.eq(message_id))
+            .filter(chat_messages::deleted_at.is_null())
+            .set(chat_messages::deleted_at.eq(Utc::now()))
             .returning(chat_messages::id)
             .get_result(self.db)
             .await?;
         Ok(id.to_string())
     }
 
-    pub async fn get_all_sessions(
+    /// Pin or unpin a message
+    pub async fn set_message_pinned(
+        &mut self,
+        session_id: &Uuid,
+        message_id: &Uuid,
+        pinned: bool,
+    ) -> Result<ChatRsMessage, diesel::result::Error> {
+        diesel::update(chat_messages::table)
+            .filter(chat_messages::session_id.eq(session_id))
+            .filter(chat_messages::id.eq(message_id))
+            .filter(chat_messages::deleted_at.is_null())
+            .set(chat_messages::pinned.eq(pinned))
+            .returning(ChatRsMessage::as_select())
+            .get_result(self.db)
+            .await
+            .map(|message| decrypt_message(self.db, message))
+    }
+
+    /// Get the pinned messages of a session, in chronological order
+    pub async fn get_pinned_messages(
+        &mut self,
+        session_id: &Uuid,
+    ) -> Result<Vec<ChatRsMessage>, diesel::result::Error> {
+        chat_messages::table
+            .filter(chat_messages::session_id.eq(session_id))
+            .filter(chat_messages::pinned.eq(true))
+            .filter(chat_messages::deleted_at.is_null())
+            .select(ChatRsMessage::as_select())
+            .order_by(chat_messages::created_at.asc())
+            .load(self.db)
+            .await
+            .map(|messages| decrypt_messages(self.db, messages))
+    }
+
+    /// List the user's sessions, pinned sessions first and then sorted by the last update.
+    /// Archived sessions are excluded unless `include_archived` is set.
+    pub async fn list_sessions(
         &mut self,
         user_id: &Uuid,
+        params: &ListQuery,
+        include_archived: bool,
+        project_id: Option<&Uuid>,
     ) -> Result<Vec<ChatRsSession>, diesel::result::Error> {
-        let sessions = chat_sessions::table
+        let mut query = chat_sessions::table
             .filter(chat_sessions::user_id.eq(user_id))
+            .filter(chat_sessions::deleted_at.is_null())
             .select(ChatRsSession::as_select())
-            .order_by(chat_sessions::updated_at.desc())
-            .limit(100)
-            .load(self.db)
-            .await?;
+            .into_boxed();
+        if !include_archived {
+            query = query.filter(chat_sessions::archived_at.is_null());
+        }
+        if let Some(project_id) = project_id {
+            query = query.filter(chat_sessions::project_id.eq(project_id));
+        }
+        if let Some(pattern) = params.filter_pattern() {
+            query = query.filter(chat_sessions::title.ilike(pattern));
+        }
+        if let Some(cursor) = params.cursor::<Uuid>()? {
+            let (cursor_pinned, cursor_time): (bool, DateTime<Utc>) = chat_sessions::table
+                .filter(chat_sessions::user_id.eq(user_id))
+                .filter(chat_sessions::id.eq(cursor))
+                .select((chat_sessions::pinned, chat_sessions::updated_at))
+                .first(self.db)
+                .await?;
+            let same_pinned = chat_sessions::pinned.eq(cursor_pinned);
+            query = match params.sort() {
+                ListSort::Newest => query.filter(
+                    chat_sessions::pinned.lt(cursor_pinned).or(same_pinned.and(
+                        chat_sessions::updated_at
+                            .lt(cursor_time)
+                            .or(chat_sessions::updated_at
+                                .eq(cursor_time)
+                                .and(chat_sessions::id.lt(cursor))),
+                    )),
+                ),
+                ListSort::Oldest => query.filter(
+                    chat_sessions::pinned.lt(cursor_pinned).or(same_pinned.and(
+                        chat_sessions::updated_at
+                            .gt(cursor_time)
+                            .or(chat_sessions::updated_at
+                                .eq(cursor_time)
+                                .and(chat_sessions::id.gt(cursor))),
+                    )),
+                ),
+            };
+        }
+        query = match params.sort() {
+            ListSort::Newest => query.order_by((
+                chat_sessions::pinned.desc(),
+                chat_sessions::updated_at.desc(),
+                chat_sessions::id.desc(),
+            )),
+            ListSort::Oldest => query.order_by((
+                chat_sessions::pinned.desc(),
+                chat_sessions::updated_at.asc(),
+                chat_sessions::id.asc(),
+            )),
+        };
 
-        Ok(sessions)
+        query.limit(params.limit()).load(self.db).await
+    }
+
+    /// Get all of the user's sessions, including the archived sessions, oldest first
+    pub async fn find_sessions_by_user(
+        &mut self,
+        user_id: &Uuid,
+    ) -> Result<Vec<ChatRsSession>, diesel::result::Error> {
+        chat_sessions::table
+            .filter(chat_sessions::user_id.eq(user_id))
+            .filter(chat_sessions::deleted_at.is_null())
+            .select(ChatRsSession::as_select())
+            .order_by(chat_sessions::created_at.asc())
+            .load(self.db)
+            .await
     }
 
     pub async fn get_session(
@@ -109,6 +439,7 @@ impl<'a> ChatDbService<'a> {
         let session = chat_sessions::table
             .filter(chat_sessions::user_id.eq(user_id))
             .filter(chat_sessions::id.eq(session_id))
+            .filter(chat_sessions::deleted_at.is_null())
             .select(ChatRsSession::as_select())
             .first(self.db)
             .await?;
@@ -116,24 +447,105 @@ impl<'a> ChatDbService<'a> {
         Ok(session)
     }
 
-    pub async fn get_session_with_messages(
+    /// Get a session that the user owns or is a member of, with the user's role
+    pub async fn get_session_with_role(
         &mut self,
         user_id: &Uuid,
         session_id: &Uuid,
-    ) -> Result<(ChatRsSession, Vec<ChatRsMessage>), diesel::result::Error> {
-        let session = chat_sessions::table
-            .filter(chat_sessions::user_id.eq(user_id))
+    ) -> Result<(ChatRsSession, ChatRsSessionRole), diesel::result::Error> {
+        let (session, member_role): (ChatRsSession, Option<String>) = chat_sessions::table
+            .left_join(
+                session_members::table.on(session_members::session_id
+                    .eq(chat_sessions::id)
+                    .and(session_members::user_id.eq(user_id))),
+            )
             .filter(chat_sessions::id.eq(session_id))
-            .select(ChatRsSession::as_select())
+            .filter(chat_sessions::deleted_at.is_null())
+            .filter(
+                chat_sessions::user_id
+                    .eq(user_id)
+                    .or(session_members::user_id.nullable().is_not_null()),
+            )
+            .select((ChatRsSession::as_select(), session_members::role.nullable()))
             .first(self.db)
             .await?;
-        let messages = ChatRsMessage::belonging_to(&session)
+        let role = match member_role {
+            _ if session.user_id == *user_id => ChatRsSessionRole::Owner,
+            Some(role) => role.as_str().into(),
+            None => ChatRsSessionRole::Read,
+        };
+
+        Ok((session, role))
+    }
+
+    /// Get a session that the user owns or is a member of, and its messages
+    pub async fn get_session_with_messages(
+        &mut self,
+        user_id: &Uuid,
+        session_id: &Uuid,
+    ) -> Result<(ChatRsSession, Vec<ChatRsMessage>), diesel::result::Error> {
+        let (session, _) = self.get_session_with_role(user_id, session_id).await?;
+        let messages = self.get_messages(&session).await?;
+
+        Ok((session, messages))
+    }
+
+    /// Get the messages of a session, in chronological order
+    pub async fn get_messages(
+        &mut self,
+        session: &ChatRsSession,
+    ) -> Result<Vec<ChatRsMessage>, diesel::result::Error> {
+        ChatRsMessage::belonging_to(session)
+            .filter(chat_messages::deleted_at.is_null())
             .select(ChatRsMessage::as_select())
             .order_by(chat_messages::created_at.asc())
             .load(self.db)
+            .await
+            .map(|messages| decrypt_messages(self.db, messages))
+    }
+
+    /// Get the messages of the session created, updated, or deleted after the given time (or
+    /// all messages not deleted), oldest change first, with the time of their last update and
+    /// when they were deleted
+    pub async fn get_changed_messages(
+        &mut self,
+        session: &ChatRsSession,
+        since: Option<i64>,
+    ) -> Result<Vec<(ChatRsMessage, i64, Option<DateTime<Utc>>)>, diesel::result::Error> {
+        let mut query = ChatRsMessage::belonging_to(session).into_boxed();
+        query = match since {
+            Some(since) => query.filter(chat_messages::change_seq.gt(since)),
+            None => query.filter(chat_messages::deleted_at.is_null()),
+        };
+        let mut messages: Vec<(ChatRsMessage, i64, Option<DateTime<Utc>>)> = query
+            .select((
+                ChatRsMessage::as_select(),
+                chat_messages::change_seq,
+                chat_messages::deleted_at,
+            ))
+            .order_by(chat_messages::change_seq.asc())
+            .load(self.db)
             .await?;
+        for (message, _, _) in &mut messages {
+            message.decrypt(self.db.message_encryption());
+        }
 
-        Ok((session, messages))
+        Ok(messages)
+    }
+
+    /// List the sessions that are shared with the user, most recently updated first
+    pub async fn list_shared_sessions(
+        &mut self,
+        user_id: &Uuid,
+    ) -> Result<Vec<(ChatRsSession, String)>, diesel::result::Error> {
+        chat_sessions::table
+            .inner_join(session_members::table)
+            .filter(session_members::user_id.eq(user_id))
+            .filter(chat_sessions::deleted_at.is_null())
+            .select((ChatRsSession::as_select(), session_members::role))
+            .order_by(chat_sessions::updated_at.desc())
+            .load(self.db)
+            .await
     }
 
     pub async fn search_sessions(
@@ -146,6 +558,18 @@ impl<'a> ChatDbService<'a> {
         Ok(sessions)
     }
 
+    /// Search the messages of a session
+    pub async fn search_session_messages(
+        &mut self,
+        user_id: &Uuid,
+        session_id: &Uuid,
+        query: &str,
+    ) -> Result<Vec<MessageSearchResult>, diesel::result::Error> {
+        let messages = session_full_text_query(self.db, user_id, session_id, query, 50).await?;
+
+        Ok(messages)
+    }
+
     pub async fn update_session(
         &mut self,
         user_id: &Uuid,
@@ -155,6 +579,7 @@ impl<'a> ChatDbService<'a> {
         let updated_id: Uuid = diesel::update(chat_sessions::table.find(session_id))
             .set(data)
             .filter(chat_sessions::user_id.eq(user_id))
+            .filter(chat_sessions::deleted_at.is_null())
             .returning(chat_sessions::id)
             .get_result(self.db)
             .await?;
@@ -162,13 +587,16 @@ impl<'a> ChatDbService<'a> {
         Ok(updated_id)
     }
 
+    /// Move a session to the trash
     pub async fn delete_session(
         &mut self,
         user_id: &Uuid,
         session_id: &Uuid,
     ) -> Result<Uuid, diesel::result::Error> {
-        let id: Uuid = diesel::delete(chat_sessions::table.find(session_id))
+        let id: Uuid = diesel::update(chat_sessions::table.find(session_id))
             .filter(chat_sessions::user_id.eq(user_id))
+            .filter(chat_sessions::deleted_at.is_null())
+            .set(chat_sessions::deleted_at.eq(Utc::now()))
             .returning(chat_sessions::id)
             .get_result(self.db)
             .await?;
@@ -176,6 +604,83 @@ impl<'a> ChatDbService<'a> {
         Ok(id)
     }
 
+    /// List the user's sessions in the trash, and the messages in the trash of the other
+    /// sessions, most recently deleted first
+    pub async fn list_trash(
+        &mut self,
+        user_id: &Uuid,
+    ) -> Result<(Vec<ChatRsSession>, Vec<ChatRsDeletedMessage>), diesel::result::Error> {
+        let sessions = chat_sessions::table
+            .filter(chat_sessions::user_id.eq(user_id))
+            .filter(chat_sessions::deleted_at.is_not_null())
+            .select(ChatRsSession::as_select())
+            .order_by(chat_sessions::deleted_at.desc())
+            .load(self.db)
+            .await?;
+        let mut messages: Vec<ChatRsDeletedMessage> = chat_messages::table
+            .inner_join(chat_sessions::table.on(chat_sessions::id.eq(chat_messages::session_id)))
+            .filter(chat_sessions::user_id.eq(user_id))
+            .filter(chat_sessions::deleted_at.is_null())
+            .filter(chat_messages::deleted_at.is_not_null())
+            .select(ChatRsDeletedMessage::as_select())
+            .order_by(chat_messages::deleted_at.desc())
+            .load(self.db)
+            .await?;
+        for deleted in &mut messages {
+            deleted.message.decrypt(self.db.message_encryption());
+        }
+
+        Ok((sessions, messages))
+    }
+
+    /// Restore a session from the trash
+    pub async fn restore_session(
+        &mut self,
+        user_id: &Uuid,
+        session_id: &Uuid,
+    ) -> Result<Uuid, diesel::result::Error> {
+        diesel::update(chat_sessions::table.find(session_id))
+            .filter(chat_sessions::user_id.eq(user_id))
+            .filter(chat_sessions::deleted_at.is_not_null())
+            .set(chat_sessions::deleted_at.eq(None::<DateTime<Utc>>))
+            .returning(chat_sessions::id)
+            .get_result(self.db)
+            .await
+    }
+
+    /// Restore a message from the trash
+    pub async fn restore_message(
+        &mut self,
+        session_id: &Uuid,
+        message_id: &Uuid,
+    ) -> Result<Uuid, diesel::result::Error> {
+        diesel::update(chat_messages::table)
+            .filter(chat_messages::session_id.eq(session_id))
+            .filter(chat_messages::id.eq(message_id))
+            .filter(chat_messages::deleted_at.is_not_null())
+            .set(chat_messages::deleted_at.eq(None::<DateTime<Utc>>))
+            .returning(chat_messages::id)
+            .get_result(self.db)
+            .await
+    }
+
+    /// Permanently delete the sessions and messages (of all users) moved to the trash before
+    /// the given time. Returns the IDs of the deleted sessions.
+    pub async fn purge_trash(
+        &mut self,
+        deleted_before: DateTime<Utc>,
+    ) -> Result<Vec<Uuid>, diesel::result::Error> {
+        diesel::delete(chat_messages::table)
+            .filter(chat_messages::deleted_at.lt(deleted_before))
+            .execute(self.db)
+            .await?;
+        diesel::delete(chat_sessions::table)
+            .filter(chat_sessions::deleted_at.lt(deleted_before))
+            .returning(chat_sessions::id)
+            .get_results(self.db)
+            .await
+    }
+
     pub async fn delete_by_user(
         &mut self,
         user_id: &Uuid,
@@ -189,3 +694,47 @@ impl<'a> ChatDbService<'a> {
         Ok(ids)
     }
 }
+
+fn encryption_error(err: LlmError) -> diesel::result::Error {
+    diesel::result::Error::SerializationError(Box::new(err))
+}
+
+/// Decrypt the content of a loaded message
+fn decrypt_message(db: &DbConnection, mut message: ChatRsMessage) -> ChatRsMessage {
+    message.decrypt(db.message_encryption());
+    message
+}
+
+/// Decrypt the contents of the loaded messages
+fn decrypt_messages(db: &DbConnection, mut messages: Vec<ChatRsMessage>) -> Vec<ChatRsMessage> {
+    for message in &mut messages {
+        message.decrypt(db.message_encryption());
+    }
+    messages
+}
+
+/// Set the full-text search vector of an encrypted message from its plaintext, if the
+/// indexing of encrypted messages is enabled
+async fn index_encrypted_message<C>(
+    conn: &mut C,
+    encryption: &MessageEncryption,
+    message_id: &Uuid,
+    plaintext: &str,
+) -> Result<(), diesel::result::Error>
+where
+    C: AsyncConnection<Backend = Pg> + Send,
+{
+    if !encryption.index_encrypted_messages() {
+        return Ok(());
+    }
+    diesel::sql_query(
+        "UPDATE chat_messages SET content_vector = setweight(to_tsvector('english', $1), 'B') \
+        WHERE id = $2",
+    )
+    .bind::<diesel::sql_types::Text, _>(plaintext)
+    .bind::<diesel::sql_types::Uuid, _>(message_id)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use diesel::prelude::*;
+use diesel::result::Error;
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::db::{
+    models::{ChatRsJob, ChatRsJobStatus, NewChatRsJob, UpdateChatRsJob},
+    schema::jobs,
+    DbConnection,
+};
+
+pub struct JobDbService<'a> {
+    pub db: &'a mut DbConnection,
+}
+
+impl<'a> JobDbService<'a> {
+    pub fn new(db: &'a mut DbConnection) -> Self {
+        JobDbService { db }
+    }
+
+    pub async fn find_by_id(&mut self, user_id: &Uuid, job_id: &Uuid) -> Result<ChatRsJob, Error> {
+        jobs::table
+            .filter(jobs::user_id.eq(user_id))
+            .filter(jobs::id.eq(job_id))
+            .select(ChatRsJob::as_select())
+            .first(self.db)
+            .await
+    }
+
+    pub async fn find_by_user_id(&mut self, user_id: &Uuid) -> Result<Vec<ChatRsJob>, Error> {
+        jobs::table
+            .filter(jobs::user_id.eq(user_id))
+            .select(ChatRsJob::as_select())
+            .order_by(jobs::created_at.desc())
+            .load(self.db)
+            .await
+    }
+
+    /// Find the jobs of all users that are still waiting for results
+    pub async fn find_processing(&mut self) -> Result<Vec<ChatRsJob>, Error> {
+        let status: &str = (&ChatRsJobStatus::Processing).into();
+        jobs::table
+            .filter(jobs::status.eq(status))
+            .select(ChatRsJob::as_select())
+            .order_by(jobs::created_at.asc())
+            .load(self.db)
+            .await
+    }
+
+    pub async fn create(&mut self, job: NewChatRsJob<'_>) -> Result<ChatRsJob, Error> {
+        diesel::insert_into(jobs::table)
+            .values(job)
+            .returning(ChatRsJob::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn update(
+        &mut self,
+        user_id: &Uuid,
+        job_id: &Uuid,
+        data: UpdateChatRsJob<'_>,
+    ) -> Result<ChatRsJob, Error> {
+        diesel::update(jobs::table)
+            .filter(jobs::user_id.eq(user_id))
+            .filter(jobs::id.eq(job_id))
+            .set(data)
+            .returning(ChatRsJob::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn delete(&mut self, user_id: &Uuid, job_id: &Uuid) -> Result<Uuid, Error> {
+        diesel::delete(jobs::table)
+            .filter(jobs::user_id.eq(user_id))
+            .filter(jobs::id.eq(job_id))
+            .returning(jobs::id)
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn delete_by_user(&mut self, user_id: &Uuid) -> Result<Vec<Uuid>, Error> {
+        diesel::delete(jobs::table)
+            .filter(jobs::user_id.eq(user_id))
+            .returning(jobs::id)
+            .get_results(self.db)
+            .await
+    }
+}
@@ -0,0 +1,181 @@
+use diesel::prelude::*;
+use diesel::result::Error;
+use diesel_async::{scoped_futures::ScopedFutureExt, AsyncConnection, RunQueryDsl};
+use pgvector::{Vector, VectorExpressionMethods};
+use uuid::Uuid;
+
+use crate::db::{
+    models::{
+        ChatRsKnowledgeBase, ChatRsKnowledgeDocument, ChatRsKnowledgeSearchResult,
+        NewChatRsKnowledgeBase, NewChatRsKnowledgeChunk, NewChatRsKnowledgeDocument,
+        UpdateChatRsKnowledgeBase,
+    },
+    schema::{knowledge_bases, knowledge_chunks, knowledge_documents},
+    DbConnection,
+};
+
+pub struct KnowledgeDbService<'a> {
+    pub db: &'a mut DbConnection,
+}
+
+impl<'a> KnowledgeDbService<'a> {
+    pub fn new(db: &'a mut DbConnection) -> Self {
+        KnowledgeDbService { db }
+    }
+
+    pub async fn find_by_user_id(
+        &mut self,
+        user_id: &Uuid,
+    ) -> Result<Vec<ChatRsKnowledgeBase>, Error> {
+        knowledge_bases::table
+            .filter(knowledge_bases::user_id.eq(user_id))
+            .select(ChatRsKnowledgeBase::as_select())
+            .order_by(knowledge_bases::name.asc())
+            .load(self.db)
+            .await
+    }
+
+    pub async fn find_by_id(
+        &mut self,
+        user_id: &Uuid,
+        knowledge_base_id: &Uuid,
+    ) -> Result<Option<ChatRsKnowledgeBase>, Error> {
+        knowledge_bases::table
+            .filter(knowledge_bases::user_id.eq(user_id))
+            .filter(knowledge_bases::id.eq(knowledge_base_id))
+            .select(ChatRsKnowledgeBase::as_select())
+            .first(self.db)
+            .await
+            .optional()
+    }
+
+    pub async fn create(
+        &mut self,
+        knowledge_base: NewChatRsKnowledgeBase<'_>,
+    ) -> Result<ChatRsKnowledgeBase, Error> {
+        diesel::insert_into(knowledge_bases::table)
+            .values(knowledge_base)
+            .returning(ChatRsKnowledgeBase::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn update(
+        &mut self,
+        user_id: &Uuid,
+        knowledge_base_id: &Uuid,
+        data: UpdateChatRsKnowledgeBase<'_>,
+    ) -> Result<ChatRsKnowledgeBase, Error> {
+        diesel::update(knowledge_bases::table)
+            .filter(knowledge_bases::user_id.eq(user_id))
+            .filter(knowledge_bases::id.eq(knowledge_base_id))
+            .set(data)
+            .returning(ChatRsKnowledgeBase::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    /// Delete the knowledge base, and all its documents and chunks
+    pub async fn delete(
+        &mut self,
+        user_id: &Uuid,
+        knowledge_base_id: &Uuid,
+    ) -> Result<Uuid, Error> {
+        diesel::delete(knowledge_bases::table)
+            .filter(knowledge_bases::user_id.eq(user_id))
+            .filter(knowledge_bases::id.eq(knowledge_base_id))
+            .returning(knowledge_bases::id)
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn list_documents(
+        &mut self,
+        knowledge_base_id: &Uuid,
+    ) -> Result<Vec<ChatRsKnowledgeDocument>, Error> {
+        knowledge_documents::table
+            .filter(knowledge_documents::knowledge_base_id.eq(knowledge_base_id))
+            .select(ChatRsKnowledgeDocument::as_select())
+            .order_by(knowledge_documents::created_at.desc())
+            .load(self.db)
+            .await
+    }
+
+    /// Save a document and the embeddings of its chunks, in a single transaction. The
+    /// chunks are numbered in the given order.
+    pub async fn add_document(
+        &mut self,
+        document: NewChatRsKnowledgeDocument<'_>,
+        chunks: Vec<(&str, Vector)>,
+    ) -> Result<ChatRsKnowledgeDocument, Error> {
+        self.db
+            .transaction::<_, Error, _>(|conn| {
+                async move {
+                    let document: ChatRsKnowledgeDocument =
+                        diesel::insert_into(knowledge_documents::table)
+                            .values(document)
+                            .returning(ChatRsKnowledgeDocument::as_returning())
+                            .get_result(conn)
+                            .await?;
+                    let new_chunks: Vec<NewChatRsKnowledgeChunk> = chunks
+                        .into_iter()
+                        .zip(0..)
+                        .map(
+                            |((content, embedding), chunk_index)| NewChatRsKnowledgeChunk {
+                                document_id: &document.id,
+                                knowledge_base_id: &document.knowledge_base_id,
+                                chunk_index,
+                                content,
+                                embedding,
+                            },
+                        )
+                        .collect();
+                    diesel::insert_into(knowledge_chunks::table)
+                        .values(&new_chunks)
+                        .execute(conn)
+                        .await?;
+
+                    Ok(document)
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    /// Delete a document and its chunks
+    pub async fn delete_document(
+        &mut self,
+        knowledge_base_id: &Uuid,
+        document_id: &Uuid,
+    ) -> Result<Uuid, Error> {
+        diesel::delete(knowledge_documents::table)
+            .filter(knowledge_documents::knowledge_base_id.eq(knowledge_base_id))
+            .filter(knowledge_documents::id.eq(document_id))
+            .returning(knowledge_documents::id)
+            .get_result(self.db)
+            .await
+    }
+
+    /// Find the chunks of the knowledge base closest to the query embedding (by cosine
+    /// distance)
+    pub async fn search(
+        &mut self,
+        knowledge_base_id: &Uuid,
+        embedding: &Vector,
+        limit: i64,
+    ) -> Result<Vec<ChatRsKnowledgeSearchResult>, Error> {
+        knowledge_chunks::table
+            .inner_join(knowledge_documents::table)
+            .filter(knowledge_chunks::knowledge_base_id.eq(knowledge_base_id))
+            .select((
+                knowledge_documents::name,
+                knowledge_chunks::chunk_index,
+                knowledge_chunks::content,
+                knowledge_chunks::embedding.cosine_distance(embedding),
+            ))
+            .order_by(knowledge_chunks::embedding.cosine_distance(embedding))
+            .limit(limit)
+            .load(self.db)
+            .await
+    }
+}
@@ -0,0 +1,88 @@
+use diesel::prelude::*;
+use diesel::result::Error;
+use diesel_async::RunQueryDsl;
+use pgvector::{Vector, VectorExpressionMethods};
+use uuid::Uuid;
+
+use crate::db::{
+    models::{ChatRsMemory, NewChatRsMemory},
+    schema::memories,
+    DbConnection,
+};
+
+pub struct MemoryDbService<'a> {
+    pub db: &'a mut DbConnection,
+}
+
+impl<'a> MemoryDbService<'a> {
+    pub fn new(db: &'a mut DbConnection) -> Self {
+        MemoryDbService { db }
+    }
+
+    /// Get the user's memories, newest first
+    pub async fn find_by_user_id(
+        &mut self,
+        user_id: &Uuid,
+        limit: i64,
+    ) -> Result<Vec<ChatRsMemory>, Error> {
+        memories::table
+            .filter(memories::user_id.eq(user_id))
+            .select(ChatRsMemory::as_select())
+            .order_by((memories::created_at.desc(), memories::id.desc()))
+            .limit(limit)
+            .load(self.db)
+            .await
+    }
+
+    pub async fn count(&mut self, user_id: &Uuid) -> Result<i64, Error> {
+        memories::table
+            .filter(memories::user_id.eq(user_id))
+            .count()
+            .get_result(self.db)
+            .await
+    }
+
+    /// Find the user's memories embedded with the given model that are closest to the
+    /// query embedding (by cosine distance)
+    pub async fn search(
+        &mut self,
+        user_id: &Uuid,
+        embedding_model: &str,
+        embedding: &Vector,
+        limit: i64,
+    ) -> Result<Vec<ChatRsMemory>, Error> {
+        memories::table
+            .filter(memories::user_id.eq(user_id))
+            .filter(memories::embedding_model.eq(embedding_model))
+            .select(ChatRsMemory::as_select())
+            .order_by(memories::embedding.cosine_distance(embedding))
+            .limit(limit)
+            .load(self.db)
+            .await
+    }
+
+    pub async fn create(&mut self, memory: NewChatRsMemory<'_>) -> Result<ChatRsMemory, Error> {
+        diesel::insert_into(memories::table)
+            .values(memory)
+            .returning(ChatRsMemory::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn delete(&mut self, user_id: &Uuid, memory_id: &Uuid) -> Result<Uuid, Error> {
+        diesel::delete(memories::table)
+            .filter(memories::user_id.eq(user_id))
+            .filter(memories::id.eq(memory_id))
+            .returning(memories::id)
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn delete_by_user(&mut self, user_id: &Uuid) -> Result<Vec<Uuid>, Error> {
+        diesel::delete(memories::table)
+            .filter(memories::user_id.eq(user_id))
+            .returning(memories::id)
+            .get_results(self.db)
+            .await
+    }
+}
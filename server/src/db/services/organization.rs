@@ -0,0 +1,219 @@
+use diesel::prelude::*;
+use diesel::result::Error;
+use diesel::upsert::excluded;
+use diesel_async::{scoped_futures::ScopedFutureExt, AsyncConnection, RunQueryDsl};
+use uuid::Uuid;
+
+use crate::db::{
+    models::{
+        ChatRsOrganization, ChatRsOrganizationMemberInfo, ChatRsOrganizationRole,
+        NewChatRsOrganization, NewChatRsOrganizationMember, UpdateChatRsOrganization,
+    },
+    schema::{
+        external_api_tools, mcp_tools, organization_members, organizations, personas, providers,
+        users,
+    },
+    DbConnection,
+};
+
+/// Subquery of the IDs of the organizations that the user is a member of, to also find the
+/// providers, tools, and personas shared with the user
+#[diesel::dsl::auto_type(no_type_alias)]
+pub fn user_organization_ids<'a>(user_id: &'a Uuid) -> _ {
+    organization_members::table
+        .filter(organization_members::user_id.eq(user_id))
+        .select(organization_members::organization_id.nullable())
+}
+
+pub struct OrganizationDbService<'a> {
+    pub db: &'a mut DbConnection,
+}
+
+impl<'a> OrganizationDbService<'a> {
+    pub fn new(db: &'a mut DbConnection) -> Self {
+        OrganizationDbService { db }
+    }
+
+    /// List the organizations that the user is a member of, with the user's role
+    pub async fn find_by_user(
+        &mut self,
+        user_id: &Uuid,
+    ) -> Result<Vec<(ChatRsOrganization, String)>, Error> {
+        organizations::table
+            .inner_join(organization_members::table)
+            .filter(organization_members::user_id.eq(user_id))
+            .select((ChatRsOrganization::as_select(), organization_members::role))
+            .order_by(organizations::name.asc())
+            .load(self.db)
+            .await
+    }
+
+    /// Get the user's role in the organization (`None` if the user isn't a member)
+    pub async fn find_role(
+        &mut self,
+        organization_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<Option<ChatRsOrganizationRole>, Error> {
+        let role: Option<String> = organization_members::table
+            .filter(organization_members::organization_id.eq(organization_id))
+            .filter(organization_members::user_id.eq(user_id))
+            .select(organization_members::role)
+            .first(self.db)
+            .await
+            .optional()?;
+
+        Ok(role.as_deref().map(ChatRsOrganizationRole::from))
+    }
+
+    /// Create the organization, with the user as its owner
+    pub async fn create(
+        &mut self,
+        user_id: &Uuid,
+        organization: NewChatRsOrganization<'_>,
+    ) -> Result<ChatRsOrganization, Error> {
+        self.db
+            .transaction::<_, Error, _>(|conn| {
+                async move {
+                    let organization: ChatRsOrganization =
+                        diesel::insert_into(organizations::table)
+                            .values(organization)
+                            .returning(ChatRsOrganization::as_returning())
+                            .get_result(conn)
+                            .await?;
+                    diesel::insert_into(organization_members::table)
+                        .values(NewChatRsOrganizationMember {
+                            organization_id: &organization.id,
+                            user_id,
+                            role: (&ChatRsOrganizationRole::Owner).into(),
+                        })
+                        .execute(conn)
+                        .await?;
+
+                    Ok(organization)
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    pub async fn update(
+        &mut self,
+        organization_id: &Uuid,
+        data: UpdateChatRsOrganization<'_>,
+    ) -> Result<ChatRsOrganization, Error> {
+        diesel::update(organizations::table.find(organization_id))
+            .set(data)
+            .returning(ChatRsOrganization::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    /// Delete the organization. Its shared resources are kept by their owners.
+    pub async fn delete(&mut self, organization_id: &Uuid) -> Result<Uuid, Error> {
+        diesel::delete(organizations::table.find(organization_id))
+            .returning(organizations::id)
+            .get_result(self.db)
+            .await
+    }
+
+    /// List the members of an organization, with their names
+    pub async fn find_members(
+        &mut self,
+        organization_id: &Uuid,
+    ) -> Result<Vec<ChatRsOrganizationMemberInfo>, Error> {
+        organization_members::table
+            .inner_join(users::table)
+            .filter(organization_members::organization_id.eq(organization_id))
+            .select((
+                organization_members::user_id,
+                users::name,
+                users::avatar_url,
+                organization_members::role,
+                organization_members::created_at,
+            ))
+            .order_by(organization_members::created_at.asc())
+            .load(self.db)
+            .await
+    }
+
+    /// Add a member to the organization, or update their role if they're already a member
+    pub async fn upsert_member(
+        &mut self,
+        member: NewChatRsOrganizationMember<'_>,
+    ) -> Result<(), Error> {
+        diesel::insert_into(organization_members::table)
+            .values(member)
+            .on_conflict((
+                organization_members::organization_id,
+                organization_members::user_id,
+            ))
+            .do_update()
+            .set(organization_members::role.eq(excluded(organization_members::role)))
+            .execute(self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_member_role(
+        &mut self,
+        organization_id: &Uuid,
+        user_id: &Uuid,
+        role: &str,
+    ) -> Result<Uuid, Error> {
+        diesel::update(organization_members::table)
+            .filter(organization_members::organization_id.eq(organization_id))
+            .filter(organization_members::user_id.eq(user_id))
+            .set(organization_members::role.eq(role))
+            .returning(organization_members::user_id)
+            .get_result(self.db)
+            .await
+    }
+
+    /// Remove the member from the organization, and stop sharing their resources with it
+    pub async fn delete_member(
+        &mut self,
+        organization_id: &Uuid,
+        user_id: &Uuid,
+    ) -> Result<Uuid, Error> {
+        self.db
+            .transaction::<_, Error, _>(|conn| {
+                async move {
+                    let member_id = diesel::delete(organization_members::table)
+                        .filter(organization_members::organization_id.eq(organization_id))
+                        .filter(organization_members::user_id.eq(user_id))
+                        .returning(organization_members::user_id)
+                        .get_result(conn)
+                        .await?;
+                    diesel::update(providers::table)
+                        .filter(providers::organization_id.eq(organization_id))
+                        .filter(providers::user_id.eq(user_id))
+                        .set(providers::organization_id.eq(None::<Uuid>))
+                        .execute(conn)
+                        .await?;
+                    diesel::update(personas::table)
+                        .filter(personas::organization_id.eq(organization_id))
+                        .filter(personas::user_id.eq(user_id))
+                        .set(personas::organization_id.eq(None::<Uuid>))
+                        .execute(conn)
+                        .await?;
+                    diesel::update(external_api_tools::table)
+                        .filter(external_api_tools::organization_id.eq(organization_id))
+                        .filter(external_api_tools::user_id.eq(user_id))
+                        .set(external_api_tools::organization_id.eq(None::<Uuid>))
+                        .execute(conn)
+                        .await?;
+                    diesel::update(mcp_tools::table)
+                        .filter(mcp_tools::organization_id.eq(organization_id))
+                        .filter(mcp_tools::user_id.eq(user_id))
+                        .set(mcp_tools::organization_id.eq(None::<Uuid>))
+                        .execute(conn)
+                        .await?;
+
+                    Ok(member_id)
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+}
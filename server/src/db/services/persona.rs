@@ -0,0 +1,116 @@
+use diesel::prelude::*;
+use diesel::result::Error;
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::db::{
+    models::{ChatRsPersona, NewChatRsPersona, UpdateChatRsPersona},
+    schema::personas,
+    services::user_organization_ids,
+    DbConnection,
+};
+
+pub struct PersonaDbService<'a> {
+    pub db: &'a mut DbConnection,
+}
+
+impl<'a> PersonaDbService<'a> {
+    pub fn new(db: &'a mut DbConnection) -> Self {
+        PersonaDbService { db }
+    }
+
+    /// Find a persona that's owned by the user or shared with one of the user's organizations
+    pub async fn find_by_id(
+        &mut self,
+        user_id: &Uuid,
+        persona_id: &Uuid,
+    ) -> Result<ChatRsPersona, Error> {
+        personas::table
+            .filter(
+                personas::user_id
+                    .eq(user_id)
+                    .or(personas::organization_id.eq_any(user_organization_ids(user_id))),
+            )
+            .filter(personas::id.eq(persona_id))
+            .select(ChatRsPersona::as_select())
+            .first(self.db)
+            .await
+    }
+
+    /// Find the user's personas, and the personas shared with the user's organizations
+    pub async fn find_by_user_id(&mut self, user_id: &Uuid) -> Result<Vec<ChatRsPersona>, Error> {
+        personas::table
+            .filter(
+                personas::user_id
+                    .eq(user_id)
+                    .or(personas::organization_id.eq_any(user_organization_ids(user_id))),
+            )
+            .select(ChatRsPersona::as_select())
+            .order_by(personas::name.asc())
+            .load(self.db)
+            .await
+    }
+
+    pub async fn create(&mut self, persona: NewChatRsPersona<'_>) -> Result<ChatRsPersona, Error> {
+        diesel::insert_into(personas::table)
+            .values(persona)
+            .returning(ChatRsPersona::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn update(
+        &mut self,
+        user_id: &Uuid,
+        persona_id: &Uuid,
+        data: UpdateChatRsPersona<'_>,
+    ) -> Result<ChatRsPersona, Error> {
+        diesel::update(personas::table)
+            .filter(personas::user_id.eq(user_id))
+            .filter(personas::id.eq(persona_id))
+            .set(data)
+            .returning(ChatRsPersona::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    /// Share the user's persona with an organization
+    pub async fn share_with_organization(
+        &mut self,
+        user_id: &Uuid,
+        persona_id: &Uuid,
+        organization_id: &Uuid,
+    ) -> Result<ChatRsPersona, Error> {
+        diesel::update(personas::table)
+            .filter(personas::user_id.eq(user_id))
+            .filter(personas::id.eq(persona_id))
+            .set(personas::organization_id.eq(organization_id))
+            .returning(ChatRsPersona::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    /// Stop sharing the persona with the organization
+    pub async fn unshare_from_organization(
+        &mut self,
+        organization_id: &Uuid,
+        persona_id: &Uuid,
+    ) -> Result<ChatRsPersona, Error> {
+        diesel::update(personas::table)
+            .filter(personas::organization_id.eq(organization_id))
+            .filter(personas::id.eq(persona_id))
+            .set(personas::organization_id.eq(None::<Uuid>))
+            .returning(ChatRsPersona::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn delete(&mut self, user_id: &Uuid, persona_id: &Uuid) -> Result<Uuid, Error> {
+        diesel::delete(personas::table)
+            .filter(personas::user_id.eq(user_id))
+            .filter(personas::id.eq(persona_id))
+            .returning(personas::id)
+            .get_result(self.db)
+            .await
+    }
+}
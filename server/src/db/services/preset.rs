@@ -0,0 +1,88 @@
+use diesel::prelude::*;
+use diesel::result::Error;
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::db::{
+    models::{ChatRsProviderPreset, NewChatRsProviderPreset, UpdateChatRsProviderPreset},
+    schema::provider_presets,
+    DbConnection,
+};
+
+pub struct PresetDbService<'a> {
+    pub db: &'a mut DbConnection,
+}
+
+impl<'a> PresetDbService<'a> {
+    pub fn new(db: &'a mut DbConnection) -> Self {
+        PresetDbService { db }
+    }
+
+    pub async fn find_by_id(
+        &mut self,
+        user_id: &Uuid,
+        preset_id: &Uuid,
+    ) -> Result<ChatRsProviderPreset, Error> {
+        provider_presets::table
+            .filter(provider_presets::user_id.eq(user_id))
+            .filter(provider_presets::id.eq(preset_id))
+            .select(ChatRsProviderPreset::as_select())
+            .first(self.db)
+            .await
+    }
+
+    pub async fn find_by_user_id(
+        &mut self,
+        user_id: &Uuid,
+    ) -> Result<Vec<ChatRsProviderPreset>, Error> {
+        provider_presets::table
+            .filter(provider_presets::user_id.eq(user_id))
+            .select(ChatRsProviderPreset::as_select())
+            .order_by(provider_presets::name.asc())
+            .load(self.db)
+            .await
+    }
+
+    pub async fn create(
+        &mut self,
+        preset: NewChatRsProviderPreset<'_>,
+    ) -> Result<ChatRsProviderPreset, Error> {
+        diesel::insert_into(provider_presets::table)
+            .values(preset)
+            .returning(ChatRsProviderPreset::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn update(
+        &mut self,
+        user_id: &Uuid,
+        preset_id: &Uuid,
+        data: UpdateChatRsProviderPreset<'_>,
+    ) -> Result<ChatRsProviderPreset, Error> {
+        diesel::update(provider_presets::table)
+            .filter(provider_presets::user_id.eq(user_id))
+            .filter(provider_presets::id.eq(preset_id))
+            .set(data)
+            .returning(ChatRsProviderPreset::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn delete(&mut self, user_id: &Uuid, preset_id: &Uuid) -> Result<Uuid, Error> {
+        diesel::delete(provider_presets::table)
+            .filter(provider_presets::user_id.eq(user_id))
+            .filter(provider_presets::id.eq(preset_id))
+            .returning(provider_presets::id)
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn delete_by_user(&mut self, user_id: &Uuid) -> Result<Vec<Uuid>, Error> {
+        diesel::delete(provider_presets::table)
+            .filter(provider_presets::user_id.eq(user_id))
+            .returning(provider_presets::id)
+            .get_results(self.db)
+            .await
+    }
+}
@@ -0,0 +1,74 @@
+use diesel::prelude::*;
+use diesel::result::Error;
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::db::{
+    models::{ChatRsProject, NewChatRsProject, UpdateChatRsProject},
+    schema::projects,
+    DbConnection,
+};
+
+pub struct ProjectDbService<'a> {
+    pub db: &'a mut DbConnection,
+}
+
+impl<'a> ProjectDbService<'a> {
+    pub fn new(db: &'a mut DbConnection) -> Self {
+        ProjectDbService { db }
+    }
+
+    pub async fn find_by_id(
+        &mut self,
+        user_id: &Uuid,
+        project_id: &Uuid,
+    ) -> Result<ChatRsProject, Error> {
+        projects::table
+            .filter(projects::user_id.eq(user_id))
+            .filter(projects::id.eq(project_id))
+            .select(ChatRsProject::as_select())
+            .first(self.db)
+            .await
+    }
+
+    pub async fn find_by_user_id(&mut self, user_id: &Uuid) -> Result<Vec<ChatRsProject>, Error> {
+        projects::table
+            .filter(projects::user_id.eq(user_id))
+            .select(ChatRsProject::as_select())
+            .order_by(projects::name.asc())
+            .load(self.db)
+            .await
+    }
+
+    pub async fn create(&mut self, project: NewChatRsProject<'_>) -> Result<ChatRsProject, Error> {
+        diesel::insert_into(projects::table)
+            .values(project)
+            .returning(ChatRsProject::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn update(
+        &mut self,
+        user_id: &Uuid,
+        project_id: &Uuid,
+        data: UpdateChatRsProject<'_>,
+    ) -> Result<ChatRsProject, Error> {
+        diesel::update(projects::table)
+            .filter(projects::user_id.eq(user_id))
+            .filter(projects::id.eq(project_id))
+            .set(data)
+            .returning(ChatRsProject::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn delete(&mut self, user_id: &Uuid, project_id: &Uuid) -> Result<Uuid, Error> {
+        diesel::delete(projects::table)
+            .filter(projects::user_id.eq(user_id))
+            .filter(projects::id.eq(project_id))
+            .returning(projects::id)
+            .get_result(self.db)
+            .await
+    }
+}
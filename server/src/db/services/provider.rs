@@ -1,10 +1,15 @@
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
-use diesel_async::RunQueryDsl;
+use diesel_async::{scoped_futures::ScopedFutureExt, AsyncConnection, RunQueryDsl};
 use uuid::Uuid;
 
 use crate::db::{
-    models::{ChatRsProvider, ChatRsSecret, NewChatRsProvider, UpdateChatRsProvider},
+    models::{
+        ChatRsProvider, ChatRsSecret, NewChatRsProvider, NewChatRsSecret, UpdateChatRsProvider,
+    },
+    pagination::{ListQuery, ListSort},
     schema::{providers, secrets},
+    services::user_organization_ids,
     DbConnection,
 };
 
@@ -34,17 +39,143 @@ impl<'a> ProviderDbService<'a> {
             .await
     }
 
+    /// Get a provider that's owned by the user or shared with one of the user's organizations
+    pub async fn get_accessible_by_id(
+        &mut self,
+        user_id: &Uuid,
+        provider_id: i32,
+    ) -> Result<(ChatRsProvider, Option<ChatRsSecret>), diesel::result::Error> {
+        providers::table
+            .left_join(secrets::table)
+            .filter(
+                providers::user_id
+                    .eq(user_id)
+                    .or(providers::organization_id.eq_any(user_organization_ids(user_id))),
+            )
+            .filter(providers::id.eq(provider_id))
+            .select((
+                ChatRsProvider::as_select(),
+                Option::<ChatRsSecret>::as_select(),
+            ))
+            .first(self.db)
+            .await
+    }
+
+    /// Get a provider along with its primary and secondary API key secrets. The provider can
+    /// be shared with one of the user's organizations.
+    pub async fn get_by_id_with_secrets(
+        &mut self,
+        user_id: &Uuid,
+        provider_id: i32,
+    ) -> Result<(ChatRsProvider, Option<ChatRsSecret>, Option<ChatRsSecret>), diesel::result::Error>
+    {
+        let (provider, secret) = self.get_accessible_by_id(user_id, provider_id).await?;
+        let secondary_secret = match provider.secondary_api_key_id {
+            Some(secret_id) => secrets::table
+                .filter(secrets::user_id.eq(provider.user_id))
+                .filter(secrets::id.eq(secret_id))
+                .select(ChatRsSecret::as_select())
+                .first(self.db)
+                .await
+                .optional()?,
+            None => None,
+        };
+
+        Ok((provider, secret, secondary_secret))
+    }
+
+    /// Get the providers of all users that have extra headers (for re-encrypting the headers
+    /// with a new secret key)
+    pub async fn find_with_extra_headers(
+        &mut self,
+    ) -> Result<Vec<ChatRsProvider>, diesel::result::Error> {
+        providers::table
+            .filter(providers::extra_headers.is_not_null())
+            .select(ChatRsProvider::as_select())
+            .load(self.db)
+            .await
+    }
+
+    /// Find the user's providers, and the providers shared with the user's organizations
     pub async fn find_by_user_id(
         &mut self,
         user_id: &Uuid,
     ) -> Result<Vec<ChatRsProvider>, diesel::result::Error> {
         providers::table
-            .filter(providers::user_id.eq(user_id))
+            .filter(
+                providers::user_id
+                    .eq(user_id)
+                    .or(providers::organization_id.eq_any(user_organization_ids(user_id))),
+            )
             .select(ChatRsProvider::as_select())
             .load(self.db)
             .await
     }
 
+    /// List the user's providers, and the providers shared with the user's organizations
+    pub async fn list(
+        &mut self,
+        user_id: &Uuid,
+        params: &ListQuery,
+    ) -> Result<Vec<ChatRsProvider>, diesel::result::Error> {
+        let mut query = providers::table
+            .filter(
+                providers::user_id
+                    .eq(user_id)
+                    .or(providers::organization_id.eq_any(user_organization_ids(user_id))),
+            )
+            .select(ChatRsProvider::as_select())
+            .into_boxed();
+        if let Some(pattern) = params.filter_pattern() {
+            query = query.filter(providers::name.ilike(pattern));
+        }
+        if let Some(cursor) = params.cursor::<i32>()? {
+            let cursor_time: DateTime<Utc> = providers::table
+                .filter(providers::id.eq(cursor))
+                .select(providers::created_at)
+                .first(self.db)
+                .await?;
+            query = match params.sort() {
+                ListSort::Newest => query.filter(
+                    providers::created_at
+                        .lt(cursor_time)
+                        .or(providers::created_at
+                            .eq(cursor_time)
+                            .and(providers::id.lt(cursor))),
+                ),
+                ListSort::Oldest => query.filter(
+                    providers::created_at
+                        .gt(cursor_time)
+                        .or(providers::created_at
+                            .eq(cursor_time)
+                            .and(providers::id.gt(cursor))),
+                ),
+            };
+        }
+        query = match params.sort() {
+            ListSort::Newest => {
+                query.order_by((providers::created_at.desc(), providers::id.desc()))
+            }
+            ListSort::Oldest => query.order_by((providers::created_at.asc(), providers::id.asc())),
+        };
+
+        query.limit(params.limit()).load(self.db).await
+    }
+
+    /// Get all providers of all users, along with their API key secrets
+    pub async fn find_all(
+        &mut self,
+    ) -> Result<Vec<(ChatRsProvider, Option<ChatRsSecret>)>, diesel::result::Error> {
+        providers::table
+            .left_join(secrets::table)
+            .select((
+                ChatRsProvider::as_select(),
+                Option::<ChatRsSecret>::as_select(),
+            ))
+            .load(self.db)
+            .await
+    }
+
     pub async fn create(
         &mut self,
         provider: NewChatRsProvider<'_>,
@@ -71,6 +202,83 @@ impl<'a> ProviderDbService<'a> {
             .await
     }
 
+    /// Share the user's provider with an organization
+    pub async fn share_with_organization(
+        &mut self,
+        user_id: &Uuid,
+        provider_id: i32,
+        organization_id: &Uuid,
+    ) -> Result<ChatRsProvider, diesel::result::Error> {
+        diesel::update(providers::table)
+            .filter(providers::user_id.eq(user_id))
+            .filter(providers::id.eq(provider_id))
+            .set(providers::organization_id.eq(organization_id))
+            .returning(ChatRsProvider::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    /// Stop sharing the provider with the organization
+    pub async fn unshare_from_organization(
+        &mut self,
+        organization_id: &Uuid,
+        provider_id: i32,
+    ) -> Result<ChatRsProvider, diesel::result::Error> {
+        diesel::update(providers::table)
+            .filter(providers::organization_id.eq(organization_id))
+            .filter(providers::id.eq(provider_id))
+            .set(providers::organization_id.eq(None::<Uuid>))
+            .returning(ChatRsProvider::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    /// Atomically rotate the provider's API key. The new secret becomes the primary key,
+    /// the current primary key becomes the secondary key, and the previous secondary key
+    /// is deleted.
+    pub async fn rotate_api_key(
+        &mut self,
+        user_id: &Uuid,
+        provider_id: i32,
+        new_secret: NewChatRsSecret<'_>,
+    ) -> Result<ChatRsProvider, diesel::result::Error> {
+        self.db
+            .transaction::<_, diesel::result::Error, _>(|conn| {
+                async move {
+                    let provider = providers::table
+                        .filter(providers::user_id.eq(user_id))
+                        .filter(providers::id.eq(provider_id))
+                        .select(ChatRsProvider::as_select())
+                        .for_update()
+                        .first(conn)
+                        .await?;
+                    let new_secret_id: Uuid = diesel::insert_into(secrets::table)
+                        .values(new_secret)
+                        .returning(secrets::id)
+                        .get_result(conn)
+                        .await?;
+                    let updated = diesel::update(providers::table.find(provider.id))
+                        .set((
+                            providers::api_key_id.eq(Some(new_secret_id)),
+                            providers::secondary_api_key_id.eq(provider.api_key_id),
+                        ))
+                        .returning(ChatRsProvider::as_returning())
+                        .get_result(conn)
+                        .await?;
+                    if let Some(old_secret_id) = provider.secondary_api_key_id {
+                        diesel::delete(secrets::table.find(old_secret_id))
+                            .filter(secrets::user_id.eq(user_id))
+                            .execute(conn)
+                            .await?;
+                    }
+
+                    Ok(updated)
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
     pub async fn delete(
         &mut self,
         user_id: &Uuid,
@@ -0,0 +1,140 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::result::Error;
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::db::{
+    models::{ChatRsRetentionSettings, NewChatRsRetentionSettings},
+    schema::{chat_sessions, retention_settings, tool_runs},
+    DbConnection,
+};
+
+pub struct RetentionDbService<'a> {
+    pub db: &'a mut DbConnection,
+}
+
+impl<'a> RetentionDbService<'a> {
+    pub fn new(db: &'a mut DbConnection) -> Self {
+        RetentionDbService { db }
+    }
+
+    pub async fn get_settings(
+        &mut self,
+        user_id: &Uuid,
+    ) -> Result<Option<ChatRsRetentionSettings>, Error> {
+        retention_settings::table
+            .filter(retention_settings::user_id.eq(user_id))
+            .select(ChatRsRetentionSettings::as_select())
+            .first(self.db)
+            .await
+            .optional()
+    }
+
+    /// Get the settings of all users who have set a retention period
+    pub async fn list_settings(&mut self) -> Result<Vec<ChatRsRetentionSettings>, Error> {
+        retention_settings::table
+            .filter(
+                retention_settings::session_retention_days
+                    .is_not_null()
+                    .or(retention_settings::tool_run_retention_days.is_not_null()),
+            )
+            .select(ChatRsRetentionSettings::as_select())
+            .load(self.db)
+            .await
+    }
+
+    pub async fn upsert_settings(
+        &mut self,
+        settings: NewChatRsRetentionSettings<'_>,
+    ) -> Result<ChatRsRetentionSettings, Error> {
+        diesel::insert_into(retention_settings::table)
+            .values(&settings)
+            .on_conflict(retention_settings::user_id)
+            .do_update()
+            .set(&settings)
+            .returning(ChatRsRetentionSettings::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn delete_settings(&mut self, user_id: &Uuid) -> Result<(), Error> {
+        diesel::delete(retention_settings::table)
+            .filter(retention_settings::user_id.eq(user_id))
+            .execute(self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// Move the sessions that haven't been updated since the given time to the trash: the
+    /// sessions of the given user, or if `user_id` is `None`, the sessions of all users
+    /// without their own session retention period. Returns the number of sessions. With
+    /// `dry_run`, the sessions are only counted.
+    pub async fn trash_sessions(
+        &mut self,
+        user_id: Option<&Uuid>,
+        updated_before: DateTime<Utc>,
+        dry_run: bool,
+    ) -> Result<usize, Error> {
+        let mut query = chat_sessions::table
+            .filter(chat_sessions::deleted_at.is_null())
+            .filter(chat_sessions::updated_at.lt(updated_before))
+            .select(chat_sessions::id)
+            .into_boxed();
+        query = match user_id {
+            Some(user_id) => query.filter(chat_sessions::user_id.eq(user_id)),
+            None => query.filter(
+                chat_sessions::user_id.ne_all(
+                    retention_settings::table
+                        .filter(retention_settings::session_retention_days.is_not_null())
+                        .select(retention_settings::user_id),
+                ),
+            ),
+        };
+        let ids: Vec<Uuid> = query.load(self.db).await?;
+        if dry_run || ids.is_empty() {
+            return Ok(ids.len());
+        }
+
+        diesel::update(chat_sessions::table)
+            .filter(chat_sessions::id.eq_any(&ids))
+            .set(chat_sessions::deleted_at.eq(Utc::now()))
+            .execute(self.db)
+            .await
+    }
+
+    /// Delete the tool runs created before the given time: the tool runs of the given user,
+    /// or if `user_id` is `None`, the tool runs of all users without their own tool run
+    /// retention period. Returns the number of tool runs. With `dry_run`, the tool runs are
+    /// only counted.
+    pub async fn delete_tool_runs(
+        &mut self,
+        user_id: Option<&Uuid>,
+        created_before: DateTime<Utc>,
+        dry_run: bool,
+    ) -> Result<usize, Error> {
+        let mut query = tool_runs::table
+            .filter(tool_runs::created_at.lt(created_before))
+            .select(tool_runs::id)
+            .into_boxed();
+        query = match user_id {
+            Some(user_id) => query.filter(tool_runs::user_id.eq(user_id)),
+            None => query.filter(
+                tool_runs::user_id.ne_all(
+                    retention_settings::table
+                        .filter(retention_settings::tool_run_retention_days.is_not_null())
+                        .select(retention_settings::user_id),
+                ),
+            ),
+        };
+        let ids: Vec<Uuid> = query.load(self.db).await?;
+        if dry_run || ids.is_empty() {
+            return Ok(ids.len());
+        }
+
+        diesel::delete(tool_runs::table)
+            .filter(tool_runs::id.eq_any(&ids))
+            .execute(self.db)
+            .await
+    }
+}
@@ -0,0 +1,105 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::result::Error;
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::db::{
+    models::{ChatRsScheduledPrompt, NewChatRsScheduledPrompt, UpdateChatRsScheduledPrompt},
+    schema::scheduled_prompts,
+    DbConnection,
+};
+
+pub struct ScheduleDbService<'a> {
+    pub db: &'a mut DbConnection,
+}
+
+impl<'a> ScheduleDbService<'a> {
+    pub fn new(db: &'a mut DbConnection) -> Self {
+        ScheduleDbService { db }
+    }
+
+    pub async fn find_by_id(
+        &mut self,
+        user_id: &Uuid,
+        schedule_id: &Uuid,
+    ) -> Result<ChatRsScheduledPrompt, Error> {
+        scheduled_prompts::table
+            .filter(scheduled_prompts::user_id.eq(user_id))
+            .filter(scheduled_prompts::id.eq(schedule_id))
+            .select(ChatRsScheduledPrompt::as_select())
+            .first(self.db)
+            .await
+    }
+
+    pub async fn find_by_user_id(
+        &mut self,
+        user_id: &Uuid,
+    ) -> Result<Vec<ChatRsScheduledPrompt>, Error> {
+        scheduled_prompts::table
+            .filter(scheduled_prompts::user_id.eq(user_id))
+            .select(ChatRsScheduledPrompt::as_select())
+            .order_by(scheduled_prompts::name.asc())
+            .load(self.db)
+            .await
+    }
+
+    pub async fn count_by_user_id(&mut self, user_id: &Uuid) -> Result<i64, Error> {
+        scheduled_prompts::table
+            .filter(scheduled_prompts::user_id.eq(user_id))
+            .count()
+            .get_result(self.db)
+            .await
+    }
+
+    /// Find the enabled scheduled prompts of all users that are due to run
+    pub async fn find_due(
+        &mut self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<ChatRsScheduledPrompt>, Error> {
+        scheduled_prompts::table
+            .filter(scheduled_prompts::enabled.eq(true))
+            .filter(scheduled_prompts::next_run_at.le(now))
+            .select(ChatRsScheduledPrompt::as_select())
+            .order_by(scheduled_prompts::next_run_at.asc())
+            .limit(limit)
+            .load(self.db)
+            .await
+    }
+
+    pub async fn create(
+        &mut self,
+        schedule: NewChatRsScheduledPrompt<'_>,
+    ) -> Result<ChatRsScheduledPrompt, Error> {
+        diesel::insert_into(scheduled_prompts::table)
+            .values(schedule)
+            .returning(ChatRsScheduledPrompt::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn update(
+        &mut self,
+        user_id: &Uuid,
+        schedule_id: &Uuid,
+        data: UpdateChatRsScheduledPrompt<'_>,
+    ) -> Result<ChatRsScheduledPrompt, Error> {
+        diesel::update(scheduled_prompts::table)
+            .filter(scheduled_prompts::user_id.eq(user_id))
+            .filter(scheduled_prompts::id.eq(schedule_id))
+            .set(data)
+            .returning(ChatRsScheduledPrompt::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn delete(&mut self, user_id: &Uuid, schedule_id: &Uuid) -> Result<Uuid, Error> {
+        diesel::delete(scheduled_prompts::table)
+            .filter(scheduled_prompts::user_id.eq(user_id))
+            .filter(scheduled_prompts::id.eq(schedule_id))
+            .returning(scheduled_prompts::id)
+            .get_result(self.db)
+            .await
+    }
+}
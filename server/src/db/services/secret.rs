@@ -1,10 +1,12 @@
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
 use diesel::result::Error;
 use diesel_async::RunQueryDsl;
 use uuid::Uuid;
 
 use crate::db::{
-    models::{ChatRsSecretMeta, NewChatRsSecret, UpdateChatRsSecret},
+    models::{ChatRsSecret, ChatRsSecretMeta, NewChatRsSecret, UpdateChatRsSecret},
+    pagination::{ListQuery, ListSort},
     schema::secrets,
     DbConnection,
 };
@@ -31,6 +33,71 @@ impl<'a> SecretDbService<'a> {
         Ok(keys)
     }
 
+    /// Find one of the user's secrets, including the encrypted value
+    pub async fn find_by_id(
+        &mut self,
+        user_id: &Uuid,
+        secret_id: &Uuid,
+    ) -> Result<Option<ChatRsSecret>, Error> {
+        secrets::table
+            .filter(secrets::user_id.eq(user_id))
+            .filter(secrets::id.eq(secret_id))
+            .select(ChatRsSecret::as_select())
+            .first(self.db)
+            .await
+            .optional()
+    }
+
+    /// Get the secrets of all users, including the encrypted values (for re-encrypting them
+    /// with a new secret key)
+    pub async fn find_all(&mut self) -> Result<Vec<ChatRsSecret>, Error> {
+        secrets::table
+            .select(ChatRsSecret::as_select())
+            .load(self.db)
+            .await
+    }
+
+    /// List the user's secrets (without the encrypted values)
+    pub async fn list(
+        &mut self,
+        user_id: &Uuid,
+        params: &ListQuery,
+    ) -> Result<Vec<ChatRsSecretMeta>, Error> {
+        let mut query = secrets::table
+            .filter(secrets::user_id.eq(user_id))
+            .select(ChatRsSecretMeta::as_select())
+            .into_boxed();
+        if let Some(pattern) = params.filter_pattern() {
+            query = query.filter(secrets::name.ilike(pattern));
+        }
+        if let Some(cursor) = params.cursor::<Uuid>()? {
+            let cursor_time: DateTime<Utc> = secrets::table
+                .filter(secrets::user_id.eq(user_id))
+                .filter(secrets::id.eq(cursor))
+                .select(secrets::created_at)
+                .first(self.db)
+                .await?;
+            query = match params.sort() {
+                ListSort::Newest => query.filter(
+                    secrets::created_at.lt(cursor_time).or(secrets::created_at
+                        .eq(cursor_time)
+                        .and(secrets::id.lt(cursor))),
+                ),
+                ListSort::Oldest => query.filter(
+                    secrets::created_at.gt(cursor_time).or(secrets::created_at
+                        .eq(cursor_time)
+                        .and(secrets::id.gt(cursor))),
+                ),
+            };
+        }
+        query = match params.sort() {
+            ListSort::Newest => query.order_by((secrets::created_at.desc(), secrets::id.desc())),
+            ListSort::Oldest => query.order_by((secrets::created_at.asc(), secrets::id.asc())),
+        };
+
+        query.limit(params.limit()).load(self.db).await
+    }
+
     pub async fn create(&mut self, secret: NewChatRsSecret<'_>) -> Result<Uuid, Error> {
         let id: Uuid = diesel::insert_into(secrets::table)
             .values(secret)
@@ -0,0 +1,107 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::result::Error;
+use diesel_async::{scoped_futures::ScopedFutureExt, AsyncConnection, RunQueryDsl};
+use uuid::Uuid;
+
+use crate::db::{
+    models::{
+        ChatRsServiceAccount, ChatRsServiceAccountInfo, ChatRsUser, ChatRsUserRole,
+        NewChatRsServiceAccount, NewChatRsUser, UpdateChatRsServiceAccount,
+    },
+    schema::{service_accounts, users},
+    DbConnection,
+};
+
+pub struct ServiceAccountDbService<'a> {
+    pub db: &'a mut DbConnection,
+}
+
+impl<'a> ServiceAccountDbService<'a> {
+    pub fn new(db: &'a mut DbConnection) -> Self {
+        ServiceAccountDbService { db }
+    }
+
+    /// List the service accounts of the server, with the names of their users
+    pub async fn list(&mut self) -> Result<Vec<ChatRsServiceAccountInfo>, Error> {
+        let accounts: Vec<(String, Option<DateTime<Utc>>, ChatRsServiceAccount)> =
+            service_accounts::table
+                .inner_join(users::table)
+                .select((
+                    users::name,
+                    users::disabled_at,
+                    ChatRsServiceAccount::as_select(),
+                ))
+                .order_by(service_accounts::created_at.asc())
+                .load(self.db)
+                .await?;
+
+        Ok(accounts
+            .into_iter()
+            .map(|(name, disabled_at, account)| ChatRsServiceAccountInfo {
+                name,
+                disabled_at,
+                account,
+            })
+            .collect())
+    }
+
+    /// Get the service account of the user (`None` if the user isn't a service account)
+    pub async fn find(&mut self, user_id: &Uuid) -> Result<Option<ChatRsServiceAccount>, Error> {
+        service_accounts::table
+            .find(user_id)
+            .select(ChatRsServiceAccount::as_select())
+            .first(self.db)
+            .await
+            .optional()
+    }
+
+    /// Whether the user is a service account
+    pub async fn is_service_account(&mut self, user_id: &Uuid) -> Result<bool, Error> {
+        diesel::select(diesel::dsl::exists(service_accounts::table.find(user_id)))
+            .get_result(self.db)
+            .await
+    }
+
+    /// Create the user of the service account (a member without any login method), and the
+    /// service account
+    pub async fn create(
+        &mut self,
+        name: &str,
+        account: NewChatRsServiceAccount<'_>,
+    ) -> Result<ChatRsServiceAccount, Error> {
+        self.db
+            .transaction::<_, Error, _>(|conn| {
+                async move {
+                    let user: ChatRsUser = diesel::insert_into(users::table)
+                        .values(NewChatRsUser {
+                            name,
+                            role: Some((&ChatRsUserRole::Member).into()),
+                            ..Default::default()
+                        })
+                        .returning(ChatRsUser::as_returning())
+                        .get_result(conn)
+                        .await?;
+                    diesel::insert_into(service_accounts::table)
+                        .values((service_accounts::user_id.eq(user.id), account))
+                        .returning(ChatRsServiceAccount::as_returning())
+                        .get_result(conn)
+                        .await
+                }
+                .scope_boxed()
+            })
+            .await
+    }
+
+    pub async fn update(
+        &mut self,
+        user_id: &Uuid,
+        data: UpdateChatRsServiceAccount<'_>,
+    ) -> Result<ChatRsServiceAccount, Error> {
+        diesel::update(service_accounts::table.find(user_id))
+            .set(data)
+            .returning(ChatRsServiceAccount::as_returning())
+            .get_result(self.db)
+            .await
+    }
+}
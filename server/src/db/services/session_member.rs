@@ -0,0 +1,78 @@
+use diesel::prelude::*;
+use diesel::result::Error;
+use diesel::upsert::excluded;
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::db::{
+    models::{ChatRsSessionMemberInfo, NewChatRsSessionMember},
+    schema::{session_members, users},
+    DbConnection,
+};
+
+pub struct SessionMemberDbService<'a> {
+    pub db: &'a mut DbConnection,
+}
+
+impl<'a> SessionMemberDbService<'a> {
+    pub fn new(db: &'a mut DbConnection) -> Self {
+        SessionMemberDbService { db }
+    }
+
+    /// List the members of a session, with their names
+    pub async fn find_by_session(
+        &mut self,
+        session_id: &Uuid,
+    ) -> Result<Vec<ChatRsSessionMemberInfo>, Error> {
+        session_members::table
+            .inner_join(users::table)
+            .filter(session_members::session_id.eq(session_id))
+            .select((
+                session_members::user_id,
+                users::name,
+                users::avatar_url,
+                session_members::role,
+                session_members::created_at,
+            ))
+            .order_by(session_members::created_at.asc())
+            .load(self.db)
+            .await
+    }
+
+    /// Add a member to the session, or update their role if they're already a member
+    pub async fn upsert(&mut self, member: NewChatRsSessionMember<'_>) -> Result<(), Error> {
+        diesel::insert_into(session_members::table)
+            .values(member)
+            .on_conflict((session_members::session_id, session_members::user_id))
+            .do_update()
+            .set(session_members::role.eq(excluded(session_members::role)))
+            .execute(self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_role(
+        &mut self,
+        session_id: &Uuid,
+        user_id: &Uuid,
+        role: &str,
+    ) -> Result<Uuid, Error> {
+        diesel::update(session_members::table)
+            .filter(session_members::session_id.eq(session_id))
+            .filter(session_members::user_id.eq(user_id))
+            .set(session_members::role.eq(role))
+            .returning(session_members::user_id)
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn delete(&mut self, session_id: &Uuid, user_id: &Uuid) -> Result<Uuid, Error> {
+        diesel::delete(session_members::table)
+            .filter(session_members::session_id.eq(session_id))
+            .filter(session_members::user_id.eq(user_id))
+            .returning(session_members::user_id)
+            .get_result(self.db)
+            .await
+    }
+}
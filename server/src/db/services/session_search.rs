@@ -0,0 +1,137 @@
+use diesel::prelude::*;
+use diesel::result::Error;
+use diesel::upsert::excluded;
+use diesel_async::RunQueryDsl;
+use pgvector::{Vector, VectorExpressionMethods};
+use uuid::Uuid;
+
+use crate::db::{
+    models::{
+        ChatRsMessageSearchResult, ChatRsSessionSearchSettings, NewChatRsMessageEmbedding,
+        NewChatRsSessionSearchSettings,
+    },
+    schema::{chat_messages, chat_sessions, message_embeddings, session_search_settings},
+    DbConnection,
+};
+
+pub struct SessionSearchDbService<'a> {
+    pub db: &'a mut DbConnection,
+}
+
+impl<'a> SessionSearchDbService<'a> {
+    pub fn new(db: &'a mut DbConnection) -> Self {
+        SessionSearchDbService { db }
+    }
+
+    pub async fn get_settings(
+        &mut self,
+        user_id: &Uuid,
+    ) -> Result<Option<ChatRsSessionSearchSettings>, Error> {
+        session_search_settings::table
+            .filter(session_search_settings::user_id.eq(user_id))
+            .select(ChatRsSessionSearchSettings::as_select())
+            .first(self.db)
+            .await
+            .optional()
+    }
+
+    /// Create or update the user's settings. The embeddings of other models are deleted.
+    pub async fn upsert_settings(
+        &mut self,
+        settings: NewChatRsSessionSearchSettings<'_>,
+    ) -> Result<ChatRsSessionSearchSettings, Error> {
+        let settings = diesel::insert_into(session_search_settings::table)
+            .values(&settings)
+            .on_conflict(session_search_settings::user_id)
+            .do_update()
+            .set(&settings)
+            .returning(ChatRsSessionSearchSettings::as_returning())
+            .get_result(self.db)
+            .await?;
+        diesel::delete(message_embeddings::table)
+            .filter(message_embeddings::user_id.eq(&settings.user_id))
+            .filter(message_embeddings::embedding_model.ne(&settings.embedding_model))
+            .execute(self.db)
+            .await?;
+
+        Ok(settings)
+    }
+
+    /// Delete the user's settings and message embeddings
+    pub async fn delete_settings(&mut self, user_id: &Uuid) -> Result<(), Error> {
+        diesel::delete(message_embeddings::table)
+            .filter(message_embeddings::user_id.eq(user_id))
+            .execute(self.db)
+            .await?;
+        diesel::delete(session_search_settings::table)
+            .filter(session_search_settings::user_id.eq(user_id))
+            .execute(self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// Save the embedding of a message, replacing any previous embedding
+    pub async fn save_embedding(
+        &mut self,
+        embedding: NewChatRsMessageEmbedding<'_>,
+    ) -> Result<(), Error> {
+        diesel::insert_into(message_embeddings::table)
+            .values(embedding)
+            .on_conflict(message_embeddings::message_id)
+            .do_update()
+            .set((
+                message_embeddings::embedding_model
+                    .eq(excluded(message_embeddings::embedding_model)),
+                message_embeddings::embedding.eq(excluded(message_embeddings::embedding)),
+            ))
+            .execute(self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// Find the user's messages embedded with the given model that are closest to the
+    /// query embedding (by cosine distance)
+    pub async fn search(
+        &mut self,
+        user_id: &Uuid,
+        embedding_model: &str,
+        embedding: &Vector,
+        limit: i64,
+    ) -> Result<Vec<ChatRsMessageSearchResult>, Error> {
+        let results: Vec<(ChatRsMessageSearchResult, bool)> = message_embeddings::table
+            .inner_join(chat_messages::table)
+            .inner_join(chat_sessions::table)
+            .filter(message_embeddings::user_id.eq(user_id))
+            .filter(message_embeddings::embedding_model.eq(embedding_model))
+            .filter(chat_sessions::deleted_at.is_null())
+            .filter(chat_messages::deleted_at.is_null())
+            .select((
+                (
+                    message_embeddings::session_id,
+                    chat_sessions::title,
+                    message_embeddings::message_id,
+                    chat_messages::content,
+                    message_embeddings::embedding.cosine_distance(embedding),
+                ),
+                chat_messages::content_encrypted,
+            ))
+            .order_by(message_embeddings::embedding.cosine_distance(embedding))
+            .limit(limit)
+            .load(self.db)
+            .await?;
+        let encryption = self.db.message_encryption();
+
+        Ok(results
+            .into_iter()
+            .map(|(mut result, encrypted)| {
+                if let Some(plaintext) = encrypted
+                    .then(|| encryption.decrypt(&result.content))
+                    .flatten()
+                {
+                    result.content = plaintext;
+                }
+                result
+            })
+            .collect())
+    }
+}
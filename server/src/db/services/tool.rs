@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
 use diesel::result::Error;
 use diesel_async::RunQueryDsl;
@@ -5,10 +6,13 @@ use uuid::Uuid;
 
 use crate::db::{
     models::{
-        ChatRsExternalApiTool, ChatRsSecret, ChatRsSystemTool, NewChatRsExternalApiTool,
-        NewChatRsSystemTool,
+        ChatRsExternalApiTool, ChatRsMcpTool, ChatRsSecret, ChatRsSystemTool,
+        NewChatRsExternalApiTool, NewChatRsMcpTool, NewChatRsSystemTool,
+        UpdateChatRsExternalApiTool, UpdateChatRsMcpTool, UpdateChatRsSystemTool,
     },
-    schema::{external_api_tools, secrets, system_tools},
+    pagination::{ListQuery, ListSort},
+    schema::{external_api_tools, mcp_tools, secrets, system_tools},
+    services::user_organization_ids,
     DbConnection,
 };
 
@@ -21,22 +25,177 @@ impl<'a> ToolDbService<'a> {
         ToolDbService { db }
     }
 
-    pub async fn find_by_user(
+    /// List the user's system, external API, and MCP tools, including the external API and MCP
+    /// tools shared with the user's organizations. The cursor can be the ID of any type of
+    /// tool, and all lists are paginated by creation time.
+    pub async fn list(
         &mut self,
         user_id: &Uuid,
-    ) -> Result<(Vec<ChatRsSystemTool>, Vec<ChatRsExternalApiTool>), Error> {
-        let system_tools = system_tools::table
+        params: &ListQuery,
+    ) -> Result<
+        (
+            Vec<ChatRsSystemTool>,
+            Vec<ChatRsExternalApiTool>,
+            Vec<ChatRsMcpTool>,
+        ),
+        Error,
+    > {
+        let cursor = match params.cursor::<Uuid>()? {
+            Some(id) => Some((self.get_tool_created_at(user_id, &id).await?, id)),
+            None => None,
+        };
+        let mut system_query = system_tools::table
             .filter(system_tools::user_id.eq(user_id))
             .select(ChatRsSystemTool::as_select())
-            .load(self.db)
-            .await?;
-        let external_api_tools = external_api_tools::table
-            .filter(external_api_tools::user_id.eq(user_id))
+            .into_boxed();
+        if let Some((cursor_time, cursor)) = cursor {
+            system_query = match params.sort() {
+                ListSort::Newest => system_query.filter(
+                    system_tools::created_at
+                        .lt(cursor_time)
+                        .or(system_tools::created_at
+                            .eq(cursor_time)
+                            .and(system_tools::id.lt(cursor))),
+                ),
+                ListSort::Oldest => system_query.filter(
+                    system_tools::created_at
+                        .gt(cursor_time)
+                        .or(system_tools::created_at
+                            .eq(cursor_time)
+                            .and(system_tools::id.gt(cursor))),
+                ),
+            };
+        }
+        system_query = match params.sort() {
+            ListSort::Newest => {
+                system_query.order_by((system_tools::created_at.desc(), system_tools::id.desc()))
+            }
+            ListSort::Oldest => {
+                system_query.order_by((system_tools::created_at.asc(), system_tools::id.asc()))
+            }
+        };
+        let mut external_api_query = external_api_tools::table
+            .filter(
+                external_api_tools::user_id
+                    .eq(user_id)
+                    .or(external_api_tools::organization_id.eq_any(user_organization_ids(user_id))),
+            )
             .select(ChatRsExternalApiTool::as_select())
+            .into_boxed();
+        if let Some((cursor_time, cursor)) = cursor {
+            external_api_query = match params.sort() {
+                ListSort::Newest => external_api_query.filter(
+                    external_api_tools::created_at.lt(cursor_time).or(
+                        external_api_tools::created_at
+                            .eq(cursor_time)
+                            .and(external_api_tools::id.lt(cursor)),
+                    ),
+                ),
+                ListSort::Oldest => external_api_query.filter(
+                    external_api_tools::created_at.gt(cursor_time).or(
+                        external_api_tools::created_at
+                            .eq(cursor_time)
+                            .and(external_api_tools::id.gt(cursor)),
+                    ),
+                ),
+            };
+        }
+        external_api_query = match params.sort() {
+            ListSort::Newest => external_api_query.order_by((
+                external_api_tools::created_at.desc(),
+                external_api_tools::id.desc(),
+            )),
+            ListSort::Oldest => external_api_query.order_by((
+                external_api_tools::created_at.asc(),
+                external_api_tools::id.asc(),
+            )),
+        };
+        let mut mcp_query = mcp_tools::table
+            .filter(
+                mcp_tools::user_id
+                    .eq(user_id)
+                    .or(mcp_tools::organization_id.eq_any(user_organization_ids(user_id))),
+            )
+            .select(ChatRsMcpTool::as_select())
+            .into_boxed();
+        if let Some((cursor_time, cursor)) = cursor {
+            mcp_query = match params.sort() {
+                ListSort::Newest => mcp_query.filter(
+                    mcp_tools::created_at
+                        .lt(cursor_time)
+                        .or(mcp_tools::created_at
+                            .eq(cursor_time)
+                            .and(mcp_tools::id.lt(cursor))),
+                ),
+                ListSort::Oldest => mcp_query.filter(
+                    mcp_tools::created_at
+                        .gt(cursor_time)
+                        .or(mcp_tools::created_at
+                            .eq(cursor_time)
+                            .and(mcp_tools::id.gt(cursor))),
+                ),
+            };
+        }
+        mcp_query = match params.sort() {
+            ListSort::Newest => {
+                mcp_query.order_by((mcp_tools::created_at.desc(), mcp_tools::id.desc()))
+            }
+            ListSort::Oldest => {
+                mcp_query.order_by((mcp_tools::created_at.asc(), mcp_tools::id.asc()))
+            }
+        };
+        let system_tools = system_query.limit(params.limit()).load(self.db).await?;
+        let external_api_tools = external_api_query
+            .limit(params.limit())
             .load(self.db)
             .await?;
+        let mcp_tools = mcp_query.limit(params.limit()).load(self.db).await?;
 
-        Ok((system_tools, external_api_tools))
+        Ok((system_tools, external_api_tools, mcp_tools))
+    }
+
+    /// Get the creation time of a system, external API, or MCP tool
+    async fn get_tool_created_at(
+        &mut self,
+        user_id: &Uuid,
+        tool_id: &Uuid,
+    ) -> Result<DateTime<Utc>, Error> {
+        let system_tool_created_at = system_tools::table
+            .filter(system_tools::user_id.eq(user_id))
+            .filter(system_tools::id.eq(tool_id))
+            .select(system_tools::created_at)
+            .first(self.db)
+            .await
+            .optional()?;
+        if let Some(created_at) = system_tool_created_at {
+            return Ok(created_at);
+        }
+
+        let external_api_tool_created_at = external_api_tools::table
+            .filter(
+                external_api_tools::user_id
+                    .eq(user_id)
+                    .or(external_api_tools::organization_id.eq_any(user_organization_ids(user_id))),
+            )
+            .filter(external_api_tools::id.eq(tool_id))
+            .select(external_api_tools::created_at)
+            .first(self.db)
+            .await
+            .optional()?;
+        if let Some(created_at) = external_api_tool_created_at {
+            return Ok(created_at);
+        }
+
+        mcp_tools::table
+            .filter(
+                mcp_tools::user_id
+                    .eq(user_id)
+                    .or(mcp_tools::organization_id.eq_any(user_organization_ids(user_id))),
+            )
+            .filter(mcp_tools::id.eq(tool_id))
+            .select(mcp_tools::created_at)
+            .first(self.db)
+            .await
     }
 
     pub async fn find_system_tool_by_id(
@@ -64,26 +223,75 @@ impl<'a> ToolDbService<'a> {
             .await
     }
 
+    /// Find the external API tool, along with its secrets in order (`secret_1`, `secret_2`,
+    /// `secret_3`)
     pub async fn find_external_api_tool_by_id(
         &mut self,
         user_id: &Uuid,
         tool_id: &Uuid,
-    ) -> Result<Option<(ChatRsExternalApiTool, Option<ChatRsSecret>)>, Error> {
-        external_api_tools::table
-            .left_outer_join(
-                secrets::table.on(external_api_tools::secret_1.eq(secrets::id.nullable())),
-            )
+    ) -> Result<Option<(ChatRsExternalApiTool, [Option<ChatRsSecret>; 3])>, Error> {
+        let Some(tool) = external_api_tools::table
             .filter(external_api_tools::user_id.eq(user_id))
             .filter(external_api_tools::id.eq(tool_id))
-            .select((
-                ChatRsExternalApiTool::as_select(),
-                Option::<ChatRsSecret>::as_select(),
-            ))
+            .select(ChatRsExternalApiTool::as_select())
             .first(self.db)
             .await
-            .optional()
+            .optional()?
+        else {
+            return Ok(None);
+        };
+        let secrets = self.find_external_api_tool_secrets(&tool).await?;
+
+        Ok(Some((tool, secrets)))
     }
 
+    /// Find an external API tool that's owned by the user or shared with one of the user's
+    /// organizations, along with its secrets in order
+    pub async fn find_accessible_external_api_tool_by_id(
+        &mut self,
+        user_id: &Uuid,
+        tool_id: &Uuid,
+    ) -> Result<Option<(ChatRsExternalApiTool, [Option<ChatRsSecret>; 3])>, Error> {
+        let Some(tool) = external_api_tools::table
+            .filter(
+                external_api_tools::user_id
+                    .eq(user_id)
+                    .or(external_api_tools::organization_id.eq_any(user_organization_ids(user_id))),
+            )
+            .filter(external_api_tools::id.eq(tool_id))
+            .select(ChatRsExternalApiTool::as_select())
+            .first(self.db)
+            .await
+            .optional()?
+        else {
+            return Ok(None);
+        };
+        let secrets = self.find_external_api_tool_secrets(&tool).await?;
+
+        Ok(Some((tool, secrets)))
+    }
+
+    /// Get the secrets of the external API tool (owned by the tool's owner) in order
+    async fn find_external_api_tool_secrets(
+        &mut self,
+        tool: &ChatRsExternalApiTool,
+    ) -> Result<[Option<ChatRsSecret>; 3], Error> {
+        let secret_ids = tool.secret_ids();
+        let mut tool_secrets: Vec<ChatRsSecret> = secrets::table
+            .filter(secrets::user_id.eq(tool.user_id))
+            .filter(secrets::id.eq_any(secret_ids.iter().flatten()))
+            .select(ChatRsSecret::as_select())
+            .load(self.db)
+            .await?;
+        let secrets = secret_ids.map(|secret_id| {
+            let index = tool_secrets.iter().position(|s| Some(s.id) == secret_id)?;
+            Some(tool_secrets.swap_remove(index))
+        });
+
+        Ok(secrets)
+    }
+
+    /// Find the user's external API tools, and the tools shared with the user's organizations
     pub async fn find_external_api_tools_by_user(
         &mut self,
         user_id: &Uuid,
@@ -92,12 +300,73 @@ impl<'a> ToolDbService<'a> {
             .left_outer_join(
                 secrets::table.on(external_api_tools::secret_1.eq(secrets::id.nullable())),
             )
-            .filter(external_api_tools::user_id.eq(user_id))
+            .filter(
+                external_api_tools::user_id
+                    .eq(user_id)
+                    .or(external_api_tools::organization_id.eq_any(user_organization_ids(user_id))),
+            )
             .select(ChatRsExternalApiTool::as_select())
             .load(self.db)
             .await
     }
 
+    pub async fn find_mcp_tool_by_id(
+        &mut self,
+        user_id: &Uuid,
+        tool_id: &Uuid,
+    ) -> Result<Option<(ChatRsMcpTool, Option<ChatRsSecret>)>, Error> {
+        mcp_tools::table
+            .left_outer_join(secrets::table.on(mcp_tools::secret_1.eq(secrets::id.nullable())))
+            .filter(mcp_tools::user_id.eq(user_id))
+            .filter(mcp_tools::id.eq(tool_id))
+            .select((
+                ChatRsMcpTool::as_select(),
+                Option::<ChatRsSecret>::as_select(),
+            ))
+            .first(self.db)
+            .await
+            .optional()
+    }
+
+    /// Find an MCP tool that's owned by the user or shared with one of the user's organizations
+    pub async fn find_accessible_mcp_tool_by_id(
+        &mut self,
+        user_id: &Uuid,
+        tool_id: &Uuid,
+    ) -> Result<Option<(ChatRsMcpTool, Option<ChatRsSecret>)>, Error> {
+        mcp_tools::table
+            .left_outer_join(secrets::table.on(mcp_tools::secret_1.eq(secrets::id.nullable())))
+            .filter(
+                mcp_tools::user_id
+                    .eq(user_id)
+                    .or(mcp_tools::organization_id.eq_any(user_organization_ids(user_id))),
+            )
+            .filter(mcp_tools::id.eq(tool_id))
+            .select((
+                ChatRsMcpTool::as_select(),
+                Option::<ChatRsSecret>::as_select(),
+            ))
+            .first(self.db)
+            .await
+            .optional()
+    }
+
+    /// Find the user's MCP tools, and the tools shared with the user's organizations
+    pub async fn find_mcp_tools_by_user(
+        &mut self,
+        user_id: &Uuid,
+    ) -> Result<Vec<ChatRsMcpTool>, Error> {
+        mcp_tools::table
+            .filter(
+                mcp_tools::user_id
+                    .eq(user_id)
+                    .or(mcp_tools::organization_id.eq_any(user_organization_ids(user_id))),
+            )
+            .select(ChatRsMcpTool::as_select())
+            .load(self.db)
+            .await
+    }
+
     pub async fn create_system_tool(
         &mut self,
         tool: NewChatRsSystemTool<'_>,
@@ -120,6 +389,124 @@ impl<'a> ToolDbService<'a> {
             .await
     }
 
+    pub async fn create_mcp_tool(
+        &mut self,
+        tool: NewChatRsMcpTool<'_>,
+    ) -> Result<ChatRsMcpTool, Error> {
+        diesel::insert_into(mcp_tools::table)
+            .values(tool)
+            .returning(ChatRsMcpTool::as_select())
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn update_system_tool(
+        &mut self,
+        user_id: &Uuid,
+        tool_id: &Uuid,
+        data: UpdateChatRsSystemTool<'_>,
+    ) -> Result<ChatRsSystemTool, Error> {
+        diesel::update(system_tools::table)
+            .filter(system_tools::user_id.eq(user_id))
+            .filter(system_tools::id.eq(tool_id))
+            .set(data)
+            .returning(ChatRsSystemTool::as_select())
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn update_external_api_tool(
+        &mut self,
+        user_id: &Uuid,
+        tool_id: &Uuid,
+        data: UpdateChatRsExternalApiTool<'_>,
+    ) -> Result<ChatRsExternalApiTool, Error> {
+        diesel::update(external_api_tools::table)
+            .filter(external_api_tools::user_id.eq(user_id))
+            .filter(external_api_tools::id.eq(tool_id))
+            .set(data)
+            .returning(ChatRsExternalApiTool::as_select())
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn update_mcp_tool(
+        &mut self,
+        user_id: &Uuid,
+        tool_id: &Uuid,
+        data: UpdateChatRsMcpTool,
+    ) -> Result<ChatRsMcpTool, Error> {
+        diesel::update(mcp_tools::table)
+            .filter(mcp_tools::user_id.eq(user_id))
+            .filter(mcp_tools::id.eq(tool_id))
+            .set(data)
+            .returning(ChatRsMcpTool::as_select())
+            .get_result(self.db)
+            .await
+    }
+
+    /// Share the user's external API tool with an organization
+    pub async fn share_external_api_tool_with_organization(
+        &mut self,
+        user_id: &Uuid,
+        tool_id: &Uuid,
+        organization_id: &Uuid,
+    ) -> Result<ChatRsExternalApiTool, Error> {
+        diesel::update(external_api_tools::table)
+            .filter(external_api_tools::user_id.eq(user_id))
+            .filter(external_api_tools::id.eq(tool_id))
+            .set(external_api_tools::organization_id.eq(organization_id))
+            .returning(ChatRsExternalApiTool::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    /// Stop sharing the external API tool with the organization
+    pub async fn unshare_external_api_tool_from_organization(
+        &mut self,
+        organization_id: &Uuid,
+        tool_id: &Uuid,
+    ) -> Result<ChatRsExternalApiTool, Error> {
+        diesel::update(external_api_tools::table)
+            .filter(external_api_tools::organization_id.eq(organization_id))
+            .filter(external_api_tools::id.eq(tool_id))
+            .set(external_api_tools::organization_id.eq(None::<Uuid>))
+            .returning(ChatRsExternalApiTool::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    /// Share the user's MCP tool with an organization
+    pub async fn share_mcp_tool_with_organization(
+        &mut self,
+        user_id: &Uuid,
+        tool_id: &Uuid,
+        organization_id: &Uuid,
+    ) -> Result<ChatRsMcpTool, Error> {
+        diesel::update(mcp_tools::table)
+            .filter(mcp_tools::user_id.eq(user_id))
+            .filter(mcp_tools::id.eq(tool_id))
+            .set(mcp_tools::organization_id.eq(organization_id))
+            .returning(ChatRsMcpTool::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    /// Stop sharing the MCP tool with the organization
+    pub async fn unshare_mcp_tool_from_organization(
+        &mut self,
+        organization_id: &Uuid,
+        tool_id: &Uuid,
+    ) -> Result<ChatRsMcpTool, Error> {
+        diesel::update(mcp_tools::table)
+            .filter(mcp_tools::organization_id.eq(organization_id))
+            .filter(mcp_tools::id.eq(tool_id))
+            .set(mcp_tools::organization_id.eq(None::<Uuid>))
+            .returning(ChatRsMcpTool::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
     pub async fn delete_system_tool(
         &mut self,
         user_id: &Uuid,
@@ -146,6 +533,15 @@ impl<'a> ToolDbService<'a> {
             .await
     }
 
+    pub async fn delete_mcp_tool(&mut self, user_id: &Uuid, tool_id: &Uuid) -> Result<Uuid, Error> {
+        diesel::delete(mcp_tools::table)
+            .filter(mcp_tools::user_id.eq(user_id))
+            .filter(mcp_tools::id.eq(tool_id))
+            .returning(mcp_tools::id)
+            .get_result(self.db)
+            .await
+    }
+
     pub async fn delete_by_user(&mut self, user_id: &Uuid) -> Result<Vec<Uuid>, Error> {
         let deleted_system_tools = diesel::delete(system_tools::table)
             .filter(system_tools::user_id.eq(user_id))
@@ -157,10 +553,16 @@ impl<'a> ToolDbService<'a> {
             .returning(external_api_tools::id)
             .get_results(self.db)
             .await?;
+        let deleted_mcp_tools = diesel::delete(mcp_tools::table)
+            .filter(mcp_tools::user_id.eq(user_id))
+            .returning(mcp_tools::id)
+            .get_results(self.db)
+            .await?;
 
         Ok(deleted_system_tools
             .into_iter()
             .chain(deleted_external_api_tools.into_iter())
+            .chain(deleted_mcp_tools.into_iter())
             .collect())
     }
 }
@@ -0,0 +1,103 @@
+use chrono::{DateTime, Utc};
+use diesel::dsl::{count_star, max, sum};
+use diesel::prelude::*;
+use diesel::result::Error;
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::db::{
+    models::{ChatRsToolRun, ChatRsToolRunStats, NewChatRsToolRun},
+    pagination::{ListQuery, ListSort},
+    schema::tool_runs,
+    DbConnection,
+};
+
+pub struct ToolRunDbService<'a> {
+    pub db: &'a mut DbConnection,
+}
+
+impl<'a> ToolRunDbService<'a> {
+    pub fn new(db: &'a mut DbConnection) -> Self {
+        ToolRunDbService { db }
+    }
+
+    pub async fn create(&mut self, run: NewChatRsToolRun<'_>) -> Result<Uuid, Error> {
+        diesel::insert_into(tool_runs::table)
+            .values(run)
+            .returning(tool_runs::id)
+            .get_result(self.db)
+            .await
+    }
+
+    /// List the executions of a tool. The filter matches the names of the tools called.
+    pub async fn list(
+        &mut self,
+        user_id: &Uuid,
+        tool_id: &Uuid,
+        params: &ListQuery,
+    ) -> Result<Vec<ChatRsToolRun>, Error> {
+        let mut query = tool_runs::table
+            .filter(tool_runs::user_id.eq(user_id))
+            .filter(tool_runs::tool_id.eq(tool_id))
+            .select(ChatRsToolRun::as_select())
+            .into_boxed();
+        if let Some(pattern) = params.filter_pattern() {
+            query = query.filter(tool_runs::tool_name.ilike(pattern));
+        }
+        if let Some(cursor) = params.cursor::<Uuid>()? {
+            let cursor_time: DateTime<Utc> = tool_runs::table
+                .filter(tool_runs::user_id.eq(user_id))
+                .filter(tool_runs::id.eq(cursor))
+                .select(tool_runs::created_at)
+                .first(self.db)
+                .await?;
+            query = match params.sort() {
+                ListSort::Newest => query.filter(
+                    tool_runs::created_at
+                        .lt(cursor_time)
+                        .or(tool_runs::created_at
+                            .eq(cursor_time)
+                            .and(tool_runs::id.lt(cursor))),
+                ),
+                ListSort::Oldest => query.filter(
+                    tool_runs::created_at
+                        .gt(cursor_time)
+                        .or(tool_runs::created_at
+                            .eq(cursor_time)
+                            .and(tool_runs::id.gt(cursor))),
+                ),
+            };
+        }
+        query = match params.sort() {
+            ListSort::Newest => {
+                query.order_by((tool_runs::created_at.desc(), tool_runs::id.desc()))
+            }
+            ListSort::Oldest => query.order_by((tool_runs::created_at.asc(), tool_runs::id.asc())),
+        };
+
+        query.limit(params.limit()).load(self.db).await
+    }
+
+    /// Get the aggregate stats of the executions of a tool since the given time, by status
+    pub async fn stats(
+        &mut self,
+        user_id: &Uuid,
+        tool_id: &Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<ChatRsToolRunStats>, Error> {
+        tool_runs::table
+            .filter(tool_runs::user_id.eq(user_id))
+            .filter(tool_runs::tool_id.eq(tool_id))
+            .filter(tool_runs::created_at.ge(since))
+            .group_by(tool_runs::status)
+            .select((
+                tool_runs::status,
+                count_star(),
+                sum(tool_runs::duration_ms),
+                max(tool_runs::duration_ms),
+                sum(tool_runs::response_bytes),
+            ))
+            .load(self.db)
+            .await
+    }
+}
@@ -0,0 +1,116 @@
+use chrono::{DateTime, Utc};
+use diesel::{result::Error, sql_query};
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::db::{
+    models::{ChatRsOrganizationUsage, ChatRsUsageSummary, UsageGroupBy},
+    DbConnection,
+};
+
+pub struct UsageDbService<'a> {
+    pub db: &'a mut DbConnection,
+}
+
+impl<'a> UsageDbService<'a> {
+    pub fn new(db: &'a mut DbConnection) -> Self {
+        UsageDbService { db }
+    }
+
+    /// Aggregate the usage metadata of the user's assistant messages created in the given
+    /// time range. Days are in chronological order, and models and providers are ordered by
+    /// the most tokens used. Groups without any messages are omitted.
+    pub async fn summary(
+        &mut self,
+        user_id: &Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        group_by: UsageGroupBy,
+    ) -> Result<Vec<ChatRsUsageSummary>, Error> {
+        let (group, provider_id, order) = match group_by {
+            UsageGroupBy::Day => (
+                "to_char(cm.created_at AT TIME ZONE 'UTC', 'YYYY-MM-DD')",
+                "NULL::int",
+                "\"group\" ASC",
+            ),
+            UsageGroupBy::Model => (
+                "COALESCE(cm.meta->'assistant'->'provider_options'->>'model', 'unknown')",
+                "NULL::int",
+                "input_tokens + output_tokens DESC, \"group\" ASC",
+            ),
+            UsageGroupBy::Provider => (
+                "COALESCE(p.name, 'Deleted provider')",
+                "p.id",
+                "input_tokens + output_tokens DESC, \"group\" ASC",
+            ),
+        };
+        sql_query(format!(
+            r#"
+            SELECT
+                {group} AS "group",
+                {provider_id} AS provider_id,
+                COUNT(*) AS messages,
+                COALESCE(SUM((cm.meta->'assistant'->'usage'->>'input_tokens')::bigint), 0)::bigint AS input_tokens,
+                COALESCE(SUM((cm.meta->'assistant'->'usage'->>'output_tokens')::bigint), 0)::bigint AS output_tokens,
+                SUM((cm.meta->'assistant'->'usage'->>'cost')::float8) AS cost
+            FROM chat_messages cm
+                JOIN chat_sessions cs ON cm.session_id = cs.id
+                LEFT JOIN providers p
+                    ON p.id = (cm.meta->'assistant'->>'provider_id')::int
+                    AND (p.user_id = cs.user_id OR p.organization_id IN (
+                        SELECT organization_id FROM organization_members WHERE user_id = cs.user_id
+                    ))
+            WHERE cs.user_id = $1
+                AND cm.role = 'assistant'
+                AND cm.created_at >= $2
+                AND cm.created_at < $3
+            GROUP BY 1, 2
+            ORDER BY {order};
+            "#
+        ))
+        .bind::<diesel::sql_types::Uuid, _>(user_id)
+        .bind::<diesel::sql_types::Timestamptz, _>(from)
+        .bind::<diesel::sql_types::Timestamptz, _>(to)
+        .load(self.db)
+        .await
+    }
+
+    /// Aggregate the usage of the providers shared with the organization, per member, for the
+    /// assistant messages created in the given time range. Members are ordered by the most
+    /// tokens used, and members without any messages are omitted.
+    pub async fn organization_summary(
+        &mut self,
+        organization_id: &Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<ChatRsOrganizationUsage>, Error> {
+        sql_query(
+            r#"
+            SELECT
+                u.id AS user_id,
+                u.name AS name,
+                COUNT(*) AS messages,
+                COALESCE(SUM((cm.meta->'assistant'->'usage'->>'input_tokens')::bigint), 0)::bigint AS input_tokens,
+                COALESCE(SUM((cm.meta->'assistant'->'usage'->>'output_tokens')::bigint), 0)::bigint AS output_tokens,
+                SUM((cm.meta->'assistant'->'usage'->>'cost')::float8) AS cost
+            FROM chat_messages cm
+                JOIN chat_sessions cs ON cm.session_id = cs.id
+                JOIN providers p ON p.id = (cm.meta->'assistant'->>'provider_id')::int
+                JOIN organization_members om
+                    ON om.organization_id = p.organization_id AND om.user_id = cs.user_id
+                JOIN users u ON u.id = cs.user_id
+            WHERE p.organization_id = $1
+                AND cm.role = 'assistant'
+                AND cm.created_at >= $2
+                AND cm.created_at < $3
+            GROUP BY 1, 2
+            ORDER BY input_tokens + output_tokens DESC, name ASC;
+            "#,
+        )
+        .bind::<diesel::sql_types::Uuid, _>(organization_id)
+        .bind::<diesel::sql_types::Timestamptz, _>(from)
+        .bind::<diesel::sql_types::Timestamptz, _>(to)
+        .load(self.db)
+        .await
+    }
+}
@@ -1,12 +1,17 @@
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
 use diesel::result::Error;
 use diesel_async::RunQueryDsl;
 use uuid::Uuid;
 
-use crate::db::{
-    models::{ChatRsUser, NewChatRsUser, UpdateChatRsUser},
-    schema::users,
-    DbConnection,
+use crate::{
+    db::{
+        models::{ChatRsUser, ChatRsUserIdentity, ChatRsUserRole, NewChatRsUser, UpdateChatRsUser},
+        pagination::{ListQuery, ListSort},
+        schema::users,
+        DbConnection, DbPool,
+    },
+    storage::{UsageRecord, UsageRecordError},
 };
 
 pub struct UserDbService<'a> {
@@ -84,7 +89,233 @@ impl<'a> UserDbService<'a> {
         Ok(user_id)
     }
 
-    pub async fn create(&mut self, user: NewChatRsUser<'_>) -> Result<ChatRsUser, Error> {
+    pub async fn find_by_email(&mut self, email: &str) -> Result<Option<ChatRsUser>, Error> {
+        let user = users::table
+            .filter(users::email.eq(email))
+            .select(ChatRsUser::as_select())
+            .first(self.db)
+            .await
+            .optional()?;
+
+        Ok(user)
+    }
+
+    /// Get the ID, password hash, and email verification time of the user with the given email
+    pub async fn find_credentials_by_email(
+        &mut self,
+        email: &str,
+    ) -> Result<Option<(Uuid, Option<String>, Option<DateTime<Utc>>)>, Error> {
+        users::table
+            .filter(users::email.eq(email))
+            .select((users::id, users::password_hash, users::email_verified_at))
+            .first(self.db)
+            .await
+            .optional()
+    }
+
+    /// Whether the user has a password for email + password login
+    pub async fn has_password(&mut self, id: &Uuid) -> Result<bool, Error> {
+        let password_hash: Option<String> = users::table
+            .filter(users::id.eq(id))
+            .select(users::password_hash)
+            .first(self.db)
+            .await?;
+
+        Ok(password_hash.is_some())
+    }
+
+    /// Remove a login method of the user. Unlinking email + password login also removes the
+    /// user's email.
+    pub async fn unlink_identity(
+        &mut self,
+        id: &Uuid,
+        identity: ChatRsUserIdentity,
+    ) -> Result<Uuid, Error> {
+        let query = diesel::update(users::table.find(id));
+        match identity {
+            ChatRsUserIdentity::Github => {
+                query
+                    .set(users::github_id.eq(None::<String>))
+                    .returning(users::id)
+                    .get_result(self.db)
+                    .await
+            }
+            ChatRsUserIdentity::Google => {
+                query
+                    .set(users::google_id.eq(None::<String>))
+                    .returning(users::id)
+                    .get_result(self.db)
+                    .await
+            }
+            ChatRsUserIdentity::Discord => {
+                query
+                    .set(users::discord_id.eq(None::<String>))
+                    .returning(users::id)
+                    .get_result(self.db)
+                    .await
+            }
+            ChatRsUserIdentity::Oidc => {
+                query
+                    .set(users::oidc_id.eq(None::<String>))
+                    .returning(users::id)
+                    .get_result(self.db)
+                    .await
+            }
+            ChatRsUserIdentity::Sso => {
+                query
+                    .set(users::sso_username.eq(None::<String>))
+                    .returning(users::id)
+                    .get_result(self.db)
+                    .await
+            }
+            ChatRsUserIdentity::Local => {
+                query
+                    .set((
+                        users::email.eq(None::<String>),
+                        users::password_hash.eq(None::<String>),
+                        users::email_verified_at.eq(None::<DateTime<Utc>>),
+                    ))
+                    .returning(users::id)
+                    .get_result(self.db)
+                    .await
+            }
+        }
+    }
+
+    /// List the users of the server. The filter matches user names and emails.
+    pub async fn list(&mut self, params: &ListQuery) -> Result<Vec<ChatRsUser>, Error> {
+        let mut query = users::table.select(ChatRsUser::as_select()).into_boxed();
+        if let Some(pattern) = params.filter_pattern() {
+            query = query.filter(
+                users::name
+                    .ilike(pattern.clone())
+                    .or(users::email.ilike(pattern)),
+            );
+        }
+        if let Some(cursor) = params.cursor::<Uuid>()? {
+            let cursor_time: DateTime<Utc> = users::table
+                .filter(users::id.eq(cursor))
+                .select(users::created_at)
+                .first(self.db)
+                .await?;
+            query = match params.sort() {
+                ListSort::Newest => query.filter(
+                    users::created_at
+                        .lt(cursor_time)
+                        .or(users::created_at.eq(cursor_time).and(users::id.lt(cursor))),
+                ),
+                ListSort::Oldest => query.filter(
+                    users::created_at
+                        .gt(cursor_time)
+                        .or(users::created_at.eq(cursor_time).and(users::id.gt(cursor))),
+                ),
+            };
+        }
+        query = match params.sort() {
+            ListSort::Newest => query.order_by((users::created_at.desc(), users::id.desc())),
+            ListSort::Oldest => query.order_by((users::created_at.asc(), users::id.asc())),
+        };
+
+        query.limit(params.limit()).load(self.db).await
+    }
+
+    /// Whether the user was disabled by an admin
+    pub async fn is_disabled(&mut self, id: &Uuid) -> Result<bool, Error> {
+        let disabled_at: Option<Option<DateTime<Utc>>> = users::table
+            .filter(users::id.eq(id))
+            .select(users::disabled_at)
+            .first(self.db)
+            .await
+            .optional()?;
+
+        Ok(disabled_at.flatten().is_some())
+    }
+
+    /// Get the role of the user
+    pub async fn find_role(&mut self, id: &Uuid) -> Result<Option<String>, Error> {
+        users::table
+            .filter(users::id.eq(id))
+            .select(users::role)
+            .first(self.db)
+            .await
+            .optional()
+    }
+
+    pub async fn count_by_role(&mut self, role: &str) -> Result<i64, Error> {
+        users::table
+            .filter(users::role.eq(role))
+            .count()
+            .get_result(self.db)
+            .await
+    }
+
+    /// Get the total size of the user's stored files, if they've been counted after the
+    /// given time
+    pub async fn find_storage_used(
+        &mut self,
+        id: &Uuid,
+        counted_after: DateTime<Utc>,
+    ) -> Result<Option<i64>, Error> {
+        let storage_used: Option<Option<i64>> = users::table
+            .filter(users::id.eq(id))
+            .filter(users::storage_counted_at.gt(counted_after))
+            .select(users::storage_used)
+            .first(self.db)
+            .await
+            .optional()?;
+
+        Ok(storage_used.flatten())
+    }
+
+    /// Set the total size of the user's stored files after counting them, unless they've been
+    /// counted after the given time (by a concurrent count, whose result may already include
+    /// the changes since then)
+    pub async fn set_storage_used(
+        &mut self,
+        id: &Uuid,
+        used: i64,
+        counted_before: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        diesel::update(users::table.find(id))
+            .filter(
+                users::storage_counted_at
+                    .is_null()
+                    .or(users::storage_counted_at.lt(counted_before)),
+            )
+            .set((
+                users::storage_used.eq(used),
+                users::storage_counted_at.eq(Utc::now()),
+            ))
+            .execute(self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Add the change in size to the user's stored files, if they've been counted
+    pub async fn add_storage_used(&mut self, id: &Uuid, change: i64) -> Result<(), Error> {
+        diesel::update(
+            users::table
+                .find(id)
+                .filter(users::storage_used.is_not_null()),
+        )
+        .set(users::storage_used.eq(users::storage_used + change))
+        .execute(self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Create a new user. The first user of the server becomes an admin.
+    pub async fn create(&mut self, mut user: NewChatRsUser<'_>) -> Result<ChatRsUser, Error> {
+        if user.role.is_none() {
+            let has_users: bool = diesel::select(diesel::dsl::exists(users::table))
+                .get_result(self.db)
+                .await?;
+            if !has_users {
+                user.role = Some((&ChatRsUserRole::Admin).into());
+            }
+        }
         diesel::insert_into(users::table)
             .values(user)
             .returning(ChatRsUser::as_returning())
@@ -115,3 +346,44 @@ impl<'a> UserDbService<'a> {
         Ok(id)
     }
 }
+
+/// Storage used by each user, recorded in the users table
+#[rocket::async_trait]
+impl UsageRecord for DbPool {
+    async fn find(
+        &self,
+        user_id: &Uuid,
+        counted_after: DateTime<Utc>,
+    ) -> Result<Option<u64>, UsageRecordError> {
+        let mut db = self.get().await?;
+        let used = UserDbService::new(&mut db)
+            .find_storage_used(user_id, counted_after)
+            .await?;
+        Ok(used.map(|used| u64::try_from(used).unwrap_or_default()))
+    }
+
+    async fn set(
+        &self,
+        user_id: &Uuid,
+        used: u64,
+        counted_before: DateTime<Utc>,
+    ) -> Result<(), UsageRecordError> {
+        let mut db = self.get().await?;
+        UserDbService::new(&mut db)
+            .set_storage_used(
+                user_id,
+                i64::try_from(used).unwrap_or(i64::MAX),
+                counted_before,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn add(&self, user_id: &Uuid, change: i64) -> Result<(), UsageRecordError> {
+        let mut db = self.get().await?;
+        UserDbService::new(&mut db)
+            .add_storage_used(user_id, change)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,186 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::result::Error;
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use crate::db::{
+    models::{
+        ChatRsWebhook, ChatRsWebhookDelivery, ChatRsWebhookDeliveryStatus, NewChatRsWebhook,
+        NewChatRsWebhookDelivery, UpdateChatRsWebhook, UpdateChatRsWebhookDelivery,
+    },
+    schema::{webhook_deliveries, webhooks},
+    DbConnection,
+};
+
+pub struct WebhookDbService<'a> {
+    pub db: &'a mut DbConnection,
+}
+
+impl<'a> WebhookDbService<'a> {
+    pub fn new(db: &'a mut DbConnection) -> Self {
+        WebhookDbService { db }
+    }
+
+    pub async fn find_by_id(
+        &mut self,
+        user_id: &Uuid,
+        webhook_id: &Uuid,
+    ) -> Result<ChatRsWebhook, Error> {
+        webhooks::table
+            .filter(webhooks::user_id.eq(user_id))
+            .filter(webhooks::id.eq(webhook_id))
+            .select(ChatRsWebhook::as_select())
+            .first(self.db)
+            .await
+    }
+
+    /// Get the webhooks of all users (for re-encrypting their secrets with a new secret key)
+    pub async fn find_all(&mut self) -> Result<Vec<ChatRsWebhook>, Error> {
+        webhooks::table
+            .select(ChatRsWebhook::as_select())
+            .load(self.db)
+            .await
+    }
+
+    pub async fn find_by_user_id(&mut self, user_id: &Uuid) -> Result<Vec<ChatRsWebhook>, Error> {
+        webhooks::table
+            .filter(webhooks::user_id.eq(user_id))
+            .select(ChatRsWebhook::as_select())
+            .order_by(webhooks::name.asc())
+            .load(self.db)
+            .await
+    }
+
+    /// Find the user's enabled webhooks that are triggered by the event
+    pub async fn find_by_event(
+        &mut self,
+        user_id: &Uuid,
+        event: &str,
+    ) -> Result<Vec<ChatRsWebhook>, Error> {
+        webhooks::table
+            .filter(webhooks::user_id.eq(user_id))
+            .filter(webhooks::enabled.eq(true))
+            .filter(webhooks::events.contains(vec![event]))
+            .select(ChatRsWebhook::as_select())
+            .load(self.db)
+            .await
+    }
+
+    pub async fn count_by_user_id(&mut self, user_id: &Uuid) -> Result<i64, Error> {
+        webhooks::table
+            .filter(webhooks::user_id.eq(user_id))
+            .count()
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn create(&mut self, webhook: NewChatRsWebhook<'_>) -> Result<ChatRsWebhook, Error> {
+        diesel::insert_into(webhooks::table)
+            .values(webhook)
+            .returning(ChatRsWebhook::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn update(
+        &mut self,
+        user_id: &Uuid,
+        webhook_id: &Uuid,
+        data: UpdateChatRsWebhook<'_>,
+    ) -> Result<ChatRsWebhook, Error> {
+        diesel::update(webhooks::table)
+            .filter(webhooks::user_id.eq(user_id))
+            .filter(webhooks::id.eq(webhook_id))
+            .set(data)
+            .returning(ChatRsWebhook::as_returning())
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn delete(&mut self, user_id: &Uuid, webhook_id: &Uuid) -> Result<Uuid, Error> {
+        diesel::delete(webhooks::table)
+            .filter(webhooks::user_id.eq(user_id))
+            .filter(webhooks::id.eq(webhook_id))
+            .returning(webhooks::id)
+            .get_result(self.db)
+            .await
+    }
+
+    pub async fn create_deliveries(
+        &mut self,
+        deliveries: &[NewChatRsWebhookDelivery<'_>],
+    ) -> Result<Vec<Uuid>, Error> {
+        diesel::insert_into(webhook_deliveries::table)
+            .values(deliveries)
+            .returning(webhook_deliveries::id)
+            .get_results(self.db)
+            .await
+    }
+
+    /// Find the latest deliveries of the user's webhook
+    pub async fn find_deliveries(
+        &mut self,
+        user_id: &Uuid,
+        webhook_id: &Uuid,
+        limit: i64,
+    ) -> Result<Vec<ChatRsWebhookDelivery>, Error> {
+        webhook_deliveries::table
+            .inner_join(webhooks::table)
+            .filter(webhooks::user_id.eq(user_id))
+            .filter(webhook_deliveries::webhook_id.eq(webhook_id))
+            .select(ChatRsWebhookDelivery::as_select())
+            .order_by(webhook_deliveries::created_at.desc())
+            .limit(limit)
+            .load(self.db)
+            .await
+    }
+
+    /// Find the pending deliveries of all users that are due for an attempt, with their webhook
+    pub async fn find_due_deliveries(
+        &mut self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<(ChatRsWebhookDelivery, ChatRsWebhook)>, Error> {
+        let status: &str = (&ChatRsWebhookDeliveryStatus::Pending).into();
+        webhook_deliveries::table
+            .inner_join(webhooks::table)
+            .filter(webhook_deliveries::status.eq(status))
+            .filter(webhook_deliveries::next_attempt_at.le(now))
+            .select((
+                ChatRsWebhookDelivery::as_select(),
+                ChatRsWebhook::as_select(),
+            ))
+            .order_by(webhook_deliveries::next_attempt_at.asc())
+            .limit(limit)
+            .load(self.db)
+            .await
+    }
+
+    pub async fn update_delivery(
+        &mut self,
+        delivery_id: &Uuid,
+        data: UpdateChatRsWebhookDelivery<'_>,
+    ) -> Result<(), Error> {
+        diesel::update(webhook_deliveries::table)
+            .filter(webhook_deliveries::id.eq(delivery_id))
+            .set(data)
+            .execute(self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete the finished deliveries of all users that were created before the given time
+    pub async fn delete_finished_deliveries(
+        &mut self,
+        created_before: DateTime<Utc>,
+    ) -> Result<usize, Error> {
+        let pending: &str = (&ChatRsWebhookDeliveryStatus::Pending).into();
+        diesel::delete(webhook_deliveries::table)
+            .filter(webhook_deliveries::status.ne(pending))
+            .filter(webhook_deliveries::created_at.lt(created_before))
+            .execute(self.db)
+            .await
+    }
+}
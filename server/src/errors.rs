@@ -8,7 +8,16 @@ use rocket::{
 use rocket_okapi::response::OpenApiResponderInner;
 use schemars::JsonSchema;
 
-use crate::{provider::LlmError, tools::ToolError};
+use crate::{
+    auth::{ApiKeyError, LocalAuthError},
+    backup::BackupError,
+    knowledge::KnowledgeError,
+    provider::{LlmError, LlmErrorCode, LlmErrorInfo},
+    scheduler::ScheduleError,
+    storage::StorageError,
+    tools::ToolError,
+    webhooks::WebhookError,
+};
 
 #[derive(thiserror::Error, Debug)]
 pub enum ApiError {
@@ -18,36 +27,114 @@ pub enum ApiError {
     DbPool(#[from] deadpool::PoolError),
     #[error("Authentication error: {0}")]
     Authentication(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+    #[error("Rate limited: {0}")]
+    RateLimited(String),
     #[error("Redis error: {0}")]
     Redis(#[from] fred::error::Error),
     #[error(transparent)]
     Chat(#[from] LlmError),
     #[error(transparent)]
     Tool(#[from] ToolError),
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+    #[error(transparent)]
+    Knowledge(#[from] KnowledgeError),
+    #[error(transparent)]
+    Schedule(#[from] ScheduleError),
+    #[error(transparent)]
+    Webhook(#[from] WebhookError),
+    #[error(transparent)]
+    ApiKey(#[from] ApiKeyError),
+    #[error(transparent)]
+    LocalAuth(#[from] LocalAuthError),
+    #[error(transparent)]
+    Backup(#[from] BackupError),
 }
 
+/// Error response body, tagged by the kind of error
 #[derive(Debug, JsonSchema, serde::Serialize)]
-struct Message {
-    message: String,
-}
-impl Message {
-    fn new(message: &str) -> Self {
-        Self {
-            message: message.to_string(),
-        }
-    }
+#[serde(tag = "type", rename_all = "snake_case")]
+#[schemars(rename = "ErrorResponse")]
+enum ErrorBody {
+    /// Invalid request
+    BadRequest {
+        message: String,
+        /// Code of the error, if it's a known provider error (e.g. invalid API key)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        code: Option<LlmErrorCode>,
+        /// The original error of the provider, if there's a code
+        #[serde(skip_serializing_if = "Option::is_none")]
+        details: Option<String>,
+    },
+    /// Missing or invalid authentication
+    Unauthorized { message: String },
+    /// Not allowed to access the resource
+    Forbidden { message: String },
+    /// Resource not found
+    NotFound { message: String },
+    /// Too many requests, try again later
+    TooManyRequests { message: String },
+    /// Internal server error
+    Server { message: String },
 }
 
 #[derive(Debug, Responder)]
 enum ApiErrorResponse {
     #[response(status = 400, content_type = "json")]
-    BadRequest(Json<Message>),
+    BadRequest(Json<ErrorBody>),
     #[response(status = 401, content_type = "json")]
-    Unauthorized(Json<Message>),
+    Unauthorized(Json<ErrorBody>),
+    #[response(status = 403, content_type = "json")]
+    Forbidden(Json<ErrorBody>),
     #[response(status = 404, content_type = "json")]
-    NotFound(Json<Message>),
+    NotFound(Json<ErrorBody>),
+    #[response(status = 429, content_type = "json")]
+    TooManyRequests(Json<ErrorBody>),
     #[response(status = 500, content_type = "json")]
-    Server(Json<Message>),
+    Server(Json<ErrorBody>),
+}
+impl ApiErrorResponse {
+    fn bad_request(message: &str) -> Self {
+        Self::BadRequest(Json(ErrorBody::BadRequest {
+            message: message.to_string(),
+            code: None,
+            details: None,
+        }))
+    }
+    fn provider_error(error: LlmErrorInfo) -> Self {
+        Self::BadRequest(Json(ErrorBody::BadRequest {
+            message: format!("Chat error: {}", error.message),
+            code: error.code,
+            details: error.details,
+        }))
+    }
+    fn unauthorized(message: &str) -> Self {
+        Self::Unauthorized(Json(ErrorBody::Unauthorized {
+            message: message.to_string(),
+        }))
+    }
+    fn forbidden(message: &str) -> Self {
+        Self::Forbidden(Json(ErrorBody::Forbidden {
+            message: message.to_string(),
+        }))
+    }
+    fn not_found(message: &str) -> Self {
+        Self::NotFound(Json(ErrorBody::NotFound {
+            message: message.to_string(),
+        }))
+    }
+    fn too_many_requests(message: &str) -> Self {
+        Self::TooManyRequests(Json(ErrorBody::TooManyRequests {
+            message: message.to_string(),
+        }))
+    }
+    fn server(message: &str) -> Self {
+        Self::Server(Json(ErrorBody::Server {
+            message: message.to_string(),
+        }))
+    }
 }
 
 /// API error response handling
@@ -56,27 +143,76 @@ impl<'r, 'o: 'r> response::Responder<'r, 'o> for ApiError {
         rocket::info!("API error: {:?}", self);
         match self {
             ApiError::Authentication(error) => {
-                ApiErrorResponse::Unauthorized(Json(Message::new(&error))).respond_to(req)
+                ApiErrorResponse::unauthorized(&error).respond_to(req)
+            }
+            ApiError::Forbidden(error) => ApiErrorResponse::forbidden(&error).respond_to(req),
+            ApiError::RateLimited(error) => {
+                ApiErrorResponse::too_many_requests(&error).respond_to(req)
             }
             ApiError::Db(error) => match error {
-                diesel::result::Error::DatabaseError(kind, info) => ApiErrorResponse::Server(Json(
-                    Message::new(&format!("Database error: {:?} | {:?}", kind, info)),
-                ))
-                .respond_to(req),
+                diesel::result::Error::DatabaseError(kind, info) => {
+                    ApiErrorResponse::server(&format!("Database error: {:?} | {:?}", kind, info))
+                        .respond_to(req)
+                }
                 diesel::result::Error::NotFound => {
-                    ApiErrorResponse::NotFound(Json(Message::new("Not found!"))).respond_to(req)
+                    ApiErrorResponse::not_found("Not found!").respond_to(req)
                 }
-                _ => ApiErrorResponse::Server(Json(Message::new("Server error!"))).respond_to(req),
+                _ => ApiErrorResponse::server("Server error!").respond_to(req),
             },
             ApiError::Chat(error) => {
-                ApiErrorResponse::BadRequest(Json(Message::new(&format!("Chat error: {}", error))))
-                    .respond_to(req)
+                ApiErrorResponse::provider_error(LlmErrorInfo::from(&error)).respond_to(req)
             }
             ApiError::Tool(error) => {
-                ApiErrorResponse::BadRequest(Json(Message::new(&format!("Tool error: {}", error))))
-                    .respond_to(req)
+                ApiErrorResponse::bad_request(&format!("Tool error: {}", error)).respond_to(req)
+            }
+            ApiError::Storage(error) => match error {
+                StorageError::NotFound | StorageError::InvalidPath => {
+                    ApiErrorResponse::not_found("Not found!").respond_to(req)
+                }
+                StorageError::UnsupportedType(_)
+                | StorageError::TooLarge(_)
+                | StorageError::TooManyFiles(_)
+                | StorageError::QuotaExceeded(_)
+                | StorageError::Infected(_) => {
+                    ApiErrorResponse::bad_request(&error.to_string()).respond_to(req)
+                }
+                _ => ApiErrorResponse::server("Server error!").respond_to(req),
+            },
+            ApiError::Knowledge(error) => match error {
+                KnowledgeError::NotFound => {
+                    ApiErrorResponse::not_found("Not found!").respond_to(req)
+                }
+                _ => ApiErrorResponse::bad_request(&format!("Knowledge base error: {}", error))
+                    .respond_to(req),
+            },
+            ApiError::Schedule(error) => {
+                ApiErrorResponse::bad_request(&error.to_string()).respond_to(req)
+            }
+            ApiError::Webhook(error) => {
+                ApiErrorResponse::bad_request(&error.to_string()).respond_to(req)
+            }
+            ApiError::ApiKey(error) => {
+                ApiErrorResponse::bad_request(&error.to_string()).respond_to(req)
             }
-            _ => ApiErrorResponse::Server(Json(Message::new("Server error!"))).respond_to(req),
+            ApiError::Backup(error) => {
+                ApiErrorResponse::bad_request(&error.to_string()).respond_to(req)
+            }
+            ApiError::LocalAuth(error) => match error {
+                LocalAuthError::InvalidCredentials | LocalAuthError::EmailNotVerified => {
+                    ApiErrorResponse::unauthorized(&error.to_string()).respond_to(req)
+                }
+                LocalAuthError::RegistrationDisabled => {
+                    ApiErrorResponse::forbidden(&error.to_string()).respond_to(req)
+                }
+                LocalAuthError::TooManyAttempts(_) => {
+                    ApiErrorResponse::too_many_requests(&error.to_string()).respond_to(req)
+                }
+                LocalAuthError::Hash(_) | LocalAuthError::Email(_) | LocalAuthError::Captcha(_) => {
+                    ApiErrorResponse::server("Server error!").respond_to(req)
+                }
+                _ => ApiErrorResponse::bad_request(&error.to_string()).respond_to(req),
+            },
+            _ => ApiErrorResponse::server("Server error!").respond_to(req),
         }
     }
 }
@@ -86,30 +222,40 @@ pub fn get_catchers() -> Vec<Catcher> {
     catchers![
         bad_request,
         unauthorized,
+        forbidden,
         unprocessable_entity,
         not_found,
+        payload_too_large,
         server_error
     ]
 }
 #[catch(400)]
 fn bad_request(_req: &Request) -> ApiErrorResponse {
-    ApiErrorResponse::BadRequest(Json(Message::new("Bad request")))
+    ApiErrorResponse::bad_request("Bad request")
 }
 #[catch(401)]
 fn unauthorized(_req: &Request) -> ApiErrorResponse {
-    ApiErrorResponse::Unauthorized(Json(Message::new("Unauthorized!")))
+    ApiErrorResponse::unauthorized("Unauthorized!")
+}
+#[catch(403)]
+fn forbidden(_req: &Request) -> ApiErrorResponse {
+    ApiErrorResponse::forbidden("Forbidden!")
 }
 #[catch(404)]
 fn not_found(_req: &Request) -> ApiErrorResponse {
-    ApiErrorResponse::NotFound(Json(Message::new("Not found!")))
+    ApiErrorResponse::not_found("Not found!")
+}
+#[catch(413)]
+fn payload_too_large(_req: &Request) -> ApiErrorResponse {
+    ApiErrorResponse::bad_request("Request body is too large")
 }
 #[catch(422)]
 fn unprocessable_entity(_req: &Request) -> ApiErrorResponse {
-    ApiErrorResponse::BadRequest(Json(Message::new("Incorrectly formatted")))
+    ApiErrorResponse::bad_request("Incorrectly formatted")
 }
 #[catch(500)]
 fn server_error(_req: &Request) -> ApiErrorResponse {
-    ApiErrorResponse::Server(Json(Message::new("Server error!")))
+    ApiErrorResponse::server("Server error!")
 }
 
 /// OpenAPI specification for API error responses
@@ -126,15 +272,18 @@ impl OpenApiResponderInner for ApiError {
         content.insert(
             "application/json".to_string(),
             MediaType {
-                schema: Some(gen.json_schema::<Message>()),
+                schema: Some(gen.json_schema::<ErrorBody>()),
                 ..Default::default()
             },
         );
         let response_data = vec![
             ("400", "Bad request"),
             ("401", "Authentication error"),
+            ("403", "Forbidden"),
             ("404", "Not found"),
+            ("413", "Request body too large"),
             ("422", "Incorrectly formatted"),
+            ("429", "Too many requests"),
             ("500", "Internal error"),
         ];
         for (status, description) in response_data {
@@ -0,0 +1,160 @@
+//! Extraction of the text of uploaded documents (PDF, DOCX, and HTML). The text is extracted in
+//! the background after the upload, and saved with the attachment: it's included in the
+//! messages instead of DOCX and HTML files, and can be added to knowledge bases. PDFs are still
+//! given to the providers as files.
+
+use std::io::Read;
+
+use tokio::sync::Semaphore;
+use uuid::Uuid;
+
+use crate::{
+    db::{models::ChatRsAttachment, services::AttachmentDbService, DbPool},
+    errors::ApiError,
+    storage::DOCX_MIME_TYPE,
+    tools::{decode_entities, extract_page_content},
+};
+
+/// Max length of the extracted text, in characters
+const MAX_TEXT_LENGTH: usize = 1_000_000;
+/// Max size of the XML of a DOCX document, in bytes
+const MAX_DOCX_XML_SIZE: u64 = 50 * 1024 * 1024;
+/// Path of the main XML document of a DOCX file
+const DOCX_DOCUMENT_PATH: &str = "word/document.xml";
+
+/// Limits the number of documents processed at the same time
+static EXTRACTION_PERMITS: Semaphore = Semaphore::const_new(2);
+
+/// Text extraction errors
+#[derive(Debug, thiserror::Error)]
+pub enum ExtractionError {
+    #[error("Unsupported file type: {0}")]
+    UnsupportedType(String),
+    #[error("Failed to read PDF: {0}")]
+    Pdf(#[from] lopdf::Error),
+    #[error("Failed to read DOCX: {0}")]
+    Docx(String),
+    #[error("Invalid text encoding")]
+    Encoding,
+}
+
+/// Whether text can be extracted from files of the MIME type
+pub fn is_extractable(mime_type: &str) -> bool {
+    matches!(mime_type, "application/pdf" | "text/html" | DOCX_MIME_TYPE)
+}
+
+/// Extract the text of a document, truncated to the max length. This is CPU-intensive, so it
+/// should run on a blocking thread.
+pub fn extract_text(mime_type: &str, data: &[u8]) -> Result<String, ExtractionError> {
+    let mut text = match mime_type {
+        "application/pdf" => extract_pdf_text(data)?,
+        DOCX_MIME_TYPE => extract_docx_text(data)?,
+        "text/html" => {
+            let html = std::str::from_utf8(data).map_err(|_| ExtractionError::Encoding)?;
+            let content = extract_page_content(html);
+            match content.title {
+                Some(title) if !title.is_empty() => format!("{title}\n\n{}", content.text),
+                _ => content.text,
+            }
+        }
+        _ => return Err(ExtractionError::UnsupportedType(mime_type.to_owned())),
+    };
+    if let Some((end, _)) = text.char_indices().nth(MAX_TEXT_LENGTH) {
+        text.truncate(end);
+    }
+
+    Ok(text.trim().to_owned())
+}
+
+/// Extract the text of an uploaded document in the background, and save it with the
+/// attachment. If the extraction fails, the attachment is saved without text.
+pub fn spawn_text_extraction(db_pool: DbPool, attachment: &ChatRsAttachment, data: Vec<u8>) {
+    if !is_extractable(&attachment.mime_type) {
+        return;
+    }
+    let attachment_id = attachment.id;
+    let mime_type = attachment.mime_type.clone();
+    tokio::spawn(async move {
+        let Ok(_permit) = EXTRACTION_PERMITS.acquire().await else {
+            return;
+        };
+        let text = match tokio::task::spawn_blocking(move || extract_text(&mime_type, &data)).await
+        {
+            Ok(Ok(text)) => Some(text).filter(|text| !text.is_empty()),
+            Ok(Err(err)) => {
+                rocket::warn!("Failed to extract text of attachment {attachment_id}: {err}");
+                None
+            }
+            Err(err) => {
+                rocket::error!("Text extraction of attachment {attachment_id} panicked: {err}");
+                None
+            }
+        };
+        if let Err(err) = save_extracted_text(&db_pool, &attachment_id, text.as_deref()).await {
+            rocket::warn!("Failed to save text of attachment {attachment_id}: {err}");
+        }
+    });
+}
+
+async fn save_extracted_text(
+    db_pool: &DbPool,
+    attachment_id: &Uuid,
+    text: Option<&str>,
+) -> Result<(), ApiError> {
+    let mut db = db_pool.get().await?;
+    AttachmentDbService::new(&mut db)
+        .set_extracted_text(attachment_id, text)
+        .await?;
+    Ok(())
+}
+
+/// Extract the text of the pages of a PDF
+fn extract_pdf_text(data: &[u8]) -> Result<String, ExtractionError> {
+    let document = lopdf::Document::load_mem(data)?;
+    let pages: Vec<u32> = document.get_pages().keys().copied().collect();
+    Ok(document.extract_text(&pages)?)
+}
+
+/// Extract the text of the paragraphs of a Word document, from its main XML document
+fn extract_docx_text(data: &[u8]) -> Result<String, ExtractionError> {
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(data)).map_err(docx_error)?;
+    let document = archive.by_name(DOCX_DOCUMENT_PATH).map_err(docx_error)?;
+    let mut xml = String::new();
+    document
+        .take(MAX_DOCX_XML_SIZE)
+        .read_to_string(&mut xml)
+        .map_err(docx_error)?;
+
+    let mut text = String::with_capacity(xml.len() / 4);
+    let mut in_text = false;
+    let mut rest = xml.as_str();
+    while let Some(start) = rest.find('<') {
+        if in_text {
+            text.push_str(&decode_entities(&rest[..start]));
+        }
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = &rest[start + 1..start + end];
+        rest = &rest[start + end + 1..];
+
+        let name = tag
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .find(|part| !part.is_empty())
+            .unwrap_or_default();
+        match (name, tag.starts_with('/')) {
+            ("w:t", false) => in_text = !tag.ends_with('/'),
+            ("w:t", true) => in_text = false,
+            ("w:p", true) => text.push('\n'),
+            ("w:tab", false) => text.push('\t'),
+            ("w:br" | "w:cr", false) => text.push('\n'),
+            _ => {}
+        }
+    }
+
+    Ok(text)
+}
+
+fn docx_error(err: impl std::fmt::Display) -> ExtractionError {
+    ExtractionError::Docx(err.to_string())
+}
@@ -0,0 +1,273 @@
+//! Import of conversations from other tools, using a simple JSON or Markdown transcript format.
+//!
+//! JSON transcripts have an optional title and a list of messages:
+//!
+//! ```json
+//! {
+//!   "title": "Trip planning",
+//!   "messages": [
+//!     { "role": "user", "content": "Where should I go in June?" },
+//!     { "role": "assistant", "content": "How about Lisbon?" }
+//!   ]
+//! }
+//! ```
+//!
+//! Markdown transcripts have an optional `# Title` heading, and each message starts with a
+//! `## User`, `## Assistant`, or `## System` heading followed by the message content:
+//!
+//! ```markdown
+//! # Trip planning
+//!
+//! ## User
+//! Where should I go in June?
+//!
+//! ## Assistant
+//! How about Lisbon?
+//! ```
+//!
+//! The conversation exports of ChatGPT and Claude can also be imported in bulk, with the
+//! progress reported to a Redis stream.
+
+mod export;
+
+pub use export::{parse_export, ExportFormat};
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        models::{ChatRsMessageRole, NewChatRsSession},
+        services::ChatDbService,
+        DbPool,
+    },
+    errors::ApiError,
+    stream::{ImportProgress, ImportStreamWriter},
+};
+
+/// Maximum number of messages in an imported transcript.
+const MAX_MESSAGES: usize = 2000;
+
+/// Format of an imported transcript
+#[derive(Debug, Clone, Copy, JsonSchema, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TranscriptFormat {
+    Json,
+    Markdown,
+}
+
+/// A parsed conversation transcript
+#[derive(Debug)]
+pub struct Transcript {
+    pub title: Option<String>,
+    pub messages: Vec<TranscriptMessage>,
+}
+
+#[derive(Debug)]
+pub struct TranscriptMessage {
+    pub role: ChatRsMessageRole,
+    pub content: String,
+    /// Original time of the message, if known
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+struct JsonTranscript {
+    title: Option<String>,
+    messages: Vec<JsonTranscriptMessage>,
+}
+
+#[derive(Deserialize)]
+struct JsonTranscriptMessage {
+    role: String,
+    content: String,
+}
+
+/// Parse and validate a transcript. Returns the problems found if the transcript is invalid.
+pub fn parse_transcript(
+    format: TranscriptFormat,
+    content: &str,
+) -> Result<Transcript, Vec<String>> {
+    let (transcript, mut problems) = match format {
+        TranscriptFormat::Json => parse_json(content),
+        TranscriptFormat::Markdown => parse_markdown(content),
+    };
+    if problems.is_empty() && transcript.messages.is_empty() {
+        problems.push("The transcript has no messages".into());
+    }
+    if transcript.messages.len() > MAX_MESSAGES {
+        problems.push(format!(
+            "The transcript has {} messages (max: {MAX_MESSAGES})",
+            transcript.messages.len()
+        ));
+    }
+    for (idx, message) in transcript.messages.iter().enumerate() {
+        if message.content.trim().is_empty() {
+            problems.push(format!("Message {}: content is empty", idx + 1));
+        }
+    }
+
+    match problems.is_empty() {
+        true => Ok(transcript),
+        false => Err(problems),
+    }
+}
+
+fn parse_json(content: &str) -> (Transcript, Vec<String>) {
+    let mut transcript = Transcript {
+        title: None,
+        messages: Vec::new(),
+    };
+    let json_transcript: JsonTranscript = match serde_json::from_str(content) {
+        Ok(json_transcript) => json_transcript,
+        Err(err) => return (transcript, vec![format!("Invalid JSON: {err}")]),
+    };
+
+    let mut problems = Vec::new();
+    transcript.title = json_transcript.title;
+    for (idx, message) in json_transcript.messages.into_iter().enumerate() {
+        match parse_role(&message.role) {
+            Some(role) => transcript.messages.push(TranscriptMessage {
+                role,
+                content: message.content,
+                created_at: None,
+            }),
+            None => problems.push(format!(
+                "Message {}: unknown role '{}' (expected user, assistant, or system)",
+                idx + 1,
+                message.role
+            )),
+        }
+    }
+
+    (transcript, problems)
+}
+
+fn parse_markdown(content: &str) -> (Transcript, Vec<String>) {
+    let mut transcript = Transcript {
+        title: None,
+        messages: Vec::new(),
+    };
+    let mut problems = Vec::new();
+    let mut current: Option<(ChatRsMessageRole, Vec<&str>)> = None;
+    for (idx, line) in content.lines().enumerate() {
+        if let Some(role) = line.strip_prefix("## ").and_then(parse_role) {
+            if let Some((role, lines)) = current.take() {
+                transcript.messages.push(new_markdown_message(role, &lines));
+            }
+            current = Some((role, Vec::new()));
+            continue;
+        }
+        match current.as_mut() {
+            Some((_, lines)) => lines.push(line),
+            None => match line.strip_prefix("# ") {
+                Some(title) if transcript.title.is_none() => {
+                    transcript.title = Some(title.trim().to_owned())
+                }
+                _ if line.trim().is_empty() => {}
+                _ => problems.push(format!(
+                    "Line {}: text before the first message heading (e.g. `## User`)",
+                    idx + 1
+                )),
+            },
+        }
+    }
+    if let Some((role, lines)) = current {
+        transcript.messages.push(new_markdown_message(role, &lines));
+    }
+
+    (transcript, problems)
+}
+
+fn new_markdown_message(role: ChatRsMessageRole, lines: &[&str]) -> TranscriptMessage {
+    TranscriptMessage {
+        role,
+        content: lines.join("\n").trim().to_owned(),
+        created_at: None,
+    }
+}
+
+fn parse_role(role: &str) -> Option<ChatRsMessageRole> {
+    match role.trim().to_lowercase().as_str() {
+        "user" => Some(ChatRsMessageRole::User),
+        "assistant" => Some(ChatRsMessageRole::Assistant),
+        "system" => Some(ChatRsMessageRole::System),
+        _ => None,
+    }
+}
+
+/// Import the transcripts as new sessions in a background task, reporting the progress to
+/// the stream writer (which should already be started)
+pub fn spawn_bulk_import(
+    user_id: Uuid,
+    transcripts: Vec<Transcript>,
+    default_title: &'static str,
+    writer: ImportStreamWriter,
+    pool: DbPool,
+) {
+    tokio::spawn(async move {
+        let mut progress = ImportProgress {
+            total: transcripts.len(),
+            ..Default::default()
+        };
+        for (idx, transcript) in transcripts.iter().enumerate() {
+            let title = transcript
+                .title
+                .as_deref()
+                .map(str::trim)
+                .filter(|title| !title.is_empty())
+                .unwrap_or(default_title);
+            let result = match transcript.messages.len() > MAX_MESSAGES {
+                true => Err(format!("too many messages (max: {MAX_MESSAGES})")),
+                false => import_transcript(&pool, &user_id, title, transcript)
+                    .await
+                    .map_err(|err| err.to_string()),
+            };
+            match result {
+                Ok(_) => progress.imported += 1,
+                Err(err) => {
+                    progress.failed += 1;
+                    let message = format!("Conversation {} ('{title}'): {err}", idx + 1);
+                    if let Err(err) = writer.error(&message).await {
+                        rocket::warn!("Failed to write import error: {}", err);
+                    }
+                }
+            }
+            if let Err(err) = writer.progress(&progress).await {
+                rocket::warn!("Failed to write import progress: {}", err);
+            }
+        }
+        if let Err(err) = writer.end(&progress).await {
+            rocket::warn!("Failed to write end of import: {}", err);
+        }
+    });
+}
+
+async fn import_transcript(
+    pool: &DbPool,
+    user_id: &Uuid,
+    title: &str,
+    transcript: &Transcript,
+) -> Result<Uuid, ApiError> {
+    let messages: Vec<_> = transcript
+        .messages
+        .iter()
+        .map(|message| (message.role, message.content.as_str(), message.created_at))
+        .collect();
+    let mut db = pool.get().await?;
+    let id = ChatDbService::new(&mut db)
+        .import_session(
+            NewChatRsSession {
+                user_id,
+                title,
+                meta: None,
+                project_id: None,
+            },
+            &messages,
+        )
+        .await?;
+
+    Ok(id)
+}
@@ -0,0 +1,206 @@
+//! Parsing of the conversation exports of ChatGPT and Claude (the `conversations.json` file
+//! of the exported data).
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use rocket::FromFormField;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::db::models::ChatRsMessageRole;
+
+use super::{Transcript, TranscriptMessage};
+
+/// Maximum number of conversations in an imported export.
+const MAX_CONVERSATIONS: usize = 5000;
+
+/// Format of an exported conversations file
+#[derive(Debug, Clone, Copy, FromFormField, JsonSchema)]
+#[schemars(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// `conversations.json` exported from ChatGPT
+    #[field(value = "chat_gpt")]
+    ChatGpt,
+    /// `conversations.json` exported from Claude
+    Claude,
+}
+
+#[derive(Deserialize)]
+struct ChatGptConversation {
+    title: Option<String>,
+    /// Message nodes of the conversation tree, by ID
+    mapping: HashMap<String, ChatGptNode>,
+    /// ID of the last message of the current branch
+    current_node: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatGptNode {
+    message: Option<ChatGptMessage>,
+    parent: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatGptMessage {
+    author: ChatGptAuthor,
+    /// Unix timestamp in seconds
+    create_time: Option<f64>,
+    content: ChatGptContent,
+    #[serde(default)]
+    metadata: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct ChatGptAuthor {
+    role: String,
+}
+
+#[derive(Deserialize)]
+struct ChatGptContent {
+    content_type: String,
+    #[serde(default)]
+    parts: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct ClaudeConversation {
+    name: Option<String>,
+    #[serde(default)]
+    chat_messages: Vec<ClaudeMessage>,
+}
+
+#[derive(Deserialize)]
+struct ClaudeMessage {
+    sender: String,
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    content: Vec<ClaudeContent>,
+    created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+struct ClaudeContent {
+    r#type: String,
+    text: Option<String>,
+}
+
+/// Parse an exported conversations file into transcripts. Conversations without any text
+/// messages are skipped. Returns the problems found if the file is invalid.
+pub fn parse_export(format: ExportFormat, content: &str) -> Result<Vec<Transcript>, Vec<String>> {
+    let transcripts = match format {
+        ExportFormat::ChatGpt => serde_json::from_str::<Vec<ChatGptConversation>>(content)
+            .map(|conversations| conversations.into_iter().map(from_chat_gpt).collect()),
+        ExportFormat::Claude => serde_json::from_str::<Vec<ClaudeConversation>>(content)
+            .map(|conversations| conversations.into_iter().map(from_claude).collect()),
+    };
+    let transcripts: Vec<Transcript> = match transcripts {
+        Ok(transcripts) => transcripts,
+        Err(err) => return Err(vec![format!("Invalid export file: {err}")]),
+    };
+    let transcripts: Vec<Transcript> = transcripts
+        .into_iter()
+        .filter(|transcript| !transcript.messages.is_empty())
+        .collect();
+
+    if transcripts.is_empty() {
+        return Err(vec!["The export has no conversations with messages".into()]);
+    }
+    if transcripts.len() > MAX_CONVERSATIONS {
+        return Err(vec![format!(
+            "The export has {} conversations (max: {MAX_CONVERSATIONS})",
+            transcripts.len()
+        )]);
+    }
+    Ok(transcripts)
+}
+
+/// Convert a ChatGPT conversation, following the current branch of the conversation tree
+/// from the last message back to the first
+fn from_chat_gpt(mut conversation: ChatGptConversation) -> Transcript {
+    let mut messages = Vec::new();
+    let mut node_id = conversation.current_node.take();
+    while let Some(node) = node_id.and_then(|id| conversation.mapping.remove(&id)) {
+        node_id = node.parent;
+        let Some(message) = node.message else {
+            continue;
+        };
+        let role = match message.author.role.as_str() {
+            "user" => ChatRsMessageRole::User,
+            "assistant" => ChatRsMessageRole::Assistant,
+            "system" => ChatRsMessageRole::System,
+            _ => continue, // e.g. tool messages
+        };
+        let is_hidden = message
+            .metadata
+            .get("is_visually_hidden_from_conversation")
+            .and_then(|hidden| hidden.as_bool())
+            .unwrap_or(false);
+        if is_hidden
+            || !matches!(
+                message.content.content_type.as_str(),
+                "text" | "multimodal_text"
+            )
+        {
+            continue;
+        }
+        let content = message
+            .content
+            .parts
+            .iter()
+            .filter_map(|part| part.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if content.trim().is_empty() {
+            continue;
+        }
+        messages.push(TranscriptMessage {
+            role,
+            content: content.trim().to_owned(),
+            created_at: message
+                .create_time
+                .and_then(|time| DateTime::from_timestamp_millis((time * 1000.0) as i64)),
+        });
+    }
+    messages.reverse();
+
+    Transcript {
+        title: conversation.title,
+        messages,
+    }
+}
+
+fn from_claude(conversation: ClaudeConversation) -> Transcript {
+    let messages = conversation
+        .chat_messages
+        .into_iter()
+        .filter_map(|message| {
+            let role = match message.sender.as_str() {
+                "human" => ChatRsMessageRole::User,
+                "assistant" => ChatRsMessageRole::Assistant,
+                _ => return None,
+            };
+            let text_blocks: Vec<&str> = message
+                .content
+                .iter()
+                .filter(|block| block.r#type == "text")
+                .filter_map(|block| block.text.as_deref())
+                .collect();
+            let content = match text_blocks.is_empty() {
+                true => message.text.trim().to_owned(),
+                false => text_blocks.join("\n").trim().to_owned(),
+            };
+            (!content.is_empty()).then_some(TranscriptMessage {
+                role,
+                content,
+                created_at: message.created_at,
+            })
+        })
+        .collect();
+
+    Transcript {
+        title: conversation.name,
+        messages,
+    }
+}
@@ -0,0 +1,238 @@
+//! Background jobs for long, non-interactive requests (e.g. bulk summarization of sessions),
+//! submitted via the Anthropic Message Batches API.
+
+use std::time::Duration;
+
+use chrono::Utc;
+use rocket::fairing::AdHoc;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        models::{
+            ChatRsJob, ChatRsJobData, ChatRsJobResult, ChatRsJobResultItem, ChatRsJobStatus,
+            ChatRsMessage, ChatRsMessageMeta, ChatRsMessageRole, ChatRsProviderType, NewChatRsJob,
+            UpdateChatRsJob,
+        },
+        services::{ChatDbService, JobDbService, ProviderDbService},
+        DbConnection, DbPool,
+    },
+    errors::ApiError,
+    provider::{anthropic::AnthropicProvider, get_extra_headers, LlmError},
+    utils::Encryptor,
+};
+
+/// Interval between checks for results of processing jobs.
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+/// Prompt appended to each session when summarizing sessions.
+const SUMMARIZE_PROMPT: &str = "Summarize the conversation above in a short paragraph. \
+    Include the main topics, questions, and conclusions. Do not add any commentary.";
+
+/// Build the Anthropic provider used to submit and check batch jobs.
+pub async fn build_batch_provider(
+    db: &mut DbConnection,
+    encryptor: &Encryptor,
+    http_client: &reqwest::Client,
+    redis: &fred::clients::Client,
+    user_id: &Uuid,
+    provider_id: i32,
+) -> Result<AnthropicProvider, ApiError> {
+    let (provider, api_key_secret, secondary_api_key_secret) = ProviderDbService::new(db)
+        .get_by_id_with_secrets(user_id, provider_id)
+        .await?;
+    let provider_type: ChatRsProviderType = provider.provider_type.as_str().try_into()?;
+    if !matches!(provider_type, ChatRsProviderType::Anthropic) {
+        return Err(LlmError::UnsupportedProvider)?;
+    }
+    let api_key = api_key_secret
+        .map(|secret| encryptor.decrypt_string(&secret.ciphertext, &secret.nonce))
+        .transpose()?
+        .ok_or(LlmError::MissingApiKey)?;
+    let secondary_api_key = secondary_api_key_secret
+        .map(|secret| encryptor.decrypt_string(&secret.ciphertext, &secret.nonce))
+        .transpose()?;
+    let extra_headers = get_extra_headers(&provider, encryptor)?;
+
+    Ok(AnthropicProvider::new(
+        http_client,
+        redis,
+        &api_key,
+        secondary_api_key.as_deref(),
+        &extra_headers,
+    ))
+}
+
+/// Submit a new job to the provider and save it to the database.
+pub async fn submit_job(
+    db: &mut DbConnection,
+    provider: &AnthropicProvider,
+    user_id: &Uuid,
+    provider_id: i32,
+    data: &ChatRsJobData,
+) -> Result<ChatRsJob, ApiError> {
+    let batch_id = match data {
+        ChatRsJobData::SummarizeSessions {
+            session_ids,
+            options,
+        } => {
+            let mut requests = Vec::with_capacity(session_ids.len());
+            for session_id in session_ids {
+                let (_, mut messages) = ChatDbService::new(db)
+                    .get_session_with_messages(user_id, session_id)
+                    .await?;
+                messages.push(ChatRsMessage {
+                    id: Uuid::new_v4(),
+                    session_id: *session_id,
+                    role: ChatRsMessageRole::User,
+                    content: SUMMARIZE_PROMPT.to_owned(),
+                    meta: ChatRsMessageMeta::default(),
+                    created_at: Utc::now(),
+                    pinned: false,
+                    content_encrypted: false,
+                });
+                requests.push((session_id.to_string(), messages));
+            }
+            provider.create_batch(&requests, options).await?
+        }
+    };
+    let job = JobDbService::new(db)
+        .create(NewChatRsJob {
+            user_id,
+            provider_id,
+            status: (&ChatRsJobStatus::Processing).into(),
+            batch_id: Some(&batch_id),
+            data,
+        })
+        .await?;
+
+    Ok(job)
+}
+
+/// Check the provider for the results of a processing job, and save them if the job
+/// has finished.
+async fn check_job(
+    db: &mut DbConnection,
+    provider: &AnthropicProvider,
+    job: &ChatRsJob,
+) -> Result<(), ApiError> {
+    let batch_id = job.batch_id.as_deref().ok_or(LlmError::NoResponse)?;
+    let Some(results) = provider.get_batch_results(batch_id).await? else {
+        return Ok(());
+    };
+    let mut items: Vec<ChatRsJobResultItem> = results
+        .into_iter()
+        .filter_map(|(custom_id, result)| {
+            let id = Uuid::parse_str(&custom_id).ok()?;
+            let (output, error) = match result {
+                Ok(output) => (Some(output), None),
+                Err(error) => (None, Some(error)),
+            };
+            Some(ChatRsJobResultItem { id, output, error })
+        })
+        .collect();
+    // Mark the items without a result (e.g. if it couldn't be parsed) as errored
+    let ChatRsJobData::SummarizeSessions { session_ids, .. } = &job.data;
+    for session_id in session_ids {
+        if !items.iter().any(|item| item.id == *session_id) {
+            items.push(ChatRsJobResultItem {
+                id: *session_id,
+                output: None,
+                error: Some("Missing result".to_owned()),
+            });
+        }
+    }
+    let result = ChatRsJobResult { items, error: None };
+    JobDbService::new(db)
+        .update(
+            &job.user_id,
+            &job.id,
+            UpdateChatRsJob {
+                status: Some((&ChatRsJobStatus::Completed).into()),
+                result: Some(&result),
+            },
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Check all processing jobs for results
+async fn check_processing_jobs(
+    db_pool: &DbPool,
+    redis: &fred::clients::Client,
+    encryptor: &Encryptor,
+    http_client: &reqwest::Client,
+) -> Result<(), ApiError> {
+    let mut db = db_pool.get().await?;
+    let jobs = JobDbService::new(&mut db).find_processing().await?;
+    for job in jobs {
+        let result = match build_batch_provider(
+            &mut db,
+            encryptor,
+            http_client,
+            redis,
+            &job.user_id,
+            job.provider_id,
+        )
+        .await
+        {
+            Ok(provider) => check_job(&mut db, &provider, &job).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = result {
+            rocket::warn!("Failed to check job {}: {}", job.id, err);
+            if matches!(err, ApiError::Chat(LlmError::ProviderError(_))) {
+                continue; // Try again on the next check
+            }
+            let result = ChatRsJobResult {
+                error: Some(err.to_string()),
+                ..Default::default()
+            };
+            JobDbService::new(&mut db)
+                .update(
+                    &job.user_id,
+                    &job.id,
+                    UpdateChatRsJob {
+                        status: Some((&ChatRsJobStatus::Failed).into()),
+                        result: Some(&result),
+                    },
+                )
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Fairing that spawns a background task to periodically check processing jobs for results.
+pub fn setup_job_polling() -> AdHoc {
+    AdHoc::on_liftoff("Job polling", |rocket| {
+        Box::pin(async move {
+            let (Some(db_pool), Some(redis_pool), Some(encryptor), Some(http_client)) = (
+                rocket.state::<DbPool>(),
+                rocket.state::<fred::clients::Pool>(),
+                rocket.state::<Encryptor>(),
+                rocket.state::<reqwest::Client>(),
+            ) else {
+                rocket::warn!("Job polling not started: missing managed state");
+                return;
+            };
+            let db_pool = db_pool.clone();
+            let redis = redis_pool.next().clone();
+            let encryptor = encryptor.clone();
+            let http_client = http_client.clone();
+
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(POLL_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    if let Err(err) =
+                        check_processing_jobs(&db_pool, &redis, &encryptor, &http_client).await
+                    {
+                        rocket::warn!("Job polling failed: {}", err);
+                    }
+                }
+            });
+        })
+    })
+}
@@ -0,0 +1,149 @@
+//! Rotation of the secret key used for encrypting stored data (secrets, API keys, and
+//! messages). To rotate the key:
+//!
+//! 1. Set the new key as `RS_CHAT_SECRET_KEY`, and add the old key to
+//!    `RS_CHAT_PREVIOUS_SECRET_KEYS`. Data encrypted with the old key can still be decrypted,
+//!    so nothing breaks in the meantime.
+//! 2. Run the server with the `reencrypt` command (e.g. `run-server reencrypt`) to re-encrypt
+//!    the stored secrets, webhook secrets, provider headers, and encrypted messages with the
+//!    new key. It's safe to run it several times, or while the server is running.
+//!
+//! The session cookies encrypted with a previous key are re-issued under the current key on
+//! the next request, so rotating the key doesn't log out the users. API keys embed a value
+//! encrypted with the key at the time they were created, so the old key must stay in
+//! `RS_CHAT_PREVIOUS_SECRET_KEYS` until the API keys created before the rotation are replaced.
+
+use diesel_async::{
+    pooled_connection::{deadpool::Pool, AsyncDieselConnectionManager},
+    AsyncPgConnection,
+};
+
+use crate::{
+    config::{get_config_provider, AppConfig},
+    db::{
+        models::{UpdateChatRsProvider, UpdateChatRsSecret, UpdateChatRsWebhook},
+        services::{ChatDbService, ProviderDbService, SecretDbService, WebhookDbService},
+        DbConnection, DbPool,
+    },
+    errors::ApiError,
+    utils::{reencrypt_message_content, Encryptor, MessageEncryption},
+};
+
+/// Number of values re-encrypted with the current secret key
+#[derive(Debug, Default)]
+pub struct ReencryptionReport {
+    pub secrets: usize,
+    pub webhooks: usize,
+    pub providers: usize,
+    pub messages: usize,
+}
+
+/// Number of messages loaded at a time for re-encryption
+const MESSAGES_BATCH_SIZE: i64 = 500;
+
+/// Re-encrypt all values encrypted with one of the previous secret keys, with the current
+/// secret key. Values already encrypted with the current key are skipped.
+pub async fn reencrypt_stored_data(
+    db: &mut DbConnection,
+    encryptor: &Encryptor,
+) -> Result<ReencryptionReport, ApiError> {
+    let mut report = ReencryptionReport::default();
+
+    for secret in SecretDbService::new(db).find_all().await? {
+        let Some((ciphertext, nonce)) = encryptor.reencrypt(&secret.ciphertext, &secret.nonce)?
+        else {
+            continue;
+        };
+        let update = UpdateChatRsSecret {
+            ciphertext: Some(&ciphertext),
+            nonce: Some(&nonce),
+            ..Default::default()
+        };
+        SecretDbService::new(db)
+            .update(&secret.user_id, &secret.id, update)
+            .await?;
+        report.secrets += 1;
+    }
+
+    for webhook in WebhookDbService::new(db).find_all().await? {
+        let Some((ciphertext, nonce)) =
+            encryptor.reencrypt(&webhook.secret_ciphertext, &webhook.secret_nonce)?
+        else {
+            continue;
+        };
+        let update = UpdateChatRsWebhook {
+            secret_ciphertext: Some(&ciphertext),
+            secret_nonce: Some(&nonce),
+            ..Default::default()
+        };
+        WebhookDbService::new(db)
+            .update(&webhook.user_id, &webhook.id, update)
+            .await?;
+        report.webhooks += 1;
+    }
+
+    for provider in ProviderDbService::new(db).find_with_extra_headers().await? {
+        let (Some(headers), Some(headers_nonce)) =
+            (&provider.extra_headers, &provider.extra_headers_nonce)
+        else {
+            continue;
+        };
+        let Some((ciphertext, nonce)) = encryptor.reencrypt(headers, headers_nonce)? else {
+            continue;
+        };
+        let update = UpdateChatRsProvider {
+            extra_headers: Some(&ciphertext),
+            extra_headers_nonce: Some(&nonce),
+            ..Default::default()
+        };
+        ProviderDbService::new(db)
+            .update(&provider.user_id, provider.id, update)
+            .await?;
+        report.providers += 1;
+    }
+
+    let mut after_id = None;
+    loop {
+        let messages = ChatDbService::new(db)
+            .find_encrypted_messages(after_id, MESSAGES_BATCH_SIZE)
+            .await?;
+        let Some((last_id, _)) = messages.last() else {
+            break;
+        };
+        after_id = Some(*last_id);
+        for (message_id, content) in &messages {
+            let Some(reencrypted) = reencrypt_message_content(encryptor, content)? else {
+                continue;
+            };
+            ChatDbService::new(db)
+                .update_encrypted_content(message_id, &reencrypted)
+                .await?;
+            report.messages += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Run the `reencrypt` command: connect to the database with the server config, and
+/// re-encrypt the stored data with the current secret key.
+pub async fn run_reencrypt_command() -> Result<(), Box<dyn std::error::Error>> {
+    let config: AppConfig = get_config_provider().extract()?;
+    let encryptor = Encryptor::from_config(&config)?;
+    let manager = AsyncDieselConnectionManager::<AsyncPgConnection>::new(&config.database_url);
+    let message_encryption = MessageEncryption::from_config(&config, &encryptor);
+    let pool = DbPool::new(
+        Pool::builder(manager).max_size(1).build()?,
+        message_encryption,
+    );
+    let mut db = pool.get().await?;
+
+    let report = reencrypt_stored_data(&mut db, &encryptor).await?;
+    println!(
+        "Re-encrypted {} secrets, {} webhook secrets, {} provider headers, and {} messages",
+        report.secrets, report.webhooks, report.providers, report.messages
+    );
+    pool.close();
+
+    Ok(())
+}
@@ -0,0 +1,628 @@
+//! Knowledge bases of the user's documents: chunking and embedding of documents, and
+//! semantic search over the chunks (used by the knowledge search tool). Also stores the
+//! memories saved by the memory tool, and the semantic search of chat sessions.
+
+use std::collections::HashSet;
+
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome},
+    Request,
+};
+use rocket_okapi::OpenApiFromRequest;
+use schemars::JsonSchema;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        models::{
+            ChatRsKnowledgeBase, ChatRsKnowledgeDocument, ChatRsKnowledgeSearchResult,
+            ChatRsMemory, ChatRsMessage, ChatRsMessageRole, ChatRsMessageSearchResult,
+            NewChatRsKnowledgeDocument, NewChatRsMemory, NewChatRsMessageEmbedding,
+        },
+        services::{
+            KnowledgeDbService, MemoryDbService, ProviderDbService, SessionSearchDbService,
+        },
+        DbConnection, DbPool,
+    },
+    errors::ApiError,
+    provider::{build_llm_provider_api, get_extra_headers, LlmApiProvider},
+    utils::{full_text_query, Encryptor, SessionSearchResult},
+};
+
+/// Target size of each chunk, in characters
+const CHUNK_SIZE: usize = 1500;
+/// Number of characters repeated at the start of the next chunk
+const CHUNK_OVERLAP: usize = 200;
+/// Max number of chunks embedded in one request to the provider
+const EMBEDDING_BATCH_SIZE: usize = 64;
+/// Max size of a document, in characters
+const MAX_DOCUMENT_LENGTH: usize = 1_000_000;
+/// Max number of memories saved for a user
+const MAX_MEMORIES: i64 = 1000;
+/// Max size of a memory, in characters
+const MAX_MEMORY_LENGTH: usize = 2000;
+/// Max number of characters of a chat message that are embedded for the session search
+const MAX_INDEXED_MESSAGE_LENGTH: usize = 8000;
+/// Number of closest messages fetched per requested session in the semantic search (several
+/// messages of a session can match)
+const SEMANTIC_MATCHES_PER_SESSION: usize = 5;
+/// Constant of the reciprocal rank fusion of search results, which reduces the weight of
+/// the top ranks
+const RRF_K: f64 = 60.0;
+
+/// Knowledge base-related errors
+#[derive(Debug, thiserror::Error)]
+pub enum KnowledgeError {
+    #[error("Document is empty")]
+    EmptyDocument,
+    #[error("The text of the file hasn't been extracted yet, try again later")]
+    TextNotExtracted,
+    #[error("Document is too large (max {MAX_DOCUMENT_LENGTH} characters)")]
+    DocumentTooLarge,
+    #[error("Knowledge base not found")]
+    NotFound,
+    #[error("Provider returned embeddings of different dimensions")]
+    InconsistentEmbeddings,
+    #[error("Memory is empty")]
+    EmptyMemory,
+    #[error("Memory is too long (max {MAX_MEMORY_LENGTH} characters)")]
+    MemoryTooLong,
+    #[error("Too many memories saved (max {MAX_MEMORIES}). Forget some memories first.")]
+    TooManyMemories,
+}
+
+/// A chat session matching a hybrid (full-text and semantic) search
+#[derive(Debug, JsonSchema, Serialize)]
+pub struct HybridSessionSearchResult {
+    pub session_id: Uuid,
+    /// Combined score of the full-text and semantic matches (higher is better)
+    pub score: f64,
+    /// Full-text match of the session, if any
+    pub full_text: Option<SessionSearchResult>,
+    /// Closest message of the session in the semantic search, if any
+    pub semantic: Option<ChatRsMessageSearchResult>,
+}
+
+/// Service to ingest and search the documents of knowledge bases, available as a request
+/// guard. Embeddings are generated by the provider configured for each knowledge base.
+#[derive(Clone, OpenApiFromRequest)]
+pub struct KnowledgeService {
+    db_pool: DbPool,
+    encryptor: Encryptor,
+    http_client: reqwest::Client,
+    redis: fred::clients::Client,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for KnowledgeService {
+    type Error = &'static str;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let rocket = req.rocket();
+        let (Some(db_pool), Some(redis_pool), Some(encryptor), Some(http_client)) = (
+            rocket.state::<DbPool>(),
+            rocket.state::<fred::clients::Pool>(),
+            rocket.state::<Encryptor>(),
+            rocket.state::<reqwest::Client>(),
+        ) else {
+            return Outcome::Error((Status::InternalServerError, "Missing managed state"));
+        };
+        Outcome::Success(KnowledgeService {
+            db_pool: db_pool.clone(),
+            encryptor: encryptor.clone(),
+            http_client: http_client.clone(),
+            redis: redis_pool.next().clone(),
+        })
+    }
+}
+
+impl KnowledgeService {
+    /// The Redis client, also used by tools (e.g. for usage limits)
+    pub fn redis(&self) -> &fred::clients::Client {
+        &self.redis
+    }
+
+    /// Split the document into chunks, embed them, and save the document to the knowledge base
+    pub async fn add_document(
+        &self,
+        user_id: &Uuid,
+        knowledge_base: &ChatRsKnowledgeBase,
+        name: &str,
+        content: &str,
+    ) -> Result<ChatRsKnowledgeDocument, ApiError> {
+        let content_length = content.chars().count();
+        if content.trim().is_empty() {
+            return Err(KnowledgeError::EmptyDocument)?;
+        }
+        if content_length > MAX_DOCUMENT_LENGTH {
+            return Err(KnowledgeError::DocumentTooLarge)?;
+        }
+
+        let mut db = self.db_pool.get().await?;
+        let provider_api = self
+            .build_embedding_provider(&mut db, user_id, knowledge_base.provider_id)
+            .await?;
+        let chunks = chunk_text(content, CHUNK_SIZE, CHUNK_OVERLAP);
+        let mut embeddings = Vec::with_capacity(chunks.len());
+        for batch in chunks.chunks(EMBEDDING_BATCH_SIZE) {
+            let batch_embeddings = provider_api
+                .embed(batch, &knowledge_base.embedding_model)
+                .await?;
+            embeddings.extend(batch_embeddings);
+        }
+        if embeddings
+            .windows(2)
+            .any(|pair| pair[0].len() != pair[1].len())
+        {
+            return Err(KnowledgeError::InconsistentEmbeddings)?;
+        }
+
+        let document = KnowledgeDbService::new(&mut db)
+            .add_document(
+                NewChatRsKnowledgeDocument {
+                    knowledge_base_id: &knowledge_base.id,
+                    name,
+                    content_length: content_length as i32,
+                    chunk_count: chunks.len() as i32,
+                },
+                chunks
+                    .iter()
+                    .map(String::as_str)
+                    .zip(embeddings.into_iter().map(pgvector::Vector::from))
+                    .collect(),
+            )
+            .await?;
+
+        Ok(document)
+    }
+
+    /// List the user's knowledge bases
+    pub async fn list(&self, user_id: &Uuid) -> Result<Vec<ChatRsKnowledgeBase>, ApiError> {
+        let mut db = self.db_pool.get().await?;
+        let knowledge_bases = KnowledgeDbService::new(&mut db)
+            .find_by_user_id(user_id)
+            .await?;
+
+        Ok(knowledge_bases)
+    }
+
+    /// Search the knowledge base for the chunks most similar to the query
+    pub async fn search(
+        &self,
+        user_id: &Uuid,
+        knowledge_base_id: &Uuid,
+        query: &str,
+        limit: u8,
+    ) -> Result<Vec<ChatRsKnowledgeSearchResult>, ApiError> {
+        let mut db = self.db_pool.get().await?;
+        let knowledge_base = KnowledgeDbService::new(&mut db)
+            .find_by_id(user_id, knowledge_base_id)
+            .await?
+            .ok_or(KnowledgeError::NotFound)?;
+        let provider_api = self
+            .build_embedding_provider(&mut db, user_id, knowledge_base.provider_id)
+            .await?;
+        let embedding = provider_api
+            .embed(&[query.to_owned()], &knowledge_base.embedding_model)
+            .await?
+            .pop()
+            .ok_or(KnowledgeError::InconsistentEmbeddings)?;
+        let results = KnowledgeDbService::new(&mut db)
+            .search(&knowledge_base.id, &embedding.into(), limit.into())
+            .await?;
+
+        Ok(results)
+    }
+
+    /// Save a memory for the user, embedded with the given provider and model if set
+    pub async fn remember(
+        &self,
+        user_id: &Uuid,
+        content: &str,
+        embedding: Option<(i32, &str)>,
+    ) -> Result<ChatRsMemory, ApiError> {
+        if content.trim().is_empty() {
+            return Err(KnowledgeError::EmptyMemory)?;
+        }
+        if content.chars().count() > MAX_MEMORY_LENGTH {
+            return Err(KnowledgeError::MemoryTooLong)?;
+        }
+        let mut db = self.db_pool.get().await?;
+        if MemoryDbService::new(&mut db).count(user_id).await? >= MAX_MEMORIES {
+            return Err(KnowledgeError::TooManyMemories)?;
+        }
+        let vector = match embedding {
+            Some((provider_id, model)) => {
+                let provider_api = self
+                    .build_embedding_provider(&mut db, user_id, provider_id)
+                    .await?;
+                let embedding = provider_api
+                    .embed(&[content.to_owned()], model)
+                    .await?
+                    .pop()
+                    .ok_or(KnowledgeError::InconsistentEmbeddings)?;
+                Some(pgvector::Vector::from(embedding))
+            }
+            None => None,
+        };
+        let memory = MemoryDbService::new(&mut db)
+            .create(NewChatRsMemory {
+                user_id,
+                content: content.trim(),
+                embedding_model: embedding
+                    .filter(|_| vector.is_some())
+                    .map(|(_, model)| model),
+                embedding: vector,
+            })
+            .await?;
+
+        Ok(memory)
+    }
+
+    /// Recall the user's memories most relevant to the query, or the most recent memories
+    /// if there's no query. Memories are matched by embedding similarity if the provider and
+    /// model are set, otherwise by keywords.
+    pub async fn recall(
+        &self,
+        user_id: &Uuid,
+        query: Option<&str>,
+        embedding: Option<(i32, &str)>,
+        limit: u8,
+    ) -> Result<Vec<ChatRsMemory>, ApiError> {
+        let mut db = self.db_pool.get().await?;
+        let Some(query) = query.map(str::trim).filter(|query| !query.is_empty()) else {
+            let memories = MemoryDbService::new(&mut db)
+                .find_by_user_id(user_id, limit.into())
+                .await?;
+            return Ok(memories);
+        };
+        let memories = match embedding {
+            Some((provider_id, model)) => {
+                let provider_api = self
+                    .build_embedding_provider(&mut db, user_id, provider_id)
+                    .await?;
+                let embedding = provider_api
+                    .embed(&[query.to_owned()], model)
+                    .await?
+                    .pop()
+                    .ok_or(KnowledgeError::InconsistentEmbeddings)?;
+                MemoryDbService::new(&mut db)
+                    .search(user_id, model, &embedding.into(), limit.into())
+                    .await?
+            }
+            None => {
+                let memories = MemoryDbService::new(&mut db)
+                    .find_by_user_id(user_id, MAX_MEMORIES)
+                    .await?;
+                rank_by_keywords(memories, query, limit.into())
+            }
+        };
+
+        Ok(memories)
+    }
+
+    /// Delete one of the user's memories
+    pub async fn forget(&self, user_id: &Uuid, memory_id: &Uuid) -> Result<Uuid, ApiError> {
+        let mut db = self.db_pool.get().await?;
+        let id = MemoryDbService::new(&mut db)
+            .delete(user_id, memory_id)
+            .await?;
+
+        Ok(id)
+    }
+
+    /// Embed a chat message and save it for the semantic search of sessions, in the
+    /// background. Does nothing if the user hasn't set up the semantic search.
+    pub fn index_message(&self, user_id: &Uuid, message: &ChatRsMessage) {
+        if !matches!(
+            message.role,
+            ChatRsMessageRole::User | ChatRsMessageRole::Assistant
+        ) || message.content.trim().is_empty()
+        {
+            return;
+        }
+        let service = self.clone();
+        let (user_id, session_id, message_id) = (*user_id, message.session_id, message.id);
+        let content: String = message
+            .content
+            .chars()
+            .take(MAX_INDEXED_MESSAGE_LENGTH)
+            .collect();
+        tokio::spawn(async move {
+            if let Err(err) = service
+                .embed_message(&user_id, &session_id, &message_id, content)
+                .await
+            {
+                rocket::warn!("Failed to index message {}: {}", message_id, err);
+            }
+        });
+    }
+
+    /// Search the user's chat sessions by full-text search, and also by semantic search if
+    /// it's set up. The results are merged by reciprocal rank fusion.
+    pub async fn search_sessions(
+        &self,
+        user_id: &Uuid,
+        query: &str,
+        limit: u8,
+    ) -> Result<Vec<HybridSessionSearchResult>, ApiError> {
+        let mut db = self.db_pool.get().await?;
+        let full_text = full_text_query(&mut db, user_id, query, limit.into()).await?;
+        let Some(settings) = SessionSearchDbService::new(&mut db)
+            .get_settings(user_id)
+            .await?
+        else {
+            return Ok(merge_session_results(full_text, Vec::new(), limit.into()));
+        };
+
+        let provider_api = self
+            .build_embedding_provider(&mut db, user_id, settings.provider_id)
+            .await?;
+        let embedding = provider_api
+            .embed(&[query.to_owned()], &settings.embedding_model)
+            .await?
+            .pop()
+            .ok_or(KnowledgeError::InconsistentEmbeddings)?;
+        let mut semantic = SessionSearchDbService::new(&mut db)
+            .search(
+                user_id,
+                &settings.embedding_model,
+                &embedding.into(),
+                (usize::from(limit) * SEMANTIC_MATCHES_PER_SESSION) as i64,
+            )
+            .await?;
+        // Keep the closest message of each session
+        let mut seen_sessions = HashSet::new();
+        semantic.retain(|result| seen_sessions.insert(result.session_id));
+
+        Ok(merge_session_results(full_text, semantic, limit.into()))
+    }
+
+    async fn embed_message(
+        &self,
+        user_id: &Uuid,
+        session_id: &Uuid,
+        message_id: &Uuid,
+        content: String,
+    ) -> Result<(), ApiError> {
+        let mut db = self.db_pool.get().await?;
+        let Some(settings) = SessionSearchDbService::new(&mut db)
+            .get_settings(user_id)
+            .await?
+        else {
+            return Ok(());
+        };
+        let provider_api = self
+            .build_embedding_provider(&mut db, user_id, settings.provider_id)
+            .await?;
+        let embedding = provider_api
+            .embed(&[content], &settings.embedding_model)
+            .await?
+            .pop()
+            .ok_or(KnowledgeError::InconsistentEmbeddings)?;
+        SessionSearchDbService::new(&mut db)
+            .save_embedding(NewChatRsMessageEmbedding {
+                message_id,
+                session_id,
+                user_id,
+                embedding_model: &settings.embedding_model,
+                embedding: embedding.into(),
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn build_embedding_provider(
+        &self,
+        db: &mut DbConnection,
+        user_id: &Uuid,
+        provider_id: i32,
+    ) -> Result<Box<dyn LlmApiProvider>, ApiError> {
+        let (provider, api_key_secret, secondary_api_key_secret) = ProviderDbService::new(db)
+            .get_by_id_with_secrets(user_id, provider_id)
+            .await?;
+        let api_key = api_key_secret
+            .map(|secret| {
+                self.encryptor
+                    .decrypt_string(&secret.ciphertext, &secret.nonce)
+            })
+            .transpose()?;
+        let secondary_api_key = secondary_api_key_secret
+            .map(|secret| {
+                self.encryptor
+                    .decrypt_string(&secret.ciphertext, &secret.nonce)
+            })
+            .transpose()?;
+        let extra_headers = get_extra_headers(&provider, &self.encryptor)?;
+        let provider_api = build_llm_provider_api(
+            &provider.provider_type.as_str().try_into()?,
+            provider.base_url.as_deref(),
+            api_key.as_deref(),
+            secondary_api_key.as_deref(),
+            &extra_headers,
+            &self.http_client,
+            &self.redis,
+        )?;
+
+        Ok(provider_api)
+    }
+}
+
+/// Split the text into chunks of about `chunk_size` characters, overlapping by `overlap`
+/// characters. Chunks end at a paragraph, line, sentence, or word break if possible.
+fn chunk_text(text: &str, chunk_size: usize, overlap: usize) -> Vec<String> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let byte_index = |char_index: usize| chars.get(char_index).map_or(text.len(), |(i, _)| *i);
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        let mut end = (start + chunk_size).min(chars.len());
+        if end < chars.len() {
+            // Look for a break in the second half of the chunk
+            let window = &text[byte_index(start + chunk_size / 2)..byte_index(end)];
+            let break_offset = ["\n\n", "\n", ". ", " "]
+                .into_iter()
+                .find_map(|separator| window.rfind(separator).map(|index| index + separator.len()));
+            if let Some(offset) = break_offset {
+                let break_byte = byte_index(start + chunk_size / 2) + offset;
+                end = chars.partition_point(|(i, _)| *i < break_byte);
+            }
+        }
+
+        let chunk = text[byte_index(start)..byte_index(end)].trim();
+        if !chunk.is_empty() {
+            chunks.push(chunk.to_owned());
+        }
+        if end >= chars.len() {
+            break;
+        }
+        // Start the overlap at a word if possible
+        let overlap_start = end.saturating_sub(overlap).max(start + 1);
+        start = chars[overlap_start..end]
+            .iter()
+            .position(|(_, c)| c.is_whitespace())
+            .map_or(overlap_start, |position| overlap_start + position + 1);
+    }
+    chunks
+}
+
+/// Rank the memories by the number of query words they contain (ignoring case), keeping
+/// only the memories that match at least one word. Ties are kept in their original order.
+fn rank_by_keywords(memories: Vec<ChatRsMemory>, query: &str, limit: usize) -> Vec<ChatRsMemory> {
+    let words: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 2)
+        .map(str::to_lowercase)
+        .collect();
+    let mut ranked: Vec<(usize, ChatRsMemory)> = memories
+        .into_iter()
+        .filter_map(|memory| {
+            let content = memory.content.to_lowercase();
+            let matches = words.iter().filter(|word| content.contains(*word)).count();
+            (matches > 0).then_some((matches, memory))
+        })
+        .collect();
+    ranked.sort_by(|(a, _), (b, _)| b.cmp(a));
+    ranked.truncate(limit);
+    ranked.into_iter().map(|(_, memory)| memory).collect()
+}
+
+/// Merge the full-text and semantic matches of sessions (each ordered from best to worst)
+/// by reciprocal rank fusion: each session scores `1 / (RRF_K + rank)` in each list it's in.
+/// Ties are kept in the full-text order.
+fn merge_session_results(
+    full_text: Vec<SessionSearchResult>,
+    semantic: Vec<ChatRsMessageSearchResult>,
+    limit: usize,
+) -> Vec<HybridSessionSearchResult> {
+    let rrf_score = |rank: usize| 1.0 / (RRF_K + rank as f64 + 1.0);
+    let mut results: Vec<HybridSessionSearchResult> = full_text
+        .into_iter()
+        .enumerate()
+        .map(|(rank, result)| HybridSessionSearchResult {
+            session_id: result.session_id,
+            score: rrf_score(rank),
+            full_text: Some(result),
+            semantic: None,
+        })
+        .collect();
+    for (rank, result) in semantic.into_iter().enumerate() {
+        match results
+            .iter_mut()
+            .find(|existing| existing.session_id == result.session_id)
+        {
+            Some(existing) => {
+                existing.score += rrf_score(rank);
+                existing.semantic = Some(result);
+            }
+            None => results.push(HybridSessionSearchResult {
+                session_id: result.session_id,
+                score: rrf_score(rank),
+                full_text: None,
+                semantic: Some(result),
+            }),
+        }
+    }
+    results.sort_by(|a, b| b.score.total_cmp(&a.score));
+    results.truncate(limit);
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::{chunk_text, merge_session_results};
+    use crate::{db::models::ChatRsMessageSearchResult, utils::SessionSearchResult};
+
+    #[test]
+    fn test_chunk_short_text() {
+        assert_eq!(
+            chunk_text("  Hello world!  ", 100, 10),
+            vec!["Hello world!"]
+        );
+        assert!(chunk_text("   ", 100, 10).is_empty());
+    }
+
+    #[test]
+    fn test_chunk_at_breaks_with_overlap() {
+        let text = "First sentence here. Second sentence here. Third sentence.";
+        let chunks = chunk_text(text, 30, 10);
+        assert_eq!(chunks[0], "First sentence here.");
+        assert!(chunks[1].starts_with("here. Second"));
+        assert!(chunks.last().unwrap().ends_with("Third sentence."));
+        assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 30));
+    }
+
+    #[test]
+    fn test_chunk_multibyte_text() {
+        let text = "é".repeat(250);
+        let chunks = chunk_text(&text, 100, 20);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|chunk| chunk.chars().all(|c| c == 'é')));
+    }
+
+    fn full_text_result(session_id: Uuid) -> SessionSearchResult {
+        SessionSearchResult {
+            session_id,
+            session_rank: 0.5,
+            session_updated_at: chrono::Utc::now(),
+            message_matches: 1,
+            title_highlight: String::new(),
+            message_highlights: String::new(),
+            message_encrypted: false,
+        }
+    }
+
+    fn semantic_result(session_id: Uuid) -> ChatRsMessageSearchResult {
+        ChatRsMessageSearchResult {
+            session_id,
+            session_title: String::new(),
+            message_id: Uuid::new_v4(),
+            content: String::new(),
+            distance: 0.2,
+        }
+    }
+
+    #[test]
+    fn test_merge_session_results() {
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let results = merge_session_results(
+            vec![full_text_result(a), full_text_result(b)],
+            vec![semantic_result(b), semantic_result(c)],
+            10,
+        );
+        let ids: Vec<Uuid> = results.iter().map(|result| result.session_id).collect();
+        assert_eq!(ids, vec![b, a, c]);
+        assert!(results[0].full_text.is_some() && results[0].semantic.is_some());
+        assert!(results[2].full_text.is_none());
+
+        let results = merge_session_results(vec![full_text_result(a)], Vec::new(), 10);
+        assert_eq!(results.len(), 1);
+        assert!(merge_session_results(Vec::new(), vec![semantic_result(c)], 0).is_empty());
+    }
+}
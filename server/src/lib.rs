@@ -1,15 +1,35 @@
+pub mod agent;
 pub mod api;
+pub mod attachments;
 pub mod auth;
+pub mod backup;
 pub mod config;
+pub mod config_check;
+pub mod data_export;
 pub mod db;
 pub mod errors;
+pub mod extraction;
+pub mod import;
+pub mod jobs;
+pub mod key_rotation;
+pub mod knowledge;
+pub mod load_test;
 pub mod provider;
+pub mod provider_debug;
+pub mod provider_health;
 pub mod provider_models;
 pub mod redis;
+pub mod retention;
+pub mod scan;
+pub mod scheduler;
+pub mod shutdown;
+pub mod storage;
 pub mod stream;
 pub mod tools;
+pub mod trash;
 pub mod utils;
 pub mod web;
+pub mod webhooks;
 
 use rocket::{fairing::AdHoc, get};
 use rocket_okapi::{mount_endpoints_and_merged_docs, openapi, openapi_get_routes_spec};
@@ -17,25 +37,51 @@ use rocket_okapi::{mount_endpoints_and_merged_docs, openapi, openapi_get_routes_
 use crate::{
     auth::setup_auth,
     config::{get_config_provider, AppConfig},
+    config_check::setup_config_check,
     db::setup_db,
     errors::get_catchers,
+    jobs::setup_job_polling,
+    provider_health::setup_provider_health,
     redis::setup_redis,
+    retention::setup_retention,
+    scan::setup_file_scanner,
+    scheduler::setup_scheduler,
+    shutdown::setup_graceful_shutdown,
+    storage::setup_storage,
+    stream::setup_stream_backend,
+    tools::{setup_code_runner_image_pool, setup_code_runner_wasm_path},
+    trash::setup_trash_purge,
     utils::setup_encryption,
     web::setup_static_files,
+    webhooks::setup_webhook_delivery,
 };
 
 /// Build the rocket server, load configuration and routes, prepare for launch
 pub fn build_rocket() -> rocket::Rocket<rocket::Build> {
     let mut server = rocket::custom(get_config_provider())
         .attach(AdHoc::config::<AppConfig>())
+        .attach(setup_config_check())
+        .attach(setup_encryption())
         .attach(setup_db())
         .attach(setup_redis())
-        .attach(setup_encryption())
+        .attach(setup_stream_backend())
+        .attach(setup_graceful_shutdown())
+        .attach(setup_storage())
+        .attach(setup_file_scanner())
         .attach(setup_auth("/api/auth"))
         .attach(setup_static_files())
+        .attach(setup_provider_health())
+        .attach(setup_job_polling())
+        .attach(setup_code_runner_image_pool())
+        .attach(setup_code_runner_wasm_path())
+        .attach(setup_trash_purge())
+        .attach(setup_retention())
+        .attach(setup_scheduler())
+        .attach(setup_webhook_delivery())
         .manage(reqwest::Client::new())
         .register("/", get_catchers())
-        .mount("/api/docs", get_doc_routes());
+        .mount("/api/docs", get_doc_routes())
+        .mount("/api/admin", api::admin_routes());
 
     let openapi_settings = rocket_okapi::settings::OpenApiSettings::default();
     mount_endpoints_and_merged_docs! {
@@ -44,11 +90,26 @@ pub fn build_rocket() -> rocket::Rocket<rocket::Build> {
         "/info" => api::info_routes(&openapi_settings),
         "/auth" => api::auth_routes(&openapi_settings),
         "/provider" => api::provider_routes(&openapi_settings),
+        "/preset" => api::preset_routes(&openapi_settings),
+        "/persona" => api::persona_routes(&openapi_settings),
+        "/organization" => api::organization_routes(&openapi_settings),
+        "/project" => api::project_routes(&openapi_settings),
         "/session" => api::session_routes(&openapi_settings),
         "/chat" => api::chat_routes(&openapi_settings),
+        "/jobs" => api::job_routes(&openapi_settings),
+        "/schedule" => api::schedule_routes(&openapi_settings),
+        "/webhook" => api::webhook_routes(&openapi_settings),
         "/tool" => api::tool_routes(&openapi_settings),
         "/secret" => api::secret_routes(&openapi_settings),
+        "/file" => api::file_routes(&openapi_settings),
+        "/knowledge" => api::knowledge_routes(&openapi_settings),
+        "/memory" => api::memory_routes(&openapi_settings),
         "/api_key" => api::api_key_routes(&openapi_settings),
+        "/usage" => api::usage_routes(&openapi_settings),
+        "/retention" => api::retention_routes(&openapi_settings),
+        "/admin/users" => api::user_routes(&openapi_settings),
+        "/admin/service-accounts" => api::service_account_routes(&openapi_settings),
+        "/admin/backup" => api::backup_routes(&openapi_settings),
     };
 
     server
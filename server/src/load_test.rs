@@ -0,0 +1,132 @@
+//! Synthetic traffic for load testing a deployment, using the Lorem provider
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use rocket::futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    db::DbPool,
+    provider::{
+        lorem::{LoremConfig, LoremProvider},
+        LlmApiProvider, LlmProviderOptions,
+    },
+    stream::{LlmStreamWriter, StreamClient},
+};
+
+/// Maximum number of synthetic streams in one load test
+const MAX_STREAMS: usize = 1000;
+/// Minimum interval in milliseconds between chunks of a synthetic stream
+const MIN_CHUNK_INTERVAL: u32 = 10;
+
+/// Configuration of a load test
+#[derive(Debug, Deserialize)]
+pub struct LoadTestConfig {
+    /// Number of concurrent synthetic chat streams (max 1000, and half of the database pool)
+    pub streams: usize,
+    /// Interval in milliseconds between chunks of each stream (default: 100, min: 10)
+    pub chunk_interval_ms: Option<u32>,
+}
+
+/// Results of a load test
+#[derive(Debug, Serialize)]
+pub struct LoadTestReport {
+    /// Number of synthetic streams started
+    pub streams: usize,
+    /// Number of streams that completed successfully
+    pub completed: usize,
+    /// Number of streams that failed
+    pub failed: usize,
+    /// Count of each error encountered
+    pub errors: HashMap<String, usize>,
+    /// Total duration of the load test in milliseconds
+    pub duration_ms: u64,
+    /// Longest duration of a completed stream in milliseconds
+    pub max_stream_ms: u64,
+}
+
+/// Run concurrent synthetic chat streams using the Lorem provider. Like a real chat stream,
+/// each one holds a database connection while writing the response to the configured stream
+/// backend. The number of streams is limited to half of the database pool, so that the server
+/// can still handle requests during the test.
+pub async fn run_load_test(
+    config: &LoadTestConfig,
+    db_pool: &DbPool,
+    stream_client: StreamClient,
+) -> LoadTestReport {
+    let streams = config.streams.min(MAX_STREAMS).min(db_pool.max_size() / 2);
+    let interval = config
+        .chunk_interval_ms
+        .unwrap_or(100)
+        .max(MIN_CHUNK_INTERVAL);
+    let user_id = Uuid::new_v4();
+
+    let start = Instant::now();
+    let tasks = (0..streams).map(|_| {
+        let db_pool = db_pool.clone();
+        let stream_client = stream_client.clone();
+        tokio::spawn(async move {
+            run_synthetic_stream(&db_pool, stream_client, &user_id, interval).await
+        })
+    });
+    let results = join_all(tasks).await;
+    let duration = start.elapsed();
+
+    let mut report = LoadTestReport {
+        streams,
+        completed: 0,
+        failed: 0,
+        errors: HashMap::new(),
+        duration_ms: get_millis(duration),
+        max_stream_ms: 0,
+    };
+    for result in results {
+        match result.map_err(|err| err.to_string()).and_then(|r| r) {
+            Ok(stream_duration) => {
+                report.completed += 1;
+                report.max_stream_ms = report.max_stream_ms.max(get_millis(stream_duration));
+            }
+            Err(err) => {
+                report.failed += 1;
+                *report.errors.entry(err).or_default() += 1;
+            }
+        }
+    }
+
+    report
+}
+
+async fn run_synthetic_stream(
+    db_pool: &DbPool,
+    stream_client: StreamClient,
+    user_id: &Uuid,
+    interval: u32,
+) -> Result<Duration, String> {
+    let start = Instant::now();
+    let _db = db_pool.get().await.map_err(|e| e.to_string())?;
+
+    let provider = LoremProvider {
+        config: LoremConfig { interval },
+    };
+    let stream = provider
+        .chat_stream(vec![], None, &LlmProviderOptions::default())
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut writer = LlmStreamWriter::new(stream_client, user_id, &Uuid::new_v4());
+    writer.start().await.map_err(|e| e.to_string())?;
+    let (.., cancelled) = writer.process(stream).await;
+    if cancelled {
+        return Err("Stream was cancelled".into());
+    }
+    writer.end().await.map_err(|e| e.to_string())?;
+
+    Ok(start.elapsed())
+}
+
+fn get_millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
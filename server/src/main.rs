@@ -1,11 +1,23 @@
-use chat_rs_api::{build_rocket, utils::setup_json_logging};
-use rocket::launch;
+use chat_rs_api::{
+    build_rocket, config_check::run_check_config_command, key_rotation::run_reencrypt_command,
+    utils::setup_json_logging,
+};
 
-#[launch]
-pub fn rocket() -> _ {
+#[rocket::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
     if cfg!(not(debug_assertions)) {
         setup_json_logging();
     }
 
-    build_rocket()
+    match std::env::args().nth(1).as_deref() {
+        // Re-encrypt stored data after rotating the secret key (see `key_rotation` module)
+        Some("reencrypt") => run_reencrypt_command().await,
+        // Check the config and connections without starting the server (see `config_check` module)
+        Some("--check-config") => run_check_config_command().await,
+        _ => {
+            // Launch errors are logged when dropped
+            let _ = build_rocket().launch().await;
+            Ok(())
+        }
+    }
 }
@@ -1,27 +1,33 @@
 //! LLM providers API
 
 pub mod anthropic;
+mod error_code;
 pub mod lorem;
 pub mod ollama;
 pub mod openai;
 mod utils;
 
-use std::pin::Pin;
+use std::{collections::HashMap, pin::Pin};
 
 use dyn_clone::DynClone;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use rocket::{async_trait, futures::Stream};
 use schemars::JsonSchema;
 use uuid::Uuid;
 
 use crate::{
-    db::models::{ChatRsMessage, ChatRsProviderType, ChatRsToolCall},
+    db::models::{ChatRsMessage, ChatRsProvider, ChatRsProviderType, ChatRsToolCall},
     provider::{
         anthropic::AnthropicProvider, lorem::LoremProvider, ollama::OllamaProvider,
         openai::OpenAIProvider,
     },
+    provider_debug::ProviderDebugLogger,
     provider_models::LlmModel,
+    utils::Encryptor,
 };
 
+pub use error_code::{classify_provider_error, LlmErrorCode, LlmErrorInfo};
+
 pub const DEFAULT_MAX_TOKENS: u32 = 2000;
 pub const DEFAULT_TEMPERATURE: f32 = 0.7;
 
@@ -38,8 +44,16 @@ pub enum LlmError {
     NoResponse,
     #[error("Unsupported provider")]
     UnsupportedProvider,
+    #[error("Invalid header: {0}")]
+    InvalidHeader(String),
+    #[error("{0}")]
+    ModelRetired(String),
+    #[error("No provider or options given, and no preset selected")]
+    MissingProviderConfig,
     #[error("Already streaming a response for this session")]
     AlreadyStreaming,
+    #[error("The server is shutting down, try again later")]
+    ShuttingDown,
     #[error("No stream found, or the stream was cancelled")]
     StreamNotFound,
     #[error("Missing event in stream")]
@@ -67,6 +81,8 @@ pub enum LlmStreamError {
     StreamTimeout,
     #[error("Stream was cancelled")]
     StreamCancelled,
+    #[error("Stream was interrupted by a server shutdown")]
+    StreamInterrupted,
     #[error("Redis error: {0}")]
     Redis(#[from] fred::error::Error),
 }
@@ -83,12 +99,18 @@ pub enum LlmStreamChunk {
     ToolCalls(Vec<ChatRsToolCall>),
     PendingToolCall(LlmPendingToolCall),
     Usage(LlmUsage),
+    FinishReason(LlmFinishReason),
 }
 
+/// A tool call that is still being generated
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct LlmPendingToolCall {
     pub index: usize,
     pub tool_name: String,
+    /// New text of the JSON arguments since the last chunk (append to the previous chunks
+    /// with the same `index` to get the arguments generated so far)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arguments_delta: Option<String>,
 }
 
 /// Usage stats from the LLM provider
@@ -101,8 +123,44 @@ pub struct LlmUsage {
     pub cost: Option<f32>,
 }
 
+/// The reason the LLM provider stopped generating the response
+#[derive(Debug, Clone, Copy, PartialEq, JsonSchema, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LlmFinishReason {
+    /// The response was completed, or a stop sequence was reached
+    Stop,
+    /// The maximum number of tokens was reached (the response is truncated)
+    Length,
+    /// The assistant requested tool calls
+    ToolCalls,
+    /// The response was filtered or refused by the provider
+    ContentFilter,
+    /// Any other reason given by the provider
+    Other,
+}
+
+/// A file attached to a user message, given to multimodal providers
+#[derive(Debug, Clone)]
+pub struct LlmAttachment {
+    pub name: String,
+    pub mime_type: String,
+    /// Base64-encoded content of the file
+    pub data: String,
+}
+
+impl LlmAttachment {
+    pub fn is_image(&self) -> bool {
+        self.mime_type.starts_with("image/")
+    }
+
+    /// The content of the file as a `data:` URL
+    pub fn data_url(&self) -> String {
+        format!("data:{};base64,{}", self.mime_type, self.data)
+    }
+}
+
 /// Shared configuration for LLM provider requests
-#[derive(Clone, Debug, Default, JsonSchema, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, JsonSchema, serde::Serialize, serde::Deserialize)]
 pub struct LlmProviderOptions {
     pub model: String,
     pub temperature: Option<f32>,
@@ -110,7 +168,7 @@ pub struct LlmProviderOptions {
 }
 
 /// Generic tool that can be passed to LLM providers
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LlmTool {
     pub name: String,
     pub description: String,
@@ -127,6 +185,7 @@ pub enum LlmToolType {
     #[default]
     System,
     ExternalApi,
+    Mcp,
 }
 
 /// Unified API for LLM providers
@@ -146,6 +205,19 @@ pub trait LlmApiProvider: Send + Sync + DynClone {
 
     /// List available models from the provider
     async fn list_models(&self) -> Result<Vec<LlmModel>, LlmError>;
+
+    /// Perform a cheap request to check that the provider is reachable and the API key is valid
+    async fn health_check(&self) -> Result<(), LlmError>;
+
+    /// Generate embeddings of the inputs with the given model, in the same order as the inputs.
+    /// Not supported by all providers.
+    async fn embed(&self, _inputs: &[String], _model: &str) -> Result<Vec<Vec<f32>>, LlmError> {
+        Err(LlmError::UnsupportedProvider)
+    }
+
+    /// Capture the raw requests and stream chunks of chat streams, for troubleshooting.
+    /// Not supported by all providers.
+    fn set_debug_logger(&mut self, _logger: ProviderDebugLogger) {}
 }
 
 /// Build the LLM API to make calls to the provider
@@ -153,6 +225,8 @@ pub fn build_llm_provider_api(
     provider_type: &ChatRsProviderType,
     base_url: Option<&str>,
     api_key: Option<&str>,
+    secondary_api_key: Option<&str>,
+    extra_headers: &HeaderMap,
     http_client: &reqwest::Client,
     redis: &fred::clients::Client,
 ) -> Result<Box<dyn LlmApiProvider>, LlmError> {
@@ -161,17 +235,52 @@ pub fn build_llm_provider_api(
             http_client,
             redis,
             api_key.ok_or(LlmError::MissingApiKey)?,
+            secondary_api_key,
             base_url,
+            extra_headers,
         ))),
         ChatRsProviderType::Anthropic => Ok(Box::new(AnthropicProvider::new(
             http_client,
             redis,
             api_key.ok_or(LlmError::MissingApiKey)?,
+            secondary_api_key,
+            extra_headers,
         ))),
         ChatRsProviderType::Ollama => Ok(Box::new(OllamaProvider::new(
             http_client,
             base_url.unwrap_or("http://localhost:11434"),
+            extra_headers,
         ))),
         ChatRsProviderType::Lorem => Ok(Box::new(LoremProvider::new())),
     }
 }
+
+/// Convert a map of extra headers into a header map, validating the header names and values
+pub fn build_header_map(headers: &HashMap<String, String>) -> Result<HeaderMap, LlmError> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let name = HeaderName::try_from(name.as_str())
+                .map_err(|_| LlmError::InvalidHeader(name.to_owned()))?;
+            let value = HeaderValue::try_from(value.as_str())
+                .map_err(|_| LlmError::InvalidHeader(name.to_string()))?;
+            Ok((name, value))
+        })
+        .collect()
+}
+
+/// Decrypt the extra headers configured for the provider
+pub fn get_extra_headers(
+    provider: &ChatRsProvider,
+    encryptor: &Encryptor,
+) -> Result<HeaderMap, LlmError> {
+    let (Some(ciphertext), Some(nonce)) = (&provider.extra_headers, &provider.extra_headers_nonce)
+    else {
+        return Ok(HeaderMap::new());
+    };
+    let json = encryptor.decrypt_string(ciphertext, nonce)?;
+    let headers: HashMap<String, String> =
+        serde_json::from_str(&json).map_err(|_| LlmError::DecryptionError)?;
+
+    build_header_map(&headers)
+}
@@ -1,16 +1,21 @@
 //! Anthropic LLM provider
 
+mod batch;
 mod request;
 mod response;
 
+use reqwest::header::HeaderMap;
 use rocket::{async_stream, async_trait, futures::StreamExt};
 
+pub use batch::AnthropicBatchResult;
+
 use crate::{
     db::models::ChatRsMessage,
     provider::{
         utils::get_sse_events, LlmApiProvider, LlmError, LlmProviderOptions, LlmStream, LlmTool,
         LlmUsage, DEFAULT_MAX_TOKENS,
     },
+    provider_debug::ProviderDebugLogger,
     provider_models::{LlmModel, ModelsDevService, ModelsDevServiceProvider},
 };
 
@@ -23,6 +28,7 @@ use {
 };
 
 const MESSAGES_API_URL: &str = "https://api.anthropic.com/v1/messages";
+const MODELS_API_URL: &str = "https://api.anthropic.com/v1/models?limit=1";
 const API_VERSION: &str = "2023-06-01";
 
 /// Anthropic chat provider
@@ -31,6 +37,9 @@ pub struct AnthropicProvider {
     client: reqwest::Client,
     redis: fred::clients::Client,
     api_key: String,
+    secondary_api_key: Option<String>,
+    extra_headers: HeaderMap,
+    debug_logger: Option<ProviderDebugLogger>,
 }
 
 impl AnthropicProvider {
@@ -38,13 +47,43 @@ impl AnthropicProvider {
         http_client: &reqwest::Client,
         redis: &fred::clients::Client,
         api_key: &str,
+        secondary_api_key: Option<&str>,
+        extra_headers: &HeaderMap,
     ) -> Self {
         Self {
             client: http_client.clone(),
             redis: redis.clone(),
             api_key: api_key.to_string(),
+            secondary_api_key: secondary_api_key.map(|key| key.to_string()),
+            extra_headers: extra_headers.clone(),
+            debug_logger: None,
         }
     }
+
+    /// Add the extra headers and API key, and send the request. If the API key is rejected,
+    /// the request is retried with the secondary API key.
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, LlmError> {
+        let request = request.headers(self.extra_headers.clone());
+        let retry_request = self
+            .secondary_api_key
+            .as_ref()
+            .and_then(|key| Some(request.try_clone()?.header("x-api-key", key)));
+        let mut response = request
+            .header("x-api-key", &self.api_key)
+            .send()
+            .await
+            .map_err(|e| LlmError::ProviderError(format!("Anthropic request failed: {}", e)))?;
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            if let Some(retry_request) = retry_request {
+                rocket::warn!("Anthropic API key rejected, retrying with secondary key");
+                response = retry_request.send().await.map_err(|e| {
+                    LlmError::ProviderError(format!("Anthropic request failed: {}", e))
+                })?;
+            }
+        }
+
+        Ok(response)
+    }
 }
 
 #[async_trait]
@@ -67,16 +106,16 @@ impl LlmApiProvider for AnthropicProvider {
             tools: anthropic_tools,
         };
 
-        let response = self
+        if let Some(logger) = &self.debug_logger {
+            logger.log_request(&request).await;
+        }
+        let request_builder = self
             .client
             .post(MESSAGES_API_URL)
             .header("anthropic-version", API_VERSION)
             .header("content-type", "application/json")
-            .header("x-api-key", &self.api_key)
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| LlmError::ProviderError(format!("Anthropic request failed: {}", e)))?;
+            .json(&request);
+        let response = self.send(request_builder).await?;
         if !response.status().is_success() {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
@@ -86,19 +125,23 @@ impl LlmApiProvider for AnthropicProvider {
             )));
         }
 
+        let debug_logger = self.debug_logger.clone();
         let stream = async_stream::stream! {
-            let mut sse_event_stream = get_sse_events(response);
+            let mut sse_event_stream = get_sse_events(response, debug_logger.clone());
             let mut tool_calls = Vec::new();
             while let Some(event_result) = sse_event_stream.next().await {
                 match event_result {
                     Ok(event) => {
-                        if let Some(chunk) = parse_anthropic_event(event, tools.as_ref(), &mut tool_calls) {
+                        for chunk in parse_anthropic_event(event, tools.as_ref(), &mut tool_calls) {
                             yield chunk;
                         }
                     },
                     Err(e) => yield Err(e),
                 }
             }
+            if let Some(logger) = &debug_logger {
+                logger.flush().await;
+            }
         };
 
         Ok(stream.boxed())
@@ -122,16 +165,13 @@ impl LlmApiProvider for AnthropicProvider {
             tools: None,
         };
 
-        let response = self
+        let request_builder = self
             .client
             .post(MESSAGES_API_URL)
             .header("anthropic-version", API_VERSION)
             .header("content-type", "application/json")
-            .header("x-api-key", &self.api_key)
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| LlmError::ProviderError(format!("Anthropic request failed: {}", e)))?;
+            .json(&request);
+        let response = self.send(request_builder).await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -170,4 +210,24 @@ impl LlmApiProvider for AnthropicProvider {
 
         Ok(models)
     }
+
+    async fn health_check(&self) -> Result<(), LlmError> {
+        let request_builder = self
+            .client
+            .get(MODELS_API_URL)
+            .header("anthropic-version", API_VERSION);
+        let response = self.send(request_builder).await?;
+        if !response.status().is_success() {
+            return Err(LlmError::ProviderError(format!(
+                "Anthropic API error {}",
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn set_debug_logger(&mut self, logger: ProviderDebugLogger) {
+        self.debug_logger = Some(logger);
+    }
 }
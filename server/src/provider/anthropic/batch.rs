@@ -0,0 +1,232 @@
+//! Anthropic Message Batches API, for non-interactive requests
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::models::ChatRsMessage,
+    provider::{LlmError, LlmProviderOptions, DEFAULT_MAX_TOKENS},
+};
+
+use super::{
+    request::{build_anthropic_messages, AnthropicRequest},
+    response::{AnthropicResponse, AnthropicResponseContentBlock},
+    AnthropicProvider, API_VERSION,
+};
+
+const BATCHES_API_URL: &str = "https://api.anthropic.com/v1/messages/batches";
+
+/// Result of a request in a message batch: the text response, or an error message
+pub type AnthropicBatchResult = Result<String, String>;
+
+impl AnthropicProvider {
+    /// Submit a message batch, where each request is identified by a custom ID.
+    /// Returns the ID of the batch.
+    pub async fn create_batch(
+        &self,
+        requests: &[(String, Vec<ChatRsMessage>)],
+        options: &LlmProviderOptions,
+    ) -> Result<String, LlmError> {
+        let batch_requests: Vec<AnthropicBatchRequest> = requests
+            .iter()
+            .map(|(custom_id, messages)| {
+                let (anthropic_messages, system_prompt) = build_anthropic_messages(messages);
+                AnthropicBatchRequest {
+                    custom_id,
+                    params: AnthropicRequest {
+                        model: &options.model,
+                        messages: anthropic_messages,
+                        max_tokens: options.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+                        temperature: options.temperature,
+                        system: system_prompt,
+                        stream: None,
+                        tools: None,
+                    },
+                }
+            })
+            .collect();
+
+        let request_builder = self
+            .client
+            .post(BATCHES_API_URL)
+            .header("anthropic-version", API_VERSION)
+            .header("content-type", "application/json")
+            .json(&AnthropicBatchCreateRequest {
+                requests: batch_requests,
+            });
+        let batch: AnthropicBatch = self.send_json(request_builder).await?;
+
+        Ok(batch.id)
+    }
+
+    /// Get the results of a message batch by custom ID, or `None` if the batch is still
+    /// being processed. Results that can't be parsed are returned as errors, or skipped if
+    /// their custom ID can't be found.
+    pub async fn get_batch_results(
+        &self,
+        batch_id: &str,
+    ) -> Result<Option<Vec<(String, AnthropicBatchResult)>>, LlmError> {
+        let request_builder = self
+            .client
+            .get(format!("{BATCHES_API_URL}/{batch_id}"))
+            .header("anthropic-version", API_VERSION);
+        let batch: AnthropicBatch = self.send_json(request_builder).await?;
+        if batch.processing_status != "ended" {
+            return Ok(None);
+        }
+        let results_url = batch
+            .results_url
+            .ok_or_else(|| LlmError::ProviderError("Missing batch results URL".into()))?;
+
+        let request_builder = self
+            .client
+            .get(results_url)
+            .header("anthropic-version", API_VERSION);
+        let response = self.send(request_builder).await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(LlmError::ProviderError(format!(
+                "Anthropic API error {}: {}",
+                status, error_text
+            )));
+        }
+        let results_text = response
+            .text()
+            .await
+            .map_err(|e| LlmError::ProviderError(format!("Failed to read results: {}", e)))?;
+        let results = results_text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(parse_batch_result_line)
+            .collect();
+
+        Ok(Some(results))
+    }
+
+    /// Send the request and parse the JSON response
+    async fn send_json<T: serde::de::DeserializeOwned>(
+        &self,
+        request_builder: reqwest::RequestBuilder,
+    ) -> Result<T, LlmError> {
+        let response = self.send(request_builder).await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(LlmError::ProviderError(format!(
+                "Anthropic API error {}: {}",
+                status, error_text
+            )));
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| LlmError::ProviderError(format!("Failed to parse response: {}", e)))
+    }
+}
+
+/// Anthropic message batch creation request
+#[derive(Debug, Serialize)]
+struct AnthropicBatchCreateRequest<'a> {
+    requests: Vec<AnthropicBatchRequest<'a>>,
+}
+
+/// A request in the message batch
+#[derive(Debug, Serialize)]
+struct AnthropicBatchRequest<'a> {
+    custom_id: &'a str,
+    params: AnthropicRequest<'a>,
+}
+
+/// Anthropic message batch
+#[derive(Debug, Deserialize)]
+struct AnthropicBatch {
+    id: String,
+    /// `in_progress`, `canceling`, or `ended`
+    processing_status: String,
+    results_url: Option<String>,
+}
+
+/// Parse a line of the message batch results. If the result can't be parsed, it's logged and
+/// marked as errored (if the custom ID of the line can be found).
+fn parse_batch_result_line(line: &str) -> Option<(String, AnthropicBatchResult)> {
+    let err = match serde_json::from_str::<AnthropicBatchResultLine>(line) {
+        Ok(line) => return Some((line.custom_id, line.result.into_result())),
+        Err(err) => err,
+    };
+    let custom_id = serde_json::from_str::<serde_json::Value>(line)
+        .ok()
+        .and_then(|value| Some(value.get("custom_id")?.as_str()?.to_owned()));
+    rocket::warn!(
+        "Failed to parse batch result (custom ID: {}): {err}",
+        custom_id.as_deref().unwrap_or("unknown")
+    );
+
+    Some((custom_id?, Err(format!("Failed to parse result: {err}"))))
+}
+
+/// A line of the message batch results (JSONL)
+#[derive(Debug, Deserialize)]
+struct AnthropicBatchResultLine {
+    custom_id: String,
+    result: AnthropicBatchResultType,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicBatchResultType {
+    Succeeded { message: AnthropicResponse },
+    Errored { error: serde_json::Value },
+    Canceled,
+    Expired,
+}
+
+impl AnthropicBatchResultType {
+    fn into_result(self) -> AnthropicBatchResult {
+        match self {
+            AnthropicBatchResultType::Succeeded { message } => message
+                .content
+                .into_iter()
+                .find_map(|block| match block {
+                    AnthropicResponseContentBlock::Text { text } => Some(text),
+                    _ => None,
+                })
+                .ok_or_else(|| "No text response".to_owned()),
+            AnthropicBatchResultType::Errored { error } => Err(error
+                .pointer("/error/message")
+                .and_then(|message| message.as_str())
+                .unwrap_or("Request failed")
+                .to_owned()),
+            AnthropicBatchResultType::Canceled => Err("Request was canceled".to_owned()),
+            AnthropicBatchResultType::Expired => Err("Request expired".to_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_batch_result_line() {
+        let line = r#"{"custom_id":"a","result":{"type":"expired"}}"#;
+        assert_eq!(
+            parse_batch_result_line(line),
+            Some(("a".to_owned(), Err("Request expired".to_owned())))
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_batch_result_line() {
+        let line = r#"{"custom_id":"a","result":{"type":"unknown"}}"#;
+        let (custom_id, result) = parse_batch_result_line(line).unwrap();
+        assert_eq!(custom_id, "a");
+        assert!(result.unwrap_err().starts_with("Failed to parse result"));
+
+        assert_eq!(
+            parse_batch_result_line(r#"{"result":{"type":"expired"}}"#),
+            None
+        );
+        assert_eq!(parse_batch_result_line("not json"), None);
+    }
+}
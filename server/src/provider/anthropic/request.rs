@@ -36,6 +36,18 @@ pub fn build_anthropic_messages<'a>(
                     });
                 }
             } else {
+                // Handle attached files, before the text
+                for attachment in &message.meta.attachments {
+                    let source = AnthropicSource {
+                        source_type: "base64",
+                        media_type: &attachment.mime_type,
+                        data: &attachment.data,
+                    };
+                    content_blocks.push(match attachment.is_image() {
+                        true => AnthropicContentBlock::Image { source },
+                        false => AnthropicContentBlock::Document { source },
+                    });
+                }
                 // Handle regular text content
                 if !message.content.is_empty() {
                     content_blocks.push(AnthropicContentBlock::Text {
@@ -122,6 +134,12 @@ pub enum AnthropicContentBlock<'a> {
     Text {
         text: &'a str,
     },
+    Image {
+        source: AnthropicSource<'a>,
+    },
+    Document {
+        source: AnthropicSource<'a>,
+    },
     ToolUse {
         id: &'a str,
         name: &'a str,
@@ -132,3 +150,12 @@ pub enum AnthropicContentBlock<'a> {
         content: &'a str,
     },
 }
+
+/// Anthropic source of an image or document block
+#[derive(Debug, Serialize)]
+pub struct AnthropicSource<'a> {
+    #[serde(rename = "type")]
+    source_type: &'a str,
+    media_type: &'a str,
+    data: &'a str,
+}
@@ -3,7 +3,8 @@ use serde::Deserialize;
 use crate::{
     db::models::ChatRsToolCall,
     provider::{
-        LlmPendingToolCall, LlmStreamChunk, LlmStreamChunkResult, LlmStreamError, LlmTool, LlmUsage,
+        LlmFinishReason, LlmPendingToolCall, LlmStreamChunk, LlmStreamChunkResult, LlmStreamError,
+        LlmTool, LlmUsage,
     },
 };
 
@@ -12,11 +13,12 @@ pub fn parse_anthropic_event(
     event: AnthropicStreamEvent,
     tools: Option<&Vec<LlmTool>>,
     tool_calls: &mut Vec<AnthropicStreamToolCall>,
-) -> Option<LlmStreamChunkResult> {
+) -> Vec<LlmStreamChunkResult> {
+    let mut chunks = Vec::with_capacity(1);
     match event {
         AnthropicStreamEvent::MessageStart { message } => {
             if let Some(usage) = message.usage {
-                return Some(Ok(LlmStreamChunk::Usage(usage.into())));
+                chunks.push(Ok(LlmStreamChunk::Usage(usage.into())));
             }
         }
         AnthropicStreamEvent::ContentBlockStart {
@@ -24,7 +26,7 @@ pub fn parse_anthropic_event(
             index,
         } => match content_block {
             AnthropicResponseContentBlock::Text { text } => {
-                return Some(Ok(LlmStreamChunk::Text(text)));
+                chunks.push(Ok(LlmStreamChunk::Text(text)));
             }
             AnthropicResponseContentBlock::ToolUse { id, name } => {
                 tool_calls.push(AnthropicStreamToolCall {
@@ -37,7 +39,7 @@ pub fn parse_anthropic_event(
         },
         AnthropicStreamEvent::ContentBlockDelta { delta, index } => match delta {
             AnthropicDelta::TextDelta { text } => {
-                return Some(Ok(LlmStreamChunk::Text(text)));
+                chunks.push(Ok(LlmStreamChunk::Text(text)));
             }
             AnthropicDelta::InputJsonDelta { partial_json } => {
                 if let Some(tool_call) = tool_calls.iter_mut().find(|tc| tc.index == index) {
@@ -45,8 +47,9 @@ pub fn parse_anthropic_event(
                     let chunk = LlmStreamChunk::PendingToolCall(LlmPendingToolCall {
                         index,
                         tool_name: tool_call.name.clone(),
+                        arguments_delta: Some(partial_json),
                     });
-                    return Some(Ok(chunk));
+                    chunks.push(Ok(chunk));
                 }
             }
         },
@@ -58,24 +61,38 @@ pub fn parse_anthropic_event(
                     .map(|i| tool_calls.swap_remove(i))
                 {
                     if let Some(tool_call) = tc.convert(llm_tools) {
-                        let chunk = LlmStreamChunk::ToolCalls(vec![tool_call]);
-                        return Some(Ok(chunk));
+                        chunks.push(Ok(LlmStreamChunk::ToolCalls(vec![tool_call])));
                     }
                 }
             }
         }
-        AnthropicStreamEvent::MessageDelta { usage } => {
+        AnthropicStreamEvent::MessageDelta { delta, usage } => {
+            if let Some(reason) = delta.stop_reason {
+                let reason = parse_anthropic_stop_reason(&reason);
+                chunks.push(Ok(LlmStreamChunk::FinishReason(reason)));
+            }
             if let Some(usage) = usage {
-                return Some(Ok(LlmStreamChunk::Usage(usage.into())));
+                chunks.push(Ok(LlmStreamChunk::Usage(usage.into())));
             }
         }
         AnthropicStreamEvent::Error { error } => {
             let error_msg = format!("{}: {}", error.error_type, error.message);
-            return Some(Err(LlmStreamError::ProviderError(error_msg)));
+            chunks.push(Err(LlmStreamError::ProviderError(error_msg)));
         }
         _ => {} // Ignore other events (ping, message_stop)
     }
-    None
+    chunks
+}
+
+/// Map the Anthropic stop reason to the shared finish reason
+fn parse_anthropic_stop_reason(reason: &str) -> LlmFinishReason {
+    match reason {
+        "end_turn" | "stop_sequence" => LlmFinishReason::Stop,
+        "max_tokens" => LlmFinishReason::Length,
+        "tool_use" => LlmFinishReason::ToolCalls,
+        "refusal" => LlmFinishReason::ContentFilter,
+        _ => LlmFinishReason::Other,
+    }
 }
 
 /// Anthropic API response content block
@@ -143,7 +160,7 @@ pub enum AnthropicStreamEvent {
         index: usize,
     },
     MessageDelta {
-        // delta: AnthropicMessageDelta,
+        delta: AnthropicMessageDelta,
         usage: Option<AnthropicUsage>,
     },
     MessageStop,
@@ -162,7 +179,7 @@ pub enum AnthropicDelta {
 
 #[derive(Debug, Deserialize)]
 pub struct AnthropicMessageDelta {
-    // stop_reason: Option<String>,
+    stop_reason: Option<String>,
     // stop_sequence: Option<String>,
 }
 
@@ -204,6 +221,7 @@ impl AnthropicStreamToolCall {
                 tool_name: self.name,
                 tool_type: tool.tool_type,
                 parameters,
+                approval: None,
             })
     }
 }
@@ -0,0 +1,219 @@
+//! Typed codes of the common provider errors, so that clients can show a helpful message (e.g.
+//! "check your API key") instead of the raw error of the provider. The providers return their
+//! errors as text including the HTTP status and the response body, which are matched against
+//! the known error types of OpenAI, Anthropic, and Ollama.
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::provider::{LlmError, LlmStreamError};
+
+/// Code of a common provider error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, JsonSchema, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LlmErrorCode {
+    /// The API key is missing, invalid, or revoked
+    InvalidApiKey,
+    /// The account has no credits left, or reached its spending limit
+    QuotaExceeded,
+    /// Too many requests to the provider
+    RateLimited,
+    /// The model doesn't exist, or the account can't access it
+    ModelNotFound,
+    /// The request or response was blocked by the content filter of the provider
+    ContentFiltered,
+    /// The conversation is too long for the model's context window
+    ContextLengthExceeded,
+    /// The provider is overloaded or temporarily unavailable
+    ProviderUnavailable,
+    /// The provider stopped responding
+    Timeout,
+}
+
+impl LlmErrorCode {
+    /// User-friendly description of the error
+    pub fn message(&self) -> &'static str {
+        match self {
+            LlmErrorCode::InvalidApiKey => "The API key of the provider is missing or invalid",
+            LlmErrorCode::QuotaExceeded => {
+                "The provider account has exceeded its quota. Check the billing details."
+            }
+            LlmErrorCode::RateLimited => "Too many requests to the provider, try again later",
+            LlmErrorCode::ModelNotFound => "The model doesn't exist, or isn't available",
+            LlmErrorCode::ContentFiltered => "The provider's content filter blocked the response",
+            LlmErrorCode::ContextLengthExceeded => {
+                "The conversation is too long for the model. Start a new session, or use a \
+                model with a larger context window."
+            }
+            LlmErrorCode::ProviderUnavailable => {
+                "The provider is overloaded or unavailable, try again later"
+            }
+            LlmErrorCode::Timeout => "The provider took too long to respond",
+        }
+    }
+}
+
+/// Error sent in the `error` events of the chat streams
+#[derive(Debug, JsonSchema, Serialize)]
+#[schemars(rename = "ChatStreamError")]
+pub struct LlmErrorInfo {
+    /// Code of the error, if it's a known provider error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<LlmErrorCode>,
+    /// Description of the error (user-friendly if there's a code)
+    pub message: String,
+    /// The original error, if there's a code
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+}
+
+impl LlmErrorInfo {
+    /// Create the error info of the original error, with the user-friendly message if there's
+    /// a code
+    pub fn new(code: Option<LlmErrorCode>, error: String) -> Self {
+        match code {
+            Some(code) => Self {
+                code: Some(code),
+                message: code.message().to_owned(),
+                details: Some(error),
+            },
+            None => Self {
+                code: None,
+                message: error,
+                details: None,
+            },
+        }
+    }
+
+    /// Serialize the error for the `error` events of the streams
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+impl From<&LlmStreamError> for LlmErrorInfo {
+    fn from(err: &LlmStreamError) -> Self {
+        Self::new(err.code(), err.to_string())
+    }
+}
+
+impl From<&LlmError> for LlmErrorInfo {
+    fn from(err: &LlmError) -> Self {
+        Self::new(err.code(), err.to_string())
+    }
+}
+
+impl LlmError {
+    /// Get the code of the error, if it's a known provider error
+    pub fn code(&self) -> Option<LlmErrorCode> {
+        match self {
+            LlmError::MissingApiKey => Some(LlmErrorCode::InvalidApiKey),
+            LlmError::ModelRetired(_) => Some(LlmErrorCode::ModelNotFound),
+            LlmError::ProviderError(error) => classify_provider_error(error),
+            _ => None,
+        }
+    }
+}
+
+impl LlmStreamError {
+    /// Get the code of the error, if it's a known provider error
+    pub fn code(&self) -> Option<LlmErrorCode> {
+        match self {
+            LlmStreamError::ProviderError(error) => classify_provider_error(error),
+            LlmStreamError::StreamTimeout => Some(LlmErrorCode::Timeout),
+            _ => None,
+        }
+    }
+}
+
+/// Error types and messages of the providers, checked in order (before the HTTP status, as
+/// e.g. OpenAI uses the 429 status for both rate limits and exceeded quotas)
+const ERROR_PATTERNS: &[(&str, LlmErrorCode)] = &[
+    ("insufficient_quota", LlmErrorCode::QuotaExceeded),
+    ("credit balance is too low", LlmErrorCode::QuotaExceeded),
+    ("billing", LlmErrorCode::QuotaExceeded),
+    (
+        "context_length_exceeded",
+        LlmErrorCode::ContextLengthExceeded,
+    ),
+    (
+        "maximum context length",
+        LlmErrorCode::ContextLengthExceeded,
+    ),
+    ("prompt is too long", LlmErrorCode::ContextLengthExceeded),
+    ("content_filter", LlmErrorCode::ContentFiltered),
+    ("content_policy_violation", LlmErrorCode::ContentFiltered),
+    ("invalid_api_key", LlmErrorCode::InvalidApiKey),
+    ("incorrect api key", LlmErrorCode::InvalidApiKey),
+    ("invalid x-api-key", LlmErrorCode::InvalidApiKey),
+    ("authentication_error", LlmErrorCode::InvalidApiKey),
+    ("model_not_found", LlmErrorCode::ModelNotFound),
+    ("rate_limit", LlmErrorCode::RateLimited),
+    ("overloaded", LlmErrorCode::ProviderUnavailable),
+];
+
+/// Get the code of a provider error from its text, matching the error types and messages of
+/// the providers, and then the HTTP status (e.g. `OpenAI API error 401 Unauthorized: ...`)
+pub fn classify_provider_error(error: &str) -> Option<LlmErrorCode> {
+    let error = error.to_lowercase();
+    if let Some((_, code)) = ERROR_PATTERNS
+        .iter()
+        .find(|(pattern, _)| error.contains(pattern))
+    {
+        return Some(*code);
+    }
+
+    let status = error
+        .split_once("api error ")
+        .and_then(|(_, rest)| rest.get(..3))
+        .and_then(|status| status.parse::<u16>().ok())?;
+    match status {
+        401 | 403 => Some(LlmErrorCode::InvalidApiKey),
+        402 => Some(LlmErrorCode::QuotaExceeded),
+        404 => Some(LlmErrorCode::ModelNotFound),
+        429 => Some(LlmErrorCode::RateLimited),
+        502 | 503 | 529 => Some(LlmErrorCode::ProviderUnavailable),
+        504 => Some(LlmErrorCode::Timeout),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_provider_error() {
+        let cases = [
+            (
+                "OpenAI API error 429 Too Many Requests: {\"code\":\"insufficient_quota\"}",
+                Some(LlmErrorCode::QuotaExceeded),
+            ),
+            (
+                "OpenAI API error 429 Too Many Requests: {\"error\":{\"type\":\"requests\"}}",
+                Some(LlmErrorCode::RateLimited),
+            ),
+            (
+                "Anthropic API error 401 Unauthorized: {\"type\":\"error\"}",
+                Some(LlmErrorCode::InvalidApiKey),
+            ),
+            (
+                "invalid_request_error: prompt is too long: 210000 tokens > 200000 maximum",
+                Some(LlmErrorCode::ContextLengthExceeded),
+            ),
+            (
+                "overloaded_error: Overloaded",
+                Some(LlmErrorCode::ProviderUnavailable),
+            ),
+            (
+                "Ollama API error 404 Not Found: model \"llama9\" not found",
+                Some(LlmErrorCode::ModelNotFound),
+            ),
+            ("OpenAI API error 400 Bad Request: {}", None),
+            ("Test error", None),
+        ];
+        for (error, expected) in cases {
+            assert_eq!(classify_provider_error(error), expected, "{error}");
+        }
+    }
+}
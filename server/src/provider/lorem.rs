@@ -127,4 +127,8 @@ impl LlmApiProvider for LoremProvider {
     async fn list_models(&self) -> Result<Vec<LlmModel>, LlmError> {
         Ok(vec![])
     }
+
+    async fn health_check(&self) -> Result<(), LlmError> {
+        Ok(())
+    }
 }
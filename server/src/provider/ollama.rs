@@ -3,6 +3,7 @@
 mod request;
 mod response;
 
+use reqwest::header::HeaderMap;
 use rocket::{async_stream, async_trait, futures::StreamExt};
 
 use crate::{
@@ -11,35 +12,42 @@ use crate::{
         utils::get_json_events, LlmApiProvider, LlmError, LlmProviderOptions, LlmStream,
         LlmStreamChunk, LlmTool, LlmUsage,
     },
+    provider_debug::ProviderDebugLogger,
     provider_models::LlmModel,
 };
 
 use {
     request::{
         build_ollama_messages, build_ollama_tools, OllamaChatRequest, OllamaCompletionRequest,
-        OllamaOptions,
+        OllamaEmbedRequest, OllamaOptions,
     },
     response::{
-        parse_ollama_event, OllamaCompletionResponse, OllamaModelsResponse, OllamaToolCall,
+        parse_ollama_event, OllamaCompletionResponse, OllamaEmbedResponse, OllamaModelsResponse,
+        OllamaToolCall,
     },
 };
 
 const CHAT_API_URL: &str = "/api/chat";
 const COMPLETION_API_URL: &str = "/api/generate";
 const MODELS_API_URL: &str = "/api/tags";
+const EMBED_API_URL: &str = "/api/embed";
 
 /// Ollama chat provider
 #[derive(Debug, Clone)]
 pub struct OllamaProvider {
     client: reqwest::Client,
     base_url: String,
+    extra_headers: HeaderMap,
+    debug_logger: Option<ProviderDebugLogger>,
 }
 
 impl OllamaProvider {
-    pub fn new(http_client: &reqwest::Client, base_url: &str) -> Self {
+    pub fn new(http_client: &reqwest::Client, base_url: &str, extra_headers: &HeaderMap) -> Self {
         Self {
             client: http_client.clone(),
             base_url: base_url.trim_end_matches('/').to_string(),
+            extra_headers: extra_headers.clone(),
+            debug_logger: None,
         }
     }
 }
@@ -67,9 +75,13 @@ impl LlmApiProvider for OllamaProvider {
             options: Some(ollama_options),
         };
 
+        if let Some(logger) = &self.debug_logger {
+            logger.log_request(&request).await;
+        }
         let response = self
             .client
             .post(format!("{}{}", self.base_url, CHAT_API_URL))
+            .headers(self.extra_headers.clone())
             .header("content-type", "application/json")
             .json(&request)
             .send()
@@ -84,8 +96,9 @@ impl LlmApiProvider for OllamaProvider {
             )));
         }
 
+        let debug_logger = self.debug_logger.clone();
         let stream = async_stream::stream! {
-            let mut json_stream = get_json_events(response);
+            let mut json_stream = get_json_events(response, debug_logger.clone());
             let mut tool_calls: Vec<OllamaToolCall> = Vec::new();
             while let Some(event) = json_stream.next().await {
                 match event {
@@ -97,6 +110,9 @@ impl LlmApiProvider for OllamaProvider {
                     Err(e) => yield Err(e),
                 }
             }
+            if let Some(logger) = &debug_logger {
+                logger.flush().await;
+            }
             if !tool_calls.is_empty() {
                 if let Some(llm_tools) = tools {
                     let converted = tool_calls
@@ -130,6 +146,7 @@ impl LlmApiProvider for OllamaProvider {
         let response = self
             .client
             .post(format!("{}{}", self.base_url, COMPLETION_API_URL))
+            .headers(self.extra_headers.clone())
             .header("content-type", "application/json")
             .json(&request)
             .send()
@@ -162,6 +179,7 @@ impl LlmApiProvider for OllamaProvider {
         let response = self
             .client
             .get(format!("{}{}", self.base_url, MODELS_API_URL))
+            .headers(self.extra_headers.clone())
             .send()
             .await
             .map_err(|e| LlmError::ProviderError(format!("Ollama models request failed: {}", e)))?;
@@ -194,4 +212,59 @@ impl LlmApiProvider for OllamaProvider {
 
         Ok(models)
     }
+
+    async fn health_check(&self) -> Result<(), LlmError> {
+        let response = self
+            .client
+            .get(format!("{}{}", self.base_url, MODELS_API_URL))
+            .headers(self.extra_headers.clone())
+            .send()
+            .await
+            .map_err(|e| LlmError::ProviderError(format!("Ollama models request failed: {}", e)))?;
+        if !response.status().is_success() {
+            return Err(LlmError::ProviderError(format!(
+                "Ollama models API error {}",
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn embed(&self, inputs: &[String], model: &str) -> Result<Vec<Vec<f32>>, LlmError> {
+        let request = OllamaEmbedRequest {
+            model,
+            input: inputs,
+        };
+        let response = self
+            .client
+            .post(format!("{}{}", self.base_url, EMBED_API_URL))
+            .headers(self.extra_headers.clone())
+            .header("content-type", "application/json")
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| LlmError::ProviderError(format!("Ollama embed request failed: {}", e)))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(LlmError::ProviderError(format!(
+                "Ollama embed API error {}: {}",
+                status, error_text
+            )));
+        }
+
+        let embed_response: OllamaEmbedResponse = response.json().await.map_err(|e| {
+            LlmError::ProviderError(format!("Failed to parse embed response: {}", e))
+        })?;
+        if embed_response.embeddings.len() != inputs.len() {
+            return Err(LlmError::NoResponse);
+        }
+
+        Ok(embed_response.embeddings)
+    }
+
+    fn set_debug_logger(&mut self, logger: ProviderDebugLogger) {
+        self.debug_logger = Some(logger);
+    }
 }
@@ -23,10 +23,23 @@ pub fn build_ollama_messages(messages: &[ChatRsMessage]) -> Vec<OllamaMessage> {
             let mut ollama_msg = OllamaMessage {
                 role,
                 content: &msg.content,
+                images: None,
                 tool_calls: None,
                 tool_name: None,
             };
 
+            // Handle attached images (other files aren't supported)
+            let images: Vec<&str> = msg
+                .meta
+                .attachments
+                .iter()
+                .filter(|attachment| attachment.is_image())
+                .map(|attachment| attachment.data.as_str())
+                .collect();
+            if !images.is_empty() {
+                ollama_msg.images = Some(images);
+            }
+
             // Handle tool calls in assistant messages
             if msg.role == ChatRsMessageRole::Assistant {
                 if let Some(msg_tool_calls) = msg
@@ -99,11 +112,21 @@ pub struct OllamaCompletionRequest<'a> {
     pub options: Option<OllamaOptions>,
 }
 
+/// Ollama embeddings request structure
+#[derive(Debug, Serialize)]
+pub struct OllamaEmbedRequest<'a> {
+    pub model: &'a str,
+    pub input: &'a [String],
+}
+
 /// Ollama chat message
 #[derive(Debug, Serialize)]
 pub struct OllamaMessage<'a> {
     pub role: &'a str,
     pub content: &'a str,
+    /// Base64-encoded images
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub images: Option<Vec<&'a str>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<OllamaToolCall<'a>>>,
     #[serde(skip_serializing_if = "Option::is_none")]
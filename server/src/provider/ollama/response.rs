@@ -4,7 +4,9 @@ use serde::Deserialize;
 
 use crate::{
     db::models::ChatRsToolCall,
-    provider::{LlmPendingToolCall, LlmStreamChunk, LlmStreamError, LlmTool, LlmUsage},
+    provider::{
+        LlmFinishReason, LlmPendingToolCall, LlmStreamChunk, LlmStreamError, LlmTool, LlmUsage,
+    },
 };
 
 /// Parse Ollama streaming event into LlmStreamChunks, and track tool calls
@@ -18,6 +20,14 @@ pub fn parse_ollama_event(
         if let Some(usage) = Option::<LlmUsage>::from(&event) {
             chunks.push(Ok(LlmStreamChunk::Usage(usage)));
         }
+        if let Some(ref reason) = event.done_reason {
+            let reason = match reason.as_str() {
+                "stop" => LlmFinishReason::Stop,
+                "length" => LlmFinishReason::Length,
+                _ => LlmFinishReason::Other,
+            };
+            chunks.push(Ok(LlmStreamChunk::FinishReason(reason)));
+        }
     }
 
     // Handle tool calls in the message
@@ -26,6 +36,7 @@ pub fn parse_ollama_event(
             let tool_call = LlmPendingToolCall {
                 index,
                 tool_name: tc.function.name.clone(),
+                arguments_delta: serde_json::to_string(&tc.function.arguments).ok(),
             };
             chunks.push(Ok(LlmStreamChunk::PendingToolCall(tool_call)));
         }
@@ -86,6 +97,12 @@ pub struct OllamaCompletionResponse {
     pub eval_duration: Option<u64>,
 }
 
+/// Ollama embeddings response
+#[derive(Debug, Deserialize)]
+pub struct OllamaEmbedResponse {
+    pub embeddings: Vec<Vec<f32>>,
+}
+
 /// Ollama message in response
 #[derive(Debug, Deserialize)]
 pub struct OllamaMessage {
@@ -121,6 +138,7 @@ impl OllamaToolFunction {
             tool_id: tool.tool_id,
             tool_name: self.name,
             tool_type: tool.tool_type,
+            approval: None,
         })
     }
 }
@@ -3,6 +3,7 @@
 mod request;
 mod response;
 
+use reqwest::header::HeaderMap;
 use rocket::{async_stream, async_trait, futures::StreamExt};
 
 use crate::{
@@ -11,15 +12,16 @@ use crate::{
         utils::get_sse_events, LlmApiProvider, LlmError, LlmProviderOptions, LlmStream,
         LlmStreamChunk, LlmTool, LlmUsage,
     },
+    provider_debug::ProviderDebugLogger,
     provider_models::{LlmModel, ModelsDevService, ModelsDevServiceProvider},
 };
 
 use {
     request::{
-        build_openai_messages, build_openai_tools, OpenAIMessage, OpenAIRequest,
-        OpenAIStreamOptions,
+        build_openai_messages, build_openai_tools, OpenAIContent, OpenAIEmbeddingRequest,
+        OpenAIMessage, OpenAIRequest, OpenAIStreamOptions,
     },
-    response::{parse_openai_event, OpenAIResponse, OpenAIStreamToolCall},
+    response::{parse_openai_event, OpenAIEmbeddingResponse, OpenAIResponse, OpenAIStreamToolCall},
 };
 
 const OPENAI_API_BASE_URL: &str = "https://api.openai.com/v1";
@@ -31,7 +33,10 @@ pub struct OpenAIProvider {
     client: reqwest::Client,
     redis: fred::clients::Client,
     api_key: String,
+    secondary_api_key: Option<String>,
     base_url: String,
+    extra_headers: HeaderMap,
+    debug_logger: Option<ProviderDebugLogger>,
 }
 
 impl OpenAIProvider {
@@ -39,15 +44,48 @@ impl OpenAIProvider {
         http_client: &reqwest::Client,
         redis: &fred::clients::Client,
         api_key: &str,
+        secondary_api_key: Option<&str>,
         base_url: Option<&str>,
+        extra_headers: &HeaderMap,
     ) -> Self {
         Self {
             client: http_client.clone(),
             redis: redis.clone(),
             api_key: api_key.to_owned(),
+            secondary_api_key: secondary_api_key.map(|key| key.to_owned()),
             base_url: base_url.unwrap_or(OPENAI_API_BASE_URL).to_owned(),
+            extra_headers: extra_headers.clone(),
+            debug_logger: None,
         }
     }
+
+    /// Add the extra headers and API key, and send the request. If the API key is rejected,
+    /// the request is retried with the secondary API key.
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, LlmError> {
+        let request = request.headers(self.extra_headers.clone());
+        let retry_request = self.secondary_api_key.as_ref().and_then(|key| {
+            Some(
+                request
+                    .try_clone()?
+                    .header("authorization", format!("Bearer {}", key)),
+            )
+        });
+        let mut response = request
+            .header("authorization", format!("Bearer {}", self.api_key))
+            .send()
+            .await
+            .map_err(|e| LlmError::ProviderError(format!("OpenAI request failed: {}", e)))?;
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            if let Some(retry_request) = retry_request {
+                rocket::warn!("OpenAI API key rejected, retrying with secondary key");
+                response = retry_request.send().await.map_err(|e| {
+                    LlmError::ProviderError(format!("OpenAI request failed: {}", e))
+                })?;
+            }
+        }
+
+        Ok(response)
+    }
 }
 
 #[async_trait]
@@ -79,15 +117,15 @@ impl LlmApiProvider for OpenAIProvider {
             tools: openai_tools,
         };
 
-        let response = self
+        if let Some(logger) = &self.debug_logger {
+            logger.log_request(&request).await;
+        }
+        let request_builder = self
             .client
             .post(format!("{}/chat/completions", self.base_url))
-            .header("authorization", format!("Bearer {}", self.api_key))
             .header("content-type", "application/json")
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| LlmError::ProviderError(format!("OpenAI request failed: {}", e)))?;
+            .json(&request);
+        let response = self.send(request_builder).await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -98,8 +136,9 @@ impl LlmApiProvider for OpenAIProvider {
             )));
         }
 
+        let debug_logger = self.debug_logger.clone();
         let stream = async_stream::stream! {
-            let mut sse_event_stream = get_sse_events(response);
+            let mut sse_event_stream = get_sse_events(response, debug_logger.clone());
             let mut tool_calls: Vec<OpenAIStreamToolCall> = Vec::new();
             while let Some(event) = sse_event_stream.next().await {
                 match event {
@@ -111,6 +150,9 @@ impl LlmApiProvider for OpenAIProvider {
                     Err(e) => yield Err(e),
                 }
             }
+            if let Some(logger) = &debug_logger {
+                logger.flush().await;
+            }
             if !tool_calls.is_empty() {
                 if let Some(llm_tools) = tools {
                     let converted = tool_calls
@@ -134,7 +176,7 @@ impl LlmApiProvider for OpenAIProvider {
             model: &options.model,
             messages: vec![OpenAIMessage {
                 role: "user",
-                content: Some(message),
+                content: Some(OpenAIContent::Text(message)),
                 ..Default::default()
             }],
             max_tokens: options.max_tokens,
@@ -142,15 +184,12 @@ impl LlmApiProvider for OpenAIProvider {
             ..Default::default()
         };
 
-        let response = self
+        let request_builder = self
             .client
             .post(format!("{}/chat/completions", self.base_url))
-            .header("authorization", format!("Bearer {}", self.api_key))
             .header("content-type", "application/json")
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| LlmError::ProviderError(format!("OpenAI request failed: {}", e)))?;
+            .json(&request);
+        let response = self.send(request_builder).await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -194,4 +233,59 @@ impl LlmApiProvider for OpenAIProvider {
 
         Ok(models)
     }
+
+    async fn health_check(&self) -> Result<(), LlmError> {
+        let request_builder = self.client.get(format!("{}/models", self.base_url));
+        let response = self.send(request_builder).await?;
+        if !response.status().is_success() {
+            return Err(LlmError::ProviderError(format!(
+                "OpenAI API error {}",
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn embed(&self, inputs: &[String], model: &str) -> Result<Vec<Vec<f32>>, LlmError> {
+        let request = OpenAIEmbeddingRequest {
+            model,
+            input: inputs,
+        };
+        let request_builder = self
+            .client
+            .post(format!("{}/embeddings", self.base_url))
+            .header("content-type", "application/json")
+            .json(&request);
+        let response = self.send(request_builder).await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(LlmError::ProviderError(format!(
+                "OpenAI embeddings API error {}: {}",
+                status, error_text
+            )));
+        }
+
+        let mut embedding_response: OpenAIEmbeddingResponse =
+            response.json().await.map_err(|e| {
+                LlmError::ProviderError(format!("Failed to parse embeddings response: {}", e))
+            })?;
+        embedding_response
+            .data
+            .sort_by_key(|embedding| embedding.index);
+        if embedding_response.data.len() != inputs.len() {
+            return Err(LlmError::NoResponse);
+        }
+
+        Ok(embedding_response
+            .data
+            .into_iter()
+            .map(|embedding| embedding.embedding)
+            .collect())
+    }
+
+    fn set_debug_logger(&mut self, logger: ProviderDebugLogger) {
+        self.debug_logger = Some(logger);
+    }
 }
@@ -15,9 +15,38 @@ pub fn build_openai_messages<'a>(messages: &'a [ChatRsMessage]) -> Vec<OpenAIMes
                 ChatRsMessageRole::System => "system",
                 ChatRsMessageRole::Tool => "tool",
             };
+            let content = match message.meta.attachments.is_empty() {
+                true => OpenAIContent::Text(&message.content),
+                false => {
+                    let mut parts: Vec<OpenAIContentPart> = message
+                        .meta
+                        .attachments
+                        .iter()
+                        .map(|attachment| match attachment.is_image() {
+                            true => OpenAIContentPart::ImageUrl {
+                                image_url: OpenAIImageUrl {
+                                    url: attachment.data_url(),
+                                },
+                            },
+                            false => OpenAIContentPart::File {
+                                file: OpenAIFile {
+                                    filename: &attachment.name,
+                                    file_data: attachment.data_url(),
+                                },
+                            },
+                        })
+                        .collect();
+                    if !message.content.is_empty() {
+                        parts.push(OpenAIContentPart::Text {
+                            text: &message.content,
+                        });
+                    }
+                    OpenAIContent::Parts(parts)
+                }
+            };
             let openai_message = OpenAIMessage {
                 role,
-                content: Some(&message.content),
+                content: Some(content),
                 tool_call_id: message.meta.tool_call.as_ref().map(|tc| tc.id.as_str()),
                 tool_calls: message
                     .meta
@@ -79,6 +108,13 @@ pub struct OpenAIRequest<'a> {
     pub tools: Option<Vec<OpenAITool<'a>>>,
 }
 
+/// OpenAI embeddings API request body
+#[derive(Debug, Serialize)]
+pub struct OpenAIEmbeddingRequest<'a> {
+    pub model: &'a str,
+    pub input: &'a [String],
+}
+
 /// OpenAI API request stream options
 #[derive(Debug, Serialize)]
 pub struct OpenAIStreamOptions {
@@ -90,13 +126,43 @@ pub struct OpenAIStreamOptions {
 pub struct OpenAIMessage<'a> {
     pub role: &'a str,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub content: Option<&'a str>,
+    pub content: Option<OpenAIContent<'a>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_call_id: Option<&'a str>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<OpenAIToolCall<'a>>>,
 }
 
+/// OpenAI message content: text, or content parts for messages with attachments
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum OpenAIContent<'a> {
+    Text(&'a str),
+    Parts(Vec<OpenAIContentPart<'a>>),
+}
+
+/// OpenAI message content part
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OpenAIContentPart<'a> {
+    Text { text: &'a str },
+    ImageUrl { image_url: OpenAIImageUrl },
+    File { file: OpenAIFile<'a> },
+}
+
+/// OpenAI image content, as a `data:` URL
+#[derive(Debug, Serialize)]
+pub struct OpenAIImageUrl {
+    url: String,
+}
+
+/// OpenAI file content (e.g. PDF), as a `data:` URL
+#[derive(Debug, Serialize)]
+pub struct OpenAIFile<'a> {
+    filename: &'a str,
+    file_data: String,
+}
+
 /// OpenAI tool definition
 #[derive(Debug, Serialize)]
 pub struct OpenAITool<'a> {
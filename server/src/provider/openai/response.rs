@@ -2,7 +2,10 @@ use serde::Deserialize;
 
 use crate::{
     db::models::ChatRsToolCall,
-    provider::{LlmPendingToolCall, LlmStreamChunk, LlmStreamChunkResult, LlmTool, LlmUsage},
+    provider::{
+        LlmFinishReason, LlmPendingToolCall, LlmStreamChunk, LlmStreamChunkResult, LlmTool,
+        LlmUsage,
+    },
 };
 
 /// Parse chunks from an OpenAI SSE event
@@ -11,7 +14,11 @@ pub fn parse_openai_event(
     tool_calls: &mut Vec<OpenAIStreamToolCall>,
 ) -> Vec<LlmStreamChunkResult> {
     let mut chunks = Vec::with_capacity(1);
-    if let Some(delta) = event.choices.pop().and_then(|c| c.delta) {
+    let (delta, finish_reason) = match event.choices.pop() {
+        Some(choice) => (choice.delta, choice.finish_reason),
+        None => (None, None),
+    };
+    if let Some(delta) = delta {
         if let Some(text) = delta.content {
             chunks.push(Ok(LlmStreamChunk::Text(text)));
         }
@@ -21,13 +28,14 @@ pub fn parse_openai_event(
                     .iter_mut()
                     .find(|tc| tc.index == tool_call_delta.index)
                 {
-                    if let Some(function_arguments) = tool_call_delta.function.arguments {
-                        *tc.function.arguments.get_or_insert_default() += &function_arguments;
+                    if let Some(ref function_arguments) = tool_call_delta.function.arguments {
+                        *tc.function.arguments.get_or_insert_default() += function_arguments;
                     }
                     if let Some(ref tool_name) = tc.function.name {
                         let chunk = LlmStreamChunk::PendingToolCall(LlmPendingToolCall {
                             index: tool_call_delta.index,
                             tool_name: tool_name.clone(),
+                            arguments_delta: tool_call_delta.function.arguments,
                         });
                         chunks.push(Ok(chunk));
                     }
@@ -36,6 +44,7 @@ pub fn parse_openai_event(
                         let chunk = LlmStreamChunk::PendingToolCall(LlmPendingToolCall {
                             index: tool_call_delta.index,
                             tool_name: tool_name.clone(),
+                            arguments_delta: tool_call_delta.function.arguments.clone(),
                         });
                         chunks.push(Ok(chunk));
                     }
@@ -44,6 +53,10 @@ pub fn parse_openai_event(
             }
         }
     }
+    if let Some(reason) = finish_reason {
+        let reason = parse_openai_finish_reason(&reason);
+        chunks.push(Ok(LlmStreamChunk::FinishReason(reason)));
+    }
     if let Some(usage) = event.usage {
         chunks.push(Ok(LlmStreamChunk::Usage(usage.into())));
     }
@@ -51,6 +64,17 @@ pub fn parse_openai_event(
     chunks
 }
 
+/// Map the OpenAI finish reason to the shared finish reason
+fn parse_openai_finish_reason(reason: &str) -> LlmFinishReason {
+    match reason {
+        "stop" => LlmFinishReason::Stop,
+        "length" => LlmFinishReason::Length,
+        "tool_calls" | "function_call" => LlmFinishReason::ToolCalls,
+        "content_filter" => LlmFinishReason::ContentFilter,
+        _ => LlmFinishReason::Other,
+    }
+}
+
 /// OpenAI API response
 #[derive(Debug, Deserialize)]
 pub struct OpenAIResponse {
@@ -58,6 +82,19 @@ pub struct OpenAIResponse {
     pub usage: Option<OpenAIUsage>,
 }
 
+/// OpenAI embeddings API response
+#[derive(Debug, Deserialize)]
+pub struct OpenAIEmbeddingResponse {
+    pub data: Vec<OpenAIEmbedding>,
+}
+
+/// OpenAI embedding of one input
+#[derive(Debug, Deserialize)]
+pub struct OpenAIEmbedding {
+    pub index: usize,
+    pub embedding: Vec<f32>,
+}
+
 /// OpenAI API streaming response
 #[derive(Debug, Deserialize)]
 pub struct OpenAIStreamResponse {
@@ -70,7 +107,7 @@ pub struct OpenAIStreamResponse {
 pub struct OpenAIChoice {
     pub message: Option<OpenAIResponseMessage>,
     pub delta: Option<OpenAIResponseDelta>,
-    // finish_reason: Option<String>,
+    pub finish_reason: Option<String>,
 }
 
 /// OpenAI API response message
@@ -111,6 +148,7 @@ impl OpenAIStreamToolCall {
                 tool_name,
                 tool_type: tool.tool_type,
                 parameters,
+                approval: None,
             })
     }
 }
@@ -6,20 +6,25 @@ use tokio_util::{
     io::StreamReader,
 };
 
-use crate::provider::LlmStreamError;
+use crate::{provider::LlmStreamError, provider_debug::ProviderDebugLogger};
 
-/// Get a stream of deserialized events from a provider SSE stream.
+/// Get a stream of deserialized events from a provider SSE stream. The raw event data
+/// is captured if a debug logger is given.
 pub fn get_sse_events<T: DeserializeOwned + Send + 'static>(
     response: reqwest::Response,
+    debug_logger: Option<ProviderDebugLogger>,
 ) -> impl Stream<Item = Result<T, LlmStreamError>> {
     let stream_reader = StreamReader::new(response.bytes_stream().map_err(std::io::Error::other));
     let line_reader = FramedRead::new(stream_reader, LinesCodec::new());
 
-    line_reader.filter_map(|line_result| {
+    line_reader.filter_map(move |line_result| {
         match line_result {
             Ok(line) => {
                 if line.len() >= 6 && line.as_bytes().starts_with(b"data: ") {
                     let data = &line[6..]; // Skip "data: " prefix
+                    if let Some(logger) = &debug_logger {
+                        logger.log_chunk(data);
+                    }
                     if data.trim_start().is_empty() || data == "[DONE]" {
                         None // Skip empty lines and termination markers
                     } else {
@@ -35,13 +40,20 @@ pub fn get_sse_events<T: DeserializeOwned + Send + 'static>(
 }
 
 /// Get a stream of deserialized events from a provider JSON stream, not SSE (e.g. Ollama uses this format).
+/// The raw lines are captured if a debug logger is given.
 pub fn get_json_events<T: DeserializeOwned + Send + 'static>(
     response: reqwest::Response,
+    debug_logger: Option<ProviderDebugLogger>,
 ) -> impl Stream<Item = Result<T, LlmStreamError>> {
     let stream_reader = StreamReader::new(response.bytes_stream().map_err(std::io::Error::other));
     let line_reader = FramedRead::new(stream_reader, LinesCodec::new());
-    line_reader.map(|line_result| match line_result {
-        Ok(line) => serde_json::from_str::<T>(&line).map_err(LlmStreamError::Parsing),
+    line_reader.map(move |line_result| match line_result {
+        Ok(line) => {
+            if let Some(logger) = &debug_logger {
+                logger.log_chunk(&line);
+            }
+            serde_json::from_str::<T>(&line).map_err(LlmStreamError::Parsing)
+        }
         Err(e) => Err(LlmStreamError::Decoding(e)),
     })
 }
@@ -0,0 +1,156 @@
+//! Capture of raw provider requests and responses, for troubleshooting malformed streams
+
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use fred::prelude::{KeysInterface, ListInterface};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{provider::LlmError, redis::redis_key};
+
+const KEY_PREFIX: &str = "provider_debug:";
+/// Maximum number of entries kept per provider.
+const MAX_ENTRIES: i64 = 500;
+/// Expiration in seconds of the debug log of a provider.
+const TTL: i64 = 86400;
+/// Replacement text for secrets found in the captured data.
+const REDACTED: &str = "[REDACTED]";
+
+/// A captured request or response chunk
+#[derive(Debug, Clone, JsonSchema, Serialize, Deserialize)]
+pub struct ProviderDebugEntry {
+    pub kind: ProviderDebugEntryKind,
+    /// The raw data (secrets are redacted)
+    pub data: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, JsonSchema, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderDebugEntryKind {
+    /// The outbound request JSON
+    Request,
+    /// A raw chunk of the streaming response
+    Chunk,
+}
+
+/// Records the raw requests and stream chunks of a provider in a capped Redis list.
+/// Stream chunks are buffered, and saved when calling [`ProviderDebugLogger::flush`].
+#[derive(Debug, Clone)]
+pub struct ProviderDebugLogger {
+    redis: fred::clients::Client,
+    key: String,
+    secrets: Vec<String>,
+    buffer: Arc<Mutex<Vec<ProviderDebugEntry>>>,
+}
+
+impl ProviderDebugLogger {
+    /// Create a logger for the provider. The given secrets (e.g. API keys) are redacted
+    /// from all captured data.
+    pub fn new(redis: &fred::clients::Client, provider_id: i32, secrets: &[Option<&str>]) -> Self {
+        Self {
+            redis: redis.clone(),
+            key: get_key(provider_id),
+            secrets: secrets
+                .iter()
+                .flatten()
+                .filter(|secret| !secret.is_empty())
+                .map(|secret| secret.to_string())
+                .collect(),
+            buffer: Arc::default(),
+        }
+    }
+
+    /// Save the outbound request JSON
+    pub async fn log_request<T: Serialize>(&self, request: &T) {
+        let data = serde_json::to_string(request).unwrap_or_default();
+        self.record(ProviderDebugEntryKind::Request, &data);
+        self.flush().await;
+    }
+
+    /// Buffer a raw chunk of the streaming response
+    pub fn log_chunk(&self, chunk: &str) {
+        self.record(ProviderDebugEntryKind::Chunk, chunk);
+    }
+
+    /// Save all buffered entries to Redis. Errors are logged and otherwise ignored, so
+    /// that debug logging never interrupts a chat.
+    pub async fn flush(&self) {
+        let entries: Vec<String> = match self.buffer.lock() {
+            Ok(mut buffer) => buffer
+                .drain(..)
+                .filter_map(|entry| serde_json::to_string(&entry).ok())
+                .collect(),
+            Err(_) => return,
+        };
+        if entries.is_empty() {
+            return;
+        }
+        if let Err(err) = self.save(entries).await {
+            rocket::warn!("Failed to save provider debug log: {}", err);
+        }
+    }
+
+    fn record(&self, kind: ProviderDebugEntryKind, data: &str) {
+        let entry = ProviderDebugEntry {
+            kind,
+            data: self.redact(data),
+            created_at: Utc::now(),
+        };
+        if let Ok(mut buffer) = self.buffer.lock() {
+            if buffer.len() >= MAX_ENTRIES as usize {
+                buffer.remove(0);
+            }
+            buffer.push(entry);
+        }
+    }
+
+    fn redact(&self, data: &str) -> String {
+        self.secrets.iter().fold(data.to_owned(), |data, secret| {
+            data.replace(secret, REDACTED)
+        })
+    }
+
+    async fn save(&self, entries: Vec<String>) -> Result<(), fred::error::Error> {
+        let pipeline = self.redis.pipeline();
+        let _: () = pipeline.rpush(&self.key, entries).await?;
+        let _: () = pipeline.ltrim(&self.key, -MAX_ENTRIES, -1).await?;
+        let _: () = pipeline.expire(&self.key, TTL, None).await?;
+        let _: () = pipeline.all().await?;
+
+        Ok(())
+    }
+}
+
+/// Service to read and clear the debug logs of providers
+pub struct ProviderDebugService<'a> {
+    redis: &'a fred::clients::Client,
+}
+
+impl<'a> ProviderDebugService<'a> {
+    pub fn new(redis: &'a fred::clients::Client) -> Self {
+        Self { redis }
+    }
+
+    /// Get the captured entries of a provider, oldest first
+    pub async fn get(&self, provider_id: i32) -> Result<Vec<ProviderDebugEntry>, LlmError> {
+        let values: Vec<String> = self.redis.lrange(get_key(provider_id), 0, -1).await?;
+
+        Ok(values
+            .iter()
+            .filter_map(|value| serde_json::from_str(value).ok())
+            .collect())
+    }
+
+    /// Delete the captured entries of a provider
+    pub async fn clear(&self, provider_id: i32) -> Result<(), LlmError> {
+        let _: () = self.redis.del(get_key(provider_id)).await?;
+
+        Ok(())
+    }
+}
+
+fn get_key(provider_id: i32) -> String {
+    redis_key(format_args!("{KEY_PREFIX}{provider_id}"))
+}
@@ -0,0 +1,211 @@
+//! Background health checks of the configured LLM providers
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use fred::prelude::{HashesInterface, KeysInterface};
+use rocket::{
+    fairing::AdHoc,
+    futures::{stream, StreamExt},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::AppConfig,
+    db::{
+        models::{ChatRsProvider, ChatRsSecret},
+        services::ProviderDbService,
+        DbPool,
+    },
+    errors::ApiError,
+    provider::{build_llm_provider_api, get_extra_headers, LlmError},
+    redis::redis_key,
+    utils::Encryptor,
+};
+
+const CACHE_KEY: &str = "provider_health";
+/// Default interval in seconds between health checks.
+const DEFAULT_INTERVAL: u64 = 300;
+/// Timeout for a single provider health check.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Maximum number of providers checked concurrently.
+const MAX_CONCURRENT_CHECKS: usize = 5;
+
+/// Health status of a provider from the latest health check
+#[derive(Debug, Clone, JsonSchema, Serialize, Deserialize)]
+pub struct ProviderHealth {
+    /// Whether the provider was reachable and accepted the API key
+    pub available: bool,
+    /// Latency of the health check in milliseconds
+    pub latency_ms: u64,
+    /// Error message if the provider is unavailable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Time of the health check
+    pub checked_at: DateTime<Utc>,
+}
+
+/// Service to read and save provider health statuses in Redis
+pub struct ProviderHealthService<'a> {
+    redis: &'a fred::clients::Client,
+}
+
+impl<'a> ProviderHealthService<'a> {
+    pub fn new(redis: &'a fred::clients::Client) -> Self {
+        Self { redis }
+    }
+
+    /// Get the health statuses of the given providers. Providers that haven't been
+    /// checked yet are omitted.
+    pub async fn get(
+        &self,
+        provider_ids: &[i32],
+    ) -> Result<HashMap<i32, ProviderHealth>, LlmError> {
+        if provider_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let fields: Vec<String> = provider_ids.iter().map(|id| id.to_string()).collect();
+        let values: Vec<Option<String>> = self.redis.hmget(redis_key(CACHE_KEY), fields).await?;
+
+        Ok(provider_ids
+            .iter()
+            .zip(values)
+            .filter_map(|(id, value)| Some((*id, serde_json::from_str(&value?).ok()?)))
+            .collect())
+    }
+
+    /// Whether the provider is available. Providers that haven't been checked yet are
+    /// assumed to be available.
+    pub async fn is_available(&self, provider_id: i32) -> Result<bool, LlmError> {
+        let value: Option<String> = self
+            .redis
+            .hget(redis_key(CACHE_KEY), provider_id.to_string())
+            .await?;
+        let health = value.and_then(|v| serde_json::from_str::<ProviderHealth>(&v).ok());
+
+        Ok(health.is_none_or(|h| h.available))
+    }
+
+    async fn save(&self, statuses: HashMap<i32, ProviderHealth>, ttl: i64) -> Result<(), LlmError> {
+        if statuses.is_empty() {
+            return Ok(());
+        }
+        let values: HashMap<String, String> = statuses
+            .into_iter()
+            .filter_map(|(id, health)| Some((id.to_string(), serde_json::to_string(&health).ok()?)))
+            .collect();
+        let key = redis_key(CACHE_KEY);
+        let pipeline = self.redis.pipeline();
+        let _: () = pipeline.hset(&key, values).await?;
+        let _: () = pipeline.expire(&key, ttl, None).await?;
+        let _: () = pipeline.all().await?;
+
+        Ok(())
+    }
+}
+
+/// Fairing that spawns a background task to periodically check the health of all providers.
+pub fn setup_provider_health() -> AdHoc {
+    AdHoc::on_liftoff("Provider health checks", |rocket| {
+        Box::pin(async move {
+            let interval = rocket
+                .state::<AppConfig>()
+                .and_then(|config| config.provider_health_interval)
+                .unwrap_or(DEFAULT_INTERVAL);
+            if interval == 0 {
+                rocket::info!("Provider health checks disabled");
+                return;
+            }
+            let (Some(db_pool), Some(redis_pool), Some(encryptor), Some(http_client)) = (
+                rocket.state::<DbPool>(),
+                rocket.state::<fred::clients::Pool>(),
+                rocket.state::<Encryptor>(),
+                rocket.state::<reqwest::Client>(),
+            ) else {
+                rocket::warn!("Provider health checks not started: missing managed state");
+                return;
+            };
+            let db_pool = db_pool.clone();
+            let redis = redis_pool.next().clone();
+            let encryptor = encryptor.clone();
+            let http_client = http_client.clone();
+
+            tokio::spawn(async move {
+                let ttl = i64::try_from(interval * 3).unwrap_or(i64::MAX);
+                let mut interval = tokio::time::interval(Duration::from_secs(interval));
+                loop {
+                    interval.tick().await;
+                    if let Err(err) =
+                        check_all_providers(&db_pool, &redis, &encryptor, &http_client, ttl).await
+                    {
+                        rocket::warn!("Provider health check failed: {}", err);
+                    }
+                }
+            });
+        })
+    })
+}
+
+/// Check the health of all providers and save the results in Redis
+async fn check_all_providers(
+    db_pool: &DbPool,
+    redis: &fred::clients::Client,
+    encryptor: &Encryptor,
+    http_client: &reqwest::Client,
+    ttl: i64,
+) -> Result<(), ApiError> {
+    let providers = {
+        let mut db = db_pool.get().await?;
+        ProviderDbService::new(&mut db).find_all().await?
+    };
+    let statuses: HashMap<i32, ProviderHealth> = stream::iter(providers)
+        .map(|(provider, secret)| async move {
+            let start = Instant::now();
+            let result = check_provider(&provider, secret, redis, encryptor, http_client).await;
+            let health = ProviderHealth {
+                available: result.is_ok(),
+                latency_ms: u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+                error: result.err().map(|err| err.to_string()),
+                checked_at: Utc::now(),
+            };
+            (provider.id, health)
+        })
+        .buffer_unordered(MAX_CONCURRENT_CHECKS)
+        .collect()
+        .await;
+    ProviderHealthService::new(redis)
+        .save(statuses, ttl)
+        .await?;
+
+    Ok(())
+}
+
+async fn check_provider(
+    provider: &ChatRsProvider,
+    secret: Option<ChatRsSecret>,
+    redis: &fred::clients::Client,
+    encryptor: &Encryptor,
+    http_client: &reqwest::Client,
+) -> Result<(), LlmError> {
+    let api_key = secret
+        .map(|secret| encryptor.decrypt_string(&secret.ciphertext, &secret.nonce))
+        .transpose()?;
+    let extra_headers = get_extra_headers(provider, encryptor)?;
+    let provider_api = build_llm_provider_api(
+        &provider.provider_type.as_str().try_into()?,
+        provider.base_url.as_deref(),
+        api_key.as_deref(),
+        None,
+        &extra_headers,
+        http_client,
+        redis,
+    )?;
+
+    tokio::time::timeout(CHECK_TIMEOUT, provider_api.health_check())
+        .await
+        .map_err(|_| LlmError::ProviderError("Health check timed out".into()))?
+}
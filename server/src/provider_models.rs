@@ -1,11 +1,12 @@
 use std::collections::HashMap;
 
+use chrono::{NaiveDate, Utc};
 use enum_iterator::{all, Sequence};
 use fred::prelude::{HashesInterface, KeysInterface};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::provider::LlmError;
+use crate::{provider::LlmError, redis::redis_key};
 
 const CACHE_KEY: &str = "models";
 const CACHE_TTL: i64 = 86400; // 1 day in seconds
@@ -36,6 +37,57 @@ pub struct LlmModel {
     pub format: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub family: Option<String>,
+    /// Set if the model has been deprecated by the server operator
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecation: Option<ModelDeprecation>,
+}
+
+/// A model deprecated by the server operator
+#[derive(Debug, Clone, JsonSchema, Serialize, Deserialize)]
+pub struct ModelDeprecation {
+    /// ID of the deprecated model
+    pub id: String,
+    /// ID of the suggested replacement model
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replacement: Option<String>,
+    /// Last day that the model can be used. If not set, the model can still be used
+    /// indefinitely (with a warning).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until: Option<NaiveDate>,
+}
+
+impl ModelDeprecation {
+    /// Whether the grace period has ended, and the model can no longer be used
+    pub fn is_expired(&self) -> bool {
+        self.until
+            .is_some_and(|until| Utc::now().date_naive() > until)
+    }
+
+    /// Warning message for users of the deprecated model
+    pub fn warning(&self) -> String {
+        let mut message = match self.until {
+            Some(_) if self.is_expired() => format!("Model '{}' is no longer available", self.id),
+            Some(until) => format!(
+                "Model '{}' is deprecated and can be used until {until}",
+                self.id
+            ),
+            None => format!("Model '{}' is deprecated", self.id),
+        };
+        if let Some(replacement) = &self.replacement {
+            message.push_str(&format!(". Please use '{replacement}' instead"));
+        }
+        message
+    }
+}
+
+/// Find the operator-defined deprecation of the given model, if any
+pub fn find_model_deprecation<'a>(
+    deprecations: Option<&'a [ModelDeprecation]>,
+    model_id: &str,
+) -> Option<&'a ModelDeprecation> {
+    deprecations?
+        .iter()
+        .find(|deprecation| deprecation.id == model_id)
 }
 
 #[derive(Debug, Clone, JsonSchema, Serialize, Deserialize)]
@@ -74,7 +126,7 @@ impl ModelsDevService {
     ) -> Result<Vec<LlmModel>, LlmError> {
         if let Some(models) = self
             .redis
-            .hget::<Option<String>, _, &str>(CACHE_KEY, (&provider).into())
+            .hget::<Option<String>, _, &str>(redis_key(CACHE_KEY), (&provider).into())
             .await?
             .and_then(|models| serde_json::from_str(&models).ok())
         {
@@ -111,9 +163,10 @@ impl ModelsDevService {
                 cache.insert(provider_str.to_owned(), parsed_models_str);
             }
 
+            let key = redis_key(CACHE_KEY);
             let pipeline = self.redis.pipeline();
-            let _: () = pipeline.hset(CACHE_KEY, cache).await?;
-            let _: () = pipeline.expire(CACHE_KEY, CACHE_TTL, None).await?;
+            let _: () = pipeline.hset(&key, cache).await?;
+            let _: () = pipeline.expire(&key, CACHE_TTL, None).await?;
             let _: () = pipeline.all().await?;
 
             Ok(models.unwrap_or_default())
@@ -1,7 +1,13 @@
-use std::{ops::Deref, sync::Arc, time::Duration};
+use std::{
+    ops::Deref,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
 
 use deadpool::managed;
-use fred::prelude::{Builder, Client, ClientLike, ReconnectPolicy, TcpConfig};
+use fred::prelude::{
+    Builder, Client, ClientLike, FredResult, KeysInterface, ReconnectPolicy, TcpConfig,
+};
 use rocket::{
     async_trait,
     fairing::AdHoc,
@@ -11,8 +17,9 @@ use rocket::{
 };
 use rocket_okapi::OpenApiFromRequest;
 use tokio::sync::Mutex;
+use uuid::Uuid;
 
-use crate::config::get_app_config;
+use crate::{config::get_app_config, shutdown::StreamShutdown};
 
 /// Default size of the static Redis pool.
 const REDIS_POOL_SIZE: usize = 4;
@@ -25,6 +32,24 @@ const IDLE_TASK_INTERVAL: Duration = Duration::from_secs(30);
 /// Shut down exclusive clients after this period of inactivity.
 const IDLE_TIME: Duration = Duration::from_secs(60);
 
+/// Prefix of all Redis keys and channels, set from the config on startup
+static KEY_PREFIX: OnceLock<String> = OnceLock::new();
+
+/// Set the prefix of all Redis keys and channels. Only the first call has an effect.
+pub fn set_key_prefix(prefix: &str) {
+    let _ = KEY_PREFIX.set(prefix.to_owned());
+}
+
+/// Get the prefix of all Redis keys and channels (empty if not configured)
+pub fn key_prefix() -> &'static str {
+    KEY_PREFIX.get().map(String::as_str).unwrap_or_default()
+}
+
+/// Get the full Redis key (or channel), with the configured prefix
+pub fn redis_key(key: impl std::fmt::Display) -> String {
+    format!("{}{}", key_prefix(), key)
+}
+
 /// Fairing that sets up and initializes the Redis connection pool.
 pub fn setup_redis() -> AdHoc {
     AdHoc::on_ignite("Redis", |rocket| async {
@@ -33,6 +58,7 @@ pub fn setup_redis() -> AdHoc {
                 "Initialize Redis connection",
                 |rocket| async {
                     let app_config = get_app_config(&rocket);
+                    set_key_prefix(app_config.redis_key_prefix.as_deref().unwrap_or_default());
                     let config = fred::prelude::Config::from_url(&app_config.redis_url)
                         .expect("RS_CHAT_REDIS_URL should be valid Redis URL");
 
@@ -69,6 +95,9 @@ pub fn setup_redis() -> AdHoc {
             ))
             .attach(AdHoc::on_shutdown("Shutdown Redis connection", |rocket| {
                 Box::pin(async {
+                    if let Some(shutdown) = rocket.state::<StreamShutdown>() {
+                        shutdown.finished().await; // streams may still write to Redis
+                    }
                     if let Some(pool) = rocket.state::<fred::clients::Pool>() {
                         rocket::info!("Shutting down static Redis pool");
                         if let Err(err) = pool.quit().await {
@@ -204,3 +233,42 @@ impl<'r> FromRequest<'r> for ExclusiveRedisClient {
         }
     }
 }
+
+/// Increment the counter and refresh its expiration, returning the new count
+pub async fn increment_count(redis: &Client, key: &str, ttl: i64) -> FredResult<u64> {
+    let pipeline = redis.pipeline();
+    let _: () = pipeline.incr(key).await?;
+    let _: () = pipeline.expire(key, ttl, None).await?;
+    let (count, _): (u64, i64) = pipeline.all().await?;
+
+    Ok(count)
+}
+
+/// Delete all Redis keys of the user: chat streams, import progress, login sessions, message
+/// counts, and tool usage counters and caches. Returns the number of deleted keys.
+pub async fn delete_user_keys(redis: &Client, user_id: &Uuid) -> FredResult<usize> {
+    let patterns = [
+        redis_key(format_args!("user:{}:*", user_id)),
+        redis_key(format_args!("tool_usage:{}:*", user_id)),
+        redis_key(format_args!("tool_calls:{}:*", user_id)),
+        redis_key(format_args!("tool_cache:{}:*", user_id)),
+    ];
+    let mut deleted = 0;
+    for pattern in patterns {
+        let mut cursor = "0".to_owned();
+        loop {
+            let (next_cursor, keys): (String, Vec<String>) =
+                redis.scan_page(cursor, &pattern, Some(100), None).await?;
+            if !keys.is_empty() {
+                deleted += keys.len();
+                let _: () = redis.del(keys).await?;
+            }
+            if next_cursor == "0" {
+                break;
+            }
+            cursor = next_cursor;
+        }
+    }
+
+    Ok(deleted)
+}
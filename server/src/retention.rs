@@ -0,0 +1,180 @@
+//! Automatic deletion of old data, for privacy-conscious deployments. Sessions without new
+//! messages are moved to the trash (and purged after the trash retention period), and tool
+//! runs are deleted, after the retention periods of the server or the user (the shorter of
+//! the two applies).
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rocket::fairing::AdHoc;
+use schemars::JsonSchema;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    config::AppConfig,
+    db::{services::RetentionDbService, DbConnection, DbPool},
+    errors::ApiError,
+};
+
+/// Interval between runs of the retention policies.
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Server-wide retention periods in days (no limit if not set)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RetentionPolicy {
+    pub session_days: Option<u64>,
+    pub tool_run_days: Option<u64>,
+}
+
+impl RetentionPolicy {
+    pub fn from_config(config: &AppConfig) -> Self {
+        RetentionPolicy {
+            session_days: config.session_retention_days.filter(|days| *days > 0),
+            tool_run_days: config.tool_run_retention_days.filter(|days| *days > 0),
+        }
+    }
+}
+
+/// Number of sessions moved to the trash and tool runs deleted by the retention policies
+#[derive(Debug, Default, JsonSchema, Serialize)]
+pub struct RetentionReport {
+    /// Whether nothing was deleted, and the data was only counted
+    pub dry_run: bool,
+    pub sessions: usize,
+    pub tool_runs: usize,
+}
+
+/// Get the time before which data is deleted
+fn get_cutoff(days: u64) -> DateTime<Utc> {
+    Utc::now() - chrono::Duration::days(i64::try_from(days).unwrap_or(i64::MAX))
+}
+
+/// Get the retention period of a user: the shorter of the user's and the server's
+fn get_user_days(user_days: Option<i32>, server_days: Option<u64>) -> Option<u64> {
+    match user_days.and_then(|days| u64::try_from(days).ok()) {
+        Some(days) => Some(server_days.map_or(days, |server_days| server_days.min(days))),
+        None => server_days,
+    }
+}
+
+/// Apply the retention policies of the server and of all users. With `dry_run`, the data is
+/// only counted.
+pub async fn apply_retention(
+    db: &mut DbConnection,
+    policy: RetentionPolicy,
+    dry_run: bool,
+) -> Result<RetentionReport, ApiError> {
+    let mut report = RetentionReport {
+        dry_run,
+        ..Default::default()
+    };
+    let mut db_service = RetentionDbService::new(db);
+
+    // Users without their own retention periods
+    if let Some(days) = policy.session_days {
+        report.sessions += db_service
+            .trash_sessions(None, get_cutoff(days), dry_run)
+            .await?;
+    }
+    if let Some(days) = policy.tool_run_days {
+        report.tool_runs += db_service
+            .delete_tool_runs(None, get_cutoff(days), dry_run)
+            .await?;
+    }
+
+    // Users with their own retention periods
+    for settings in db_service.list_settings().await? {
+        let (session_days, tool_run_days) = (
+            settings.session_retention_days,
+            settings.tool_run_retention_days,
+        );
+        if let Some(days) = session_days.and(get_user_days(session_days, policy.session_days)) {
+            report.sessions += db_service
+                .trash_sessions(Some(&settings.user_id), get_cutoff(days), dry_run)
+                .await?;
+        }
+        if let Some(days) = tool_run_days.and(get_user_days(tool_run_days, policy.tool_run_days)) {
+            report.tool_runs += db_service
+                .delete_tool_runs(Some(&settings.user_id), get_cutoff(days), dry_run)
+                .await?;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Count the user's data that would be deleted by the next run of the retention policies
+pub async fn get_user_retention_report(
+    db: &mut DbConnection,
+    policy: RetentionPolicy,
+    user_id: &Uuid,
+) -> Result<RetentionReport, ApiError> {
+    let mut report = RetentionReport {
+        dry_run: true,
+        ..Default::default()
+    };
+    let mut db_service = RetentionDbService::new(db);
+    let settings = db_service.get_settings(user_id).await?;
+
+    let session_days = settings.as_ref().and_then(|s| s.session_retention_days);
+    if let Some(days) = get_user_days(session_days, policy.session_days) {
+        report.sessions = db_service
+            .trash_sessions(Some(user_id), get_cutoff(days), true)
+            .await?;
+    }
+    let tool_run_days = settings.as_ref().and_then(|s| s.tool_run_retention_days);
+    if let Some(days) = get_user_days(tool_run_days, policy.tool_run_days) {
+        report.tool_runs = db_service
+            .delete_tool_runs(Some(user_id), get_cutoff(days), true)
+            .await?;
+    }
+
+    Ok(report)
+}
+
+/// Fairing that spawns a background task to periodically apply the retention policies. If
+/// `retention_dry_run` is set, the task only logs what would be deleted.
+pub fn setup_retention() -> AdHoc {
+    AdHoc::on_liftoff("Data retention", |rocket| {
+        Box::pin(async move {
+            let (Some(config), Some(db_pool)) =
+                (rocket.state::<AppConfig>(), rocket.state::<DbPool>())
+            else {
+                rocket::warn!("Data retention not started: missing managed state");
+                return;
+            };
+            let policy = RetentionPolicy::from_config(config);
+            let dry_run = config.retention_dry_run.unwrap_or(false);
+            let db_pool = db_pool.clone();
+
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(RETENTION_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    let result = match db_pool.get().await {
+                        Ok(mut conn) => apply_retention(&mut conn, policy, dry_run).await,
+                        Err(err) => Err(err.into()),
+                    };
+                    match result {
+                        Ok(report) if report.dry_run => rocket::info!(
+                            "Data retention (dry run): would trash {} sessions and delete {} \
+                            tool runs",
+                            report.sessions,
+                            report.tool_runs
+                        ),
+                        Ok(report) if report.sessions > 0 || report.tool_runs > 0 => {
+                            rocket::info!(
+                                "Data retention: trashed {} sessions and deleted {} tool runs",
+                                report.sessions,
+                                report.tool_runs
+                            )
+                        }
+                        Ok(_) => {}
+                        Err(err) => rocket::warn!("Data retention failed: {}", err),
+                    }
+                }
+            });
+        })
+    })
+}
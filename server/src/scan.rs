@@ -0,0 +1,148 @@
+//! Scanning of uploaded files with ClamAV. When a clamd address is configured, uploads are
+//! streamed to clamd (with the `INSTREAM` command over TCP) before they're stored, and infected
+//! files are rejected, or moved to the quarantine directory of the storage. Uploads are also
+//! rejected if clamd can't be reached.
+
+use std::time::Duration;
+
+use rocket::fairing::AdHoc;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use crate::config::get_app_config;
+
+/// Size of the chunks sent to clamd
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Max length of the response of clamd
+const MAX_RESPONSE_LENGTH: u64 = 4096;
+/// Timeout of a scan, including the connection
+const SCAN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What to do with infected uploads
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanAction {
+    /// Reject the upload
+    #[default]
+    Reject,
+    /// Reject the upload, and keep the file in the quarantine directory for review
+    Quarantine,
+}
+
+/// Scan errors
+#[derive(Debug, thiserror::Error)]
+pub enum ScanError {
+    #[error("Failed to connect to clamd: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Scan timed out")]
+    Timeout,
+    #[error("clamd error: {0}")]
+    Clamd(String),
+}
+
+/// Result of a scan
+#[derive(Debug, PartialEq)]
+pub enum ScanResult {
+    Clean,
+    /// The file is infected, with the name of the detected signature
+    Infected(String),
+}
+
+/// Scans files with a clamd server
+#[derive(Debug, Clone)]
+pub struct FileScanner {
+    /// Address of clamd (`host:port`)
+    address: String,
+    pub action: ScanAction,
+}
+
+impl FileScanner {
+    pub fn new(address: impl Into<String>, action: ScanAction) -> Self {
+        Self {
+            address: address.into(),
+            action,
+        }
+    }
+
+    /// Scan the file with clamd
+    pub async fn scan(&self, data: &[u8]) -> Result<ScanResult, ScanError> {
+        let response = tokio::time::timeout(SCAN_TIMEOUT, self.send_instream(data))
+            .await
+            .map_err(|_| ScanError::Timeout)??;
+        parse_scan_response(&response)
+    }
+
+    /// Check that clamd is reachable
+    pub async fn ping(&self) -> Result<(), ScanError> {
+        let response = tokio::time::timeout(SCAN_TIMEOUT, self.send_command(b"zPING\0"))
+            .await
+            .map_err(|_| ScanError::Timeout)??;
+        match response.as_str() {
+            "PONG" => Ok(()),
+            _ => Err(ScanError::Clamd(response)),
+        }
+    }
+
+    /// Stream the file to clamd in chunks prefixed by their length, ending with an empty chunk
+    async fn send_instream(&self, data: &[u8]) -> Result<String, ScanError> {
+        let mut stream = TcpStream::connect(&self.address).await?;
+        stream.write_all(b"zINSTREAM\0").await?;
+        for chunk in data.chunks(CHUNK_SIZE) {
+            stream
+                .write_all(&(chunk.len() as u32).to_be_bytes())
+                .await?;
+            stream.write_all(chunk).await?;
+        }
+        stream.write_all(&0u32.to_be_bytes()).await?;
+
+        read_response(stream).await
+    }
+
+    async fn send_command(&self, command: &[u8]) -> Result<String, ScanError> {
+        let mut stream = TcpStream::connect(&self.address).await?;
+        stream.write_all(command).await?;
+
+        read_response(stream).await
+    }
+}
+
+/// Read the null-terminated response of clamd
+async fn read_response(stream: TcpStream) -> Result<String, ScanError> {
+    let mut response = Vec::new();
+    stream
+        .take(MAX_RESPONSE_LENGTH)
+        .read_to_end(&mut response)
+        .await?;
+    let response = String::from_utf8_lossy(&response);
+
+    Ok(response.trim_end_matches('\0').trim().to_owned())
+}
+
+/// Parse the response of a scan, e.g. `stream: OK` or `stream: Eicar-Signature FOUND`
+fn parse_scan_response(response: &str) -> Result<ScanResult, ScanError> {
+    let result = response.strip_prefix("stream:").unwrap_or(response).trim();
+    if result == "OK" {
+        Ok(ScanResult::Clean)
+    } else if let Some(signature) = result.strip_suffix(" FOUND") {
+        Ok(ScanResult::Infected(signature.trim().to_owned()))
+    } else {
+        Err(ScanError::Clamd(result.to_owned()))
+    }
+}
+
+/// Fairing that sets up the scanning of uploads, if a clamd address is configured
+pub fn setup_file_scanner() -> AdHoc {
+    AdHoc::on_ignite("File scanner setup", |rocket| async {
+        let app_config = get_app_config(&rocket);
+        let Some(address) = app_config.clamav_address.clone() else {
+            return rocket;
+        };
+        let action = app_config.clamav_action.unwrap_or_default();
+        rocket::info!("Scanning uploads with clamd at {address} (action: {action:?})");
+
+        rocket.manage(FileScanner::new(address, action))
+    })
+}
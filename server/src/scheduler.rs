@@ -0,0 +1,264 @@
+//! Scheduled prompts, e.g. for daily digests and recurring reports. A background task sends
+//! the due prompts to their provider, and saves the prompts and responses to their session.
+
+use std::{str::FromStr, time::Duration};
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use fred::{
+    prelude::KeysInterface,
+    types::{Expiration, SetOptions},
+};
+use rocket::{fairing::AdHoc, futures::StreamExt};
+
+use crate::{
+    db::{
+        models::{
+            AssistantMeta, ChatRsMessage, ChatRsMessageMeta, ChatRsMessageRole,
+            ChatRsScheduledPrompt, NewChatRsMessage, UpdateChatRsScheduledPrompt,
+        },
+        services::{ChatDbService, ProviderDbService, ScheduleDbService},
+        DbConnection, DbPool,
+    },
+    errors::ApiError,
+    provider::{build_llm_provider_api, get_extra_headers, LlmError, LlmStreamChunk, LlmUsage},
+    redis::redis_key,
+    utils::Encryptor,
+    webhooks::trigger_message_webhooks,
+};
+
+/// Interval between checks for due prompts.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Redis key of the lock that lets only one server instance run the due prompts.
+const LOCK_KEY: &str = "scheduler_lock";
+/// Expiration of the lock in seconds, shorter than the check interval so that another
+/// instance can take over if this one stops.
+const LOCK_TTL: i64 = 55;
+/// Max number of due prompts run in one check.
+const MAX_DUE_PROMPTS: i64 = 50;
+/// Max number of scheduled prompts per user.
+pub const MAX_SCHEDULED_PROMPTS: i64 = 20;
+
+/// Errors of scheduled prompts
+#[derive(Debug, thiserror::Error)]
+pub enum ScheduleError {
+    #[error("Invalid cron expression: {0}")]
+    InvalidCron(String),
+    #[error("Unknown timezone '{0}'. Use an IANA timezone name, e.g. `America/New_York`")]
+    InvalidTimezone(String),
+    #[error("Too many scheduled prompts (max {0})")]
+    TooManySchedules(i64),
+}
+
+/// Get the next run time of a cron schedule after the given time. Accepts standard cron
+/// expressions with 5 fields (minute, hour, day of month, month, and day of week), or with
+/// an additional seconds field first. Returns `None` if the schedule has no more runs.
+pub fn next_run(
+    cron: &str,
+    timezone: &str,
+    after: DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>, ScheduleError> {
+    let tz: Tz = timezone
+        .trim()
+        .parse()
+        .map_err(|_| ScheduleError::InvalidTimezone(timezone.to_owned()))?;
+    let expression = match cron.split_whitespace().count() {
+        5 => format!("0 {}", cron.trim()),
+        _ => cron.trim().to_owned(),
+    };
+    let schedule = cron::Schedule::from_str(&expression)
+        .map_err(|err| ScheduleError::InvalidCron(err.to_string()))?;
+
+    Ok(schedule
+        .after(&after.with_timezone(&tz))
+        .next()
+        .map(|time| time.with_timezone(&Utc)))
+}
+
+/// Send a scheduled prompt to its provider, and save the prompt and the response to its
+/// session. The prompt is sent without the rest of the session's messages.
+pub async fn run_scheduled_prompt(
+    db: &mut DbConnection,
+    redis: &fred::clients::Client,
+    encryptor: &Encryptor,
+    http_client: &reqwest::Client,
+    schedule: &ChatRsScheduledPrompt,
+) -> Result<ChatRsMessage, ApiError> {
+    // Check that the session hasn't been deleted
+    ChatDbService::new(db)
+        .get_session(&schedule.user_id, &schedule.session_id)
+        .await?;
+
+    let (provider, api_key_secret, secondary_api_key_secret) = ProviderDbService::new(db)
+        .get_by_id_with_secrets(&schedule.user_id, schedule.provider_id)
+        .await?;
+    let api_key = api_key_secret
+        .map(|secret| encryptor.decrypt_string(&secret.ciphertext, &secret.nonce))
+        .transpose()?;
+    let secondary_api_key = secondary_api_key_secret
+        .map(|secret| encryptor.decrypt_string(&secret.ciphertext, &secret.nonce))
+        .transpose()?;
+    let extra_headers = get_extra_headers(&provider, encryptor)?;
+    let provider_api = build_llm_provider_api(
+        &provider.provider_type.as_str().try_into()?,
+        provider.base_url.as_deref(),
+        api_key.as_deref(),
+        secondary_api_key.as_deref(),
+        &extra_headers,
+        http_client,
+        redis,
+    )?;
+
+    let user_message = ChatDbService::new(db)
+        .save_message(NewChatRsMessage {
+            session_id: &schedule.session_id,
+            role: ChatRsMessageRole::User,
+            content: &schedule.prompt,
+            meta: ChatRsMessageMeta::default(),
+        })
+        .await?;
+    let options = &schedule.data.options;
+    let mut stream = provider_api
+        .chat_stream(vec![user_message], None, options)
+        .await?;
+
+    let mut text = String::new();
+    let mut usage: Option<LlmUsage> = None;
+    let mut finish_reason = None;
+    let mut errors = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        match chunk {
+            Ok(LlmStreamChunk::Text(chunk)) => text.push_str(&chunk),
+            Ok(LlmStreamChunk::Usage(chunk)) => {
+                let usage = usage.get_or_insert_default();
+                usage.input_tokens = chunk.input_tokens.or(usage.input_tokens);
+                usage.output_tokens = chunk.output_tokens.or(usage.output_tokens);
+                usage.cost = chunk.cost.or(usage.cost);
+            }
+            Ok(LlmStreamChunk::FinishReason(reason)) => finish_reason = Some(reason),
+            Ok(_) => {} // no tools are given
+            Err(err) => errors.push(err.to_string()),
+        }
+    }
+
+    let error = (!errors.is_empty()).then(|| errors.join("; "));
+    let assistant_meta = AssistantMeta {
+        provider_id: schedule.provider_id,
+        provider_options: Some(options.clone()),
+        usage,
+        finish_reason,
+        errors: (!errors.is_empty()).then_some(errors),
+        ..Default::default()
+    };
+    let message = ChatDbService::new(db)
+        .save_message(NewChatRsMessage {
+            session_id: &schedule.session_id,
+            role: ChatRsMessageRole::Assistant,
+            content: &text,
+            meta: ChatRsMessageMeta::new_assistant(assistant_meta),
+        })
+        .await?;
+    if let Err(err) = trigger_message_webhooks(db, &schedule.user_id, &message).await {
+        rocket::warn!("Failed to trigger webhooks: {}", err);
+    }
+    if let Some(error) = error {
+        return Err(LlmError::ProviderError(error))?;
+    }
+
+    Ok(message)
+}
+
+/// Run the scheduled prompts that are due, if no other server instance is running them
+async fn run_due_prompts(
+    db_pool: &DbPool,
+    redis: &fred::clients::Client,
+    encryptor: &Encryptor,
+    http_client: &reqwest::Client,
+) -> Result<(), ApiError> {
+    let lock: Option<String> = redis
+        .set(
+            redis_key(LOCK_KEY),
+            "1",
+            Some(Expiration::EX(LOCK_TTL)),
+            Some(SetOptions::NX),
+            false,
+        )
+        .await?;
+    if lock.is_none() {
+        return Ok(());
+    }
+
+    let mut db = db_pool.get().await?;
+    let now = Utc::now();
+    let schedules = ScheduleDbService::new(&mut db)
+        .find_due(now, MAX_DUE_PROMPTS)
+        .await?;
+    for schedule in schedules {
+        // Set the next run first, so that the prompt isn't run twice
+        let next_run_at = next_run(&schedule.cron, &schedule.timezone, now)
+            .ok()
+            .flatten();
+        ScheduleDbService::new(&mut db)
+            .update(
+                &schedule.user_id,
+                &schedule.id,
+                UpdateChatRsScheduledPrompt {
+                    next_run_at: Some(next_run_at),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        let result = run_scheduled_prompt(&mut db, redis, encryptor, http_client, &schedule).await;
+        let error = result.err().map(|err| err.to_string());
+        if let Some(error) = &error {
+            rocket::warn!("Scheduled prompt {} failed: {}", schedule.id, error);
+        }
+        ScheduleDbService::new(&mut db)
+            .update(
+                &schedule.user_id,
+                &schedule.id,
+                UpdateChatRsScheduledPrompt {
+                    last_run_at: Some(Some(Utc::now())),
+                    last_error: Some(error.as_deref()),
+                    ..Default::default()
+                },
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Fairing that spawns a background task to periodically run the due scheduled prompts.
+pub fn setup_scheduler() -> AdHoc {
+    AdHoc::on_liftoff("Scheduler", |rocket| {
+        Box::pin(async move {
+            let (Some(db_pool), Some(redis_pool), Some(encryptor), Some(http_client)) = (
+                rocket.state::<DbPool>(),
+                rocket.state::<fred::clients::Pool>(),
+                rocket.state::<Encryptor>(),
+                rocket.state::<reqwest::Client>(),
+            ) else {
+                rocket::warn!("Scheduler not started: missing managed state");
+                return;
+            };
+            let db_pool = db_pool.clone();
+            let redis = redis_pool.next().clone();
+            let encryptor = encryptor.clone();
+            let http_client = http_client.clone();
+
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(CHECK_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    if let Err(err) =
+                        run_due_prompts(&db_pool, &redis, &encryptor, &http_client).await
+                    {
+                        rocket::warn!("Scheduler failed: {}", err);
+                    }
+                }
+            });
+        })
+    })
+}
@@ -0,0 +1,131 @@
+//! Graceful shutdown of the chat streams. When the server shuts down, new chat streams are
+//! rejected, and the ongoing streams get some time to finish and save their response. Streams
+//! still running after the timeout are interrupted, and their responses are saved as partial
+//! messages. The database and Redis connections are closed once the streams are done.
+
+use std::{sync::Arc, time::Duration};
+
+use rocket::fairing::AdHoc;
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::get_app_config;
+
+/// Default time to wait for the ongoing streams to finish, in seconds
+const DEFAULT_SHUTDOWN_TIMEOUT: u64 = 30;
+/// Time to wait for the interrupted streams to save their partial responses
+const INTERRUPT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Tracks the ongoing chat streams, to wait for them on shutdown
+#[derive(Clone)]
+pub struct StreamShutdown {
+    inner: Arc<ShutdownState>,
+}
+
+struct ShutdownState {
+    /// Number of ongoing streams
+    active: watch::Sender<usize>,
+    /// Cancelled once the server starts shutting down
+    shutting_down: CancellationToken,
+    /// Cancelled when the remaining streams should be interrupted
+    interrupt: CancellationToken,
+    /// Set once the streams are done, or the shutdown timed out
+    finished: watch::Sender<bool>,
+}
+
+/// Handle of an ongoing stream. The stream is considered done when it's dropped.
+pub struct ActiveStream {
+    inner: Arc<ShutdownState>,
+}
+
+impl ActiveStream {
+    /// Signal that is cancelled if the stream should be interrupted
+    pub fn interrupt_signal(&self) -> CancellationToken {
+        self.inner.interrupt.clone()
+    }
+}
+
+impl Drop for ActiveStream {
+    fn drop(&mut self) {
+        self.inner.active.send_modify(|active| *active -= 1);
+    }
+}
+
+impl Default for StreamShutdown {
+    fn default() -> Self {
+        StreamShutdown {
+            inner: Arc::new(ShutdownState {
+                active: watch::Sender::new(0),
+                shutting_down: CancellationToken::new(),
+                interrupt: CancellationToken::new(),
+                finished: watch::Sender::new(false),
+            }),
+        }
+    }
+}
+
+impl StreamShutdown {
+    /// Register a new stream. Returns `None` if the server is shutting down.
+    pub fn start_stream(&self) -> Option<ActiveStream> {
+        if self.inner.shutting_down.is_cancelled() {
+            return None;
+        }
+        self.inner.active.send_modify(|active| *active += 1);
+        Some(ActiveStream {
+            inner: self.inner.clone(),
+        })
+    }
+
+    /// Wait until the streams are done after the server started shutting down
+    pub async fn finished(&self) {
+        let mut finished = self.inner.finished.subscribe();
+        finished.wait_for(|finished| *finished).await.ok();
+    }
+
+    /// Reject new streams, and wait for the ongoing streams to finish. They're interrupted
+    /// after the timeout.
+    async fn shutdown(&self, timeout: Duration) {
+        self.inner.shutting_down.cancel();
+        let active = *self.inner.active.borrow();
+        if active > 0 {
+            rocket::info!("Waiting for {} chat streams to finish", active);
+            if !self.wait_for_streams(timeout).await {
+                let active = *self.inner.active.borrow();
+                rocket::warn!("Interrupting {} chat streams", active);
+                self.inner.interrupt.cancel();
+                if !self.wait_for_streams(INTERRUPT_TIMEOUT).await {
+                    rocket::warn!("Chat streams didn't finish, responses may be lost");
+                }
+            }
+        }
+        self.inner.finished.send_replace(true);
+    }
+
+    /// Wait until there are no ongoing streams. Returns `false` if timed out.
+    async fn wait_for_streams(&self, timeout: Duration) -> bool {
+        let mut active = self.inner.active.subscribe();
+        tokio::time::timeout(timeout, active.wait_for(|active| *active == 0))
+            .await
+            .is_ok()
+    }
+}
+
+/// Fairing that tracks the chat streams, and waits for them on shutdown. The wait is bounded by
+/// the `shutdown_timeout` config.
+pub fn setup_graceful_shutdown() -> AdHoc {
+    AdHoc::on_ignite("Graceful shutdown", |rocket| async {
+        let timeout = get_app_config(&rocket)
+            .shutdown_timeout
+            .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT);
+        let shutdown = StreamShutdown::default();
+
+        rocket.manage(shutdown.clone()).attach(AdHoc::on_shutdown(
+            "Finish chat streams",
+            move |_| {
+                Box::pin(async move {
+                    shutdown.shutdown(Duration::from_secs(timeout)).await;
+                })
+            },
+        ))
+    })
+}
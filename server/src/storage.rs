@@ -0,0 +1,427 @@
+//! Local file storage for files generated by tools (e.g. images), files written by the
+//! files tool, and files attached to chat messages
+
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use chrono::{DateTime, Utc};
+use rocket::fairing::AdHoc;
+use schemars::JsonSchema;
+use uuid::Uuid;
+
+use crate::{config::get_app_config, db::DbPool, scan::ScanError};
+
+/// Default directory for stored files
+pub(crate) const DEFAULT_STORAGE_PATH: &str = "./data/files";
+/// MIME type of Word documents
+pub const DOCX_MIME_TYPE: &str =
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+/// Directory of the quarantined files, in the storage root
+const QUARANTINE_DIR: &str = "quarantine";
+/// Default storage quota of each user, in MiB
+const DEFAULT_QUOTA_MIB: u64 = 1024;
+/// How often the user's files are counted again, to correct the recorded usage (e.g. if a
+/// file was saved while the files were counted)
+const RECOUNT_INTERVAL: chrono::Duration = chrono::Duration::days(1);
+
+/// Storage-related errors
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    #[error("File not found")]
+    NotFound,
+    #[error("Invalid storage path")]
+    InvalidPath,
+    #[error("Unsupported file type: {0}")]
+    UnsupportedType(String),
+    #[error("File is too large (max {0} bytes)")]
+    TooLarge(u64),
+    #[error("Too many files (max {0})")]
+    TooManyFiles(usize),
+    #[error("Storage quota exceeded (max {0} bytes)")]
+    QuotaExceeded(u64),
+    #[error("File rejected by the virus scan ({0})")]
+    Infected(String),
+    #[error("Failed to scan file: {0}")]
+    Scan(#[from] ScanError),
+    #[error("Storage error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// A file or directory in the user's storage
+#[derive(Debug, JsonSchema, serde::Serialize)]
+pub struct StoredFileInfo {
+    /// Path relative to the user's directory
+    pub path: String,
+    pub is_dir: bool,
+    /// Size of the file, in bytes
+    pub size: u64,
+    /// Last modification time
+    pub modified_at: Option<DateTime<Utc>>,
+}
+
+/// Storage used by a user
+#[derive(Debug, JsonSchema, serde::Serialize)]
+pub struct StorageUsage {
+    /// Total size of the user's files, in bytes
+    pub used: u64,
+    /// Quota of the user, in bytes (not set if unlimited)
+    pub quota: Option<u64>,
+}
+
+/// Error of the usage record
+pub type UsageRecordError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Record of the total size of each user's files (e.g. in the database), kept up to date as
+/// files are saved and deleted
+#[rocket::async_trait]
+pub trait UsageRecord: Send + Sync {
+    /// Get the recorded size of the user's files, if they've been counted after the given time
+    async fn find(
+        &self,
+        user_id: &Uuid,
+        counted_after: DateTime<Utc>,
+    ) -> Result<Option<u64>, UsageRecordError>;
+
+    /// Record the counted size of the user's files, unless they've been counted after the
+    /// given time
+    async fn set(
+        &self,
+        user_id: &Uuid,
+        used: u64,
+        counted_before: DateTime<Utc>,
+    ) -> Result<(), UsageRecordError>;
+
+    /// Add the change in size to the recorded size of the user's files, if they've been counted
+    async fn add(&self, user_id: &Uuid, change: i64) -> Result<(), UsageRecordError>;
+}
+
+/// Stores files on the local filesystem, in a separate directory for each user.
+/// Files are referenced by their storage path (`<user_id>/<file_id>.<extension>`).
+/// The total size of each user's files is counted once a day, and kept up to date in
+/// the usage record as files are saved and deleted.
+#[derive(Clone)]
+pub struct LocalStorage {
+    root: PathBuf,
+    /// Maximum total size of each user's files, in bytes
+    quota: Option<u64>,
+    /// Record of the storage used by each user (if not set, the files are counted each time)
+    usage_record: Option<Arc<dyn UsageRecord>>,
+}
+
+impl std::fmt::Debug for LocalStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocalStorage")
+            .field("root", &self.root)
+            .field("quota", &self.quota)
+            .finish()
+    }
+}
+
+impl LocalStorage {
+    pub fn new(
+        root: impl Into<PathBuf>,
+        quota: Option<u64>,
+        usage_record: Option<Arc<dyn UsageRecord>>,
+    ) -> Self {
+        Self {
+            root: root.into(),
+            quota,
+            usage_record,
+        }
+    }
+
+    /// Save a file for the user and return its storage path.
+    pub async fn save(
+        &self,
+        user_id: &Uuid,
+        data: &[u8],
+        mime: &str,
+    ) -> Result<String, StorageError> {
+        let extension = get_file_extension(mime)
+            .ok_or_else(|| StorageError::UnsupportedType(mime.to_owned()))?;
+        self.check_quota(user_id, data.len() as u64, 0).await?;
+        let storage_path = format!("{}/{}.{}", user_id, Uuid::new_v4(), extension);
+        let path = self.root.join(&storage_path);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&path, data).await?;
+        self.record_usage(user_id, data.len() as i64).await;
+
+        Ok(storage_path)
+    }
+
+    /// Save a file rejected by the virus scan to the quarantine directory, outside of the
+    /// users' directories, and return its path.
+    pub async fn quarantine(&self, data: &[u8], mime: &str) -> Result<PathBuf, StorageError> {
+        let extension = get_file_extension(mime).unwrap_or("bin");
+        let dir = self.root.join(QUARANTINE_DIR);
+        tokio::fs::create_dir_all(&dir).await?;
+        let path = dir.join(format!("{}.{}", Uuid::new_v4(), extension));
+        tokio::fs::write(&path, data).await?;
+
+        Ok(path)
+    }
+
+    /// Get the full path of the user's stored file, ensuring that it stays within
+    /// the user's directory.
+    pub async fn get_path(
+        &self,
+        user_id: &Uuid,
+        storage_path: &Path,
+    ) -> Result<PathBuf, StorageError> {
+        let path = self.resolve_path(user_id, storage_path)?;
+        if !tokio::fs::try_exists(&path).await? {
+            return Err(StorageError::NotFound);
+        }
+
+        Ok(path)
+    }
+
+    /// List the files and directories in one of the user's directories. The user's own
+    /// directory is empty if no files have been saved yet.
+    pub async fn list(
+        &self,
+        user_id: &Uuid,
+        storage_path: &Path,
+    ) -> Result<Vec<StoredFileInfo>, StorageError> {
+        let path = self.resolve_path(user_id, storage_path)?;
+        let mut entries = match tokio::fs::read_dir(&path).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return match storage_path.components().count() {
+                    1 => Ok(Vec::new()),
+                    _ => Err(StorageError::NotFound),
+                };
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let user_root = self.root.join(user_id.to_string());
+        let mut files = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            let entry_path = entry.path();
+            let relative_path = entry_path.strip_prefix(&user_root).unwrap_or(&entry_path);
+            files.push(StoredFileInfo {
+                path: relative_path.to_string_lossy().into_owned(),
+                is_dir: metadata.is_dir(),
+                size: metadata.len(),
+                modified_at: metadata.modified().ok().map(DateTime::from),
+            });
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(files)
+    }
+
+    /// Read one of the user's stored files, up to the given size in bytes.
+    pub async fn read(
+        &self,
+        user_id: &Uuid,
+        storage_path: &Path,
+        max_size: u64,
+    ) -> Result<Vec<u8>, StorageError> {
+        let path = self.get_path(user_id, storage_path).await?;
+        let metadata = tokio::fs::metadata(&path).await?;
+        if !metadata.is_file() {
+            return Err(StorageError::NotFound);
+        }
+        if metadata.len() > max_size {
+            return Err(StorageError::TooLarge(max_size));
+        }
+
+        Ok(tokio::fs::read(&path).await?)
+    }
+
+    /// Write a file in the user's directory at the given storage path, creating its parent
+    /// directories and replacing any existing file.
+    pub async fn write(
+        &self,
+        user_id: &Uuid,
+        storage_path: &Path,
+        data: &[u8],
+    ) -> Result<(), StorageError> {
+        let path = self.resolve_path(user_id, storage_path)?;
+        if storage_path.components().count() < 2 {
+            return Err(StorageError::InvalidPath);
+        }
+        let replaced_size = match tokio::fs::metadata(&path).await {
+            Ok(metadata) if metadata.is_dir() => return Err(StorageError::InvalidPath),
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        };
+        self.check_quota(user_id, data.len() as u64, replaced_size)
+            .await?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&path, data).await?;
+        self.record_usage(user_id, data.len() as i64 - replaced_size as i64)
+            .await;
+
+        Ok(())
+    }
+
+    /// Delete one of the user's stored files.
+    pub async fn delete(&self, user_id: &Uuid, storage_path: &Path) -> Result<(), StorageError> {
+        let path = self.get_path(user_id, storage_path).await?;
+        let metadata = tokio::fs::metadata(&path).await?;
+        if !metadata.is_file() {
+            return Err(StorageError::NotFound);
+        }
+        tokio::fs::remove_file(&path).await?;
+        self.record_usage(user_id, -(metadata.len() as i64)).await;
+
+        Ok(())
+    }
+
+    /// Get the directory of the user's stored files (it may not exist yet).
+    pub fn get_user_dir(&self, user_id: &Uuid) -> PathBuf {
+        self.root.join(user_id.to_string())
+    }
+
+    /// Get the total size of the user's stored files, and their quota. The files are counted
+    /// if their size hasn't been recorded in the last day.
+    pub async fn usage(&self, user_id: &Uuid) -> Result<StorageUsage, StorageError> {
+        let Some(usage_record) = &self.usage_record else {
+            return Ok(StorageUsage {
+                used: self.count_usage(user_id).await?,
+                quota: self.quota,
+            });
+        };
+        let counted_after = Utc::now() - RECOUNT_INTERVAL;
+        let used = match usage_record.find(user_id, counted_after).await {
+            Ok(Some(used)) => used,
+            Ok(None) => {
+                let used = self.count_usage(user_id).await?;
+                if let Err(err) = usage_record.set(user_id, used, counted_after).await {
+                    rocket::warn!("Failed to save storage usage of user {user_id}: {err}");
+                }
+                used
+            }
+            Err(err) => {
+                rocket::warn!("Failed to get storage usage of user {user_id}: {err}");
+                self.count_usage(user_id).await?
+            }
+        };
+
+        Ok(StorageUsage {
+            used,
+            quota: self.quota,
+        })
+    }
+
+    /// Count the total size of the user's stored files.
+    async fn count_usage(&self, user_id: &Uuid) -> Result<u64, StorageError> {
+        let mut used = 0;
+        let mut dirs = vec![self.get_user_dir(user_id)];
+        while let Some(dir) = dirs.pop() {
+            let mut entries = match tokio::fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.into()),
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let metadata = entry.metadata().await?;
+                match metadata.is_dir() {
+                    true => dirs.push(entry.path()),
+                    false => used += metadata.len(),
+                }
+            }
+        }
+
+        Ok(used)
+    }
+
+    /// Add the change in size of the user's files to the recorded usage. Failures are only
+    /// logged, as the file has already been saved or deleted.
+    async fn record_usage(&self, user_id: &Uuid, change: i64) {
+        let Some(usage_record) = &self.usage_record else {
+            return;
+        };
+        if let Err(err) = usage_record.add(user_id, change).await {
+            rocket::warn!("Failed to update storage usage of user {user_id}: {err}");
+        }
+    }
+
+    /// Delete all stored files of the user.
+    pub async fn delete_by_user(&self, user_id: &Uuid) -> Result<(), StorageError> {
+        match tokio::fs::remove_dir_all(self.get_user_dir(user_id)).await {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that adding a file of the given size, replacing a file of `replaced_size`,
+    /// keeps the user's files within their quota.
+    async fn check_quota(
+        &self,
+        user_id: &Uuid,
+        size: u64,
+        replaced_size: u64,
+    ) -> Result<(), StorageError> {
+        let Some(quota) = self.quota else {
+            return Ok(());
+        };
+        let usage = self.usage(user_id).await?;
+        if usage.used.saturating_sub(replaced_size) + size > quota {
+            return Err(StorageError::QuotaExceeded(quota));
+        }
+
+        Ok(())
+    }
+
+    /// Get the full path of a storage path, ensuring that it's within the user's directory
+    /// and doesn't contain any `..` or root components.
+    fn resolve_path(&self, user_id: &Uuid, storage_path: &Path) -> Result<PathBuf, StorageError> {
+        let mut components = storage_path.components();
+        if components.next() != Some(Component::Normal(user_id.to_string().as_ref()))
+            || !components.all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(StorageError::InvalidPath);
+        }
+
+        Ok(self.root.join(storage_path))
+    }
+}
+
+/// Get the file extension for the supported MIME types
+fn get_file_extension(mime: &str) -> Option<&'static str> {
+    match mime {
+        "image/png" => Some("png"),
+        "image/jpeg" => Some("jpg"),
+        "image/gif" => Some("gif"),
+        "image/webp" => Some("webp"),
+        "image/svg+xml" => Some("svg"),
+        "application/pdf" => Some("pdf"),
+        DOCX_MIME_TYPE => Some("docx"),
+        "text/plain" => Some("txt"),
+        "text/markdown" => Some("md"),
+        "text/html" => Some("html"),
+        _ => None,
+    }
+}
+
+/// Fairing that sets up the local file storage. Must be attached after the database setup,
+/// for the storage used by each user.
+pub fn setup_storage() -> AdHoc {
+    AdHoc::on_ignite("Storage setup", |rocket| async {
+        let app_config = get_app_config(&rocket);
+        let root = app_config
+            .storage_path
+            .as_deref()
+            .unwrap_or(DEFAULT_STORAGE_PATH);
+        let quota = match app_config.storage_quota_mib.unwrap_or(DEFAULT_QUOTA_MIB) {
+            0 => None,
+            quota_mib => Some(quota_mib * 1024 * 1024),
+        };
+        let usage_record = rocket
+            .state::<DbPool>()
+            .map(|db_pool| Arc::new(db_pool.clone()) as Arc<dyn UsageRecord>);
+
+        rocket.manage(LocalStorage::new(root, quota, usage_record))
+    })
+}
@@ -1,15 +1,26 @@
+mod backend;
+mod import_writer;
+mod lease;
 mod llm_writer;
 mod reader;
+mod session_events;
+mod tool_writer;
 
 use std::collections::HashMap;
 
-use fred::{
-    prelude::{FredResult, KeysInterface, StreamsInterface},
-    types::scan::ScanType,
-};
+use chrono::{DateTime, Utc};
+use fred::prelude::FredResult;
 
+pub use backend::{
+    setup_stream_backend, ExclusiveStreamClient, MemoryStreams, StreamBackend, StreamBackendKind,
+    StreamClient, StreamEntry,
+};
+pub use import_writer::*;
+pub use lease::*;
 pub use llm_writer::*;
 pub use reader::*;
+pub use session_events::*;
+pub use tool_writer::*;
 
 use rocket::{
     async_trait,
@@ -18,56 +29,99 @@ use rocket::{
     Request,
 };
 use rocket_okapi::OpenApiFromRequest;
+use schemars::JsonSchema;
 use uuid::Uuid;
 
-/// Get the key prefix for the user's chat streams in Redis
+use crate::redis::redis_key;
+
+/// Get the key prefix for the user's chat streams in Redis (with the configured key prefix)
 fn get_chat_stream_prefix(user_id: &Uuid) -> String {
-    format!("user:{}:chat:", user_id)
+    redis_key(format_args!("user:{}:chat:", user_id))
 }
 
 /// Get the key of the chat stream in Redis for the given user and session ID
-fn get_chat_stream_key(user_id: &Uuid, session_id: &Uuid) -> String {
+pub fn get_chat_stream_key(user_id: &Uuid, session_id: &Uuid) -> String {
     format!("{}{}", get_chat_stream_prefix(user_id), session_id)
 }
 
-/// Get the ongoing chat stream sessions for a user.
+/// Get the key of the progress stream in Redis for the given user and bulk import ID
+pub fn get_import_stream_key(user_id: &Uuid, import_id: &Uuid) -> String {
+    redis_key(format_args!("user:{}:import:{}", user_id, import_id))
+}
+
+/// An ongoing chat stream
+#[derive(Debug, JsonSchema, serde::Serialize)]
+pub struct ChatStreamInfo {
+    /// The session of the stream
+    pub session_id: Uuid,
+    /// When the stream started
+    pub started_at: Option<DateTime<Utc>>,
+    /// The provider generating the response
+    pub provider_id: Option<i32>,
+    /// The model generating the response
+    pub model: Option<String>,
+}
+
+/// Get the ongoing chat streams for a user.
 pub async fn get_current_chat_streams(
-    redis: &fred::clients::Client,
+    streams: &StreamClient,
     user_id: &Uuid,
-) -> FredResult<Vec<String>> {
+) -> FredResult<Vec<ChatStreamInfo>> {
     let prefix = get_chat_stream_prefix(user_id);
-    let pattern = format!("{}*", prefix);
-    let (_, keys): (String, Vec<String>) = redis
-        .scan_page("0", &pattern, Some(20), Some(ScanType::Stream))
-        .await?;
-    Ok(keys
-        .into_iter()
-        .filter_map(|key| Some(key.strip_prefix(&prefix)?.to_string()))
-        .collect())
+    let keys = streams.keys(&prefix).await?;
+
+    let mut chat_streams = Vec::with_capacity(keys.len());
+    for key in keys {
+        let Some(session_id) = key
+            .strip_prefix(&prefix)
+            .and_then(|id| Uuid::parse_str(id).ok())
+        else {
+            continue;
+        };
+        // The `start` entry has the provider and model, and its ID has the start time
+        let (id, mut data) = streams.first(&key).await?.unwrap_or_default();
+        let started_at = id
+            .split('-')
+            .next()
+            .and_then(|ms| ms.parse().ok())
+            .and_then(DateTime::from_timestamp_millis);
+        chat_streams.push(ChatStreamInfo {
+            session_id,
+            started_at,
+            provider_id: data.get("provider_id").and_then(|id| id.parse().ok()),
+            model: data.remove("model"),
+        });
+    }
+    chat_streams.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+
+    Ok(chat_streams)
 }
 
 /// Check if the chat stream exists.
 pub async fn check_chat_stream_exists(
-    redis: &fred::clients::Client,
+    streams: &StreamClient,
     user_id: &Uuid,
     session_id: &Uuid,
 ) -> FredResult<bool> {
     let key = get_chat_stream_key(user_id, session_id);
-    let first_entry: Option<()> = redis.xread(Some(1), None, &key, "0-0").await?;
-    Ok(first_entry.is_some())
+    Ok(streams.first(&key).await?.is_some())
 }
 
-/// Cancel a stream by adding a `cancel` event to the stream and then deleting it from Redis
-/// (not using a pipeline since we need to ensure the `cancel` event is processed before deleting the stream).
+/// Cancel a stream by adding a `cancel` event to the stream and then deleting it (not in the
+/// same call, since we need to ensure the `cancel` event is processed before deleting the stream).
+/// The lease of the stream is released, whichever server instance holds it.
 pub async fn cancel_current_chat_stream(
-    redis: &fred::clients::Client,
+    streams: &StreamClient,
     user_id: &Uuid,
     session_id: &Uuid,
 ) -> FredResult<()> {
     let key = get_chat_stream_key(user_id, session_id);
     let entry: HashMap<String, String> = RedisStreamChunk::Cancel.into();
-    let _: () = redis.xadd(&key, true, None, "*", entry).await?;
-    redis.del(&key).await
+    streams.add(&key, &[entry], false, None).await?;
+    streams.delete(&key).await?;
+    streams
+        .release_lease(&get_stream_lease_key(&key), None)
+        .await
 }
 
 /// Request guard to extract the Last-Event-ID from the request headers
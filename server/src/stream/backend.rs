@@ -0,0 +1,658 @@
+//! Backends of the chat, import, and tool output streams, and of the live session events. By
+//! default, they use Redis streams and pub/sub, so that they're shared by all server
+//! instances. Small deployments running a single instance can keep them in the memory of the
+//! process instead, by setting `RS_CHAT_STREAM_BACKEND=local`. Redis is still required in that
+//! case, for the sessions, caches, and counters.
+//!
+//! The backends also hold the leases of the chat streams, which record the server instance
+//! generating each response (see the `lease` module).
+
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use fred::{
+    prelude::{
+        Client, ClientLike, EventInterface, FredResult, KeysInterface, LuaInterface,
+        PubsubInterface, StreamsInterface,
+    },
+    types::{scan::ScanType, Expiration, SetOptions},
+};
+use rocket::{
+    async_trait,
+    fairing::AdHoc,
+    request::{FromRequest, Outcome},
+    Request,
+};
+use rocket_okapi::OpenApiFromRequest;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, mpsc};
+
+use crate::{
+    config::get_app_config,
+    redis::{ExclusiveRedisClient, RedisClient},
+    stream::{instance_id, set_instance_id},
+};
+
+/// Max number of entries kept in each stream (older entries are dropped)
+const MAX_STREAM_LENGTH: usize = 500;
+/// Capacity of the in-memory channels of the session events
+const CHANNEL_CAPACITY: usize = 50;
+/// Capacity of the in-memory channels of the new entries of each stream
+const UPDATES_CAPACITY: usize = 16;
+
+/// Refresh the expiration of a lease if it's held by the owner
+const RENEW_LEASE_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("EXPIRE", KEYS[1], ARGV[2])
+end
+return 0
+"#;
+/// Delete a lease if it's held by the owner
+const RELEASE_LEASE_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("DEL", KEYS[1])
+end
+return 0
+"#;
+
+/// An entry of a stream: its ID (`<milliseconds>-<sequence>`), and its fields
+pub type StreamEntry = (String, HashMap<String, String>);
+
+/// Where the streams and session events are stored
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamBackendKind {
+    /// Redis streams and pub/sub, shared by all server instances
+    #[default]
+    Redis,
+    /// In-process streams and channels, for a single server instance (Redis is still used
+    /// for everything else)
+    Local,
+}
+
+/// Storage of the streams and session events. Errors are reported as Redis errors, as the
+/// in-memory backend can't fail.
+#[async_trait]
+pub trait StreamBackend: Send + Sync {
+    /// Add the entries to the stream, and refresh its expiration (in seconds) if given. If
+    /// `create` is set, the stream is created if it doesn't exist, otherwise nothing is added
+    /// and `false` is returned (e.g. the stream was cancelled).
+    async fn add(
+        &self,
+        key: &str,
+        entries: &[HashMap<String, String>],
+        create: bool,
+        expire: Option<i64>,
+    ) -> FredResult<bool>;
+
+    /// Delete the stream
+    async fn delete(&self, key: &str) -> FredResult<()>;
+
+    /// Get the entries of the stream after the given ID (`0-0` for all entries)
+    async fn read(&self, key: &str, after_id: &str) -> FredResult<Vec<StreamEntry>>;
+
+    /// Wait for the next entry of the stream after the given ID. Returns `None` if the stream
+    /// doesn't exist, or if there's no new entry before the timeout.
+    async fn read_next(
+        &self,
+        key: &str,
+        after_id: &str,
+        timeout: Duration,
+    ) -> FredResult<Option<StreamEntry>>;
+
+    /// Get the first entry of the stream
+    async fn first(&self, key: &str) -> FredResult<Option<StreamEntry>>;
+
+    /// Get the last entry of the stream
+    async fn last(&self, key: &str) -> FredResult<Option<StreamEntry>>;
+
+    /// List the keys of the streams starting with the prefix
+    async fn keys(&self, prefix: &str) -> FredResult<Vec<String>>;
+
+    /// Publish the payload to the subscribers of the channel
+    async fn publish(&self, channel: &str, payload: String) -> FredResult<()>;
+
+    /// Subscribe to the channel. The subscription ends when the receiver is dropped.
+    async fn subscribe(&self, channel: &str) -> FredResult<mpsc::Receiver<String>>;
+
+    /// Take the lease for the owner, with the expiration in seconds, if it isn't held. Returns
+    /// whether the lease was taken.
+    async fn acquire_lease(&self, key: &str, owner: &str, ttl: i64) -> FredResult<bool>;
+
+    /// Refresh the expiration of the lease (in seconds), if it's held by the owner. Returns
+    /// whether the owner still holds the lease.
+    async fn renew_lease(&self, key: &str, owner: &str, ttl: i64) -> FredResult<bool>;
+
+    /// Release the lease if it's held by the owner, or whoever holds it if no owner is given
+    async fn release_lease(&self, key: &str, owner: Option<&str>) -> FredResult<()>;
+
+    /// Get the current holder of the lease
+    async fn lease_owner(&self, key: &str) -> FredResult<Option<String>>;
+}
+
+/// Client of the configured stream backend
+#[derive(Clone, OpenApiFromRequest)]
+pub struct StreamClient(Arc<dyn StreamBackend>);
+
+impl StreamClient {
+    /// Use Redis with the given client
+    pub fn redis(client: impl Deref<Target = Client> + Send + Sync + 'static) -> Self {
+        StreamClient(Arc::new(RedisStreamBackend(client)))
+    }
+
+    /// Use the shared in-memory streams
+    pub fn memory(streams: &MemoryStreams) -> Self {
+        StreamClient(Arc::new(streams.clone()))
+    }
+}
+
+impl Deref for StreamClient {
+    type Target = dyn StreamBackend;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+/// Request guard to get the stream backend, using the shared Redis pool. For short
+/// operations: use [`ExclusiveStreamClient`] to read or write a stream.
+#[async_trait]
+impl<'r> FromRequest<'r> for StreamClient {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        if let Some(streams) = req.rocket().state::<MemoryStreams>() {
+            return Outcome::Success(StreamClient::memory(streams));
+        }
+        req.guard::<RedisClient>().await.map(StreamClient::redis)
+    }
+}
+
+/// Request guard to get the stream backend with an exclusive Redis connection, for reading
+/// and writing streams.
+#[derive(OpenApiFromRequest)]
+pub struct ExclusiveStreamClient(pub StreamClient);
+
+impl ExclusiveStreamClient {
+    pub fn into_inner(self) -> StreamClient {
+        self.0
+    }
+}
+
+#[async_trait]
+impl<'r> FromRequest<'r> for ExclusiveStreamClient {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        if let Some(streams) = req.rocket().state::<MemoryStreams>() {
+            return Outcome::Success(ExclusiveStreamClient(StreamClient::memory(streams)));
+        }
+        req.guard::<ExclusiveRedisClient>()
+            .await
+            .map(|client| ExclusiveStreamClient(StreamClient::redis(client)))
+    }
+}
+
+/// Fairing that sets the instance ID, and sets up the in-memory streams if configured
+pub fn setup_stream_backend() -> AdHoc {
+    AdHoc::on_ignite("Stream backend", |rocket| async {
+        let app_config = get_app_config(&rocket);
+        if let Some(id) = &app_config.instance_id {
+            set_instance_id(id);
+        }
+        rocket::info!("Streams: instance ID {}", instance_id());
+        match app_config.stream_backend.unwrap_or_default() {
+            StreamBackendKind::Redis => rocket,
+            StreamBackendKind::Local => {
+                rocket::info!("Streams: using local in-memory streams (single instance only)");
+                rocket.manage(MemoryStreams::default())
+            }
+        }
+    })
+}
+
+/// Redis backend, using Redis streams and pub/sub
+struct RedisStreamBackend<C>(C);
+
+#[async_trait]
+impl<C: Deref<Target = Client> + Send + Sync> StreamBackend for RedisStreamBackend<C> {
+    async fn add(
+        &self,
+        key: &str,
+        entries: &[HashMap<String, String>],
+        create: bool,
+        expire: Option<i64>,
+    ) -> FredResult<bool> {
+        let pipeline = self.0.pipeline();
+        for entry in entries {
+            let _: () = pipeline
+                .xadd(
+                    key,
+                    !create,
+                    ("MAXLEN", "~", MAX_STREAM_LENGTH as i64),
+                    "*",
+                    entry.clone(),
+                )
+                .await?;
+        }
+        if let Some(expire) = expire {
+            let _: () = pipeline.expire(key, expire, None).await?;
+        }
+        let res: Vec<fred::prelude::Value> = pipeline.all().await?;
+
+        // `nil` responses indicate that the stream doesn't exist
+        Ok(!res.iter().any(|r| r.is_null()))
+    }
+
+    async fn delete(&self, key: &str) -> FredResult<()> {
+        self.0.del(key).await
+    }
+
+    async fn read(&self, key: &str, after_id: &str) -> FredResult<Vec<StreamEntry>> {
+        let entries = self
+            .0
+            .xread::<Option<Vec<(String, Vec<StreamEntry>)>>, _, _>(None, None, key, after_id)
+            .await?
+            .and_then(|mut streams| streams.pop()) // only 1 stream, since we're sending 1 key
+            .map(|(_, entries)| entries)
+            .unwrap_or_default();
+
+        Ok(entries)
+    }
+
+    async fn read_next(
+        &self,
+        key: &str,
+        after_id: &str,
+        timeout: Duration,
+    ) -> FredResult<Option<StreamEntry>> {
+        let block = timeout.as_millis() as u64;
+        let entry = self
+            .0
+            .xread::<Option<Vec<(String, Vec<StreamEntry>)>>, _, _>(
+                Some(1),
+                Some(block),
+                key,
+                after_id,
+            )
+            .await?
+            .and_then(|mut streams| streams.pop())
+            .and_then(|(_, mut entries)| entries.pop());
+
+        Ok(entry)
+    }
+
+    async fn first(&self, key: &str) -> FredResult<Option<StreamEntry>> {
+        let mut entries: Vec<StreamEntry> = self.0.xrange(key, "-", "+", Some(1)).await?;
+        Ok(entries.pop())
+    }
+
+    async fn last(&self, key: &str) -> FredResult<Option<StreamEntry>> {
+        let mut entries: Vec<StreamEntry> = self.0.xrevrange(key, "+", "-", Some(1)).await?;
+        Ok(entries.pop())
+    }
+
+    async fn keys(&self, prefix: &str) -> FredResult<Vec<String>> {
+        let pattern = format!("{}*", prefix);
+        let mut keys = Vec::new();
+        let mut cursor = "0".to_owned();
+        loop {
+            let (next_cursor, page): (String, Vec<String>) = self
+                .0
+                .scan_page(cursor, &pattern, Some(100), Some(ScanType::Stream))
+                .await?;
+            keys.extend(page);
+            if next_cursor == "0" {
+                break;
+            }
+            cursor = next_cursor;
+        }
+
+        Ok(keys)
+    }
+
+    async fn publish(&self, channel: &str, payload: String) -> FredResult<()> {
+        self.0.publish::<(), _, _>(channel, payload).await
+    }
+
+    /// Uses a new Redis client, as a subscribed client can't send other commands
+    async fn subscribe(&self, channel: &str) -> FredResult<mpsc::Receiver<String>> {
+        let subscriber = self.0.clone_new();
+        subscriber.init().await?;
+        if let Err(err) = subscriber.subscribe(channel).await {
+            subscriber.quit().await.ok();
+            return Err(err);
+        }
+
+        let mut message_rx = subscriber.message_rx();
+        let (tx, rx) = mpsc::channel::<String>(CHANNEL_CAPACITY);
+        tokio::spawn(async move {
+            loop {
+                let message = tokio::select! {
+                    message = message_rx.recv() => message,
+                    _ = tx.closed() => break,
+                };
+                match message {
+                    Ok(message) => {
+                        let Some(payload) = message.value.into_string() else {
+                            continue;
+                        };
+                        if tx.send(payload).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+            subscriber.quit().await.ok();
+        });
+
+        Ok(rx)
+    }
+
+    async fn acquire_lease(&self, key: &str, owner: &str, ttl: i64) -> FredResult<bool> {
+        let acquired: Option<String> = self
+            .0
+            .set(
+                key,
+                owner,
+                Some(Expiration::EX(ttl)),
+                Some(SetOptions::NX),
+                false,
+            )
+            .await?;
+        Ok(acquired.is_some())
+    }
+
+    /// The owner is checked and the expiration refreshed atomically, in a Lua script
+    async fn renew_lease(&self, key: &str, owner: &str, ttl: i64) -> FredResult<bool> {
+        let renewed: i64 = self
+            .0
+            .eval(
+                RENEW_LEASE_SCRIPT,
+                key,
+                vec![owner.to_owned(), ttl.to_string()],
+            )
+            .await?;
+        Ok(renewed == 1)
+    }
+
+    /// The owner is checked and the lease deleted atomically, in a Lua script
+    async fn release_lease(&self, key: &str, owner: Option<&str>) -> FredResult<()> {
+        match owner {
+            Some(owner) => {
+                let _: i64 = self.0.eval(RELEASE_LEASE_SCRIPT, key, owner).await?;
+                Ok(())
+            }
+            None => self.0.del(key).await,
+        }
+    }
+
+    async fn lease_owner(&self, key: &str) -> FredResult<Option<String>> {
+        self.0.get(key).await
+    }
+}
+
+/// In-memory streams and channels, shared by all requests of the server instance. Expired
+/// streams are removed when streams are added or listed.
+#[derive(Clone, Default)]
+pub struct MemoryStreams {
+    streams: Arc<Mutex<HashMap<String, MemoryStream>>>,
+    channels: Arc<Mutex<HashMap<String, broadcast::Sender<String>>>>,
+    /// Leases, with their owner and expiration
+    leases: Arc<Mutex<HashMap<String, (String, Instant)>>>,
+}
+
+struct MemoryStream {
+    entries: Vec<StreamEntry>,
+    /// ID of the last entry, as milliseconds and sequence number
+    last_id: (u64, u64),
+    expires_at: Option<Instant>,
+    /// Sends the new entries to the readers waiting for them. Readers still get the entries
+    /// sent before the stream was deleted (e.g. the `end` entry).
+    updates: broadcast::Sender<StreamEntry>,
+}
+
+impl MemoryStream {
+    fn new() -> Self {
+        MemoryStream {
+            entries: Vec::new(),
+            last_id: (0, 0),
+            expires_at: None,
+            updates: broadcast::channel(UPDATES_CAPACITY).0,
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|at| at <= Instant::now())
+    }
+
+    /// Generate the ID of a new entry, like Redis: the current time in milliseconds, and a
+    /// sequence number for entries added in the same millisecond
+    fn next_id(&mut self) -> String {
+        let ms = chrono::Utc::now().timestamp_millis().max(0) as u64;
+        self.last_id = match self.last_id {
+            (last_ms, seq) if ms <= last_ms => (last_ms, seq + 1),
+            _ => (ms, 0),
+        };
+        format!("{}-{}", self.last_id.0, self.last_id.1)
+    }
+
+    fn entries_after(&self, after_id: &str) -> impl Iterator<Item = &StreamEntry> {
+        let after_id = parse_entry_id(after_id);
+        self.entries
+            .iter()
+            .filter(move |(id, _)| parse_entry_id(id) > after_id)
+    }
+}
+
+/// Parse the entry ID into milliseconds and sequence number (invalid IDs are parsed as `0-0`)
+fn parse_entry_id(id: &str) -> (u64, u64) {
+    let (ms, seq) = id.split_once('-').unwrap_or((id, "0"));
+    (ms.parse().unwrap_or(0), seq.parse().unwrap_or(0))
+}
+
+impl MemoryStreams {
+    /// Run the function with the leases, after removing the expired leases
+    fn with_leases<T>(&self, f: impl FnOnce(&mut HashMap<String, (String, Instant)>) -> T) -> T {
+        let mut leases = self.leases.lock().expect("should not be poisoned");
+        let now = Instant::now();
+        leases.retain(|_, (_, expires_at)| *expires_at > now);
+        f(&mut leases)
+    }
+
+    /// Run the function with the stream, if it exists and hasn't expired
+    fn with_stream<T>(&self, key: &str, f: impl FnOnce(&mut MemoryStream) -> T) -> Option<T> {
+        let mut streams = self.streams.lock().expect("should not be poisoned");
+        if streams.get(key).is_some_and(MemoryStream::is_expired) {
+            streams.remove(key);
+        }
+        streams.get_mut(key).map(f)
+    }
+}
+
+#[async_trait]
+impl StreamBackend for MemoryStreams {
+    async fn add(
+        &self,
+        key: &str,
+        entries: &[HashMap<String, String>],
+        create: bool,
+        expire: Option<i64>,
+    ) -> FredResult<bool> {
+        let mut streams = self.streams.lock().expect("should not be poisoned");
+        streams.retain(|_, stream| !stream.is_expired());
+        let stream = match streams.get_mut(key) {
+            Some(stream) => stream,
+            None if create => streams
+                .entry(key.to_owned())
+                .or_insert_with(MemoryStream::new),
+            None => return Ok(false),
+        };
+        for entry in entries {
+            let entry = (stream.next_id(), entry.clone());
+            stream.updates.send(entry.clone()).ok(); // fails if there are no readers
+            stream.entries.push(entry);
+        }
+        if stream.entries.len() > MAX_STREAM_LENGTH {
+            let overflow = stream.entries.len() - MAX_STREAM_LENGTH;
+            stream.entries.drain(..overflow);
+        }
+        if let Some(expire) = expire {
+            stream.expires_at = Some(Instant::now() + Duration::from_secs(expire.max(0) as u64));
+        }
+
+        Ok(true)
+    }
+
+    async fn delete(&self, key: &str) -> FredResult<()> {
+        self.streams
+            .lock()
+            .expect("should not be poisoned")
+            .remove(key);
+        Ok(())
+    }
+
+    async fn read(&self, key: &str, after_id: &str) -> FredResult<Vec<StreamEntry>> {
+        let entries = self.with_stream(key, |stream| {
+            stream.entries_after(after_id).cloned().collect::<Vec<_>>()
+        });
+        Ok(entries.unwrap_or_default())
+    }
+
+    async fn read_next(
+        &self,
+        key: &str,
+        after_id: &str,
+        timeout: Duration,
+    ) -> FredResult<Option<StreamEntry>> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            // Subscribe to the updates before checking the entries, so that no entry is missed
+            let next = self.with_stream(key, |stream| {
+                let updates = stream.updates.subscribe();
+                match stream.entries_after(after_id).next() {
+                    Some(entry) => Ok(entry.clone()),
+                    None => Err(updates),
+                }
+            });
+            let mut updates = match next {
+                Some(Ok(entry)) => return Ok(Some(entry)),
+                Some(Err(updates)) => updates,
+                None => return Ok(None),
+            };
+            match tokio::time::timeout_at(deadline, updates.recv()).await {
+                Ok(Ok(entry)) => return Ok(Some(entry)),
+                Ok(Err(broadcast::error::RecvError::Lagged(_))) => continue,
+                Ok(Err(broadcast::error::RecvError::Closed)) | Err(_) => return Ok(None),
+            }
+        }
+    }
+
+    async fn first(&self, key: &str) -> FredResult<Option<StreamEntry>> {
+        Ok(self
+            .with_stream(key, |stream| stream.entries.first().cloned())
+            .flatten())
+    }
+
+    async fn last(&self, key: &str) -> FredResult<Option<StreamEntry>> {
+        Ok(self
+            .with_stream(key, |stream| stream.entries.last().cloned())
+            .flatten())
+    }
+
+    async fn keys(&self, prefix: &str) -> FredResult<Vec<String>> {
+        let mut streams = self.streams.lock().expect("should not be poisoned");
+        streams.retain(|_, stream| !stream.is_expired());
+        Ok(streams
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+
+    async fn publish(&self, channel: &str, payload: String) -> FredResult<()> {
+        let mut channels = self.channels.lock().expect("should not be poisoned");
+        if let Some(sender) = channels.get(channel) {
+            if sender.send(payload).is_err() {
+                channels.remove(channel); // no more subscribers
+            }
+        }
+        Ok(())
+    }
+
+    async fn subscribe(&self, channel: &str) -> FredResult<mpsc::Receiver<String>> {
+        let mut message_rx = self
+            .channels
+            .lock()
+            .expect("should not be poisoned")
+            .entry(channel.to_owned())
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe();
+
+        let (tx, rx) = mpsc::channel::<String>(CHANNEL_CAPACITY);
+        tokio::spawn(async move {
+            loop {
+                let message = tokio::select! {
+                    message = message_rx.recv() => message,
+                    _ = tx.closed() => break,
+                };
+                match message {
+                    Ok(payload) => {
+                        if tx.send(payload).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        Ok(rx)
+    }
+
+    async fn acquire_lease(&self, key: &str, owner: &str, ttl: i64) -> FredResult<bool> {
+        let expires_at = Instant::now() + Duration::from_secs(ttl.max(0) as u64);
+        Ok(self.with_leases(|leases| match leases.contains_key(key) {
+            true => false,
+            false => {
+                leases.insert(key.to_owned(), (owner.to_owned(), expires_at));
+                true
+            }
+        }))
+    }
+
+    async fn renew_lease(&self, key: &str, owner: &str, ttl: i64) -> FredResult<bool> {
+        let expires_at = Instant::now() + Duration::from_secs(ttl.max(0) as u64);
+        Ok(self.with_leases(|leases| match leases.get_mut(key) {
+            Some((holder, lease_expires_at)) if holder == owner => {
+                *lease_expires_at = expires_at;
+                true
+            }
+            _ => false,
+        }))
+    }
+
+    async fn release_lease(&self, key: &str, owner: Option<&str>) -> FredResult<()> {
+        self.with_leases(|leases| {
+            let held = match owner {
+                Some(owner) => leases.get(key).is_some_and(|(holder, _)| holder == owner),
+                None => true,
+            };
+            if held {
+                leases.remove(key);
+            }
+        });
+        Ok(())
+    }
+
+    async fn lease_owner(&self, key: &str) -> FredResult<Option<String>> {
+        Ok(self.with_leases(|leases| leases.get(key).map(|(holder, _)| holder.clone())))
+    }
+}
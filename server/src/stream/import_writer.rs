@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use fred::prelude::FredResult;
+use schemars::JsonSchema;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::stream::{get_import_stream_key, StreamClient};
+
+/// Expiration in seconds set on the stream, so that clients can still get the results
+/// for a while after the import has ended
+const STREAM_EXPIRE: i64 = 3600;
+
+/// Progress of a bulk import of sessions
+#[derive(Debug, Default, Serialize, JsonSchema)]
+pub struct ImportProgress {
+    /// Total number of conversations to import
+    pub total: usize,
+    /// Number of conversations imported as new sessions
+    pub imported: usize,
+    /// Number of conversations that couldn't be imported
+    pub failed: usize,
+}
+
+/// Events of the import progress stream
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+#[schemars(rename = "ImportStreamEvent")]
+pub enum ImportStreamEvent {
+    /// JSON-encoded progress of the import (`ImportProgress`)
+    Progress(String),
+    /// A conversation couldn't be imported
+    Error(String),
+    /// The import has ended, with the JSON-encoded final progress (`ImportProgress`)
+    End(String),
+}
+impl From<ImportStreamEvent> for HashMap<String, String> {
+    /// Converts an `ImportStreamEvent` into a hash map, suitable for the Redis client.
+    fn from(event: ImportStreamEvent) -> Self {
+        let value = serde_json::to_value(event).unwrap_or_default();
+        serde_json::from_value(value).unwrap_or_default()
+    }
+}
+
+/// Utility for reporting the progress of a bulk import to a stream.
+pub struct ImportStreamWriter {
+    streams: StreamClient,
+    key: String,
+}
+
+impl ImportStreamWriter {
+    pub fn new(streams: StreamClient, user_id: &Uuid, import_id: &Uuid) -> Self {
+        ImportStreamWriter {
+            streams,
+            key: get_import_stream_key(user_id, import_id),
+        }
+    }
+
+    /// Create the stream with the initial progress.
+    pub async fn start(&self, progress: &ImportProgress) -> FredResult<()> {
+        let entry: HashMap<String, String> =
+            ImportStreamEvent::Progress(serde_json::to_string(progress).unwrap_or_default()).into();
+        self.add(entry, true).await
+    }
+
+    /// Add a `progress` event.
+    pub async fn progress(&self, progress: &ImportProgress) -> FredResult<()> {
+        let entry: HashMap<String, String> =
+            ImportStreamEvent::Progress(serde_json::to_string(progress).unwrap_or_default()).into();
+        self.add(entry, false).await
+    }
+
+    /// Add an `error` event for a conversation that couldn't be imported.
+    pub async fn error(&self, message: &str) -> FredResult<()> {
+        let entry: HashMap<String, String> = ImportStreamEvent::Error(message.into()).into();
+        self.add(entry, false).await
+    }
+
+    /// Add an `end` event with the final progress. The stream is kept until it expires.
+    pub async fn end(&self, progress: &ImportProgress) -> FredResult<()> {
+        let entry: HashMap<String, String> =
+            ImportStreamEvent::End(serde_json::to_string(progress).unwrap_or_default()).into();
+        self.add(entry, false).await
+    }
+
+    async fn add(&self, entry: HashMap<String, String>, create: bool) -> FredResult<()> {
+        let expire = create.then_some(STREAM_EXPIRE);
+        self.streams
+            .add(&self.key, &[entry], create, expire)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+//! Ownership of the chat streams, for running several server instances behind a load balancer.
+//! The response being generated holds the lease of the chat stream, and refreshes it while
+//! streaming, so that no other response is started for the session (on any instance). Any
+//! instance can serve the stream to clients. If an instance stops while streaming, its lease
+//! expires, and the stale stream is ended by the next instance starting a stream for the session.
+
+use std::{collections::HashMap, sync::OnceLock};
+
+use fred::prelude::FredResult;
+use uuid::Uuid;
+
+use crate::{
+    provider::LlmErrorInfo,
+    stream::{get_chat_stream_key, RedisStreamChunk, StreamClient},
+};
+
+/// Expiration of the chat stream leases in seconds, refreshed by the writer's pings
+pub const STREAM_LEASE_TTL: i64 = 20;
+
+/// Error sent to clients of a stale stream
+const STALE_STREAM_MESSAGE: &str = "The server generating the response stopped";
+
+/// ID of this server instance, set on startup
+static INSTANCE_ID: OnceLock<String> = OnceLock::new();
+
+/// Set the ID of this server instance. Only the first call has an effect.
+pub fn set_instance_id(id: &str) {
+    let _ = INSTANCE_ID.set(id.to_owned());
+}
+
+/// Get the ID of this server instance (random if not set on startup)
+pub fn instance_id() -> &'static str {
+    INSTANCE_ID.get_or_init(|| Uuid::new_v4().to_string())
+}
+
+/// Get the key of the lease of the given stream
+pub fn get_stream_lease_key(stream_key: &str) -> String {
+    format!("{}:owner", stream_key)
+}
+
+/// The lease of a chat stream, held by the response being generated. The owner is unique to
+/// the response (the instance ID and a random ID), so that two requests for the same session
+/// on one instance can't both hold it.
+#[derive(Debug, Clone)]
+pub struct ChatStreamLease {
+    pub key: String,
+    pub owner: String,
+}
+
+/// Whether a server instance is currently streaming a response for the session: the stream
+/// exists and its lease hasn't expired.
+pub async fn is_chat_stream_active(
+    streams: &StreamClient,
+    user_id: &Uuid,
+    session_id: &Uuid,
+) -> FredResult<bool> {
+    let key = get_chat_stream_key(user_id, session_id);
+    if streams.first(&key).await?.is_none() {
+        return Ok(false);
+    }
+    Ok(streams
+        .lease_owner(&get_stream_lease_key(&key))
+        .await?
+        .is_some())
+}
+
+/// Take the lease of the chat stream for a new response. Returns `None` if a response is
+/// already being streamed for the session. Once the lease is taken, a stale stream left by an
+/// instance that stopped is ended with an error, and deleted.
+pub async fn acquire_chat_stream(
+    streams: &StreamClient,
+    user_id: &Uuid,
+    session_id: &Uuid,
+) -> FredResult<Option<ChatStreamLease>> {
+    let key = get_chat_stream_key(user_id, session_id);
+    let lease = ChatStreamLease {
+        key: get_stream_lease_key(&key),
+        owner: format!("{}:{}", instance_id(), Uuid::new_v4()),
+    };
+    if !streams
+        .acquire_lease(&lease.key, &lease.owner, STREAM_LEASE_TTL)
+        .await?
+    {
+        return Ok(None);
+    }
+
+    if streams.first(&key).await?.is_some() {
+        rocket::warn!("Recovering stale chat stream {}", key);
+        let entries: [HashMap<String, String>; 2] = [
+            RedisStreamChunk::Error(LlmErrorInfo::new(None, STALE_STREAM_MESSAGE.into()).to_json())
+                .into(),
+            RedisStreamChunk::End.into(),
+        ];
+        streams.add(&key, &entries, false, None).await?;
+        streams.delete(&key).await?;
+    }
+
+    Ok(Some(lease))
+}
+
+/// Release the lease of the chat stream (e.g. if the stream couldn't be started).
+pub async fn release_chat_stream(streams: &StreamClient, lease: &ChatStreamLease) {
+    if let Err(err) = streams.release_lease(&lease.key, Some(&lease.owner)).await {
+        rocket::warn!("Failed to release chat stream lease: {}", err);
+    }
+}
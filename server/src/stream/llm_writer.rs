@@ -3,16 +3,20 @@ use std::{
     time::{Duration, Instant},
 };
 
-use fred::prelude::{FredResult, KeysInterface, StreamsInterface};
+use fred::prelude::FredResult;
 use rocket::futures::StreamExt;
+use schemars::JsonSchema;
 use serde::Serialize;
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
 use crate::{
-    db::models::ChatRsToolCall,
-    provider::{LlmPendingToolCall, LlmStream, LlmStreamChunk, LlmStreamError, LlmUsage},
-    redis::ExclusiveRedisClient,
-    stream::get_chat_stream_key,
+    db::models::{ChatRsMessage, ChatRsToolCall},
+    provider::{
+        LlmErrorInfo, LlmFinishReason, LlmPendingToolCall, LlmStream, LlmStreamChunk,
+        LlmStreamError, LlmUsage,
+    },
+    stream::{get_chat_stream_key, ChatStreamLease, StreamClient, STREAM_LEASE_TTL},
 };
 
 /// Interval at which chunks are flushed to the Redis stream.
@@ -25,14 +29,20 @@ const STREAM_EXPIRE: i64 = 30;
 const LLM_TIMEOUT: Duration = Duration::from_secs(60);
 /// Interval for sending ping messages to the Redis stream.
 const PING_INTERVAL: Duration = Duration::from_secs(5);
+/// Interval between attempts to write to Redis while it is unavailable.
+const REDIS_RETRY_INTERVAL: Duration = Duration::from_secs(2);
+/// Max number of entries buffered in memory while Redis is unavailable (oldest entries are dropped).
+const MAX_BUFFERED_ENTRIES: usize = 500;
 
-/// Utility for processing an incoming LLM response stream and writing to a Redis stream.
-#[derive(Debug)]
+/// Utility for processing an incoming LLM response stream and writing to a stream (Redis by
+/// default).
 pub struct LlmStreamWriter {
-    /// Redis client with an exclusive connection.
-    redis: ExclusiveRedisClient,
-    /// The key of the Redis stream.
+    /// Stream client (with an exclusive connection if using Redis).
+    streams: StreamClient,
+    /// The key of the stream.
     key: String,
+    /// The stream's lease, held while streaming the response.
+    lease: Option<ChatStreamLease>,
     /// The current chunk of data being processed.
     current_chunk: ChunkState,
     /// Accumulated text response from the assistant.
@@ -43,6 +53,16 @@ pub struct LlmStreamWriter {
     errors: Option<Vec<LlmStreamError>>,
     /// Accumulated usage information from the LLM provider.
     usage: Option<LlmUsage>,
+    /// The reason the LLM provider stopped generating the response.
+    finish_reason: Option<LlmFinishReason>,
+    /// Set if Redis became unavailable during the stream (degraded mode).
+    degraded: Option<DegradedState>,
+    /// Background task that pings the Redis stream.
+    ping_task: Option<tokio::task::JoinHandle<()>>,
+    /// Signal to interrupt the stream (e.g. when the server shuts down).
+    interrupt: CancellationToken,
+    /// Set if the stream was interrupted.
+    interrupted: bool,
 }
 
 /// Internal state
@@ -51,20 +71,66 @@ struct ChunkState {
     text: Option<String>,
     tool_calls: Option<Vec<ChatRsToolCall>>,
     pending_tool_calls: Option<Vec<LlmPendingToolCall>>,
+    finish_reason: Option<LlmFinishReason>,
     error: Option<String>,
 }
 
-/// Chunk of the LLM response stored in the Redis stream.
-#[derive(Debug, Serialize)]
+/// State of the writer while Redis is unavailable. The entries are buffered in memory
+/// and written once Redis is available again, while the response is still processed
+/// and accumulated as usual.
+#[derive(Debug)]
+struct DegradedState {
+    entries: Vec<HashMap<String, String>>,
+    last_attempt: Instant,
+    /// Whether the stream may have been lost during the outage, because its existence couldn't
+    /// be checked when entering degraded mode. Only then is it re-created when flushing, so that
+    /// a stream cancelled during the outage isn't re-created.
+    recreate_stream: bool,
+}
+
+impl DegradedState {
+    fn buffer(&mut self, entries: Vec<HashMap<String, String>>) {
+        self.entries.extend(entries);
+        if self.entries.len() > MAX_BUFFERED_ENTRIES {
+            let overflow = self.entries.len() - MAX_BUFFERED_ENTRIES;
+            self.entries.drain(..overflow);
+        }
+    }
+}
+
+/// Chunk of the LLM response stored in the Redis stream. The `type` is sent as the SSE event
+/// name, and the `data` as the SSE event data.
+#[derive(Debug, Serialize, JsonSchema)]
 #[serde(tag = "type", content = "data", rename_all = "snake_case")]
-pub(super) enum RedisStreamChunk {
+#[schemars(rename = "ChatStreamEvent")]
+pub enum RedisStreamChunk {
+    /// The stream has started
     Start,
+    /// Keep-alive ping
     Ping,
+    /// Text chunk of the assistant response
     Text(String),
+    /// JSON-encoded tool call
     ToolCall(String),
+    /// JSON-encoded tool call that is still being generated, with the new argument text
+    /// since the previous event for the same tool call
     PendingToolCall(String),
+    /// JSON-encoded tool message, saved after the server executed a tool call in auto mode.
+    /// The following events belong to the next assistant message.
+    ToolResult(String),
+    /// The reason the provider stopped generating the response (e.g. `length` if truncated)
+    FinishReason(LlmFinishReason),
+    /// Warning message (e.g. the model is deprecated)
+    Warning(String),
+    /// The stream is temporarily unavailable (e.g. Redis is down). Only sent by the reader:
+    /// the response is still saved to the session once completed.
+    Unavailable(String),
+    /// JSON-encoded error (`ChatStreamError`), with a code and a user-friendly message if it's
+    /// a known provider error (e.g. invalid API key)
     Error(String),
+    /// The stream was cancelled
     Cancel,
+    /// The stream has ended
     End,
 }
 impl From<RedisStreamChunk> for HashMap<String, String> {
@@ -76,35 +142,100 @@ impl From<RedisStreamChunk> for HashMap<String, String> {
 }
 
 impl LlmStreamWriter {
-    pub fn new(redis: ExclusiveRedisClient, user_id: &Uuid, session_id: &Uuid) -> Self {
+    pub fn new(streams: StreamClient, user_id: &Uuid, session_id: &Uuid) -> Self {
+        let key = get_chat_stream_key(user_id, session_id);
         LlmStreamWriter {
-            redis,
-            key: get_chat_stream_key(user_id, session_id),
+            streams,
+            key,
+            lease: None,
             current_chunk: ChunkState::default(),
             complete_text: None,
             tool_calls: None,
             errors: None,
             usage: None,
+            finish_reason: None,
+            degraded: None,
+            ping_task: None,
+            interrupt: CancellationToken::new(),
+            interrupted: false,
         }
     }
 
+    /// Stop processing the response once the signal is cancelled. The accumulated response
+    /// is still returned, so that it can be saved as a partial response.
+    pub fn with_interrupt(mut self, interrupt: CancellationToken) -> Self {
+        self.interrupt = interrupt;
+        self
+    }
+
+    /// Renew the lease of the stream with the pings, and release it when the stream ends
+    pub fn with_lease(mut self, lease: ChatStreamLease) -> Self {
+        self.lease = Some(lease);
+        self
+    }
+
+    /// Whether the stream was interrupted by the interrupt signal
+    pub fn interrupted(&self) -> bool {
+        self.interrupted
+    }
+
     /// Create the Redis stream and write a `start` entry.
     pub async fn start(&self) -> FredResult<()> {
         let entry: HashMap<String, String> = RedisStreamChunk::Start.into();
-        let pipeline = self.redis.pipeline();
-        let _: () = pipeline.xadd(&self.key, false, None, "*", entry).await?;
-        let _: () = pipeline.expire(&self.key, STREAM_EXPIRE, None).await?;
-        pipeline.all().await
+        self.create_stream(entry).await
+    }
+
+    /// Create the Redis stream and write a `start` entry with the provider and model that
+    /// generate the response, which are listed in the user's ongoing streams.
+    pub async fn start_with_model(&self, provider_id: i32, model: &str) -> FredResult<()> {
+        let mut entry: HashMap<String, String> = RedisStreamChunk::Start.into();
+        entry.insert("provider_id".into(), provider_id.to_string());
+        entry.insert("model".into(), model.to_owned());
+        self.create_stream(entry).await
+    }
+
+    async fn create_stream(&self, entry: HashMap<String, String>) -> FredResult<()> {
+        self.streams
+            .add(&self.key, &[entry], true, Some(STREAM_EXPIRE))
+            .await?;
+        Ok(())
+    }
+
+    /// Add a `warning` entry to notify clients of an issue with the request.
+    pub async fn warning(&self, message: &str) -> FredResult<()> {
+        let entry: HashMap<String, String> = RedisStreamChunk::Warning(message.into()).into();
+        self.streams.add(&self.key, &[entry], false, None).await?;
+        Ok(())
     }
 
     /// Add an `end` event to notify clients that the stream has ended, and then
-    /// delete the stream from Redis.
+    /// delete the stream and release its lease.
     pub async fn end(&self) -> FredResult<()> {
         let entry: HashMap<String, String> = RedisStreamChunk::End.into();
-        let pipeline = self.redis.pipeline();
-        let _: () = pipeline.xadd(&self.key, true, None, "*", entry).await?;
-        let _: () = pipeline.del(&self.key).await?;
-        pipeline.all().await
+        self.streams.add(&self.key, &[entry], false, None).await?;
+        self.streams.delete(&self.key).await?;
+        if let Some(lease) = &self.lease {
+            self.streams
+                .release_lease(&lease.key, Some(&lease.owner))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Add a `tool_result` entry with the tool message of an automatically executed tool call.
+    /// Returns a `LlmStreamError::StreamCancelled` error if the stream has been deleted or cancelled.
+    pub async fn tool_result(&self, message: &ChatRsMessage) -> Result<(), LlmStreamError> {
+        let data = serde_json::to_string(message).unwrap_or_default();
+        let entry: HashMap<String, String> = RedisStreamChunk::ToolResult(data).into();
+        self.add_to_redis_stream(&[entry], true).await
+    }
+
+    /// Keep pinging the Redis stream in between provider responses (e.g. while executing tools),
+    /// until the next call to `process`.
+    pub fn keep_alive(&mut self) {
+        if self.ping_task.is_none() {
+            self.ping_task = Some(self.start_ping_task());
+        }
     }
 
     /// Process the incoming stream from the LLM provider, intermittently flushing
@@ -116,15 +247,29 @@ impl LlmStreamWriter {
         Option<String>,
         Option<Vec<ChatRsToolCall>>,
         Option<LlmUsage>,
+        Option<LlmFinishReason>,
         Option<Vec<String>>,
         bool,
     ) {
-        let ping_handle = self.start_ping_task();
+        if let Some(ping_task) = self.ping_task.replace(self.start_ping_task()) {
+            ping_task.abort();
+        }
 
         let mut last_flush_time = Instant::now();
         let mut cancelled = false;
+        let interrupt = self.interrupt.clone();
         loop {
-            match tokio::time::timeout(LLM_TIMEOUT, stream.next()).await {
+            let next = tokio::select! {
+                next = tokio::time::timeout(LLM_TIMEOUT, stream.next()) => next,
+                _ = interrupt.cancelled() => {
+                    self.interrupted = true;
+                    self.process_error(LlmStreamError::StreamInterrupted);
+                    self.flush_chunk().await.ok();
+                    self.flush_buffered_entries(true).await.ok();
+                    break;
+                }
+            };
+            match next {
                 Ok(Some(Ok(chunk))) => match chunk {
                     LlmStreamChunk::Text(text) => self.process_text(&text),
                     LlmStreamChunk::ToolCalls(tool_calls) => self.process_tool_calls(tool_calls),
@@ -132,17 +277,20 @@ impl LlmStreamWriter {
                         self.process_pending_tool_call(pending_tool_call)
                     }
                     LlmStreamChunk::Usage(usage) => self.process_usage(usage),
+                    LlmStreamChunk::FinishReason(reason) => self.process_finish_reason(reason),
                 },
                 Ok(Some(Err(err))) => self.process_error(err),
                 Ok(None) => {
                     // stream ended
                     self.flush_chunk().await.ok();
+                    self.flush_buffered_entries(true).await.ok();
                     break;
                 }
                 Err(_) => {
                     // timed out waiting for provider response
                     self.process_error(LlmStreamError::StreamTimeout);
                     self.flush_chunk().await.ok();
+                    self.flush_buffered_entries(true).await.ok();
                     break;
                 }
             }
@@ -159,17 +307,27 @@ impl LlmStreamWriter {
                 last_flush_time = Instant::now();
             }
         }
-        ping_handle.abort();
+        if let Some(ping_task) = self.ping_task.take() {
+            ping_task.abort();
+        }
 
         let complete_text = self.complete_text.take();
         let tool_calls = self.tool_calls.take();
         let usage = self.usage.take();
+        let finish_reason = self.finish_reason.take();
         let errors = self.errors.take().map(|e| {
             e.into_iter()
                 .map(|e| e.to_string())
                 .collect::<Vec<String>>()
         });
-        (complete_text, tool_calls, usage, errors, cancelled)
+        (
+            complete_text,
+            tool_calls,
+            usage,
+            finish_reason,
+            errors,
+            cancelled,
+        )
     }
 
     fn process_text(&mut self, text: &str) {
@@ -190,13 +348,26 @@ impl LlmStreamWriter {
         self.tool_calls.get_or_insert_default().extend(tool_calls);
     }
 
+    /// Merge the pending tool call into the current chunk, accumulating the argument deltas
+    /// of each tool call since the last flush.
     fn process_pending_tool_call(&mut self, tool_call: LlmPendingToolCall) {
         let current_chunk = self
             .current_chunk
             .pending_tool_calls
             .get_or_insert_default();
-        if !current_chunk.iter().any(|tc| tc.index == tool_call.index) {
-            current_chunk.push(tool_call);
+        match current_chunk
+            .iter_mut()
+            .find(|tc| tc.index == tool_call.index)
+        {
+            Some(existing) => {
+                if let Some(delta) = tool_call.arguments_delta {
+                    existing
+                        .arguments_delta
+                        .get_or_insert_default()
+                        .push_str(&delta);
+                }
+            }
+            None => current_chunk.push(tool_call),
         }
     }
 
@@ -213,8 +384,13 @@ impl LlmStreamWriter {
         }
     }
 
+    fn process_finish_reason(&mut self, reason: LlmFinishReason) {
+        self.current_chunk.finish_reason = Some(reason);
+        self.finish_reason = Some(reason);
+    }
+
     fn process_error(&mut self, err: LlmStreamError) {
-        self.current_chunk.error = Some(err.to_string());
+        self.current_chunk.error = Some(LlmErrorInfo::from(&err).to_json());
         self.errors.get_or_insert_default().push(err);
     }
 
@@ -227,7 +403,8 @@ impl LlmStreamWriter {
     }
 
     /// Flushes the current chunk to the Redis stream. Returns a `LlmStreamError::StreamCancelled` error
-    /// if the stream has been deleted or cancelled.
+    /// if the stream has been deleted or cancelled. If Redis is unavailable, the chunk is buffered
+    /// in memory and the writer enters degraded mode.
     async fn flush_chunk(&mut self) -> Result<(), LlmStreamError> {
         let chunk_state = std::mem::take(&mut self.current_chunk);
 
@@ -245,6 +422,9 @@ impl LlmStreamWriter {
                 RedisStreamChunk::PendingToolCall(serde_json::to_string(&tc).unwrap_or_default())
             }));
         }
+        if let Some(reason) = chunk_state.finish_reason {
+            chunks.push(RedisStreamChunk::FinishReason(reason));
+        }
         if let Some(error) = chunk_state.error {
             chunks.push(RedisStreamChunk::Error(error));
         }
@@ -252,48 +432,111 @@ impl LlmStreamWriter {
             return Ok(());
         }
 
-        let entries = chunks.into_iter().map(|chunk| chunk.into()).collect();
-        self.add_to_redis_stream(entries).await
+        let entries: Vec<HashMap<String, String>> =
+            chunks.into_iter().map(|chunk| chunk.into()).collect();
+        if let Some(degraded) = self.degraded.as_mut() {
+            degraded.buffer(entries);
+            return self.flush_buffered_entries(false).await;
+        }
+        match self.add_to_redis_stream(&entries, true).await {
+            Err(LlmStreamError::Redis(err)) => {
+                rocket::warn!("Redis unavailable, buffering stream {}: {}", self.key, err);
+                let recreate_stream = self.streams.last(&self.key).await.is_err();
+                self.degraded = Some(DegradedState {
+                    entries,
+                    last_attempt: Instant::now(),
+                    recreate_stream,
+                });
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Try writing the entries buffered in degraded mode to the Redis stream (at most every
+    /// `REDIS_RETRY_INTERVAL`, unless forced). The stream is re-created only if it may have been
+    /// lost during the outage. Exits degraded mode if successful, and returns a
+    /// `LlmStreamError::StreamCancelled` error if the stream was cancelled during the outage.
+    async fn flush_buffered_entries(&mut self, force: bool) -> Result<(), LlmStreamError> {
+        let Some(mut degraded) = self.degraded.take() else {
+            return Ok(());
+        };
+        if !force && degraded.last_attempt.elapsed() < REDIS_RETRY_INTERVAL {
+            self.degraded = Some(degraded);
+            return Ok(());
+        }
+        let nomkstream = !degraded.recreate_stream;
+        match self
+            .add_to_redis_stream(&degraded.entries, nomkstream)
+            .await
+        {
+            Ok(()) => {
+                rocket::info!("Redis available again, resumed stream {}", self.key);
+                if self
+                    .ping_task
+                    .as_ref()
+                    .is_some_and(|task| task.is_finished())
+                {
+                    self.ping_task = Some(self.start_ping_task());
+                }
+                Ok(())
+            }
+            Err(LlmStreamError::StreamCancelled) => {
+                rocket::info!("Stream {} was cancelled during the Redis outage", self.key);
+                Err(LlmStreamError::StreamCancelled)
+            }
+            Err(err) => {
+                rocket::warn!("Still failing to write stream {}: {}", self.key, err);
+                degraded.last_attempt = Instant::now();
+                self.degraded = Some(degraded);
+                Ok(())
+            }
+        }
     }
 
-    /// Adds new entries to the Redis stream. Returns a `LlmStreamError::StreamCancelled` error if the
-    /// stream has been deleted or cancelled.
+    /// Adds new entries to the stream. Returns a `LlmStreamError::StreamCancelled` error if the
+    /// stream has been deleted or cancelled. If `nomkstream` is false, the stream is created if it
+    /// doesn't exist.
     async fn add_to_redis_stream(
         &self,
-        entries: Vec<HashMap<String, String>>,
+        entries: &[HashMap<String, String>],
+        nomkstream: bool,
     ) -> Result<(), LlmStreamError> {
-        let pipeline = self.redis.pipeline();
-        for entry in entries {
-            let _: () = pipeline
-                .xadd(&self.key, true, ("MAXLEN", "~", 500), "*", entry)
-                .await?;
-        }
-        let res: Vec<fred::prelude::Value> = pipeline.all().await?;
-
-        // Check for `nil` responses indicating the stream has been deleted/cancelled
-        if res.iter().any(|r| r.is_null()) {
-            Err(LlmStreamError::StreamCancelled)
-        } else {
+        let expire = (!nomkstream).then_some(STREAM_EXPIRE);
+        if self
+            .streams
+            .add(&self.key, entries, !nomkstream, expire)
+            .await?
+        {
             Ok(())
+        } else {
+            Err(LlmStreamError::StreamCancelled)
         }
     }
 
-    /// Start task that pings the Redis stream every `PING_INTERVAL` seconds and extends the expiration time
+    /// Start task that pings the stream every `PING_INTERVAL` seconds and extends the expiration time.
+    /// Failed pings (e.g. Redis is unavailable) are ignored, and the task stops once the stream is deleted.
+    /// The lease of the stream is renewed along with the pings.
     fn start_ping_task(&self) -> tokio::task::JoinHandle<()> {
-        let redis = self.redis.clone();
+        let streams = self.streams.clone();
         let key = self.key.to_owned();
+        let lease = self.lease.clone();
         let ping_handle = tokio::spawn(async move {
             let mut interval = tokio::time::interval(PING_INTERVAL);
             loop {
                 interval.tick().await;
                 let entry: HashMap<String, String> = RedisStreamChunk::Ping.into();
-                let pipeline = redis.pipeline();
-                let _: FredResult<()> = pipeline.xadd(&key, true, None, "*", entry).await;
-                let _: FredResult<()> = pipeline.expire(&key, STREAM_EXPIRE, None).await;
-                let res: FredResult<Vec<fred::prelude::Value>> = pipeline.all().await;
-                if res.is_err() || res.is_ok_and(|r| r.iter().any(|v| v.is_null())) {
+                let res = streams
+                    .add(&key, &[entry], false, Some(STREAM_EXPIRE))
+                    .await;
+                if res.is_ok_and(|exists| !exists) {
                     break;
                 }
+                if let Some(lease) = &lease {
+                    let _ = streams
+                        .renew_lease(&lease.key, &lease.owner, STREAM_LEASE_TTL)
+                        .await;
+                }
             }
         });
         ping_handle
@@ -306,9 +549,12 @@ mod tests {
     use crate::{
         provider::{lorem::LoremProvider, LlmApiProvider, LlmProviderOptions},
         redis::{ExclusiveClientManager, ExclusiveClientPool},
-        stream::{cancel_current_chat_stream, check_chat_stream_exists},
+        stream::{
+            acquire_chat_stream, cancel_current_chat_stream, check_chat_stream_exists,
+            get_chat_stream_key, MemoryStreams, SseStreamReader,
+        },
     };
-    use fred::prelude::{Builder, ClientLike, Config};
+    use fred::prelude::{Builder, ClientLike, Config, StreamsInterface};
     use std::time::Duration;
 
     async fn setup_redis_pool() -> ExclusiveClientPool {
@@ -334,13 +580,13 @@ mod tests {
         session_id: &Uuid,
     ) -> LlmStreamWriter {
         let client = redis.get().await.expect("Failed to get Redis client");
-        LlmStreamWriter::new(ExclusiveRedisClient(client), user_id, session_id)
+        LlmStreamWriter::new(StreamClient::redis(client), user_id, session_id)
     }
 
     #[tokio::test]
     async fn test_stream_writer_basic_functionality() {
         let redis = setup_redis_pool().await;
-        let client = redis.get().await.unwrap();
+        let client = StreamClient::redis(redis.get().await.unwrap());
         let user_id = Uuid::new_v4();
         let session_id = Uuid::new_v4();
         let mut writer = create_test_writer(&redis, &user_id, &session_id).await;
@@ -359,7 +605,8 @@ mod tests {
             .expect("Failed to create lorem stream");
 
         // Process the stream
-        let (text, tool_calls, usage, errors, cancelled) = writer.process(stream).await;
+        let (text, tool_calls, usage, finish_reason, errors, cancelled) =
+            writer.process(stream).await;
 
         // Verify results
         assert!(text.is_some());
@@ -370,6 +617,7 @@ mod tests {
 
         assert!(tool_calls.is_none());
         assert!(usage.is_none());
+        assert!(finish_reason.is_none());
         assert!(errors.is_some()); // Lorem provider generates some test errors
         assert!(!cancelled);
 
@@ -402,7 +650,7 @@ mod tests {
         );
 
         let stream: LlmStream = Box::pin(chunk_stream);
-        let (text, _, _, _, cancelled) = writer.process(stream).await;
+        let (text, _, _, _, _, cancelled) = writer.process(stream).await;
 
         assert!(text.is_some());
         let text = text.unwrap();
@@ -429,7 +677,7 @@ mod tests {
         ]);
 
         let stream: LlmStream = Box::pin(error_stream);
-        let (text, _, _, errors, cancelled) = writer.process(stream).await;
+        let (text, _, _, _, errors, cancelled) = writer.process(stream).await;
 
         assert!(text.is_some());
         let text = text.unwrap();
@@ -461,7 +709,7 @@ mod tests {
 
         // This should timeout due to LLM_TIMEOUT
         let start = std::time::Instant::now();
-        let (text, _, _, errors, cancelled) = writer.process(stream).await;
+        let (text, _, _, _, errors, cancelled) = writer.process(stream).await;
         let elapsed = start.elapsed();
 
         // Should complete in roughly LLM_TIMEOUT duration
@@ -480,7 +728,7 @@ mod tests {
     #[tokio::test]
     async fn test_stream_writer_cancel() {
         let redis = setup_redis_pool().await;
-        let client = redis.get().await.unwrap();
+        let client = StreamClient::redis(redis.get().await.unwrap());
         let user_id = Uuid::new_v4();
         let session_id = Uuid::new_v4();
         let writer = create_test_writer(&redis, &user_id, &session_id).await;
@@ -527,7 +775,7 @@ mod tests {
         ]);
 
         let stream: LlmStream = Box::pin(usage_stream);
-        let (text, _, usage, _, cancelled) = writer.process(stream).await;
+        let (text, _, usage, _, _, cancelled) = writer.process(stream).await;
 
         assert!(text.is_some());
         assert_eq!(text.unwrap(), "Hello World");
@@ -590,4 +838,118 @@ mod tests {
 
         writer.end().await.ok();
     }
+
+    #[tokio::test]
+    async fn test_memory_stream_writer_and_reader() {
+        let streams = StreamClient::memory(&MemoryStreams::default());
+        let user_id = Uuid::new_v4();
+        let session_id = Uuid::new_v4();
+        let mut writer = LlmStreamWriter::new(streams.clone(), &user_id, &session_id);
+
+        assert!(writer.start_with_model(1, "test-model").await.is_ok());
+        assert!(check_chat_stream_exists(&streams, &user_id, &session_id)
+            .await
+            .unwrap());
+
+        let text_stream = tokio_stream::iter(vec![Ok(LlmStreamChunk::Text("Hello".into()))]);
+        let (text, _, _, _, _, cancelled) = writer.process(Box::pin(text_stream)).await;
+        assert_eq!(text.as_deref(), Some("Hello"));
+        assert!(!cancelled);
+
+        // Should have start + text entries, readable from the start or after the first entry
+        let key = get_chat_stream_key(&user_id, &session_id);
+        let reader = SseStreamReader::new(streams.clone());
+        let (events, last_event_id, is_end) = reader.get_prev_events(&key, None).await.unwrap();
+        assert!(events.len() >= 2);
+        assert!(!is_end);
+        let (first_id, first) = streams.first(&key).await.unwrap().unwrap();
+        assert_eq!(first.get("model").map(String::as_str), Some("test-model"));
+        let (after_first, _, _) = reader.get_prev_events(&key, Some(&first_id)).await.unwrap();
+        assert_eq!(after_first.len(), events.len() - 1);
+        let (after_last, id, _) = reader
+            .get_prev_events(&key, Some("99999999999999-0"))
+            .await
+            .unwrap();
+        assert!(after_last.is_empty());
+        assert_eq!(id, "99999999999999-0");
+
+        // A waiting reader gets the `end` event
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        let read_task = tokio::spawn(async move {
+            reader.stream(&key, &last_event_id, &tx).await;
+        });
+        assert!(writer.end().await.is_ok());
+        assert!(rx.recv().await.is_some());
+        read_task.await.unwrap();
+
+        assert!(!check_chat_stream_exists(&streams, &user_id, &session_id)
+            .await
+            .unwrap());
+        assert!(cancel_current_chat_stream(&streams, &user_id, &session_id)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_memory_chat_stream_lease() {
+        let streams = StreamClient::memory(&MemoryStreams::default());
+        let user_id = Uuid::new_v4();
+        let session_id = Uuid::new_v4();
+        let lease = acquire_chat_stream(&streams, &user_id, &session_id)
+            .await
+            .unwrap()
+            .expect("lease should be free");
+        let mut writer =
+            LlmStreamWriter::new(streams.clone(), &user_id, &session_id).with_lease(lease.clone());
+        assert!(writer.start().await.is_ok());
+
+        // A second response for the session can't take the lease, and the stream is kept
+        assert!(acquire_chat_stream(&streams, &user_id, &session_id)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(check_chat_stream_exists(&streams, &user_id, &session_id)
+            .await
+            .unwrap());
+
+        // Only the holder can renew the lease, and it's released when the stream ends
+        assert!(!streams
+            .renew_lease(&lease.key, "other", STREAM_LEASE_TTL)
+            .await
+            .unwrap());
+        assert!(streams
+            .renew_lease(&lease.key, &lease.owner, STREAM_LEASE_TTL)
+            .await
+            .unwrap());
+        let text_stream = tokio_stream::iter(vec![Ok(LlmStreamChunk::Text("Hello".into()))]);
+        writer.process(Box::pin(text_stream)).await;
+        assert!(writer.end().await.is_ok());
+        assert!(streams.lease_owner(&lease.key).await.unwrap().is_none());
+        assert!(acquire_chat_stream(&streams, &user_id, &session_id)
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_stream_writer_pending_tool_call_deltas() {
+        let redis = setup_redis_pool().await;
+        let mut writer = create_test_writer(&redis, &Uuid::new_v4(), &Uuid::new_v4()).await;
+
+        for (index, delta) in [(0, "{\"query\":"), (1, "{}"), (0, " \"rust\"}")] {
+            writer.process_pending_tool_call(LlmPendingToolCall {
+                index,
+                tool_name: format!("tool_{index}"),
+                arguments_delta: Some(delta.into()),
+            });
+        }
+
+        let pending = writer.current_chunk.pending_tool_calls.as_ref().unwrap();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(
+            pending[0].arguments_delta.as_deref(),
+            Some("{\"query\": \"rust\"}")
+        );
+        assert_eq!(pending[1].arguments_delta.as_deref(), Some("{}"));
+    }
 }
@@ -1,45 +1,54 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
-use fred::prelude::StreamsInterface;
+use crate::{
+    provider::{LlmError, LlmErrorInfo},
+    stream::{StreamClient, StreamEntry},
+};
 use rocket::response::stream::Event;
 use tokio::sync::mpsc;
-use uuid::Uuid;
 
-use crate::{provider::LlmError, redis::ExclusiveRedisClient, stream::get_chat_stream_key};
+/// Timeout waiting for the next event of the stream.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+/// Message sent to clients if Redis is unavailable while reading the stream.
+const UNAVAILABLE_MESSAGE: &str =
+    "Stream temporarily unavailable. The response will be saved to the session once completed.";
 
-/// Timeout in milliseconds for the blocking `xread` command.
-const XREAD_BLOCK_TIMEOUT: u64 = 5_000; // 5 seconds
-
-/// Utility for reading SSE events from a Redis stream.
+/// Utility for reading SSE events from a stream.
 pub struct SseStreamReader {
-    redis: ExclusiveRedisClient,
+    streams: StreamClient,
 }
 
 impl SseStreamReader {
-    pub fn new(redis: ExclusiveRedisClient) -> Self {
-        Self { redis }
+    pub fn new(streams: StreamClient) -> Self {
+        Self { streams }
     }
 
-    /// Retrieve the previous events from the given Redis stream.
+    /// Retrieve the previous events from the given stream, after the given event ID
+    /// (e.g. from the `Last-Event-ID` header of a reconnecting client). Invalid event IDs are
+    /// ignored, and all events are returned.
     /// Returns a tuple containing the previous events, the last event ID, and a boolean
     /// indicating if the stream has already ended.
     pub async fn get_prev_events(
         &self,
-        user_id: &Uuid,
-        session_id: &Uuid,
+        key: &str,
         start_event_id: Option<&str>,
     ) -> Result<(Vec<Event>, String, bool), LlmError> {
-        let key = get_chat_stream_key(user_id, session_id);
-        let start_event_id = start_event_id.unwrap_or("0-0");
-        let (_, prev_events): (String, Vec<(String, HashMap<String, String>)>) = self
-            .redis
-            .xread::<Option<Vec<_>>, _, _>(None, None, &key, start_event_id)
-            .await?
-            .and_then(|mut streams| streams.pop()) // should only be 1 stream since we're sending 1 key in the command
-            .ok_or(LlmError::StreamNotFound)?;
+        let start_event_id = start_event_id
+            .map(str::trim)
+            .filter(|id| is_valid_event_id(id))
+            .unwrap_or("0-0");
+        let prev_events = self.streams.read(key, start_event_id).await?;
+        if prev_events.is_empty() {
+            // No events after the given ID: check that the stream exists and hasn't ended. The
+            // new events are then read after the given ID.
+            return match self.streams.last(key).await? {
+                Some((_, data)) => Ok((Vec::new(), start_event_id.to_owned(), is_end_event(&data))),
+                None => Err(LlmError::StreamNotFound),
+            };
+        }
         let (last_event_id, is_end) = prev_events
             .last()
-            .map(|(id, data)| (id.to_owned(), data.get("type").is_some_and(|t| t == "end")))
+            .map(|(id, data)| (id.to_owned(), is_end_event(data)))
             .unwrap_or_else(|| (start_event_id.into(), false));
         let sse_events = prev_events
             .into_iter()
@@ -49,18 +58,11 @@ impl SseStreamReader {
         Ok((sse_events, last_event_id, is_end))
     }
 
-    /// Stream the events from the given Redis stream using a blocking `xread` command.
-    pub async fn stream(
-        &self,
-        user_id: &Uuid,
-        session_id: &Uuid,
-        last_event_id: &str,
-        tx: &mpsc::Sender<Event>,
-    ) {
-        let key = get_chat_stream_key(user_id, session_id);
+    /// Stream the events from the given stream, waiting for each new event.
+    pub async fn stream(&self, key: &str, last_event_id: &str, tx: &mpsc::Sender<Event>) {
         let mut last_event_id = last_event_id.to_owned();
         loop {
-            match self.get_next_event(&key, &mut last_event_id, tx).await {
+            match self.get_next_event(key, &mut last_event_id, tx).await {
                 Ok((id, data, is_end)) => {
                     let event = convert_redis_event_to_sse((id, data));
                     if let Err(_) = tx.send(event).await {
@@ -70,8 +72,15 @@ impl SseStreamReader {
                         break; // reached end of stream
                     }
                 }
+                Err(LlmError::Redis(err)) => {
+                    rocket::warn!("Redis unavailable while reading stream {}: {}", key, err);
+                    let event = Event::data(UNAVAILABLE_MESSAGE).event("unavailable");
+                    tx.send(event).await.ok();
+                    break;
+                }
                 Err(err) => {
-                    let event = Event::data(format!("Error: {}", err)).event("error");
+                    let data = LlmErrorInfo::from(&err).to_json();
+                    let event = Event::data(data).event("error");
                     tx.send(event).await.ok();
                     break;
                 }
@@ -79,7 +88,7 @@ impl SseStreamReader {
         }
     }
 
-    /// Get the next event from the given Redis stream using a blocking `xread` command.
+    /// Wait for the next event from the given stream.
     /// - Updates the last event ID
     /// - Cancels waiting for the next event if the client disconnects
     /// - Returns the event ID, data, and a `bool` indicating whether it's an ending event
@@ -89,28 +98,34 @@ impl SseStreamReader {
         last_event_id: &mut String,
         tx: &mpsc::Sender<Event>,
     ) -> Result<(String, HashMap<String, String>, bool), LlmError> {
-        let (_, mut events): (String, Vec<(String, HashMap<String, String>)>) = tokio::select! {
-            res = self.redis.xread::<Option<Vec<_>>, _, _>(Some(1), Some(XREAD_BLOCK_TIMEOUT), key, &*last_event_id) => {
-                match res?.as_mut().and_then(|streams| streams.pop()) {
-                    Some(stream) => stream,
-                    None => return Err(LlmError::StreamNotFound),
-                }
+        let (id, data): StreamEntry = tokio::select! {
+            res = self.streams.read_next(key, last_event_id, READ_TIMEOUT) => {
+                res?.ok_or(LlmError::StreamNotFound)?
             },
             _ = tx.closed() => return Err(LlmError::ClientDisconnected)
         };
-        match events.pop() {
-            Some((id, data)) => {
-                *last_event_id = id.clone();
-                let is_end = data
-                    .get("type")
-                    .is_some_and(|t| t == "end" || t == "cancel");
-                Ok((id, data, is_end))
-            }
-            None => Err(LlmError::NoStreamEvent),
-        }
+        *last_event_id = id.clone();
+        let is_end = is_end_event(&data);
+
+        Ok((id, data, is_end))
     }
 }
 
+/// Whether the stream event ends the stream
+fn is_end_event(event: &HashMap<String, String>) -> bool {
+    event
+        .get("type")
+        .is_some_and(|t| t == "end" || t == "cancel")
+}
+
+/// Whether the event ID is a valid stream ID (`<milliseconds>-<sequence>`)
+fn is_valid_event_id(id: &str) -> bool {
+    let (ms, seq) = id.split_once('-').unwrap_or((id, "0"));
+    [ms, seq]
+        .iter()
+        .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+}
+
 /// Convert a Redis stream event into an SSE event. Expects the event hash map to contain
 /// a "type" and "data" field (e.g. serialized using the appropriate serde tag and content).
 fn convert_redis_event_to_sse((id, event): (String, HashMap<String, String>)) -> Event {
@@ -0,0 +1,85 @@
+use fred::prelude::FredResult;
+use rocket::response::stream::Event;
+use schemars::JsonSchema;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::{db::models::ChatRsMessage, redis::redis_key, stream::StreamClient};
+
+/// Get the pub/sub channel of the events of the given session
+pub fn get_session_channel(session_id: &Uuid) -> String {
+    redis_key(format_args!("session:{}:events", session_id))
+}
+
+/// Live events of a chat session, broadcast to all members connected to the session
+#[derive(Serialize, JsonSchema)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+#[schemars(rename = "SessionEvent")]
+pub enum SessionEvent<'a> {
+    /// A message was saved to the session
+    Message(&'a ChatRsMessage),
+    /// A member sent a message and the assistant started streaming the response, which can be
+    /// read from `/api/chat/<session_id>/stream`
+    StreamStarted { user_id: Uuid },
+}
+
+/// Publish an event to the members connected to the session. The events are also saved to the
+/// database, so errors are only logged.
+pub async fn publish_session_event(
+    streams: &StreamClient,
+    session_id: &Uuid,
+    event: &SessionEvent<'_>,
+) {
+    let payload = match serde_json::to_string(event) {
+        Ok(payload) => payload,
+        Err(err) => {
+            rocket::warn!("Failed to serialize session event: {}", err);
+            return;
+        }
+    };
+    if let Err(err) = streams
+        .publish(&get_session_channel(session_id), payload)
+        .await
+    {
+        rocket::warn!("Failed to publish session event: {}", err);
+    }
+}
+
+/// Subscribe to the events of the session, and send them as SSE events to the returned channel
+/// until it's closed.
+pub async fn subscribe_session_events(
+    streams: &StreamClient,
+    session_id: &Uuid,
+) -> FredResult<mpsc::Receiver<Event>> {
+    let mut payload_rx = streams.subscribe(&get_session_channel(session_id)).await?;
+    let (tx, rx) = mpsc::channel::<Event>(50);
+    tokio::spawn(async move {
+        loop {
+            let payload = tokio::select! {
+                payload = payload_rx.recv() => payload,
+                _ = tx.closed() => break, // client disconnected
+            };
+            let Some(payload) = payload else {
+                break;
+            };
+            let Some(event) = convert_to_sse(payload) else {
+                continue;
+            };
+            if tx.send(event).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(rx)
+}
+
+/// Convert a published `SessionEvent` payload to an SSE event
+fn convert_to_sse(payload: String) -> Option<Event> {
+    let mut value: serde_json::Value = serde_json::from_str(&payload).ok()?;
+    let r#type = value.get("type")?.as_str()?.to_owned();
+    let data = value.get_mut("data").map(serde_json::Value::take);
+
+    Some(Event::data(data.unwrap_or_default().to_string()).event(r#type))
+}
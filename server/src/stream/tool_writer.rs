@@ -0,0 +1,112 @@
+use std::{collections::HashMap, time::Duration};
+
+use fred::prelude::FredResult;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::{redis::redis_key, stream::StreamClient};
+
+/// Expiration in seconds set on the stream (refreshed on each event), so that clients can
+/// reconnect and get the output for a while after the execution has ended
+const STREAM_EXPIRE: i64 = 600;
+/// Interval for sending ping events while the tool is running, so that readers don't time out
+const PING_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Get the key of the output stream in Redis for the execution of the given tool call, or
+/// of all tool calls of the message if no tool call ID is given
+pub fn get_tool_stream_key(
+    user_id: &Uuid,
+    message_id: &Uuid,
+    tool_call_id: Option<&str>,
+) -> String {
+    match tool_call_id {
+        Some(tool_call_id) => redis_key(format_args!(
+            "user:{}:tool:{}:{}",
+            user_id, message_id, tool_call_id
+        )),
+        None => redis_key(format_args!("user:{}:tool:{}", user_id, message_id)),
+    }
+}
+
+/// Utility for writing the output of a tool execution to a stream. The events are the
+/// `ToolStreamEvent` or `BatchToolStreamEvent` chunks and `ping` events, between a `start`
+/// and an `end` event.
+pub struct ToolStreamWriter {
+    streams: StreamClient,
+    key: String,
+}
+
+impl ToolStreamWriter {
+    pub fn new(
+        streams: StreamClient,
+        user_id: &Uuid,
+        message_id: &Uuid,
+        tool_call_id: Option<&str>,
+    ) -> Self {
+        ToolStreamWriter {
+            streams,
+            key: get_tool_stream_key(user_id, message_id, tool_call_id),
+        }
+    }
+
+    /// Get the key of the stream
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Create the stream with a `start` event, replacing the stream of a previous execution.
+    pub async fn start(&self) -> FredResult<()> {
+        self.streams.delete(&self.key).await?;
+        self.add(event_entry("start", String::new()), true).await
+    }
+
+    /// Write the chunks of the tool output received from the channel until it's closed, and
+    /// ping the stream in between.
+    pub async fn forward<T: Into<HashMap<String, String>>>(&self, rx: &mut mpsc::Receiver<T>) {
+        let mut interval = tokio::time::interval(PING_INTERVAL);
+        interval.tick().await; // the first tick completes immediately
+        loop {
+            tokio::select! {
+                chunk = rx.recv() => match chunk {
+                    Some(chunk) => self.write(chunk).await,
+                    None => break,
+                },
+                _ = interval.tick() => self.write(event_entry("ping", String::new())).await,
+            }
+        }
+    }
+
+    /// Add a chunk of the tool output. Errors are only logged, as the result of the tool is
+    /// still saved to the database.
+    async fn write(&self, chunk: impl Into<HashMap<String, String>>) {
+        if let Err(err) = self.add(chunk.into(), false).await {
+            rocket::warn!(
+                "Failed to write tool output to stream {}: {}",
+                self.key,
+                err
+            );
+        }
+    }
+
+    /// Add an `end` event. The stream is kept until it expires.
+    pub async fn end(&self) {
+        if let Err(err) = self.add(event_entry("end", String::new()), false).await {
+            rocket::warn!("Failed to end tool output stream {}: {}", self.key, err);
+        }
+    }
+
+    async fn add(&self, entry: HashMap<String, String>, create: bool) -> FredResult<()> {
+        self.streams
+            .add(&self.key, &[entry], create, Some(STREAM_EXPIRE))
+            .await?;
+        Ok(())
+    }
+}
+
+/// Create a stream entry with the given event type and data
+fn event_entry(r#type: &str, data: String) -> HashMap<String, String> {
+    HashMap::from([
+        ("type".to_owned(), r#type.to_owned()),
+        ("data".to_owned(), data),
+    ])
+}
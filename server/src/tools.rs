@@ -1,16 +1,42 @@
+mod approval;
 mod core;
+mod execution;
 mod external_api;
+mod mcp;
+mod oauth2;
 mod system;
+mod test_run;
 mod utils;
 
 pub use {
-    core::{ToolError, ToolJsonSchema, ToolLog, ToolParameters, ToolResponseFormat, ToolResult},
-    external_api::{ChatRsExternalApiToolConfig, ExternalApiToolInput},
-    system::{ChatRsSystemToolConfig, SystemToolInput},
+    approval::{request_approvals, ToolCallApproval, ToolCallApprovalStatus},
+    core::{
+        BatchToolLog, ToolError, ToolExecutionLimits, ToolJsonSchema, ToolLog, ToolParameters,
+        ToolResponseFormat, ToolResult, ToolSecrets, ToolStorage, DEFAULT_TOOL_MAX_OUTPUT_BYTES,
+        DEFAULT_TOOL_TIMEOUT_SECONDS,
+    },
+    execution::{
+        find_executable_tool, run_tool_call, save_tool_call_output, ExecutableTool, ToolCallOutput,
+    },
+    external_api::{
+        decode_entities, extract_page_content, ChatRsExternalApiToolConfig, ExternalApiToolInput,
+        EXTERNAL_API_SECRET_NAMES,
+    },
+    mcp::{ChatRsMcpToolConfig, McpToolInput, McpTransport},
+    oauth2::{OAuth2SecretInput, OAuth2Settings},
+    system::{
+        delete_code_runner_workspace, setup_code_runner_image_pool, setup_code_runner_wasm_path,
+        ChatRsSystemToolConfig, SystemToolInput,
+    },
+    test_run::UnsavedTool,
 };
 
 use {
-    crate::{db::services::ToolDbService, errors::ApiError, provider::LlmTool},
+    crate::{
+        db::{models::NewChatRsSystemTool, services::ToolDbService},
+        errors::ApiError,
+        provider::LlmTool,
+    },
     schemars::JsonSchema,
     uuid::Uuid,
 };
@@ -20,6 +46,7 @@ use {
 pub struct SendChatToolInput {
     pub system: Option<SystemToolInput>,
     pub external_apis: Option<Vec<ExternalApiToolInput>>,
+    pub mcp: Option<Vec<McpToolInput>>,
 }
 
 /// Get all tools from the user's input in LLM generic format
@@ -29,11 +56,25 @@ pub async fn get_llm_tools_from_input(
     tool_db_service: &mut ToolDbService<'_>,
 ) -> Result<Vec<LlmTool>, ApiError> {
     let mut llm_tools = Vec::with_capacity(5);
-    if let Some(ref system_tool_input) = input.system {
-        let system_tools = tool_db_service.find_system_tools_by_user(&user_id).await?;
-        let system_llm_tools = system_tool_input.get_llm_tools(&system_tools)?;
-        llm_tools.extend(system_llm_tools);
+    // System tools are always checked, as some can be enabled automatically (e.g. memory)
+    let mut system_tools = tool_db_service.find_system_tools_by_user(&user_id).await?;
+    let default_system_input = SystemToolInput::default();
+    let system_tool_input = input.system.as_ref().unwrap_or(&default_system_input);
+    // The date/time tools need no configuration, so save them for the user on first use
+    if system_tool_input.date_time_enabled()
+        && !system_tools
+            .iter()
+            .any(|t| matches!(t.data, ChatRsSystemToolConfig::DateTime))
+    {
+        let date_time_tool = tool_db_service
+            .create_system_tool(NewChatRsSystemTool {
+                user_id,
+                data: &ChatRsSystemToolConfig::DateTime,
+            })
+            .await?;
+        system_tools.push(date_time_tool);
     }
+    llm_tools.extend(system_tool_input.get_llm_tools(&system_tools)?);
     if let Some(ref external_apis_input) = input.external_apis {
         let external_api_tools = tool_db_service
             .find_external_api_tools_by_user(&user_id)
@@ -43,6 +84,13 @@ pub async fn get_llm_tools_from_input(
             llm_tools.extend(api_llm_tools);
         }
     }
+    if let Some(ref mcp_input) = input.mcp {
+        let mcp_tools = tool_db_service.find_mcp_tools_by_user(&user_id).await?;
+        for tool_input in mcp_input {
+            let mcp_llm_tools = tool_input.into_llm_tools(&mcp_tools)?;
+            llm_tools.extend(mcp_llm_tools);
+        }
+    }
 
     Ok(llm_tools)
 }
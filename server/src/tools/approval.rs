@@ -0,0 +1,150 @@
+//! Approval of tool calls for tools with side effects (e.g. running code or calling custom APIs)
+
+use chrono::{DateTime, Duration, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    db::{models::ChatRsToolCall, services::ToolDbService, DbConnection},
+    provider::LlmToolType,
+    tools::{ChatRsExternalApiToolConfig, ChatRsSystemToolConfig, ToolError},
+};
+
+/// Time allowed to approve and execute a tool call.
+const APPROVAL_TTL: Duration = Duration::hours(1);
+
+/// Approval state of a tool call
+#[derive(Debug, Clone, Copy, PartialEq, JsonSchema, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCallApprovalStatus {
+    /// Waiting for the user to approve or deny the tool call
+    Pending,
+    /// The tool call can be executed
+    Approved,
+    /// The tool call won't be executed
+    Denied,
+}
+
+/// Approval of a tool call that requires explicit confirmation before being executed
+#[derive(Debug, Clone, JsonSchema, Serialize, Deserialize)]
+pub struct ToolCallApproval {
+    pub status: ToolCallApprovalStatus,
+    /// The tool call can't be approved or executed after this time
+    pub expires_at: DateTime<Utc>,
+    /// When the tool call was approved or denied
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decided_at: Option<DateTime<Utc>>,
+}
+
+impl ToolCallApproval {
+    fn pending() -> Self {
+        Self {
+            status: ToolCallApprovalStatus::Pending,
+            expires_at: Utc::now() + APPROVAL_TTL,
+            decided_at: None,
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        Utc::now() > self.expires_at
+    }
+
+    /// Approve or deny the pending tool call
+    pub fn decide(&mut self, approved: bool) -> Result<(), ToolError> {
+        if self.status != ToolCallApprovalStatus::Pending {
+            return Err(ToolError::ApprovalAlreadyDecided);
+        }
+        if self.is_expired() {
+            return Err(ToolError::ApprovalExpired);
+        }
+        self.status = match approved {
+            true => ToolCallApprovalStatus::Approved,
+            false => ToolCallApprovalStatus::Denied,
+        };
+        self.decided_at = Some(Utc::now());
+
+        Ok(())
+    }
+
+    /// Check that the tool call has been approved, and can be executed
+    pub fn check_approved(&self) -> Result<(), ToolError> {
+        match self.status {
+            ToolCallApprovalStatus::Pending if self.is_expired() => Err(ToolError::ApprovalExpired),
+            ToolCallApprovalStatus::Pending => Err(ToolError::ApprovalRequired),
+            ToolCallApprovalStatus::Denied => Err(ToolError::ApprovalDenied),
+            ToolCallApprovalStatus::Approved if self.is_expired() => {
+                Err(ToolError::ApprovalExpired)
+            }
+            ToolCallApprovalStatus::Approved => Ok(()),
+        }
+    }
+}
+
+impl ChatRsToolCall {
+    /// Check that the tool call can be executed, i.e. it doesn't require approval or has
+    /// been approved
+    pub fn check_approval(&self) -> Result<(), ToolError> {
+        match &self.approval {
+            Some(approval) => approval.check_approved(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl ChatRsSystemToolConfig {
+    /// Whether calls of this tool must be approved before being executed
+    pub fn requires_approval(&self) -> bool {
+        matches!(self, ChatRsSystemToolConfig::CodeRunner(_))
+    }
+}
+
+impl ChatRsExternalApiToolConfig {
+    /// Whether calls of this tool must be approved before being executed. GraphQL queries
+    /// don't require approval, but mutations do.
+    pub fn requires_approval(&self, tool_name: &str) -> bool {
+        match self {
+            ChatRsExternalApiToolConfig::CustomApi(_) => true,
+            ChatRsExternalApiToolConfig::GraphQl(_) => !self.is_read_only(tool_name),
+            ChatRsExternalApiToolConfig::WebSearch(_) => false,
+            ChatRsExternalApiToolConfig::Browser(_) => false,
+            ChatRsExternalApiToolConfig::Email(_) => true,
+            ChatRsExternalApiToolConfig::Webhook(_) => true,
+        }
+    }
+}
+
+/// Mark the tool calls that require approval as pending. Tools that are auto-approved
+/// by the user don't require approval.
+pub async fn request_approvals(
+    db: &mut DbConnection,
+    user_id: &Uuid,
+    tool_calls: &mut [ChatRsToolCall],
+) -> Result<(), diesel::result::Error> {
+    let mut tool_db_service = ToolDbService::new(db);
+    for tool_call in tool_calls {
+        let requires_approval = match tool_call.tool_type {
+            LlmToolType::System => tool_db_service
+                .find_system_tool_by_id(user_id, &tool_call.tool_id)
+                .await?
+                .is_some_and(|tool| !tool.auto_approve && tool.data.requires_approval()),
+            LlmToolType::ExternalApi => tool_db_service
+                .find_accessible_external_api_tool_by_id(user_id, &tool_call.tool_id)
+                .await?
+                .is_some_and(|(tool, _)| {
+                    !tool.auto_approve && tool.data.requires_approval(&tool_call.tool_name)
+                }),
+            LlmToolType::Mcp => tool_db_service
+                .find_accessible_mcp_tool_by_id(user_id, &tool_call.tool_id)
+                .await?
+                .is_some_and(|(tool, _)| {
+                    !tool.auto_approve && tool.data.requires_approval(&tool_call.tool_name)
+                }),
+        };
+        if requires_approval {
+            tool_call.approval = Some(ToolCallApproval::pending());
+        }
+    }
+
+    Ok(())
+}
@@ -1,30 +1,127 @@
 //! Core types and interfaces for tools
 
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
 
+use fred::{prelude::KeysInterface, types::Expiration};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    db::models::{ChatRsKnowledgeBase, ChatRsKnowledgeSearchResult, ChatRsMemory},
+    knowledge::KnowledgeService,
+    redis::redis_key,
+    storage::{LocalStorage, StoredFileInfo},
+    utils::{Redact, Redactor},
+};
+
+/// Expiration of the daily usage counts of tools, in seconds
+const USAGE_TTL: i64 = 2 * 24 * 60 * 60;
+/// Expiration of the hourly call counts of tools, in seconds
+const HOURLY_CALLS_TTL: i64 = 2 * 60 * 60;
+/// Expiration of the call counts of tools in a session, in seconds
+const SESSION_CALLS_TTL: i64 = 30 * 24 * 60 * 60;
+
+/// Directory of the user's files that tools can write to, so that they can't replace other
+/// files (e.g. uploads, and files attached to messages)
+const TOOL_FILES_DIR: &str = "files";
+
+/// Default max time to execute a tool call, in seconds
+pub const DEFAULT_TOOL_TIMEOUT_SECONDS: u32 = 300;
+/// Default max size of the output of a tool call, in bytes
+pub const DEFAULT_TOOL_MAX_OUTPUT_BYTES: u32 = 1024 * 1024;
 
 /// Standard result type for all tool operations
 pub type ToolResult<T> = Result<T, ToolError>;
 
-/// Tool logging stream chunk
-#[derive(Debug, Clone)]
+/// Tool logging stream chunk. The `type` is sent as the SSE event name, and the `data`
+/// as the SSE event data.
+#[derive(Debug, Clone, JsonSchema, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+#[schemars(rename = "ToolStreamEvent")]
 pub enum ToolLog {
+    /// Final result of the tool
     Result(String),
+    /// Log message
     Log(String),
+    /// Debug message
     Debug(String),
+    /// Error message
     Error(String),
 }
 
+impl ToolLog {
+    /// Split this chunk into its SSE event name and data
+    fn into_event_parts(self) -> (&'static str, String) {
+        match self {
+            ToolLog::Result(data) => ("result", data),
+            ToolLog::Log(data) => ("log", data),
+            ToolLog::Debug(data) => ("debug", data),
+            ToolLog::Error(data) => ("error", data),
+        }
+    }
+}
+
+impl Redact for ToolLog {
+    fn redact(&mut self, redactor: &Redactor) {
+        match self {
+            ToolLog::Result(data)
+            | ToolLog::Log(data)
+            | ToolLog::Debug(data)
+            | ToolLog::Error(data) => redactor.redact(data),
+        }
+    }
+}
+
 impl From<ToolLog> for rocket::response::stream::Event {
     fn from(chunk: ToolLog) -> Self {
-        match chunk {
-            ToolLog::Result(data) => Self::data(data).event("result"),
-            ToolLog::Log(data) => Self::data(data).event("log"),
-            ToolLog::Debug(data) => Self::data(data).event("debug"),
-            ToolLog::Error(data) => Self::data(data).event("error"),
-        }
+        let (event, data) = chunk.into_event_parts();
+        Self::data(data).event(event)
+    }
+}
+
+impl From<ToolLog> for HashMap<String, String> {
+    /// Converts a `ToolLog` into a hash map, suitable for the stream client.
+    fn from(chunk: ToolLog) -> Self {
+        let (event, data) = chunk.into_event_parts();
+        HashMap::from([("type".into(), event.into()), ("data".into(), data)])
+    }
+}
+
+/// Tool logging stream chunk when executing multiple tool calls at once. The `type` is sent
+/// as the SSE event name, and the `tool_call_id` and `data` as the JSON-encoded SSE event data.
+#[derive(Debug, Clone, JsonSchema, Serialize)]
+#[schemars(rename = "BatchToolStreamEvent")]
+pub struct BatchToolLog {
+    /// ID of the tool call that this chunk belongs to
+    pub tool_call_id: String,
+    #[serde(flatten)]
+    pub log: ToolLog,
+}
+
+impl From<BatchToolLog> for rocket::response::stream::Event {
+    fn from(chunk: BatchToolLog) -> Self {
+        let (event, data) = chunk.log.into_event_parts();
+        let data = serde_json::json!({ "tool_call_id": chunk.tool_call_id, "data": data });
+        Self::data(data.to_string()).event(event)
+    }
+}
+
+impl From<BatchToolLog> for HashMap<String, String> {
+    /// Converts a `BatchToolLog` into a hash map, suitable for the stream client.
+    fn from(chunk: BatchToolLog) -> Self {
+        let (event, data) = chunk.log.into_event_parts();
+        let data = serde_json::json!({ "tool_call_id": chunk.tool_call_id, "data": data });
+        HashMap::from([
+            ("type".into(), event.into()),
+            ("data".into(), data.to_string()),
+        ])
     }
 }
 
@@ -36,11 +133,347 @@ pub enum ToolResponseFormat {
     Text,
     Json,
     Markdown,
+    /// Image stored in the user's files, which can be fetched from the file routes
+    Image {
+        /// Storage path of the image
+        storage_path: String,
+        /// MIME type of the image
+        mime: String,
+    },
+}
+
+/// Storage available to tools (files generated by tools, and the user's knowledge bases
+/// and memories), scoped to the user executing the tool and optionally the chat session
+pub struct ToolStorage {
+    storage: LocalStorage,
+    knowledge: KnowledgeService,
+    user_id: Uuid,
+    session_id: Option<Uuid>,
+}
+
+impl ToolStorage {
+    pub fn new(storage: LocalStorage, knowledge: KnowledgeService, user_id: Uuid) -> Self {
+        Self {
+            storage,
+            knowledge,
+            user_id,
+            session_id: None,
+        }
+    }
+
+    /// Scope the storage to the chat session of the tool call
+    pub fn with_session(mut self, session_id: Uuid) -> Self {
+        self.session_id = Some(session_id);
+        self
+    }
+
+    /// The chat session of the tool call (not set when testing tools)
+    pub fn session_id(&self) -> Option<&Uuid> {
+        self.session_id.as_ref()
+    }
+
+    /// Save an image generated by the tool, and get the response format referencing it
+    pub async fn save_image(&self, data: &[u8], mime: &str) -> ToolResult<ToolResponseFormat> {
+        let storage_path = self
+            .storage
+            .save(&self.user_id, data, mime)
+            .await
+            .map_err(|e| ToolError::ToolExecutionError(e.to_string()))?;
+
+        Ok(ToolResponseFormat::Image {
+            storage_path,
+            mime: mime.to_owned(),
+        })
+    }
+
+    /// List the files in a directory of the user's files (relative to the user's directory)
+    pub async fn list_files(&self, path: &str) -> ToolResult<Vec<StoredFileInfo>> {
+        self.storage
+            .list(&self.user_id, &self.user_path(path))
+            .await
+            .map_err(|e| ToolError::ToolExecutionError(e.to_string()))
+    }
+
+    /// Read one of the user's files (relative to the user's directory), up to the max size
+    pub async fn read_file(&self, path: &str, max_size: u64) -> ToolResult<Vec<u8>> {
+        self.storage
+            .read(&self.user_id, &self.user_path(path), max_size)
+            .await
+            .map_err(|e| ToolError::ToolExecutionError(e.to_string()))
+    }
+
+    /// Write one of the user's files in the tools' directory, and get its storage path. Paths
+    /// that are outside of the tools' directory are placed inside it.
+    pub async fn write_file(&self, path: &str, data: &[u8]) -> ToolResult<String> {
+        let storage_path = self.user_path(&get_tool_file_path(path)?);
+        self.storage
+            .write(&self.user_id, &storage_path, data)
+            .await
+            .map_err(|e| ToolError::ToolExecutionError(e.to_string()))?;
+        Ok(storage_path.to_string_lossy().into_owned())
+    }
+
+    /// Get the storage path of a path relative to the user's directory
+    fn user_path(&self, path: &str) -> PathBuf {
+        let mut storage_path = PathBuf::from(self.user_id.to_string());
+        storage_path.push(path.trim_start_matches("./"));
+        storage_path
+    }
+
+    /// List the user's knowledge bases
+    pub async fn list_knowledge_bases(&self) -> ToolResult<Vec<ChatRsKnowledgeBase>> {
+        self.knowledge
+            .list(&self.user_id)
+            .await
+            .map_err(|e| ToolError::ToolExecutionError(e.to_string()))
+    }
+
+    /// Search one of the user's knowledge bases for the chunks most similar to the query
+    pub async fn search_knowledge(
+        &self,
+        knowledge_base_id: &Uuid,
+        query: &str,
+        limit: u8,
+    ) -> ToolResult<Vec<ChatRsKnowledgeSearchResult>> {
+        self.knowledge
+            .search(&self.user_id, knowledge_base_id, query, limit)
+            .await
+            .map_err(|e| ToolError::ToolExecutionError(e.to_string()))
+    }
+
+    /// Save a memory for the user, embedded with the given provider ID and model if set
+    pub async fn remember(
+        &self,
+        content: &str,
+        embedding: Option<(i32, &str)>,
+    ) -> ToolResult<ChatRsMemory> {
+        self.knowledge
+            .remember(&self.user_id, content, embedding)
+            .await
+            .map_err(|e| ToolError::ToolExecutionError(e.to_string()))
+    }
+
+    /// Recall the user's memories relevant to the query (or the most recent memories)
+    pub async fn recall(
+        &self,
+        query: Option<&str>,
+        embedding: Option<(i32, &str)>,
+        limit: u8,
+    ) -> ToolResult<Vec<ChatRsMemory>> {
+        self.knowledge
+            .recall(&self.user_id, query, embedding, limit)
+            .await
+            .map_err(|e| ToolError::ToolExecutionError(e.to_string()))
+    }
+
+    /// Increment the user's usage count of the tool for today (e.g. `email:<address>`),
+    /// returning the new count
+    pub async fn increment_daily_usage(&self, name: &str) -> ToolResult<u64> {
+        let today = chrono::Utc::now().format("%Y-%m-%d");
+        let key = redis_key(format_args!("tool_usage:{}:{name}:{today}", self.user_id));
+        self.increment_count(&key, USAGE_TTL).await
+    }
+
+    /// Increment the user's call count of the tool for the current hour, returning the
+    /// new count
+    pub async fn increment_hourly_calls(&self, tool_id: &Uuid) -> ToolResult<u64> {
+        let hour = chrono::Utc::now().format("%Y-%m-%dT%H");
+        let key = redis_key(format_args!("tool_calls:{}:{tool_id}:{hour}", self.user_id));
+        self.increment_count(&key, HOURLY_CALLS_TTL).await
+    }
+
+    /// Increment the call count of the tool in the current session, returning the new
+    /// count (`None` if not in a session)
+    pub async fn increment_session_calls(&self, tool_id: &Uuid) -> ToolResult<Option<u64>> {
+        let Some(session_id) = self.session_id else {
+            return Ok(None);
+        };
+        let key = redis_key(format_args!(
+            "tool_calls:{}:{tool_id}:session:{session_id}",
+            self.user_id
+        ));
+        self.increment_count(&key, SESSION_CALLS_TTL)
+            .await
+            .map(Some)
+    }
+
+    async fn increment_count(&self, key: &str, ttl: i64) -> ToolResult<u64> {
+        let pipeline = self.knowledge.redis().pipeline();
+        let _: () = pipeline.incr(key).await.map_err(usage_error)?;
+        let _: () = pipeline.expire(key, ttl, None).await.map_err(usage_error)?;
+        let (count, _): (u64, i64) = pipeline.all().await.map_err(usage_error)?;
+        Ok(count)
+    }
+
+    /// Get the cached result of an identical call of the tool, if any
+    pub async fn get_cached_result(
+        &self,
+        tool_id: &Uuid,
+        tool_name: &str,
+        parameters: &ToolParameters,
+    ) -> Option<(String, ToolResponseFormat)> {
+        let key = self.cache_key(tool_id, tool_name, parameters)?;
+        let cached: Option<String> = match self.knowledge.redis().get(&key).await {
+            Ok(cached) => cached,
+            Err(err) => {
+                rocket::warn!("Failed to get cached tool result: {err}");
+                return None;
+            }
+        };
+        cached.and_then(|result| serde_json::from_str(&result).ok())
+    }
+
+    /// Cache the result of the tool call for the given number of seconds
+    pub async fn cache_result(
+        &self,
+        tool_id: &Uuid,
+        tool_name: &str,
+        parameters: &ToolParameters,
+        result: &(String, ToolResponseFormat),
+        ttl: i32,
+    ) {
+        let Some(key) = self.cache_key(tool_id, tool_name, parameters) else {
+            return;
+        };
+        let Ok(value) = serde_json::to_string(result) else {
+            return;
+        };
+        let expiration = Some(Expiration::EX(ttl.into()));
+        let set_result: Result<(), _> = self
+            .knowledge
+            .redis()
+            .set(&key, value, expiration, None, false)
+            .await;
+        if let Err(err) = set_result {
+            rocket::warn!("Failed to cache tool result: {err}");
+        }
+    }
+
+    /// Cache key of the tool call, using a hash of the normalized parameters
+    fn cache_key(
+        &self,
+        tool_id: &Uuid,
+        tool_name: &str,
+        parameters: &ToolParameters,
+    ) -> Option<String> {
+        let parameters: BTreeMap<_, _> = parameters
+            .iter()
+            .map(|(name, value)| (name, normalize_json(value)))
+            .collect();
+        let hash = Sha256::digest(serde_json::to_vec(&parameters).ok()?);
+        Some(redis_key(format_args!(
+            "tool_cache:{}:{tool_id}:{tool_name}:{}",
+            self.user_id,
+            hex::encode(hash)
+        )))
+    }
+
+    /// Delete one of the user's memories
+    pub async fn forget(&self, memory_id: &Uuid) -> ToolResult<()> {
+        self.knowledge
+            .forget(&self.user_id, memory_id)
+            .await
+            .map_err(|e| ToolError::ToolExecutionError(e.to_string()))?;
+        Ok(())
+    }
+}
+
+/// Get the path of a file written by a tool, relative to the user's directory (in the tools'
+/// directory, e.g. `notes/todo.md` becomes `files/notes/todo.md`)
+pub fn get_tool_file_path(path: &str) -> ToolResult<String> {
+    let path = path.trim_start_matches("./");
+    let is_relative = Path::new(path)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    if path.is_empty() || !is_relative {
+        return Err(ToolError::InvalidParameters(format!(
+            "Invalid file path: {path}"
+        )));
+    }
+    match Path::new(path).starts_with(TOOL_FILES_DIR) {
+        true => Ok(path.to_owned()),
+        false => Ok(format!("{TOOL_FILES_DIR}/{path}")),
+    }
+}
+
+fn usage_error(err: fred::error::Error) -> ToolError {
+    ToolError::ToolExecutionError(format!("Failed to check usage limit: {err}"))
+}
+
+/// Sort the keys of all JSON objects, so that identical values are serialized identically
+fn normalize_json(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.to_owned(), normalize_json(value)))
+                .collect()
+        }
+        serde_json::Value::Array(items) => items.iter().map(normalize_json).collect(),
+        _ => value.to_owned(),
+    }
+}
+
+/// Limits of the execution of a tool call
+#[derive(Debug, Clone, Copy)]
+pub struct ToolExecutionLimits {
+    /// Max time to execute the tool call
+    pub timeout: Duration,
+    /// Max size of the text output in bytes. Longer output is truncated.
+    pub max_output_bytes: usize,
+}
+
+impl Default for ToolExecutionLimits {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+impl ToolExecutionLimits {
+    /// Create the limits from the tool's settings, using the defaults if not set
+    pub fn new(timeout_seconds: Option<i32>, max_output_bytes: Option<i32>) -> Self {
+        let timeout_seconds = timeout_seconds
+            .and_then(|seconds| u32::try_from(seconds).ok())
+            .filter(|seconds| *seconds > 0)
+            .unwrap_or(DEFAULT_TOOL_TIMEOUT_SECONDS);
+        let max_output_bytes = max_output_bytes
+            .and_then(|bytes| u32::try_from(bytes).ok())
+            .filter(|bytes| *bytes > 0)
+            .unwrap_or(DEFAULT_TOOL_MAX_OUTPUT_BYTES);
+        Self {
+            timeout: Duration::from_secs(timeout_seconds.into()),
+            max_output_bytes: max_output_bytes as usize,
+        }
+    }
+
+    /// Run the tool execution within the timeout, and truncate its output if too long
+    pub async fn apply(
+        &self,
+        execution: impl Future<Output = ToolResult<(String, ToolResponseFormat)>>,
+    ) -> ToolResult<(String, ToolResponseFormat)> {
+        let (mut output, format) = tokio::time::timeout(self.timeout, execution)
+            .await
+            .map_err(|_| ToolError::Timeout(self.timeout.as_secs()))??;
+        if output.len() > self.max_output_bytes {
+            let mut end = self.max_output_bytes;
+            while !output.is_char_boundary(end) {
+                end -= 1;
+            }
+            output.truncate(end);
+            output.push_str("\n... (output truncated)");
+        }
+        Ok((output, format))
+    }
 }
 
 /// Tool input parameters
 pub type ToolParameters = HashMap<String, serde_json::Value>;
 
+/// Decrypted secrets of a tool, keyed by name (`secret_1`, `secret_2`, `secret_3`)
+pub type ToolSecrets = HashMap<String, String>;
+
 /// Tool-related errors
 #[derive(Debug, thiserror::Error)]
 pub enum ToolError {
@@ -54,12 +487,32 @@ pub enum ToolError {
     ToolNotFound,
     #[error("Tool call not found")]
     ToolCallNotFound,
+    #[error("Tool call was planned in a dry run, and can't be executed")]
+    DryRunToolCall,
+    #[error("Tool call must be approved before being executed")]
+    ApprovalRequired,
+    #[error("Tool call was denied")]
+    ApprovalDenied,
+    #[error("Tool call approval has expired")]
+    ApprovalExpired,
+    #[error("Tool call has already been approved or denied")]
+    ApprovalAlreadyDecided,
+    #[error("Tool call doesn't require approval")]
+    ApprovalNotRequired,
+    #[error("Tool has side effects, and can't be executed when testing")]
+    UnsafeTestExecution,
+    #[error("OAuth2 token refresh failed: {0}")]
+    OAuth2Error(String),
     #[error("Formatting error: {0}")]
     FormattingError(String),
     #[error("Serialization error")]
     SerializationError(#[from] serde_json::Error),
     #[error("Tool execution error: {0}")]
     ToolExecutionError(String),
+    #[error("Tool execution timed out after {0} seconds")]
+    Timeout(u64),
+    #[error("Rate limit reached: {0}")]
+    RateLimited(String),
     #[error("Tool execution cancelled: {0}")]
     Cancelled(String),
     #[error("IO error: {0}")]
@@ -82,3 +535,28 @@ pub enum ToolJsonSchemaType {
     #[serde(rename = "object")]
     Object,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_file_path() {
+        assert_eq!(
+            get_tool_file_path("notes/todo.md").unwrap(),
+            "files/notes/todo.md"
+        );
+        assert_eq!(get_tool_file_path("./todo.md").unwrap(), "files/todo.md");
+        assert_eq!(
+            get_tool_file_path("files/todo.md").unwrap(),
+            "files/todo.md"
+        );
+        assert_eq!(
+            get_tool_file_path("filesx/a.md").unwrap(),
+            "files/filesx/a.md"
+        );
+        for invalid in ["", "../a.md", "files/../a.md", "/etc/passwd", "a/./../b"] {
+            assert!(get_tool_file_path(invalid).is_err(), "{invalid}");
+        }
+    }
+}
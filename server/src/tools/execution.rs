@@ -0,0 +1,426 @@
+//! Execution of the tool calls requested by the assistant
+
+use std::time::Instant;
+
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        models::{
+            ChatRsExecutedToolCall, ChatRsExternalApiTool, ChatRsMcpTool, ChatRsMessage,
+            ChatRsMessageMeta, ChatRsMessageRole, ChatRsSystemTool, ChatRsToolCall,
+            ChatRsToolRunStatus, NewChatRsMessage, NewChatRsToolRun,
+        },
+        services::{ChatDbService, SecretDbService, ToolDbService, ToolRunDbService},
+        DbConnection,
+    },
+    errors::ApiError,
+    provider::LlmToolType,
+    tools::{
+        oauth2::get_access_token, ToolError, ToolExecutionLimits, ToolLog, ToolResponseFormat,
+        ToolResult, ToolSecrets, ToolStorage, EXTERNAL_API_SECRET_NAMES,
+    },
+    utils::{Encryptor, Redactor, SenderWithLogging},
+};
+
+/// Name of the access token of the tool's first OAuth2 credential, for use in templates
+const ACCESS_TOKEN_NAME: &str = "access_token";
+
+/// A tool that can execute a tool call
+pub enum ExecutableTool {
+    /// System tool, with its decrypted secret if any
+    System(ChatRsSystemTool, Option<String>),
+    /// External API tool, with its decrypted secrets
+    ExternalApi(ChatRsExternalApiTool, ToolSecrets),
+    /// MCP tool, with its decrypted secrets
+    Mcp(ChatRsMcpTool, Vec<String>),
+}
+
+impl ExecutableTool {
+    /// Whether the tool can be executed automatically by the server
+    pub fn auto_approve(&self) -> bool {
+        match self {
+            ExecutableTool::System(tool, _) => tool.auto_approve,
+            ExecutableTool::ExternalApi(tool, _) => tool.auto_approve,
+            ExecutableTool::Mcp(tool, _) => tool.auto_approve,
+        }
+    }
+
+    /// Seconds to cache the results of the tool, if caching is enabled
+    pub fn cache_ttl(&self) -> Option<i32> {
+        match self {
+            ExecutableTool::System(tool, _) => tool.cache_ttl,
+            ExecutableTool::ExternalApi(tool, _) => tool.cache_ttl,
+            ExecutableTool::Mcp(tool, _) => tool.cache_ttl,
+        }
+        .filter(|ttl| *ttl > 0)
+    }
+
+    /// Redactor of the tool's decrypted secrets
+    pub fn redactor(&self) -> Redactor {
+        match self {
+            ExecutableTool::System(_, secret) => Redactor::new(secret.as_deref()),
+            ExecutableTool::ExternalApi(_, secrets) => {
+                Redactor::new(secrets.values().map(String::as_str))
+            }
+            ExecutableTool::Mcp(_, secrets) => Redactor::new(secrets.iter().map(String::as_str)),
+        }
+    }
+
+    /// Limits of the execution of the tool's calls
+    pub fn execution_limits(&self) -> ToolExecutionLimits {
+        let (timeout_seconds, max_output_bytes) = match self {
+            ExecutableTool::System(tool, _) => (tool.timeout_seconds, tool.max_output_bytes),
+            ExecutableTool::ExternalApi(tool, _) => (tool.timeout_seconds, tool.max_output_bytes),
+            ExecutableTool::Mcp(tool, _) => (tool.timeout_seconds, tool.max_output_bytes),
+        };
+        ToolExecutionLimits::new(timeout_seconds, max_output_bytes)
+    }
+
+    /// Max number of calls of the tool per hour and per session
+    pub fn call_limits(&self) -> (Option<i32>, Option<i32>) {
+        match self {
+            ExecutableTool::System(tool, _) => {
+                (tool.max_calls_per_hour, tool.max_calls_per_session)
+            }
+            ExecutableTool::ExternalApi(tool, _) => {
+                (tool.max_calls_per_hour, tool.max_calls_per_session)
+            }
+            ExecutableTool::Mcp(tool, _) => (tool.max_calls_per_hour, tool.max_calls_per_session),
+        }
+    }
+}
+
+/// Find the tool used by the tool call, decrypting its secrets and refreshing the access
+/// tokens of OAuth2 credentials. MCP tools using the stdio transport are only allowed if
+/// `allow_mcp_stdio` is set.
+pub async fn find_executable_tool(
+    db: &mut DbConnection,
+    encryptor: &Encryptor,
+    http_client: &reqwest::Client,
+    user_id: &Uuid,
+    tool_call: &ChatRsToolCall,
+    allow_mcp_stdio: bool,
+) -> Result<ExecutableTool, ApiError> {
+    let mut tool_db_service = ToolDbService::new(db);
+    match tool_call.tool_type {
+        LlmToolType::System => {
+            let tool = tool_db_service
+                .find_system_tool_by_id(user_id, &tool_call.tool_id)
+                .await?
+                .ok_or(ToolError::ToolNotFound)?;
+            let secret = match tool.data.secret_id() {
+                Some(secret_id) => {
+                    let s = SecretDbService::new(db)
+                        .find_by_id(user_id, secret_id)
+                        .await?
+                        .ok_or_else(|| ToolError::ToolExecutionError("Secret not found".into()))?;
+                    Some(encryptor.decrypt_string(&s.ciphertext, &s.nonce)?)
+                }
+                None => None,
+            };
+            Ok(ExecutableTool::System(tool, secret))
+        }
+        LlmToolType::ExternalApi => {
+            let (tool, tool_secrets) = tool_db_service
+                .find_accessible_external_api_tool_by_id(user_id, &tool_call.tool_id)
+                .await?
+                .ok_or(ToolError::ToolNotFound)?;
+            let mut secrets = ToolSecrets::new();
+            for (name, secret) in EXTERNAL_API_SECRET_NAMES.into_iter().zip(tool_secrets) {
+                let Some(s) = secret else { continue };
+                let key = match &s.oauth2 {
+                    Some(settings) => {
+                        let access_token =
+                            get_access_token(db, encryptor, http_client, &s, settings).await?;
+                        secrets
+                            .entry(ACCESS_TOKEN_NAME.to_owned())
+                            .or_insert_with(|| access_token.clone());
+                        access_token
+                    }
+                    None => encryptor.decrypt_string(&s.ciphertext, &s.nonce)?,
+                };
+                secrets.insert(name.to_owned(), key);
+            }
+            Ok(ExecutableTool::ExternalApi(tool, secrets))
+        }
+        LlmToolType::Mcp => {
+            let (tool, secret) = tool_db_service
+                .find_accessible_mcp_tool_by_id(user_id, &tool_call.tool_id)
+                .await?
+                .ok_or(ToolError::ToolNotFound)?;
+            tool.data.transport.check_allowed(allow_mcp_stdio)?;
+            let secrets = secret
+                .map(|s| encryptor.decrypt_string(&s.ciphertext, &s.nonce))
+                .transpose()?
+                .into_iter()
+                .collect();
+            Ok(ExecutableTool::Mcp(tool, secrets))
+        }
+    }
+}
+
+/// Output of an executed tool call
+pub struct ToolCallOutput {
+    /// Content of the tool message
+    pub content: String,
+    /// Metadata of the executed tool call
+    pub tool_call: ChatRsExecutedToolCall,
+    pub status: ChatRsToolRunStatus,
+    /// Execution time in milliseconds
+    pub duration_ms: i32,
+}
+
+/// Execute the tool call while sending its output to the given channel. Returns the
+/// content of the tool message, the metadata of the executed tool call, and the status
+/// and duration of the execution.
+pub async fn run_tool_call(
+    tool: ExecutableTool,
+    tool_call: ChatRsToolCall,
+    http_client: &reqwest::Client,
+    tool_storage: &ToolStorage,
+    streaming_tx: tokio::sync::mpsc::Sender<ToolLog>,
+) -> ToolCallOutput {
+    let start = Instant::now();
+    let (log_tx, mut log_rx) = tokio::sync::mpsc::channel(50);
+    let redactor = tool.redactor();
+    let sender_with_logging =
+        SenderWithLogging::new(streaming_tx, log_tx).with_redactor(redactor.clone());
+
+    let log_collector_task = tokio::spawn(async move {
+        let mut logs = None;
+        let mut errors = None;
+        while let Some(chunk) = log_rx.recv().await {
+            match chunk {
+                ToolLog::Log(data) => logs
+                    .get_or_insert_with(|| Vec::with_capacity(20))
+                    .push(data),
+                ToolLog::Error(data) => errors
+                    .get_or_insert_with(|| Vec::with_capacity(5))
+                    .push(data),
+                _ => {}
+            }
+        }
+        (logs, errors)
+    });
+
+    // Execute tool (or use the cached result) and collect logs
+    let cache_ttl = tool.cache_ttl();
+    let cached_result = match cache_ttl {
+        Some(_) => {
+            tool_storage
+                .get_cached_result(
+                    &tool_call.tool_id,
+                    &tool_call.tool_name,
+                    &tool_call.parameters,
+                )
+                .await
+        }
+        None => None,
+    };
+    let is_cached = cached_result.is_some();
+    let tool_result = match cached_result {
+        Some(result) => {
+            let _ = sender_with_logging
+                .send(ToolLog::Log("Using cached result".into()))
+                .await;
+            Ok(result)
+        }
+        None => {
+            let mut result = execute_tool(
+                tool,
+                &tool_call,
+                http_client,
+                tool_storage,
+                &sender_with_logging,
+            )
+            .await;
+            if let Ok((content, _)) = &mut result {
+                redactor.redact(content);
+            }
+            if let (Ok(result), Some(ttl)) = (&result, cache_ttl) {
+                tool_storage
+                    .cache_result(
+                        &tool_call.tool_id,
+                        &tool_call.tool_name,
+                        &tool_call.parameters,
+                        result,
+                        ttl,
+                    )
+                    .await;
+            }
+            result
+        }
+    };
+    let (content, format, is_error) = match tool_result {
+        Ok((response, format)) => (response, format, None),
+        Err(e) => {
+            let mut message = e.to_string();
+            redactor.redact(&mut message);
+            (message, ToolResponseFormat::Text, Some(true))
+        }
+    };
+    let duration_ms = start.elapsed().as_millis().try_into().unwrap_or(i32::MAX);
+    let status = match (is_cached, is_error) {
+        (true, _) => ChatRsToolRunStatus::Cached,
+        (false, Some(true)) => ChatRsToolRunStatus::Error,
+        _ => ChatRsToolRunStatus::Success,
+    };
+    drop(sender_with_logging); // Drop sender to close logging channel
+    let (logs, errors) = log_collector_task.await.unwrap_or_default();
+
+    let executed_tool_call = ChatRsExecutedToolCall {
+        id: tool_call.id,
+        tool_id: tool_call.tool_id,
+        tool_name: tool_call.tool_name,
+        tool_type: tool_call.tool_type,
+        response_format: format,
+        is_error,
+        logs,
+        errors,
+    };
+    ToolCallOutput {
+        content,
+        tool_call: executed_tool_call,
+        status,
+        duration_ms,
+    }
+}
+
+/// Execute the tool call with the tool's executor, if the tool's call limits haven't
+/// been reached
+async fn execute_tool(
+    tool: ExecutableTool,
+    tool_call: &ChatRsToolCall,
+    http_client: &reqwest::Client,
+    tool_storage: &ToolStorage,
+    sender_with_logging: &SenderWithLogging<ToolLog>,
+) -> ToolResult<(String, ToolResponseFormat)> {
+    if let Err(err) = check_call_limits(&tool, tool_call, tool_storage).await {
+        let _ = sender_with_logging
+            .send(ToolLog::Error(err.to_string()))
+            .await;
+        return Err(err);
+    }
+    let limits = tool.execution_limits();
+    match tool {
+        ExecutableTool::System(system_tool, secret) => {
+            system_tool
+                .build_executor(secret.as_deref())
+                .validate_and_execute(
+                    &tool_call.tool_name,
+                    &tool_call.parameters,
+                    tool_storage,
+                    sender_with_logging,
+                    &limits,
+                )
+                .await
+        }
+        ExecutableTool::ExternalApi(api_tool, secrets) => {
+            api_tool
+                .build_executor()
+                .validate_and_execute(
+                    &tool_call.tool_name,
+                    &tool_call.parameters,
+                    &secrets,
+                    http_client,
+                    tool_storage,
+                    sender_with_logging,
+                    &limits,
+                )
+                .await
+        }
+        ExecutableTool::Mcp(mcp_tool, secrets) => {
+            mcp_tool
+                .build_executor()
+                .validate_and_execute(
+                    &tool_call.tool_name,
+                    &tool_call.parameters,
+                    &secrets,
+                    http_client,
+                    sender_with_logging,
+                    &limits,
+                )
+                .await
+        }
+    }
+}
+
+/// Count the call of the tool, and check that its hourly and session limits haven't
+/// been exceeded
+async fn check_call_limits(
+    tool: &ExecutableTool,
+    tool_call: &ChatRsToolCall,
+    tool_storage: &ToolStorage,
+) -> ToolResult<()> {
+    let (max_per_hour, max_per_session) = tool.call_limits();
+    if let Some(max) = max_per_hour.filter(|max| *max > 0) {
+        let calls = tool_storage
+            .increment_hourly_calls(&tool_call.tool_id)
+            .await?;
+        if calls > max as u64 {
+            return Err(ToolError::RateLimited(format!(
+                "this tool can only be called {max} times per hour. Don't call it again until \
+                later."
+            )));
+        }
+    }
+    if let Some(max) = max_per_session.filter(|max| *max > 0) {
+        let calls = tool_storage
+            .increment_session_calls(&tool_call.tool_id)
+            .await?;
+        if calls.is_some_and(|calls| calls > max as u64) {
+            return Err(ToolError::RateLimited(format!(
+                "this tool can only be called {max} times per chat session. Don't call it again \
+                in this session."
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Save the tool message with the result of the tool call, and record the execution in
+/// the tool runs
+pub async fn save_tool_call_output(
+    db: &mut DbConnection,
+    user_id: &Uuid,
+    session_id: &Uuid,
+    output: ToolCallOutput,
+) -> Result<ChatRsMessage, diesel::result::Error> {
+    let run = NewChatRsToolRun {
+        user_id,
+        tool_id: &output.tool_call.tool_id,
+        tool_name: &output.tool_call.tool_name,
+        session_id: Some(session_id),
+        status: output.status.into(),
+        duration_ms: output.duration_ms,
+        response_bytes: output.content.len().try_into().unwrap_or(i32::MAX),
+    };
+    if let Err(err) = ToolRunDbService::new(db).create(run).await {
+        rocket::warn!("Failed to save tool run: {err}");
+    }
+    ChatDbService::new(db)
+        .save_message(new_tool_message(
+            session_id,
+            &output.content,
+            output.tool_call,
+        ))
+        .await
+}
+
+/// Build the tool message saving the result of the tool call
+fn new_tool_message<'a>(
+    session_id: &'a Uuid,
+    content: &'a str,
+    tool_call: ChatRsExecutedToolCall,
+) -> NewChatRsMessage<'a> {
+    NewChatRsMessage {
+        session_id,
+        role: ChatRsMessageRole::Tool,
+        content,
+        meta: ChatRsMessageMeta {
+            tool_call: Some(tool_call),
+            ..Default::default()
+        },
+    }
+}
@@ -1,5 +1,11 @@
+mod browser;
 mod custom_api;
+mod email;
+mod graphql;
 mod web_search;
+mod webhook;
+
+pub use web_search::html::{decode_entities, extract_page_content};
 
 use diesel_as_jsonb::AsJsonb;
 use rocket::async_trait;
@@ -9,7 +15,13 @@ use uuid::Uuid;
 
 use crate::{db::models::ChatRsExternalApiTool, provider::LlmTool, utils::SenderWithLogging};
 
-use super::{ToolError, ToolLog, ToolParameters, ToolResponseFormat, ToolResult};
+use super::{
+    ToolError, ToolExecutionLimits, ToolLog, ToolParameters, ToolResponseFormat, ToolResult,
+    ToolSecrets, ToolStorage,
+};
+
+/// Names of the secrets of external API tools, in order of their columns
+pub const EXTERNAL_API_SECRET_NAMES: [&str; 3] = ["secret_1", "secret_2", "secret_3"];
 
 /// External API tool configuration saved in the database
 #[derive(Debug, Serialize, Deserialize, JsonSchema, AsJsonb)]
@@ -17,6 +29,11 @@ use super::{ToolError, ToolLog, ToolParameters, ToolResponseFormat, ToolResult};
 pub enum ChatRsExternalApiToolConfig {
     CustomApi(custom_api::CustomApiConfig),
     WebSearch(web_search::WebSearchConfig),
+    #[serde(rename = "graphql")]
+    GraphQl(graphql::GraphQlConfig),
+    Browser(browser::BrowserConfig),
+    Email(email::EmailConfig),
+    Webhook(webhook::WebhookConfig),
 }
 impl ChatRsExternalApiToolConfig {
     /// Validate the configuration
@@ -24,6 +41,44 @@ impl ChatRsExternalApiToolConfig {
         match self {
             ChatRsExternalApiToolConfig::CustomApi(config) => config.validate(),
             ChatRsExternalApiToolConfig::WebSearch(config) => config.validate(),
+            ChatRsExternalApiToolConfig::GraphQl(config) => config.validate(),
+            ChatRsExternalApiToolConfig::Browser(config) => config.validate(),
+            ChatRsExternalApiToolConfig::Email(config) => config.validate(),
+            ChatRsExternalApiToolConfig::Webhook(config) => config.validate(),
+        }
+    }
+
+    /// Whether the tool has no side effects, and can be executed when testing the configuration
+    pub fn is_read_only(&self, tool_name: &str) -> bool {
+        match self {
+            ChatRsExternalApiToolConfig::CustomApi(config) => config.is_read_only(tool_name),
+            ChatRsExternalApiToolConfig::WebSearch(_) => true,
+            ChatRsExternalApiToolConfig::GraphQl(config) => config.is_read_only(tool_name),
+            ChatRsExternalApiToolConfig::Browser(_) => true,
+            ChatRsExternalApiToolConfig::Email(_) => false,
+            ChatRsExternalApiToolConfig::Webhook(_) => false,
+        }
+    }
+
+    /// Create the tool executor from the configuration
+    pub fn build_executor(&self) -> Box<dyn ExternalApiTool + '_> {
+        match self {
+            ChatRsExternalApiToolConfig::CustomApi(config) => {
+                Box::new(custom_api::CustomApiTool::new(config))
+            }
+            ChatRsExternalApiToolConfig::WebSearch(config) => {
+                Box::new(web_search::WebSearchTool::new(config))
+            }
+            ChatRsExternalApiToolConfig::GraphQl(config) => {
+                Box::new(graphql::GraphQlTool::new(config))
+            }
+            ChatRsExternalApiToolConfig::Browser(config) => {
+                Box::new(browser::BrowserTool::new(config))
+            }
+            ChatRsExternalApiToolConfig::Email(config) => Box::new(email::EmailTool::new(config)),
+            ChatRsExternalApiToolConfig::Webhook(config) => {
+                Box::new(webhook::WebhookTool::new(config))
+            }
         }
     }
 }
@@ -42,6 +97,8 @@ pub struct ExternalApiToolInput {
 enum ExternalApiToolInputConfig {
     WebSearch(web_search::WebSearchDynamicConfig),
     CustomApi(custom_api::CustomApiDynamicConfig),
+    #[serde(rename = "graphql")]
+    GraphQl(graphql::GraphQlDynamicConfig),
 }
 
 impl ExternalApiToolInput {
@@ -69,6 +126,22 @@ impl ExternalApiToolInput {
                 };
                 web_search_config.get_llm_tools(tool.id, dynamic_config)
             }
+            ChatRsExternalApiToolConfig::GraphQl(graphql_config) => {
+                let dynamic_config = match &self.config {
+                    Some(ExternalApiToolInputConfig::GraphQl(config)) => Some(config),
+                    _ => None,
+                };
+                graphql_config.get_llm_tools(tool.id, dynamic_config)
+            }
+            ChatRsExternalApiToolConfig::Browser(browser_config) => {
+                browser_config.get_llm_tools(tool.id, None)
+            }
+            ChatRsExternalApiToolConfig::Email(email_config) => {
+                email_config.get_llm_tools(tool.id, None)
+            }
+            ChatRsExternalApiToolConfig::Webhook(webhook_config) => {
+                webhook_config.get_llm_tools(tool.id, None)
+            }
         };
         Ok(llm_tools)
     }
@@ -82,25 +155,33 @@ pub trait ExternalApiTool: Send + Sync {
         &self,
         tool_name: &str,
         parameters: &ToolParameters,
-        secrets: &[String],
+        secrets: &ToolSecrets,
         http_client: &reqwest::Client,
+        storage: &ToolStorage,
         sender: &SenderWithLogging<ToolLog>,
     ) -> ToolResult<(String, ToolResponseFormat)>;
 
+    fn validate_parameters(&self, tool_name: &str, parameters: &ToolParameters) -> ToolResult<()> {
+        jsonschema::validate(
+            &self.input_schema(tool_name)?,
+            &serde_json::to_value(parameters)?,
+        )
+        .map_err(|err| ToolError::InvalidParameters(err.to_string()))
+    }
+
     async fn validate_and_execute(
         &self,
         tool_name: &str,
         parameters: &ToolParameters,
-        secrets: &[String],
+        secrets: &ToolSecrets,
         http_client: &reqwest::Client,
+        storage: &ToolStorage,
         sender: &SenderWithLogging<ToolLog>,
+        limits: &ToolExecutionLimits,
     ) -> ToolResult<(String, ToolResponseFormat)> {
-        jsonschema::validate(
-            &self.input_schema(tool_name)?,
-            &serde_json::to_value(parameters)?,
-        )
-        .map_err(|err| ToolError::InvalidParameters(err.to_string()))?;
-        self.execute(tool_name, parameters, secrets, http_client, sender)
+        self.validate_parameters(tool_name, parameters)?;
+        limits
+            .apply(self.execute(tool_name, parameters, secrets, http_client, storage, sender))
             .await
     }
 }
@@ -126,13 +207,6 @@ trait ExternalApiToolConfig {
 impl ChatRsExternalApiTool {
     /// Create the tool executor from the database entity
     pub fn build_executor(&self) -> Box<dyn ExternalApiTool + '_> {
-        match &self.data {
-            ChatRsExternalApiToolConfig::CustomApi(config) => {
-                Box::new(custom_api::CustomApiTool::new(config))
-            }
-            ChatRsExternalApiToolConfig::WebSearch(config) => {
-                Box::new(web_search::WebSearchTool::new(config))
-            }
-        }
+        self.data.build_executor()
     }
 }
@@ -0,0 +1,269 @@
+use std::{sync::LazyLock, time::Duration};
+
+use chromiumoxide::{page::ScreenshotParams, Browser, Page};
+use rocket::{async_trait, futures::StreamExt};
+use schemars::{schema_for, JsonSchema};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    provider::{LlmTool, LlmToolType},
+    utils::SenderWithLogging,
+};
+
+use super::{
+    web_search::html::extract_page_content, ExternalApiTool, ExternalApiToolConfig, ToolError,
+    ToolLog, ToolParameters, ToolResponseFormat, ToolResult, ToolSecrets, ToolStorage,
+};
+
+const CONTENT_NAME: &str = "browser_content";
+const CONTENT_DESC: &str = "Open a URL in a headless browser, and extract the text of the \
+    rendered page. Use this for pages that need JavaScript to render their content.";
+const SCREENSHOT_NAME: &str = "browser_screenshot";
+const SCREENSHOT_DESC: &str = "Open a URL in a headless browser, and take a screenshot of \
+    the rendered page.";
+/// Interval between checks for the element to wait for
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ContentInputSchema {
+    /// The URL of the page
+    url: String,
+    /// CSS selector of an element to wait for before extracting the text, e.g. `#results`
+    wait_for: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ScreenshotInputSchema {
+    /// The URL of the page
+    url: String,
+    /// CSS selector of an element to wait for before taking the screenshot
+    wait_for: Option<String>,
+    /// Capture the full scrollable page instead of only the viewport (default: false)
+    full_page: Option<bool>,
+}
+
+/// What to capture from the rendered page
+enum Capture {
+    Text,
+    Screenshot { full_page: bool },
+}
+
+static CONTENT_INPUT_SCHEMA: LazyLock<serde_json::Value> = LazyLock::new(|| {
+    serde_json::to_value(schema_for!(ContentInputSchema)).expect("Should be valid JSON")
+});
+static SCREENSHOT_INPUT_SCHEMA: LazyLock<serde_json::Value> = LazyLock::new(|| {
+    serde_json::to_value(schema_for!(ScreenshotInputSchema)).expect("Should be valid JSON")
+});
+
+/// Headless browser tool that renders pages in a remote Chromium, using the Chrome
+/// DevTools Protocol (CDP)
+pub struct BrowserTool<'a> {
+    config: &'a BrowserConfig,
+}
+
+/// Saved configuration for the headless browser tool
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct BrowserConfig {
+    /// CDP endpoint of the headless Chromium, e.g. `http://chromium:9222` or a WebSocket
+    /// URL like `ws://browserless:3000?token=${secret_1}`. Can reference the tool's secrets.
+    cdp_url: String,
+    /// Max time to wait for the page to load, in seconds
+    #[serde(default = "default_timeout")]
+    #[validate(range(min = 5, max = 120))]
+    timeout_seconds: u32,
+    /// Max characters of the extracted text. Longer text is truncated.
+    #[serde(default = "default_max_characters")]
+    #[validate(range(min = 500, max = 100_000))]
+    max_characters: u32,
+}
+fn default_timeout() -> u32 {
+    30
+}
+fn default_max_characters() -> u32 {
+    20_000
+}
+
+impl ExternalApiToolConfig for BrowserConfig {
+    type DynamicConfig = ();
+
+    fn get_llm_tools(&self, tool_id: uuid::Uuid, _dynamic_config: Option<&()>) -> Vec<LlmTool> {
+        [
+            (CONTENT_NAME, CONTENT_DESC, &*CONTENT_INPUT_SCHEMA),
+            (SCREENSHOT_NAME, SCREENSHOT_DESC, &*SCREENSHOT_INPUT_SCHEMA),
+        ]
+        .into_iter()
+        .map(|(name, description, input_schema)| LlmTool {
+            tool_id,
+            tool_type: LlmToolType::ExternalApi,
+            name: name.into(),
+            description: description.into(),
+            input_schema: input_schema.to_owned(),
+        })
+        .collect()
+    }
+
+    fn validate(&mut self) -> ToolResult<()> {
+        let config_schema = serde_json::to_value(schema_for!(Self))?;
+        jsonschema::validate(&config_schema, &serde_json::to_value(&*self)?)
+            .map_err(|e| ToolError::InvalidConfiguration(e.to_string()))?;
+        if !["http://", "https://", "ws://", "wss://"]
+            .iter()
+            .any(|scheme| self.cdp_url.starts_with(scheme))
+        {
+            return Err(ToolError::InvalidConfiguration(
+                "CDP URL must be an HTTP or WebSocket URL".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ExternalApiTool for BrowserTool<'_> {
+    fn input_schema(&self, tool_name: &str) -> ToolResult<serde_json::Value> {
+        match tool_name {
+            CONTENT_NAME => Ok(CONTENT_INPUT_SCHEMA.to_owned()),
+            SCREENSHOT_NAME => Ok(SCREENSHOT_INPUT_SCHEMA.to_owned()),
+            _ => Err(ToolError::ToolNotFound),
+        }
+    }
+
+    async fn execute(
+        &self,
+        tool_name: &str,
+        parameters: &ToolParameters,
+        secrets: &ToolSecrets,
+        _http_client: &reqwest::Client,
+        storage: &ToolStorage,
+        tx: &SenderWithLogging<ToolLog>,
+    ) -> ToolResult<(String, ToolResponseFormat)> {
+        let parameters = serde_json::to_value(parameters)?;
+        let (url, wait_for, capture) = match tool_name {
+            CONTENT_NAME => {
+                let input: ContentInputSchema = serde_json::from_value(parameters)?;
+                (input.url, input.wait_for, Capture::Text)
+            }
+            SCREENSHOT_NAME => {
+                let input: ScreenshotInputSchema = serde_json::from_value(parameters)?;
+                let full_page = input.full_page.unwrap_or(false);
+                (input.url, input.wait_for, Capture::Screenshot { full_page })
+            }
+            _ => return Err(ToolError::ToolNotFound),
+        };
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(ToolError::InvalidParameters(
+                "URL must be an HTTP URL".into(),
+            ));
+        }
+
+        let cdp_url = subst::substitute(&self.config.cdp_url, secrets)
+            .map_err(|e| ToolError::FormattingError(format!("CDP URL templating failed: {e}")))?;
+        let _ = tx
+            .send(ToolLog::Log("Connecting to browser...".into()))
+            .await;
+        let (browser, mut handler) = Browser::connect(cdp_url).await.map_err(|e| {
+            ToolError::ToolExecutionError(format!("Failed to connect to browser: {e}"))
+        })?;
+        let handler_task = tokio::spawn(async move {
+            while let Some(event) = handler.next().await {
+                if event.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let result = self
+            .render_page(&browser, &url, wait_for.as_deref(), capture, storage, tx)
+            .await;
+
+        // Only the handler is stopped, as closing the browser would shut down the remote Chromium
+        handler_task.abort();
+        if let Err(err) = &result {
+            let _ = tx.send(ToolLog::Error(err.to_string())).await;
+        }
+        result
+    }
+}
+
+impl<'a> BrowserTool<'a> {
+    pub fn new(config: &'a BrowserConfig) -> Self {
+        Self { config }
+    }
+
+    /// Open the page in a new tab and capture its text or a screenshot, within the timeout.
+    /// The tab is closed afterwards.
+    async fn render_page(
+        &self,
+        browser: &Browser,
+        url: &str,
+        wait_for: Option<&str>,
+        capture: Capture,
+        storage: &ToolStorage,
+        tx: &SenderWithLogging<ToolLog>,
+    ) -> ToolResult<(String, ToolResponseFormat)> {
+        let _ = tx.send(ToolLog::Log(format!("Opening {url}..."))).await;
+        let page = browser
+            .new_page("about:blank")
+            .await
+            .map_err(|e| ToolError::ToolExecutionError(format!("Failed to open page: {e}")))?;
+        let timeout = Duration::from_secs(self.config.timeout_seconds.into());
+        let result = tokio::time::timeout(
+            timeout,
+            self.read_page(&page, url, wait_for, capture, storage, tx),
+        )
+        .await
+        .unwrap_or_else(|_| Err(ToolError::ToolExecutionError("Page load timed out".into())));
+        let _ = page.close().await;
+        result
+    }
+
+    async fn read_page(
+        &self,
+        page: &Page,
+        url: &str,
+        wait_for: Option<&str>,
+        capture: Capture,
+        storage: &ToolStorage,
+        tx: &SenderWithLogging<ToolLog>,
+    ) -> ToolResult<(String, ToolResponseFormat)> {
+        let page_error = |e: chromiumoxide::error::CdpError| {
+            ToolError::ToolExecutionError(format!("Failed to render page: {e}"))
+        };
+        page.goto(url).await.map_err(page_error)?;
+        page.wait_for_navigation().await.map_err(page_error)?;
+        if let Some(selector) = wait_for {
+            let _ = tx
+                .send(ToolLog::Log(format!("Waiting for '{selector}'...")))
+                .await;
+            while page.find_element(selector).await.is_err() {
+                tokio::time::sleep(WAIT_POLL_INTERVAL).await;
+            }
+        }
+
+        if let Capture::Screenshot { full_page } = capture {
+            let _ = tx.send(ToolLog::Log("Taking screenshot...".into())).await;
+            let params = ScreenshotParams::builder().full_page(full_page).build();
+            let data = page.screenshot(params).await.map_err(page_error)?;
+            let format = storage.save_image(&data, "image/png").await?;
+            return Ok((format!("Screenshot of {url} (image/png)"), format));
+        }
+
+        let html = page.content().await.map_err(page_error)?;
+        let mut content = extract_page_content(&html);
+        if let Some((end, _)) = content
+            .text
+            .char_indices()
+            .nth(self.config.max_characters as usize)
+        {
+            content.text.truncate(end);
+        }
+        let final_url = page.url().await.ok().flatten();
+        let _ = tx.send(ToolLog::Log("Success!".into())).await;
+        Ok((
+            content.format(final_url.as_deref().unwrap_or(url)),
+            ToolResponseFormat::Text,
+        ))
+    }
+}
@@ -1,3 +1,6 @@
+mod pagination;
+mod pre_request;
+
 use std::{collections::HashMap, str::FromStr};
 
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
@@ -9,7 +12,7 @@ use subst::VariableMap;
 use crate::{
     provider::{LlmTool, LlmToolType},
     tools::{
-        utils::{validate_json_schema, HttpRequestBuilder},
+        utils::{read_text_response, validate_json_schema, HttpRequestBuilder},
         ToolJsonSchema,
     },
     utils::SenderWithLogging,
@@ -17,9 +20,12 @@ use crate::{
 
 use super::{
     ExternalApiTool, ExternalApiToolConfig, ToolError, ToolLog, ToolParameters, ToolResponseFormat,
-    ToolResult,
+    ToolResult, ToolSecrets, ToolStorage,
 };
 
+use pagination::PaginationConfig;
+use pre_request::PreRequestConfig;
+
 /// Custom API tool that is a collection of HTTP requests
 pub struct CustomApiTool<'a> {
     config: &'a CustomApiConfig,
@@ -45,12 +51,25 @@ pub struct CustomApiDynamicConfig {
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct HttpRequestConfig {
     description: String,
+    #[serde(flatten)]
+    request: HttpRequestTemplate,
+    input_schema: ToolJsonSchema,
+    /// Follow paginated responses and combine the results of multiple pages
+    pagination: Option<PaginationConfig>,
+    /// Request to send first, e.g. to obtain an access token. The values extracted from its
+    /// response can be referenced in this request (e.g. `${token}`).
+    pre_request: Option<PreRequestConfig>,
+}
+
+/// Templated HTTP request. The URL, query, body, and headers can reference the input
+/// parameters (e.g. `${user_id}`) and the tool's secrets (e.g. `${secret_1}`).
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct HttpRequestTemplate {
     url: String,
     method: String,
     query: Option<HashMap<String, String>>,
     body: Option<serde_json::Value>,
     headers: Option<HashMap<String, String>>,
-    input_schema: ToolJsonSchema,
 }
 
 impl ExternalApiToolConfig for CustomApiConfig {
@@ -83,11 +102,30 @@ impl ExternalApiToolConfig for CustomApiConfig {
     fn validate(&mut self) -> ToolResult<()> {
         for (_, config) in self.tools.iter_mut() {
             validate_json_schema(&mut config.input_schema)?;
+            if let Some(pagination) = &config.pagination {
+                pagination.validate()?;
+            }
+            if let Some(pre_request) = &config.pre_request {
+                pre_request.validate()?;
+            }
         }
         Ok(())
     }
 }
 
+impl CustomApiConfig {
+    /// Whether the request only reads data (uses the GET or HEAD method)
+    pub fn is_read_only(&self, tool_name: &str) -> bool {
+        tool_name
+            .strip_prefix(&format!("{}_", self.name))
+            .and_then(|request_name| self.tools.get(request_name))
+            .is_some_and(|request| {
+                let method = request.request.method.to_uppercase();
+                method == "GET" || method == "HEAD"
+            })
+    }
+}
+
 #[async_trait]
 impl ExternalApiTool for CustomApiTool<'_> {
     fn input_schema(&self, tool_name: &str) -> ToolResult<serde_json::Value> {
@@ -103,8 +141,9 @@ impl ExternalApiTool for CustomApiTool<'_> {
         &self,
         tool_name: &str,
         parameters: &ToolParameters,
-        _secrets: &[String],
+        secrets: &ToolSecrets,
         http_client: &reqwest::Client,
+        storage: &ToolStorage,
         tx: &SenderWithLogging<ToolLog>,
     ) -> Result<(String, ToolResponseFormat), ToolError> {
         let request_config = self
@@ -113,21 +152,45 @@ impl ExternalApiTool for CustomApiTool<'_> {
             .get(self.get_request_name(tool_name)?)
             .ok_or(ToolError::ToolNotFound)?;
 
+        // Send the pre-request, and add the extracted values to the parameters
+        let mut parameters = parameters.clone();
+        if let Some(pre_request) = &request_config.pre_request {
+            let _ = tx.send(ToolLog::Log("Sending pre-request...".into())).await;
+            let param_map = ParameterMap(&parameters, secrets);
+            match pre_request.fetch_values(http_client, &param_map).await {
+                Ok(values) => parameters.extend(values),
+                Err(err) => {
+                    let _ = tx.send(ToolLog::Error(err.to_string())).await;
+                    return Err(err);
+                }
+            }
+        }
+
         // Build the HTTP request components
         let _ = tx.send(ToolLog::Log("Building request...".into())).await;
-        let url = request_config.build_url(parameters)?;
-        let headers = request_config.build_headers(parameters)?;
-        let body = request_config.build_body(parameters, &request_config.body)?;
+        let request = &request_config.request;
+        let param_map = ParameterMap(&parameters, secrets);
+        let url = request.build_url(&param_map)?;
+        let headers = request.build_headers(&param_map)?;
+        let body = request.build_body(&param_map, &request.body)?;
 
         // Execute the HTTP request
         let _ = tx.send(ToolLog::Log("Sending request...".into())).await;
-        match self
-            .execute_request(http_client, &request_config.method, &url, headers, body)
-            .await
-        {
+        let result = match &request_config.pagination {
+            Some(pagination) => {
+                pagination
+                    .fetch_pages(http_client, &request.method, &url, headers, body, tx)
+                    .await
+            }
+            None => {
+                self.execute_request(http_client, &request.method, &url, headers, body, storage)
+                    .await
+            }
+        };
+        match result {
             Ok(response) => {
                 let _ = tx.send(ToolLog::Log("Success!".into())).await;
-                Ok((response, ToolResponseFormat::Text))
+                Ok(response)
             }
             Err(err) => {
                 let _ = tx.send(ToolLog::Error(err.to_string())).await;
@@ -155,23 +218,43 @@ impl<'a> CustomApiTool<'a> {
         url: &str,
         headers: reqwest::header::HeaderMap,
         body: Option<String>,
-    ) -> Result<String, ToolError> {
+        storage: &ToolStorage,
+    ) -> Result<(String, ToolResponseFormat), ToolError> {
         let mut request = HttpRequestBuilder::new(method, url).headers(headers);
         if let Some(body_content) = body {
             request = request.body(body_content);
         }
-        let response = request.send(http_client).await?;
-        Ok(response)
+        let response = request.send_raw(http_client).await?;
+
+        // Save image responses to the user's files
+        let image_mime = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(|mime| mime.trim().to_owned())
+            .filter(|mime| mime.starts_with("image/"));
+        if let Some(mime) = image_mime.filter(|_| response.status().is_success()) {
+            let data = response.bytes().await.map_err(|e| {
+                ToolError::ToolExecutionError(format!("Failed to read response: {}", e))
+            })?;
+            let format = storage.save_image(&data, &mime).await?;
+            return Ok((format!("Image received ({mime})"), format));
+        }
+
+        Ok((
+            read_text_response(response).await?,
+            ToolResponseFormat::Text,
+        ))
     }
 }
 
-impl HttpRequestConfig {
-    fn build_url(&self, parameters: &ToolParameters) -> Result<String, ToolError> {
-        let param_map = ParameterMap(parameters);
-        let url = subst::substitute(&self.url, &param_map)
+impl HttpRequestTemplate {
+    fn build_url(&self, param_map: &ParameterMap) -> Result<String, ToolError> {
+        let url = subst::substitute(&self.url, param_map)
             .map_err(|e| ToolError::FormattingError(format!("URL templating failed: {}", e)))?;
 
-        let query_params = self.build_query_params(parameters)?;
+        let query_params = self.build_query_params(param_map)?;
         if !query_params.is_empty() {
             let separator = if url.contains('?') { "&" } else { "?" };
             Ok(format!("{}{}{}", url, separator, query_params))
@@ -180,7 +263,7 @@ impl HttpRequestConfig {
         }
     }
 
-    fn build_headers(&self, parameters: &ToolParameters) -> Result<HeaderMap, ToolError> {
+    fn build_headers(&self, param_map: &ParameterMap) -> Result<HeaderMap, ToolError> {
         let mut headers = HeaderMap::new();
         headers.insert(
             reqwest::header::CONTENT_TYPE,
@@ -188,10 +271,8 @@ impl HttpRequestConfig {
         );
 
         if let Some(header_mapping) = &self.headers {
-            let param_map = ParameterMap(parameters);
-
             for (key, template) in header_mapping {
-                let value = subst::substitute(template, &param_map).map_err(|e| {
+                let value = subst::substitute(template, param_map).map_err(|e| {
                     ToolError::FormattingError(format!("Header templating failed: {}", e))
                 })?;
 
@@ -200,7 +281,7 @@ impl HttpRequestConfig {
                         ToolError::FormattingError(format!("Invalid header name: {}", key))
                     })?;
                     let header_value = HeaderValue::from_str(&value).map_err(|_| {
-                        ToolError::FormattingError(format!("Invalid header value: {}", value))
+                        ToolError::FormattingError(format!("Invalid header value for {}", key))
                     })?;
                     headers.insert(header_name, header_value);
                 }
@@ -212,16 +293,15 @@ impl HttpRequestConfig {
 
     fn build_body(
         &self,
-        parameters: &ToolParameters,
+        param_map: &ParameterMap,
         body_template: &Option<serde_json::Value>,
     ) -> Result<Option<String>, ToolError> {
         if let Some(template) = body_template {
             // First pass: direct value injection for exact parameter matches
-            let mut body = self.apply_direct_injection(template, parameters)?;
+            let mut body = self.apply_direct_injection(template, param_map)?;
 
             // Second pass: string substitution for partial matches
-            let param_map = ParameterMap(parameters);
-            subst::json::substitute_string_values(&mut body, &param_map).map_err(|e| {
+            subst::json::substitute_string_values(&mut body, param_map).map_err(|e| {
                 ToolError::FormattingError(format!("Body templating failed: {}", e))
             })?;
 
@@ -231,14 +311,12 @@ impl HttpRequestConfig {
         }
     }
 
-    fn build_query_params(&self, parameters: &ToolParameters) -> Result<String, ToolError> {
+    fn build_query_params(&self, param_map: &ParameterMap) -> Result<String, ToolError> {
         let mut query_parts = Vec::new();
 
         if let Some(query_mapping) = &self.query {
-            let param_map = ParameterMap(parameters);
-
             for (key, template) in query_mapping {
-                let substituted = subst::substitute(template, &param_map).map_err(|e| {
+                let substituted = subst::substitute(template, param_map).map_err(|e| {
                     ToolError::FormattingError(format!("Query templating failed: {}", e))
                 })?;
 
@@ -258,20 +336,20 @@ impl HttpRequestConfig {
     fn apply_direct_injection(
         &self,
         template: &serde_json::Value,
-        parameters: &ToolParameters,
+        param_map: &ParameterMap,
     ) -> Result<serde_json::Value, ToolError> {
         match template {
             serde_json::Value::Object(obj) => {
                 let mut result = serde_json::Map::new();
                 for (key, value) in obj {
-                    result.insert(key.clone(), self.apply_direct_injection(value, parameters)?);
+                    result.insert(key.clone(), self.apply_direct_injection(value, param_map)?);
                 }
                 Ok(serde_json::Value::Object(result))
             }
             serde_json::Value::Array(arr) => {
                 let mut result = Vec::new();
                 for item in arr {
-                    result.push(self.apply_direct_injection(item, parameters)?);
+                    result.push(self.apply_direct_injection(item, param_map)?);
                 }
                 Ok(serde_json::Value::Array(result))
             }
@@ -282,10 +360,7 @@ impl HttpRequestConfig {
                     name.chars().all(|c| c.is_alphanumeric() || c == '_')
                 }) {
                     // Direct value injection - use the parameter value as-is
-                    Ok(parameters
-                        .get(param_name)
-                        .cloned()
-                        .unwrap_or(serde_json::Value::Null))
+                    Ok(param_map.get_value(param_name))
                 } else {
                     // Keep as string for later substitution by subst
                     Ok(template.clone())
@@ -296,13 +371,28 @@ impl HttpRequestConfig {
     }
 }
 
-/// Wrapper to make our parameters work with subst
-struct ParameterMap<'a>(&'a ToolParameters);
+/// Wrapper to make our parameters and the tool's secrets work with subst. Secrets (e.g.
+/// `${secret_1}`) take precedence over parameters with the same name, and the values extracted
+/// from a pre-request are added to the parameters.
+struct ParameterMap<'a>(&'a ToolParameters, &'a ToolSecrets);
+
+impl ParameterMap<'_> {
+    /// Get the JSON value of a secret or parameter, for direct injection
+    fn get_value(&self, key: &str) -> serde_json::Value {
+        match self.1.get(key) {
+            Some(secret) => serde_json::Value::String(secret.clone()),
+            None => self.0.get(key).cloned().unwrap_or(serde_json::Value::Null),
+        }
+    }
+}
 
 impl<'a> VariableMap<'_> for ParameterMap<'a> {
     type Value = String;
 
     fn get(&self, key: &str) -> Option<Self::Value> {
+        if let Some(secret) = self.1.get(key) {
+            return Some(secret.clone());
+        }
         self.0.get(key).map(|value| match value {
             serde_json::Value::String(s) => s.clone(),
             serde_json::Value::Number(n) => n.to_string(),
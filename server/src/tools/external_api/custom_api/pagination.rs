@@ -0,0 +1,188 @@
+use reqwest::{header::HeaderMap, Url};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    tools::{
+        utils::{read_text_response, HttpRequestBuilder},
+        ToolError, ToolLog, ToolResponseFormat, ToolResult,
+    },
+    utils::SenderWithLogging,
+};
+
+/// Default max number of pages fetched for a tool call
+const DEFAULT_MAX_PAGES: u8 = 5;
+/// Upper limit for the max number of pages fetched for a tool call
+const MAX_PAGES_LIMIT: u8 = 20;
+
+/// Configuration for following paginated responses, so that a tool call aggregates the
+/// results of multiple pages. Responses must be JSON.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PaginationConfig {
+    /// Where to find the next page in the response
+    next: PaginationNext,
+    /// Max number of pages to fetch (default: 5, max: 20)
+    max_pages: Option<u8>,
+    /// JSON pointer to the results array of each page (e.g. `/data/items`). The results
+    /// of all pages are combined into one array. If not set, an array of all the pages
+    /// is returned.
+    results_pointer: Option<String>,
+}
+
+/// Where to find the next page in the response
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PaginationNext {
+    /// URL of the next page in the `Link` header with `rel="next"` (e.g. GitHub)
+    LinkHeader,
+    /// JSON pointer to the URL of the next page in the response body (e.g. `/next`)
+    BodyUrl { pointer: String },
+    /// JSON pointer to the cursor of the next page in the response body (e.g.
+    /// `/meta/next_cursor`). The cursor is sent in the given query parameter.
+    BodyCursor {
+        pointer: String,
+        query_param: String,
+    },
+}
+
+impl PaginationConfig {
+    /// Validate the JSON pointers and max pages
+    pub fn validate(&self) -> ToolResult<()> {
+        let max_pages = self.max_pages.unwrap_or(DEFAULT_MAX_PAGES);
+        if max_pages == 0 || max_pages > MAX_PAGES_LIMIT {
+            return Err(ToolError::InvalidConfiguration(format!(
+                "Max pages must be between 1 and {MAX_PAGES_LIMIT}"
+            )));
+        }
+        let pointers = [
+            match &self.next {
+                PaginationNext::BodyUrl { pointer } => Some(pointer),
+                PaginationNext::BodyCursor { pointer, .. } => Some(pointer),
+                PaginationNext::LinkHeader => None,
+            },
+            self.results_pointer.as_ref(),
+        ];
+        for pointer in pointers.into_iter().flatten() {
+            if !pointer.is_empty() && !pointer.starts_with('/') {
+                return Err(ToolError::InvalidConfiguration(format!(
+                    "Invalid JSON pointer: '{pointer}' (must start with '/')"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Send the request and follow the next pages, returning the combined results as JSON
+    pub async fn fetch_pages(
+        &self,
+        http_client: &reqwest::Client,
+        method: &str,
+        url: &str,
+        headers: HeaderMap,
+        body: Option<String>,
+        tx: &SenderWithLogging<ToolLog>,
+    ) -> ToolResult<(String, ToolResponseFormat)> {
+        let max_pages = self.max_pages.unwrap_or(DEFAULT_MAX_PAGES);
+        let mut results = Vec::new();
+        let mut next_url = Some(parse_url(url)?);
+        let mut page = 1;
+        while let Some(url) = next_url.take() {
+            let _ = tx
+                .send(ToolLog::Log(format!("Fetching page {page}...")))
+                .await;
+            let mut request =
+                HttpRequestBuilder::new(method, url.as_str()).headers(headers.clone());
+            if let Some(body) = &body {
+                request = request.body(body.clone());
+            }
+            let response = request.send_raw(http_client).await?;
+            let link_header = response
+                .headers()
+                .get(reqwest::header::LINK)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned);
+            let text = read_text_response(response).await?;
+            let mut json: serde_json::Value = serde_json::from_str(&text).map_err(|e| {
+                ToolError::ToolExecutionError(format!("Page {page} is not valid JSON: {e}"))
+            })?;
+
+            if page < max_pages {
+                next_url = self.get_next_url(&url, &json, link_header.as_deref())?;
+            }
+            match self.results_pointer.as_deref() {
+                Some(pointer) => match json.pointer_mut(pointer).map(serde_json::Value::take) {
+                    Some(serde_json::Value::Array(items)) => results.extend(items),
+                    Some(item) => results.push(item),
+                    None => {}
+                },
+                None => results.push(json),
+            }
+            page += 1;
+        }
+
+        Ok((serde_json::to_string(&results)?, ToolResponseFormat::Json))
+    }
+
+    fn get_next_url(
+        &self,
+        current_url: &Url,
+        json: &serde_json::Value,
+        link_header: Option<&str>,
+    ) -> ToolResult<Option<Url>> {
+        let next = match &self.next {
+            PaginationNext::LinkHeader => link_header.and_then(parse_next_link),
+            PaginationNext::BodyUrl { pointer } => json
+                .pointer(pointer)
+                .and_then(|value| value.as_str())
+                .filter(|url| !url.is_empty())
+                .map(str::to_owned),
+            PaginationNext::BodyCursor {
+                pointer,
+                query_param,
+            } => {
+                let cursor = match json.pointer(pointer) {
+                    Some(serde_json::Value::String(cursor)) if !cursor.is_empty() => {
+                        cursor.to_owned()
+                    }
+                    Some(serde_json::Value::Number(cursor)) => cursor.to_string(),
+                    _ => return Ok(None),
+                };
+                let mut url = current_url.clone();
+                let query: Vec<(String, String)> = url
+                    .query_pairs()
+                    .filter(|(key, _)| key != query_param)
+                    .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                    .collect();
+                url.query_pairs_mut()
+                    .clear()
+                    .extend_pairs(query)
+                    .append_pair(query_param, &cursor);
+                return Ok(Some(url));
+            }
+        };
+
+        // Resolve relative URLs against the current page
+        next.map(|next| {
+            current_url
+                .join(&next)
+                .map_err(|e| ToolError::ToolExecutionError(format!("Invalid next page URL: {e}")))
+        })
+        .transpose()
+    }
+}
+
+fn parse_url(url: &str) -> ToolResult<Url> {
+    Url::parse(url).map_err(|e| ToolError::FormattingError(format!("Invalid URL: {e}")))
+}
+
+/// Get the URL with `rel="next"` from a `Link` header
+fn parse_next_link(link_header: &str) -> Option<String> {
+    link_header.split(',').find_map(|link| {
+        let (url, params) = link.split_once(';')?;
+        let is_next = params
+            .split(';')
+            .any(|param| matches!(param.trim(), "rel=\"next\"" | "rel=next"));
+        let url = url.trim().strip_prefix('<')?.strip_suffix('>')?;
+        is_next.then(|| url.to_owned())
+    })
+}
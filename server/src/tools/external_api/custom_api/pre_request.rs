@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::tools::{utils::HttpRequestBuilder, ToolError, ToolParameters, ToolResult};
+
+use super::{HttpRequestTemplate, ParameterMap};
+
+/// Request sent before the main request of a tool, e.g. to obtain an access token. The
+/// response must be JSON.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PreRequestConfig {
+    #[serde(flatten)]
+    request: HttpRequestTemplate,
+    /// Values to extract from the response, as a map of variable names to JSON pointers
+    /// (e.g. `{"token": "/access_token"}`). They take precedence over input parameters with
+    /// the same name.
+    extract: HashMap<String, String>,
+}
+
+impl PreRequestConfig {
+    /// Validate the names and JSON pointers of the extracted values
+    pub fn validate(&self) -> ToolResult<()> {
+        if self.extract.is_empty() {
+            return Err(ToolError::InvalidConfiguration(
+                "Pre-request must extract at least one value".into(),
+            ));
+        }
+        for (name, pointer) in &self.extract {
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(ToolError::InvalidConfiguration(format!(
+                    "Invalid extracted value name: '{name}' (must only contain letters, numbers, \
+                    and underscores)"
+                )));
+            }
+            if name.starts_with("secret_") {
+                return Err(ToolError::InvalidConfiguration(format!(
+                    "Extracted value name '{name}' conflicts with the tool's secrets"
+                )));
+            }
+            if !pointer.is_empty() && !pointer.starts_with('/') {
+                return Err(ToolError::InvalidConfiguration(format!(
+                    "Invalid JSON pointer: '{pointer}' (must start with '/')"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Send the request and extract the values from the JSON response
+    pub async fn fetch_values(
+        &self,
+        http_client: &reqwest::Client,
+        param_map: &ParameterMap<'_>,
+    ) -> ToolResult<ToolParameters> {
+        let url = self.request.build_url(param_map)?;
+        let headers = self.request.build_headers(param_map)?;
+        let body = self.request.build_body(param_map, &self.request.body)?;
+        let mut request = HttpRequestBuilder::new(&self.request.method, &url).headers(headers);
+        if let Some(body) = body {
+            request = request.body(body);
+        }
+        let text = request.send(http_client).await?;
+        let json: serde_json::Value = serde_json::from_str(&text).map_err(|e| {
+            ToolError::ToolExecutionError(format!("Pre-request response is not valid JSON: {e}"))
+        })?;
+
+        self.extract
+            .iter()
+            .map(|(name, pointer)| {
+                let value = json
+                    .pointer(pointer)
+                    .filter(|value| !value.is_null())
+                    .ok_or_else(|| {
+                        ToolError::ToolExecutionError(format!(
+                            "Value '{name}' not found in the pre-request response at '{pointer}'"
+                        ))
+                    })?;
+                Ok((name.to_owned(), value.to_owned()))
+            })
+            .collect()
+    }
+}
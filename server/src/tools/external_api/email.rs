@@ -0,0 +1,248 @@
+use std::sync::LazyLock;
+
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use rocket::async_trait;
+use schemars::{schema_for, JsonSchema};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    provider::{LlmTool, LlmToolType},
+    utils::SenderWithLogging,
+};
+
+use super::{
+    ExternalApiTool, ExternalApiToolConfig, ToolError, ToolLog, ToolParameters, ToolResponseFormat,
+    ToolResult, ToolSecrets, ToolStorage,
+};
+
+const SEND_EMAIL_NAME: &str = "send_email";
+const SEND_EMAIL_DESC: &str = "Send a plain text email. Only allowed recipients can receive \
+    emails, and the number of emails per day is limited.";
+/// Max number of recipients of an email
+const MAX_RECIPIENTS: usize = 10;
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct SendEmailInput {
+    /// Email addresses of the recipients
+    to: Vec<String>,
+    /// Subject of the email
+    subject: String,
+    /// Plain text body of the email
+    body: String,
+}
+
+static SEND_EMAIL_INPUT_SCHEMA: LazyLock<serde_json::Value> = LazyLock::new(|| {
+    serde_json::to_value(schema_for!(SendEmailInput)).expect("Should be valid JSON")
+});
+
+/// Email tool that sends emails with an SMTP server
+pub struct EmailTool<'a> {
+    config: &'a EmailConfig,
+}
+
+/// Encryption of the SMTP connection
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpEncryption {
+    /// Implicit TLS (usually port 465)
+    Tls,
+    /// Upgrade to TLS with STARTTLS (usually port 587)
+    #[default]
+    StartTls,
+    /// No encryption, e.g. for a local mail relay
+    None,
+}
+
+/// Saved configuration for the email tool. The SMTP password is stored in `secret_1`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct EmailConfig {
+    /// Hostname of the SMTP server
+    smtp_host: String,
+    /// Port of the SMTP server (default: depends on the encryption)
+    smtp_port: Option<u16>,
+    #[serde(default)]
+    encryption: SmtpEncryption,
+    /// SMTP username. No authentication is used if not set.
+    username: Option<String>,
+    /// Sender of the emails, e.g. `Assistant <assistant@example.com>`
+    from: String,
+    /// Allowed recipients: email addresses (e.g. `me@example.com`) or domains starting with
+    /// `@` (e.g. `@example.com`)
+    #[validate(length(min = 1))]
+    allowed_recipients: Vec<String>,
+    /// Max number of emails sent per day
+    #[serde(default = "default_max_per_day")]
+    #[validate(range(min = 1, max = 1000))]
+    max_per_day: u32,
+}
+fn default_max_per_day() -> u32 {
+    20
+}
+
+impl ExternalApiToolConfig for EmailConfig {
+    type DynamicConfig = ();
+
+    fn get_llm_tools(&self, tool_id: uuid::Uuid, _dynamic_config: Option<&()>) -> Vec<LlmTool> {
+        vec![LlmTool {
+            tool_id,
+            tool_type: LlmToolType::ExternalApi,
+            name: SEND_EMAIL_NAME.into(),
+            description: SEND_EMAIL_DESC.into(),
+            input_schema: SEND_EMAIL_INPUT_SCHEMA.to_owned(),
+        }]
+    }
+
+    fn validate(&mut self) -> ToolResult<()> {
+        let config_schema = serde_json::to_value(schema_for!(Self))?;
+        jsonschema::validate(&config_schema, &serde_json::to_value(&*self)?)
+            .map_err(|e| ToolError::InvalidConfiguration(e.to_string()))?;
+        self.from
+            .parse::<Mailbox>()
+            .map_err(|e| ToolError::InvalidConfiguration(format!("Invalid sender: {e}")))?;
+        for recipient in self.allowed_recipients.iter_mut() {
+            *recipient = recipient.trim().to_lowercase();
+            if recipient.len() < 2 || !recipient.contains('@') {
+                return Err(ToolError::InvalidConfiguration(format!(
+                    "Invalid allowed recipient: '{recipient}'"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl EmailConfig {
+    /// Whether the email address is one of the allowed recipients
+    fn is_allowed(&self, address: &str) -> bool {
+        let address = address.to_lowercase();
+        self.allowed_recipients
+            .iter()
+            .any(|allowed| match allowed.starts_with('@') {
+                true => address.ends_with(allowed.as_str()),
+                false => address == *allowed,
+            })
+    }
+}
+
+#[async_trait]
+impl ExternalApiTool for EmailTool<'_> {
+    fn input_schema(&self, _tool_name: &str) -> ToolResult<serde_json::Value> {
+        Ok(SEND_EMAIL_INPUT_SCHEMA.to_owned())
+    }
+
+    async fn execute(
+        &self,
+        tool_name: &str,
+        parameters: &ToolParameters,
+        secrets: &ToolSecrets,
+        _http_client: &reqwest::Client,
+        storage: &ToolStorage,
+        tx: &SenderWithLogging<ToolLog>,
+    ) -> ToolResult<(String, ToolResponseFormat)> {
+        if tool_name != SEND_EMAIL_NAME {
+            return Err(ToolError::ToolNotFound);
+        }
+        let input: SendEmailInput = serde_json::from_value(serde_json::to_value(parameters)?)?;
+        let message = self.build_message(input)?;
+        let _ = tx
+            .send(ToolLog::Log(format!(
+                "Composed email:\n\n{}",
+                String::from_utf8_lossy(&message.formatted())
+            )))
+            .await;
+
+        let sent_today = storage
+            .increment_daily_usage(&format!("email:{}", self.config.from))
+            .await?;
+        if sent_today > self.config.max_per_day.into() {
+            let message = format!(
+                "Daily limit of {} emails has been reached",
+                self.config.max_per_day
+            );
+            let _ = tx.send(ToolLog::Error(message.clone())).await;
+            return Err(ToolError::ToolExecutionError(message));
+        }
+
+        let _ = tx.send(ToolLog::Log("Sending email...".into())).await;
+        let response = self
+            .build_transport(secrets)?
+            .send(message)
+            .await
+            .map_err(|e| ToolError::ToolExecutionError(format!("Failed to send email: {e}")))?;
+        let _ = tx.send(ToolLog::Log("Success!".into())).await;
+
+        let server_message = response.message().collect::<Vec<_>>().join(" ");
+        Ok((
+            format!("Email sent ({server_message})"),
+            ToolResponseFormat::Text,
+        ))
+    }
+}
+
+impl<'a> EmailTool<'a> {
+    pub fn new(config: &'a EmailConfig) -> Self {
+        Self { config }
+    }
+
+    /// Build the email, checking that all recipients are allowed
+    fn build_message(&self, input: SendEmailInput) -> ToolResult<Message> {
+        if input.to.is_empty() || input.to.len() > MAX_RECIPIENTS {
+            return Err(ToolError::InvalidParameters(format!(
+                "Email must have between 1 and {MAX_RECIPIENTS} recipients"
+            )));
+        }
+        let from = self
+            .config
+            .from
+            .parse::<Mailbox>()
+            .map_err(|e| ToolError::InvalidConfiguration(format!("Invalid sender: {e}")))?;
+        let mut builder = Message::builder()
+            .from(from)
+            .subject(input.subject)
+            .header(ContentType::TEXT_PLAIN);
+        for recipient in &input.to {
+            let mailbox = recipient.parse::<Mailbox>().map_err(|e| {
+                ToolError::InvalidParameters(format!("Invalid recipient '{recipient}': {e}"))
+            })?;
+            if !self.config.is_allowed(&mailbox.email.to_string()) {
+                return Err(ToolError::InvalidParameters(format!(
+                    "Recipient '{recipient}' is not allowed"
+                )));
+            }
+            builder = builder.to(mailbox);
+        }
+
+        builder
+            .body(input.body)
+            .map_err(|e| ToolError::ToolExecutionError(format!("Failed to build email: {e}")))
+    }
+
+    fn build_transport(
+        &self,
+        secrets: &ToolSecrets,
+    ) -> ToolResult<AsyncSmtpTransport<Tokio1Executor>> {
+        let host = self.config.smtp_host.as_str();
+        let mut builder = match self.config.encryption {
+            SmtpEncryption::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host),
+            SmtpEncryption::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host),
+            SmtpEncryption::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(
+                host,
+            )),
+        }
+        .map_err(|e| ToolError::InvalidConfiguration(format!("Invalid SMTP server: {e}")))?;
+        if let Some(port) = self.config.smtp_port {
+            builder = builder.port(port);
+        }
+        if let Some(username) = &self.config.username {
+            let password = secrets.get("secret_1").cloned().unwrap_or_default();
+            builder = builder.credentials(Credentials::new(username.to_owned(), password));
+        }
+
+        Ok(builder.build())
+    }
+}
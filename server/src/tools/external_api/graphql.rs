@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use rocket::async_trait;
+use schemars::{schema_for, JsonSchema};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    provider::{LlmTool, LlmToolType},
+    tools::{
+        utils::{read_text_response, validate_json_schema, HttpRequestBuilder},
+        ToolJsonSchema,
+    },
+    utils::SenderWithLogging,
+};
+
+use super::{
+    ExternalApiTool, ExternalApiToolConfig, ToolError, ToolLog, ToolParameters, ToolResponseFormat,
+    ToolResult, ToolSecrets, ToolStorage,
+};
+
+/// GraphQL API tool that is a collection of queries and mutations
+pub struct GraphQlTool<'a> {
+    config: &'a GraphQlConfig,
+}
+
+/// Saved configuration for the GraphQL API tool
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct GraphQlConfig {
+    /// Name of the GraphQL API tool. Will be prefixed to the operation names.
+    name: String,
+    /// URL of the GraphQL endpoint
+    url: String,
+    /// Headers sent with every request. Values can reference the tool's secrets, e.g.
+    /// `${secret_2}`. If `secret_1` is set, it is sent as a bearer token in the
+    /// `Authorization` header, unless that header is set here.
+    headers: Option<HashMap<String, String>>,
+    /// Map of operation names to their configurations
+    operations: HashMap<String, GraphQlOperationConfig>,
+    /// Max characters of the response. Longer responses are truncated.
+    #[serde(default = "default_max_response_length")]
+    #[validate(range(min = 500, max = 100_000))]
+    max_response_length: u32,
+}
+fn default_max_response_length() -> u32 {
+    20_000
+}
+
+/// Dynamic configuration for the GraphQL API tool
+#[derive(Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct GraphQlDynamicConfig {
+    /// Which operations/tools are enabled
+    enabled: Option<Vec<String>>,
+}
+
+/// Configuration for individual GraphQL queries and mutations
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct GraphQlOperationConfig {
+    description: String,
+    /// The GraphQL document of the query or mutation
+    query: String,
+    /// JSON schema of the variables. The input parameters of the tool are sent as the
+    /// variables of the operation.
+    variables_schema: ToolJsonSchema,
+}
+
+impl GraphQlOperationConfig {
+    /// Whether the operation is a query (as opposed to a mutation or subscription)
+    fn is_query(&self) -> bool {
+        let document = self.query.trim_start();
+        document.starts_with('{') || document.starts_with("query")
+    }
+}
+
+/// GraphQL response body
+#[derive(Debug, Deserialize)]
+struct GraphQlResponse {
+    data: Option<serde_json::Value>,
+    errors: Option<Vec<GraphQlError>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GraphQlError {
+    message: String,
+}
+
+impl ExternalApiToolConfig for GraphQlConfig {
+    type DynamicConfig = GraphQlDynamicConfig;
+
+    fn get_llm_tools(
+        &self,
+        tool_id: uuid::Uuid,
+        input_config: Option<&GraphQlDynamicConfig>,
+    ) -> Vec<LlmTool> {
+        self.operations
+            .iter()
+            .filter(|(name, _)| {
+                input_config
+                    .as_ref()
+                    .and_then(|c| c.enabled.as_ref())
+                    .map_or(true, |enabled| enabled.contains(name))
+            })
+            .map(|(operation_name, config)| LlmTool {
+                tool_id,
+                tool_type: LlmToolType::ExternalApi,
+                name: format!("{}_{operation_name}", self.name),
+                description: config.description.clone(),
+                input_schema: serde_json::to_value(&config.variables_schema)
+                    .expect("Should be valid JSON"),
+            })
+            .collect()
+    }
+
+    fn validate(&mut self) -> ToolResult<()> {
+        let config_schema = serde_json::to_value(schema_for!(Self))?;
+        jsonschema::validate(&config_schema, &serde_json::to_value(&*self)?)
+            .map_err(|e| ToolError::InvalidConfiguration(e.to_string()))?;
+        for (name, config) in self.operations.iter_mut() {
+            if config.query.trim().is_empty() {
+                return Err(ToolError::InvalidConfiguration(format!(
+                    "Operation '{name}' has an empty query"
+                )));
+            }
+            validate_json_schema(&mut config.variables_schema)?;
+        }
+        Ok(())
+    }
+}
+
+impl GraphQlConfig {
+    /// Whether the operation only reads data (is a query)
+    pub fn is_read_only(&self, tool_name: &str) -> bool {
+        tool_name
+            .strip_prefix(&format!("{}_", self.name))
+            .and_then(|operation_name| self.operations.get(operation_name))
+            .is_some_and(GraphQlOperationConfig::is_query)
+    }
+}
+
+#[async_trait]
+impl ExternalApiTool for GraphQlTool<'_> {
+    fn input_schema(&self, tool_name: &str) -> ToolResult<serde_json::Value> {
+        let operation = self.get_operation(tool_name)?;
+        Ok(serde_json::to_value(&operation.variables_schema)?)
+    }
+
+    async fn execute(
+        &self,
+        tool_name: &str,
+        parameters: &ToolParameters,
+        secrets: &ToolSecrets,
+        http_client: &reqwest::Client,
+        _storage: &ToolStorage,
+        tx: &SenderWithLogging<ToolLog>,
+    ) -> Result<(String, ToolResponseFormat), ToolError> {
+        let operation = self.get_operation(tool_name)?;
+        let headers = self.build_headers(secrets)?;
+        let body = serde_json::json!({ "query": operation.query, "variables": parameters });
+
+        let _ = tx.send(ToolLog::Log("Sending request...".into())).await;
+        let response = HttpRequestBuilder::new("POST", &self.config.url)
+            .headers(headers)
+            .body(serde_json::to_string(&body)?)
+            .send_raw(http_client)
+            .await?;
+        let response_text = match read_text_response(response).await {
+            Ok(text) => text,
+            Err(err) => {
+                let _ = tx.send(ToolLog::Error(err.to_string())).await;
+                return Err(err);
+            }
+        };
+        let response: GraphQlResponse = serde_json::from_str(&response_text)
+            .map_err(|e| ToolError::ToolExecutionError(format!("Invalid GraphQL response: {e}")))?;
+
+        // Return the errors along with any partial data
+        let errors = response.errors.filter(|errors| !errors.is_empty());
+        if let Some(errors) = &errors {
+            for error in errors {
+                let _ = tx.send(ToolLog::Error(error.message.clone())).await;
+            }
+        }
+        let output = match (response.data, errors) {
+            (Some(data), None) if !data.is_null() => data,
+            (Some(data), Some(errors)) if !data.is_null() => {
+                serde_json::json!({ "data": data, "errors": errors })
+            }
+            (_, Some(errors)) => {
+                let messages: Vec<String> = errors.into_iter().map(|e| e.message).collect();
+                return Err(ToolError::ToolExecutionError(format!(
+                    "GraphQL errors: {}",
+                    messages.join("; ")
+                )));
+            }
+            (_, None) => serde_json::Value::Null,
+        };
+
+        let _ = tx.send(ToolLog::Log("Success!".into())).await;
+        Ok(self.truncate_response(serde_json::to_string(&output)?))
+    }
+}
+
+impl<'a> GraphQlTool<'a> {
+    pub fn new(config: &'a GraphQlConfig) -> Self {
+        Self { config }
+    }
+
+    fn get_operation(&self, tool_name: &str) -> ToolResult<&GraphQlOperationConfig> {
+        tool_name
+            .strip_prefix(&format!("{}_", self.config.name))
+            .and_then(|operation_name| self.config.operations.get(operation_name))
+            .ok_or(ToolError::ToolNotFound)
+    }
+
+    fn build_headers(&self, secrets: &ToolSecrets) -> ToolResult<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        if let Some(secret) = secrets.get("secret_1") {
+            let header_value = HeaderValue::from_str(&format!("Bearer {secret}"))
+                .map_err(|_| ToolError::FormattingError("Invalid secret key".into()))?;
+            headers.insert(AUTHORIZATION, header_value);
+        }
+        for (key, template) in self.config.headers.iter().flatten() {
+            let header_name = HeaderName::try_from(key)
+                .map_err(|_| ToolError::FormattingError(format!("Invalid header name: {key}")))?;
+            let value = subst::substitute(template, secrets).map_err(|e| {
+                ToolError::FormattingError(format!("Header templating failed: {e}"))
+            })?;
+            let header_value = HeaderValue::from_str(&value).map_err(|_| {
+                ToolError::FormattingError(format!("Invalid header value for {key}"))
+            })?;
+            headers.insert(header_name, header_value);
+        }
+
+        Ok(headers)
+    }
+
+    /// Truncate the response if it's longer than the max length. Truncated responses are
+    /// no longer valid JSON, so they're returned as text.
+    fn truncate_response(&self, response: String) -> (String, ToolResponseFormat) {
+        let max_length = self.config.max_response_length as usize;
+        match response.char_indices().nth(max_length) {
+            Some((end, _)) => {
+                let total_length = response.chars().count();
+                let truncated = format!(
+                    "{}\n... (truncated, {total_length} characters total)",
+                    &response[..end]
+                );
+                (truncated, ToolResponseFormat::Text)
+            }
+            None => (response, ToolResponseFormat::Json),
+        }
+    }
+}
@@ -1,4 +1,10 @@
+mod brave;
+mod crawl;
 mod exa;
+pub(super) mod html;
+mod searxng;
+mod serpapi;
+mod tavily;
 
 use std::sync::LazyLock;
 
@@ -8,20 +14,27 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     provider::{LlmTool, LlmToolType},
+    tools::utils::get_tool_description,
     utils::SenderWithLogging,
 };
 
 use super::{
     ExternalApiTool, ExternalApiToolConfig, ToolError, ToolLog, ToolParameters, ToolResponseFormat,
-    ToolResult,
+    ToolResult, ToolSecrets, ToolStorage,
 };
 
-use exa::ExaSearchTool;
+use {
+    brave::BraveSearchTool, crawl::WebCrawler, exa::ExaSearchTool, searxng::SearxngSearchTool,
+    serpapi::SerpApiSearchTool, tavily::TavilySearchTool,
+};
 
 const WEB_SEARCH_NAME: &str = "web_search";
 const WEB_SEARCH_DESC: &str = "Search the web for a given query.";
 const EXTRACT_NAME: &str = "web_content";
 const EXTRACT_DESC: &str = "Extract content from a given URL.";
+const CRAWL_NAME: &str = "web_crawl";
+const CRAWL_DESC: &str = "Crawl a website from a given URL, following links to pages on the same \
+    site, and get a digest of the text of each page. Useful for summarizing documentation sites.";
 
 #[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
@@ -37,16 +50,33 @@ struct ContentInputSchema {
     url: String,
 }
 
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct CrawlInputSchema {
+    /// The URL to start crawling from
+    url: String,
+    /// How many links deep to follow from the starting URL (limited by the configuration)
+    depth: Option<u8>,
+    /// Max number of pages to crawl (limited by the configuration)
+    max_pages: Option<u8>,
+}
+
 static WEB_SEARCH_INPUT_SCHEMA: LazyLock<serde_json::Value> = LazyLock::new(|| {
     serde_json::to_value(schema_for!(QueryInputSchema)).expect("Should be valid JSON")
 });
 static EXTRACT_INPUT_SCHEMA: LazyLock<serde_json::Value> = LazyLock::new(|| {
     serde_json::to_value(schema_for!(ContentInputSchema)).expect("Should be valid JSON")
 });
+static CRAWL_INPUT_SCHEMA: LazyLock<serde_json::Value> = LazyLock::new(|| {
+    serde_json::to_value(schema_for!(CrawlInputSchema)).expect("Should be valid JSON")
+});
 
 /// A web search tool that can support multiple providers.
 pub struct WebSearchTool {
     provider: Box<dyn WebSearchProvider + Send + Sync>,
+    max_crawl_depth: u8,
+    max_crawl_pages: u8,
+    max_crawl_characters: u32,
 }
 
 /// Saved configuration for the web search tool.
@@ -62,6 +92,32 @@ pub struct WebSearchConfig {
     #[serde(default = "default_max_characters")]
     #[validate(range(min = 500, max = 10_000))]
     max_characters: u32,
+    /// Max number of links to follow from the starting URL when crawling.
+    #[serde(default = "default_max_crawl_depth")]
+    #[validate(range(min = 1, max = 3))]
+    max_crawl_depth: u8,
+    /// Max number of pages to fetch when crawling.
+    #[serde(default = "default_max_crawl_pages")]
+    #[validate(range(min = 1, max = 30))]
+    max_crawl_pages: u8,
+    /// Max characters of the text of all crawled pages.
+    #[serde(default = "default_max_crawl_characters")]
+    #[validate(range(min = 1_000, max = 50_000))]
+    max_crawl_characters: u32,
+    /// Custom description of the search tool for the LLM (uses the default description if not set)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(length(max = 1024))]
+    search_description: Option<String>,
+    /// Custom description of the content extraction tool for the LLM (uses the default
+    /// description if not set)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(length(max = 1024))]
+    extract_description: Option<String>,
+    /// Custom description of the crawling tool for the LLM (uses the default description if
+    /// not set)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(length(max = 1024))]
+    crawl_description: Option<String>,
 }
 fn default_count() -> u8 {
     10
@@ -69,16 +125,39 @@ fn default_count() -> u8 {
 fn default_max_characters() -> u32 {
     5_000
 }
+fn default_max_crawl_depth() -> u8 {
+    2
+}
+fn default_max_crawl_pages() -> u8 {
+    10
+}
+fn default_max_crawl_characters() -> u32 {
+    20_000
+}
 
+/// Web search provider. All providers except SearXNG require an API key as the first secret.
 #[derive(Debug, JsonSchema, Serialize, Deserialize)]
 #[serde(tag = "type", content = "config", rename_all = "snake_case")]
 pub enum WebSearchProviderConfig {
     Exa,
+    Brave,
+    #[serde(rename = "serpapi")]
+    SerpApi,
+    Tavily,
+    /// Self-hosted SearXNG instance, with the JSON format enabled
+    Searxng {
+        /// Base URL of the SearXNG instance
+        url: String,
+    },
 }
 impl WebSearchProviderConfig {
     fn provider_str(&self) -> &'static str {
         match self {
             WebSearchProviderConfig::Exa => "exa",
+            WebSearchProviderConfig::Brave => "brave",
+            WebSearchProviderConfig::SerpApi => "serpapi",
+            WebSearchProviderConfig::Tavily => "tavily",
+            WebSearchProviderConfig::Searxng { .. } => "searxng",
         }
     }
 }
@@ -90,6 +169,9 @@ pub struct WebSearchDynamicConfig {
     search: bool,
     /// Whether content extraction is enabled.
     extract: bool,
+    /// Whether crawling is enabled.
+    #[serde(default)]
+    crawl: bool,
 }
 
 impl<'a> ExternalApiToolConfig for WebSearchConfig {
@@ -100,11 +182,14 @@ impl<'a> ExternalApiToolConfig for WebSearchConfig {
         tool_id: uuid::Uuid,
         input_config: Option<&WebSearchDynamicConfig>,
     ) -> Vec<LlmTool> {
-        let mut llm_tools: Vec<LlmTool> = Vec::with_capacity(2);
+        let mut llm_tools: Vec<LlmTool> = Vec::with_capacity(3);
         if input_config.as_ref().map_or(true, |config| config.search) {
             llm_tools.push(LlmTool {
                 name: format!("{}_{}", self.provider.provider_str(), WEB_SEARCH_NAME),
-                description: WEB_SEARCH_DESC.into(),
+                description: get_tool_description(
+                    self.search_description.as_deref(),
+                    WEB_SEARCH_DESC,
+                ),
                 input_schema: WEB_SEARCH_INPUT_SCHEMA.clone(),
                 tool_id,
                 tool_type: LlmToolType::ExternalApi,
@@ -113,19 +198,39 @@ impl<'a> ExternalApiToolConfig for WebSearchConfig {
         if input_config.as_ref().map_or(true, |config| config.extract) {
             llm_tools.push(LlmTool {
                 name: format!("{}_{}", self.provider.provider_str(), EXTRACT_NAME),
-                description: EXTRACT_DESC.into(),
+                description: get_tool_description(
+                    self.extract_description.as_deref(),
+                    EXTRACT_DESC,
+                ),
                 input_schema: EXTRACT_INPUT_SCHEMA.clone(),
                 tool_id,
                 tool_type: LlmToolType::ExternalApi,
             });
         }
+        if input_config.as_ref().map_or(true, |config| config.crawl) {
+            llm_tools.push(LlmTool {
+                name: format!("{}_{}", self.provider.provider_str(), CRAWL_NAME),
+                description: get_tool_description(self.crawl_description.as_deref(), CRAWL_DESC),
+                input_schema: CRAWL_INPUT_SCHEMA.clone(),
+                tool_id,
+                tool_type: LlmToolType::ExternalApi,
+            });
+        }
         llm_tools
     }
 
     fn validate(&mut self) -> ToolResult<()> {
         let config_schema = serde_json::to_value(schema_for!(Self))?;
-        jsonschema::validate(&config_schema, &serde_json::to_value(self)?)
-            .map_err(|e| ToolError::InvalidConfiguration(e.to_string()))
+        jsonschema::validate(&config_schema, &serde_json::to_value(&*self)?)
+            .map_err(|e| ToolError::InvalidConfiguration(e.to_string()))?;
+        if let WebSearchProviderConfig::Searxng { url } = &self.provider {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(ToolError::InvalidConfiguration(
+                    "SearXNG URL must start with http:// or https://".into(),
+                ));
+            }
+        }
+        Ok(())
     }
 }
 
@@ -135,6 +240,7 @@ impl ExternalApiTool for WebSearchTool {
         match tool_name.split_once('_').ok_or(ToolError::ToolNotFound)?.1 {
             WEB_SEARCH_NAME => Ok(WEB_SEARCH_INPUT_SCHEMA.clone()),
             EXTRACT_NAME => Ok(EXTRACT_INPUT_SCHEMA.clone()),
+            CRAWL_NAME => Ok(CRAWL_INPUT_SCHEMA.clone()),
             _ => Err(ToolError::ToolNotFound),
         }
     }
@@ -143,13 +249,12 @@ impl ExternalApiTool for WebSearchTool {
         &self,
         tool_name: &str,
         parameters: &ToolParameters,
-        secrets: &[String],
+        secrets: &ToolSecrets,
         http_client: &reqwest::Client,
+        _storage: &ToolStorage,
         tx: &SenderWithLogging<ToolLog>,
     ) -> ToolResult<(String, ToolResponseFormat)> {
-        let api_key = secrets
-            .get(0)
-            .ok_or_else(|| ToolError::InvalidConfiguration("Missing API key".into()))?;
+        let api_key = secrets.get("secret_1").map(String::as_str);
         match tool_name.split_once('_').ok_or(ToolError::ToolNotFound)?.1 {
             WEB_SEARCH_NAME => {
                 let query = parameters
@@ -196,6 +301,31 @@ impl ExternalApiTool for WebSearchTool {
                     }
                 }
             }
+            CRAWL_NAME => {
+                let input: CrawlInputSchema =
+                    serde_json::from_value(serde_json::to_value(parameters)?)?;
+                let crawler = WebCrawler::new(
+                    input.depth.map_or(self.max_crawl_depth, |depth| {
+                        depth.min(self.max_crawl_depth)
+                    }),
+                    input.max_pages.map_or(self.max_crawl_pages, |pages| {
+                        pages.clamp(1, self.max_crawl_pages)
+                    }),
+                    self.max_crawl_characters,
+                );
+                match crawler.crawl(input.url.trim(), http_client, tx).await {
+                    Ok(digest) => {
+                        let message = format!("Crawled {} pages", digest.page_count());
+                        let _ = tx.send(ToolLog::Log(message)).await;
+                        Ok((serde_json::to_string(&digest)?, ToolResponseFormat::Json))
+                    }
+                    Err(err) => {
+                        let error_message = format!("Crawl error: {}", err);
+                        let _ = tx.send(ToolLog::Error(error_message)).await;
+                        Err(err)
+                    }
+                }
+            }
             _ => Err(ToolError::ToolNotFound),
         }
     }
@@ -207,8 +337,27 @@ impl WebSearchTool {
             WebSearchProviderConfig::Exa => {
                 Box::new(ExaSearchTool::new(config.count, config.max_characters))
             }
+            WebSearchProviderConfig::Brave => {
+                Box::new(BraveSearchTool::new(config.count, config.max_characters))
+            }
+            WebSearchProviderConfig::SerpApi => {
+                Box::new(SerpApiSearchTool::new(config.count, config.max_characters))
+            }
+            WebSearchProviderConfig::Tavily => {
+                Box::new(TavilySearchTool::new(config.count, config.max_characters))
+            }
+            WebSearchProviderConfig::Searxng { url } => Box::new(SearxngSearchTool::new(
+                url.to_owned(),
+                config.count,
+                config.max_characters,
+            )),
         };
-        Self { provider }
+        Self {
+            provider,
+            max_crawl_depth: config.max_crawl_depth,
+            max_crawl_pages: config.max_crawl_pages,
+            max_crawl_characters: config.max_crawl_characters,
+        }
     }
 }
 
@@ -218,18 +367,23 @@ trait WebSearchProvider {
     async fn search(
         &self,
         query: &str,
-        api_key: &str,
+        api_key: Option<&str>,
         http_client: &reqwest::Client,
     ) -> ToolResult<Vec<WebSearchResult>>;
     async fn extract(
         &self,
         url: &str,
-        api_key: &str,
+        api_key: Option<&str>,
         http_client: &reqwest::Client,
     ) -> ToolResult<String>;
 }
 
-/// Shared search result format
+/// Get the API key, for providers that require it
+fn require_api_key(api_key: Option<&str>) -> ToolResult<&str> {
+    api_key.ok_or_else(|| ToolError::InvalidConfiguration("Missing API key".into()))
+}
+
+/// Shared search result format, which the results of all providers are normalized into
 #[derive(Debug, Serialize)]
 struct WebSearchResult {
     title: String,
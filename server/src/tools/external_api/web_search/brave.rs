@@ -0,0 +1,85 @@
+use rocket::async_trait;
+use serde::Deserialize;
+
+use crate::tools::utils::HttpRequestBuilder;
+
+use super::{
+    html::fetch_page, require_api_key, ToolError, ToolResult, WebSearchProvider, WebSearchResult,
+};
+
+pub struct BraveSearchTool {
+    count: u8,
+    max_characters: u32,
+}
+impl BraveSearchTool {
+    pub fn new(count: u8, max_characters: u32) -> Self {
+        Self {
+            count,
+            max_characters,
+        }
+    }
+}
+#[async_trait]
+impl WebSearchProvider for BraveSearchTool {
+    async fn search(
+        &self,
+        query: &str,
+        api_key: Option<&str>,
+        http_client: &reqwest::Client,
+    ) -> ToolResult<Vec<WebSearchResult>> {
+        let url = format!(
+            "https://api.search.brave.com/res/v1/web/search?q={}&count={}",
+            urlencoding::encode(query),
+            self.count
+        );
+        let response_text = HttpRequestBuilder::new("GET", &url)
+            .header("Accept", "application/json")?
+            .header("X-Subscription-Token", require_api_key(api_key)?)?
+            .send(http_client)
+            .await?;
+        let brave_response: BraveSearchResponse =
+            serde_json::from_str(&response_text).map_err(|e| {
+                ToolError::ToolExecutionError(format!("Failed to parse Brave response: {}", e))
+            })?;
+
+        Ok(brave_response
+            .web
+            .map(|web| web.results)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|result| WebSearchResult {
+                title: result.title,
+                url: result.url,
+                text: result.description.unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    /// Brave doesn't have a content API, so the page is fetched directly
+    async fn extract(
+        &self,
+        url: &str,
+        _api_key: Option<&str>,
+        http_client: &reqwest::Client,
+    ) -> ToolResult<String> {
+        let page = fetch_page(url, self.max_characters, http_client).await?;
+        Ok(page.format(url))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct BraveSearchResponse {
+    web: Option<BraveWebResults>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BraveWebResults {
+    results: Vec<BraveSearchResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BraveSearchResult {
+    title: String,
+    url: String,
+    description: Option<String>,
+}
@@ -0,0 +1,177 @@
+use std::collections::{HashSet, VecDeque};
+
+use reqwest::Url;
+use serde::Serialize;
+
+use crate::utils::SenderWithLogging;
+
+use super::{html::fetch_page, ToolError, ToolLog, ToolResult};
+
+/// Min characters of text for each crawled page
+const MIN_PAGE_CHARACTERS: u32 = 500;
+
+/// Crawls pages linked from a starting URL, staying on the same site
+pub struct WebCrawler {
+    max_depth: u8,
+    max_pages: u8,
+    max_characters: u32,
+}
+
+/// Digest of the crawled pages
+#[derive(Debug, Serialize)]
+pub struct CrawlDigest {
+    pages: Vec<CrawledPage>,
+    /// Number of links found but not crawled because of the depth or page limits
+    skipped_links: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct CrawledPage {
+    url: String,
+    title: Option<String>,
+    /// Number of links followed from the starting URL
+    depth: u8,
+    text: String,
+}
+
+impl WebCrawler {
+    /// Create a new crawler. The max characters are shared by all crawled pages.
+    pub fn new(max_depth: u8, max_pages: u8, max_characters: u32) -> Self {
+        Self {
+            max_depth,
+            max_pages,
+            max_characters,
+        }
+    }
+
+    /// Crawl the pages breadth-first, up to the max depth and number of pages. Pages that
+    /// fail to load are skipped (except the starting page).
+    pub async fn crawl(
+        &self,
+        start_url: &str,
+        http_client: &reqwest::Client,
+        tx: &SenderWithLogging<ToolLog>,
+    ) -> ToolResult<CrawlDigest> {
+        let start_url = Url::parse(start_url)
+            .map_err(|e| ToolError::FormattingError(format!("Invalid URL: {e}")))?;
+        if !matches!(start_url.scheme(), "http" | "https") {
+            return Err(ToolError::FormattingError(
+                "URL must start with http:// or https://".into(),
+            ));
+        }
+        let page_characters =
+            (self.max_characters / self.max_pages.max(1) as u32).max(MIN_PAGE_CHARACTERS);
+
+        let mut pages = Vec::with_capacity(self.max_pages as usize);
+        let mut skipped_links = 0;
+        let mut visited: HashSet<Url> = HashSet::from([start_url.clone()]);
+        let mut queue: VecDeque<(Url, u8)> = VecDeque::from([(start_url.clone(), 0)]);
+        while let Some((url, depth)) = queue.pop_front() {
+            if pages.len() >= self.max_pages as usize {
+                skipped_links += queue.len() + 1;
+                break;
+            }
+            let message = format!("Fetching {url} (depth {depth})...");
+            let _ = tx.send(ToolLog::Log(message)).await;
+            let page = match fetch_page(url.as_str(), page_characters, http_client).await {
+                Ok(page) => page,
+                Err(err) if depth == 0 => return Err(err),
+                Err(err) => {
+                    let _ = tx.send(ToolLog::Error(format!("{url}: {err}"))).await;
+                    continue;
+                }
+            };
+
+            for link in page
+                .links
+                .into_iter()
+                .filter(|link| is_same_site(link, &start_url))
+            {
+                if !visited.insert(link.clone()) {
+                    continue;
+                }
+                if depth >= self.max_depth {
+                    skipped_links += 1;
+                    continue;
+                }
+                queue.push_back((link, depth + 1));
+            }
+            pages.push(CrawledPage {
+                url: url.to_string(),
+                title: page.title,
+                depth,
+                text: page.text,
+            });
+        }
+
+        Ok(CrawlDigest {
+            pages,
+            skipped_links,
+        })
+    }
+}
+
+impl CrawlDigest {
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+}
+
+/// Whether the link is on the same host as the starting URL, and under its path
+fn is_same_site(link: &Url, start_url: &Url) -> bool {
+    let start_dir = match start_url.path().rfind('/') {
+        Some(index) => &start_url.path()[..=index],
+        None => "/",
+    };
+    link.host_str() == start_url.host_str()
+        && link.port_or_known_default() == start_url.port_or_known_default()
+        && link.path().starts_with(start_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_same_site_url(link: &str, start_url: &str) -> bool {
+        is_same_site(&Url::parse(link).unwrap(), &Url::parse(start_url).unwrap())
+    }
+
+    #[test]
+    fn test_is_same_site() {
+        let start_url = "https://example.com/docs/index.html";
+        for link in [
+            "https://example.com/docs/index.html",
+            "https://example.com/docs/guide.html",
+            "https://example.com/docs/api/client.html?page=2",
+            "https://example.com:443/docs/",
+        ] {
+            assert!(is_same_site_url(link, start_url), "{link}");
+        }
+        for link in [
+            "https://example.com/",
+            "https://example.com/blog/post.html",
+            "https://example.com/docs",
+            "https://example.com:8443/docs/guide.html",
+            "http://example.com/docs/guide.html",
+            "https://www.example.com/docs/guide.html",
+            "https://example.org/docs/guide.html",
+        ] {
+            assert!(!is_same_site_url(link, start_url), "{link}");
+        }
+    }
+
+    #[test]
+    fn test_is_same_site_root() {
+        for start_url in ["https://example.com", "https://example.com/"] {
+            assert!(is_same_site_url(
+                "https://example.com/blog/post.html",
+                start_url
+            ));
+            assert!(!is_same_site_url("https://other.com/", start_url));
+        }
+        assert!(is_same_site_url(
+            "https://example.com/docs/guide.html",
+            "https://example.com/docs/"
+        ));
+    }
+}
@@ -3,7 +3,7 @@ use serde::Deserialize;
 
 use crate::tools::utils::HttpRequestBuilder;
 
-use super::{ToolError, ToolResult, WebSearchProvider, WebSearchResult};
+use super::{require_api_key, ToolError, ToolResult, WebSearchProvider, WebSearchResult};
 
 pub struct ExaSearchTool {
     count: u8,
@@ -22,11 +22,11 @@ impl WebSearchProvider for ExaSearchTool {
     async fn search(
         &self,
         query: &str,
-        api_key: &str,
+        api_key: Option<&str>,
         http_client: &reqwest::Client,
     ) -> Result<Vec<WebSearchResult>, ToolError> {
         let builder = HttpRequestBuilder::new("POST", "https://api.exa.ai/search")
-            .header("X-Api-Key", api_key)?
+            .header("X-Api-Key", require_api_key(api_key)?)?
             .body(
                 serde_json::json!({
                     "query": query,
@@ -61,11 +61,11 @@ impl WebSearchProvider for ExaSearchTool {
     async fn extract(
         &self,
         url: &str,
-        api_key: &str,
+        api_key: Option<&str>,
         http_client: &reqwest::Client,
     ) -> ToolResult<String> {
         let builder = HttpRequestBuilder::new("POST", "https://api.exa.ai/contents")
-            .header("X-Api-Key", api_key)?
+            .header("X-Api-Key", require_api_key(api_key)?)?
             .body(
                 serde_json::json!({
                     "ids": [url],
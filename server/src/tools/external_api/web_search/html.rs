@@ -0,0 +1,291 @@
+//! Extraction of readable text and links from HTML pages, for providers without a content
+//! API and for crawling
+
+use reqwest::Url;
+
+use crate::tools::utils::read_text_response;
+
+use super::{ToolError, ToolResult};
+
+/// Elements whose content is never readable text
+const SKIPPED_ELEMENTS: [&str; 10] = [
+    "head", "script", "style", "noscript", "svg", "template", "nav", "header", "footer", "aside",
+];
+/// Elements that start a new line of text
+const BLOCK_ELEMENTS: [&str; 22] = [
+    "p",
+    "div",
+    "br",
+    "li",
+    "ul",
+    "ol",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "tr",
+    "table",
+    "section",
+    "article",
+    "main",
+    "blockquote",
+    "pre",
+    "hr",
+    "dt",
+    "dd",
+];
+
+/// Title, readable text, and links of an HTML page
+pub struct PageContent {
+    pub title: Option<String>,
+    pub text: String,
+    /// Absolute URLs of the links on the page
+    pub links: Vec<Url>,
+}
+
+impl PageContent {
+    /// Format the extracted page for the LLM
+    pub fn format(&self, url: &str) -> String {
+        let title = self.title.as_deref().unwrap_or(url);
+        format!("Title: {title}\nURL: {url}\n\nContent:\n\n{}", self.text)
+    }
+}
+
+/// Fetch a web page and extract its readable text, truncated to the max characters
+pub async fn fetch_page(
+    url: &str,
+    max_characters: u32,
+    http_client: &reqwest::Client,
+) -> ToolResult<PageContent> {
+    let response = http_client
+        .get(url)
+        .header(reqwest::header::ACCEPT, "text/html,text/plain")
+        .send()
+        .await
+        .map_err(|e| ToolError::ToolExecutionError(format!("Failed to fetch page: {e}")))?;
+    let final_url = response.url().clone();
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map_or(true, |value| value.contains("html"));
+    let body = read_text_response(response).await?;
+
+    let mut content = match is_html {
+        true => PageContent {
+            links: extract_links(&body, &final_url),
+            ..extract_page_content(&body)
+        },
+        false => PageContent {
+            title: None,
+            text: body,
+            links: Vec::new(),
+        },
+    };
+    if let Some((end, _)) = content.text.char_indices().nth(max_characters as usize) {
+        content.text.truncate(end);
+    }
+    Ok(content)
+}
+
+/// Extract the title and readable text of an HTML document. Navigation, scripts, etc. are
+/// skipped, and only the main content is used if the page marks it (with `<main>` or
+/// `<article>`).
+pub fn extract_page_content(html: &str) -> PageContent {
+    let title = find_element_text(html, "title").map(|title| decode_entities(title.trim()));
+    let body = ["main", "article"]
+        .into_iter()
+        .find_map(|tag| find_element_html(html, tag))
+        .unwrap_or(html);
+
+    let mut text = String::with_capacity(body.len() / 2);
+    let mut skip_until: Option<String> = None;
+    let mut rest = body;
+    while let Some(start) = rest.find('<') {
+        if skip_until.is_none() {
+            push_text(&mut text, &rest[..start]);
+        }
+        if rest[start..].starts_with("<!--") {
+            rest = rest[start..]
+                .find("-->")
+                .map_or("", |end| &rest[start + end + 3..]);
+            continue;
+        }
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = &rest[start + 1..start + end];
+        rest = &rest[start + end + 1..];
+
+        let is_closing = tag.starts_with('/');
+        let name = tag_name(tag);
+        match &skip_until {
+            Some(skipped) => {
+                if is_closing && name == *skipped {
+                    skip_until = None;
+                }
+            }
+            None if !is_closing
+                && !tag.ends_with('/')
+                && SKIPPED_ELEMENTS.contains(&name.as_str()) =>
+            {
+                skip_until = Some(name);
+            }
+            None if BLOCK_ELEMENTS.contains(&name.as_str()) => text.push('\n'),
+            None => {}
+        }
+    }
+    if skip_until.is_none() {
+        push_text(&mut text, rest);
+    }
+
+    PageContent {
+        title,
+        text: normalize_whitespace(&text),
+        links: Vec::new(),
+    }
+}
+
+/// Extract the absolute URLs (without fragments) of the links in an HTML document
+pub fn extract_links(html: &str, base_url: &Url) -> Vec<Url> {
+    let mut links: Vec<Url> = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = &rest[start + 1..start + end];
+        rest = &rest[start + end + 1..];
+        if tag_name(tag) != "a" {
+            continue;
+        }
+        let Some(href) = find_attribute(tag, "href") else {
+            continue;
+        };
+        let Ok(mut url) = base_url.join(decode_entities(href).trim()) else {
+            continue;
+        };
+        if !matches!(url.scheme(), "http" | "https") {
+            continue;
+        }
+        url.set_fragment(None);
+        if !links.contains(&url) {
+            links.push(url);
+        }
+    }
+    links
+}
+
+/// Lowercase name of the tag, without the brackets, slash, or attributes
+pub fn tag_name(tag: &str) -> String {
+    tag.trim_start_matches('/')
+        .split(|c: char| c.is_whitespace() || c == '/')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// Get the value of an attribute in a tag
+fn find_attribute<'a>(tag: &'a str, attribute: &str) -> Option<&'a str> {
+    let lowercase = tag.to_ascii_lowercase();
+    let mut search_start = 0;
+    while let Some(index) = lowercase[search_start..].find(attribute) {
+        let name_start = search_start + index;
+        let name_end = name_start + attribute.len();
+        search_start = name_end;
+        let is_whole_name = lowercase[..name_start].ends_with(char::is_whitespace);
+        let Some(value) = tag[name_end..].trim_start().strip_prefix('=') else {
+            continue;
+        };
+        if !is_whole_name {
+            continue;
+        }
+        let value = value.trim_start();
+        return match value.chars().next()? {
+            quote @ ('"' | '\'') => value[1..].split(quote).next(),
+            _ => value.split(|c: char| c.is_whitespace()).next(),
+        };
+    }
+    None
+}
+
+/// Decode the common HTML entities
+pub fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_owned();
+    }
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity_end = rest.find(';').filter(|end| *end <= 10);
+        let entity = entity_end.and_then(|end| {
+            let name = &rest[1..end];
+            let char = match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ => match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                    None => name
+                        .strip_prefix('#')
+                        .and_then(|num| num.parse().ok())
+                        .and_then(char::from_u32),
+                },
+            };
+            char.map(|char| (char, end))
+        });
+        match entity {
+            Some((char, end)) => {
+                decoded.push(char);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn push_text(text: &mut String, html_text: &str) {
+    if !html_text.trim().is_empty() {
+        text.push_str(&decode_entities(html_text));
+    } else if !html_text.is_empty() {
+        text.push(' ');
+    }
+}
+
+/// Find the inner HTML of the first element with the given tag
+fn find_element_html<'a>(html: &'a str, tag: &str) -> Option<&'a str> {
+    let lowercase = html.to_ascii_lowercase();
+    let open = lowercase.find(&format!("<{tag}"))?;
+    let content_start = open + lowercase[open..].find('>')? + 1;
+    let content_end = lowercase.rfind(&format!("</{tag}"))?;
+    (content_end > content_start).then(|| &html[content_start..content_end])
+}
+
+fn find_element_text<'a>(html: &'a str, tag: &str) -> Option<&'a str> {
+    let lowercase = html.to_ascii_lowercase();
+    let open = lowercase.find(&format!("<{tag}"))?;
+    let content_start = open + lowercase[open..].find('>')? + 1;
+    let content_end = content_start + lowercase[content_start..].find(&format!("</{tag}"))?;
+    Some(&html[content_start..content_end])
+}
+
+/// Collapse runs of whitespace within lines, and remove blank lines
+fn normalize_whitespace(text: &str) -> String {
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,82 @@
+use rocket::async_trait;
+use serde::Deserialize;
+
+use crate::tools::utils::HttpRequestBuilder;
+
+use super::{html::fetch_page, ToolError, ToolResult, WebSearchProvider, WebSearchResult};
+
+pub struct SearxngSearchTool {
+    url: String,
+    count: u8,
+    max_characters: u32,
+}
+impl SearxngSearchTool {
+    pub fn new(url: String, count: u8, max_characters: u32) -> Self {
+        Self {
+            url,
+            count,
+            max_characters,
+        }
+    }
+}
+#[async_trait]
+impl WebSearchProvider for SearxngSearchTool {
+    async fn search(
+        &self,
+        query: &str,
+        api_key: Option<&str>,
+        http_client: &reqwest::Client,
+    ) -> ToolResult<Vec<WebSearchResult>> {
+        let url = format!(
+            "{}/search?q={}&format=json",
+            self.url.trim_end_matches('/'),
+            urlencoding::encode(query)
+        );
+        let mut builder = HttpRequestBuilder::new("GET", &url);
+        if let Some(api_key) = api_key {
+            builder = builder.header("Authorization", &format!("Bearer {}", api_key))?;
+        }
+        let response_text = builder.send(http_client).await?;
+        let searxng_response: SearxngSearchResponse = serde_json::from_str(&response_text)
+            .map_err(|e| {
+                ToolError::ToolExecutionError(format!(
+                    "Failed to parse SearXNG response (is the JSON format enabled?): {}",
+                    e
+                ))
+            })?;
+
+        Ok(searxng_response
+            .results
+            .into_iter()
+            .take(self.count as usize)
+            .map(|result| WebSearchResult {
+                title: result.title,
+                url: result.url,
+                text: result.content.unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    /// SearXNG doesn't have a content API, so the page is fetched directly
+    async fn extract(
+        &self,
+        url: &str,
+        _api_key: Option<&str>,
+        http_client: &reqwest::Client,
+    ) -> ToolResult<String> {
+        let page = fetch_page(url, self.max_characters, http_client).await?;
+        Ok(page.format(url))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SearxngSearchResponse {
+    results: Vec<SearxngSearchResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearxngSearchResult {
+    title: String,
+    url: String,
+    content: Option<String>,
+}
@@ -0,0 +1,83 @@
+use rocket::async_trait;
+use serde::Deserialize;
+
+use crate::tools::utils::HttpRequestBuilder;
+
+use super::{
+    html::fetch_page, require_api_key, ToolError, ToolResult, WebSearchProvider, WebSearchResult,
+};
+
+pub struct SerpApiSearchTool {
+    count: u8,
+    max_characters: u32,
+}
+impl SerpApiSearchTool {
+    pub fn new(count: u8, max_characters: u32) -> Self {
+        Self {
+            count,
+            max_characters,
+        }
+    }
+}
+#[async_trait]
+impl WebSearchProvider for SerpApiSearchTool {
+    async fn search(
+        &self,
+        query: &str,
+        api_key: Option<&str>,
+        http_client: &reqwest::Client,
+    ) -> ToolResult<Vec<WebSearchResult>> {
+        let url = format!(
+            "https://serpapi.com/search.json?engine=google&q={}&num={}&api_key={}",
+            urlencoding::encode(query),
+            self.count,
+            urlencoding::encode(require_api_key(api_key)?)
+        );
+        let response_text = HttpRequestBuilder::new("GET", &url)
+            .send(http_client)
+            .await?;
+        let serpapi_response: SerpApiSearchResponse = serde_json::from_str(&response_text)
+            .map_err(|e| {
+                ToolError::ToolExecutionError(format!("Failed to parse SerpAPI response: {}", e))
+            })?;
+        if let Some(error) = serpapi_response.error {
+            return Err(ToolError::ToolExecutionError(error));
+        }
+
+        Ok(serpapi_response
+            .organic_results
+            .unwrap_or_default()
+            .into_iter()
+            .take(self.count as usize)
+            .map(|result| WebSearchResult {
+                title: result.title,
+                url: result.link,
+                text: result.snippet.unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    /// SerpAPI doesn't have a content API, so the page is fetched directly
+    async fn extract(
+        &self,
+        url: &str,
+        _api_key: Option<&str>,
+        http_client: &reqwest::Client,
+    ) -> ToolResult<String> {
+        let page = fetch_page(url, self.max_characters, http_client).await?;
+        Ok(page.format(url))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SerpApiSearchResponse {
+    organic_results: Option<Vec<SerpApiSearchResult>>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SerpApiSearchResult {
+    title: String,
+    link: String,
+    snippet: Option<String>,
+}
@@ -0,0 +1,114 @@
+use rocket::async_trait;
+use serde::Deserialize;
+
+use crate::tools::utils::HttpRequestBuilder;
+
+use super::{require_api_key, ToolError, ToolResult, WebSearchProvider, WebSearchResult};
+
+pub struct TavilySearchTool {
+    count: u8,
+    max_characters: u32,
+}
+impl TavilySearchTool {
+    pub fn new(count: u8, max_characters: u32) -> Self {
+        Self {
+            count,
+            max_characters,
+        }
+    }
+}
+#[async_trait]
+impl WebSearchProvider for TavilySearchTool {
+    async fn search(
+        &self,
+        query: &str,
+        api_key: Option<&str>,
+        http_client: &reqwest::Client,
+    ) -> ToolResult<Vec<WebSearchResult>> {
+        let response_text = HttpRequestBuilder::new("POST", "https://api.tavily.com/search")
+            .header(
+                "Authorization",
+                &format!("Bearer {}", require_api_key(api_key)?),
+            )?
+            .body(
+                serde_json::json!({
+                    "query": query,
+                    "max_results": self.count,
+                    "search_depth": "basic",
+                })
+                .to_string(),
+            )
+            .send(http_client)
+            .await?;
+        let tavily_response: TavilySearchResponse =
+            serde_json::from_str(&response_text).map_err(|e| {
+                ToolError::ToolExecutionError(format!("Failed to parse Tavily response: {}", e))
+            })?;
+
+        Ok(tavily_response
+            .results
+            .into_iter()
+            .map(|result| WebSearchResult {
+                title: result.title,
+                url: result.url,
+                text: result.content,
+            })
+            .collect())
+    }
+
+    async fn extract(
+        &self,
+        url: &str,
+        api_key: Option<&str>,
+        http_client: &reqwest::Client,
+    ) -> ToolResult<String> {
+        let response_text = HttpRequestBuilder::new("POST", "https://api.tavily.com/extract")
+            .header(
+                "Authorization",
+                &format!("Bearer {}", require_api_key(api_key)?),
+            )?
+            .body(serde_json::json!({ "urls": [url] }).to_string())
+            .send(http_client)
+            .await?;
+        let tavily_response: TavilyExtractResponse =
+            serde_json::from_str(&response_text).map_err(|e| {
+                ToolError::ToolExecutionError(format!("Failed to parse Tavily response: {}", e))
+            })?;
+        let tavily_result = tavily_response.results.into_iter().next().ok_or_else(|| {
+            ToolError::ToolExecutionError("No result found in Tavily response".to_string())
+        })?;
+
+        let content: String = tavily_result
+            .raw_content
+            .chars()
+            .take(self.max_characters as usize)
+            .collect();
+        Ok(format!(
+            "URL: {}\n\nContent:\n\n{}",
+            tavily_result.url, content
+        ))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TavilySearchResponse {
+    results: Vec<TavilySearchResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TavilySearchResult {
+    title: String,
+    url: String,
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TavilyExtractResponse {
+    results: Vec<TavilyExtractResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TavilyExtractResult {
+    url: String,
+    raw_content: String,
+}
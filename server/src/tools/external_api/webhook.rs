@@ -0,0 +1,152 @@
+use hmac::{Hmac, Mac};
+use rocket::async_trait;
+use schemars::{schema_for, JsonSchema};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{
+    provider::{LlmTool, LlmToolType},
+    tools::{
+        utils::{read_text_response, validate_json_schema, HttpRequestBuilder},
+        ToolJsonSchema,
+    },
+    utils::SenderWithLogging,
+};
+
+use super::{
+    ExternalApiTool, ExternalApiToolConfig, ToolError, ToolLog, ToolParameters, ToolResponseFormat,
+    ToolResult, ToolSecrets, ToolStorage,
+};
+
+/// Max characters of the webhook response returned to the LLM
+const MAX_RESPONSE_LENGTH: usize = 5000;
+
+/// Webhook tool that POSTs the parameters as a signed JSON payload
+pub struct WebhookTool<'a> {
+    config: &'a WebhookConfig,
+}
+
+/// Saved configuration for the webhook tool. The payload is signed with HMAC-SHA256 using
+/// the tool's `secret_1` as the key.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WebhookConfig {
+    /// Name of the tool (letters, numbers, and underscores)
+    name: String,
+    /// Description of what the webhook does, for the LLM
+    description: String,
+    /// URL of the webhook
+    url: String,
+    /// JSON schema of the payload. The input parameters of the tool are sent as the payload.
+    payload_schema: ToolJsonSchema,
+    /// Header with the signature of the payload, formatted as `sha256=<hex digest>`
+    /// (default: `X-Signature-256`)
+    signature_header: Option<String>,
+}
+
+impl ExternalApiToolConfig for WebhookConfig {
+    type DynamicConfig = ();
+
+    fn get_llm_tools(&self, tool_id: uuid::Uuid, _dynamic_config: Option<&()>) -> Vec<LlmTool> {
+        vec![LlmTool {
+            tool_id,
+            tool_type: LlmToolType::ExternalApi,
+            name: self.name.clone(),
+            description: self.description.clone(),
+            input_schema: serde_json::to_value(&self.payload_schema).expect("Should be valid JSON"),
+        }]
+    }
+
+    fn validate(&mut self) -> ToolResult<()> {
+        let config_schema = serde_json::to_value(schema_for!(Self))?;
+        jsonschema::validate(&config_schema, &serde_json::to_value(&*self)?)
+            .map_err(|e| ToolError::InvalidConfiguration(e.to_string()))?;
+        if self.name.is_empty()
+            || !self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(ToolError::InvalidConfiguration(
+                "Name must only contain letters, numbers, and underscores".into(),
+            ));
+        }
+        if !self.url.starts_with("http://") && !self.url.starts_with("https://") {
+            return Err(ToolError::InvalidConfiguration(
+                "URL must be an HTTP URL".into(),
+            ));
+        }
+        validate_json_schema(&mut self.payload_schema)
+    }
+}
+
+#[async_trait]
+impl ExternalApiTool for WebhookTool<'_> {
+    fn input_schema(&self, tool_name: &str) -> ToolResult<serde_json::Value> {
+        if tool_name != self.config.name {
+            return Err(ToolError::ToolNotFound);
+        }
+        Ok(serde_json::to_value(&self.config.payload_schema)?)
+    }
+
+    async fn execute(
+        &self,
+        tool_name: &str,
+        parameters: &ToolParameters,
+        secrets: &ToolSecrets,
+        http_client: &reqwest::Client,
+        _storage: &ToolStorage,
+        tx: &SenderWithLogging<ToolLog>,
+    ) -> ToolResult<(String, ToolResponseFormat)> {
+        if tool_name != self.config.name {
+            return Err(ToolError::ToolNotFound);
+        }
+        let key = secrets.get("secret_1").ok_or_else(|| {
+            ToolError::InvalidConfiguration("Signing secret (secret_1) is not set".into())
+        })?;
+        let payload = serde_json::to_string(parameters)?;
+        let signature = sign_payload(key, &payload)?;
+        let signature_header = self
+            .config
+            .signature_header
+            .as_deref()
+            .unwrap_or("X-Signature-256");
+
+        let _ = tx.send(ToolLog::Log("Triggering webhook...".into())).await;
+        let response = HttpRequestBuilder::new("POST", &self.config.url)
+            .header(signature_header, &format!("sha256={signature}"))?
+            .body(payload)
+            .send_raw(http_client)
+            .await?;
+        let mut response_text = match read_text_response(response).await {
+            Ok(text) => text,
+            Err(err) => {
+                let _ = tx.send(ToolLog::Error(err.to_string())).await;
+                return Err(err);
+            }
+        };
+        if let Some((end, _)) = response_text.char_indices().nth(MAX_RESPONSE_LENGTH) {
+            response_text.truncate(end);
+            response_text.push_str("\n... (truncated)");
+        }
+
+        let _ = tx.send(ToolLog::Log("Success!".into())).await;
+        Ok((
+            format!("Webhook triggered. Response:\n\n{response_text}"),
+            ToolResponseFormat::Text,
+        ))
+    }
+}
+
+impl<'a> WebhookTool<'a> {
+    pub fn new(config: &'a WebhookConfig) -> Self {
+        Self { config }
+    }
+}
+
+/// Hex-encoded HMAC-SHA256 signature of the payload
+fn sign_payload(key: &str, payload: &str) -> ToolResult<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes())
+        .map_err(|e| ToolError::InvalidConfiguration(format!("Invalid signing secret: {e}")))?;
+    mac.update(payload.as_bytes());
+    Ok(hex::encode(mac.finalize().into_bytes()))
+}
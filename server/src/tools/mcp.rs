@@ -0,0 +1,390 @@
+mod client;
+
+use std::collections::HashMap;
+
+use diesel_as_jsonb::AsJsonb;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    db::models::ChatRsMcpTool,
+    provider::{LlmTool, LlmToolType},
+    utils::SenderWithLogging,
+};
+
+use super::{
+    ToolError, ToolExecutionLimits, ToolLog, ToolParameters, ToolResponseFormat, ToolResult,
+};
+
+use client::McpClient;
+
+/// Name of the tool for reading the resources of the MCP server (prefixed by the server name)
+const READ_RESOURCE_TOOL: &str = "read_resource";
+
+/// MCP server tool configuration saved in the database
+#[derive(Debug, Serialize, Deserialize, JsonSchema, AsJsonb)]
+pub struct ChatRsMcpToolConfig {
+    /// Name of the MCP server. Will be prefixed to the tool names.
+    pub name: String,
+    /// How to connect to the MCP server
+    pub transport: McpTransport,
+    /// Tools discovered on the MCP server when it was added
+    #[serde(default)]
+    pub tools: Vec<McpToolDefinition>,
+    /// Resources discovered on the MCP server when it was added
+    #[serde(default)]
+    pub resources: Vec<McpResource>,
+}
+
+/// How to connect to the MCP server
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum McpTransport {
+    /// Command started on the server, communicating over stdin/stdout. Must be enabled
+    /// by the server operator.
+    Stdio {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+        /// Environment variables for the command
+        #[serde(default)]
+        env: HashMap<String, String>,
+        /// Environment variable that is set to the secret key, if given
+        secret_env: Option<String>,
+    },
+    /// Remote server using the streamable HTTP transport. The secret key, if given, is sent
+    /// as a bearer token.
+    Http {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+}
+
+/// A tool of the MCP server
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct McpToolDefinition {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(alias = "inputSchema")]
+    pub input_schema: Value,
+}
+
+/// A resource of the MCP server
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct McpResource {
+    pub uri: String,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(alias = "mimeType")]
+    pub mime_type: Option<String>,
+}
+
+impl McpTransport {
+    /// Check that the server operator allows this transport
+    pub fn check_allowed(&self, allow_stdio: bool) -> ToolResult<()> {
+        match self {
+            McpTransport::Stdio { .. } if !allow_stdio => Err(ToolError::InvalidConfiguration(
+                "MCP servers using the stdio transport are disabled on this server".into(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ChatRsMcpToolConfig {
+    /// Connect to the MCP server and discover its tools and resources
+    pub async fn discover(
+        name: String,
+        transport: McpTransport,
+        secret: Option<&str>,
+        http_client: &reqwest::Client,
+    ) -> ToolResult<Self> {
+        if name.trim().is_empty() {
+            return Err(ToolError::InvalidConfiguration(
+                "MCP server name is empty".into(),
+            ));
+        }
+        let mut client = McpClient::connect(&transport, secret, http_client).await?;
+        let discovered: ToolResult<(Vec<McpToolDefinition>, Vec<McpResource>)> = async {
+            let tools = client.list_tools().await?;
+            let resources = client.list_resources().await?;
+            Ok((tools, resources))
+        }
+        .await;
+        client.close().await;
+
+        let (mut tools, resources) = discovered?;
+        if tools.is_empty() && resources.is_empty() {
+            return Err(ToolError::InvalidConfiguration(
+                "The MCP server has no tools or resources".into(),
+            ));
+        }
+        for tool in tools.iter_mut() {
+            validate_input_schema(tool)?;
+        }
+
+        Ok(Self {
+            name,
+            transport,
+            tools,
+            resources,
+        })
+    }
+
+    /// Whether calls of this tool must be approved before being executed. Reading resources
+    /// doesn't require approval, but the tools of the server may have any side effects.
+    pub fn requires_approval(&self, tool_name: &str) -> bool {
+        self.get_tool_name(tool_name) != Some(READ_RESOURCE_TOOL)
+    }
+
+    /// Get the available LLM tools, optionally only including the enabled tools
+    fn get_llm_tools(&self, tool_id: Uuid, enabled: Option<&Vec<String>>) -> Vec<LlmTool> {
+        let is_enabled =
+            |name: &str| enabled.map_or(true, |enabled| enabled.iter().any(|e| e == name));
+        let mut llm_tools: Vec<LlmTool> = self
+            .tools
+            .iter()
+            .filter(|tool| is_enabled(&tool.name))
+            .map(|tool| LlmTool {
+                tool_id,
+                tool_type: LlmToolType::Mcp,
+                name: format!("{}_{}", self.name, tool.name),
+                description: tool.description.clone().unwrap_or_default(),
+                input_schema: tool.input_schema.clone(),
+            })
+            .collect();
+        if !self.resources.is_empty() && is_enabled(READ_RESOURCE_TOOL) {
+            llm_tools.push(self.get_read_resource_tool(tool_id));
+        }
+
+        llm_tools
+    }
+
+    fn get_read_resource_tool(&self, tool_id: Uuid) -> LlmTool {
+        let mut description = format!(
+            "Read a resource of the {} server. Available resources:",
+            self.name
+        );
+        for resource in &self.resources {
+            description.push_str(&format!("\n- {}: {}", resource.uri, resource.name));
+            if let Some(resource_description) = &resource.description {
+                description.push_str(&format!(" ({resource_description})"));
+            }
+        }
+
+        LlmTool {
+            tool_id,
+            tool_type: LlmToolType::Mcp,
+            name: format!("{}_{READ_RESOURCE_TOOL}", self.name),
+            description,
+            input_schema: self.get_read_resource_schema(),
+        }
+    }
+
+    fn get_read_resource_schema(&self) -> Value {
+        let uris: Vec<&str> = self.resources.iter().map(|r| r.uri.as_str()).collect();
+        json!({
+            "type": "object",
+            "properties": {
+                "uri": { "type": "string", "enum": uris, "description": "URI of the resource" }
+            },
+            "required": ["uri"],
+            "additionalProperties": false,
+        })
+    }
+
+    /// Get the name of the tool on the MCP server from the LLM tool name
+    fn get_tool_name<'a>(&self, tool_name: &'a str) -> Option<&'a str> {
+        tool_name.strip_prefix(&format!("{}_", self.name))
+    }
+}
+
+/// Chat input settings for an MCP tool
+#[derive(Debug, PartialEq, JsonSchema, Serialize, Deserialize)]
+pub struct McpToolInput {
+    /// ID of the MCP tool
+    id: Uuid,
+    /// Which tools of the MCP server are enabled (default: all). Reading resources can be
+    /// enabled with `read_resource`.
+    enabled: Option<Vec<String>>,
+}
+
+impl McpToolInput {
+    /// Get all the LLM tools given the user's input
+    pub fn into_llm_tools(&self, mcp_tools: &[ChatRsMcpTool]) -> ToolResult<Vec<LlmTool>> {
+        let tool = mcp_tools
+            .iter()
+            .find(|tool| tool.id == self.id)
+            .ok_or(ToolError::ToolNotFound)?;
+        Ok(tool.data.get_llm_tools(tool.id, self.enabled.as_ref()))
+    }
+}
+
+/// Executor for the tools of an MCP server. Connects to the server for each tool call.
+pub struct McpTool<'a> {
+    config: &'a ChatRsMcpToolConfig,
+}
+
+impl ChatRsMcpTool {
+    /// Create the tool executor from the database entity
+    pub fn build_executor(&self) -> McpTool<'_> {
+        McpTool { config: &self.data }
+    }
+}
+
+impl McpTool<'_> {
+    fn input_schema(&self, tool_name: &str) -> ToolResult<Value> {
+        let name = self
+            .config
+            .get_tool_name(tool_name)
+            .ok_or(ToolError::ToolNotFound)?;
+        if name == READ_RESOURCE_TOOL && !self.config.resources.is_empty() {
+            return Ok(self.config.get_read_resource_schema());
+        }
+        let tool = self
+            .config
+            .tools
+            .iter()
+            .find(|tool| tool.name == name)
+            .ok_or(ToolError::ToolNotFound)?;
+        Ok(tool.input_schema.clone())
+    }
+
+    /// Validate the parameters, and call the tool on the MCP server while streaming its
+    /// log and progress notifications
+    pub async fn validate_and_execute(
+        &self,
+        tool_name: &str,
+        parameters: &ToolParameters,
+        secrets: &[String],
+        http_client: &reqwest::Client,
+        sender: &SenderWithLogging<ToolLog>,
+        limits: &ToolExecutionLimits,
+    ) -> ToolResult<(String, ToolResponseFormat)> {
+        let arguments = serde_json::to_value(parameters)?;
+        jsonschema::validate(&self.input_schema(tool_name)?, &arguments)
+            .map_err(|err| ToolError::InvalidParameters(err.to_string()))?;
+        let name = self
+            .config
+            .get_tool_name(tool_name)
+            .ok_or(ToolError::ToolNotFound)?;
+
+        let execution = async {
+            let _ = sender
+                .send(ToolLog::Log("Connecting to MCP server...".into()))
+                .await;
+            let secret = secrets.first().map(String::as_str);
+            let mut client =
+                McpClient::connect(&self.config.transport, secret, http_client).await?;
+            let result = match name {
+                READ_RESOURCE_TOOL if !self.config.resources.is_empty() => {
+                    let uri = arguments["uri"].as_str().unwrap_or_default();
+                    let _ = sender
+                        .send(ToolLog::Log(format!("Reading resource {uri}...")))
+                        .await;
+                    client.read_resource(uri).await.map(parse_resource_result)
+                }
+                _ => {
+                    let _ = sender
+                        .send(ToolLog::Log(format!("Calling {name}...")))
+                        .await;
+                    client
+                        .call_tool(name, &arguments, sender)
+                        .await
+                        .and_then(parse_tool_result)
+                }
+            };
+            client.close().await;
+            result
+        };
+        let result = limits.apply(execution).await;
+
+        match result {
+            Ok(response) => {
+                let _ = sender.send(ToolLog::Log("Success!".into())).await;
+                Ok(response)
+            }
+            Err(err) => {
+                let _ = sender.send(ToolLog::Error(err.to_string())).await;
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Ensure the input schema of a discovered tool is a valid object schema.
+/// Also sets `additionalProperties` to false if not set, as required by OpenAI.
+fn validate_input_schema(tool: &mut McpToolDefinition) -> ToolResult<()> {
+    let Some(schema) = tool.input_schema.as_object_mut() else {
+        return Err(ToolError::InvalidJsonSchema(format!(
+            "Input schema of MCP tool '{}' is not an object",
+            tool.name
+        )));
+    };
+    schema
+        .entry("additionalProperties")
+        .or_insert(Value::Bool(false));
+    jsonschema::draft202012::meta::validate(&tool.input_schema)
+        .map_err(|e| ToolError::InvalidJsonSchema(format!("MCP tool '{}': {e}", tool.name)))?;
+    Ok(())
+}
+
+/// Get the response of the tool from the `tools/call` result
+fn parse_tool_result(result: Value) -> ToolResult<(String, ToolResponseFormat)> {
+    let mut texts = Vec::new();
+    for content in result["content"].as_array().into_iter().flatten() {
+        let text = match content["type"].as_str() {
+            Some("text") => content["text"].as_str().map(str::to_owned),
+            Some("resource") => match content["resource"]["text"].as_str() {
+                Some(text) => Some(text.to_owned()),
+                None => Some(format!("[Resource: {}]", content["resource"]["uri"])),
+            },
+            Some("resource_link") => Some(format!("[Resource: {}]", content["uri"])),
+            Some(content_type) => Some(format!(
+                "[{content_type} content: {}]",
+                content["mimeType"].as_str().unwrap_or("unknown type")
+            )),
+            None => None,
+        };
+        texts.extend(text);
+    }
+    let text = texts.join("\n\n");
+    if result["isError"].as_bool().unwrap_or(false) {
+        return Err(ToolError::ToolExecutionError(text));
+    }
+    match result.get("structuredContent") {
+        Some(structured) if text.is_empty() => {
+            Ok((structured.to_string(), ToolResponseFormat::Json))
+        }
+        _ => Ok((text, ToolResponseFormat::Text)),
+    }
+}
+
+/// Get the contents of the resource from the `resources/read` result
+fn parse_resource_result(result: Value) -> (String, ToolResponseFormat) {
+    let contents = result["contents"].as_array().cloned().unwrap_or_default();
+    let format = match contents.as_slice() {
+        [content] => match content["mimeType"].as_str() {
+            Some("application/json") => ToolResponseFormat::Json,
+            Some("text/markdown") => ToolResponseFormat::Markdown,
+            _ => ToolResponseFormat::Text,
+        },
+        _ => ToolResponseFormat::Text,
+    };
+    let text = contents
+        .iter()
+        .map(|content| match content["text"].as_str() {
+            Some(text) => text.to_owned(),
+            None => format!(
+                "[Binary content: {}]",
+                content["mimeType"].as_str().unwrap_or("unknown type")
+            ),
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    (text, format)
+}
@@ -0,0 +1,470 @@
+//! Minimal MCP client, sending JSON-RPC requests over the stdio or streamable HTTP transport
+
+use std::{collections::HashMap, process::Stdio, time::Duration};
+
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE},
+    StatusCode,
+};
+use rocket::futures::{StreamExt, TryStreamExt};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    process::{Child, ChildStdin, ChildStdout, Command},
+};
+use tokio_util::{
+    codec::{FramedRead, LinesCodec},
+    io::StreamReader,
+};
+
+use crate::{
+    tools::{ToolError, ToolLog, ToolResult},
+    utils::SenderWithLogging,
+};
+
+use super::McpTransport;
+
+/// MCP protocol version requested by the client
+const PROTOCOL_VERSION: &str = "2025-06-18";
+/// Timeout for each request to the MCP server
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+/// Maximum number of pages fetched when listing tools or resources
+const MAX_LIST_PAGES: usize = 20;
+
+const SESSION_ID_HEADER: &str = "mcp-session-id";
+const PROTOCOL_VERSION_HEADER: &str = "mcp-protocol-version";
+
+/// A connection to an MCP server
+pub struct McpClient {
+    transport: Transport,
+    next_id: u64,
+    /// Capabilities announced by the server during initialization
+    capabilities: Value,
+}
+
+enum Transport {
+    Stdio {
+        child: Child,
+        stdin: ChildStdin,
+        stdout: Lines<BufReader<ChildStdout>>,
+    },
+    Http(HttpTransport),
+}
+
+struct HttpTransport {
+    http_client: reqwest::Client,
+    url: String,
+    headers: HeaderMap,
+    session_id: Option<String>,
+}
+
+/// A JSON-RPC request, response, or notification received from the server
+#[derive(Debug, Deserialize)]
+struct JsonRpcMessage {
+    id: Option<Value>,
+    method: Option<String>,
+    params: Option<Value>,
+    result: Option<Value>,
+    error: Option<JsonRpcError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonRpcError {
+    code: i64,
+    message: String,
+}
+
+impl McpClient {
+    /// Connect to the MCP server and initialize the session. The secret is passed as a bearer
+    /// token to HTTP servers, and as the configured environment variable to stdio servers.
+    pub async fn connect(
+        transport: &McpTransport,
+        secret: Option<&str>,
+        http_client: &reqwest::Client,
+    ) -> ToolResult<Self> {
+        let transport = match transport {
+            McpTransport::Stdio {
+                command,
+                args,
+                env,
+                secret_env,
+            } => Transport::spawn(command, args, env, secret_env.as_deref().zip(secret))?,
+            McpTransport::Http { url, headers } => {
+                let mut header_map = HeaderMap::with_capacity(headers.len() + 1);
+                for (name, value) in headers {
+                    let name = HeaderName::try_from(name).map_err(|_| {
+                        ToolError::InvalidConfiguration(format!("Invalid header name: {name}"))
+                    })?;
+                    let value = HeaderValue::try_from(value).map_err(|_| {
+                        ToolError::InvalidConfiguration(format!("Invalid value for header {name}"))
+                    })?;
+                    header_map.insert(name, value);
+                }
+                if let Some(secret) = secret {
+                    let value =
+                        HeaderValue::try_from(format!("Bearer {secret}")).map_err(|_| {
+                            ToolError::InvalidConfiguration("Invalid secret key".into())
+                        })?;
+                    header_map.insert(AUTHORIZATION, value);
+                }
+                Transport::Http(HttpTransport {
+                    http_client: http_client.clone(),
+                    url: url.to_owned(),
+                    headers: header_map,
+                    session_id: None,
+                })
+            }
+        };
+
+        let mut client = Self {
+            transport,
+            next_id: 1,
+            capabilities: Value::Null,
+        };
+        let init_params = json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": { "name": "rs-chat", "version": env!("CARGO_PKG_VERSION") },
+        });
+        let init_result = client.request("initialize", init_params, None).await?;
+        client.capabilities = init_result.get("capabilities").cloned().unwrap_or_default();
+        client.notify("notifications/initialized").await?;
+
+        Ok(client)
+    }
+
+    /// List the tools of the server
+    pub async fn list_tools<T: DeserializeOwned>(&mut self) -> ToolResult<Vec<T>> {
+        match self.capabilities.get("tools") {
+            Some(_) => self.list("tools/list", "tools").await,
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// List the resources of the server
+    pub async fn list_resources<T: DeserializeOwned>(&mut self) -> ToolResult<Vec<T>> {
+        match self.capabilities.get("resources") {
+            Some(_) => self.list("resources/list", "resources").await,
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Call a tool, forwarding the server's log and progress notifications to the sender
+    pub async fn call_tool(
+        &mut self,
+        name: &str,
+        arguments: &Value,
+        sender: &SenderWithLogging<ToolLog>,
+    ) -> ToolResult<Value> {
+        let params = json!({
+            "name": name,
+            "arguments": arguments,
+            "_meta": { "progressToken": self.next_id },
+        });
+        self.request("tools/call", params, Some(sender)).await
+    }
+
+    /// Read a resource of the server
+    pub async fn read_resource(&mut self, uri: &str) -> ToolResult<Value> {
+        self.request("resources/read", json!({ "uri": uri }), None)
+            .await
+    }
+
+    /// Close the connection, stopping the server process or ending the HTTP session
+    pub async fn close(self) {
+        match self.transport {
+            Transport::Stdio {
+                mut child, stdin, ..
+            } => {
+                drop(stdin);
+                let _ = child.kill().await;
+            }
+            Transport::Http(http) => {
+                if let Some(session_id) = http.session_id {
+                    let _ = http
+                        .http_client
+                        .delete(http.url)
+                        .headers(http.headers)
+                        .header(SESSION_ID_HEADER, session_id)
+                        .send()
+                        .await;
+                }
+            }
+        }
+    }
+
+    async fn list<T: DeserializeOwned>(&mut self, method: &str, key: &str) -> ToolResult<Vec<T>> {
+        let mut items = Vec::new();
+        let mut cursor: Option<String> = None;
+        for _ in 0..MAX_LIST_PAGES {
+            let params = match cursor.take() {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let mut result = self.request(method, params, None).await?;
+            if let Some(page) = result.get_mut(key).map(Value::take) {
+                items.extend(serde_json::from_value::<Vec<T>>(page)?);
+            }
+            cursor = result
+                .get("nextCursor")
+                .and_then(Value::as_str)
+                .map(str::to_owned);
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        Ok(items)
+    }
+
+    async fn request(
+        &mut self,
+        method: &str,
+        params: Value,
+        sender: Option<&SenderWithLogging<ToolLog>>,
+    ) -> ToolResult<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        let response = tokio::time::timeout(
+            REQUEST_TIMEOUT,
+            self.transport.send_request(id, &message, sender),
+        )
+        .await
+        .map_err(|_| ToolError::ToolExecutionError(format!("MCP request timed out: {method}")))??;
+
+        match response.error {
+            Some(error) => Err(ToolError::ToolExecutionError(format!(
+                "MCP server error ({}): {}",
+                error.code, error.message
+            ))),
+            None => Ok(response.result.unwrap_or_default()),
+        }
+    }
+
+    async fn notify(&mut self, method: &str) -> ToolResult<()> {
+        let message = json!({ "jsonrpc": "2.0", "method": method });
+        self.transport.send_notification(&message).await
+    }
+}
+
+impl Transport {
+    fn spawn(
+        command: &str,
+        args: &[String],
+        env: &HashMap<String, String>,
+        secret_env: Option<(&str, &str)>,
+    ) -> ToolResult<Self> {
+        // Don't leak the server's environment (database URL, secret key, etc.) to the command
+        let mut command = Command::new(command);
+        command
+            .args(args)
+            .env_clear()
+            .envs(std::env::var_os("PATH").map(|path| ("PATH", path)))
+            .envs(env)
+            .envs(secret_env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+        let mut child = command.spawn().map_err(|e| {
+            ToolError::ToolExecutionError(format!("Failed to start MCP server: {e}"))
+        })?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(ToolError::ToolExecutionError(
+                "Failed to connect to MCP server process".into(),
+            ));
+        };
+
+        Ok(Transport::Stdio {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout).lines(),
+        })
+    }
+
+    async fn send_request(
+        &mut self,
+        id: u64,
+        message: &Value,
+        sender: Option<&SenderWithLogging<ToolLog>>,
+    ) -> ToolResult<JsonRpcMessage> {
+        match self {
+            Transport::Stdio { stdin, stdout, .. } => {
+                write_line(stdin, message).await?;
+                while let Some(line) = stdout.next_line().await? {
+                    let Ok(message) = serde_json::from_str::<JsonRpcMessage>(&line) else {
+                        continue; // Ignore any non-JSON output
+                    };
+                    if message.method.is_none() && is_response_to(&message, id) {
+                        return Ok(message);
+                    }
+                    if let Some(response) = handle_server_message(message, sender).await {
+                        write_line(stdin, &response).await?;
+                    }
+                }
+                Err(ToolError::ToolExecutionError(
+                    "MCP server process exited".into(),
+                ))
+            }
+            Transport::Http(http) => http.send_request(id, message, sender).await,
+        }
+    }
+
+    async fn send_notification(&mut self, message: &Value) -> ToolResult<()> {
+        match self {
+            Transport::Stdio { stdin, .. } => write_line(stdin, message).await,
+            Transport::Http(http) => http.post(message).await.map(|_| ()),
+        }
+    }
+}
+
+impl HttpTransport {
+    async fn send_request(
+        &mut self,
+        id: u64,
+        message: &Value,
+        sender: Option<&SenderWithLogging<ToolLog>>,
+    ) -> ToolResult<JsonRpcMessage> {
+        let response = self.post(message).await?;
+        let is_event_stream = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+        if !is_event_stream {
+            return response
+                .json()
+                .await
+                .map_err(|e| ToolError::ToolExecutionError(format!("Invalid MCP response: {e}")));
+        }
+
+        let stream_reader =
+            StreamReader::new(response.bytes_stream().map_err(std::io::Error::other));
+        let mut lines = FramedRead::new(stream_reader, LinesCodec::new());
+        let mut data = String::new();
+        while let Some(line) = lines.next().await {
+            let line = line.map_err(|e| ToolError::ToolExecutionError(e.to_string()))?;
+            if let Some(chunk) = line.strip_prefix("data:") {
+                data.push_str(chunk.strip_prefix(' ').unwrap_or(chunk));
+                continue;
+            }
+            if !line.is_empty() || data.is_empty() {
+                continue; // Wait for the end of the event
+            }
+            if let Ok(message) = serde_json::from_str::<JsonRpcMessage>(&data) {
+                if message.method.is_none() && is_response_to(&message, id) {
+                    return Ok(message);
+                }
+                // Server requests can't be answered within this stream
+                handle_server_message(message, sender).await;
+            }
+            data.clear();
+        }
+        Err(ToolError::ToolExecutionError(
+            "MCP server closed the stream without a response".into(),
+        ))
+    }
+
+    /// Send a message to the HTTP server, keeping track of the session ID
+    async fn post(&mut self, message: &Value) -> ToolResult<reqwest::Response> {
+        let mut request = self
+            .http_client
+            .post(self.url.as_str())
+            .headers(self.headers.clone())
+            .header(ACCEPT, "application/json, text/event-stream")
+            .header(PROTOCOL_VERSION_HEADER, PROTOCOL_VERSION)
+            .json(message);
+        if let Some(session_id) = self.session_id.as_deref() {
+            request = request.header(SESSION_ID_HEADER, session_id);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ToolError::ToolExecutionError(format!("MCP request failed: {e}")))?;
+        if response.status() == StatusCode::NOT_FOUND && self.session_id.is_some() {
+            return Err(ToolError::ToolExecutionError("MCP session expired".into()));
+        }
+        if !response.status().is_success() {
+            return Err(ToolError::ToolExecutionError(format!(
+                "MCP server responded with status {}",
+                response.status()
+            )));
+        }
+        if let Some(new_session_id) = response
+            .headers()
+            .get(SESSION_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+        {
+            self.session_id = Some(new_session_id.to_owned());
+        }
+
+        Ok(response)
+    }
+}
+
+async fn write_line(stdin: &mut ChildStdin, message: &Value) -> ToolResult<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    stdin.write_all(line.as_bytes()).await?;
+    stdin.flush().await?;
+    Ok(())
+}
+
+fn is_response_to(message: &JsonRpcMessage, id: u64) -> bool {
+    message.id.as_ref().and_then(Value::as_u64) == Some(id)
+}
+
+/// Handle a notification or request sent by the server. Log and progress notifications are
+/// forwarded to the sender. Returns the response to send back, if the message is a request.
+async fn handle_server_message(
+    message: JsonRpcMessage,
+    sender: Option<&SenderWithLogging<ToolLog>>,
+) -> Option<Value> {
+    let method = message.method.as_deref()?;
+    if let Some(id) = message.id {
+        // Sampling, elicitation, etc. aren't supported
+        return Some(match method {
+            "ping" => json!({ "jsonrpc": "2.0", "id": id, "result": {} }),
+            _ => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32601, "message": "Method not supported by client" },
+            }),
+        });
+    }
+
+    let (Some(sender), Some(params)) = (sender, message.params) else {
+        return None;
+    };
+    let log = match method {
+        "notifications/message" => {
+            let data = match params.get("data") {
+                Some(Value::String(text)) => text.to_owned(),
+                Some(data) => data.to_string(),
+                None => return None,
+            };
+            match params.get("level").and_then(Value::as_str) {
+                Some("error" | "critical" | "alert" | "emergency") => ToolLog::Error(data),
+                Some("debug") => ToolLog::Debug(data),
+                _ => ToolLog::Log(data),
+            }
+        }
+        "notifications/progress" => {
+            let progress = params.get("progress").and_then(Value::as_f64)?;
+            let mut log = match params.get("total").and_then(Value::as_f64) {
+                Some(total) => format!("Progress: {progress}/{total}"),
+                None => format!("Progress: {progress}"),
+            };
+            if let Some(message) = params.get("message").and_then(Value::as_str) {
+                log.push_str(&format!(" ({message})"));
+            }
+            ToolLog::Log(log)
+        }
+        _ => return None,
+    };
+    let _ = sender.send(log).await;
+
+    None
+}
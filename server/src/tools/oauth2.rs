@@ -0,0 +1,171 @@
+//! OAuth2 credentials for tool secrets, with automatic refreshing of access tokens
+
+use chrono::{DateTime, Duration, Utc};
+use diesel_as_jsonb::AsJsonb;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{
+        models::{ChatRsSecret, UpdateChatRsSecret},
+        services::SecretDbService,
+        DbConnection,
+    },
+    errors::ApiError,
+    tools::{ToolError, ToolResult},
+    utils::Encryptor,
+};
+
+/// Access tokens expiring within this many seconds are refreshed
+const EXPIRY_MARGIN_SECS: i64 = 60;
+/// Assumed lifetime of access tokens if the token endpoint doesn't specify it
+const DEFAULT_EXPIRES_IN_SECS: i64 = 3600;
+
+/// Settings of an OAuth2 credential, saved unencrypted along with the secret
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, AsJsonb)]
+pub struct OAuth2Settings {
+    /// URL of the token endpoint (e.g. `https://oauth2.googleapis.com/token`)
+    pub token_url: String,
+    pub client_id: String,
+    /// Space-separated scopes to request when refreshing the access token
+    pub scope: Option<String>,
+    /// When the current access token expires
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Input to manage a secret as an OAuth2 credential
+#[derive(Debug, JsonSchema, Deserialize)]
+pub struct OAuth2SecretInput {
+    /// URL of the token endpoint (e.g. `https://oauth2.googleapis.com/token`)
+    pub token_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Space-separated scopes to request when refreshing the access token
+    pub scope: Option<String>,
+}
+
+/// Tokens of an OAuth2 credential, saved as the encrypted key of the secret
+#[derive(Serialize, Deserialize)]
+struct OAuth2Tokens {
+    client_secret: String,
+    refresh_token: String,
+    access_token: Option<String>,
+}
+
+/// Response of the token endpoint
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<i64>,
+    /// New refresh token, if the provider rotates refresh tokens
+    refresh_token: Option<String>,
+}
+
+impl OAuth2SecretInput {
+    /// Get the settings and the plaintext key of the secret to encrypt
+    pub fn to_secret(&self, refresh_token: &str) -> ToolResult<(OAuth2Settings, String)> {
+        let tokens = OAuth2Tokens {
+            client_secret: self.client_secret.clone(),
+            refresh_token: refresh_token.to_owned(),
+            access_token: None,
+        };
+        let settings = OAuth2Settings {
+            token_url: self.token_url.clone(),
+            client_id: self.client_id.clone(),
+            scope: self.scope.clone(),
+            expires_at: None,
+        };
+        Ok((settings, serde_json::to_string(&tokens)?))
+    }
+}
+
+/// Get a valid access token of the OAuth2 credential, refreshing it (and saving the new
+/// tokens) if it's expired or about to expire
+pub async fn get_access_token(
+    db: &mut DbConnection,
+    encryptor: &Encryptor,
+    http_client: &reqwest::Client,
+    secret: &ChatRsSecret,
+    settings: &OAuth2Settings,
+) -> Result<String, ApiError> {
+    let json = encryptor.decrypt_string(&secret.ciphertext, &secret.nonce)?;
+    let mut tokens: OAuth2Tokens = serde_json::from_str(&json)
+        .map_err(|_| ToolError::OAuth2Error("Invalid saved credential".into()))?;
+    let is_valid = settings
+        .expires_at
+        .is_some_and(|expires_at| expires_at > Utc::now() + Duration::seconds(EXPIRY_MARGIN_SECS));
+    if let Some(access_token) = tokens.access_token.as_ref().filter(|_| is_valid) {
+        return Ok(access_token.to_owned());
+    }
+
+    let response = refresh_access_token(http_client, settings, &tokens).await?;
+    let expires_in = response.expires_in.unwrap_or(DEFAULT_EXPIRES_IN_SECS);
+    let new_settings = OAuth2Settings {
+        expires_at: Some(Utc::now() + Duration::seconds(expires_in)),
+        ..settings.clone()
+    };
+    if let Some(refresh_token) = response.refresh_token {
+        tokens.refresh_token = refresh_token;
+    }
+    tokens.access_token = Some(response.access_token.clone());
+
+    let (ciphertext, nonce) =
+        encryptor.encrypt_string(&serde_json::to_string(&tokens).map_err(ToolError::from)?)?;
+    SecretDbService::new(db)
+        .update(
+            &secret.user_id,
+            &secret.id,
+            UpdateChatRsSecret {
+                ciphertext: Some(&ciphertext),
+                nonce: Some(&nonce),
+                oauth2: Some(Some(&new_settings)),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    Ok(response.access_token)
+}
+
+async fn refresh_access_token(
+    http_client: &reqwest::Client,
+    settings: &OAuth2Settings,
+    tokens: &OAuth2Tokens,
+) -> ToolResult<TokenResponse> {
+    let mut form = vec![
+        ("grant_type", "refresh_token"),
+        ("refresh_token", tokens.refresh_token.as_str()),
+        ("client_id", settings.client_id.as_str()),
+        ("client_secret", tokens.client_secret.as_str()),
+    ];
+    if let Some(scope) = &settings.scope {
+        form.push(("scope", scope.as_str()));
+    }
+    let body = form
+        .into_iter()
+        .map(|(key, value)| format!("{key}={}", urlencoding::encode(value)))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let response = http_client
+        .post(&settings.token_url)
+        .header(
+            reqwest::header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .header(reqwest::header::ACCEPT, "application/json")
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| ToolError::OAuth2Error(e.to_string()))?;
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        return Err(ToolError::OAuth2Error(format!("{status}: {text}")));
+    }
+
+    response
+        .json()
+        .await
+        .map_err(|e| ToolError::OAuth2Error(format!("Invalid token response: {e}")))
+}
@@ -1,6 +1,18 @@
+mod calculator;
 mod code_runner;
+mod database_query;
+mod date_time;
+mod files;
+mod knowledge_search;
+mod memory;
 mod system_info;
 
+pub use code_runner::{
+    delete_workspace as delete_code_runner_workspace,
+    setup_image_pool as setup_code_runner_image_pool,
+    setup_wasm_path as setup_code_runner_wasm_path,
+};
+
 use diesel_as_jsonb::AsJsonb;
 use rocket::async_trait;
 use schemars::JsonSchema;
@@ -9,42 +21,144 @@ use uuid::Uuid;
 
 use crate::{db::models::ChatRsSystemTool, provider::LlmTool, utils::SenderWithLogging};
 
-use super::{ToolError, ToolLog, ToolParameters, ToolResponseFormat, ToolResult};
+use super::{
+    ToolError, ToolExecutionLimits, ToolLog, ToolParameters, ToolResponseFormat, ToolResult,
+    ToolStorage,
+};
 
 /// System tool configuration saved in the database
 #[derive(Debug, Serialize, Deserialize, JsonSchema, AsJsonb)]
 #[serde(tag = "type", content = "config", rename_all = "snake_case")]
 pub enum ChatRsSystemToolConfig {
+    Calculator,
     CodeRunner(code_runner::CodeRunnerConfig),
+    DatabaseQuery(database_query::DatabaseQueryConfig),
+    DateTime,
     Files(()),
+    KnowledgeSearch(knowledge_search::KnowledgeSearchConfig),
+    Memory(memory::MemoryConfig),
     SystemInfo,
 }
 impl ChatRsSystemToolConfig {
     /// Validate the configuration
     pub fn validate(&self) -> ToolResult<()> {
         match self {
+            ChatRsSystemToolConfig::Calculator => Ok(()),
             ChatRsSystemToolConfig::CodeRunner(config) => config.validate(),
+            ChatRsSystemToolConfig::DatabaseQuery(config) => config.validate(),
+            ChatRsSystemToolConfig::DateTime => Ok(()),
             ChatRsSystemToolConfig::Files(_) => Ok(()),
+            ChatRsSystemToolConfig::KnowledgeSearch(config) => config.validate(),
+            ChatRsSystemToolConfig::Memory(config) => config.validate(),
             ChatRsSystemToolConfig::SystemInfo => Ok(()),
         }
     }
+
+    /// Whether the tool has no side effects, and can be executed when testing the configuration
+    pub fn is_read_only(&self) -> bool {
+        match self {
+            ChatRsSystemToolConfig::DatabaseQuery(config) => config.read_only,
+            _ => matches!(
+                self,
+                ChatRsSystemToolConfig::Calculator
+                    | ChatRsSystemToolConfig::DateTime
+                    | ChatRsSystemToolConfig::SystemInfo
+                    | ChatRsSystemToolConfig::KnowledgeSearch(_)
+            ),
+        }
+    }
+
+    /// ID of the secret used by the tool, if any (e.g. a database connection string)
+    pub fn secret_id(&self) -> Option<&Uuid> {
+        match self {
+            ChatRsSystemToolConfig::DatabaseQuery(config) => Some(&config.connection_secret_id),
+            _ => None,
+        }
+    }
+
+    /// Mutable ID of the secret used by the tool, if any (e.g. to replace it when
+    /// exporting and importing the tool)
+    pub fn secret_id_mut(&mut self) -> Option<&mut Uuid> {
+        match self {
+            ChatRsSystemToolConfig::DatabaseQuery(config) => Some(&mut config.connection_secret_id),
+            _ => None,
+        }
+    }
+
+    /// Create the system tool executor from the configuration, with the decrypted secret
+    /// of the tool if any
+    pub fn build_executor<'a>(&'a self, secret: Option<&'a str>) -> Box<dyn SystemTool + 'a> {
+        match self {
+            ChatRsSystemToolConfig::Calculator => Box::new(calculator::Calculator::new()),
+            ChatRsSystemToolConfig::CodeRunner(config) => {
+                Box::new(code_runner::CodeRunner::new(config))
+            }
+            ChatRsSystemToolConfig::DatabaseQuery(config) => {
+                Box::new(database_query::DatabaseQuery::new(config, secret))
+            }
+            ChatRsSystemToolConfig::DateTime => Box::new(date_time::DateTimeTool::new()),
+            ChatRsSystemToolConfig::KnowledgeSearch(config) => {
+                Box::new(knowledge_search::KnowledgeSearch::new(config))
+            }
+            ChatRsSystemToolConfig::Memory(config) => Box::new(memory::Memory::new(config)),
+            ChatRsSystemToolConfig::SystemInfo => Box::new(system_info::SystemInfo::new()),
+            ChatRsSystemToolConfig::Files(_) => Box::new(files::Files::new()),
+        }
+    }
 }
 
 /// Chat input settings for system tools
 #[derive(Debug, Default, PartialEq, JsonSchema, Serialize, Deserialize)]
 pub struct SystemToolInput {
+    /// Enable/disable the calculator tool
+    #[serde(default)]
+    calculator: bool,
     /// Enable/disable the code runner tool
     #[serde(default)]
     code_runner: bool,
+    /// Enable/disable the database query tool
+    #[serde(default)]
+    database: bool,
+    /// Enable/disable the date/time and timezone tools (enabled if not set)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    date_time: Option<bool>,
+    /// Enable/disable the files tool
+    #[serde(default)]
+    files: bool,
     /// Enable/disable tools to get system information, current date/time, etc.
     #[serde(default)]
     info: bool,
+    /// Enable/disable the tools to search the user's knowledge bases
+    #[serde(default)]
+    knowledge: bool,
+    /// Enable/disable the memory tool. If not set, the memory tool is enabled if it's
+    /// configured to be enabled automatically.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    memory: Option<bool>,
     // TODO files, etc...
 }
 impl SystemToolInput {
+    /// Whether the date/time tools are enabled. They need no configuration, so they're
+    /// enabled by default.
+    pub fn date_time_enabled(&self) -> bool {
+        self.date_time.unwrap_or(true)
+    }
+
     /// Get all the LLM tools given the user's input
     pub fn get_llm_tools(&self, system_tools: &[ChatRsSystemTool]) -> ToolResult<Vec<LlmTool>> {
         let mut llm_tools = Vec::with_capacity(1);
+        if self.calculator {
+            let (config, tool_id) = system_tools
+                .iter()
+                .find_map(|t| match &t.data {
+                    ChatRsSystemToolConfig::Calculator => {
+                        Some((calculator::CalculatorConfig {}, t.id))
+                    }
+                    _ => None,
+                })
+                .ok_or(ToolError::ToolNotFound)?;
+            llm_tools.extend(config.get_llm_tools(tool_id, None));
+        }
         if self.code_runner {
             let (config, tool_id) = system_tools
                 .iter()
@@ -55,6 +169,36 @@ impl SystemToolInput {
                 .ok_or(ToolError::ToolNotFound)?;
             llm_tools.extend(config.get_llm_tools(tool_id, None));
         }
+        if self.database {
+            let (config, tool_id) = system_tools
+                .iter()
+                .find_map(|t| match &t.data {
+                    ChatRsSystemToolConfig::DatabaseQuery(config) => Some((config, t.id)),
+                    _ => None,
+                })
+                .ok_or(ToolError::ToolNotFound)?;
+            llm_tools.extend(config.get_llm_tools(tool_id, None));
+        }
+        if self.date_time_enabled() {
+            let (config, tool_id) = system_tools
+                .iter()
+                .find_map(|t| match &t.data {
+                    ChatRsSystemToolConfig::DateTime => Some((date_time::DateTimeConfig {}, t.id)),
+                    _ => None,
+                })
+                .ok_or(ToolError::ToolNotFound)?;
+            llm_tools.extend(config.get_llm_tools(tool_id, None));
+        }
+        if self.files {
+            let (config, tool_id) = system_tools
+                .iter()
+                .find_map(|t| match &t.data {
+                    ChatRsSystemToolConfig::Files(_) => Some((files::FilesConfig {}, t.id)),
+                    _ => None,
+                })
+                .ok_or(ToolError::ToolNotFound)?;
+            llm_tools.extend(config.get_llm_tools(tool_id, None));
+        }
         if self.info {
             let (config, tool_id) = system_tools
                 .iter()
@@ -67,6 +211,30 @@ impl SystemToolInput {
                 .ok_or(ToolError::ToolNotFound)?;
             llm_tools.extend(config.get_llm_tools(tool_id, None));
         }
+        if self.knowledge {
+            let (config, tool_id) = system_tools
+                .iter()
+                .find_map(|t| match &t.data {
+                    ChatRsSystemToolConfig::KnowledgeSearch(config) => Some((config, t.id)),
+                    _ => None,
+                })
+                .ok_or(ToolError::ToolNotFound)?;
+            llm_tools.extend(config.get_llm_tools(tool_id, None));
+        }
+        let memory_tool = system_tools.iter().find_map(|t| match &t.data {
+            ChatRsSystemToolConfig::Memory(config) => Some((config, t.id)),
+            _ => None,
+        });
+        match (self.memory, memory_tool) {
+            (Some(true), None) => return Err(ToolError::ToolNotFound),
+            (Some(true), Some((config, tool_id))) => {
+                llm_tools.extend(config.get_llm_tools(tool_id, None));
+            }
+            (None, Some((config, tool_id))) if config.auto_enable => {
+                llm_tools.extend(config.get_llm_tools(tool_id, None));
+            }
+            _ => {}
+        }
         Ok(llm_tools)
     }
 }
@@ -79,21 +247,30 @@ pub trait SystemTool: Send + Sync {
         &self,
         tool_name: &str,
         parameters: &ToolParameters,
+        storage: &ToolStorage,
         sender: &SenderWithLogging<ToolLog>,
     ) -> ToolResult<(String, ToolResponseFormat)>;
 
+    fn validate_parameters(&self, tool_name: &str, parameters: &ToolParameters) -> ToolResult<()> {
+        jsonschema::validate(
+            self.input_schema(tool_name),
+            &serde_json::to_value(parameters)?,
+        )
+        .map_err(|err| ToolError::InvalidParameters(err.to_string()))
+    }
+
     async fn validate_and_execute(
         &self,
         tool_name: &str,
         parameters: &ToolParameters,
+        storage: &ToolStorage,
         tx: &SenderWithLogging<ToolLog>,
+        limits: &ToolExecutionLimits,
     ) -> ToolResult<(String, ToolResponseFormat)> {
-        jsonschema::validate(
-            self.input_schema(tool_name),
-            &serde_json::to_value(parameters)?,
-        )
-        .map_err(|err| ToolError::InvalidParameters(err.to_string()))?;
-        self.execute(tool_name, parameters, tx).await
+        self.validate_parameters(tool_name, parameters)?;
+        limits
+            .apply(self.execute(tool_name, parameters, storage, tx))
+            .await
     }
 }
 
@@ -117,13 +294,7 @@ trait SystemToolConfig {
 
 impl ChatRsSystemTool {
     /// Create the system tool executor from the database entity
-    pub fn build_executor(&self) -> Box<dyn SystemTool + '_> {
-        match &self.data {
-            ChatRsSystemToolConfig::CodeRunner(config) => {
-                Box::new(code_runner::CodeRunner::new(config))
-            }
-            ChatRsSystemToolConfig::SystemInfo => Box::new(system_info::SystemInfo::new()),
-            ChatRsSystemToolConfig::Files(_) => unimplemented!(),
-        }
+    pub fn build_executor<'a>(&'a self, secret: Option<&'a str>) -> Box<dyn SystemTool + 'a> {
+        self.data.build_executor(secret)
     }
 }
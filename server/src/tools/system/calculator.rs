@@ -0,0 +1,125 @@
+use std::{
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use rocket::async_trait;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    provider::{LlmTool, LlmToolType},
+    tools::{
+        core::{ToolLog, ToolParameters, ToolResponseFormat, ToolResult, ToolStorage},
+        system::{SystemTool, SystemToolConfig},
+        utils::get_json_schema,
+        ToolError,
+    },
+    utils::SenderWithLogging,
+};
+
+const CALCULATOR_NAME: &str = "calculator";
+const CALCULATOR_DESC: &str = "Evaluate a math expression with arbitrary precision, \
+    e.g. `sqrt(2) * 3^20`, `15% * 2450`, `sin(30 degrees)`, `0b1011 to hex`. \
+    Supports units and unit conversion (e.g. `5 miles + 2 km to meters`, \
+    `100 fahrenheit to celsius`, `3 GiB / (20 MB/s) to seconds`). Multiple expressions and \
+    variables can be separated by semicolons (e.g. `a = 4; b = 7; a * b`), and the last result \
+    is returned. Use this instead of calculating by hand.";
+/// Max time to evaluate an expression
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// Max length of an expression, in characters
+const MAX_EXPRESSION_LENGTH: usize = 2000;
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct CalculatorInput {
+    /// The expression to evaluate
+    expression: String,
+}
+
+static CALCULATOR_INPUT_SCHEMA: LazyLock<serde_json::Value> =
+    LazyLock::new(|| get_json_schema::<CalculatorInput>());
+
+/// Offline calculator tool to evaluate math expressions.
+#[derive(Debug)]
+pub struct Calculator {}
+impl Calculator {
+    pub fn new() -> Self {
+        Calculator {}
+    }
+}
+
+pub struct CalculatorConfig {}
+impl SystemToolConfig for CalculatorConfig {
+    type DynamicConfig = ();
+
+    fn get_llm_tools(&self, tool_id: Uuid, _input_config: Option<()>) -> Vec<LlmTool> {
+        vec![LlmTool {
+            tool_id,
+            name: CALCULATOR_NAME.into(),
+            description: CALCULATOR_DESC.into(),
+            input_schema: CALCULATOR_INPUT_SCHEMA.to_owned(),
+            tool_type: LlmToolType::System,
+        }]
+    }
+
+    fn validate(&self) -> ToolResult<()> {
+        Ok(())
+    }
+}
+
+/// Interrupts the evaluation after the deadline
+struct Deadline(Instant);
+impl fend_core::Interrupt for Deadline {
+    fn should_interrupt(&self) -> bool {
+        Instant::now() > self.0
+    }
+}
+
+#[async_trait]
+impl SystemTool for Calculator {
+    fn input_schema(&self, _tool_name: &str) -> &serde_json::Value {
+        &CALCULATOR_INPUT_SCHEMA
+    }
+
+    async fn execute(
+        &self,
+        tool_name: &str,
+        parameters: &ToolParameters,
+        _storage: &ToolStorage,
+        _tx: &SenderWithLogging<ToolLog>,
+    ) -> ToolResult<(String, ToolResponseFormat)> {
+        if tool_name != CALCULATOR_NAME {
+            return Err(ToolError::ToolNotFound);
+        }
+        let input: CalculatorInput = serde_json::from_value(serde_json::to_value(parameters)?)?;
+        if input.expression.chars().count() > MAX_EXPRESSION_LENGTH {
+            return Err(ToolError::InvalidParameters(format!(
+                "Expression is too long (max {MAX_EXPRESSION_LENGTH} characters)"
+            )));
+        }
+
+        // Evaluation is CPU-bound, so run it on a blocking thread
+        let result = tokio::task::spawn_blocking(move || evaluate(&input.expression))
+            .await
+            .map_err(|e| ToolError::ToolExecutionError(e.to_string()))??;
+
+        Ok((result, ToolResponseFormat::Text))
+    }
+}
+
+/// Evaluate the expression without any network access (e.g. for currency conversion)
+fn evaluate(expression: &str) -> ToolResult<String> {
+    let mut context = fend_core::Context::new();
+    let deadline = Deadline(Instant::now() + TIMEOUT);
+    let result =
+        fend_core::evaluate_with_interrupt(expression, &mut context, &deadline).map_err(|e| {
+            match Instant::now() > deadline.0 {
+                true => ToolError::ToolExecutionError("Evaluation timed out".into()),
+                false => ToolError::ToolExecutionError(e),
+            }
+        })?;
+
+    Ok(result.get_main_result().to_owned())
+}
@@ -1,20 +1,28 @@
 mod docker;
 mod dockerfiles;
+mod wasm;
 use docker::{DockerExecutor, DockerExecutorOptions};
+use wasm::{WasmExecutor, WasmExecutorOptions};
 
-use std::sync::LazyLock;
+pub use docker::{delete_workspace, setup_image_pool};
 
-use rocket::async_trait;
+use std::{
+    path::PathBuf,
+    sync::{LazyLock, OnceLock},
+};
+
+use rocket::{async_trait, fairing::AdHoc};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
+    config::get_app_config,
     provider::{LlmTool, LlmToolType},
     tools::{
-        core::{ToolLog, ToolParameters, ToolResponseFormat, ToolResult},
+        core::{ToolLog, ToolParameters, ToolResponseFormat, ToolResult, ToolStorage},
         system::{SystemTool, SystemToolConfig},
-        utils::get_json_schema,
+        utils::{get_json_schema, get_tool_description},
         ToolError,
     },
     utils::SenderWithLogging,
@@ -23,10 +31,33 @@ use crate::{
 const CODE_RUNNER_NAME: &str = "code_runner";
 const CODE_RUNNER_DESCRIPTION: &str = "Run code snippet in a sandboxed environment. \
     Temporary files can be written to the `$HOME` directory (must be created first). \
-    Other than that, it is a read-only environment.";
+    Files written to the `/var/output` directory (e.g. generated charts or reports) are saved \
+    to the user's files, and linked in the result. Other than that, it is a read-only \
+    environment.";
+const WORKSPACE_DESCRIPTION: &str = "The `$HOME` directory is a persistent workspace: files \
+    written there are kept across code runs in this chat. Packages can't be installed in the \
+    workspace: list them in `dependencies` instead.";
+const WASM_DESCRIPTION: &str = "Run code snippet in a WebAssembly sandbox. Only Python \
+    (standard library only) and JavaScript (QuickJS, without Node.js APIs) are supported, \
+    without dependencies, network access, or a writable filesystem.";
 const DEFAULT_TIMEOUT_SECONDS: u32 = 30;
 const DEFAULT_MEMORY_LIMIT_MB: u32 = 512;
 const DEFAULT_CPU_LIMIT: f32 = 0.5;
+const DEFAULT_WASM_FUEL_MILLIONS: u32 = 10_000;
+const DEFAULT_WORKSPACE_SIZE_LIMIT_MB: u32 = 100;
+
+/// Directory of the WebAssembly runtimes, from the `code_runner_wasm_path` config
+static WASM_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Fairing that sets the directory of the WebAssembly runtimes for the wasm backend
+pub fn setup_wasm_path() -> AdHoc {
+    AdHoc::on_ignite("Code runner wasm path", |rocket| async {
+        if let Some(path) = &get_app_config(&rocket).code_runner_wasm_path {
+            let _ = WASM_PATH.set(PathBuf::from(path));
+        }
+        rocket
+    })
+}
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
@@ -39,10 +70,18 @@ struct CodeRunnerInput {
     /// as supported by the language's package manager CLI, e.g. for Python, `["numpy==1.23.4", "pandas>=1.0.0"]`
     /// or for JavaScript: `["axios@0.27.2", "lodash@4.17.21"]`.
     /// For Rust, features can be added at the end of the list as supported by `cargo add`, e.g., `["package1", "package2", "--features", "package2/feature1"]`.
+    /// For Java, use Maven coordinates without transitive dependencies, e.g. `["com.google.code.gson:gson:2.11.0"]`.
+    /// For C/C++ and Bash, use system packages, e.g. `["libeigen3-dev"]` (Debian) or `["jq"]` (Alpine). For PHP, use Composer
+    /// packages, e.g. `["nesbot/carbon"]` (autoloaded). For Ruby, use gems, and for R, use CRAN packages.
     dependencies: Vec<String>,
     /// Whether to enable network access. Set to `true` only if the program needs to access the internet at runtime.
     /// Network access is not needed for downloading dependencies.
     network: bool,
+    /// Optional input written to the program's stdin.
+    stdin: Option<String>,
+    /// Optional command-line arguments passed to the program.
+    #[serde(default)]
+    args: Vec<String>,
 }
 
 static CODE_RUNNER_INPUT_SCHEMA: LazyLock<serde_json::Value> =
@@ -68,11 +107,48 @@ enum CodeLanguage {
     Rust,
     Go,
     Bash,
+    Ruby,
+    Php,
+    Java,
+    C,
+    Cpp,
+    R,
+}
+impl CodeLanguage {
+    const ALL: [CodeLanguage; 12] = [
+        CodeLanguage::Python,
+        CodeLanguage::JavaScript,
+        CodeLanguage::TypeScript,
+        CodeLanguage::Rust,
+        CodeLanguage::Go,
+        CodeLanguage::Bash,
+        CodeLanguage::Ruby,
+        CodeLanguage::Php,
+        CodeLanguage::Java,
+        CodeLanguage::C,
+        CodeLanguage::Cpp,
+        CodeLanguage::R,
+    ];
+}
+
+/// Backend used to run the code
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeRunnerBackend {
+    /// Build and run a Docker container for each code run
+    #[default]
+    Docker,
+    /// Run Python and JavaScript code in a WebAssembly sandbox, for servers without Docker.
+    /// The runtimes are loaded from the `RS_CHAT_CODE_RUNNER_WASM_PATH` directory.
+    Wasm,
 }
 
 /// Configuration for the code runner tool.
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct CodeRunnerConfig {
+    /// Backend used to run the code (default: docker)
+    #[serde(default)]
+    pub backend: CodeRunnerBackend,
     /// Timeout in seconds for the code execution.
     #[serde(default = "default_timeout")]
     #[validate(range(min = 5, max = 60))]
@@ -85,6 +161,23 @@ pub struct CodeRunnerConfig {
     #[serde(default = "default_cpu_limit")]
     #[validate(range(min = 0.1, max = 1.2))]
     pub cpu_limit: f32,
+    /// Max number of instructions executed by the code with the wasm backend, in millions
+    #[serde(default = "default_wasm_fuel")]
+    #[validate(range(min = 100, max = 1_000_000))]
+    pub wasm_fuel_millions: u32,
+    /// Keep the `$HOME` directory in a workspace for each chat, so that later code runs in
+    /// the chat can use files from earlier runs (default: false).
+    #[serde(default)]
+    pub persistent_workspace: bool,
+    /// Max size in MB of the files in the workspace of each chat. Code runs that leave more
+    /// files in the workspace fail (default: 100).
+    #[serde(default = "default_workspace_size_limit")]
+    #[validate(range(min = 10, max = 10_000))]
+    pub workspace_size_limit_mb: u32,
+    /// Custom description of the tool for the LLM (uses the default description if not set)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(length(max = 1024))]
+    pub description: Option<String>,
 }
 fn default_timeout() -> u32 {
     DEFAULT_TIMEOUT_SECONDS
@@ -95,6 +188,12 @@ fn default_memory_limit() -> u32 {
 fn default_cpu_limit() -> f32 {
     DEFAULT_CPU_LIMIT
 }
+fn default_wasm_fuel() -> u32 {
+    DEFAULT_WASM_FUEL_MILLIONS
+}
+fn default_workspace_size_limit() -> u32 {
+    DEFAULT_WORKSPACE_SIZE_LIMIT_MB
+}
 
 impl SystemToolConfig for CodeRunnerConfig {
     type DynamicConfig = ();
@@ -106,9 +205,17 @@ impl SystemToolConfig for CodeRunnerConfig {
     }
 
     fn get_llm_tools(&self, tool_id: Uuid, _input_config: Option<()>) -> Vec<LlmTool> {
+        let default_description = match self.backend {
+            CodeRunnerBackend::Docker if self.persistent_workspace => {
+                format!("{CODE_RUNNER_DESCRIPTION} {WORKSPACE_DESCRIPTION}")
+            }
+            CodeRunnerBackend::Docker => CODE_RUNNER_DESCRIPTION.to_owned(),
+            CodeRunnerBackend::Wasm => WASM_DESCRIPTION.to_owned(),
+        };
+        let description = get_tool_description(self.description.as_deref(), &default_description);
         vec![LlmTool {
             name: CODE_RUNNER_NAME.into(),
-            description: CODE_RUNNER_DESCRIPTION.into(),
+            description,
             input_schema: CODE_RUNNER_INPUT_SCHEMA.to_owned(),
             tool_id,
             tool_type: LlmToolType::System,
@@ -126,23 +233,51 @@ impl SystemTool for CodeRunner<'_> {
         &self,
         _tool_name: &str,
         params: &ToolParameters,
+        storage: &ToolStorage,
         sender: &SenderWithLogging<ToolLog>,
     ) -> ToolResult<(String, ToolResponseFormat)> {
         let input = serde_json::from_value::<CodeRunnerInput>(serde_json::to_value(params)?)
             .map_err(|e| ToolError::InvalidParameters(e.to_string()))?;
-        let executor = DockerExecutor::new(
-            input.language,
-            DockerExecutorOptions {
-                timeout_seconds: self.config.timeout_seconds,
-                memory_limit_mb: self.config.memory_limit_mb,
-                cpu_limit: self.config.cpu_limit,
-                network: input.network,
-            },
-        );
-
-        let tool_response = executor
-            .execute(&input.code, &input.dependencies, sender)
-            .await?;
+        let tool_response = match self.config.backend {
+            CodeRunnerBackend::Docker => {
+                let executor = DockerExecutor::new(
+                    input.language,
+                    DockerExecutorOptions {
+                        timeout_seconds: self.config.timeout_seconds,
+                        memory_limit_mb: self.config.memory_limit_mb,
+                        cpu_limit: self.config.cpu_limit,
+                        network: input.network,
+                        stdin: input.stdin,
+                        args: input.args,
+                        workspace_session_id: self
+                            .config
+                            .persistent_workspace
+                            .then(|| storage.session_id().copied())
+                            .flatten(),
+                        workspace_size_limit_mb: self.config.workspace_size_limit_mb,
+                    },
+                );
+                executor
+                    .execute(&input.code, &input.dependencies, storage, sender)
+                    .await?
+            }
+            CodeRunnerBackend::Wasm => {
+                let executor = WasmExecutor::new(
+                    input.language,
+                    WasmExecutorOptions {
+                        timeout_seconds: self.config.timeout_seconds,
+                        memory_limit_mb: self.config.memory_limit_mb,
+                        fuel_millions: self.config.wasm_fuel_millions,
+                        stdin: input.stdin,
+                        args: input.args,
+                        runtimes_path: WASM_PATH.get().cloned(),
+                    },
+                );
+                executor
+                    .execute(&input.code, &input.dependencies, sender)
+                    .await?
+            }
+        };
         Ok((tool_response, ToolResponseFormat::Markdown))
     }
 }
@@ -1,4 +1,4 @@
-use std::{sync::LazyLock, time::Duration};
+use std::{collections::HashMap, sync::LazyLock, time::Duration};
 
 use bollard::{
     body_try_stream,
@@ -6,20 +6,31 @@ use bollard::{
     models::{ContainerCreateBody, HostConfig, ResourcesUlimits},
     query_parameters::{
         AttachContainerOptionsBuilder, BuildImageOptionsBuilder, CreateContainerOptionsBuilder,
-        CreateImageOptionsBuilder, RemoveContainerOptionsBuilder, RemoveImageOptionsBuilder,
+        CreateImageOptionsBuilder, DownloadFromContainerOptionsBuilder, ListImagesOptionsBuilder,
+        RemoveContainerOptionsBuilder, RemoveImageOptionsBuilder, RemoveVolumeOptions,
         StartContainerOptions, StopContainerOptions, WaitContainerOptions,
     },
     Docker,
 };
-use rocket::futures::StreamExt;
-use tokio_util::io::ReaderStream;
+use rocket::{
+    fairing::AdHoc,
+    futures::{Stream, StreamExt, TryStreamExt},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    sync::Mutex,
+};
+use tokio_util::io::{ReaderStream, StreamReader};
 use uuid::Uuid;
 
 use crate::{
+    config::AppConfig,
     tools::{
-        core::{ToolLog, ToolResult},
+        core::{ToolLog, ToolResult, ToolStorage},
         system::code_runner::{
-            dockerfiles::{get_dockerfile, get_dockerfile_info},
+            dockerfiles::{
+                get_base_dockerfile, get_dockerfile, get_dockerfile_info, get_interpreter_cmd,
+            },
             CodeLanguage,
         },
         ToolError,
@@ -31,6 +42,28 @@ static DOCKER: LazyLock<Result<Docker, bollard::errors::Error>> =
     LazyLock::new(|| Docker::connect_with_defaults());
 
 const GRACE_PERIOD_SECONDS: u32 = 5;
+/// Directory in the container where the code can write output files
+const OUTPUT_DIR: &str = "/var/output";
+/// Max number of output files saved after the execution
+const MAX_OUTPUT_FILES: usize = 10;
+/// Max size of each output file, in bytes
+const MAX_OUTPUT_FILE_SIZE: u64 = 10 * 1024 * 1024;
+/// Home directory of the user running the code
+const HOME_DIR: &str = "/tmp/home";
+/// Directory in the container where the code is written, when running directly in the base image
+const CODE_DIR: &str = "/tmp/code";
+/// Max size of code passed to the base image as an environment variable. Larger code is
+/// added to an image built on the base image.
+const MAX_ENV_CODE_SIZE: usize = 64 * 1024;
+/// Label of the images built by the code runner (value is `base` or `exec`)
+const IMAGE_LABEL: &str = "rs-chat.code-runner";
+/// Interval between image garbage collections
+const IMAGE_GC_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Min age of an execution image before it is garbage collected, in seconds
+const IMAGE_GC_MIN_AGE_SECONDS: i64 = 60 * 60;
+
+/// Serializes building the base images, so that each one is only built once
+static BASE_IMAGE_LOCK: LazyLock<Mutex<()>> = LazyLock::new(Default::default);
 
 pub struct DockerExecutor {
     lang: CodeLanguage,
@@ -38,6 +71,10 @@ pub struct DockerExecutor {
     memory_limit_mb: u32,
     cpu_limit: f32,
     network: bool,
+    stdin: Option<String>,
+    args: Vec<String>,
+    workspace_volume: Option<String>,
+    workspace_size_limit_mb: u32,
     image_tag: String,
     container_name: String,
 }
@@ -48,6 +85,14 @@ pub struct DockerExecutorOptions {
     pub memory_limit_mb: u32,
     pub cpu_limit: f32,
     pub network: bool,
+    /// Input written to the program's stdin
+    pub stdin: Option<String>,
+    /// Command-line arguments passed to the program
+    pub args: Vec<String>,
+    /// Mount the persistent workspace of this chat session as the home directory
+    pub workspace_session_id: Option<Uuid>,
+    /// Max size of the files in the workspace, in MB
+    pub workspace_size_limit_mb: u32,
 }
 
 impl DockerExecutor {
@@ -58,6 +103,13 @@ impl DockerExecutor {
             memory_limit_mb: options.memory_limit_mb,
             cpu_limit: options.cpu_limit,
             network: options.network,
+            stdin: options.stdin,
+            args: options.args,
+            workspace_volume: options
+                .workspace_session_id
+                .as_ref()
+                .map(workspace_volume_name),
+            workspace_size_limit_mb: options.workspace_size_limit_mb,
             image_tag: format!("code-runner-{}", Uuid::new_v4()),
             container_name: format!("code-runner-{}", Uuid::new_v4()),
         }
@@ -67,6 +119,7 @@ impl DockerExecutor {
         &self,
         code: &str,
         dependencies: &[String],
+        storage: &ToolStorage,
         tx: &SenderWithLogging<ToolLog>,
     ) -> ToolResult<String> {
         let docker = DOCKER
@@ -81,7 +134,7 @@ impl DockerExecutor {
 
         // Run the code in a Docker container, returning early if the client disconnects
         let result = tokio::select! {
-            result = self.run(docker, code, dependencies, &tx) => result,
+            result = self.run(docker, code, dependencies, storage, &tx) => result,
             _ = tx.closed() => Err(ToolError::Cancelled("client disconnected".to_string()))
         };
 
@@ -105,124 +158,68 @@ impl DockerExecutor {
         docker: &Docker,
         code: &str,
         dependencies: &[String],
+        storage: &ToolStorage,
         tx: &SenderWithLogging<ToolLog>,
     ) -> ToolResult<String> {
-        let (base_image, file_name, cmd) = get_dockerfile_info(&self.lang);
-
-        // Check if base image exists locally, pull if needed
-        send_log(tx, format!("Checking base image '{base_image}'...")).await;
-        if docker.inspect_image(base_image).await.is_err() {
-            send_log(tx, format!("Pulling base image '{base_image}'...")).await;
-            let image_options = CreateImageOptionsBuilder::new()
-                .from_image(base_image)
-                .build();
-            let mut pull_image_stream = docker.create_image(Some(image_options), None, None);
-            while let Some(result) = pull_image_stream.next().await {
-                match result {
-                    Ok(mut response) => {
-                        let status = response.status.unwrap_or_default();
-                        let progress_detail = response.progress_detail.take().unwrap_or_default();
-                        if let Some(progress) = response.progress {
-                            send_debug(tx, format!("Pulling image: {status} {progress}")).await;
-                        } else if let Some((current, total)) =
-                            progress_detail.current.zip(progress_detail.total)
-                        {
-                            send_debug(tx, format!("Pulling image: {status} {current}/{total}"))
-                                .await;
-                        }
-                        if let Some(error_detail) = response.error_detail {
-                            send_error(tx, format!("Error pulling image: {:?}", error_detail))
-                                .await;
-                        }
-                    }
-                    Err(err) => {
-                        let message = format!("Error pulling image: {err}");
-                        send_error(tx, message.clone()).await;
-                        return Err(ToolError::ToolExecutionError(message));
-                    }
-                }
-            }
-        }
+        let (_, file_name, cmd) = get_dockerfile_info(&self.lang);
+        let base_tag = ensure_base_image(docker, &self.lang, tx).await?;
+        let dependencies = self.build_dependency_string(dependencies);
 
-        // Create tar archive with build context (Dockerfile and code files)
-        let (tar_writer, tar_reader) = tokio::io::duplex(8192); // 8KB buffer
-        let dockerfile = get_dockerfile(&self.lang);
-        let code = code.to_owned();
-        send_log(tx, "Creating build context with 2 files...".into()).await;
-
-        let tar_creation_task = tokio::spawn(async move {
-            let mut tar = tokio_tar::Builder::new(tar_writer);
-            for (path, content) in [("Dockerfile", dockerfile), (file_name, &code)] {
-                let mut header = tokio_tar::Header::new_gnu();
-                header.set_size(content.len() as u64);
-                header.set_mode(0o644);
-                let _ = tar.append_data(&mut header, path, content.as_bytes()).await;
+        let (image, cmd, code_env) = match get_interpreter_cmd(&self.lang, CODE_DIR) {
+            // Interpreted code without dependencies runs directly in the base image
+            Some(interpreter_cmd) if dependencies.is_empty() && code.len() <= MAX_ENV_CODE_SIZE => {
+                let cmd = format!(
+                    "mkdir -p {CODE_DIR} && printf '%s' \"$CODE\" > {CODE_DIR}/{file_name} \
+                        && {interpreter_cmd}"
+                );
+                (base_tag, cmd, Some(format!("CODE={code}")))
             }
-            tar.finish().await
-        });
-
-        // Build Docker image (streaming the build context tar file)
-        send_log(tx, format!("Building image '{}'...", self.image_tag)).await;
-        let build_options = BuildImageOptionsBuilder::new()
-            .buildargs(&[("DEPENDENCIES", self.build_dependency_string(dependencies))].into())
-            .t(&self.image_tag)
-            .build();
-        let mut build_stream = docker.build_image(
-            build_options,
-            None,
-            Some(body_try_stream(ReaderStream::new(tar_reader))),
-        );
-
-        let mut build_logs = String::new();
-        let mut image_id = None;
-        while let Some(build_info_result) = build_stream.next().await {
-            match build_info_result {
-                Ok(info) => {
-                    if let Some(id) = info.aux.and_then(|aux| aux.id) {
-                        image_id = Some(id);
-                    }
-                    if let Some(stream) = info.stream {
-                        build_logs.push_str(&format!("{stream}\n"));
-                        send_debug(tx, stream).await;
-                    }
-                    if let Some(err) = info.error_detail.and_then(|e| e.message) {
-                        build_logs.push_str(&format!("{err}\n"));
-                        send_error(tx, format!("Error during build: {err}")).await;
-                    }
-                }
-                Err(err) => {
-                    build_logs.push_str(&format!("{err}\n"));
-                    send_error(tx, format!("Error during build: {err}")).await;
-                }
+            _ => {
+                send_log(tx, "Creating build context with 2 files...".into()).await;
+                let build_args = [("BASE_IMAGE", base_tag), ("DEPENDENCIES", dependencies)];
+                build_image(
+                    docker,
+                    &self.image_tag,
+                    get_dockerfile(&self.lang),
+                    Some((file_name, code.to_owned())),
+                    &build_args.into(),
+                    "exec",
+                    tx,
+                )
+                .await?;
+                (self.image_tag.clone(), cmd.to_owned(), None)
             }
-        }
-        if let Ok(Err(err)) = tar_creation_task.await {
-            let message = format!("Error while creating build context: {err}");
-            send_error(tx, message).await;
-        }
-        if let Some(image_id) = image_id {
-            let message = format!("Built image '{}' with ID {}", self.image_tag, image_id);
-            send_log(tx, message).await;
-        } else {
-            let message = format!("Failed to build image '{}'", self.image_tag);
-            send_error(tx, message).await;
-            return Err(ToolError::ToolExecutionError(format!(
-                "Failed to build image '{}'. Build logs:\n\n{build_logs}",
-                self.image_tag
-            )));
-        }
+        };
 
         // Create container with run command
         let timeout_str = format!("{}s", self.timeout_seconds + GRACE_PERIOD_SECONDS);
-        let run_command = ["timeout", &timeout_str, "sh", "-c", &cmd];
+        let cmd = format!("{cmd} \"$@\""); // Pass the arguments to the program
+        let run_command: Vec<String> = ["timeout", &timeout_str, "sh", "-c", &cmd, "sh"]
+            .into_iter()
+            .map(String::from)
+            .chain(self.args.iter().cloned())
+            .collect();
         let container_body = ContainerCreateBody {
-            image: Some(self.image_tag.clone()),
-            cmd: Some(run_command.iter().map(|s| s.to_string()).collect()),
-            env: Some(vec!["HOME=/tmp/home".into()]),
+            image: Some(image),
+            cmd: Some(run_command.clone()),
+            open_stdin: Some(self.stdin.is_some()),
+            stdin_once: Some(self.stdin.is_some()),
+            env: Some(
+                [Some(format!("HOME={HOME_DIR}")), code_env]
+                    .into_iter()
+                    .flatten()
+                    .collect(),
+            ),
+            // Anonymous volume, so that the output files are kept after the container exits
+            volumes: Some([(OUTPUT_DIR.into(), Default::default())].into()),
             network_disabled: Some(!self.network),
             host_config: Some(HostConfig {
                 readonly_rootfs: Some(true),
                 tmpfs: Some([("/tmp".into(), "rw,noexec,nosuid,size=100m".into())].into()),
+                binds: self
+                    .workspace_volume
+                    .as_ref()
+                    .map(|volume| vec![format!("{volume}:{HOME_DIR}")]),
                 memory: Some((self.memory_limit_mb * 1024 * 1024).into()),
                 nano_cpus: Some((self.cpu_limit * 1000.0).round() as i64 * 1_000_000),
                 pids_limit: Some(50),
@@ -269,9 +266,10 @@ impl DockerExecutor {
             .stream(true)
             .stdout(true)
             .stderr(true)
+            .stdin(self.stdin.is_some())
             .logs(true)
             .build();
-        let attached_container = match docker
+        let AttachContainerResults { output, mut input } = match docker
             .attach_container(&self.container_name, Some(attach_options))
             .await
         {
@@ -292,7 +290,7 @@ impl DockerExecutor {
             let mut stderr = String::new();
             let _ = tokio::time::timeout(
                 Duration::from_secs(output_timeout_secs.into()),
-                capture_container_output(attached_container, &mut stdout, &mut stderr, &output_tx),
+                capture_container_output(output, &mut stdout, &mut stderr, &output_tx),
             )
             .await;
             (stdout, stderr)
@@ -309,6 +307,16 @@ impl DockerExecutor {
             return Err(ToolError::ToolExecutionError(message));
         }
 
+        // Write the input to stdin in the background, in case the program doesn't read all of it
+        if let Some(stdin) = self.stdin.clone() {
+            tokio::spawn(async move {
+                if let Err(err) = input.write_all(stdin.as_bytes()).await {
+                    rocket::debug!("Failed to write to container stdin: {}", err);
+                }
+                let _ = input.shutdown().await;
+            });
+        }
+
         // Wait for container to exit and get exit status
         let container_exit_result = tokio::time::timeout(
             Duration::from_secs(self.timeout_seconds.into()),
@@ -318,11 +326,44 @@ impl DockerExecutor {
         )
         .await;
 
-        // Process output and exit status
+        // Process output, output files, and exit status
         let (stdout, stderr) = container_output_task.await.unwrap_or_default();
-        let output_text = format!("Output (stdout):\n\n{stdout}\n\nLogs (stderr):\n\n{stderr}\n");
+        let output_files = self.save_output_files(docker, storage, tx).await;
+        let files_text = match output_files.is_empty() {
+            true => String::new(),
+            false => {
+                let links: Vec<String> = output_files
+                    .iter()
+                    .map(|(name, storage_path)| format!("- [{name}](/api/file/{storage_path})"))
+                    .collect();
+                format!("\n## Output files:\n{}\n", links.join("\n"))
+            }
+        };
+        let output_text =
+            format!("Output (stdout):\n\n{stdout}\n\nLogs (stderr):\n\n{stderr}\n{files_text}");
         let output_markdown =
-            format!("## Output (stdout):\n```text\n{stdout}\n```\n## Logs (stderr):\n```text\n{stderr}\n```\n");
+            format!("## Output (stdout):\n```text\n{stdout}\n```\n## Logs (stderr):\n```text\n{stderr}\n```\n{files_text}");
+
+        // The workspace volume has no size limit of its own, so check it after each run
+        if self.workspace_volume.is_some() {
+            let limit = u64::from(self.workspace_size_limit_mb) * 1024 * 1024;
+            match self.get_workspace_size(docker, limit).await {
+                Ok(size) if size > limit => {
+                    let message = format!(
+                        "Workspace exceeds the size limit of {} MB",
+                        self.workspace_size_limit_mb
+                    );
+                    send_error(tx, message.clone()).await;
+                    return Err(ToolError::ToolExecutionError(format!(
+                        "❌ {message}: delete files from `$HOME` to free up space.\n\n{output_text}"
+                    )));
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    send_error(tx, format!("Failed to check the workspace size: {err}")).await;
+                }
+            }
+        }
         match container_exit_result {
             Err(_) => {
                 send_error(tx, "Code execution timed out".into()).await;
@@ -355,6 +396,104 @@ impl DockerExecutor {
         }
     }
 
+    /// Copy the files written to the output directory out of the container, and save them
+    /// in the user's files. Returns the names and storage paths of the saved files.
+    async fn save_output_files(
+        &self,
+        docker: &Docker,
+        storage: &ToolStorage,
+        tx: &SenderWithLogging<ToolLog>,
+    ) -> Vec<(String, String)> {
+        let download_options = DownloadFromContainerOptionsBuilder::new()
+            .path(OUTPUT_DIR)
+            .build();
+        let tar_stream = docker
+            .download_from_container(&self.container_name, Some(download_options))
+            .map_err(std::io::Error::other);
+        let mut archive = tokio_tar::Archive::new(StreamReader::new(Box::pin(tar_stream)));
+        let mut entries = match archive.entries() {
+            Ok(entries) => entries,
+            Err(err) => {
+                send_error(tx, format!("Failed to read output files: {err}")).await;
+                return Vec::new();
+            }
+        };
+
+        let mut saved_files = Vec::new();
+        while let Some(entry) = entries.next().await {
+            let mut entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    send_error(tx, format!("Failed to read output files: {err}")).await;
+                    break;
+                }
+            };
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            // Paths in the archive start with the name of the output directory
+            let Some(name) = entry.path().ok().and_then(|path| {
+                let relative_path = path.components().skip(1).collect::<std::path::PathBuf>();
+                Some(relative_path.to_str()?.to_owned())
+            }) else {
+                continue;
+            };
+            if saved_files.len() >= MAX_OUTPUT_FILES {
+                let message = format!("Skipping output file '{name}': too many output files");
+                send_error(tx, message).await;
+                continue;
+            }
+            if entry.header().size().unwrap_or(u64::MAX) > MAX_OUTPUT_FILE_SIZE {
+                send_error(
+                    tx,
+                    format!("Skipping output file '{name}': file is too large"),
+                )
+                .await;
+                continue;
+            }
+
+            let mut data = Vec::new();
+            if let Err(err) = entry.read_to_end(&mut data).await {
+                send_error(tx, format!("Failed to read output file '{name}': {err}")).await;
+                continue;
+            }
+            let path = format!("code_runner/{}/{name}", self.container_name);
+            match storage.write_file(&path, &data).await {
+                Ok(storage_path) => {
+                    send_log(tx, format!("Saved output file '{name}'")).await;
+                    saved_files.push((name, storage_path));
+                }
+                Err(err) => {
+                    send_error(tx, format!("Failed to save output file '{name}': {err}")).await;
+                }
+            }
+        }
+
+        saved_files
+    }
+
+    /// Get the size of the files in the workspace, from the archive of the home directory.
+    /// Stops counting once the size exceeds the limit.
+    async fn get_workspace_size(&self, docker: &Docker, limit: u64) -> std::io::Result<u64> {
+        let download_options = DownloadFromContainerOptionsBuilder::new()
+            .path(HOME_DIR)
+            .build();
+        let tar_stream = docker
+            .download_from_container(&self.container_name, Some(download_options))
+            .map_err(std::io::Error::other);
+        let mut archive = tokio_tar::Archive::new(StreamReader::new(Box::pin(tar_stream)));
+        let mut entries = archive.entries()?;
+        let mut size = 0;
+        while let Some(entry) = entries.next().await {
+            size += entry?.header().size()?;
+            if size > limit {
+                break;
+            }
+        }
+
+        Ok(size)
+    }
+
     fn build_dependency_string(&self, dependencies: &[String]) -> String {
         dependencies
             .iter()
@@ -392,12 +531,12 @@ impl DockerExecutor {
 
 /// Capture stdout and stderr from the attached container
 async fn capture_container_output(
-    mut attached_container: AttachContainerResults,
+    mut output: impl Stream<Item = Result<LogOutput, bollard::errors::Error>> + Unpin,
     stdout: &mut String,
     stderr: &mut String,
     tx: &SenderWithLogging<ToolLog>,
 ) {
-    while let Some(output_result) = attached_container.output.next().await {
+    while let Some(output_result) = output.next().await {
         match output_result {
             Ok(output) => match output {
                 LogOutput::StdOut { message } => {
@@ -419,6 +558,245 @@ async fn capture_container_output(
     }
 }
 
+/// Tag of the prebuilt base image of the language. The tag changes with the server version,
+/// so that outdated base images are rebuilt and garbage collected.
+fn base_image_tag(lang: &CodeLanguage) -> String {
+    let lang = format!("{lang:?}").to_lowercase();
+    format!("code-runner-base-{lang}:{}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Get the tag of the base image of the language, building it if it doesn't exist yet
+async fn ensure_base_image(
+    docker: &Docker,
+    lang: &CodeLanguage,
+    tx: &SenderWithLogging<ToolLog>,
+) -> ToolResult<String> {
+    let tag = base_image_tag(lang);
+    let _lock = BASE_IMAGE_LOCK.lock().await;
+    if docker.inspect_image(&tag).await.is_ok() {
+        return Ok(tag);
+    }
+
+    let (upstream_image, _, _) = get_dockerfile_info(lang);
+    send_log(tx, format!("Checking base image '{upstream_image}'...")).await;
+    if docker.inspect_image(upstream_image).await.is_err() {
+        pull_image(docker, upstream_image, tx).await?;
+    }
+    let dockerfile = get_base_dockerfile(lang);
+    build_image(docker, &tag, dockerfile, None, &HashMap::new(), "base", tx).await?;
+
+    Ok(tag)
+}
+
+/// Pull the image from the registry
+async fn pull_image(
+    docker: &Docker,
+    image: &str,
+    tx: &SenderWithLogging<ToolLog>,
+) -> ToolResult<()> {
+    send_log(tx, format!("Pulling base image '{image}'...")).await;
+    let image_options = CreateImageOptionsBuilder::new().from_image(image).build();
+    let mut pull_image_stream = docker.create_image(Some(image_options), None, None);
+    while let Some(result) = pull_image_stream.next().await {
+        match result {
+            Ok(mut response) => {
+                let status = response.status.unwrap_or_default();
+                let progress_detail = response.progress_detail.take().unwrap_or_default();
+                if let Some(progress) = response.progress {
+                    send_debug(tx, format!("Pulling image: {status} {progress}")).await;
+                } else if let Some((current, total)) =
+                    progress_detail.current.zip(progress_detail.total)
+                {
+                    send_debug(tx, format!("Pulling image: {status} {current}/{total}")).await;
+                }
+                if let Some(error_detail) = response.error_detail {
+                    send_error(tx, format!("Error pulling image: {:?}", error_detail)).await;
+                }
+            }
+            Err(err) => {
+                let message = format!("Error pulling image: {err}");
+                send_error(tx, message.clone()).await;
+                return Err(ToolError::ToolExecutionError(message));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Build the image from the Dockerfile and the optional code file, labeling it with the
+/// kind of image (`base` or `exec`)
+async fn build_image(
+    docker: &Docker,
+    tag: &str,
+    dockerfile: &'static str,
+    code_file: Option<(&'static str, String)>,
+    build_args: &HashMap<&str, String>,
+    kind: &str,
+    tx: &SenderWithLogging<ToolLog>,
+) -> ToolResult<()> {
+    // Create tar archive with build context (Dockerfile and code files)
+    let (tar_writer, tar_reader) = tokio::io::duplex(8192); // 8KB buffer
+    let tar_creation_task = tokio::spawn(async move {
+        let mut tar = tokio_tar::Builder::new(tar_writer);
+        let files = std::iter::once(("Dockerfile", dockerfile.to_owned())).chain(code_file);
+        for (path, content) in files {
+            let mut header = tokio_tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            let _ = tar.append_data(&mut header, path, content.as_bytes()).await;
+        }
+        tar.finish().await
+    });
+
+    // Build Docker image (streaming the build context tar file)
+    send_log(tx, format!("Building image '{tag}'...")).await;
+    let build_options = BuildImageOptionsBuilder::new()
+        .buildargs(build_args)
+        .labels(&[(IMAGE_LABEL, kind)].into())
+        .t(tag)
+        .build();
+    let mut build_stream = docker.build_image(
+        build_options,
+        None,
+        Some(body_try_stream(ReaderStream::new(tar_reader))),
+    );
+
+    let mut build_logs = String::new();
+    let mut image_id = None;
+    while let Some(build_info_result) = build_stream.next().await {
+        match build_info_result {
+            Ok(info) => {
+                if let Some(id) = info.aux.and_then(|aux| aux.id) {
+                    image_id = Some(id);
+                }
+                if let Some(stream) = info.stream {
+                    build_logs.push_str(&format!("{stream}\n"));
+                    send_debug(tx, stream).await;
+                }
+                if let Some(err) = info.error_detail.and_then(|e| e.message) {
+                    build_logs.push_str(&format!("{err}\n"));
+                    send_error(tx, format!("Error during build: {err}")).await;
+                }
+            }
+            Err(err) => {
+                build_logs.push_str(&format!("{err}\n"));
+                send_error(tx, format!("Error during build: {err}")).await;
+            }
+        }
+    }
+    if let Ok(Err(err)) = tar_creation_task.await {
+        let message = format!("Error while creating build context: {err}");
+        send_error(tx, message).await;
+    }
+    if let Some(image_id) = image_id {
+        send_log(tx, format!("Built image '{tag}' with ID {image_id}")).await;
+        Ok(())
+    } else {
+        send_error(tx, format!("Failed to build image '{tag}'")).await;
+        Err(ToolError::ToolExecutionError(format!(
+            "Failed to build image '{tag}'. Build logs:\n\n{build_logs}"
+        )))
+    }
+}
+
+/// Prebuild the base images of all languages on startup, and periodically remove outdated
+/// base images and leftover execution images (enable with the `code_runner_image_pool` config)
+pub fn setup_image_pool() -> AdHoc {
+    AdHoc::on_liftoff("Code runner image pool", |rocket| {
+        Box::pin(async move {
+            let enabled = rocket
+                .state::<AppConfig>()
+                .and_then(|config| config.code_runner_image_pool)
+                .unwrap_or(false);
+            if !enabled {
+                return;
+            }
+            let Ok(docker) = DOCKER.as_ref() else {
+                rocket::warn!("Code runner image pool not started: failed to initialize Docker");
+                return;
+            };
+
+            tokio::spawn(async move {
+                // Build logs aren't collected
+                let (log_tx, _) = tokio::sync::mpsc::channel(1);
+                let tx = SenderWithLogging::new(log_tx.clone(), log_tx);
+                for lang in CodeLanguage::ALL {
+                    match ensure_base_image(docker, &lang, &tx).await {
+                        Ok(tag) => rocket::info!("Code runner base image '{}' is ready", tag),
+                        Err(err) => {
+                            rocket::warn!("Failed to build code runner base image: {}", err)
+                        }
+                    }
+                }
+
+                let mut interval = tokio::time::interval(IMAGE_GC_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    if let Err(err) = remove_stale_images(docker).await {
+                        rocket::warn!("Code runner image garbage collection failed: {}", err);
+                    }
+                }
+            });
+        })
+    })
+}
+
+/// Remove base images of other server versions, and execution images that weren't cleaned up
+async fn remove_stale_images(docker: &Docker) -> Result<(), bollard::errors::Error> {
+    let current_tags: Vec<String> = CodeLanguage::ALL.iter().map(base_image_tag).collect();
+    let list_options = ListImagesOptionsBuilder::new()
+        .filters(&[("label", vec![IMAGE_LABEL])].into())
+        .build();
+    let now = chrono::Utc::now().timestamp();
+    for image in docker.list_images(Some(list_options)).await? {
+        let is_stale = match image.labels.get(IMAGE_LABEL).map(|kind| kind.as_str()) {
+            Some("base") => !image.repo_tags.iter().any(|tag| current_tags.contains(tag)),
+            _ => now - image.created > IMAGE_GC_MIN_AGE_SECONDS,
+        };
+        if !is_stale {
+            continue;
+        }
+        let remove_options = RemoveImageOptionsBuilder::new().force(true).build();
+        match docker
+            .remove_image(&image.id, Some(remove_options), None)
+            .await
+        {
+            Ok(_) => rocket::debug!("Removed code runner image {:?}", image.repo_tags),
+            Err(err) => rocket::debug!("Failed to remove code runner image: {}", err),
+        }
+    }
+
+    Ok(())
+}
+
+/// Name of the Docker volume used as the persistent workspace of the chat session
+fn workspace_volume_name(session_id: &Uuid) -> String {
+    format!("code-runner-workspace-{session_id}")
+}
+
+/// Delete the persistent workspace of the chat session, if it exists
+pub async fn delete_workspace(session_id: &Uuid) -> ToolResult<()> {
+    let docker = DOCKER
+        .as_ref()
+        .map_err(|_| ToolError::ToolExecutionError("Failed to initialize Docker".into()))?;
+    match docker
+        .remove_volume(
+            &workspace_volume_name(session_id),
+            None::<RemoveVolumeOptions>,
+        )
+        .await
+    {
+        Ok(_)
+        | Err(bollard::errors::Error::DockerResponseServerError {
+            status_code: 404, ..
+        }) => Ok(()),
+        Err(err) => Err(ToolError::ToolExecutionError(format!(
+            "Failed to delete workspace: {err}"
+        ))),
+    }
+}
+
 async fn docker_cleanup(docker: &Docker, container_name: &str, image_tag: &str) {
     let _ = docker
         .stop_container(container_name, None::<StopContainerOptions>)
@@ -426,7 +804,12 @@ async fn docker_cleanup(docker: &Docker, container_name: &str, image_tag: &str)
     let _ = tokio::join!(
         docker.remove_container(
             container_name,
-            Some(RemoveContainerOptionsBuilder::new().force(true).build()),
+            Some(
+                RemoveContainerOptionsBuilder::new()
+                    .force(true)
+                    .v(true)
+                    .build()
+            ),
         ),
         docker.remove_image(
             image_tag,
@@ -2,6 +2,26 @@ use const_format::formatcp;
 
 use super::CodeLanguage;
 
+/// Dockerfile of the prebuilt base image of the language, with the runtime and tools
+/// but without any dependencies or code
+pub fn get_base_dockerfile(language: &CodeLanguage) -> &'static str {
+    match language {
+        CodeLanguage::JavaScript => JS_BASE_DOCKERFILE,
+        CodeLanguage::TypeScript => TS_BASE_DOCKERFILE,
+        CodeLanguage::Python => PYTHON_BASE_DOCKERFILE,
+        CodeLanguage::Rust => RUST_BASE_DOCKERFILE,
+        CodeLanguage::Go => GO_BASE_DOCKERFILE,
+        CodeLanguage::Bash => BASH_BASE_DOCKERFILE,
+        CodeLanguage::Ruby => RUBY_BASE_DOCKERFILE,
+        CodeLanguage::Php => PHP_BASE_DOCKERFILE,
+        CodeLanguage::Java => JAVA_BASE_DOCKERFILE,
+        CodeLanguage::C | CodeLanguage::Cpp => C_BASE_DOCKERFILE,
+        CodeLanguage::R => R_BASE_DOCKERFILE,
+    }
+}
+
+/// Dockerfile building on the base image (`BASE_IMAGE` build arg), which installs the
+/// dependencies and adds the code
 pub fn get_dockerfile(language: &CodeLanguage) -> &'static str {
     match language {
         CodeLanguage::JavaScript => JS_DOCKERFILE,
@@ -10,6 +30,12 @@ pub fn get_dockerfile(language: &CodeLanguage) -> &'static str {
         CodeLanguage::Rust => RUST_DOCKERFILE,
         CodeLanguage::Go => GO_DOCKERFILE,
         CodeLanguage::Bash => BASH_DOCKERFILE,
+        CodeLanguage::Ruby => RUBY_DOCKERFILE,
+        CodeLanguage::Php => PHP_DOCKERFILE,
+        CodeLanguage::Java => JAVA_DOCKERFILE,
+        CodeLanguage::C => C_DOCKERFILE,
+        CodeLanguage::Cpp => CPP_DOCKERFILE,
+        CodeLanguage::R => R_DOCKERFILE,
     }
 }
 
@@ -21,28 +47,65 @@ pub fn get_dockerfile_info(language: &CodeLanguage) -> (&'static str, &'static s
         CodeLanguage::Rust => (RUST_IMAGE, "main.rs", "./target/debug/temp"),
         CodeLanguage::Go => (GO_IMAGE, "main.go", "./temp"),
         CodeLanguage::Bash => (BASH_IMAGE, "script.sh", "bash script.sh"),
+        CodeLanguage::Ruby => (RUBY_IMAGE, "main.rb", "ruby main.rb"),
+        CodeLanguage::Php => (PHP_IMAGE, "main.php", PHP_CMD),
+        CodeLanguage::Java => (JAVA_IMAGE, "Main.java", "java -cp '/opt/jars/*' Main.java"),
+        CodeLanguage::C => (C_IMAGE, "main.c", "./main"),
+        CodeLanguage::Cpp => (C_IMAGE, "main.cpp", "./main"),
+        CodeLanguage::R => (R_IMAGE, "main.R", "Rscript main.R"),
     };
     (base_image, file_name, cmd)
 }
 
+/// Command running a code file in the given directory, for interpreted languages that can
+/// run code without dependencies directly in the base image
+pub fn get_interpreter_cmd(language: &CodeLanguage, dir: &str) -> Option<String> {
+    match language {
+        CodeLanguage::JavaScript => Some(format!("node {dir}/main.js")),
+        CodeLanguage::TypeScript => Some(format!("pnpm tsx {dir}/main.ts")),
+        CodeLanguage::Python => Some(format!("python {dir}/main.py")),
+        CodeLanguage::Bash => Some(format!("bash {dir}/script.sh")),
+        CodeLanguage::Ruby => Some(format!("ruby {dir}/main.rb")),
+        CodeLanguage::Php => Some(format!("{PHP_CMD_PREFIX} {dir}/main.php")),
+        CodeLanguage::Java => Some(format!("java {dir}/Main.java")),
+        CodeLanguage::R => Some(format!("Rscript {dir}/main.R")),
+        CodeLanguage::Rust | CodeLanguage::Go | CodeLanguage::C | CodeLanguage::Cpp => None,
+    }
+}
+
 const JS_IMAGE: &str = "node:20-slim";
 const PYTHON_IMAGE: &str = "python:3.13-slim";
 const RUST_IMAGE: &str = "rust:1.85-slim";
 const GO_IMAGE: &str = "golang:1.24";
 const BASH_IMAGE: &str = "bash:5.3";
+const RUBY_IMAGE: &str = "ruby:3.4-slim";
+const PHP_IMAGE: &str = "php:8.4-cli";
+const JAVA_IMAGE: &str = "eclipse-temurin:21-jdk";
+const C_IMAGE: &str = "gcc:14";
+const R_IMAGE: &str = "r-base:4.4.3";
+
+/// Composer's autoloader is loaded before the code, so that dependencies can be used directly
+const PHP_CMD_PREFIX: &str = "php -d auto_prepend_file=/app/vendor/autoload.php";
+const PHP_CMD: &str = formatcp!("{PHP_CMD_PREFIX} main.php");
 
 const SET_USER_AND_HOME_DIR: &str = r#"
-RUN mkdir -p /app && chown 1000:1000 /app
+RUN mkdir -p /app /var/output && chown 1000:1000 /app /var/output
 USER 1000:1000
 RUN mkdir -p /tmp/home
 WORKDIR /app
 "#;
 
-const JS_DOCKERFILE: &str = formatcp!(
+const FROM_BASE_IMAGE: &str = r#"
+ARG BASE_IMAGE
+FROM $BASE_IMAGE
+
+ARG DEPENDENCIES
+"#;
+
+const JS_BASE_DOCKERFILE: &str = formatcp!(
     r#"
 FROM {JS_IMAGE}
 
-ARG DEPENDENCIES
 ENV PNPM_HOME="/opt/pnpm"
 ENV PATH="$PNPM_HOME:$PATH"
 
@@ -52,6 +115,13 @@ RUN npm install -g pnpm@9
 {SET_USER_AND_HOME_DIR}
 
 RUN pnpm init
+"#
+);
+
+const JS_DOCKERFILE: &str = formatcp!(
+    r#"
+{FROM_BASE_IMAGE}
+
 RUN if [ -n "$DEPENDENCIES" ]; then pnpm install $DEPENDENCIES; fi
 
 COPY main.js .
@@ -60,21 +130,19 @@ CMD ["node", "main.js"]
 "#
 );
 
-const TS_DOCKERFILE: &str = formatcp!(
+const TS_BASE_DOCKERFILE: &str = formatcp!(
     r#"
-FROM {JS_IMAGE}
-
-ARG DEPENDENCIES
-ENV PNPM_HOME="/opt/pnpm"
-ENV PATH="$PNPM_HOME:$PATH"
+{JS_BASE_DOCKERFILE}
 
-RUN mkdir -p /opt/pnpm && chown 1000:1000 /opt/pnpm
-RUN npm install -g pnpm@9
+RUN pnpm install tsx
+"#
+);
 
-{SET_USER_AND_HOME_DIR}
+const TS_DOCKERFILE: &str = formatcp!(
+    r#"
+{FROM_BASE_IMAGE}
 
-RUN pnpm init
-RUN pnpm install tsx $DEPENDENCIES
+RUN if [ -n "$DEPENDENCIES" ]; then pnpm install $DEPENDENCIES; fi
 
 COPY main.ts .
 
@@ -82,11 +150,10 @@ CMD ["pnpm", "tsx", "main.ts"]
 "#
 );
 
-const PYTHON_DOCKERFILE: &str = formatcp!(
+const PYTHON_BASE_DOCKERFILE: &str = formatcp!(
     r#"
 FROM {PYTHON_IMAGE}
 
-ARG DEPENDENCIES
 ENV PYTHONUNBUFFERED=1
 ENV PYTHONUSERBASE="/opt/python"
 ENV PATH="/opt/python/bin:$PATH"
@@ -94,6 +161,12 @@ ENV PATH="/opt/python/bin:$PATH"
 RUN mkdir -p /opt/python && chown 1000:1000 /opt/python
 
 {SET_USER_AND_HOME_DIR}
+"#
+);
+
+const PYTHON_DOCKERFILE: &str = formatcp!(
+    r#"
+{FROM_BASE_IMAGE}
 
 RUN if [ -n "$DEPENDENCIES" ]; then pip install --user --no-cache-dir $DEPENDENCIES; fi
 
@@ -103,18 +176,23 @@ CMD ["python", "main.py"]
 "#
 );
 
-const RUST_DOCKERFILE: &str = formatcp!(
+const RUST_BASE_DOCKERFILE: &str = formatcp!(
     r#"
 FROM {RUST_IMAGE}
 RUN apt-get update -qq && apt-get install -y -qq pkg-config libssl-dev ca-certificates && apt-get clean
 
-ARG DEPENDENCIES
-
 {SET_USER_AND_HOME_DIR}
 
 RUN cargo init --name temp
-RUN if [ -n "$DEPENDENCIES" ]; then cargo add $DEPENDENCIES; fi
 RUN cargo build
+"#
+);
+
+const RUST_DOCKERFILE: &str = formatcp!(
+    r#"
+{FROM_BASE_IMAGE}
+
+RUN if [ -n "$DEPENDENCIES" ]; then cargo add $DEPENDENCIES && cargo build; fi
 
 COPY --chown=1000:1000 main.rs src/
 RUN touch src/main.rs
@@ -124,12 +202,10 @@ CMD ["./target/debug/temp"]
 "#
 );
 
-const GO_DOCKERFILE: &str = formatcp!(
+const GO_BASE_DOCKERFILE: &str = formatcp!(
     r#"
 FROM {GO_IMAGE}
 
-ARG DEPENDENCIES
-
 ENV GOTMPDIR=/opt/gotmpdir GOCACHE=/opt/gocache
 RUN mkdir -p /opt/gotmpdir && chown 1000:1000 /opt/gotmpdir
 RUN mkdir -p /opt/gocache && chown 1000:1000 /opt/gocache
@@ -137,6 +213,13 @@ RUN mkdir -p /opt/gocache && chown 1000:1000 /opt/gocache
 {SET_USER_AND_HOME_DIR}
 
 RUN go mod init temp
+"#
+);
+
+const GO_DOCKERFILE: &str = formatcp!(
+    r#"
+{FROM_BASE_IMAGE}
+
 RUN if [ -n "$DEPENDENCIES" ]; then go get $DEPENDENCIES; fi
 
 COPY main.go .
@@ -146,17 +229,172 @@ CMD ["./temp"]
 "#
 );
 
-const BASH_DOCKERFILE: &str = formatcp!(
+const BASH_BASE_DOCKERFILE: &str = formatcp!(
     r#"
 FROM {BASH_IMAGE}
 
-ARG DEPENDENCIES
-RUN if [ -n "$DEPENDENCIES" ]; then apk add --no-cache $DEPENDENCIES; fi
-
 {SET_USER_AND_HOME_DIR}
+"#
+);
+
+const BASH_DOCKERFILE: &str = formatcp!(
+    r#"
+{FROM_BASE_IMAGE}
+
+USER root
+RUN if [ -n "$DEPENDENCIES" ]; then apk add --no-cache $DEPENDENCIES; fi
+USER 1000:1000
 
 COPY script.sh .
 
 CMD ["bash", "script.sh"]
 "#
 );
+
+const RUBY_BASE_DOCKERFILE: &str = formatcp!(
+    r#"
+FROM {RUBY_IMAGE}
+
+ENV GEM_HOME="/opt/gems"
+ENV PATH="/opt/gems/bin:$PATH"
+
+RUN mkdir -p /opt/gems && chown 1000:1000 /opt/gems
+
+{SET_USER_AND_HOME_DIR}
+"#
+);
+
+const RUBY_DOCKERFILE: &str = formatcp!(
+    r#"
+{FROM_BASE_IMAGE}
+
+RUN if [ -n "$DEPENDENCIES" ]; then gem install --no-document $DEPENDENCIES; fi
+
+COPY main.rb .
+
+CMD ["ruby", "main.rb"]
+"#
+);
+
+const PHP_BASE_DOCKERFILE: &str = formatcp!(
+    r#"
+FROM {PHP_IMAGE}
+RUN apt-get update -qq && apt-get install -y -qq git unzip && apt-get clean
+COPY --from=composer:2 /usr/bin/composer /usr/bin/composer
+
+{SET_USER_AND_HOME_DIR}
+
+RUN composer init -n --name temp/temp && composer dump-autoload
+"#
+);
+
+const PHP_DOCKERFILE: &str = formatcp!(
+    r#"
+{FROM_BASE_IMAGE}
+
+RUN if [ -n "$DEPENDENCIES" ]; then composer require -n $DEPENDENCIES; fi
+
+COPY main.php .
+
+CMD ["sh", "-c", "{PHP_CMD}"]
+"#
+);
+
+const JAVA_BASE_DOCKERFILE: &str = formatcp!(
+    r#"
+FROM {JAVA_IMAGE}
+RUN apt-get update -qq && apt-get install -y -qq curl && apt-get clean
+
+RUN mkdir -p /opt/jars && chown 1000:1000 /opt/jars
+
+{SET_USER_AND_HOME_DIR}
+"#
+);
+
+/// Dependencies are Maven coordinates (`group:artifact:version`), downloaded from Maven Central
+/// without their transitive dependencies
+const JAVA_DOCKERFILE: &str = formatcp!(
+    r#"
+{FROM_BASE_IMAGE}
+
+RUN for dep in $DEPENDENCIES; do \
+        group=$(echo "$dep" | cut -d: -f1 | tr . /); \
+        artifact=$(echo "$dep" | cut -d: -f2); \
+        version=$(echo "$dep" | cut -d: -f3); \
+        curl -fsSL -o "/opt/jars/$artifact-$version.jar" \
+            "https://repo1.maven.org/maven2/$group/$artifact/$version/$artifact-$version.jar" \
+            || exit 1; \
+    done
+
+COPY Main.java .
+
+CMD ["sh", "-c", "java -cp '/opt/jars/*' Main.java"]
+"#
+);
+
+const C_BASE_DOCKERFILE: &str = formatcp!(
+    r#"
+FROM {C_IMAGE}
+
+{SET_USER_AND_HOME_DIR}
+"#
+);
+
+const C_DOCKERFILE: &str = formatcp!(
+    r#"
+{FROM_BASE_IMAGE}
+
+USER root
+RUN if [ -n "$DEPENDENCIES" ]; then \
+        apt-get update -qq && apt-get install -y -qq $DEPENDENCIES && apt-get clean; \
+    fi
+USER 1000:1000
+
+COPY main.c .
+RUN gcc -O2 -Wall -o main main.c -lm
+
+CMD ["./main"]
+"#
+);
+
+const CPP_DOCKERFILE: &str = formatcp!(
+    r#"
+{FROM_BASE_IMAGE}
+
+USER root
+RUN if [ -n "$DEPENDENCIES" ]; then \
+        apt-get update -qq && apt-get install -y -qq $DEPENDENCIES && apt-get clean; \
+    fi
+USER 1000:1000
+
+COPY main.cpp .
+RUN g++ -O2 -Wall -std=c++20 -o main main.cpp
+
+CMD ["./main"]
+"#
+);
+
+const R_BASE_DOCKERFILE: &str = formatcp!(
+    r#"
+FROM {R_IMAGE}
+
+ENV R_LIBS_USER="/opt/R"
+RUN mkdir -p /opt/R && chown 1000:1000 /opt/R
+
+{SET_USER_AND_HOME_DIR}
+"#
+);
+
+const R_DOCKERFILE: &str = formatcp!(
+    r#"
+{FROM_BASE_IMAGE}
+
+RUN if [ -n "$DEPENDENCIES" ]; then \
+        Rscript -e 'install.packages(commandArgs(TRUE), lib = Sys.getenv("R_LIBS_USER"), repos = "https://cloud.r-project.org")' $DEPENDENCIES; \
+    fi
+
+COPY main.R .
+
+CMD ["Rscript", "main.R"]
+"#
+);
@@ -0,0 +1,260 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use wasmtime::{Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, Trap};
+use wasmtime_wasi::{
+    pipe::{MemoryInputPipe, MemoryOutputPipe},
+    preview1::{self, WasiP1Ctx},
+    DirPerms, FilePerms, I32Exit, WasiCtxBuilder,
+};
+
+use crate::{
+    tools::{
+        core::{ToolLog, ToolResult},
+        system::code_runner::CodeLanguage,
+        ToolError,
+    },
+    utils::SenderWithLogging,
+};
+
+/// Interval between epoch ticks, used to interrupt code running past its timeout
+const EPOCH_TICK: Duration = Duration::from_millis(100);
+/// Max size of stdout and stderr, in bytes
+const MAX_OUTPUT_SIZE: usize = 1024 * 1024;
+
+/// Shared engine with fuel metering and epoch interruption enabled
+static ENGINE: LazyLock<Result<Engine, String>> = LazyLock::new(|| {
+    let mut config = Config::new();
+    config.consume_fuel(true).epoch_interruption(true);
+    let engine = Engine::new(&config).map_err(|e| e.to_string())?;
+    let ticker = engine.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(EPOCH_TICK);
+        ticker.increment_epoch();
+    });
+    Ok(engine)
+});
+
+/// Compiled runtime modules, keyed by file path
+static MODULES: LazyLock<Mutex<HashMap<PathBuf, Module>>> = LazyLock::new(Default::default);
+
+struct WasmState {
+    wasi: WasiP1Ctx,
+    limits: StoreLimits,
+}
+
+/// Runs Python and JavaScript code in a WebAssembly sandbox, without Docker. The code has
+/// no network access, and can only read the runtime's standard library.
+pub struct WasmExecutor {
+    lang: CodeLanguage,
+    timeout_seconds: u32,
+    memory_limit_mb: u32,
+    fuel: u64,
+    stdin: Option<String>,
+    args: Vec<String>,
+    runtimes_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Default)]
+pub struct WasmExecutorOptions {
+    pub timeout_seconds: u32,
+    pub memory_limit_mb: u32,
+    /// Max number of instructions (approximately) executed by the code, in millions
+    pub fuel_millions: u32,
+    /// Input written to the program's stdin
+    pub stdin: Option<String>,
+    /// Command-line arguments passed to the program
+    pub args: Vec<String>,
+    /// Directory of the WebAssembly runtimes: `python.wasm` (CPython WASI build, with its
+    /// standard library in `lib/`) and `qjs.wasm` (QuickJS WASI build)
+    pub runtimes_path: Option<PathBuf>,
+}
+
+/// Outcome of running the code
+enum WasmExit {
+    Success,
+    ExitCode(i32),
+    OutOfFuel,
+    TimedOut,
+}
+
+impl WasmExecutor {
+    pub fn new(lang: CodeLanguage, options: WasmExecutorOptions) -> Self {
+        WasmExecutor {
+            lang,
+            timeout_seconds: options.timeout_seconds,
+            memory_limit_mb: options.memory_limit_mb,
+            fuel: u64::from(options.fuel_millions) * 1_000_000,
+            stdin: options.stdin,
+            args: options.args,
+            runtimes_path: options.runtimes_path,
+        }
+    }
+
+    pub async fn execute(
+        &self,
+        code: &str,
+        dependencies: &[String],
+        tx: &SenderWithLogging<ToolLog>,
+    ) -> ToolResult<String> {
+        if !dependencies.is_empty() {
+            return Err(ToolError::InvalidParameters(
+                "Dependencies can't be installed in the WebAssembly sandbox".into(),
+            ));
+        }
+        let root = self.runtimes_path.as_ref().ok_or_else(|| {
+            ToolError::ToolExecutionError(
+                "WebAssembly runtimes aren't configured (set RS_CHAT_CODE_RUNNER_WASM_PATH)".into(),
+            )
+        })?;
+        let (module_path, mut args, lib_dir) = match self.lang {
+            CodeLanguage::Python => (
+                root.join("python.wasm"),
+                vec!["python".into(), "-c".into(), code.to_owned()],
+                Some(root.join("lib")),
+            ),
+            CodeLanguage::JavaScript => (
+                root.join("qjs.wasm"),
+                vec!["qjs".into(), "--std".into(), "-e".into(), code.to_owned()],
+                None,
+            ),
+            _ => {
+                return Err(ToolError::InvalidParameters(
+                    "Only Python and JavaScript are supported in the WebAssembly sandbox".into(),
+                ))
+            }
+        };
+
+        args.extend(self.args.iter().cloned());
+
+        let _ = tx
+            .send(ToolLog::Log(
+                "Running code in WebAssembly sandbox...".into(),
+            ))
+            .await;
+        let stdout = MemoryOutputPipe::new(MAX_OUTPUT_SIZE);
+        let stderr = MemoryOutputPipe::new(MAX_OUTPUT_SIZE);
+        let (stdout_pipe, stderr_pipe) = (stdout.clone(), stderr.clone());
+        let stdin = MemoryInputPipe::new(self.stdin.clone().unwrap_or_default());
+        let limits = (
+            self.fuel,
+            self.memory_limit_mb as usize * 1024 * 1024,
+            Duration::from_secs(self.timeout_seconds.into()),
+        );
+        let exit = tokio::task::spawn_blocking(move || {
+            run_module(
+                &module_path,
+                args,
+                lib_dir.as_deref(),
+                stdin,
+                stdout_pipe,
+                stderr_pipe,
+                limits,
+            )
+        })
+        .await
+        .map_err(|e| ToolError::ToolExecutionError(e.to_string()))??;
+
+        let stdout = String::from_utf8_lossy(&stdout.contents()).into_owned();
+        let stderr = String::from_utf8_lossy(&stderr.contents()).into_owned();
+        let output_text = format!("Output (stdout):\n\n{stdout}\n\nLogs (stderr):\n\n{stderr}\n");
+        let output_markdown =
+            format!("## Output (stdout):\n```text\n{stdout}\n```\n## Logs (stderr):\n```text\n{stderr}\n```\n");
+        let error_message = match exit {
+            WasmExit::Success | WasmExit::ExitCode(0) => {
+                return Ok(format!(
+                    "✅ Code executed successfully!\n\n{output_markdown}"
+                ))
+            }
+            WasmExit::ExitCode(code) => format!("Code execution failed with exit status {code}"),
+            WasmExit::OutOfFuel => "Code execution exceeded the instruction limit".into(),
+            WasmExit::TimedOut => "Code execution timed out".into(),
+        };
+        let _ = tx.send(ToolLog::Error(error_message.clone())).await;
+        Err(ToolError::ToolExecutionError(format!(
+            "❌ {error_message}.\n\n{output_text}"
+        )))
+    }
+}
+
+/// Run the WASI module with the given arguments and limits (fuel, memory in bytes, and timeout)
+fn run_module(
+    module_path: &Path,
+    args: Vec<String>,
+    lib_dir: Option<&Path>,
+    stdin: MemoryInputPipe,
+    stdout: MemoryOutputPipe,
+    stderr: MemoryOutputPipe,
+    (fuel, memory_limit, timeout): (u64, usize, Duration),
+) -> ToolResult<WasmExit> {
+    let engine = ENGINE
+        .as_ref()
+        .inspect_err(|e| rocket::error!("Failed to initialize WebAssembly engine: {}", e))
+        .map_err(|_| ToolError::ToolExecutionError("Failed to initialize WebAssembly".into()))?;
+    let module = load_module(engine, module_path)?;
+
+    let mut wasi = WasiCtxBuilder::new();
+    wasi.args(&args).stdin(stdin).stdout(stdout).stderr(stderr);
+    if let Some(lib_dir) = lib_dir {
+        wasi.preopened_dir(lib_dir, "/usr/local/lib", DirPerms::READ, FilePerms::READ)
+            .map_err(|e| ToolError::ToolExecutionError(e.to_string()))?;
+    }
+    let state = WasmState {
+        wasi: wasi.build_p1(),
+        limits: StoreLimitsBuilder::new()
+            .memory_size(memory_limit)
+            .instances(1)
+            .build(),
+    };
+
+    let mut store = Store::new(engine, state);
+    store.limiter(|state| &mut state.limits);
+    store
+        .set_fuel(fuel)
+        .map_err(|e| ToolError::ToolExecutionError(e.to_string()))?;
+    store.set_epoch_deadline((timeout.as_millis() / EPOCH_TICK.as_millis()) as u64 + 1);
+
+    let mut linker: Linker<WasmState> = Linker::new(engine);
+    preview1::add_to_linker_sync(&mut linker, |state| &mut state.wasi)
+        .map_err(|e| ToolError::ToolExecutionError(e.to_string()))?;
+    let start = linker
+        .instantiate(&mut store, &module)
+        .and_then(|instance| instance.get_typed_func::<(), ()>(&mut store, "_start"))
+        .map_err(|e| ToolError::ToolExecutionError(format!("Failed to start runtime: {e}")))?;
+
+    match start.call(&mut store, ()) {
+        Ok(()) => Ok(WasmExit::Success),
+        Err(err) => {
+            if let Some(exit) = err.downcast_ref::<I32Exit>() {
+                return Ok(WasmExit::ExitCode(exit.0));
+            }
+            match err.downcast_ref::<Trap>() {
+                Some(Trap::OutOfFuel) => Ok(WasmExit::OutOfFuel),
+                Some(Trap::Interrupt) => Ok(WasmExit::TimedOut),
+                _ => Err(ToolError::ToolExecutionError(format!(
+                    "Code execution failed: {err}"
+                ))),
+            }
+        }
+    }
+}
+
+/// Get the compiled module, compiling it on first use
+fn load_module(engine: &Engine, path: &Path) -> ToolResult<Module> {
+    let mut modules = MODULES.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(module) = modules.get(path) {
+        return Ok(module.clone());
+    }
+    let module = Module::from_file(engine, path).map_err(|e| {
+        ToolError::ToolExecutionError(format!(
+            "Failed to load WebAssembly runtime '{}': {e}",
+            path.display()
+        ))
+    })?;
+    modules.insert(path.to_owned(), module.clone());
+    Ok(module)
+}
@@ -0,0 +1,294 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+use chrono::{
+    DateTime, Datelike, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Utc,
+};
+use chrono_tz::Tz;
+use rocket::async_trait;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    provider::{LlmTool, LlmToolType},
+    tools::{
+        core::{ToolLog, ToolParameters, ToolResponseFormat, ToolResult, ToolStorage},
+        system::{SystemTool, SystemToolConfig},
+        utils::get_json_schema,
+        ToolError,
+    },
+    utils::SenderWithLogging,
+};
+
+const CURRENT_TIME_NAME: &str = "current_time";
+const CURRENT_TIME_DESC: &str = "Get the current date and time, optionally in a timezone. \
+    Use this whenever you need today's date or the current time, instead of guessing.";
+
+const CONVERT_TIMEZONE_NAME: &str = "convert_timezone";
+const CONVERT_TIMEZONE_DESC: &str = "Convert a date and time from one timezone to another, \
+    e.g. `2025-03-14 15:30` from `America/New_York` to `Asia/Tokyo`. \
+    Accounts for daylight saving time.";
+
+const DATE_DIFF_NAME: &str = "date_diff";
+const DATE_DIFF_DESC: &str = "Calculate the time between two dates or date-times, \
+    e.g. the number of days until a date, or someone's age. Use `now` for the current time.";
+
+/// Date and time formats accepted without a UTC offset, in the order they're tried
+const NAIVE_DATE_TIME_FORMATS: [&str; 4] = [
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+const DATE_FORMAT: &str = "%Y-%m-%d";
+/// Format of the returned date-times, e.g. `Friday, 2025-03-14 15:30:00 EDT (UTC-04:00)`
+const OUTPUT_FORMAT: &str = "%A, %Y-%m-%d %H:%M:%S %Z (UTC%:z)";
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct CurrentTimeInput {
+    /// IANA timezone name, e.g. `Europe/Paris` (default: UTC)
+    timezone: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ConvertTimezoneInput {
+    /// Date and time to convert, e.g. `2025-03-14 15:30`, `2025-03-14T15:30:00Z`, or `now`.
+    /// A UTC offset in the date-time takes precedence over `from_timezone`.
+    datetime: String,
+    /// IANA timezone name of the date and time, e.g. `America/New_York` (default: UTC)
+    from_timezone: Option<String>,
+    /// IANA timezone name to convert to, e.g. `Asia/Tokyo`
+    to_timezone: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct DateDiffInput {
+    /// Start date or date-time, e.g. `2025-01-01`, `2025-01-01 09:00`, or `now`
+    start: String,
+    /// End date or date-time, e.g. `2025-12-25`, `2025-12-25T18:00:00+01:00`, or `now`
+    end: String,
+    /// IANA timezone name of dates and date-times without a UTC offset, and of `now`
+    /// (default: UTC)
+    timezone: Option<String>,
+}
+
+static INPUT_SCHEMAS: LazyLock<HashMap<&'static str, serde_json::Value>> = LazyLock::new(|| {
+    HashMap::from([
+        (CURRENT_TIME_NAME, get_json_schema::<CurrentTimeInput>()),
+        (
+            CONVERT_TIMEZONE_NAME,
+            get_json_schema::<ConvertTimezoneInput>(),
+        ),
+        (DATE_DIFF_NAME, get_json_schema::<DateDiffInput>()),
+    ])
+});
+
+/// Tool to get the current date and time, convert between timezones, and calculate the time
+/// between dates. Requires no configuration.
+#[derive(Debug)]
+pub struct DateTimeTool {}
+impl DateTimeTool {
+    pub fn new() -> Self {
+        DateTimeTool {}
+    }
+}
+
+pub struct DateTimeConfig {}
+impl SystemToolConfig for DateTimeConfig {
+    type DynamicConfig = ();
+
+    fn get_llm_tools(&self, tool_id: Uuid, _input_config: Option<()>) -> Vec<LlmTool> {
+        [
+            (CURRENT_TIME_NAME, CURRENT_TIME_DESC),
+            (CONVERT_TIMEZONE_NAME, CONVERT_TIMEZONE_DESC),
+            (DATE_DIFF_NAME, DATE_DIFF_DESC),
+        ]
+        .into_iter()
+        .map(|(name, description)| LlmTool {
+            tool_id,
+            name: name.into(),
+            description: description.into(),
+            input_schema: INPUT_SCHEMAS[name].to_owned(),
+            tool_type: LlmToolType::System,
+        })
+        .collect()
+    }
+
+    fn validate(&self) -> ToolResult<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SystemTool for DateTimeTool {
+    fn input_schema(&self, tool_name: &str) -> &serde_json::Value {
+        INPUT_SCHEMAS
+            .get(tool_name)
+            .unwrap_or(&INPUT_SCHEMAS[CURRENT_TIME_NAME])
+    }
+
+    async fn execute(
+        &self,
+        tool_name: &str,
+        parameters: &ToolParameters,
+        _storage: &ToolStorage,
+        _tx: &SenderWithLogging<ToolLog>,
+    ) -> ToolResult<(String, ToolResponseFormat)> {
+        let parameters = serde_json::to_value(parameters)?;
+        let result = match tool_name {
+            CURRENT_TIME_NAME => {
+                let input: CurrentTimeInput = serde_json::from_value(parameters)?;
+                current_time(input.timezone.as_deref())?
+            }
+            CONVERT_TIMEZONE_NAME => {
+                let input: ConvertTimezoneInput = serde_json::from_value(parameters)?;
+                convert_timezone(&input)?
+            }
+            DATE_DIFF_NAME => {
+                let input: DateDiffInput = serde_json::from_value(parameters)?;
+                date_diff(&input)?
+            }
+            _ => return Err(ToolError::ToolNotFound),
+        };
+
+        Ok((result, ToolResponseFormat::Text))
+    }
+}
+
+fn current_time(timezone: Option<&str>) -> ToolResult<String> {
+    let tz = parse_timezone(timezone)?;
+    let now = Utc::now().with_timezone(&tz);
+    Ok(format!(
+        "{} in {tz}\nRFC3339: {}\nUnix timestamp: {}",
+        now.format(OUTPUT_FORMAT),
+        now.to_rfc3339(),
+        now.timestamp()
+    ))
+}
+
+fn convert_timezone(input: &ConvertTimezoneInput) -> ToolResult<String> {
+    let from_tz = parse_timezone(input.from_timezone.as_deref())?;
+    let to_tz = parse_timezone(Some(&input.to_timezone))?;
+    let (datetime, _) = parse_datetime(&input.datetime, from_tz)?;
+    let converted = datetime.with_timezone(&to_tz);
+    Ok(format!(
+        "{} in {from_tz}\n= {} in {to_tz}\nRFC3339: {}",
+        datetime.format(OUTPUT_FORMAT),
+        converted.format(OUTPUT_FORMAT),
+        converted.to_rfc3339()
+    ))
+}
+
+fn date_diff(input: &DateDiffInput) -> ToolResult<String> {
+    let tz = parse_timezone(input.timezone.as_deref())?;
+    let (start, start_is_date) = parse_datetime(&input.start, tz)?;
+    let (end, end_is_date) = parse_datetime(&input.end, tz)?;
+    let (earlier, later, sign) = match end >= start {
+        true => (start, end, ""),
+        false => (end, start, "-"),
+    };
+    let (years, months, rest) = calendar_diff(earlier.naive_local(), later.naive_local());
+    let total = later - earlier;
+
+    let mut lines = vec![format!(
+        "From {} to {}",
+        start.format(OUTPUT_FORMAT),
+        end.format(OUTPUT_FORMAT)
+    )];
+    match start_is_date && end_is_date {
+        true => lines.push(format!(
+            "Difference: {sign}{years} years, {months} months, {} days",
+            rest.num_days()
+        )),
+        false => lines.push(format!(
+            "Difference: {sign}{years} years, {months} months, {} days, {} hours, {} minutes, \
+            {} seconds",
+            rest.num_days(),
+            rest.num_hours() % 24,
+            rest.num_minutes() % 60,
+            rest.num_seconds() % 60
+        )),
+    }
+    lines.push(format!(
+        "Total: {sign}{} days ({sign}{} weeks and {} days)",
+        total.num_days(),
+        total.num_weeks(),
+        total.num_days() % 7
+    ));
+    if !start_is_date || !end_is_date {
+        lines.push(format!(
+            "Total: {sign}{} hours = {sign}{} minutes = {sign}{} seconds",
+            total.num_hours(),
+            total.num_minutes(),
+            total.num_seconds()
+        ));
+    }
+
+    Ok(lines.join("\n"))
+}
+
+/// Parse an IANA timezone name, defaulting to UTC
+fn parse_timezone(name: Option<&str>) -> ToolResult<Tz> {
+    match name.map(str::trim) {
+        None | Some("") => Ok(Tz::UTC),
+        Some(name) => name.parse::<Tz>().map_err(|_| {
+            ToolError::InvalidParameters(format!(
+                "Unknown timezone '{name}'. Use an IANA timezone name, e.g. `America/New_York`"
+            ))
+        }),
+    }
+}
+
+/// Parse a date-time (RFC3339 or without an offset), a date, or `now`, in the given timezone
+/// if it has no UTC offset. Also returns whether the value is a date without a time.
+fn parse_datetime(value: &str, tz: Tz) -> ToolResult<(DateTime<Tz>, bool)> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("now") {
+        return Ok((Utc::now().with_timezone(&tz), false));
+    }
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Ok((datetime.with_timezone(&tz), false));
+    }
+    let (naive, is_date) = NAIVE_DATE_TIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(|naive| (naive, false))
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, DATE_FORMAT)
+                .ok()
+                .map(|date| (date.and_time(NaiveTime::MIN), true))
+        })
+        .ok_or_else(|| {
+            ToolError::InvalidParameters(format!(
+                "Invalid date or date-time '{value}'. Use a format like `2025-03-14`, \
+                `2025-03-14 15:30`, or `2025-03-14T15:30:00Z`"
+            ))
+        })?;
+
+    // Use the earlier time if ambiguous (e.g. when the clocks go back)
+    let datetime = tz.from_local_datetime(&naive).earliest().ok_or_else(|| {
+        ToolError::InvalidParameters(format!(
+            "'{value}' does not exist in {tz} (e.g. skipped when the clocks go forward)"
+        ))
+    })?;
+    Ok((datetime, is_date))
+}
+
+/// Calendar difference between two local date-times, in whole years and months, and the
+/// remaining time
+fn calendar_diff(earlier: NaiveDateTime, later: NaiveDateTime) -> (u32, u32, TimeDelta) {
+    let add_months = |months: u32| earlier.checked_add_months(Months::new(months));
+    let mut months = ((later.year() - earlier.year()) * 12 + later.month() as i32
+        - earlier.month() as i32)
+        .max(0) as u32;
+    // Don't count the last month if it's incomplete
+    while months > 0 && add_months(months).is_none_or(|anchor| anchor > later) {
+        months -= 1;
+    }
+    let rest = add_months(months).map_or(later - earlier, |anchor| later - anchor);
+
+    (months / 12, months % 12, rest)
+}
@@ -0,0 +1,150 @@
+use std::sync::LazyLock;
+
+use rocket::async_trait;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    provider::{LlmTool, LlmToolType},
+    tools::{
+        core::{
+            get_tool_file_path, ToolLog, ToolParameters, ToolResponseFormat, ToolResult,
+            ToolStorage,
+        },
+        system::{SystemTool, SystemToolConfig},
+        utils::get_json_schema,
+        ToolError,
+    },
+    utils::SenderWithLogging,
+};
+
+const LIST_NAME: &str = "list_files";
+const LIST_DESC: &str = "List the files and directories in the user's files, e.g. files \
+    uploaded by the user or generated by tools. Returns the path, size (in bytes), and whether \
+    each entry is a directory.";
+const READ_NAME: &str = "read_file";
+const READ_DESC: &str = "Read the text content of one of the user's files.";
+const WRITE_NAME: &str = "write_file";
+const WRITE_DESC: &str = "Write text content to a file in the `files` directory of the \
+    user's files, creating any parent directories. Replaces the file if it already exists.";
+/// Max size of a file that can be read, in bytes
+const MAX_READ_SIZE: u64 = 200_000;
+/// Max size of a file that can be written, in bytes
+const MAX_WRITE_SIZE: usize = 1_000_000;
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ListInput {
+    /// Path of the directory to list, relative to the user's files (e.g. `notes`). Lists
+    /// the top-level directory if not set.
+    path: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ReadInput {
+    /// Path of the file, relative to the user's files (e.g. `notes/todo.md`)
+    path: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct WriteInput {
+    /// Path of the file, relative to the `files` directory (e.g. `notes/todo.md`)
+    path: String,
+    /// The text content of the file
+    content: String,
+}
+
+static LIST_INPUT_SCHEMA: LazyLock<serde_json::Value> =
+    LazyLock::new(|| get_json_schema::<ListInput>());
+static READ_INPUT_SCHEMA: LazyLock<serde_json::Value> =
+    LazyLock::new(|| get_json_schema::<ReadInput>());
+static WRITE_INPUT_SCHEMA: LazyLock<serde_json::Value> =
+    LazyLock::new(|| get_json_schema::<WriteInput>());
+
+/// Tool to list, read, and write the user's stored files.
+#[derive(Debug)]
+pub struct Files {}
+impl Files {
+    pub fn new() -> Self {
+        Files {}
+    }
+}
+
+pub struct FilesConfig {}
+impl SystemToolConfig for FilesConfig {
+    type DynamicConfig = ();
+
+    fn get_llm_tools(&self, tool_id: Uuid, _input_config: Option<()>) -> Vec<LlmTool> {
+        [
+            (LIST_NAME, LIST_DESC, &*LIST_INPUT_SCHEMA),
+            (READ_NAME, READ_DESC, &*READ_INPUT_SCHEMA),
+            (WRITE_NAME, WRITE_DESC, &*WRITE_INPUT_SCHEMA),
+        ]
+        .into_iter()
+        .map(|(name, description, input_schema)| LlmTool {
+            name: name.into(),
+            description: description.into(),
+            input_schema: input_schema.to_owned(),
+            tool_id,
+            tool_type: LlmToolType::System,
+        })
+        .collect()
+    }
+
+    fn validate(&self) -> ToolResult<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SystemTool for Files {
+    fn input_schema(&self, tool_name: &str) -> &serde_json::Value {
+        match tool_name {
+            READ_NAME => &READ_INPUT_SCHEMA,
+            WRITE_NAME => &WRITE_INPUT_SCHEMA,
+            _ => &LIST_INPUT_SCHEMA,
+        }
+    }
+
+    async fn execute(
+        &self,
+        tool_name: &str,
+        parameters: &ToolParameters,
+        storage: &ToolStorage,
+        _tx: &SenderWithLogging<ToolLog>,
+    ) -> ToolResult<(String, ToolResponseFormat)> {
+        let parameters = serde_json::to_value(parameters)?;
+        match tool_name {
+            LIST_NAME => {
+                let input: ListInput = serde_json::from_value(parameters)?;
+                let files = storage
+                    .list_files(input.path.as_deref().unwrap_or_default())
+                    .await?;
+                Ok((serde_json::to_string(&files)?, ToolResponseFormat::Json))
+            }
+            READ_NAME => {
+                let input: ReadInput = serde_json::from_value(parameters)?;
+                let data = storage.read_file(&input.path, MAX_READ_SIZE).await?;
+                let content = String::from_utf8(data)
+                    .map_err(|_| ToolError::ToolExecutionError("File is not a text file".into()))?;
+                Ok((content, ToolResponseFormat::Text))
+            }
+            WRITE_NAME => {
+                let input: WriteInput = serde_json::from_value(parameters)?;
+                if input.content.len() > MAX_WRITE_SIZE {
+                    return Err(ToolError::InvalidParameters(format!(
+                        "Content is too large (max {MAX_WRITE_SIZE} bytes)"
+                    )));
+                }
+                let path = get_tool_file_path(&input.path)?;
+                storage.write_file(&path, input.content.as_bytes()).await?;
+                let response = format!("Wrote {} bytes to {path}", input.content.len());
+                Ok((response, ToolResponseFormat::Text))
+            }
+            _ => Err(ToolError::ToolNotFound),
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use std::sync::LazyLock;
+
+use rocket::async_trait;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    provider::{LlmTool, LlmToolType},
+    tools::{
+        core::{ToolLog, ToolParameters, ToolResponseFormat, ToolResult, ToolStorage},
+        system::{SystemTool, SystemToolConfig},
+        utils::{get_json_schema, get_tool_description},
+        ToolError,
+    },
+    utils::SenderWithLogging,
+};
+
+const LIST_NAME: &str = "knowledge_bases";
+const LIST_DESCRIPTION: &str = "List the user's knowledge bases of documents, \
+    with their IDs and descriptions.";
+const SEARCH_NAME: &str = "knowledge_search";
+const SEARCH_DESCRIPTION: &str = "Search the user's knowledge bases for passages of their \
+    documents relevant to the query. Use this to answer questions about the user's own \
+    documents and notes. Matches are sorted by similarity to the query (lower distance is \
+    more similar).";
+const DEFAULT_MAX_RESULTS: u8 = 5;
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ListInput {}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct SearchInput {
+    /// The search query, describing the information to find.
+    query: String,
+    /// ID of the knowledge base to search. Searches all knowledge bases if not set.
+    knowledge_base_id: Option<Uuid>,
+}
+
+static LIST_INPUT_SCHEMA: LazyLock<serde_json::Value> =
+    LazyLock::new(|| get_json_schema::<ListInput>());
+static SEARCH_INPUT_SCHEMA: LazyLock<serde_json::Value> =
+    LazyLock::new(|| get_json_schema::<SearchInput>());
+
+/// Configuration for the knowledge search tool.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct KnowledgeSearchConfig {
+    /// Max number of passages returned for a search.
+    #[serde(default = "default_max_results")]
+    #[validate(range(min = 1, max = 20))]
+    pub max_results: u8,
+    /// Custom description of the search tool for the LLM (uses the default description if
+    /// not set)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(length(max = 1024))]
+    pub description: Option<String>,
+}
+fn default_max_results() -> u8 {
+    DEFAULT_MAX_RESULTS
+}
+
+/// Tool to search the user's knowledge bases.
+#[derive(Debug)]
+pub struct KnowledgeSearch<'a> {
+    config: &'a KnowledgeSearchConfig,
+}
+impl<'a> KnowledgeSearch<'a> {
+    pub fn new(config: &'a KnowledgeSearchConfig) -> Self {
+        KnowledgeSearch { config }
+    }
+}
+
+/// A passage matching the search, as returned to the LLM
+#[derive(Serialize)]
+struct KnowledgeSearchMatch<'a> {
+    knowledge_base: &'a str,
+    document: String,
+    content: String,
+    distance: f64,
+}
+
+#[derive(Serialize)]
+struct KnowledgeBaseInfo<'a> {
+    id: Uuid,
+    name: &'a str,
+    description: Option<&'a str>,
+}
+
+impl SystemToolConfig for KnowledgeSearchConfig {
+    type DynamicConfig = ();
+
+    fn validate(&self) -> ToolResult<()> {
+        let config_schema = serde_json::to_value(schemars::schema_for!(Self))?;
+        jsonschema::validate(&config_schema, &serde_json::to_value(self)?)
+            .map_err(|e| ToolError::InvalidConfiguration(e.to_string()))
+    }
+
+    fn get_llm_tools(&self, tool_id: Uuid, _input_config: Option<()>) -> Vec<LlmTool> {
+        vec![
+            LlmTool {
+                name: LIST_NAME.into(),
+                description: LIST_DESCRIPTION.into(),
+                input_schema: LIST_INPUT_SCHEMA.to_owned(),
+                tool_id,
+                tool_type: LlmToolType::System,
+            },
+            LlmTool {
+                name: SEARCH_NAME.into(),
+                description: get_tool_description(self.description.as_deref(), SEARCH_DESCRIPTION),
+                input_schema: SEARCH_INPUT_SCHEMA.to_owned(),
+                tool_id,
+                tool_type: LlmToolType::System,
+            },
+        ]
+    }
+}
+
+#[async_trait]
+impl SystemTool for KnowledgeSearch<'_> {
+    fn input_schema(&self, tool_name: &str) -> &serde_json::Value {
+        match tool_name {
+            LIST_NAME => &LIST_INPUT_SCHEMA,
+            _ => &SEARCH_INPUT_SCHEMA,
+        }
+    }
+
+    async fn execute(
+        &self,
+        tool_name: &str,
+        parameters: &ToolParameters,
+        storage: &ToolStorage,
+        tx: &SenderWithLogging<ToolLog>,
+    ) -> ToolResult<(String, ToolResponseFormat)> {
+        let knowledge_bases = storage.list_knowledge_bases().await?;
+        match tool_name {
+            LIST_NAME => {
+                let infos: Vec<KnowledgeBaseInfo> = knowledge_bases
+                    .iter()
+                    .map(|kb| KnowledgeBaseInfo {
+                        id: kb.id,
+                        name: &kb.name,
+                        description: kb.description.as_deref(),
+                    })
+                    .collect();
+                Ok((serde_json::to_string(&infos)?, ToolResponseFormat::Json))
+            }
+            SEARCH_NAME => {
+                let input: SearchInput = serde_json::from_value(serde_json::to_value(parameters)?)?;
+                let searched: Vec<_> = knowledge_bases
+                    .iter()
+                    .filter(|kb| input.knowledge_base_id.map_or(true, |id| id == kb.id))
+                    .collect();
+                if searched.is_empty() {
+                    return Err(ToolError::ToolExecutionError(
+                        "Knowledge base not found".into(),
+                    ));
+                }
+
+                let mut matches = Vec::with_capacity(self.config.max_results as usize);
+                for kb in searched {
+                    let message = format!("Searching knowledge base '{}'...", kb.name);
+                    let _ = tx.send(ToolLog::Log(message)).await;
+                    let results = storage
+                        .search_knowledge(&kb.id, &input.query, self.config.max_results)
+                        .await?;
+                    matches.extend(results.into_iter().map(|result| KnowledgeSearchMatch {
+                        knowledge_base: &kb.name,
+                        document: result.document_name,
+                        content: result.content,
+                        distance: result.distance,
+                    }));
+                }
+                matches.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+                matches.truncate(self.config.max_results as usize);
+
+                Ok((serde_json::to_string(&matches)?, ToolResponseFormat::Json))
+            }
+            _ => Err(ToolError::ToolNotFound),
+        }
+    }
+}
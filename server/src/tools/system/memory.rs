@@ -0,0 +1,201 @@
+use std::sync::LazyLock;
+
+use rocket::async_trait;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    provider::{LlmTool, LlmToolType},
+    tools::{
+        core::{ToolLog, ToolParameters, ToolResponseFormat, ToolResult, ToolStorage},
+        system::{SystemTool, SystemToolConfig},
+        utils::get_json_schema,
+        ToolError,
+    },
+    utils::SenderWithLogging,
+};
+
+const TOOL_PREFIX: &str = "memory_";
+
+const REMEMBER_NAME: &str = "remember";
+const REMEMBER_DESC: &str = "Save a fact about the user (e.g. preferences, personal details, \
+    ongoing projects) to remember in future conversations. Save one short, self-contained fact \
+    per memory. Only save information the user would expect you to remember.";
+const RECALL_NAME: &str = "recall";
+const RECALL_DESC: &str = "Recall saved memories about the user that are relevant to the \
+    query, or the most recent memories if no query is given. Use this when previous context \
+    about the user could help answer their message.";
+const FORGET_NAME: &str = "forget";
+const FORGET_DESC: &str = "Delete a saved memory by its ID, e.g. if it's outdated or the \
+    user asks you to forget it.";
+const DEFAULT_MAX_RESULTS: u8 = 10;
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct RememberInput {
+    /// The fact to remember
+    content: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct RecallInput {
+    /// What to recall (e.g. "favorite programming languages")
+    query: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ForgetInput {
+    /// ID of the memory to delete
+    id: Uuid,
+}
+
+static REMEMBER_INPUT_SCHEMA: LazyLock<serde_json::Value> =
+    LazyLock::new(|| get_json_schema::<RememberInput>());
+static RECALL_INPUT_SCHEMA: LazyLock<serde_json::Value> =
+    LazyLock::new(|| get_json_schema::<RecallInput>());
+static FORGET_INPUT_SCHEMA: LazyLock<serde_json::Value> =
+    LazyLock::new(|| get_json_schema::<ForgetInput>());
+
+/// Configuration for the memory tool.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct MemoryConfig {
+    /// Enable the memory tool in every chat, unless it's disabled in the chat's tool settings.
+    #[serde(default = "default_auto_enable")]
+    pub auto_enable: bool,
+    /// Embed the memories for semantic recall. Memories are recalled by keywords if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<MemoryEmbeddingConfig>,
+    /// Max number of memories returned when recalling.
+    #[serde(default = "default_max_results")]
+    #[validate(range(min = 1, max = 50))]
+    pub max_results: u8,
+}
+fn default_auto_enable() -> bool {
+    true
+}
+fn default_max_results() -> u8 {
+    DEFAULT_MAX_RESULTS
+}
+
+/// Provider and model used to embed memories
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct MemoryEmbeddingConfig {
+    /// The ID of the provider used to generate embeddings (OpenAI-compatible or Ollama)
+    pub provider_id: i32,
+    /// The embedding model (e.g. `text-embedding-3-small`, `nomic-embed-text`)
+    pub model: String,
+}
+
+impl MemoryConfig {
+    fn embedding(&self) -> Option<(i32, &str)> {
+        self.embedding
+            .as_ref()
+            .map(|embedding| (embedding.provider_id, embedding.model.as_str()))
+    }
+}
+
+/// Tool to save and recall memories about the user across chats.
+#[derive(Debug)]
+pub struct Memory<'a> {
+    config: &'a MemoryConfig,
+}
+impl<'a> Memory<'a> {
+    pub fn new(config: &'a MemoryConfig) -> Self {
+        Memory { config }
+    }
+}
+
+/// A memory, as returned to the LLM
+#[derive(Serialize)]
+struct RecalledMemory<'a> {
+    id: Uuid,
+    content: &'a str,
+    /// Date the memory was saved
+    date: String,
+}
+
+impl SystemToolConfig for MemoryConfig {
+    type DynamicConfig = ();
+
+    fn validate(&self) -> ToolResult<()> {
+        let config_schema = serde_json::to_value(schemars::schema_for!(Self))?;
+        jsonschema::validate(&config_schema, &serde_json::to_value(self)?)
+            .map_err(|e| ToolError::InvalidConfiguration(e.to_string()))
+    }
+
+    fn get_llm_tools(&self, tool_id: Uuid, _input_config: Option<()>) -> Vec<LlmTool> {
+        [
+            (REMEMBER_NAME, REMEMBER_DESC, &*REMEMBER_INPUT_SCHEMA),
+            (RECALL_NAME, RECALL_DESC, &*RECALL_INPUT_SCHEMA),
+            (FORGET_NAME, FORGET_DESC, &*FORGET_INPUT_SCHEMA),
+        ]
+        .into_iter()
+        .map(|(name, description, input_schema)| LlmTool {
+            name: format!("{}{}", TOOL_PREFIX, name),
+            description: description.into(),
+            input_schema: input_schema.to_owned(),
+            tool_id,
+            tool_type: LlmToolType::System,
+        })
+        .collect()
+    }
+}
+
+#[async_trait]
+impl SystemTool for Memory<'_> {
+    fn input_schema(&self, tool_name: &str) -> &serde_json::Value {
+        match tool_name.strip_prefix(TOOL_PREFIX) {
+            Some(REMEMBER_NAME) => &REMEMBER_INPUT_SCHEMA,
+            Some(FORGET_NAME) => &FORGET_INPUT_SCHEMA,
+            _ => &RECALL_INPUT_SCHEMA,
+        }
+    }
+
+    async fn execute(
+        &self,
+        tool_name: &str,
+        parameters: &ToolParameters,
+        storage: &ToolStorage,
+        _tx: &SenderWithLogging<ToolLog>,
+    ) -> ToolResult<(String, ToolResponseFormat)> {
+        let parameters = serde_json::to_value(parameters)?;
+        match tool_name.strip_prefix(TOOL_PREFIX) {
+            Some(REMEMBER_NAME) => {
+                let input: RememberInput = serde_json::from_value(parameters)?;
+                let memory = storage
+                    .remember(&input.content, self.config.embedding())
+                    .await?;
+                let response = format!("Saved memory with ID {}", memory.id);
+                Ok((response, ToolResponseFormat::Text))
+            }
+            Some(RECALL_NAME) => {
+                let input: RecallInput = serde_json::from_value(parameters)?;
+                let memories = storage
+                    .recall(
+                        input.query.as_deref(),
+                        self.config.embedding(),
+                        self.config.max_results,
+                    )
+                    .await?;
+                let recalled: Vec<RecalledMemory> = memories
+                    .iter()
+                    .map(|memory| RecalledMemory {
+                        id: memory.id,
+                        content: &memory.content,
+                        date: memory.created_at.format("%Y-%m-%d").to_string(),
+                    })
+                    .collect();
+                Ok((serde_json::to_string(&recalled)?, ToolResponseFormat::Json))
+            }
+            Some(FORGET_NAME) => {
+                let input: ForgetInput = serde_json::from_value(parameters)?;
+                storage.forget(&input.id).await?;
+                Ok(("Memory deleted".into(), ToolResponseFormat::Text))
+            }
+            _ => Err(ToolError::ToolNotFound),
+        }
+    }
+}
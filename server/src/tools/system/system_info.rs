@@ -9,7 +9,9 @@ use crate::{
     utils::SenderWithLogging,
 };
 
-use super::{SystemTool, ToolError, ToolLog, ToolParameters, ToolResponseFormat, ToolResult};
+use super::{
+    SystemTool, ToolError, ToolLog, ToolParameters, ToolResponseFormat, ToolResult, ToolStorage,
+};
 
 const TOOL_PREFIX: &str = "system_";
 
@@ -75,6 +77,7 @@ impl SystemTool for SystemInfo {
         &self,
         tool_name: &str,
         _params: &ToolParameters,
+        _storage: &ToolStorage,
         _tx: &SenderWithLogging<ToolLog>,
     ) -> ToolResult<(String, ToolResponseFormat)> {
         match tool_name.strip_prefix(TOOL_PREFIX) {
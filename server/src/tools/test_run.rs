@@ -0,0 +1,92 @@
+//! Test runs of tool configurations that haven't been saved, so users can debug them
+
+use crate::{
+    tools::{
+        ChatRsExternalApiToolConfig, ChatRsSystemToolConfig, ToolError, ToolExecutionLimits,
+        ToolLog, ToolParameters, ToolResponseFormat, ToolResult, ToolSecrets, ToolStorage,
+    },
+    utils::{Redactor, SenderWithLogging},
+};
+
+/// A tool configuration that hasn't been saved
+pub enum UnsavedTool {
+    System(ChatRsSystemToolConfig),
+    /// External API tool, with its secrets
+    ExternalApi(ChatRsExternalApiToolConfig, ToolSecrets),
+}
+
+impl UnsavedTool {
+    /// Validate the configuration, and the parameters of the tool call
+    pub fn validate(&mut self, tool_name: &str, parameters: &ToolParameters) -> ToolResult<()> {
+        match self {
+            UnsavedTool::System(config) => {
+                config.validate()?;
+                config
+                    .build_executor(None)
+                    .validate_parameters(tool_name, parameters)
+            }
+            UnsavedTool::ExternalApi(config, _) => {
+                config.validate()?;
+                config
+                    .build_executor()
+                    .validate_parameters(tool_name, parameters)
+            }
+        }
+    }
+
+    /// Redactor of the tool's secrets
+    pub fn redactor(&self) -> Redactor {
+        match self {
+            UnsavedTool::System(_) => Redactor::default(),
+            UnsavedTool::ExternalApi(_, secrets) => {
+                Redactor::new(secrets.values().map(String::as_str))
+            }
+        }
+    }
+
+    /// Execute the tool call in safe mode: tools with side effects (e.g. running code, custom
+    /// API requests that don't use GET or HEAD, or GraphQL mutations) are not executed.
+    pub async fn execute_safe(
+        &self,
+        tool_name: &str,
+        parameters: &ToolParameters,
+        http_client: &reqwest::Client,
+        storage: &ToolStorage,
+        sender: &SenderWithLogging<ToolLog>,
+    ) -> ToolResult<(String, ToolResponseFormat)> {
+        match self {
+            UnsavedTool::System(config) => {
+                if !config.is_read_only() {
+                    return Err(ToolError::UnsafeTestExecution);
+                }
+                config
+                    .build_executor(None)
+                    .validate_and_execute(
+                        tool_name,
+                        parameters,
+                        storage,
+                        sender,
+                        &ToolExecutionLimits::default(),
+                    )
+                    .await
+            }
+            UnsavedTool::ExternalApi(config, secrets) => {
+                if !config.is_read_only(tool_name) {
+                    return Err(ToolError::UnsafeTestExecution);
+                }
+                config
+                    .build_executor()
+                    .validate_and_execute(
+                        tool_name,
+                        parameters,
+                        secrets,
+                        http_client,
+                        storage,
+                        sender,
+                        &ToolExecutionLimits::default(),
+                    )
+                    .await
+            }
+        }
+    }
+}
@@ -4,7 +4,7 @@ use schemars::{gen::SchemaSettings, JsonSchema};
 
 use super::{ToolError, ToolJsonSchema, ToolResult};
 
-pub use http_request_builder::HttpRequestBuilder;
+pub use http_request_builder::{read_text_response, HttpRequestBuilder};
 
 /// Get the JSON schema for a given type.
 pub fn get_json_schema<T: JsonSchema>() -> serde_json::Value {
@@ -16,6 +16,15 @@ pub fn get_json_schema<T: JsonSchema>() -> serde_json::Value {
     serde_json::to_value(schema).expect("Should be valid JSON")
 }
 
+/// Get the description of a tool, using the user's custom description if set.
+pub fn get_tool_description(custom_description: Option<&str>, default: &str) -> String {
+    custom_description
+        .map(str::trim)
+        .filter(|description| !description.is_empty())
+        .unwrap_or(default)
+        .to_owned()
+}
+
 /// Ensure JSON schema is valid (using Draft 2020-12).
 /// Also sets `additionalProperties` to false as required by OpenAI.
 pub fn validate_json_schema(input_schema: &mut ToolJsonSchema) -> ToolResult<()> {
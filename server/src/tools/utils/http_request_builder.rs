@@ -57,7 +57,14 @@ impl HttpRequestBuilder {
         self
     }
 
+    /// Send the request and get the text response, returning an error if unsuccessful
     pub async fn send(self, client: &reqwest::Client) -> ToolResult<String> {
+        let response = self.send_raw(client).await?;
+        read_text_response(response).await
+    }
+
+    /// Send the request and get the raw response
+    pub async fn send_raw(self, client: &reqwest::Client) -> ToolResult<reqwest::Response> {
         let mut request_builder = match self.method.as_str() {
             "GET" => client.get(&self.url),
             "POST" => client.post(&self.url),
@@ -85,22 +92,27 @@ impl HttpRequestBuilder {
                 e, request_builder_debug
             ))
         })?;
-        let response = client
+        client
             .execute(request)
             .await
-            .map_err(|e| ToolError::ToolExecutionError(format!("HTTP request failed: {}", e)))?;
-        let status = response.status();
-        let response_text = response.text().await.map_err(|e| {
-            ToolError::ToolExecutionError(format!("Failed to read response: {}", e))
-        })?;
+            .map_err(|e| ToolError::ToolExecutionError(format!("HTTP request failed: {}", e)))
+    }
+}
 
-        if status.is_success() {
-            Ok(response_text)
-        } else {
-            Err(ToolError::ToolExecutionError(format!(
-                "Request failed with status {}: {}",
-                status, response_text
-            )))
-        }
+/// Read the text of the response, returning an error if unsuccessful
+pub async fn read_text_response(response: reqwest::Response) -> ToolResult<String> {
+    let status = response.status();
+    let response_text = response
+        .text()
+        .await
+        .map_err(|e| ToolError::ToolExecutionError(format!("Failed to read response: {}", e)))?;
+
+    if status.is_success() {
+        Ok(response_text)
+    } else {
+        Err(ToolError::ToolExecutionError(format!(
+            "Request failed with status {}: {}",
+            status, response_text
+        )))
     }
 }
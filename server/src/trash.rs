@@ -0,0 +1,74 @@
+//! Background purge of the sessions and messages in the trash, after the retention period
+
+use std::time::Duration;
+
+use chrono::Utc;
+use rocket::fairing::AdHoc;
+
+use crate::{
+    config::AppConfig,
+    db::{services::ChatDbService, DbPool},
+    errors::ApiError,
+    tools::delete_code_runner_workspace,
+};
+
+/// Default number of days that deleted sessions and messages are kept in the trash.
+const DEFAULT_RETENTION_DAYS: u64 = 30;
+/// Interval between purges of the trash.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Permanently delete the sessions and messages that have been in the trash for longer than
+/// the retention period, and the code runner workspaces of the deleted sessions
+async fn purge_trash(db_pool: &DbPool, retention_days: u64) -> Result<(), ApiError> {
+    let deleted_before =
+        Utc::now() - chrono::Duration::days(i64::try_from(retention_days).unwrap_or(i64::MAX));
+    let mut db = db_pool.get().await?;
+    let session_ids = ChatDbService::new(&mut db)
+        .purge_trash(deleted_before)
+        .await?;
+    for session_id in &session_ids {
+        if let Err(err) = delete_code_runner_workspace(session_id).await {
+            rocket::debug!(
+                "Failed to delete workspace of session {}: {}",
+                session_id,
+                err
+            );
+        }
+    }
+    if !session_ids.is_empty() {
+        rocket::info!("Purged {} sessions from the trash", session_ids.len());
+    }
+
+    Ok(())
+}
+
+/// Fairing that spawns a background task to periodically purge the trash.
+pub fn setup_trash_purge() -> AdHoc {
+    AdHoc::on_liftoff("Trash purge", |rocket| {
+        Box::pin(async move {
+            let retention_days = rocket
+                .state::<AppConfig>()
+                .and_then(|config| config.trash_retention_days)
+                .unwrap_or(DEFAULT_RETENTION_DAYS);
+            if retention_days == 0 {
+                rocket::info!("Trash purge disabled");
+                return;
+            }
+            let Some(db_pool) = rocket.state::<DbPool>() else {
+                rocket::warn!("Trash purge not started: missing managed state");
+                return;
+            };
+            let db_pool = db_pool.clone();
+
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(PURGE_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    if let Err(err) = purge_trash(&db_pool, retention_days).await {
+                        rocket::warn!("Trash purge failed: {}", err);
+                    }
+                }
+            });
+        })
+    })
+}
@@ -2,10 +2,14 @@ mod encryption;
 mod full_text_search;
 mod generate_title;
 mod json_logging;
+mod message_encryption;
+mod redact;
 mod sender_with_logging;
 
 pub use encryption::*;
 pub use full_text_search::*;
 pub use generate_title::*;
 pub use json_logging::*;
+pub use message_encryption::*;
+pub use redact::*;
 pub use sender_with_logging::*;
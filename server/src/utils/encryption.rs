@@ -4,20 +4,53 @@ use aes_gcm::{
 };
 use rocket::fairing::AdHoc;
 
-use crate::{config::get_app_config, provider::LlmError};
+use crate::{
+    config::{get_app_config, AppConfig},
+    provider::LlmError,
+};
 
 /// Encryption service for encrypting and decrypting API keys
 #[derive(Clone)]
 pub struct Encryptor {
     cipher: Aes256Gcm,
+    /// Ciphers of the previous secret keys, only used for decrypting
+    previous_ciphers: Vec<Aes256Gcm>,
 }
 
 impl Encryptor {
     pub fn new(key: &str) -> Result<Self, LlmError> {
-        let key_bytes = hex::decode(key).or(Err(LlmError::EncryptionError))?;
-        let cipher =
-            Aes256Gcm::new_from_slice(&key_bytes).map_err(|_| LlmError::EncryptionError)?;
-        Ok(Self { cipher })
+        Ok(Self {
+            cipher: build_cipher(key)?,
+            previous_ciphers: Vec::new(),
+        })
+    }
+
+    /// Create the encryption service with the current and previous secret keys of the config
+    pub fn from_config(config: &AppConfig) -> Result<Self, LlmError> {
+        let previous_keys = config.previous_secret_keys.as_deref().unwrap_or_default();
+        Self::new(&config.secret_key)?.with_previous_keys(previous_keys)
+    }
+
+    /// Add the previous secret keys, used for decrypting data encrypted before a key rotation
+    pub fn with_previous_keys(mut self, keys: &[String]) -> Result<Self, LlmError> {
+        self.previous_ciphers = keys
+            .iter()
+            .map(|key| build_cipher(key))
+            .collect::<Result<_, _>>()?;
+        Ok(self)
+    }
+
+    /// Create an encryption service with a key derived from the passphrase and salt (Argon2),
+    /// e.g. for encrypting backups
+    pub fn from_passphrase(passphrase: &str, salt: &[u8]) -> Result<Self, LlmError> {
+        let mut key = [0u8; 32];
+        argon2::Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|_| LlmError::EncryptionError)?;
+        Ok(Self {
+            cipher: Aes256Gcm::new_from_slice(&key).map_err(|_| LlmError::EncryptionError)?,
+            previous_ciphers: Vec::new(),
+        })
     }
 
     /// Encrypts a string using AES-256-GCM and returns the ciphertext and nonce.
@@ -44,33 +77,58 @@ impl Encryptor {
 
     /// Decrypts a string using AES-256-GCM.
     pub fn decrypt_string(&self, ciphertext: &[u8], nonce: &[u8]) -> Result<String, LlmError> {
-        let nonce = Nonce::from_slice(nonce);
-        let plaintext = self
-            .cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|_| LlmError::DecryptionError)?;
+        let plaintext = self.decrypt_bytes(ciphertext, nonce)?;
 
         Ok(String::from_utf8(plaintext).map_err(|_| LlmError::DecryptionError)?)
     }
 
-    /// Decrypts a byte slice using AES-256-GCM.
+    /// Decrypts a byte slice using AES-256-GCM, with the current secret key or one of the
+    /// previous keys.
     pub fn decrypt_bytes(&self, ciphertext: &[u8], nonce: &[u8]) -> Result<Vec<u8>, LlmError> {
         let nonce = Nonce::from_slice(nonce);
-        let bytes = self
-            .cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|_| LlmError::DecryptionError)?;
+        let bytes = std::iter::once(&self.cipher)
+            .chain(&self.previous_ciphers)
+            .find_map(|cipher| cipher.decrypt(nonce, ciphertext).ok())
+            .ok_or(LlmError::DecryptionError)?;
 
         Ok(bytes)
     }
+
+    /// Re-encrypts the data with the current secret key if it was encrypted with one of the
+    /// previous keys, returning the new ciphertext and nonce (`None` if already encrypted
+    /// with the current key).
+    pub fn reencrypt(
+        &self,
+        ciphertext: &[u8],
+        nonce: &[u8],
+    ) -> Result<Option<(Vec<u8>, Vec<u8>)>, LlmError> {
+        if self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .is_ok()
+        {
+            return Ok(None);
+        }
+        let bytes = self.decrypt_bytes(ciphertext, nonce)?;
+
+        Ok(Some(self.encrypt_bytes(&bytes)?))
+    }
+}
+
+fn build_cipher(key: &str) -> Result<Aes256Gcm, LlmError> {
+    let key_bytes = hex::decode(key).or(Err(LlmError::EncryptionError))?;
+    Aes256Gcm::new_from_slice(&key_bytes).map_err(|_| LlmError::EncryptionError)
 }
 
 /// Fairing that sets up an encryption service
 pub fn setup_encryption() -> AdHoc {
     AdHoc::on_ignite("Encryption setup", |rocket| async {
         let app_config = get_app_config(&rocket);
-        let encryptor = Encryptor::new(&app_config.secret_key)
+        let encryptor = Encryptor::from_config(app_config)
             .expect("Invalid secret key: must be 64-character hexadecimal string");
+        if app_config.encrypt_messages.unwrap_or(false) {
+            rocket::info!("Encryption: message encryption enabled!");
+        }
 
         rocket.manage(encryptor)
     })
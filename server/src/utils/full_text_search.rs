@@ -3,7 +3,14 @@ use diesel_async::RunQueryDsl;
 use schemars::JsonSchema;
 use uuid::Uuid;
 
-use crate::db::DbConnection;
+use crate::{
+    db::{models::ChatRsMessageRole, DbConnection},
+    utils::MessageEncryption,
+};
+
+/// Markers of the start and end of the highlighted words in the search headlines
+const HIGHLIGHT_START: &str = "§§§HIGHLIGHT_START§§§";
+const HIGHLIGHT_END: &str = "§§§HIGHLIGHT_END§§§";
 
 /// Session matches for a full-text search query of chat titles and messages
 #[derive(Debug, Clone, QueryableByName, JsonSchema, serde::Serialize)]
@@ -20,6 +27,10 @@ pub struct SessionSearchResult {
     pub title_highlight: String,
     #[diesel(sql_type = diesel::sql_types::Text)]
     pub message_highlights: String,
+    /// Whether the message of the highlights is encrypted (see `decrypt_headline`)
+    #[diesel(sql_type = diesel::sql_types::Bool)]
+    #[serde(skip)]
+    pub message_encrypted: bool,
 }
 
 /// Performs a full-text search of user's chat titles and messages
@@ -40,6 +51,7 @@ pub async fn full_text_query(
                 cs.title,
                 cs.updated_at,
                 cm.content,
+                cm.content_encrypted,
                 ts_rank(cm.search_vector, sq.query) AS rank,
                 COUNT(*) OVER (PARTITION BY cm.session_id) AS message_matches,
                 ROW_NUMBER() OVER (
@@ -51,6 +63,8 @@ pub async fn full_text_query(
                 CROSS JOIN search_query sq
             WHERE cm.search_vector @@ sq.query
                 AND cs.user_id = $2
+                AND cs.deleted_at IS NULL
+                AND cm.deleted_at IS NULL
         )
         SELECT
             session_id,
@@ -58,7 +72,11 @@ pub async fn full_text_query(
             updated_at AS session_updated_at,
             message_matches,
             ts_headline('english', title, sq.query, 'StartSel=§§§HIGHLIGHT_START§§§, StopSel=§§§HIGHLIGHT_END§§§, HighlightAll=true') AS title_highlight,
-            ts_headline('english', content, sq.query, 'StartSel=§§§HIGHLIGHT_START§§§, StopSel=§§§HIGHLIGHT_END§§§, MinWords=8, MaxWords=12, MaxFragments=3') AS message_highlights
+            CASE
+                WHEN content_encrypted THEN content
+                ELSE ts_headline('english', content, sq.query, 'StartSel=§§§HIGHLIGHT_START§§§, StopSel=§§§HIGHLIGHT_END§§§, MinWords=8, MaxWords=12, MaxFragments=3')
+            END AS message_highlights,
+            content_encrypted AS message_encrypted
         FROM message_stats ms
         CROSS JOIN search_query sq
         WHERE rank_in_session = 1  -- Only best message per session
@@ -71,5 +89,158 @@ pub async fn full_text_query(
     .bind::<diesel::sql_types::Integer, _>(limit)
     .load(conn).await?;
 
+    let encryption = conn.message_encryption();
+    Ok(results
+        .into_iter()
+        .map(|result| SessionSearchResult {
+            message_highlights: match result.message_encrypted {
+                true => decrypt_headline(encryption, result.message_highlights, 12),
+                false => result.message_highlights,
+            },
+            ..result
+        })
+        .collect())
+}
+
+/// A message matching a full-text search within a chat session
+#[derive(Debug, JsonSchema, serde::Serialize)]
+pub struct MessageSearchResult {
+    pub message_id: Uuid,
+    pub role: ChatRsMessageRole,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub rank: f64,
+    /// Fragments of the message around the matching words
+    pub snippet: String,
+    /// Ranges of the matching words in the snippet
+    pub highlights: Vec<HighlightRange>,
+}
+
+/// Range of highlighted text, in UTF-16 code units (as in JavaScript strings)
+#[derive(Debug, PartialEq, JsonSchema, serde::Serialize)]
+pub struct HighlightRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(QueryableByName)]
+struct MessageMatch {
+    #[diesel(sql_type = diesel::sql_types::Uuid)]
+    message_id: Uuid,
+    #[diesel(sql_type = crate::db::schema::sql_types::ChatMessageRole)]
+    role: ChatRsMessageRole,
+    #[diesel(sql_type = diesel::sql_types::Timestamptz)]
+    created_at: chrono::DateTime<chrono::Utc>,
+    #[diesel(sql_type = diesel::sql_types::Double)]
+    rank: f64,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    headline: String,
+    #[diesel(sql_type = diesel::sql_types::Bool)]
+    encrypted: bool,
+}
+
+/// Performs a full-text search of the messages of one of the user's chat sessions. The
+/// matching messages are returned in chronological order.
+pub async fn session_full_text_query(
+    conn: &mut DbConnection,
+    user_id: &Uuid,
+    session_id: &Uuid,
+    query: &str,
+    limit: i32,
+) -> Result<Vec<MessageSearchResult>, diesel::result::Error> {
+    let matches: Vec<MessageMatch> = sql_query(
+    r#"
+        WITH search_query AS (
+            SELECT plainto_tsquery('english', $1) AS query
+        )
+        SELECT
+            cm.id AS message_id,
+            cm.role,
+            cm.created_at,
+            ts_rank(cm.search_vector, sq.query)::float8 AS rank,
+            CASE
+                WHEN cm.content_encrypted THEN cm.content
+                ELSE ts_headline('english', cm.content, sq.query, 'StartSel=§§§HIGHLIGHT_START§§§, StopSel=§§§HIGHLIGHT_END§§§, MinWords=8, MaxWords=20, MaxFragments=2')
+            END AS headline,
+            cm.content_encrypted AS encrypted
+        FROM chat_messages cm
+            JOIN chat_sessions cs ON cm.session_id = cs.id
+            CROSS JOIN search_query sq
+        WHERE cm.search_vector @@ sq.query
+            AND cm.session_id = $2
+            AND cs.user_id = $3
+            AND cs.deleted_at IS NULL
+            AND cm.deleted_at IS NULL
+        ORDER BY cm.created_at ASC
+        LIMIT $4;
+    "#,
+    )
+    .bind::<diesel::sql_types::Text, _>(query)
+    .bind::<diesel::sql_types::Uuid, _>(session_id)
+    .bind::<diesel::sql_types::Uuid, _>(user_id)
+    .bind::<diesel::sql_types::Integer, _>(limit)
+    .load(conn).await?;
+
+    let encryption = conn.message_encryption();
+    let results = matches
+        .into_iter()
+        .map(|message| {
+            let headline = match message.encrypted {
+                true => decrypt_headline(encryption, message.headline, 20),
+                false => message.headline,
+            };
+            let (snippet, highlights) = parse_highlights(&headline);
+            MessageSearchResult {
+                message_id: message.message_id,
+                role: message.role,
+                created_at: message.created_at,
+                rank: message.rank,
+                snippet,
+                highlights,
+            }
+        })
+        .collect();
+
     Ok(results)
 }
+
+/// Get the headline of an encrypted message: Postgres can't generate headlines from the
+/// encrypted content, so the headline is the start of the decrypted message, without
+/// highlights.
+fn decrypt_headline(encryption: &MessageEncryption, headline: String, max_words: usize) -> String {
+    let Some(plaintext) = encryption.decrypt(&headline) else {
+        return headline;
+    };
+    plaintext
+        .split_whitespace()
+        .take(max_words)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Remove the highlight markers from a search headline, returning the plain text and the
+/// ranges of the highlighted words
+fn parse_highlights(headline: &str) -> (String, Vec<HighlightRange>) {
+    let mut text = String::with_capacity(headline.len());
+    let mut highlights = Vec::new();
+    let mut offset = 0; // in UTF-16 code units
+    let mut rest = headline;
+    while let Some(start_index) = rest.find(HIGHLIGHT_START) {
+        let before = &rest[..start_index];
+        text.push_str(before);
+        offset += before.encode_utf16().count();
+        rest = &rest[start_index + HIGHLIGHT_START.len()..];
+
+        let end_index = rest.find(HIGHLIGHT_END).unwrap_or(rest.len());
+        let highlighted = &rest[..end_index];
+        text.push_str(highlighted);
+        let start = offset;
+        offset += highlighted.encode_utf16().count();
+        highlights.push(HighlightRange { start, end: offset });
+        rest = rest
+            .get(end_index + HIGHLIGHT_END.len()..)
+            .unwrap_or_default();
+    }
+    text.push_str(rest);
+
+    (text, highlights)
+}
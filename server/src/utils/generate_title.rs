@@ -3,7 +3,7 @@ use std::ops::Deref;
 use uuid::Uuid;
 
 use crate::{
-    db::{models::UpdateChatRsSession, services::ChatDbService, DbConnection, DbPool},
+    db::{models::UpdateChatRsSession, services::ChatDbService, DbPool},
     errors::ApiError,
     provider::{LlmApiProvider, LlmProviderOptions, DEFAULT_TEMPERATURE},
 };
@@ -53,7 +53,7 @@ async fn generate(
     let message = format!("{}: \"{}\"", TITLE_PROMPT, user_message);
     let title = provider.prompt(&message, &provider_options).await?;
 
-    let mut db = DbConnection(pool.get().await?);
+    let mut db = pool.get().await?;
     ChatDbService::new(&mut db)
         .update_session(
             &user_id,
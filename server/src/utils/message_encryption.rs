@@ -0,0 +1,208 @@
+//! Optional encryption at rest of the message contents, so that database dumps don't expose
+//! private conversations. Encrypted contents are stored as `enc:v1:<nonce>:<ciphertext>`
+//! (base64) and flagged with the `content_encrypted` column, and are decrypted by the chat
+//! service when the messages are loaded. Messages saved before encryption was enabled are
+//! kept in plaintext.
+//!
+//! The full-text search of encrypted messages only matches the session titles, unless the
+//! indexing of encrypted messages is enabled: the search vectors are then computed from the
+//! plaintext when the messages are saved, so the (stemmed) words of the messages are stored
+//! unencrypted in the search index.
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+
+use crate::{config::AppConfig, provider::LlmError, utils::Encryptor};
+
+/// Prefix of the encrypted message contents
+pub const ENCRYPTED_CONTENT_PREFIX: &str = "enc:v1:";
+/// Length of the nonces of AES-256-GCM, in bytes
+const NONCE_LENGTH: usize = 12;
+
+/// Message encryption settings, passed to the database connections (see `DbPool`)
+#[derive(Clone)]
+pub struct MessageEncryption {
+    encryptor: Encryptor,
+    /// Whether new messages are encrypted
+    enabled: bool,
+    /// Whether the search vectors of encrypted messages are computed from the plaintext
+    index: bool,
+}
+
+impl MessageEncryption {
+    pub fn new(encryptor: Encryptor, enabled: bool, index: bool) -> Self {
+        Self {
+            encryptor,
+            enabled,
+            index,
+        }
+    }
+
+    /// Get the message encryption settings of the config
+    pub fn from_config(config: &AppConfig, encryptor: &Encryptor) -> Self {
+        Self::new(
+            encryptor.clone(),
+            config.encrypt_messages.unwrap_or(false),
+            config.index_encrypted_messages.unwrap_or(false),
+        )
+    }
+
+    /// Whether encrypted messages should be indexed for the full-text search
+    pub fn index_encrypted_messages(&self) -> bool {
+        self.index
+    }
+
+    /// Encrypt the content of a new message, if message encryption is enabled (`None` if the
+    /// message should be saved in plaintext)
+    pub fn encrypt(&self, content: &str) -> Result<Option<String>, LlmError> {
+        if !self.enabled {
+            return Ok(None);
+        }
+        encrypt_content_with(&self.encryptor, content).map(Some)
+    }
+
+    /// Decrypt the encrypted content of a message (messages encrypted before encryption was
+    /// disabled can still be decrypted). Returns `None` and logs the error if it can't be
+    /// decrypted, e.g. if the secret key was changed.
+    pub fn decrypt(&self, content: &str) -> Option<String> {
+        decrypt_content_with(&self.encryptor, content)
+            .inspect_err(|err| rocket::error!("Failed to decrypt message: {}", err))
+            .ok()
+    }
+}
+
+/// Re-encrypt the encrypted content of a message with the current secret key if it was
+/// encrypted with one of the previous keys (`None` if already encrypted with the current key).
+pub fn reencrypt_message_content(
+    encryptor: &Encryptor,
+    content: &str,
+) -> Result<Option<String>, LlmError> {
+    let (ciphertext, nonce) = parse_encrypted_content(content)?;
+    let reencrypted = encryptor.reencrypt(&ciphertext, &nonce)?;
+
+    Ok(reencrypted.map(|(ciphertext, nonce)| format_encrypted_content(&ciphertext, &nonce)))
+}
+
+/// Encrypt the message content with the given encryption service (e.g. for backups)
+pub fn encrypt_content_with(encryptor: &Encryptor, content: &str) -> Result<String, LlmError> {
+    let (ciphertext, nonce) = encryptor.encrypt_string(content)?;
+    Ok(format_encrypted_content(&ciphertext, &nonce))
+}
+
+/// Decrypt the encrypted message content with the given encryption service
+pub fn decrypt_content_with(encryptor: &Encryptor, content: &str) -> Result<String, LlmError> {
+    let (ciphertext, nonce) = parse_encrypted_content(content)?;
+    encryptor.decrypt_string(&ciphertext, &nonce)
+}
+
+fn format_encrypted_content(ciphertext: &[u8], nonce: &[u8]) -> String {
+    format!(
+        "{ENCRYPTED_CONTENT_PREFIX}{}:{}",
+        BASE64_STANDARD.encode(nonce),
+        BASE64_STANDARD.encode(ciphertext)
+    )
+}
+
+fn parse_encrypted_content(content: &str) -> Result<(Vec<u8>, Vec<u8>), LlmError> {
+    let (nonce, ciphertext) = content
+        .strip_prefix(ENCRYPTED_CONTENT_PREFIX)
+        .and_then(|rest| rest.split_once(':'))
+        .ok_or(LlmError::DecryptionError)?;
+    let nonce = BASE64_STANDARD
+        .decode(nonce)
+        .ok()
+        .filter(|nonce| nonce.len() == NONCE_LENGTH)
+        .ok_or(LlmError::DecryptionError)?;
+    let ciphertext = BASE64_STANDARD
+        .decode(ciphertext)
+        .map_err(|_| LlmError::DecryptionError)?;
+
+    Ok((ciphertext, nonce))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "0000000000000000000000000000000000000000000000000000000000000001";
+    const PREVIOUS_KEY: &str = "0000000000000000000000000000000000000000000000000000000000000002";
+
+    fn message_encryption(enabled: bool) -> MessageEncryption {
+        MessageEncryption::new(Encryptor::new(KEY).unwrap(), enabled, false)
+    }
+
+    #[test]
+    fn encrypts_and_decrypts_content() {
+        let encryption = message_encryption(true);
+        let encrypted = encryption.encrypt("Hello, world!").unwrap().unwrap();
+
+        assert!(encrypted.starts_with(ENCRYPTED_CONTENT_PREFIX));
+        assert!(!encrypted.contains("Hello"));
+        assert_eq!(encryption.decrypt(&encrypted).unwrap(), "Hello, world!");
+    }
+
+    #[test]
+    fn keeps_plaintext_when_disabled() {
+        assert_eq!(message_encryption(false).encrypt("Hello").unwrap(), None);
+    }
+
+    #[test]
+    fn decrypts_when_disabled() {
+        let encrypted = message_encryption(true).encrypt("Hello").unwrap().unwrap();
+        assert_eq!(
+            message_encryption(false).decrypt(&encrypted).unwrap(),
+            "Hello"
+        );
+    }
+
+    #[test]
+    fn fails_to_decrypt_invalid_content() {
+        let encryption = message_encryption(true);
+        for content in [
+            "enc:v1: not encrypted",
+            "enc:v1:AAAA:AAAA",
+            "enc:v1:AAAAAAAAAAAAAAAA:AAAA",
+            "Hello",
+        ] {
+            assert_eq!(encryption.decrypt(content), None);
+        }
+    }
+
+    #[test]
+    fn reencrypts_content_of_previous_key() {
+        let previous = Encryptor::new(PREVIOUS_KEY).unwrap();
+        let encrypted = encrypt_content_with(&previous, "Hello").unwrap();
+        let encryptor = Encryptor::new(KEY)
+            .unwrap()
+            .with_previous_keys(&[PREVIOUS_KEY.to_owned()])
+            .unwrap();
+
+        let reencrypted = reencrypt_message_content(&encryptor, &encrypted)
+            .unwrap()
+            .expect("should be re-encrypted");
+        assert!(decrypt_content_with(&previous, &reencrypted).is_err());
+        assert_eq!(
+            decrypt_content_with(&Encryptor::new(KEY).unwrap(), &reencrypted).unwrap(),
+            "Hello"
+        );
+    }
+
+    #[test]
+    fn skips_content_of_current_key() {
+        let encryptor = Encryptor::new(KEY)
+            .unwrap()
+            .with_previous_keys(&[PREVIOUS_KEY.to_owned()])
+            .unwrap();
+        let encrypted = encrypt_content_with(&encryptor, "Hello").unwrap();
+
+        assert_eq!(
+            reencrypt_message_content(&encryptor, &encrypted).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn fails_to_reencrypt_invalid_content() {
+        let encryptor = Encryptor::new(KEY).unwrap();
+        assert!(reencrypt_message_content(&encryptor, "enc:v1: not encrypted").is_err());
+    }
+}
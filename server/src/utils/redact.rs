@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+/// Replacement of the redacted secret values
+const REDACTED: &str = "[REDACTED]";
+/// Secrets shorter than this are not redacted, to avoid masking unrelated text
+const MIN_SECRET_LENGTH: usize = 6;
+
+/// Masks secret values, and their URL-encoded forms, in text (e.g. in the logs and results
+/// of tools)
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    /// Secret values to mask, longest first
+    secrets: Arc<[String]>,
+}
+
+impl Redactor {
+    pub fn new<'a>(secrets: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut values: Vec<String> = Vec::new();
+        for secret in secrets {
+            let secret = secret.trim();
+            if secret.len() < MIN_SECRET_LENGTH {
+                continue;
+            }
+            let encoded = urlencoding::encode(secret);
+            if encoded != secret {
+                values.push(encoded.into_owned());
+            }
+            values.push(secret.to_owned());
+        }
+        values.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        values.dedup();
+
+        Self {
+            secrets: values.into(),
+        }
+    }
+
+    /// Mask all secret values in the text
+    pub fn redact(&self, text: &mut String) {
+        for secret in self.secrets.iter() {
+            if text.contains(secret.as_str()) {
+                *text = text.replace(secret.as_str(), REDACTED);
+            }
+        }
+    }
+}
+
+/// A value that can contain secrets to redact
+pub trait Redact {
+    fn redact(&mut self, redactor: &Redactor);
+}
+
+impl Redact for String {
+    fn redact(&mut self, redactor: &Redactor) {
+        redactor.redact(self);
+    }
+}
@@ -1,11 +1,15 @@
 use tokio::sync::mpsc::{error::SendError, Sender};
 
+use super::{Redact, Redactor};
+
 /// A sender wrapper that sends messages to a primary and a log collector
 /// channel, while preserving the primary channel's closure semantics.
 #[derive(Debug, Clone)]
 pub struct SenderWithLogging<T> {
     primary_tx: Sender<T>,
     log_tx: Sender<T>,
+    /// Redactor of the secrets in the messages, and the function applying it
+    redactor: Option<(Redactor, fn(&mut T, &Redactor))>,
 }
 
 impl<T: Clone> SenderWithLogging<T> {
@@ -13,10 +17,15 @@ impl<T: Clone> SenderWithLogging<T> {
         Self {
             primary_tx: primary_sender,
             log_tx: log_sender,
+            redactor: None,
         }
     }
 
-    pub async fn send(&self, chunk: T) -> Result<(), SendError<T>> {
+    pub async fn send(&self, mut chunk: T) -> Result<(), SendError<T>> {
+        if let Some((redactor, redact)) = &self.redactor {
+            redact(&mut chunk, redactor);
+        }
+
         // Send to log collector first
         let _ = self.log_tx.send(chunk.clone()).await;
 
@@ -35,3 +44,11 @@ impl<T: Clone> SenderWithLogging<T> {
         self.primary_tx.is_closed()
     }
 }
+
+impl<T: Clone + Redact> SenderWithLogging<T> {
+    /// Mask the secrets of the redactor in all messages
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some((redactor, T::redact));
+        self
+    }
+}
@@ -0,0 +1,322 @@
+//! Outbound webhooks on chat events (e.g. a completed assistant message), for notifications
+//! and external automation. Events are queued as deliveries in the database, and a background
+//! task sends them with an HMAC-SHA256 signature, retrying failed deliveries with a backoff.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use fred::{
+    prelude::KeysInterface,
+    types::{Expiration, SetOptions},
+};
+use hmac::{Hmac, Mac};
+use rocket::fairing::AdHoc;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        models::{
+            ChatRsMessage, ChatRsWebhook, ChatRsWebhookDelivery, ChatRsWebhookDeliveryStatus,
+            ChatRsWebhookEvent, NewChatRsWebhookDelivery, UpdateChatRsWebhookDelivery,
+        },
+        services::WebhookDbService,
+        DbConnection, DbPool,
+    },
+    errors::ApiError,
+    redis::redis_key,
+    utils::Encryptor,
+};
+
+/// Interval between checks for due deliveries.
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+/// Redis key of the lock that lets only one server instance send the due deliveries.
+const LOCK_KEY: &str = "webhook_delivery_lock";
+/// Expiration of the lock in seconds, in case the instance holding it stops.
+const LOCK_TTL: i64 = 10 * 60;
+/// Max number of due deliveries sent in one check.
+const MAX_DUE_DELIVERIES: i64 = 50;
+/// Timeout of each webhook request.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Max number of attempts of each delivery.
+const MAX_ATTEMPTS: i32 = 5;
+/// Delay before the first retry, multiplied by 4 for each following retry.
+const RETRY_DELAY_SECS: i64 = 30;
+/// Number of days that finished deliveries are kept.
+const DELIVERY_RETENTION_DAYS: i64 = 7;
+/// Max number of webhooks per user.
+pub const MAX_WEBHOOKS: i64 = 10;
+
+/// Header with the event of the delivery
+const EVENT_HEADER: &str = "X-RsChat-Event";
+/// Header with the ID of the delivery, which stays the same across retries
+const DELIVERY_HEADER: &str = "X-RsChat-Delivery";
+/// Header with the hex-encoded HMAC-SHA256 signature of the body
+const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+/// Errors of webhooks
+#[derive(Debug, thiserror::Error)]
+pub enum WebhookError {
+    #[error("Invalid webhook URL: {0}")]
+    InvalidUrl(String),
+    #[error("Webhook must have at least one event")]
+    NoEvents,
+    #[error("Too many webhooks (max {0})")]
+    TooManyWebhooks(i64),
+}
+
+/// Check that the webhook URL is a valid HTTP(S) URL
+pub fn validate_url(url: &str) -> Result<(), WebhookError> {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Ok(()),
+        Ok(_) => Err(WebhookError::InvalidUrl("must be an HTTP(S) URL".into())),
+        Err(err) => Err(WebhookError::InvalidUrl(err.to_string())),
+    }
+}
+
+/// Generate a new signing secret for a webhook
+pub fn generate_secret() -> String {
+    format!("whsec_{}", hex::encode(rand::random::<[u8; 32]>()))
+}
+
+/// Queue a delivery of the event to each of the user's webhooks that are triggered by it
+pub async fn trigger_webhooks(
+    db: &mut DbConnection,
+    user_id: &Uuid,
+    event: ChatRsWebhookEvent,
+    data: serde_json::Value,
+) -> Result<(), diesel::result::Error> {
+    let event_name: &str = (&event).into();
+    let webhooks = WebhookDbService::new(db)
+        .find_by_event(user_id, event_name)
+        .await?;
+    if webhooks.is_empty() {
+        return Ok(());
+    }
+    queue_deliveries(db, &webhooks, event, data).await?;
+
+    Ok(())
+}
+
+/// Queue a delivery of the event to the given webhooks, and return the IDs of the deliveries
+pub async fn queue_deliveries(
+    db: &mut DbConnection,
+    webhooks: &[ChatRsWebhook],
+    event: ChatRsWebhookEvent,
+    data: serde_json::Value,
+) -> Result<Vec<Uuid>, diesel::result::Error> {
+    let now = Utc::now();
+    let payload = serde_json::json!({
+        "event": event,
+        "created_at": now,
+        "data": data,
+    });
+    let deliveries: Vec<_> = webhooks
+        .iter()
+        .map(|webhook| NewChatRsWebhookDelivery {
+            webhook_id: &webhook.id,
+            event: (&event).into(),
+            payload: &payload,
+            status: (&ChatRsWebhookDeliveryStatus::Pending).into(),
+            next_attempt_at: Some(now),
+        })
+        .collect();
+
+    WebhookDbService::new(db)
+        .create_deliveries(&deliveries)
+        .await
+}
+
+/// Trigger the webhooks for a saved assistant message: `message_completed` for complete
+/// messages, `tool_call_requested` if the message has tool calls, and `stream_error` if the
+/// provider stream had errors.
+pub async fn trigger_message_webhooks(
+    db: &mut DbConnection,
+    user_id: &Uuid,
+    message: &ChatRsMessage,
+) -> Result<(), diesel::result::Error> {
+    let Some(assistant) = &message.meta.assistant else {
+        return Ok(());
+    };
+    if let Some(errors) = assistant
+        .errors
+        .as_ref()
+        .filter(|errors| !errors.is_empty())
+    {
+        let data = serde_json::json!({
+            "session_id": message.session_id,
+            "message_id": message.id,
+            "errors": errors,
+        });
+        trigger_webhooks(db, user_id, ChatRsWebhookEvent::StreamError, data).await?;
+    }
+    if let Some(tool_calls) = assistant
+        .tool_calls
+        .as_ref()
+        .filter(|calls| !calls.is_empty())
+    {
+        let data = serde_json::json!({
+            "session_id": message.session_id,
+            "message_id": message.id,
+            "tool_calls": tool_calls,
+        });
+        trigger_webhooks(db, user_id, ChatRsWebhookEvent::ToolCallRequested, data).await?;
+    }
+    if assistant.partial != Some(true) {
+        let data = serde_json::json!({
+            "session_id": message.session_id,
+            "message": message,
+        });
+        trigger_webhooks(db, user_id, ChatRsWebhookEvent::MessageCompleted, data).await?;
+    }
+
+    Ok(())
+}
+
+/// Hex-encoded HMAC-SHA256 signature of the body
+fn sign_body(secret: &str, body: &str) -> Result<String, String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .map_err(|e| format!("Invalid signing secret: {e}"))?;
+    mac.update(body.as_bytes());
+    Ok(hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Send a delivery to its webhook, and get the response status
+async fn send_delivery(
+    http_client: &reqwest::Client,
+    encryptor: &Encryptor,
+    delivery: &ChatRsWebhookDelivery,
+    webhook: &ChatRsWebhook,
+) -> Result<reqwest::StatusCode, String> {
+    let secret = encryptor
+        .decrypt_string(&webhook.secret_ciphertext, &webhook.secret_nonce)
+        .map_err(|e| e.to_string())?;
+    let body = delivery.payload.to_string();
+    let signature = sign_body(&secret, &body)?;
+    let response = http_client
+        .post(&webhook.url)
+        .timeout(DELIVERY_TIMEOUT)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, &delivery.event)
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .header(SIGNATURE_HEADER, format!("sha256={signature}"))
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| format!("Request failed: {e}"))?;
+
+    Ok(response.status())
+}
+
+/// Time of the next attempt after the given number of failed attempts
+fn next_attempt_at(attempts: i32, now: DateTime<Utc>) -> DateTime<Utc> {
+    let exponent = u32::try_from(attempts - 1).unwrap_or(0);
+    now + chrono::Duration::seconds(RETRY_DELAY_SECS * 4_i64.pow(exponent))
+}
+
+/// Send the deliveries that are due, if no other server instance is sending them
+async fn send_due_deliveries(
+    db_pool: &DbPool,
+    redis: &fred::clients::Client,
+    encryptor: &Encryptor,
+    http_client: &reqwest::Client,
+) -> Result<(), ApiError> {
+    let lock: Option<String> = redis
+        .set(
+            redis_key(LOCK_KEY),
+            "1",
+            Some(Expiration::EX(LOCK_TTL)),
+            Some(SetOptions::NX),
+            false,
+        )
+        .await?;
+    if lock.is_none() {
+        return Ok(());
+    }
+
+    let mut db = db_pool.get().await?;
+    let result = async {
+        let deliveries = WebhookDbService::new(&mut db)
+            .find_due_deliveries(Utc::now(), MAX_DUE_DELIVERIES)
+            .await?;
+        for (delivery, webhook) in deliveries {
+            let attempts = delivery.attempts + 1;
+            let (response_status, error) =
+                match send_delivery(http_client, encryptor, &delivery, &webhook).await {
+                    Ok(status) if status.is_success() => (Some(status.as_u16().into()), None),
+                    Ok(status) => (
+                        Some(status.as_u16().into()),
+                        Some(format!("Webhook responded with status {status}")),
+                    ),
+                    Err(err) => (None, Some(err)),
+                };
+            let (status, next_attempt) = match &error {
+                None => (ChatRsWebhookDeliveryStatus::Delivered, None),
+                Some(_) if attempts >= MAX_ATTEMPTS => (ChatRsWebhookDeliveryStatus::Failed, None),
+                Some(_) => (
+                    ChatRsWebhookDeliveryStatus::Pending,
+                    Some(next_attempt_at(attempts, Utc::now())),
+                ),
+            };
+            if let Some(error) = &error {
+                rocket::debug!("Webhook delivery {} failed: {}", delivery.id, error);
+            }
+            WebhookDbService::new(&mut db)
+                .update_delivery(
+                    &delivery.id,
+                    UpdateChatRsWebhookDelivery {
+                        status: Some((&status).into()),
+                        attempts: Some(attempts),
+                        next_attempt_at: Some(next_attempt),
+                        response_status: Some(response_status),
+                        last_error: Some(error.as_deref()),
+                    },
+                )
+                .await?;
+        }
+
+        let created_before = Utc::now() - chrono::Duration::days(DELIVERY_RETENTION_DAYS);
+        WebhookDbService::new(&mut db)
+            .delete_finished_deliveries(created_before)
+            .await?;
+
+        Ok::<_, ApiError>(())
+    }
+    .await;
+    let _: () = redis.del(redis_key(LOCK_KEY)).await?;
+
+    result
+}
+
+/// Fairing that spawns a background task to periodically send the due webhook deliveries.
+pub fn setup_webhook_delivery() -> AdHoc {
+    AdHoc::on_liftoff("Webhook delivery", |rocket| {
+        Box::pin(async move {
+            let (Some(db_pool), Some(redis_pool), Some(encryptor), Some(http_client)) = (
+                rocket.state::<DbPool>(),
+                rocket.state::<fred::clients::Pool>(),
+                rocket.state::<Encryptor>(),
+                rocket.state::<reqwest::Client>(),
+            ) else {
+                rocket::warn!("Webhook delivery not started: missing managed state");
+                return;
+            };
+            let db_pool = db_pool.clone();
+            let redis = redis_pool.next().clone();
+            let encryptor = encryptor.clone();
+            let http_client = http_client.clone();
+
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(CHECK_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    if let Err(err) =
+                        send_due_deliveries(&db_pool, &redis, &encryptor, &http_client).await
+                    {
+                        rocket::warn!("Webhook delivery failed: {}", err);
+                    }
+                }
+            });
+        })
+    })
+}